@@ -1,37 +1,194 @@
-use crate::debug::DebugServer;
-use crate::domain::{DebugEvent, DebugEventReceiver, DebugEventSender, Provider};
-use crate::env::{get_env_var, get_optional_env_var};
-use crate::helpers::{get_project_context, path_to_dirname};
-use crate::providers::copilot;
-use crate::session::Session;
-use crate::tools::{CreateFileTool, EditFileTool, ReadDirTool, ReadFileTool, RunCmdTool};
+use crate::cli::{
+    AgxCommand, Args, ConfigCommand, CronCommand, ImportFrom, ProviderArgs, ReportFormat,
+    TasksCommand,
+};
+use crate::crash::{self, CRASH_REPORT_EVENTS};
+use crate::cron::{list_cron, run_cron};
+use crate::eval::{EvalTarget, run_eval};
+use crate::self_update::run_self_update;
+use crate::tasks::run_tasks;
+use agx_core::config::{check_config, get_template, get_user_config};
+use agx_core::debug::{DEFAULT_DEBUG_SERVER_ADDRESS, DebugServer};
+use agx_core::domain::{
+    ApprovalMode, ConcurrentSessionsPolicy, Config, DebugEventSender, DebugServerConfig, Provider,
+    ProviderProfile, Template, UserConfig, debug_channel, debug_channel_with_history,
+};
+use agx_core::env::{get_env_var, get_optional_env_var};
+use agx_core::forge::{Forge, get_forge};
+use agx_core::helpers::{
+    LockAttempt, ProjectLock, get_project_context, parse_duration, path_to_dirname,
+    read_prompt_file,
+};
+use agx_core::import::{ImportSource, import_transcript};
+use agx_core::index::{SemanticIndex, run_index};
+use agx_core::providers::replay::ReplayModel;
+use agx_core::providers::{DynModel, ProviderAccess, agent_for};
+use agx_core::report::UsageReport;
+use agx_core::session::{Session, Transcript, TranscriptEvent};
+use agx_core::share::transcript_to_html;
+use agx_core::worktree::Worktree;
 use anyhow::Context;
 use colored::Colorize;
-use rig::client::{Client, CompletionClient};
-use rig::providers::anthropic::client::AnthropicExt;
-use rig::providers::gemini::client::GeminiExt;
-use rig::providers::openai::OpenAICompletionsExt;
-use rig::providers::openrouter::client::OpenRouterExt;
-use rig::providers::{anthropic, gemini, openai, openrouter};
+use etcetera::BaseStrategy;
+use rig::agent::{Agent, AgentBuilder};
+use rustyline::DefaultEditor;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::Arc;
 
-pub async fn run() -> anyhow::Result<()> {
+pub async fn run(args: Args) -> anyhow::Result<()> {
     let xdg = etcetera::choose_base_strategy().context("couldn't determine your home directory")?;
-    let _telemetry_guard = crate::telemetry::setup(&xdg).context("couldn't set up logging")?;
+    let _telemetry_guard = agx_core::telemetry::setup(&xdg).context("couldn't set up logging")?;
+    crash::install_panic_hook(agx_core::telemetry::get_log_dir(&xdg).join("crashes"));
 
-    let provider =
-        Provider::from_str(get_env_var("PROVIDER")?.as_str()).map_err(|e| anyhow::anyhow!(e))?;
-    let api_key = get_env_var("API_KEY")?;
-    let model_name = get_env_var("MODEL_NAME")?;
-    let base_url = get_optional_env_var("BASE_URL")?;
+    let cwd = std::env::current_dir().context("couldn't determine current working directory")?;
 
-    let config = crate::config::get_local_config().await?;
+    if args.prompt_file.is_some() && args.command.is_some() {
+        anyhow::bail!("--prompt-file can only be used when starting an interactive session");
+    }
 
-    let cwd = std::env::current_dir().context("couldn't determine current working directory")?;
-    let agx_log_dir = crate::telemetry::get_log_dir(&xdg);
+    if args.resume.is_some() && args.command.is_some() {
+        anyhow::bail!("--resume can only be used when starting an interactive session");
+    }
+
+    let mode = match args.command {
+        Some(AgxCommand::Index) => return run_index(&cwd).await,
+        Some(AgxCommand::Run {
+            template: name,
+            args,
+        }) => {
+            let template = get_template(&name).await?;
+            println!(
+                "{}",
+                format!(
+                    "using template {name}{}",
+                    template
+                        .description
+                        .as_ref()
+                        .map(|d| format!(": {d}"))
+                        .unwrap_or_default()
+                )
+                .blue()
+            );
+            SessionMode::Template(template, args)
+        }
+        Some(AgxCommand::Auto { task, open_pr }) => {
+            if open_pr && !args.worktree {
+                anyhow::bail!("--open-pr can only be used along with --worktree");
+            }
+            SessionMode::Auto { task, open_pr }
+        }
+        Some(AgxCommand::Replay { transcript }) => {
+            let project_log_dir = agx_core::telemetry::get_log_dir(&xdg)
+                .join("projects")
+                .join(path_to_dirname(&cwd));
+            return run_replay(&transcript, cwd, project_log_dir).await;
+        }
+        Some(AgxCommand::Import { path, from }) => {
+            SessionMode::Import(read_imported_conversation(&path, from).await?)
+        }
+        Some(AgxCommand::Share { session, output }) => {
+            let project_log_dir = agx_core::telemetry::get_log_dir(&xdg)
+                .join("projects")
+                .join(path_to_dirname(&cwd));
+            return run_share(&session, output, &project_log_dir).await;
+        }
+        Some(AgxCommand::Report { since, format }) => {
+            let projects_dir = agx_core::telemetry::get_log_dir(&xdg).join("projects");
+            return run_report(&since, format, &projects_dir, &xdg.config_dir()).await;
+        }
+        Some(AgxCommand::Tasks {
+            command: TasksCommand::Run { file },
+        }) => {
+            let project_log_dir = agx_core::telemetry::get_log_dir(&xdg)
+                .join("projects")
+                .join(path_to_dirname(&cwd));
+            return run_tasks(&file, &cwd, &project_log_dir, &args.provider).await;
+        }
+        Some(AgxCommand::Eval { suite }) => {
+            let user_config = get_user_config(&xdg.config_dir()).await?;
+            let (provider, access, model_name) = resolve_provider(&user_config, &args.provider)?;
+            let config = user_config
+                .defaults
+                .layered_with(agx_core::config::get_project_config().await?);
+            let project_log_dir = agx_core::telemetry::get_log_dir(&xdg)
+                .join("projects")
+                .join(path_to_dirname(&cwd));
+            let target = EvalTarget {
+                provider,
+                access,
+                model_name,
+                config,
+            };
+            return run_eval(&suite, target, &cwd, &project_log_dir).await;
+        }
+        Some(AgxCommand::SelfUpdate { check }) => {
+            let user_config = get_user_config(&xdg.config_dir()).await?;
+            return run_self_update(check, user_config.self_update()).await;
+        }
+        Some(AgxCommand::Cron { command }) => {
+            let project_log_dir = agx_core::telemetry::get_log_dir(&xdg)
+                .join("projects")
+                .join(path_to_dirname(&cwd));
+            return match command {
+                CronCommand::Run { manifest, all } => {
+                    run_cron(&manifest, all, &cwd, &project_log_dir, &args.provider).await
+                }
+                CronCommand::List { manifest } => list_cron(&manifest, &project_log_dir).await,
+            };
+        }
+        Some(AgxCommand::Config {
+            command: ConfigCommand::Check,
+        }) => return run_config_check(&xdg.config_dir()).await,
+        None => match (&args.prompt_file, args.resume) {
+            (Some(path), _) => SessionMode::Interactive(Some(read_prompt_file(path).await?)),
+            (None, Some(session)) => SessionMode::Resume(session),
+            (None, None) => SessionMode::Interactive(None),
+        },
+    };
+
+    let user_config = get_user_config(&xdg.config_dir()).await?;
+    let (provider, access, model_name) = resolve_provider(&user_config, &args.provider)?;
+    let approval_mode = user_config.approval_mode.unwrap_or_default();
+    crash::set_session_info(provider.to_string(), &model_name);
+
+    let config = user_config
+        .defaults
+        .layered_with(agx_core::config::get_project_config().await?);
+
+    let agx_log_dir = agx_core::telemetry::get_log_dir(&xdg);
     let project_log_dir = agx_log_dir.join("projects").join(path_to_dirname(&cwd));
 
-    let project_context = get_project_context().await?;
+    let project_context = get_project_context(&cwd).await?;
+
+    let semantic_index = match SemanticIndex::load(&cwd).await {
+        Ok(index) => index.map(Arc::new),
+        Err(e) => {
+            eprintln!(
+                "{}",
+                format!(
+                    "couldn't load the semantic index; semantic_search won't be available: {e:#}"
+                )
+                .yellow()
+            );
+            None
+        }
+    };
+
+    // keep retrieval fresh; only files that changed since the last refresh are re-embedded
+    if let Some(index) = &semantic_index {
+        match index.refresh().await {
+            Ok(summary) if !summary.is_empty() => {
+                println!("{}", format!("refreshed semantic index: {summary}").blue())
+            }
+            Ok(_) => {}
+            Err(e) => eprintln!(
+                "{}",
+                format!("couldn't refresh the semantic index: {e:#}").yellow()
+            ),
+        }
+    }
 
     tokio::fs::create_dir_all(&project_log_dir)
         .await
@@ -42,193 +199,627 @@ pub async fn run() -> anyhow::Result<()> {
             )
         })?;
 
+    // sessions in worktrees don't change the project's files
+    let (_project_lock, read_only) = if args.worktree {
+        (None, false)
+    } else {
+        match ProjectLock::try_acquire(&project_log_dir)? {
+            LockAttempt::Acquired(lock) => (Some(lock), false),
+            LockAttempt::Held { pid } => {
+                let running = match pid {
+                    Some(pid) => {
+                        format!("another agx session (pid {pid}) is running in this project")
+                    }
+                    None => "another agx session is running in this project".to_string(),
+                };
+                match config.concurrent_sessions() {
+                    ConcurrentSessionsPolicy::Refuse => anyhow::bail!(
+                        "{running}; start this one in a worktree (via --worktree) to work alongside it"
+                    ),
+                    ConcurrentSessionsPolicy::ReadOnly => {
+                        eprintln!(
+                            "{}",
+                            format!("{running}; this session is read-only (files can be read, but not changed, and commands can't be run)").yellow()
+                        );
+                        (None, true)
+                    }
+                }
+            }
+        }
+    };
+
+    let debug_tx = start_debug_server(config.debug_server.as_ref())?;
+
+    let copilot_token =
+        matches!(provider, Provider::GitHubCopilot).then_some(access.api_key.as_str());
+    let forge = match get_forge(&cwd, config.forge.as_ref(), copilot_token).await {
+        Ok(forge) => forge,
+        Err(e) => {
+            eprintln!("{}", format!("couldn't set up forge tools: {e:#}").yellow());
+            None
+        }
+    };
+
+    // set up before the worktree, so that failing to do so doesn't leave the worktree behind
+    let agent = agent_for(&provider, access.clone(), &model_name, &config).await?;
+
+    let worktree = if args.worktree {
+        let worktree = Worktree::create(&cwd, args.worktree_branch).await?;
+        println!(
+            "{}",
+            format!(
+                "working in worktree {} (branch {})",
+                worktree.path().to_string_lossy(),
+                worktree.branch()
+            )
+            .blue()
+        );
+        Some(worktree)
+    } else {
+        None
+    };
+
+    let setup = SessionSetup {
+        config,
+        project_context,
+        semantic_index,
+        forge,
+        project_dir: worktree
+            .as_ref()
+            .map(|w| w.project_dir().to_path_buf())
+            .unwrap_or(cwd),
+        worktree,
+        project_log_dir,
+        model_name,
+        user_config_dir: xdg.config_dir(),
+        approval_mode,
+        debug_tx,
+        read_only,
+        mode,
+    };
+
+    run_session(agent, provider, access, setup).await
+}
+
+fn resolve_provider(
+    user_config: &UserConfig,
+    flags: &ProviderArgs,
+) -> anyhow::Result<(Provider, ProviderAccess, String)> {
+    match selected_profile(user_config, flags)? {
+        Some(profile) => provider_from_profile(profile, flags),
+        None => provider_from_env(flags),
+    }
+}
+
+/// Returns the provider profile to use: the one named via --profile (or AGX_PROFILE), if set;
+/// otherwise the user config's default profile, unless the provider is set via --provider (or
+/// PROVIDER). Flags take precedence over environment variables.
+fn selected_profile<'a>(
+    user_config: &'a UserConfig,
+    flags: &ProviderArgs,
+) -> anyhow::Result<Option<&'a ProviderProfile>> {
+    let name = match &flags.profile {
+        Some(name) => name.clone(),
+        None if flags.provider.is_some() => return Ok(None),
+        None => match get_optional_env_var("AGX_PROFILE")? {
+            Some(name) => name,
+            None if get_optional_env_var("PROVIDER")?.is_some() => return Ok(None),
+            None => match &user_config.profile {
+                Some(name) => name.clone(),
+                None => return Ok(None),
+            },
+        },
+    };
+
+    user_config.profile(&name).map(Some)
+}
+
+/// --model and --base-url override the profile's settings.
+fn provider_from_profile(
+    profile: &ProviderProfile,
+    flags: &ProviderArgs,
+) -> anyhow::Result<(Provider, ProviderAccess, String)> {
+    let provider = Provider::from_str(&profile.provider).map_err(|e| anyhow::anyhow!(e))?;
+    let api_key_env = profile.api_key_env.as_deref().unwrap_or("API_KEY");
+    let api_key = match provider {
+        Provider::OpenAICompatible => get_optional_env_var(api_key_env)?.unwrap_or_default(),
+        _ => get_env_var(api_key_env)?,
+    };
+    let access = ProviderAccess {
+        api_key,
+        base_url: flags.base_url.clone().or_else(|| profile.base_url.clone()),
+    };
+    let model = flags.model.clone().unwrap_or_else(|| profile.model.clone());
+
+    Ok((provider, access, model))
+}
+
+fn provider_from_env(flags: &ProviderArgs) -> anyhow::Result<(Provider, ProviderAccess, String)> {
+    let provider = match &flags.provider {
+        Some(provider) => provider.clone(),
+        None => get_optional_env_var("PROVIDER")?.context(
+            "provider isn't set; pass it via --provider (or set PROVIDER), or set up a provider profile in the user config",
+        )?,
+    };
+    let provider = Provider::from_str(&provider).map_err(|e| anyhow::anyhow!(e))?;
+    let api_key = match provider {
+        Provider::OpenAICompatible => get_optional_env_var("API_KEY")?.unwrap_or_default(),
+        _ => get_env_var("API_KEY")?,
+    };
+    let base_url = match &flags.base_url {
+        Some(base_url) => Some(base_url.clone()),
+        None => get_optional_env_var("BASE_URL")?,
+    };
+    let access = ProviderAccess { api_key, base_url };
+    let model = match &flags.model {
+        Some(model) => model.clone(),
+        None => get_optional_env_var("MODEL_NAME")?
+            .context("model isn't set; pass it via --model (or set MODEL_NAME)")?,
+    };
+
+    Ok((provider, access, model))
+}
+
+/// Sets up the channel debug events are sent on; events are served by the debug server if it's
+/// enabled. Either way, recent events are included in crash reports.
+fn start_debug_server(
+    config: Option<&DebugServerConfig>,
+) -> anyhow::Result<Option<DebugEventSender>> {
     let enable_debug_server = get_optional_env_var("AGX_DEBUG_SERVER")?
         .map(|v| v == "1")
         .unwrap_or(false);
 
-    let debug_tx = if enable_debug_server {
-        let (tx, _) = tokio::sync::broadcast::channel::<DebugEvent>(64);
-        let debug_rx = DebugEventReceiver::new(tx.clone());
-        let debug_tx = DebugEventSender::new(tx);
+    if !enable_debug_server {
+        let (debug_tx, debug_rx) = debug_channel_with_history(CRASH_REPORT_EVENTS);
+        crash::set_debug_events(debug_rx);
+        return Ok(Some(debug_tx));
+    }
 
-        tokio::spawn(async move {
-            let server = DebugServer::new(debug_rx);
-            if let Err(e) = server.run().await {
-                eprintln!("\n{}", format!("couldn't run debug server: {:?}", e).red());
-            }
-        });
+    let address = config
+        .and_then(|c| c.address.as_deref())
+        .unwrap_or(DEFAULT_DEBUG_SERVER_ADDRESS);
+    let addr: SocketAddr = address
+        .parse()
+        .with_context(|| format!(r#"invalid address for the debug server: "{address}""#))?;
+    let token = config.and_then(|c| c.token.clone());
 
-        Some(debug_tx)
-    } else {
-        None
+    let (debug_tx, debug_rx) = debug_channel();
+    crash::set_debug_events(debug_rx.clone());
+    let server = DebugServer::new(debug_rx, addr, token)?;
+
+    tokio::spawn(async move {
+        if let Err(e) = server.run().await {
+            eprintln!("\n{}", format!("couldn't run debug server: {:?}", e).red());
+        }
+    });
+
+    Ok(Some(debug_tx))
+}
+
+async fn read_imported_conversation(
+    path: &Path,
+    from: Option<ImportFrom>,
+) -> anyhow::Result<Vec<TranscriptEvent>> {
+    let contents = tokio::fs::read_to_string(path)
+        .await
+        .with_context(|| format!("couldn't read {}", path.to_string_lossy()))?;
+
+    let source = match from {
+        Some(ImportFrom::ClaudeCode) => ImportSource::ClaudeCode,
+        Some(ImportFrom::Codex) => ImportSource::Codex,
+        None => ImportSource::detect(&contents)
+            .context("couldn't tell which CLI the session log is from; specify it via --from")?,
     };
 
-    match provider {
-        Provider::Anthropic => {
-            let mut builder = anthropic::Client::builder().api_key(api_key);
-            if let Some(u) = base_url {
-                builder = builder.base_url(u);
+    import_transcript(&contents, source)
+        .with_context(|| format!("couldn't import {}", path.to_string_lossy()))
+}
+
+async fn run_config_check(config_dir: &Path) -> anyhow::Result<()> {
+    let report = check_config(config_dir).await?;
+    print!("{report}");
+
+    if report.has_errors() {
+        anyhow::bail!("config has errors");
+    }
+    println!("{}", "\nconfig is valid".green());
+
+    Ok(())
+}
+
+/// Prints token usage and cost across the sessions recorded since a while ago. Pricing comes
+/// from user and project config.
+async fn run_report(
+    since: &str,
+    format: ReportFormat,
+    projects_dir: &Path,
+    config_dir: &Path,
+) -> anyhow::Result<()> {
+    let since = chrono::Local::now().naive_local() - parse_duration(since)?;
+    let config = get_user_config(config_dir)
+        .await?
+        .defaults
+        .layered_with(agx_core::config::get_project_config().await?);
+
+    let mut report = UsageReport::new(&config.models);
+    report.collect(projects_dir, since).await?;
+
+    match format {
+        ReportFormat::Table => print!("{report}"),
+        ReportFormat::Csv => print!("{}", report.to_csv()),
+    }
+
+    Ok(())
+}
+
+/// Writes a session's transcript as a self-contained HTML file.
+async fn run_share(
+    session: &str,
+    output: Option<PathBuf>,
+    project_log_dir: &Path,
+) -> anyhow::Result<()> {
+    let (name, transcript_path) = find_transcript(session, project_log_dir).await?;
+    let transcript = tokio::fs::read_to_string(&transcript_path)
+        .await
+        .with_context(|| {
+            format!(
+                "couldn't read transcript {}",
+                transcript_path.to_string_lossy()
+            )
+        })?;
+
+    let html = transcript_to_html(&transcript, &name)?;
+    let output = output.unwrap_or_else(|| PathBuf::from(format!("agx-{name}.html")));
+    tokio::fs::write(&output, html)
+        .await
+        .with_context(|| format!("couldn't write {}", output.to_string_lossy()))?;
+
+    println!(
+        "{}",
+        format!(
+            "wrote {}; tool outputs are included as is, so review it before sharing",
+            output.to_string_lossy()
+        )
+        .green()
+    );
+
+    Ok(())
+}
+
+/// Resolves a session (a path to its transcript, or the name of its directory in the project's
+/// log directory, or "latest") to its name and the path to its transcript.
+async fn find_transcript(
+    session: &str,
+    project_log_dir: &Path,
+) -> anyhow::Result<(String, PathBuf)> {
+    let path = Path::new(session);
+    if path.is_file() {
+        let name = path
+            .parent()
+            .and_then(|p| p.file_name())
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "session".to_string());
+        return Ok((name, path.to_path_buf()));
+    }
+
+    let chats_dir = project_log_dir.join("chats");
+    let name = if session == "latest" {
+        let mut entries = tokio::fs::read_dir(&chats_dir)
+            .await
+            .context("couldn't find any sessions for this project")?;
+        let mut latest = None;
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().to_string();
+            // session directories are named after the time they were started at
+            if entry.path().join("transcript.jsonl").is_file()
+                && latest.as_ref().is_none_or(|l| &name > l)
+            {
+                latest = Some(name);
             }
-            let client: Client<AnthropicExt> = builder.build().context("couldn't build client")?;
-
-            let agent = client
-                .agent(&model_name)
-                .without_preamble()
-                .max_tokens(200_000)
-                .tool(CreateFileTool)
-                .tool(EditFileTool)
-                .tool(ReadDirTool)
-                .tool(ReadFileTool)
-                .tool(RunCmdTool)
-                .build();
-
-            let mut session = Session::new(
-                config,
-                agent,
-                project_context,
-                cwd,
-                project_log_dir,
-                provider,
-                &model_name,
-                debug_tx,
-            )?;
-            session.run().await?;
-        }
-        Provider::Gemini => {
-            let mut builder = gemini::Client::builder().api_key(api_key);
-            if let Some(u) = base_url {
-                builder = builder.base_url(u);
+        }
+        latest.context("couldn't find any recorded sessions for this project")?
+    } else {
+        session.to_string()
+    };
+
+    let transcript_path = chats_dir.join(&name).join("transcript.jsonl");
+    if !transcript_path.is_file() {
+        anyhow::bail!(
+            "session {name} doesn't have a transcript; sessions are looked up in {}",
+            chats_dir.to_string_lossy()
+        );
+    }
+
+    Ok((name, transcript_path))
+}
+
+/// Replays a transcript without making any requests to the model, or executing any tools.
+async fn run_replay(
+    transcript_path: &Path,
+    project_dir: PathBuf,
+    project_log_dir: PathBuf,
+) -> anyhow::Result<()> {
+    let transcript = Transcript::load(transcript_path).await?;
+    let provider = Provider::from_str(&transcript.provider).map_err(|e| anyhow::anyhow!(e))?;
+    let config = agx_core::config::get_project_config().await?;
+    let project_context = get_project_context(&project_dir).await?;
+    let debug_tx = start_debug_server(config.debug_server.as_ref())?;
+
+    println!(
+        "{}",
+        format!(
+            "replaying {} prompt(s) and {} response(s) recorded with {}/{}",
+            transcript.prompts.len(),
+            transcript.responses.len(),
+            transcript.provider,
+            transcript.model
+        )
+        .blue()
+    );
+    if let Some(version) = &transcript.agx_version {
+        println!(
+            "{}",
+            format!(
+                "recorded by agx {version} (config {}); this is agx {}",
+                transcript.config_digest.as_deref().unwrap_or("unknown"),
+                env!("CARGO_PKG_VERSION")
+            )
+            .blue()
+        );
+    }
+
+    let model = ReplayModel::new(transcript.responses.clone());
+    let agent = AgentBuilder::new(model.clone()).build();
+    let model_name = transcript.model.clone();
+    let mut session = Session::builder(agent, provider, model_name, project_dir, project_log_dir)
+        .config(config)
+        .project_context(project_context)
+        .debug_tx(debug_tx)
+        .build()?;
+
+    session.replay(transcript).await?;
+
+    match model.remaining() {
+        0 => println!("{}", "\nreplay finished".green()),
+        n => println!(
+            "{}",
+            format!("\nreplay finished with {n} recorded response(s) left; it diverged from the recording").yellow()
+        ),
+    }
+
+    Ok(())
+}
+
+struct SessionSetup {
+    config: Config,
+    project_context: Option<String>,
+    semantic_index: Option<Arc<SemanticIndex>>,
+    forge: Option<Arc<dyn Forge>>,
+    project_dir: PathBuf,
+    worktree: Option<Worktree>,
+    project_log_dir: PathBuf,
+    model_name: String,
+    user_config_dir: PathBuf,
+    approval_mode: ApprovalMode,
+    debug_tx: Option<DebugEventSender>,
+    read_only: bool,
+    mode: SessionMode,
+}
+
+enum SessionMode {
+    /// Along with a prompt to start with, if any.
+    Interactive(Option<String>),
+    Template(Template, Vec<String>),
+    /// Continues a conversation imported from another CLI.
+    Import(Vec<TranscriptEvent>),
+    /// Continues the conversation of a saved session.
+    Resume(String),
+    Auto {
+        task: String,
+        /// Whether a pull request is to be opened for the changes made (in the worktree).
+        open_pr: bool,
+    },
+}
+
+async fn run_session(
+    agent: Agent<DynModel>,
+    provider: Provider,
+    access: ProviderAccess,
+    setup: SessionSetup,
+) -> anyhow::Result<()> {
+    let session = Session::builder(
+        agent,
+        provider,
+        &setup.model_name,
+        setup.project_dir,
+        setup.project_log_dir,
+    )
+    .provider_access(access)
+    .config(setup.config)
+    .user_config_dir(Some(setup.user_config_dir))
+    .approval_mode(setup.approval_mode)
+    .project_context(setup.project_context)
+    .semantic_index(setup.semantic_index)
+    .debug_tx(setup.debug_tx)
+    .read_only(setup.read_only)
+    .build();
+
+    // the worktree is wrapped up however the session ends, including when it couldn't start
+    let result = match session {
+        Ok(mut session) => {
+            if let Some(forge) = &setup.forge {
+                session.register_forge_tools(Arc::clone(forge));
             }
-            let client: Client<GeminiExt> = builder.build().context("couldn't build client")?;
-
-            let agent = client
-                .agent(&model_name)
-                .without_preamble()
-                .tool(CreateFileTool)
-                .tool(EditFileTool)
-                .tool(ReadDirTool)
-                .tool(ReadFileTool)
-                .tool(RunCmdTool)
-                .build();
-
-            let mut session = Session::new(
-                config,
-                agent,
-                project_context,
-                cwd,
-                project_log_dir,
-                provider,
-                &model_name,
-                debug_tx,
-            )?;
-            session.run().await?;
-        }
-        Provider::GitHubCopilot => {
-            let client: Client<OpenAICompletionsExt> = {
-                let http_client = reqwest::Client::builder()
-                    .default_headers(copilot::get_headers())
-                    .build()
-                    .context("couldn't build http client for copilot API calls")?;
-
-                let copilot_auth = copilot::get_auth_token(&http_client, &api_key)
-                    .await
-                    .context("couldn't get a short lived GitHub Copilot token")?;
-
-                let builder = openai::Client::<reqwest::Client>::builder()
-                    .base_url(&copilot_auth.endpoints.api)
-                    .api_key(&copilot_auth.token)
-                    .http_client(http_client);
-
-                builder
-                    .build()
-                    .context("couldn't build client")?
-                    .completions_api() // This is to maintain consistency with the other clients
-            };
+            run_in_mode(&mut session, &setup.mode).await
+        }
+        Err(e) => Err(e),
+    };
 
-            let agent = client
-                .agent(&model_name)
-                .without_preamble()
-                .tool(CreateFileTool)
-                .tool(EditFileTool)
-                .tool(ReadDirTool)
-                .tool(ReadFileTool)
-                .tool(RunCmdTool)
-                .build();
-
-            let mut session = Session::new(
-                config,
-                agent,
-                project_context,
-                cwd,
-                project_log_dir,
-                provider,
-                &model_name,
-                debug_tx,
-            )?;
-            session.run().await?;
-        }
-        Provider::OpenAI => {
-            let mut builder = openai::Client::builder().api_key(api_key);
-            if let Some(u) = base_url {
-                builder = builder.base_url(u);
+    if let Some(worktree) = setup.worktree {
+        match &setup.mode {
+            // changes of failed runs are kept for review instead; a pull request that couldn't
+            // be opened fails the run
+            SessionMode::Auto {
+                task,
+                open_pr: true,
+            } if result.is_ok() => {
+                return open_pull_request(worktree, setup.forge.as_deref(), task).await;
             }
-            let client: Client<OpenAICompletionsExt> = builder
-                .build()
-                .context("couldn't build client")?
-                .completions_api();
-
-            let agent = client
-                .agent(&model_name)
-                .without_preamble()
-                .tool(CreateFileTool)
-                .tool(EditFileTool)
-                .tool(ReadDirTool)
-                .tool(ReadFileTool)
-                .tool(RunCmdTool)
-                .build();
-
-            let mut session = Session::new(
-                config,
-                agent,
-                project_context,
-                cwd,
-                project_log_dir,
-                provider,
-                &model_name,
-                debug_tx,
-            )?;
-            session.run().await?;
-        }
-        Provider::Openrouter => {
-            let mut builder = openrouter::Client::builder().api_key(api_key);
-            if let Some(u) = base_url {
-                builder = builder.base_url(u);
+            mode => {
+                // unattended runs can't be asked what to do; their changes are kept for review
+                let ask = !matches!(mode, SessionMode::Auto { .. });
+                if let Err(e) = wrap_up_worktree(worktree, ask).await {
+                    eprintln!("{}", format!("{e:#}").red());
+                }
             }
-            let client: Client<OpenRouterExt> = builder.build().context("couldn't build client")?;
-
-            let agent = client
-                .agent(&model_name)
-                .without_preamble()
-                .tool(CreateFileTool)
-                .tool(EditFileTool)
-                .tool(ReadDirTool)
-                .tool(ReadFileTool)
-                .tool(RunCmdTool)
-                .build();
-
-            let mut session = Session::new(
-                config,
-                agent,
-                project_context,
-                cwd,
-                project_log_dir,
-                provider,
-                &model_name,
-                debug_tx,
-            )?;
-            session.run().await?;
         }
     }
 
-    Ok(())
+    result
+}
+
+async fn run_in_mode(session: &mut Session, mode: &SessionMode) -> anyhow::Result<()> {
+    match mode {
+        SessionMode::Interactive(prompt) => {
+            if let Some(prompt) = prompt {
+                session.set_initial_prompt(prompt);
+            }
+            session.run().await
+        }
+        SessionMode::Template(template, args) => {
+            session.apply_template(template, args)?;
+            session.run().await
+        }
+        SessionMode::Import(events) => {
+            session.set_imported_conversation(events.clone());
+            session.run().await
+        }
+        SessionMode::Resume(name) => {
+            session.set_resumed_session(name);
+            session.run().await
+        }
+        SessionMode::Auto { task, .. } => session.run_task(task).await,
+    }
+}
+
+/// Commits the changes made in the worktree, pushes its branch, and opens a pull request for it;
+/// the worktree is removed once that's done (its branch lives on in the remote).
+async fn open_pull_request(
+    worktree: Worktree,
+    forge: Option<&dyn Forge>,
+    task: &str,
+) -> anyhow::Result<()> {
+    if !worktree.has_changes().await? {
+        worktree.discard().await?;
+        println!("{}", "no changes were made; removed the worktree".blue());
+        return Ok(());
+    }
+
+    let kept = format!(
+        "the changes have been kept in worktree {} (branch {})",
+        worktree.path().to_string_lossy(),
+        worktree.branch()
+    );
+    let Some(forge) = forge else {
+        anyhow::bail!(
+            "couldn't open a pull request, since no forge is set up for the project (a token needs to be set); {kept}"
+        );
+    };
+
+    let title = pull_request_title(task);
+    let body = format!(
+        "Changes made by agx, running unattended, for the following task:\n\n{}",
+        task.lines()
+            .map(|l| format!("> {l}"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    );
+    worktree.commit(&title).await.context(kept.clone())?;
+    worktree.push().await.context(kept.clone())?;
+    let pull_request = forge
+        .create_pull_request(&title, &body, worktree.branch(), None, false)
+        .await
+        .context(kept)?;
+    println!(
+        "{}",
+        format!(
+            "opened {} {}",
+            forge.kind().pull_request_term(),
+            pull_request.url
+        )
+        .green()
+    );
+
+    worktree.discard().await
+}
+
+/// The task's first line, cut down to a reasonable length.
+fn pull_request_title(task: &str) -> String {
+    const MAX_TITLE_CHARS: usize = 72;
+
+    let first_line = task
+        .lines()
+        .find(|l| !l.trim().is_empty())
+        .unwrap_or("")
+        .trim();
+    if first_line.chars().count() <= MAX_TITLE_CHARS {
+        return first_line.to_string();
+    }
+
+    let mut title = first_line
+        .chars()
+        .take(MAX_TITLE_CHARS - 1)
+        .collect::<String>();
+    title.push('…');
+    title
+}
+
+async fn wrap_up_worktree(worktree: Worktree, ask: bool) -> anyhow::Result<()> {
+    if !worktree.has_changes().await? {
+        worktree.discard().await?;
+        println!("{}", "no changes were made; removed the worktree".blue());
+        return Ok(());
+    }
+
+    let keep_message = format!(
+        "kept worktree {} (branch {})",
+        worktree.path().to_string_lossy(),
+        worktree.branch()
+    );
+
+    if !ask {
+        println!("{}", keep_message.blue());
+        return Ok(());
+    }
+
+    let mut editor = DefaultEditor::new()?;
+    loop {
+        let prompt = format!(
+            "\nchanges were made in branch {}; (m)erge, (d)iscard, or (k)eep? ",
+            worktree.branch()
+        );
+        let Ok(answer) = editor.readline(&prompt) else {
+            println!("{}", keep_message.blue());
+            return Ok(());
+        };
+
+        match answer.trim() {
+            "m" | "merge" => {
+                let branch = worktree.branch().to_string();
+                worktree.merge().await?;
+                println!("{}", format!("merged branch {branch}").green());
+                return Ok(());
+            }
+            "d" | "discard" => {
+                worktree.discard().await?;
+                println!("{}", "discarded changes".yellow());
+                return Ok(());
+            }
+            "k" | "keep" => {
+                println!("{}", keep_message.blue());
+                return Ok(());
+            }
+            _ => continue,
+        }
+    }
 }
@@ -0,0 +1,203 @@
+use clap::{Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
+
+/// agx is a simple agentic executor
+#[derive(Parser, Debug)]
+#[command(about, version)]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<AgxCommand>,
+    /// Work in a temporary git worktree (on a branch of its own); its changes can be merged or
+    /// discarded once the session ends
+    #[arg(long, global = true)]
+    pub worktree: bool,
+    /// Name of the worktree's branch; defaults to one based on the current time
+    #[arg(long, global = true, requires = "worktree", value_name = "NAME")]
+    pub worktree_branch: Option<String>,
+    /// Start an interactive session by sending the prompt in this file
+    #[arg(short = 'f', long, value_name = "PATH")]
+    pub prompt_file: Option<PathBuf>,
+    /// Start an interactive session by continuing a previous session's conversation: the name of
+    /// its directory in agx's log directory, or "latest"
+    #[arg(long, value_name = "SESSION", conflicts_with = "prompt_file")]
+    pub resume: Option<String>,
+    #[command(flatten)]
+    pub provider: ProviderArgs,
+}
+
+/// Which provider and model to use; these take precedence over environment variables (PROVIDER,
+/// MODEL_NAME, BASE_URL, and AGX_PROFILE) and the user config's default profile. API keys are
+/// only read from environment variables, so that they don't end up in shell history.
+#[derive(clap::Args, Debug, Default, Clone)]
+pub struct ProviderArgs {
+    /// Use this provider profile from the user config
+    #[arg(long, global = true, value_name = "NAME", conflicts_with = "provider")]
+    pub profile: Option<String>,
+    /// Use this provider (eg. anthropic); its API key is read from API_KEY
+    #[arg(long, global = true, value_name = "PROVIDER")]
+    pub provider: Option<String>,
+    /// Use this model of the provider
+    #[arg(long, global = true, value_name = "MODEL")]
+    pub model: Option<String>,
+    /// Send requests to this base URL, eg. for the openai-compatible provider
+    #[arg(long, global = true, value_name = "URL")]
+    pub base_url: Option<String>,
+}
+
+impl ProviderArgs {
+    /// The flags to pass on to agx processes started by this one (eg. for tasks).
+    pub fn to_args(&self) -> Vec<String> {
+        [
+            ("--profile", &self.profile),
+            ("--provider", &self.provider),
+            ("--model", &self.model),
+            ("--base-url", &self.base_url),
+        ]
+        .into_iter()
+        .filter_map(|(flag, value)| value.as_ref().map(|v| [flag.to_string(), v.clone()]))
+        .flatten()
+        .collect()
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum AgxCommand {
+    /// Chunk and embed the project's files into a local index; enables the semantic_search tool
+    Index,
+    /// Start a session using a template from .agx/templates
+    Run {
+        /// Name of the template (without the ".md" extension)
+        template: String,
+        /// Arguments for the template's prompt
+        #[arg(trailing_var_arg = true)]
+        args: Vec<String>,
+    },
+    /// Run a task without confirmations, under the guardrails set in agx's local config; exits
+    /// with a non-zero code if a guardrail trips
+    Auto {
+        /// The task to perform
+        task: String,
+        /// Commit the changes made, push them, and open a pull request for them; needs --worktree,
+        /// and a token for the project's forge
+        #[arg(long)]
+        open_pr: bool,
+    },
+    /// Replay a session's transcript (recorded in agx's log directory) against the current code;
+    /// recorded responses and tool results are used in place of the model and the tools
+    Replay {
+        /// Path to the transcript
+        transcript: PathBuf,
+    },
+    /// Continue a conversation from another agent CLI in an interactive session; its prompts,
+    /// responses, and tool calls (as text) are imported into the session's history
+    Import {
+        /// Path to the conversation's session log, eg. a JSONL file in ~/.claude/projects or in
+        /// ~/.codex/sessions
+        path: PathBuf,
+        /// The CLI the session log is from; detected from its contents if not given
+        #[arg(long, value_enum, value_name = "CLI")]
+        from: Option<ImportFrom>,
+    },
+    /// Package a session's transcript (prompts, responses, tool calls and their results) into a
+    /// single, self-contained HTML file, for sharing; review it before posting, since tool
+    /// outputs are included as is
+    Share {
+        /// Session to share: the name of its directory in agx's log directory, "latest", or a path
+        /// to its transcript
+        session: String,
+        /// Where to write the HTML file; defaults to agx-<session>.html
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+    /// Report token usage and cost per project and model, aggregated from the transcripts of
+    /// past sessions; costs are computed for models that have pricing in config
+    Report {
+        /// Only include sessions started within this long, eg. "12h", "7d", or "4w"
+        #[arg(long, value_name = "DURATION", default_value = "7d")]
+        since: String,
+        /// Output format
+        #[arg(long, value_enum, default_value_t = ReportFormat::Table)]
+        format: ReportFormat,
+    },
+    /// Evaluate the current model on scripted tasks: each task is run in autopilot mode, in a git
+    /// worktree of its own, and passes if the files it leaves behind meet the suite's expectations
+    Eval {
+        /// Path to the eval suite (YAML)
+        suite: PathBuf,
+    },
+    /// Manage batches of independent tasks
+    Tasks {
+        #[command(subcommand)]
+        command: TasksCommand,
+    },
+    /// Run recurring chores (eg. updating the dependency lockfile every week) in autopilot mode;
+    /// meant to be invoked periodically, eg. every hour via cron
+    Cron {
+        #[command(subcommand)]
+        command: CronCommand,
+    },
+    /// Update agx to its latest release on GitHub, replacing the running executable; the
+    /// release's checksum is verified before anything is replaced. The checksum is published in
+    /// the same release, so it catches corrupted downloads, but not a tampered release
+    SelfUpdate {
+        /// Only check whether an update is available
+        #[arg(long)]
+        check: bool,
+    },
+    /// Inspect agx's config
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    /// Parse and validate every config layer (user, team, and local), reporting unknown keys,
+    /// invalid values, and local settings that override the team's
+    Check,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TasksCommand {
+    /// Run the tasks in a task list concurrently, each via "agx auto" in a git worktree of its
+    /// own
+    Run {
+        /// Path to the task list (YAML)
+        file: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CronCommand {
+    /// Run the jobs that are due, one after another, each via "agx auto" in a git worktree of its
+    /// own; a pull request is opened for the changes a job makes
+    Run {
+        /// Path to the manifest of jobs (TOML)
+        #[arg(long, value_name = "PATH", default_value = CRON_MANIFEST_PATH)]
+        manifest: PathBuf,
+        /// Run all jobs, whether they're due or not
+        #[arg(long)]
+        all: bool,
+    },
+    /// List the jobs in the manifest, along with when they last ran and when they're due next
+    List {
+        /// Path to the manifest of jobs (TOML)
+        #[arg(long, value_name = "PATH", default_value = CRON_MANIFEST_PATH)]
+        manifest: PathBuf,
+    },
+}
+
+const CRON_MANIFEST_PATH: &str = ".agx/cron.toml";
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum ImportFrom {
+    ClaudeCode,
+    Codex,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum ReportFormat {
+    Table,
+    Csv,
+}
@@ -0,0 +1,605 @@
+//! Validation of every config layer, for `agx config check`. Problems are reported along with
+//! the file (and line, where it can be found) they're in, instead of failing at startup.
+
+use super::{
+    AGX_DIR, ConfigFormat, LOCAL_CONFIG_FILE_STEM, TEAM_CONFIG_FILE_STEM, USER_CONFIG_FILE_STEM,
+    find_config_file, strip_local_only_settings,
+};
+use crate::debug::MIN_DEBUG_SERVER_TOKEN_LEN;
+use crate::domain::{ApprovalMode, Config, UserConfig};
+use anyhow::Context;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::fmt::Display;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug)]
+pub struct ConfigIssue {
+    pub severity: Severity,
+    /// Line (starting at 1) the issue is on, if it could be determined.
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl ConfigIssue {
+    fn error(line: Option<usize>, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            line,
+            message: message.into(),
+        }
+    }
+
+    fn warning(line: Option<usize>, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+/// Results of checking a single config file.
+#[derive(Debug)]
+pub struct LayerReport {
+    pub name: &'static str,
+    pub path: PathBuf,
+    pub exists: bool,
+    pub issues: Vec<ConfigIssue>,
+}
+
+#[derive(Debug)]
+pub struct ConfigReport {
+    /// In order of increasing precedence.
+    pub layers: Vec<LayerReport>,
+}
+
+impl ConfigReport {
+    pub fn has_errors(&self) -> bool {
+        self.layers
+            .iter()
+            .flat_map(|l| &l.issues)
+            .any(|i| i.severity == Severity::Error)
+    }
+}
+
+impl Display for ConfigReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for layer in &self.layers {
+            let errors = layer
+                .issues
+                .iter()
+                .filter(|i| i.severity == Severity::Error)
+                .count();
+            let warnings = layer.issues.len() - errors;
+            let status = match (layer.exists, errors, warnings) {
+                (false, _, _) => "not found".to_string(),
+                (true, 0, 0) => "ok".to_string(),
+                (true, e, w) => format!("{e} error(s), {w} warning(s)"),
+            };
+            writeln!(
+                f,
+                "{} ({}): {status}",
+                layer.name,
+                layer.path.to_string_lossy()
+            )?;
+
+            let mut issues = layer.issues.iter().collect::<Vec<_>>();
+            issues.sort_by_key(|i| i.line.unwrap_or(usize::MAX));
+            for issue in issues {
+                let severity = match issue.severity {
+                    Severity::Error => "error",
+                    Severity::Warning => "warning",
+                };
+                let location = issue
+                    .line
+                    .map(|l| format!("line {l}: "))
+                    .unwrap_or_default();
+                // parse errors span multiple lines
+                let message = issue.message.replace('\n', "\n    ");
+                writeln!(f, "  - {location}{severity}: {message}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A config file that's been read and checked on its own.
+struct Layer<T> {
+    report: LayerReport,
+    contents: String,
+    config: Option<T>,
+}
+
+/// Checks the user level config (from agx's directory within `config_dir`), and the project's
+/// team and local config; both on their own, and for conflicts between them.
+pub async fn check_config(config_dir: &Path) -> anyhow::Result<ConfigReport> {
+    let user_path = find_config_file(&config_dir.join("agx"), USER_CONFIG_FILE_STEM).await?;
+    let team_path = find_config_file(Path::new(AGX_DIR), TEAM_CONFIG_FILE_STEM).await?;
+    let local_path = find_config_file(Path::new(AGX_DIR), LOCAL_CONFIG_FILE_STEM).await?;
+
+    let mut user = read_layer::<UserConfig>("user config", user_path).await?;
+    let mut team = read_layer::<Config>("team config", team_path).await?;
+    let mut local = read_layer::<Config>("local config", local_path).await?;
+
+    if let Some(config) = &user.config {
+        user.report
+            .issues
+            .extend(validate_user_config(config, &user.contents));
+    }
+    if let Some(config) = &team.config {
+        team.report
+            .issues
+            .extend(validate_team_config(config, &team.contents));
+    }
+    if let Some(config) = &local.config {
+        local
+            .report
+            .issues
+            .extend(validate_config(config, &local.contents));
+    }
+
+    if let (Some(team_config), Some(local_config)) = (&team.config, &local.config) {
+        local
+            .report
+            .issues
+            .extend(overrides(team_config, local_config, &local.contents));
+    }
+
+    if let Some(user_config) = &user.config
+        && user_config.approval_mode == Some(ApprovalMode::Strict)
+    {
+        for layer in [&mut team, &mut local] {
+            if layer
+                .config
+                .as_ref()
+                .is_some_and(|c| !c.approved_commands.is_empty())
+            {
+                layer.report.issues.push(ConfigIssue::warning(
+                    line_of_key(&layer.contents, &["approved_commands"]),
+                    "approved commands aren't applied, since the user config sets the approval mode to strict",
+                ));
+            }
+        }
+    }
+
+    Ok(ConfigReport {
+        layers: vec![user.report, team.report, local.report],
+    })
+}
+
+async fn read_layer<T>(name: &'static str, path: PathBuf) -> anyhow::Result<Layer<T>>
+where
+    T: DeserializeOwned + Serialize,
+{
+    let contents = match tokio::fs::read_to_string(&path).await {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(Layer {
+                report: LayerReport {
+                    name,
+                    path,
+                    exists: false,
+                    issues: vec![],
+                },
+                contents: String::new(),
+                config: None,
+            });
+        }
+        Err(e) => {
+            return Err(e)
+                .with_context(|| format!(r#"couldn't read {name} (from "{}")"#, path.display()));
+        }
+    };
+
+    let format = ConfigFormat::of(&path)?;
+    let (issues, config) = check_contents(format, &contents);
+
+    Ok(Layer {
+        report: LayerReport {
+            name,
+            path,
+            exists: true,
+            issues,
+        },
+        contents,
+        config,
+    })
+}
+
+/// Parses config, and reports keys that'd be ignored when it's read.
+fn check_contents<T>(format: ConfigFormat, contents: &str) -> (Vec<ConfigIssue>, Option<T>)
+where
+    T: DeserializeOwned + Serialize,
+{
+    let config = match format.parse::<T>(contents) {
+        Ok(c) => c,
+        Err(e) => return (vec![ConfigIssue::error(None, format!("{e:#}"))], None),
+    };
+
+    // keys that don't survive a round trip through the config's type are unknown to it
+    let (Ok(raw), Ok(known)) = (
+        format.parse::<Value>(contents),
+        serde_json::to_value(&config),
+    ) else {
+        return (vec![], Some(config));
+    };
+
+    let mut unknown = Vec::new();
+    unknown_keys(&raw, &known, &mut Vec::new(), &mut unknown);
+
+    let issues = unknown
+        .into_iter()
+        .map(|path| {
+            let keys = path.iter().map(String::as_str).collect::<Vec<_>>();
+            ConfigIssue::error(
+                line_of_key(contents, &keys),
+                format!(r#"unknown key "{}""#, keys.join(".")),
+            )
+        })
+        .collect();
+
+    (issues, Some(config))
+}
+
+fn unknown_keys(
+    raw: &Value,
+    known: &Value,
+    path: &mut Vec<String>,
+    unknown: &mut Vec<Vec<String>>,
+) {
+    let (Value::Object(raw), Value::Object(known)) = (raw, known) else {
+        return;
+    };
+
+    for (key, value) in raw {
+        path.push(key.clone());
+        match known.get(key) {
+            Some(known_value) => unknown_keys(value, known_value, path, unknown),
+            // empty values are left out when config is serialized
+            None if is_empty(value) => {}
+            None => unknown.push(path.clone()),
+        }
+        path.pop();
+    }
+}
+
+fn is_empty(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::Array(a) => a.is_empty(),
+        Value::Object(o) => o.is_empty(),
+        _ => false,
+    }
+}
+
+fn validate_config(config: &Config, contents: &str) -> Vec<ConfigIssue> {
+    let mut issues = Vec::new();
+
+    for pattern in config.approved_commands.iter() {
+        if let Err(e) = pattern.validate() {
+            issues.push(ConfigIssue::error(
+                line_of_key(contents, &["approved_commands"]),
+                format!(r#"invalid approved command "{pattern}": {e}"#),
+            ));
+        }
+    }
+
+    if let Some(autopilot) = &config.autopilot {
+        for path in &autopilot.protected_paths {
+            if let Err(e) = globset::Glob::new(path) {
+                issues.push(ConfigIssue::error(
+                    line_of_key(contents, &["autopilot", "protected_paths"]),
+                    format!(r#"invalid protected path "{path}": {}"#, e.kind()),
+                ));
+            }
+        }
+
+        if autopilot.max_cost.is_some() && config.models.values().all(|m| m.pricing.is_none()) {
+            issues.push(ConfigIssue::warning(
+                line_of_key(contents, &["autopilot", "max_cost"]),
+                "max_cost is only enforced for models that have pricing configured, and none do here",
+            ));
+        }
+    }
+
+    if let Some(address) = config
+        .debug_server
+        .as_ref()
+        .and_then(|d| d.address.as_ref())
+        && address.parse::<SocketAddr>().is_err()
+    {
+        issues.push(ConfigIssue::error(
+            line_of_key(contents, &["debug_server", "address"]),
+            format!(r#"invalid address for the debug server: "{address}""#),
+        ));
+    }
+
+    if let Some(token) = config.debug_server.as_ref().and_then(|d| d.token.as_ref())
+        && token.chars().count() < MIN_DEBUG_SERVER_TOKEN_LEN
+    {
+        issues.push(ConfigIssue::error(
+            line_of_key(contents, &["debug_server", "token"]),
+            format!(
+                "the debug server's token needs to be at least {MIN_DEBUG_SERVER_TOKEN_LEN} characters long"
+            ),
+        ));
+    }
+
+    for webhook in &config.webhooks {
+        if !webhook.url.starts_with("https://") && !webhook.url.starts_with("http://") {
+            issues.push(ConfigIssue::error(
+                line_of_key(contents, &["webhooks"]),
+                format!(
+                    r#"invalid webhook URL "{}"; expected an http(s) URL"#,
+                    webhook.url
+                ),
+            ));
+        }
+    }
+
+    issues
+}
+
+fn validate_user_config(config: &UserConfig, contents: &str) -> Vec<ConfigIssue> {
+    let mut issues = validate_config(&config.defaults, contents);
+
+    if let Some(name) = &config.profile
+        && let Err(e) = config.profile(name)
+    {
+        issues.push(ConfigIssue::error(
+            line_of_key(contents, &["profile"]),
+            e.to_string(),
+        ));
+    }
+
+    issues
+}
+
+fn validate_team_config(config: &Config, contents: &str) -> Vec<ConfigIssue> {
+    let mut issues = validate_config(config, contents);
+
+    if !config.mcp_servers.is_empty() {
+        issues.push(ConfigIssue::warning(
+            line_of_key(contents, &["mcp_servers"]),
+            "MCP servers in the team config aren't started, since it's committed; declare them in the local or user config instead",
+        ));
+    }
+
+    for key in strip_local_only_settings(&mut config.clone()) {
+        issues.push(ConfigIssue::warning(
+            line_of_key(contents, key),
+            format!(
+                r#""{}" in the team config is ignored, since it's committed; set it in the local or user config instead"#,
+                key.join(".")
+            ),
+        ));
+    }
+
+    issues
+}
+
+/// Settings in the local config that replace different ones in the team's.
+fn overrides(team: &Config, local: &Config, local_contents: &str) -> Vec<ConfigIssue> {
+    let (Ok(Value::Object(team)), Ok(Value::Object(local))) =
+        (serde_json::to_value(team), serde_json::to_value(local))
+    else {
+        return vec![];
+    };
+
+    let mut issues = Vec::new();
+    for (key, value) in &local {
+        // approved commands and webhooks from both are kept
+        if key == "approved_commands" || key == "webhooks" {
+            continue;
+        }
+
+        match (key.as_str(), team.get(key)) {
+            ("models", Some(Value::Object(team_models))) => {
+                let Value::Object(local_models) = value else {
+                    continue;
+                };
+                for (model, settings) in local_models {
+                    if team_models.get(model).is_some_and(|s| s != settings) {
+                        issues.push(ConfigIssue::warning(
+                            line_of_key(local_contents, &["models", model]),
+                            format!(r#"settings for model "{model}" replace the team config's"#),
+                        ));
+                    }
+                }
+            }
+            (_, Some(team_value)) if team_value != value => {
+                issues.push(ConfigIssue::warning(
+                    line_of_key(local_contents, &[key]),
+                    format!(r#""{key}" overrides the team config's value"#),
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    issues
+}
+
+/// Finds the line a (possibly nested) key is declared on, in either JSON or TOML. Keys are
+/// looked for in order, each after the previous one; keys that can't be found (eg. the parts of
+/// a TOML table header after the first) are skipped, apart from the last one.
+fn line_of_key(contents: &str, keys: &[&str]) -> Option<usize> {
+    let lines = contents.lines().collect::<Vec<_>>();
+    let mut start = 0;
+    let mut line = None;
+
+    for key in keys {
+        line = lines[start..]
+            .iter()
+            .position(|l| declares_key(l, key))
+            .map(|i| start + i);
+        if let Some(l) = line {
+            start = l;
+        }
+    }
+
+    line.map(|l| l + 1)
+}
+
+fn declares_key(line: &str, key: &str) -> bool {
+    let line = line.trim_start().trim_start_matches('[');
+    let rest = line
+        .strip_prefix(&format!(r#""{key}""#))
+        .or_else(|| line.strip_prefix(key));
+
+    rest.is_some_and(|r| r.trim_start().starts_with(['=', ':', '.', ']']))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::assert_snapshot;
+
+    fn report(issues: Vec<ConfigIssue>) -> String {
+        ConfigReport {
+            layers: vec![LayerReport {
+                name: "local config",
+                path: PathBuf::from(".agx/config.local.toml"),
+                exists: true,
+                issues,
+            }],
+        }
+        .to_string()
+    }
+
+    #[test]
+    fn unknown_keys_are_reported_with_their_lines() {
+        // GIVEN
+        let contents = r#"
+workspace = "packages/api"
+tool_failure_reminder = false
+
+[models.claude-sonnet-4-5]
+tool_calling = "text"
+pricng = { input_per_million_tokens = 3.0, output_per_million_tokens = 15.0 }
+
+[autopilot]
+max_iterations = 20
+"#;
+
+        // WHEN
+        let (issues, config) = check_contents::<Config>(ConfigFormat::Toml, contents);
+
+        // THEN
+        assert!(config.is_some());
+        assert_snapshot!(report(issues), @r#"
+        local config (.agx/config.local.toml): 2 error(s), 0 warning(s)
+          - line 3: error: unknown key "tool_failure_reminder"
+          - line 7: error: unknown key "models.claude-sonnet-4-5.pricng"
+        "#);
+    }
+
+    #[test]
+    fn invalid_values_and_overrides_of_team_config_are_reported() {
+        // GIVEN
+        let team: Config = ConfigFormat::Json
+            .parse(r#"{ "workspace": "packages/api", "autopilot": { "max_iterations": 20 } }"#)
+            .expect("team config should be valid");
+        let contents = r#"{
+  "approved_commands": [{ "binary": "cargo test" }],
+  "workspace": "packages/web",
+  "autopilot": {
+    "max_iterations": 20,
+    "protected_paths": ["src/**/[a.rs"]
+  },
+  "webhooks": [{ "url": "hooks.slack.com/services/T000/B000/XXXX", "kind": "slack" }],
+  "debug_server": {
+    "token": "secret"
+  }
+}"#;
+        let (mut issues, local) = check_contents::<Config>(ConfigFormat::Json, contents);
+        let local = local.expect("local config should've been parsed");
+
+        // WHEN
+        issues.extend(validate_config(&local, contents));
+        issues.extend(overrides(&team, &local, contents));
+
+        // THEN
+        assert_snapshot!(report(issues), @r#"
+        local config (.agx/config.local.toml): 4 error(s), 2 warning(s)
+          - line 2: error: invalid approved command "cargo test .*": binary contains whitespace; it should only be the command's first word
+          - line 3: warning: "workspace" overrides the team config's value
+          - line 4: warning: "autopilot" overrides the team config's value
+          - line 6: error: invalid protected path "src/**/[a.rs": unclosed character class; missing ']'
+          - line 8: error: invalid webhook URL "hooks.slack.com/services/T000/B000/XXXX"; expected an http(s) URL
+          - line 10: error: the debug server's token needs to be at least 16 characters long
+        "#);
+    }
+
+    #[test]
+    fn mcp_servers_in_team_config_are_reported() {
+        // GIVEN
+        let contents = r#"
+workspace = "packages/api"
+
+[mcp_servers.tracker]
+command = "./scripts/tracker-mcp.sh"
+"#;
+        let (_, team) = check_contents::<Config>(ConfigFormat::Toml, contents);
+        let team = team.expect("team config should've been parsed");
+
+        // WHEN
+        let issues = validate_team_config(&team, contents);
+
+        // THEN
+        let issues = issues
+            .iter()
+            .map(|i| format!("{:?} on line {:?}: {}", i.severity, i.line, i.message))
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_snapshot!(issues, @r"
+        Warning on line Some(4): MCP servers in the team config aren't started, since it's committed; declare them in the local or user config instead
+        ");
+    }
+
+    #[test]
+    fn settings_ignored_in_team_config_are_reported() {
+        // GIVEN
+        let contents = r#"
+workspace = "packages/api"
+
+[command_env]
+BASH_ENV = "./scripts/env.sh"
+
+[forge]
+kind = "github"
+api_url = "https://forge.example.com/api"
+
+[local_model]
+base_url = "https://models.example.com/v1"
+model = "qwen3-coder"
+"#;
+        let (_, team) = check_contents::<Config>(ConfigFormat::Toml, contents);
+        let team = team.expect("team config should've been parsed");
+
+        // WHEN
+        let issues = validate_team_config(&team, contents);
+
+        // THEN
+        let issues = issues
+            .iter()
+            .map(|i| format!("{:?} on line {:?}: {}", i.severity, i.line, i.message))
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_snapshot!(issues, @r#"
+        Warning on line Some(4): "command_env" in the team config is ignored, since it's committed; set it in the local or user config instead
+        Warning on line Some(9): "forge.api_url" in the team config is ignored, since it's committed; set it in the local or user config instead
+        Warning on line Some(11): "local_model" in the team config is ignored, since it's committed; set it in the local or user config instead
+        "#);
+    }
+}
@@ -0,0 +1,581 @@
+//! Reading and writing agx's config and prompt templates (from `.agx/`).
+//!
+//! Config is layered, in order of increasing precedence:
+//!
+//! 1. `config.toml` in agx's config directory (eg. `~/.config/agx/config.toml`): user defaults
+//!    that apply across projects.
+//! 2. `.agx/config.toml`: team defaults, meant to be committed.
+//! 3. `.agx/config.local.toml`: personal settings (eg. commands approved during sessions), kept
+//!    out of git.
+//!
+//! Fields set in the local config replace the team's, settings for a model replace the team's
+//! for the same model, and commands approved in either are allowed. agx only ever writes to the
+//! local config.
+//!
+//! Since the team config comes along with any repository that's cloned, settings that start
+//! processes, or decide where requests (and the credentials sent with them) go, are ignored in
+//! it: MCP servers, formatters, linters, command environment variables, the forge's API URL,
+//! the debug server, webhooks, HTTP settings, and the local model.
+//!
+//! Each of these can be written in TOML or JSON; the format is picked based on the file's
+//! extension (`config.json` and `config.local.json` work as they always have). A config can't
+//! exist in both formats at once.
+
+mod check;
+
+pub use check::*;
+
+use crate::domain::{CmdPattern, Config, Template, UserConfig};
+use anyhow::Context;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::path::{Path, PathBuf};
+
+const AGX_DIR: &str = ".agx";
+const TEAM_CONFIG_FILE_STEM: &str = "config";
+const LOCAL_CONFIG_FILE_STEM: &str = "config.local";
+const USER_CONFIG_FILE_STEM: &str = "config";
+const INDEX_FILE: &str = "index.json";
+const TEMPLATES_DIR: &str = "templates";
+const GITIGNORE_FILE: &str = ".gitignore";
+/// Files agx writes to `.agx/` that are specific to a machine (approvals, the semantic index),
+/// and shouldn't be committed.
+const IGNORED_PATTERNS: [&str; 2] = ["*.local.*", INDEX_FILE];
+
+/// Formats config files can be written in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ConfigFormat {
+    Toml,
+    Json,
+}
+
+impl ConfigFormat {
+    /// In order of preference, for files that don't exist yet.
+    const ALL: [ConfigFormat; 2] = [ConfigFormat::Toml, ConfigFormat::Json];
+
+    fn extension(self) -> &'static str {
+        match self {
+            ConfigFormat::Toml => "toml",
+            ConfigFormat::Json => "json",
+        }
+    }
+
+    fn of(path: &Path) -> anyhow::Result<Self> {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default();
+
+        Self::ALL
+            .into_iter()
+            .find(|f| f.extension() == extension)
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    r#"unsupported config format: "{extension}"; supported formats: [toml, json]"#
+                )
+            })
+    }
+
+    fn parse<T>(self, contents: &str) -> anyhow::Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        match self {
+            ConfigFormat::Toml => toml::from_str(contents).context("couldn't parse TOML"),
+            ConfigFormat::Json => serde_json::from_str(contents).context("couldn't parse JSON"),
+        }
+    }
+
+    fn serialize<T>(self, value: &T) -> anyhow::Result<String>
+    where
+        T: Serialize,
+    {
+        match self {
+            ConfigFormat::Toml => {
+                toml::to_string_pretty(value).context("couldn't serialize to TOML")
+            }
+            ConfigFormat::Json => {
+                serde_json::to_string_pretty(value).context("couldn't serialize to JSON")
+            }
+        }
+    }
+}
+
+pub fn get_index_file_path() -> PathBuf {
+    PathBuf::from(AGX_DIR).join(INDEX_FILE)
+}
+
+/// Returns the team config layered with the local one.
+pub async fn get_project_config() -> anyhow::Result<Config> {
+    let config_file_path = find_config_file(Path::new(AGX_DIR), TEAM_CONFIG_FILE_STEM).await?;
+
+    let team_config: Config = get_config(&config_file_path).await.with_context(|| {
+        format!(
+            r#"couldn't get team config (from "{}")"#,
+            config_file_path.to_string_lossy()
+        )
+    })?;
+
+    Ok(layer_project_config(team_config, get_local_config().await?))
+}
+
+/// Layers the local config over the team's, leaving out settings only read from the local
+/// (and user) config; see the module's docs.
+fn layer_project_config(mut team: Config, local: Config) -> Config {
+    team.mcp_servers.clear();
+    strip_local_only_settings(&mut team);
+    team.layered_with(local)
+}
+
+/// Clears the settings (other than MCP servers) that are ignored in the team config. Returns
+/// the keys of the ones that were set.
+fn strip_local_only_settings(team: &mut Config) -> Vec<&'static [&'static str]> {
+    let forge_api_url = team.forge.as_mut().and_then(|f| f.api_url.take());
+    let settings: [(&'static [&'static str], bool); 8] = [
+        (
+            &["formatters"],
+            !std::mem::take(&mut team.formatters).is_empty(),
+        ),
+        (&["linters"], !std::mem::take(&mut team.linters).is_empty()),
+        (
+            &["command_env"],
+            !std::mem::take(&mut team.command_env).is_empty(),
+        ),
+        (&["forge", "api_url"], forge_api_url.is_some()),
+        (&["debug_server"], team.debug_server.take().is_some()),
+        (
+            &["webhooks"],
+            !std::mem::take(&mut team.webhooks).is_empty(),
+        ),
+        (&["http"], team.http.take().is_some()),
+        (&["local_model"], team.local_model.take().is_some()),
+    ];
+
+    settings
+        .into_iter()
+        .filter(|(_, was_set)| *was_set)
+        .map(|(key, _)| key)
+        .collect()
+}
+
+/// Returns the user level config from agx's directory within `config_dir`, or the default one
+/// if there isn't any.
+pub async fn get_user_config(config_dir: &Path) -> anyhow::Result<UserConfig> {
+    let config_file_path = find_config_file(&config_dir.join("agx"), USER_CONFIG_FILE_STEM).await?;
+
+    get_config(&config_file_path).await.with_context(|| {
+        format!(
+            r#"couldn't get user config (from "{}")"#,
+            config_file_path.to_string_lossy()
+        )
+    })
+}
+
+pub async fn get_local_config() -> anyhow::Result<Config> {
+    let config_file_path = find_config_file(Path::new(AGX_DIR), LOCAL_CONFIG_FILE_STEM).await?;
+
+    let config = get_config(&config_file_path).await.with_context(|| {
+        format!(
+            r#"couldn't get local config (from "{}")"#,
+            config_file_path.to_string_lossy()
+        )
+    })?;
+
+    Ok(config)
+}
+
+pub async fn get_template(name: &str) -> anyhow::Result<Template> {
+    let template_path = PathBuf::from(AGX_DIR)
+        .join(TEMPLATES_DIR)
+        .join(format!("{name}.md"));
+
+    let contents = tokio::fs::read_to_string(&template_path)
+        .await
+        .with_context(|| {
+            format!(
+                r#"couldn't read template "{name}" (from "{}")"#,
+                template_path.to_string_lossy()
+            )
+        })?;
+
+    contents
+        .parse()
+        .with_context(|| format!(r#"couldn't parse template "{name}""#))
+}
+
+/// Returns the path of the config file named `stem` in `dir`, in whichever format it exists in;
+/// or the path it'd have in the preferred format if it doesn't exist.
+async fn find_config_file(dir: &Path, stem: &str) -> anyhow::Result<PathBuf> {
+    let mut existing = Vec::new();
+    for format in ConfigFormat::ALL {
+        let path = dir.join(format!("{stem}.{}", format.extension()));
+        if tokio::fs::try_exists(&path)
+            .await
+            .with_context(|| format!(r#"couldn't check if "{}" exists"#, path.to_string_lossy()))?
+        {
+            existing.push(path);
+        }
+    }
+
+    match existing.as_slice() {
+        [] => Ok(dir.join(format!("{stem}.{}", ConfigFormat::ALL[0].extension()))),
+        [path] => Ok(path.clone()),
+        paths => anyhow::bail!(
+            "config exists in more than one format ({}); keep only one of them",
+            paths
+                .iter()
+                .map(|p| format!(r#""{}""#, p.to_string_lossy()))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+async fn get_config<T>(path: &Path) -> anyhow::Result<T>
+where
+    T: DeserializeOwned + Default,
+{
+    let format = ConfigFormat::of(path)?;
+    match tokio::fs::read_to_string(path).await {
+        Ok(contents) => format.parse(&contents),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(T::default()),
+        Err(e) => Err(e).context("couldn't read file"),
+    }
+}
+
+/// Saves the local config, in the format it's already in (TOML, if it doesn't exist yet).
+pub async fn save_local_config(config: &Config) -> anyhow::Result<()> {
+    let config_file_path = find_config_file(Path::new(AGX_DIR), LOCAL_CONFIG_FILE_STEM).await?;
+    let contents = ConfigFormat::of(&config_file_path)?.serialize(config)?;
+
+    save_config(&config_file_path, &contents)
+        .await
+        .with_context(|| {
+            format!(
+                r#"couldn't save local config (to "{}")"#,
+                config_file_path.to_string_lossy()
+            )
+        })?;
+
+    ensure_gitignore().await
+}
+
+/// Adds a command pattern to the ones approved in the local config.
+pub async fn save_approved_command(pattern: &CmdPattern) -> anyhow::Result<()> {
+    let mut config = get_local_config().await?;
+    config.approved_commands.insert(pattern);
+
+    save_local_config(&config).await
+}
+
+/// Creates (or updates) `.agx/.gitignore` so that the machine specific files agx writes to
+/// `.agx/` aren't committed by accident.
+pub async fn ensure_gitignore() -> anyhow::Result<()> {
+    let gitignore_path = PathBuf::from(AGX_DIR).join(GITIGNORE_FILE);
+
+    let existing = match tokio::fs::read_to_string(&gitignore_path).await {
+        Ok(contents) => Some(contents),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => {
+            return Err(e).with_context(|| {
+                format!(r#"couldn't read "{}""#, gitignore_path.to_string_lossy())
+            });
+        }
+    };
+
+    let Some(contents) = gitignore_with_ignored_patterns(existing.as_deref()) else {
+        return Ok(());
+    };
+
+    save_config(&gitignore_path, &contents)
+        .await
+        .with_context(|| format!(r#"couldn't update "{}""#, gitignore_path.to_string_lossy()))
+}
+
+/// Returns the contents of the gitignore file with agx's patterns added, or `None` if it
+/// already has all of them.
+fn gitignore_with_ignored_patterns(existing: Option<&str>) -> Option<String> {
+    let existing = existing.unwrap_or_default();
+    let missing = IGNORED_PATTERNS
+        .iter()
+        .filter(|pattern| !existing.lines().any(|line| line.trim() == **pattern))
+        .collect::<Vec<_>>();
+
+    if missing.is_empty() {
+        return None;
+    }
+
+    let mut contents = existing.to_string();
+    if !contents.is_empty() && !contents.ends_with('\n') {
+        contents.push('\n');
+    }
+    if contents.is_empty() {
+        contents.push_str("# machine specific files written by agx\n");
+    }
+    for pattern in missing {
+        contents.push_str(pattern);
+        contents.push('\n');
+    }
+
+    Some(contents)
+}
+
+async fn save_config<P>(path: P, contents: &str) -> anyhow::Result<()>
+where
+    P: AsRef<Path>,
+{
+    if let Some(parent) = path.as_ref().parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .context("couldn't create directory")?;
+    }
+
+    tokio::fs::write(path, contents)
+        .await
+        .context("couldn't write contents to file")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::ForgeKind;
+    use insta::assert_snapshot;
+    use std::collections::BTreeMap;
+    use std::str::FromStr;
+
+    #[test]
+    fn gitignore_is_created_with_ignored_patterns() {
+        // GIVEN
+        // WHEN
+        let contents = gitignore_with_ignored_patterns(None).expect("contents should've changed");
+
+        // THEN
+        assert_snapshot!(contents, @r"
+        # machine specific files written by agx
+        *.local.*
+        index.json
+        ");
+    }
+
+    #[test]
+    fn only_missing_patterns_are_added_to_an_existing_gitignore() {
+        // GIVEN
+        let existing = "scratch/\n*.local.*";
+
+        // WHEN
+        let contents =
+            gitignore_with_ignored_patterns(Some(existing)).expect("contents should've changed");
+        let unchanged = gitignore_with_ignored_patterns(Some(&contents));
+
+        // THEN
+        assert_snapshot!(contents, @r"
+        scratch/
+        *.local.*
+        index.json
+        ");
+        assert!(unchanged.is_none());
+    }
+
+    #[test]
+    fn config_written_as_toml_can_be_read_back() -> anyhow::Result<()> {
+        // GIVEN
+        let mut config: Config = ConfigFormat::Json.parse(
+            r#"{
+                "workspace": "packages/api",
+                "models": {
+                    "claude-sonnet-4-5": { "tool_calling": "text" }
+                }
+            }"#,
+        )?;
+        config
+            .approved_commands
+            .insert(&CmdPattern::from_str("cargo test").expect("pattern should be valid"));
+        let format = ConfigFormat::of(Path::new(".agx/config.local.toml"))?;
+
+        // WHEN
+        let contents = format.serialize(&config)?;
+        let read_back: Config = format.parse(&contents)?;
+
+        // THEN
+        assert_eq!(format, ConfigFormat::Toml);
+        assert_snapshot!(contents, @r#"
+        workspace = "packages/api"
+
+        [[approved_commands]]
+        binary = "cargo"
+        first_arg = "test"
+
+        [models.claude-sonnet-4-5]
+        tool_calling = "text"
+        "#);
+        assert_eq!(read_back.workspace.as_deref(), Some("packages/api"));
+        assert!(read_back.approved_commands.is_approved("cargo test --all"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn mcp_servers_are_only_read_from_the_local_config() -> anyhow::Result<()> {
+        // GIVEN
+        let team: Config = ConfigFormat::Toml.parse(
+            r#"
+workspace = "packages/api"
+
+[mcp_servers.tracker]
+command = "./scripts/tracker-mcp.sh"
+"#,
+        )?;
+        let local: Config = ConfigFormat::Toml.parse(
+            r#"
+[mcp_servers.postgres]
+command = "npx"
+args = ["-y", "@modelcontextprotocol/server-postgres"]
+"#,
+        )?;
+
+        // WHEN
+        let config = layer_project_config(team, local);
+
+        // THEN
+        assert_eq!(config.workspace.as_deref(), Some("packages/api"));
+        assert_eq!(
+            config.mcp_servers.keys().collect::<Vec<_>>(),
+            vec!["postgres"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn settings_that_start_processes_or_route_requests_are_only_read_from_the_local_config()
+    -> anyhow::Result<()> {
+        // GIVEN
+        let team: Config = ConfigFormat::Toml.parse(
+            r#"
+workspace = "packages/api"
+webhooks = [{ url = "https://example.com/team-hook" }]
+
+[command_env]
+BASH_ENV = "./scripts/env.sh"
+
+[forge]
+kind = "github"
+api_url = "https://forge.example.com/api"
+
+[debug_server]
+address = "0.0.0.0:4880"
+token = "0123456789abcdef"
+
+[http]
+connect_timeout_secs = 5
+
+[local_model]
+base_url = "https://models.example.com/v1"
+model = "qwen3-coder"
+"#,
+        )?;
+        let local: Config = ConfigFormat::Toml.parse(
+            r#"
+[command_env]
+RUST_BACKTRACE = "1"
+"#,
+        )?;
+
+        // WHEN
+        let config = layer_project_config(team, local);
+
+        // THEN
+        assert_eq!(config.workspace.as_deref(), Some("packages/api"));
+        assert_eq!(
+            config.command_env.keys().collect::<Vec<_>>(),
+            vec!["RUST_BACKTRACE"]
+        );
+        assert!(config.webhooks.is_empty());
+        assert_eq!(
+            config
+                .forge
+                .as_ref()
+                .map(|f| (f.kind, f.api_url.as_deref())),
+            Some((Some(ForgeKind::GitHub), None))
+        );
+        assert!(config.debug_server.is_none());
+        assert!(config.http.is_none());
+        assert!(config.local_model.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn formatters_are_only_read_from_the_local_config() -> anyhow::Result<()> {
+        // GIVEN
+        let team: Config = ConfigFormat::Toml.parse(
+            r#"
+[formatters]
+rs = "rustfmt --edition 2024"
+py = "./scripts/format.sh"
+"#,
+        )?;
+        let local: Config = ConfigFormat::Toml.parse(
+            r#"
+[formatters]
+py = "ruff format -q"
+"#,
+        )?;
+
+        // WHEN
+        let config = layer_project_config(team, local);
+
+        // THEN
+        assert_eq!(
+            config.formatters,
+            BTreeMap::from([("py".to_string(), "ruff format -q".to_string())])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn linters_are_only_read_from_the_local_config() -> anyhow::Result<()> {
+        // GIVEN
+        let team: Config = ConfigFormat::Toml.parse(
+            r#"
+[linters]
+py = "./scripts/lint.sh"
+"#,
+        )?;
+        let local: Config = ConfigFormat::Toml.parse(
+            r#"
+[linters]
+rs = "cargo clippy --quiet"
+"#,
+        )?;
+
+        // WHEN
+        let config = layer_project_config(team, local);
+
+        // THEN
+        assert_eq!(
+            config.linters,
+            BTreeMap::from([("rs".to_string(), "cargo clippy --quiet".to_string())])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn config_in_an_unsupported_format_is_rejected() {
+        // GIVEN
+        // WHEN
+        let result = ConfigFormat::of(Path::new(".agx/config.yaml"));
+
+        // THEN
+        assert_eq!(
+            result.err().map(|e| e.to_string()),
+            Some(
+                r#"unsupported config format: "yaml"; supported formats: [toml, json]"#.to_string()
+            )
+        );
+    }
+}
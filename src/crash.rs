@@ -0,0 +1,189 @@
+use agx_core::domain::DebugEventReceiver;
+use chrono::Local;
+use std::backtrace::Backtrace;
+use std::fmt::Write;
+use std::panic::PanicHookInfo;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Number of recent debug events included in crash reports (when the debug server isn't
+/// running, only these many are kept around).
+pub const CRASH_REPORT_EVENTS: usize = 20;
+
+/// Environment variables whose names contain these are treated as secrets, and their values are
+/// redacted from crash reports.
+const SECRET_NAME_PARTS: [&str; 5] = ["KEY", "TOKEN", "SECRET", "PASSWORD", "CREDENTIAL"];
+/// Shorter values are too likely to appear in reports by chance to be worth redacting.
+const MIN_SECRET_LEN: usize = 8;
+
+/// What's known about the session when a panic happens.
+struct CrashContext {
+    provider: Option<String>,
+    model: Option<String>,
+    events: Option<DebugEventReceiver>,
+}
+
+static CONTEXT: Mutex<CrashContext> = Mutex::new(CrashContext {
+    provider: None,
+    model: None,
+    events: None,
+});
+
+/// Writes a crash report to `crashes_dir` whenever agx panics, and prints its path, so that bug
+/// reports don't need users to reconstruct what happened. Reports are redacted: they include
+/// summaries of recent debug events rather than their contents, and the values of environment
+/// variables that look like secrets are removed.
+pub fn install_panic_hook(crashes_dir: PathBuf) {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+
+        let report = redact(&crash_report(info), &secrets());
+        match write_report(&crashes_dir, &report) {
+            Ok(path) => eprintln!(
+                "\nagx crashed; a crash report was written to {}\nplease attach it when reporting the bug at https://github.com/dhth/agx/issues",
+                path.to_string_lossy()
+            ),
+            Err(e) => eprintln!("\nagx crashed, and the crash report couldn't be written: {e}"),
+        }
+    }));
+}
+
+pub fn set_session_info(provider: impl Into<String>, model: impl Into<String>) {
+    let mut context = CONTEXT.lock().unwrap_or_else(|e| e.into_inner());
+    context.provider = Some(provider.into());
+    context.model = Some(model.into());
+}
+
+pub fn set_debug_events(events: DebugEventReceiver) {
+    let mut context = CONTEXT.lock().unwrap_or_else(|e| e.into_inner());
+    context.events = Some(events);
+}
+
+fn crash_report(info: &PanicHookInfo<'_>) -> String {
+    let context = CONTEXT.lock().unwrap_or_else(|e| e.into_inner());
+    let message = info
+        .payload()
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| info.payload().downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "(no message)".to_string());
+    let unknown = || "unknown".to_string();
+
+    let mut report = String::new();
+    _ = writeln!(report, "agx version: {}", env!("CARGO_PKG_VERSION"));
+    _ = writeln!(
+        report,
+        "platform: {} ({})",
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    );
+    _ = writeln!(
+        report,
+        "provider: {}",
+        context.provider.clone().unwrap_or_else(unknown)
+    );
+    _ = writeln!(
+        report,
+        "model: {}",
+        context.model.clone().unwrap_or_else(unknown)
+    );
+    _ = writeln!(report, "time: {}", Local::now().to_rfc3339());
+    _ = writeln!(
+        report,
+        "thread: {}",
+        std::thread::current().name().unwrap_or("unnamed")
+    );
+    _ = writeln!(report, "\npanic: {message}");
+    if let Some(location) = info.location() {
+        _ = writeln!(report, "location: {location}");
+    }
+
+    _ = writeln!(
+        report,
+        "\nrecent events (last {CRASH_REPORT_EVENTS}, oldest first):"
+    );
+    let events = context
+        .events
+        .as_ref()
+        .map(|rx| rx.subscribe(None).0)
+        .unwrap_or_default();
+    if events.is_empty() {
+        _ = writeln!(report, "(none)");
+    }
+    let skip = events.len().saturating_sub(CRASH_REPORT_EVENTS);
+    for event in events.iter().skip(skip) {
+        _ = writeln!(report, "- {}", event.event.summary());
+    }
+
+    _ = writeln!(report, "\nbacktrace:\n{}", Backtrace::force_capture());
+
+    report
+}
+
+fn secrets() -> Vec<String> {
+    // vars() panics on variables that aren't valid unicode, which would abort the process here
+    secrets_in(
+        std::env::vars_os().filter_map(|(name, value)| {
+            Some((name.into_string().ok()?, value.into_string().ok()?))
+        }),
+    )
+}
+
+/// Returns the values of the environment variables given that look like secrets.
+fn secrets_in(vars: impl Iterator<Item = (String, String)>) -> Vec<String> {
+    vars.filter(|(name, value)| {
+        let name = name.to_uppercase();
+        value.len() >= MIN_SECRET_LEN && SECRET_NAME_PARTS.iter().any(|p| name.contains(p))
+    })
+    .map(|(_, value)| value)
+    .collect()
+}
+
+fn redact(report: &str, secrets: &[String]) -> String {
+    secrets.iter().fold(report.to_string(), |report, secret| {
+        report.replace(secret, "[redacted]")
+    })
+}
+
+fn write_report(crashes_dir: &Path, report: &str) -> std::io::Result<PathBuf> {
+    std::fs::create_dir_all(crashes_dir)?;
+    let path = crashes_dir.join(format!(
+        "crash-{}.txt",
+        Local::now().format("%Y-%m-%d-%H-%M-%S")
+    ));
+    std::fs::write(&path, report)?;
+
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::assert_snapshot;
+
+    #[test]
+    fn values_of_secret_looking_variables_are_redacted() {
+        // GIVEN
+        let vars = [
+            ("ANTHROPIC_API_KEY", "sk-ant-0123456789"),
+            ("GITHUB_TOKEN", "ghp_abcdefghij"),
+            ("SHORT_TOKEN", "main"),
+            ("EDITOR", "/usr/local/bin/nvim"),
+        ]
+        .map(|(name, value)| (name.to_string(), value.to_string()));
+        let report = "panic: request failed with key sk-ant-0123456789
+- tool_call bash (gh api --header 'Authorization: token ghp_abcdefghij')
+- on branch main, editor /usr/local/bin/nvim";
+
+        // WHEN
+        let redacted = redact(report, &secrets_in(vars.into_iter()));
+
+        // THEN
+        assert_snapshot!(redacted, @r"
+        panic: request failed with key [redacted]
+        - tool_call bash (gh api --header 'Authorization: token [redacted]')
+        - on branch main, editor /usr/local/bin/nvim
+        ");
+    }
+}
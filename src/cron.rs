@@ -0,0 +1,155 @@
+use crate::cli::ProviderArgs;
+use crate::tasks::run_auto;
+use agx_core::domain::CronManifest;
+use anyhow::Context;
+use chrono::{DateTime, Local, Utc};
+use colored::Colorize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+const STATE_FILE: &str = "state.json";
+
+/// When each job last ran, keyed by the job's name.
+type CronState = BTreeMap<String, DateTime<Utc>>;
+
+/// Runs the jobs in a manifest that are due (or all of them), one after another, each via
+/// `agx auto` in a worktree of its own; pull requests are opened for the changes jobs make. A
+/// job counts as run once it starts, whether it succeeds or not, so that failing jobs aren't
+/// retried on every invocation.
+pub async fn run_cron(
+    manifest_path: &Path,
+    all: bool,
+    project_dir: &Path,
+    project_log_dir: &Path,
+    provider: &ProviderArgs,
+) -> anyhow::Result<()> {
+    let manifest = read_manifest(manifest_path).await?;
+    let cron_dir = project_log_dir.join("cron");
+    let mut state = read_state(&cron_dir).await?;
+
+    let now = Utc::now();
+    let due = manifest
+        .jobs
+        .iter()
+        .filter(|job| all || job.is_due(state.get(&job.name).copied(), now))
+        .collect::<Vec<_>>();
+    if due.is_empty() {
+        println!("{}", "no jobs are due".blue());
+        return Ok(());
+    }
+
+    let agx = std::env::current_exe().context("couldn't determine agx's executable")?;
+    let mut failed = 0;
+    for job in &due {
+        let run_id = Local::now().format("%Y-%m-%d-%H-%M-%S").to_string();
+        let branch = format!("agx/cron/{}-{run_id}", job.name);
+        let log_dir = cron_dir.join(&job.name);
+        tokio::fs::create_dir_all(&log_dir).await.with_context(|| {
+            format!(
+                "couldn't create directory for job logs: {}",
+                log_dir.to_string_lossy()
+            )
+        })?;
+        let log_path = log_dir.join(format!("{run_id}.log"));
+
+        state.insert(job.name.clone(), Utc::now());
+        write_state(&cron_dir, &state).await?;
+
+        println!("{}", format!("started {}", job.name).blue());
+        match run_auto(
+            &agx,
+            provider,
+            project_dir,
+            &job.prompt,
+            &branch,
+            true,
+            &log_path,
+        )
+        .await
+        {
+            Ok(()) => println!(
+                "{}",
+                format!("✓ {} (see {})", job.name, log_path.to_string_lossy()).green()
+            ),
+            Err(e) => {
+                failed += 1;
+                println!(
+                    "{}",
+                    format!("✗ {}: {e:#} (see {})", job.name, log_path.to_string_lossy()).red()
+                );
+            }
+        }
+    }
+
+    if failed > 0 {
+        anyhow::bail!("{failed} of {} jobs failed", due.len());
+    }
+
+    Ok(())
+}
+
+/// Prints the jobs in a manifest, along with when they last ran and when they're due next.
+pub async fn list_cron(manifest_path: &Path, project_log_dir: &Path) -> anyhow::Result<()> {
+    let manifest = read_manifest(manifest_path).await?;
+    let state = read_state(&project_log_dir.join("cron")).await?;
+
+    let now = Utc::now();
+    let format_time =
+        |t: DateTime<Utc>| t.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string();
+    let name_width = manifest
+        .jobs
+        .iter()
+        .map(|j| j.name.len())
+        .max()
+        .unwrap_or_default();
+
+    for job in &manifest.jobs {
+        let last_run = state.get(&job.name).copied();
+        let next_run = match job.next_run(last_run) {
+            Some(t) if t > now => format!("due {}", format_time(t)),
+            _ => "due now".to_string(),
+        };
+        let last_run = match last_run {
+            Some(t) => format!("last ran {}", format_time(t)),
+            None => "never ran".to_string(),
+        };
+
+        println!("{:<name_width$}  {last_run:<25}  {next_run}", job.name);
+    }
+
+    Ok(())
+}
+
+async fn read_manifest(path: &Path) -> anyhow::Result<CronManifest> {
+    let contents = tokio::fs::read_to_string(path)
+        .await
+        .with_context(|| format!("couldn't read jobs from {}", path.to_string_lossy()))?;
+
+    contents
+        .parse()
+        .with_context(|| format!("couldn't parse jobs in {}", path.to_string_lossy()))
+}
+
+async fn read_state(cron_dir: &Path) -> anyhow::Result<CronState> {
+    let path = cron_dir.join(STATE_FILE);
+    let contents = match tokio::fs::read_to_string(&path).await {
+        Ok(c) => c,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(CronState::new()),
+        Err(e) => {
+            return Err(e).with_context(|| format!("couldn't read {}", path.to_string_lossy()));
+        }
+    };
+
+    serde_json::from_str(&contents)
+        .with_context(|| format!("{} is invalid", path.to_string_lossy()))
+}
+
+async fn write_state(cron_dir: &Path, state: &CronState) -> anyhow::Result<()> {
+    let path = cron_dir.join(STATE_FILE);
+    tokio::fs::create_dir_all(cron_dir)
+        .await
+        .with_context(|| format!("couldn't create {}", cron_dir.to_string_lossy()))?;
+    tokio::fs::write(&path, serde_json::to_string_pretty(state)?)
+        .await
+        .with_context(|| format!("couldn't write {}", path.to_string_lossy()))
+}
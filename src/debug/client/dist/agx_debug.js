@@ -1021,6 +1021,33 @@ function index_map_loop(loop$list, loop$fun, loop$index, loop$acc) {
     }
   }
 }
+function filter_loop(loop$list, loop$fun, loop$acc) {
+  while (true) {
+    let list = loop$list;
+    let fun = loop$fun;
+    let acc = loop$acc;
+    if (list instanceof Empty) {
+      return reverse(acc);
+    } else {
+      let first$1 = list.head;
+      let rest$1 = list.tail;
+      let _block;
+      let $ = fun(first$1);
+      if ($) {
+        _block = prepend(first$1, acc);
+      } else {
+        _block = acc;
+      }
+      let new_acc = _block;
+      loop$list = rest$1;
+      loop$fun = fun;
+      loop$acc = new_acc;
+    }
+  }
+}
+function filter(list, predicate) {
+  return filter_loop(list, predicate, toList([]));
+}
 function index_map(list, fun) {
   return index_map_loop(list, fun, 0, toList([]));
 }
@@ -5156,9 +5183,10 @@ function stringify(value) {
 
 // build/dev/javascript/agx_debug/agx_debug/types.mjs
 class Controls extends CustomType {
-  constructor(scroll_to_new_event) {
+  constructor(scroll_to_new_event, errors_only) {
     super();
     this.scroll_to_new_event = scroll_to_new_event;
+    this.errors_only = errors_only;
   }
 }
 class Model extends CustomType {
@@ -5176,6 +5204,8 @@ class EventReceived extends CustomType {
 }
 class ToggleScrollToNewEvent extends CustomType {
 }
+class ToggleErrorsOnly extends CustomType {
+}
 class ScrollToEvent extends CustomType {
   constructor($0) {
     super();
@@ -5234,6 +5264,47 @@ class Interrupted extends CustomType {
 }
 class NewSession extends CustomType {
 }
+class ApprovalRequested extends CustomType {
+  constructor(tool_call_id, tool_call, details) {
+    super();
+    this.tool_call_id = tool_call_id;
+    this.tool_call = tool_call;
+    this.details = details;
+  }
+}
+class ErrorEvent extends CustomType {
+  constructor(category, message) {
+    super();
+    this.category = category;
+    this.message = message;
+  }
+}
+class TurnDiffStat extends CustomType {
+  constructor(turn, files, lines_added, lines_removed) {
+    super();
+    this.turn = turn;
+    this.files = files;
+    this.lines_added = lines_added;
+    this.lines_removed = lines_removed;
+  }
+}
+class FileDiffStat extends CustomType {
+  constructor(path, change, lines_added, lines_removed) {
+    super();
+    this.path = path;
+    this.change = change;
+    this.lines_added = lines_added;
+    this.lines_removed = lines_removed;
+  }
+}
+class ApprovalResolved extends CustomType {
+  constructor(tool_call_id, decision, waited_ms) {
+    super();
+    this.tool_call_id = tool_call_id;
+    this.decision = decision;
+    this.waited_ms = waited_ms;
+  }
+}
 class ToolCallData extends CustomType {
   constructor(id2, call_id, function$, signature) {
     super();
@@ -5376,6 +5447,53 @@ function turn_complete_payload_decoder() {
     return success(new TurnComplete(history));
   });
 }
+function approval_requested_payload_decoder() {
+  return field("tool_call_id", string2, (tool_call_id) => {
+    return field("tool_call", string2, (tool_call) => {
+      return optional_field("details", new None, optional(string2), (details) => {
+        return success(new ApprovalRequested(tool_call_id, tool_call, details));
+      });
+    });
+  });
+}
+function approval_resolved_payload_decoder() {
+  return field("tool_call_id", string2, (tool_call_id) => {
+    return field("decision", string2, (decision) => {
+      return field("waited_ms", int2, (waited_ms) => {
+        return success(new ApprovalResolved(tool_call_id, decision, waited_ms));
+      });
+    });
+  });
+}
+function error_payload_decoder() {
+  return field("category", string2, (category) => {
+    return field("message", string2, (message) => {
+      return success(new ErrorEvent(category, message));
+    });
+  });
+}
+function file_diff_stat_decoder() {
+  return field("path", string2, (path) => {
+    return field("change", string2, (change) => {
+      return field("lines_added", optional(int2), (lines_added) => {
+        return field("lines_removed", optional(int2), (lines_removed) => {
+          return success(new FileDiffStat(path, change, lines_added, lines_removed));
+        });
+      });
+    });
+  });
+}
+function turn_diff_stat_payload_decoder() {
+  return field("turn", int2, (turn) => {
+    return field("files", list2(file_diff_stat_decoder()), (files) => {
+      return field("lines_added", int2, (lines_added) => {
+        return field("lines_removed", int2, (lines_removed) => {
+          return success(new TurnDiffStat(turn, files, lines_added, lines_removed));
+        });
+      });
+    });
+  });
+}
 function tool_result_event_payload_decoder() {
   return field("id", string2, (id2) => {
     return optional_field("call_id", new None, optional(string2), (call_id) => {
@@ -5504,6 +5622,14 @@ function debug_event_payload_decoder() {
       return interrupted_payload_decoder();
     } else if (kind === "new_session") {
       return new_session_payload_decoder();
+    } else if (kind === "approval_requested") {
+      return approval_requested_payload_decoder();
+    } else if (kind === "approval_resolved") {
+      return approval_resolved_payload_decoder();
+    } else if (kind === "error") {
+      return error_payload_decoder();
+    } else if (kind === "turn_diff_stat") {
+      return turn_diff_stat_payload_decoder();
     } else {
       return failure(new LlmRequest(new UserMessage(toList([])), ""), "unknown payload kind");
     }
@@ -5535,7 +5661,12 @@ function scroll_to_element(id2) {
 
 // build/dev/javascript/agx_debug/agx_debug/ffi/sse.mjs
 function subscribe_sse(url, on_message) {
-  const source = new EventSource(url);
+  const resolved = new URL(url, window.location.href);
+  const token = new URLSearchParams(window.location.search).get("token");
+  if (token) {
+    resolved.searchParams.set("token", token);
+  }
+  const source = new EventSource(resolved);
   source.onmessage = (event3) => on_message(event3.data);
 }
 
@@ -5561,7 +5692,7 @@ function scroll_to_element2(id2) {
 
 // build/dev/javascript/agx_debug/agx_debug/model.mjs
 function default_controls() {
-  return new Controls(false);
+  return new Controls(false, false);
 }
 function init_model() {
   return new Model(toList([]), default_controls());
@@ -5588,7 +5719,12 @@ function update2(model, msg) {
       return zero;
     }
   } else if (msg instanceof ToggleScrollToNewEvent) {
-    let new_controls = new Controls(!model.controls.scroll_to_new_event);
+    let _record = model.controls;
+    let new_controls = new Controls(!model.controls.scroll_to_new_event, _record.errors_only);
+    return [new Model(model.events, new_controls), none()];
+  } else if (msg instanceof ToggleErrorsOnly) {
+    let _record = model.controls;
+    let new_controls = new Controls(_record.scroll_to_new_event, !model.controls.errors_only);
     return [new Model(model.events, new_controls), none()];
   } else {
     let index4 = msg[0];
@@ -5608,7 +5744,41 @@ function on_click(msg) {
 }
 
 // build/dev/javascript/agx_debug/agx_debug/view.mjs
-function control_panel(controls) {
+function is_error(event4) {
+  let $ = event4.payload;
+  if ($ instanceof ErrorEvent) {
+    return true;
+  } else {
+    return false;
+  }
+}
+function is_waiting_on_user(loop$events) {
+  while (true) {
+    let events = loop$events;
+    if (events instanceof Empty) {
+      return false;
+    } else {
+      let payload = events.head.payload;
+      if (payload instanceof ApprovalRequested) {
+        return true;
+      } else if (payload instanceof ApprovalResolved) {
+        return false;
+      } else if (payload instanceof NewSession) {
+        return false;
+      } else {
+        let rest = events.tail;
+        loop$events = rest;
+      }
+    }
+  }
+}
+function control_panel(controls, waiting_on_user) {
+  let _block;
+  if (waiting_on_user) {
+    _block = span(toList([class$("ml-auto font-semibold text-[#d65d0e]")]), toList([text2("waiting on user")]));
+  } else {
+    _block = none2();
+  }
   return div(toList([
     class$("fixed bottom-0 left-0 right-0 h-8 bg-[#3c3836] border-t border-[#504945] flex items-center px-4 text-sm text-[#a89984] z-50")
   ]), toList([
@@ -5620,7 +5790,19 @@ function control_panel(controls) {
         class$("cursor-pointer")
       ])),
       text2("scroll to new event")
-    ]))
+    ])),
+    label(toList([
+      class$("flex items-center gap-2 cursor-pointer ml-4")
+    ]), toList([
+      input(toList([
+        type_("checkbox"),
+        checked(controls.errors_only),
+        on_click(new ToggleErrorsOnly),
+        class$("cursor-pointer")
+      ])),
+      text2("errors only")
+    ])),
+    _block
   ]));
 }
 function heading() {
@@ -5653,8 +5835,16 @@ function payload_kind_and_color(payload) {
     return ["turn_complete", "#83a598"];
   } else if (payload instanceof Interrupted) {
     return ["interrupted", "#fb4934"];
-  } else {
+  } else if (payload instanceof NewSession) {
     return ["new_session", "#bdae93"];
+  } else if (payload instanceof ApprovalRequested) {
+    return ["approval_requested", "#d65d0e"];
+  } else if (payload instanceof ApprovalResolved) {
+    return ["approval_resolved", "#689d6a"];
+  } else if (payload instanceof ErrorEvent) {
+    return ["error", "#cc241d"];
+  } else {
+    return ["turn_diff_stat", "#458588"];
   }
 }
 function minimap_marker(event4, index4) {
@@ -5772,6 +5962,92 @@ function render_new_session() {
     div(toList([class$("flex-1 h-px bg-[#504945]")]), toList([]))
   ]));
 }
+function render_approval_requested(tool_call_id, tool_call, details) {
+  let _block;
+  if (details instanceof Some) {
+    let d = details[0];
+    _block = pre(toList([
+      class$("text-xs bg-[#282828] p-1 rounded whitespace-pre-wrap break-all max-h-[50vh] overflow-auto")
+    ]), toList([text3(d)]));
+  } else {
+    _block = none2();
+  }
+  return div(toList([class$("p-2 bg-[#3c3836] rounded")]), toList([
+    div(toList([class$("flex gap-2 items-center mb-1")]), toList([
+      span(toList([class$("text-sm text-[#d65d0e]")]), toList([text2("waiting on user")])),
+      span(toList([
+        class$("font-mono text-sm bg-[#282828] px-1 rounded")
+      ]), toList([text2(tool_call)])),
+      span(toList([class$("text-xs text-[#a89984]")]), toList([text2("id: " + tool_call_id)]))
+    ])),
+    _block
+  ]));
+}
+function render_approval_resolved(tool_call_id, decision, waited_ms) {
+  return div(toList([
+    class$("p-2 bg-[#3c3836] rounded flex gap-2 items-center")
+  ]), toList([
+    span(toList([class$("text-sm")]), toList([
+      text2(decision + " after " + to_string(waited_ms) + "ms")
+    ])),
+    span(toList([class$("text-xs text-[#a89984]")]), toList([text2("id: " + tool_call_id)]))
+  ]));
+}
+function render_error(category, message) {
+  return div(toList([class$("p-2 bg-[#3c3836] rounded")]), toList([
+    span(toList([
+      class$("font-mono text-xs bg-[#cc241d] px-1 rounded")
+    ]), toList([text2(category)])),
+    pre(toList([
+      class$("mt-1 text-xs text-[#fb4934] whitespace-pre-wrap break-all max-h-[50vh] overflow-auto")
+    ]), toList([text3(message)]))
+  ]));
+}
+function render_file_diff_stat(file) {
+  let path;
+  let change;
+  let lines_added;
+  let lines_removed;
+  path = file.path;
+  change = file.change;
+  lines_added = file.lines_added;
+  lines_removed = file.lines_removed;
+  let _block;
+  if (change === "created") {
+    _block = "#b8bb26";
+  } else if (change === "deleted") {
+    _block = "#fb4934";
+  } else {
+    _block = "#fabd2f";
+  }
+  let color = _block;
+  let _block$1;
+  if (lines_added instanceof Some && lines_removed instanceof Some) {
+    let added = lines_added[0];
+    let removed = lines_removed[0];
+    _block$1 = "+" + to_string(added) + " -" + to_string(removed);
+  } else {
+    _block$1 = "binary";
+  }
+  let lines = _block$1;
+  return div(toList([class$("flex gap-2 items-center text-xs font-mono")]), toList([
+    span(toList([class$("w-16"), style("color", color)]), toList([text2(change)])),
+    span(toList([class$("break-all")]), toList([text2(path)])),
+    span(toList([class$("text-[#a89984]")]), toList([text2(lines)]))
+  ]));
+}
+function render_turn_diff_stat(turn, files, lines_added, lines_removed) {
+  return div(toList([class$("p-2 bg-[#3c3836] rounded")]), toList([
+    div(toList([class$("flex gap-2 items-center mb-1")]), toList([
+      span(toList([class$("text-sm")]), toList([
+        text2("turn " + to_string(turn) + ": " + to_string(length(files)) + " file(s) changed")
+      ])),
+      span(toList([class$("text-xs text-[#b8bb26]")]), toList([text2("+" + to_string(lines_added))])),
+      span(toList([class$("text-xs text-[#fb4934]")]), toList([text2("-" + to_string(lines_removed))]))
+    ])),
+    div(toList([class$("flex flex-col gap-1")]), map(files, render_file_diff_stat))
+  ]));
+}
 function render_user_content(content) {
   if (content instanceof UserText) {
     let text4 = content.text;
@@ -5896,8 +6172,28 @@ function render_payload(payload) {
     return render_turn_complete(history);
   } else if (payload instanceof Interrupted) {
     return render_interrupted();
-  } else {
+  } else if (payload instanceof NewSession) {
     return render_new_session();
+  } else if (payload instanceof ApprovalRequested) {
+    let tool_call_id = payload.tool_call_id;
+    let tool_call = payload.tool_call;
+    let details = payload.details;
+    return render_approval_requested(tool_call_id, tool_call, details);
+  } else if (payload instanceof ApprovalResolved) {
+    let tool_call_id = payload.tool_call_id;
+    let decision = payload.decision;
+    let waited_ms = payload.waited_ms;
+    return render_approval_resolved(tool_call_id, decision, waited_ms);
+  } else if (payload instanceof ErrorEvent) {
+    let category = payload.category;
+    let message = payload.message;
+    return render_error(category, message);
+  } else {
+    let turn = payload.turn;
+    let files = payload.files;
+    let lines_added = payload.lines_added;
+    let lines_removed = payload.lines_removed;
+    return render_turn_diff_stat(turn, files, lines_added, lines_removed);
   }
 }
 function render_event_details(event4, index4) {
@@ -5948,12 +6244,20 @@ function events_div(events) {
   ]));
 }
 function view(model) {
+  let _block;
+  let $ = model.controls.errors_only;
+  if ($) {
+    _block = filter(model.events, is_error);
+  } else {
+    _block = model.events;
+  }
+  let events = _block;
   return div(toList([
     class$("flex flex-col min-h-screen bg-[#282828] text-[#ebdbb2] pl-20")
   ]), toList([
-    minimap(model.events),
-    div(toList([class$("mt-8 mb-12 w-full max-w-7xl mx-auto px-4")]), toList([heading(), events_div(model.events)])),
-    control_panel(model.controls)
+    minimap(events),
+    div(toList([class$("mt-8 mb-12 w-full max-w-7xl mx-auto px-4")]), toList([heading(), events_div(events)])),
+    control_panel(model.controls, is_waiting_on_user(model.events))
   ]));
 }
 
@@ -5962,7 +6266,7 @@ var FILEPATH = "src/agx_debug.gleam";
 function init(_) {
   return [
     init_model(),
-    subscribe_sse2("http://127.0.0.1:4880/api/debug/events")
+    subscribe_sse2("/api/debug/events")
   ];
 }
 function main() {
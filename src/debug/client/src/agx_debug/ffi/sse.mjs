@@ -1,5 +1,12 @@
 export function subscribe_sse(url, on_message) {
-  const source = new EventSource(url);
+  // the token the page was opened with is needed to receive events
+  const resolved = new URL(url, window.location.href);
+  const token = new URLSearchParams(window.location.search).get("token");
+  if (token) {
+    resolved.searchParams.set("token", token);
+  }
+
+  const source = new EventSource(resolved);
   // TODO: error should be handled here
   source.onmessage = (event) => on_message(event.data);
 }
@@ -1,14 +1,19 @@
-use crate::domain::DebugEventReceiver;
+use crate::domain::{DebugEventReceiver, SequencedDebugEvent};
 use anyhow::Context;
 use axum::Router;
-use axum::extract::State;
-use axum::http::HeaderMap;
-use axum::response::sse::{Event, Sse};
+use axum::extract::{Query, State};
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::response::sse::{Event, KeepAlive, Sse};
 use axum::response::{Html, IntoResponse};
 use axum::routing::get;
 use colored::Colorize;
 use futures::stream::Stream;
+use rand::distr::{Alphanumeric, SampleString};
+use serde::Deserialize;
 use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::net::TcpListener;
 use tokio_stream::StreamExt;
 use tokio_stream::wrappers::BroadcastStream;
@@ -17,18 +22,58 @@ use tower_http::cors::{Any, CorsLayer};
 const EVENTS_PATH: &str = "/api/debug/events";
 const ROOT_HTML: &str = include_str!("client/dist/index.html");
 const DEPS_JS: &str = include_str!("client/dist/agx_debug.js");
-const DEPS_CSS: &str = include_str!("client/dist/agx_debug.css");
+pub const DEPS_CSS: &str = include_str!("client/dist/agx_debug.css");
 const FAVICON: &[u8] = include_bytes!("client/assets/favicon.png");
+pub const DEFAULT_DEBUG_SERVER_ADDRESS: &str = "127.0.0.1:4880";
+const GENERATED_TOKEN_LEN: usize = 32;
+/// Configured tokens shorter than this are refused, as they're easy to guess.
+pub const MIN_DEBUG_SERVER_TOKEN_LEN: usize = 16;
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
 
 pub struct DebugServer {
     debug_rx: DebugEventReceiver,
+    addr: SocketAddr,
+    token: Option<String>,
+}
+
+#[derive(Clone)]
+struct ServerState {
+    debug_rx: DebugEventReceiver,
+    token: Option<Arc<str>>,
+}
+
+#[derive(Deserialize)]
+struct EventsQuery {
+    token: Option<String>,
 }
 
 impl DebugServer {
-    pub fn new(events_rx: DebugEventReceiver) -> Self {
-        Self {
-            debug_rx: events_rx,
+    /// Events are only served to clients that present the token, which is required when
+    /// binding to a non-loopback address. A random token is generated if none is provided;
+    /// provided tokens need to be at least [`MIN_DEBUG_SERVER_TOKEN_LEN`] characters long.
+    pub fn new(
+        events_rx: DebugEventReceiver,
+        addr: SocketAddr,
+        token: Option<String>,
+    ) -> anyhow::Result<Self> {
+        if let Some(token) = &token
+            && token.chars().count() < MIN_DEBUG_SERVER_TOKEN_LEN
+        {
+            anyhow::bail!(
+                "the debug server's token needs to be at least {MIN_DEBUG_SERVER_TOKEN_LEN} characters long"
+            );
         }
+
+        let token = token.or_else(|| {
+            (!addr.ip().is_loopback())
+                .then(|| Alphanumeric.sample_string(&mut rand::rng(), GENERATED_TOKEN_LEN))
+        });
+
+        Ok(Self {
+            debug_rx: events_rx,
+            addr,
+            token,
+        })
     }
 
     pub async fn run(&self) -> anyhow::Result<()> {
@@ -40,19 +85,23 @@ impl DebugServer {
             .route("/agx_debug.css", get(css_get))
             .route("/favicon.png", get(favicon_get))
             .route(EVENTS_PATH, get(sse_handler))
-            .with_state(self.debug_rx.clone())
+            .with_state(ServerState {
+                debug_rx: self.debug_rx.clone(),
+                token: self.token.as_deref().map(Arc::from),
+            })
             .layer(cors);
 
-        let addr = "127.0.0.1:4880";
+        let addr = self.addr;
 
         let listener = TcpListener::bind(&addr)
             .await
             .with_context(|| format!(r#"couldn't bind TCP listener to address "{addr}""#))?;
 
-        println!(
-            "debug UI available at {}",
-            format!("http://{}/debug", addr).green(),
-        );
+        let url = match &self.token {
+            Some(token) => format!("http://{addr}/debug?token={token}"),
+            None => format!("http://{addr}/debug"),
+        };
+        println!("debug UI available at {}", url.green());
         axum::serve(listener, app)
             .await
             .context("couldn't start debug web server")?;
@@ -104,16 +153,132 @@ async fn favicon_get() -> impl IntoResponse {
 }
 
 async fn sse_handler(
-    State(debug_rx): State<DebugEventReceiver>,
-) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
-    let rx = debug_rx.subscribe();
-    let stream = BroadcastStream::new(rx).filter_map(|result| match result {
-        Ok(event) => {
+    State(state): State<ServerState>,
+    Query(query): Query<EventsQuery>,
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, StatusCode> {
+    if let Some(expected) = &state.token {
+        let provided = query.token.or_else(|| {
+            headers
+                .get(header::AUTHORIZATION)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.strip_prefix("Bearer "))
+                .map(str::to_string)
+        });
+
+        if !provided.is_some_and(|t| tokens_match(t.as_bytes(), expected.as_bytes())) {
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+    }
+
+    // browsers send this header when reconnecting on their own
+    let last_event_id = headers
+        .get("last-event-id")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok());
+
+    let (missed, rx) = state.debug_rx.subscribe(last_event_id);
+    let stream = tokio_stream::iter(missed.into_iter().map(Ok))
+        .chain(BroadcastStream::new(rx))
+        // a client that lags behind is disconnected, so that it reconnects, and catches up on
+        // the events it missed via the history
+        .take_while(Result::is_ok)
+        .filter_map(|result| {
+            let SequencedDebugEvent { id, event } = result.ok()?;
             let json = serde_json::to_string(&event).ok()?;
-            Some(Ok(Event::default().data(json)))
+            Some(Ok(Event::default().id(id.to_string()).data(json)))
+        });
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::new().interval(KEEP_ALIVE_INTERVAL)))
+}
+
+/// Compares tokens in constant time (for tokens of the same length), so that a token can't be
+/// guessed by timing responses.
+fn tokens_match(provided: &[u8], expected: &[u8]) -> bool {
+    provided.len() == expected.len()
+        && provided
+            .iter()
+            .zip(expected)
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::debug_channel;
+
+    const TOKEN: &str = "0123456789abcdef0123";
+
+    async fn status(query_token: Option<&str>, header_token: Option<&str>) -> StatusCode {
+        let (_debug_tx, debug_rx) = debug_channel();
+        let state = ServerState {
+            debug_rx,
+            token: Some(Arc::from(TOKEN)),
+        };
+        let query = EventsQuery {
+            token: query_token.map(str::to_string),
+        };
+        let mut headers = HeaderMap::new();
+        if let Some(token) = header_token {
+            #[allow(clippy::expect_used)]
+            headers.insert(
+                header::AUTHORIZATION,
+                format!("Bearer {token}")
+                    .parse()
+                    .expect("authorization header value should've been parsed"),
+            );
         }
-        Err(_) => None, // TODO: handle this error
-    });
 
-    Sse::new(stream)
+        sse_handler(State(state), Query(query), headers)
+            .await
+            .into_response()
+            .status()
+    }
+
+    #[tokio::test]
+    async fn events_are_only_served_to_clients_that_present_the_token() {
+        // GIVEN
+        // WHEN
+        let statuses = [
+            status(None, None).await,
+            status(Some("wrong-token-0123456789"), None).await,
+            status(None, Some("wrong-token-0123456789")).await,
+            status(Some(""), None).await,
+            status(Some(TOKEN), None).await,
+            status(None, Some(TOKEN)).await,
+        ];
+
+        // THEN
+        assert_eq!(
+            statuses,
+            [
+                StatusCode::UNAUTHORIZED,
+                StatusCode::UNAUTHORIZED,
+                StatusCode::UNAUTHORIZED,
+                StatusCode::UNAUTHORIZED,
+                StatusCode::OK,
+                StatusCode::OK,
+            ]
+        );
+    }
+
+    #[test]
+    fn short_tokens_are_refused() {
+        // GIVEN
+        let (_debug_tx, debug_rx) = debug_channel();
+        let addr = SocketAddr::from(([0, 0, 0, 0], 4880));
+
+        // WHEN
+        let results = ["", "secret", TOKEN]
+            .map(|token| DebugServer::new(debug_rx.clone(), addr, Some(token.to_string())));
+
+        // THEN
+        assert!(results[0].is_err(), "empty token should've been refused");
+        assert!(results[1].is_err(), "short token should've been refused");
+        assert!(
+            results[2].is_ok(),
+            "long enough token should've been accepted"
+        );
+    }
 }
@@ -26,6 +26,24 @@ impl FromStr for CmdPattern {
     }
 }
 
+impl CmdPattern {
+    /// Returns why the pattern can never match a command, if that's the case; patterns read
+    /// from config aren't parsed from a command, and can be malformed.
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.binary.trim().is_empty() {
+            return Err("binary is empty");
+        }
+        if self.binary.contains(char::is_whitespace) {
+            return Err("binary contains whitespace; it should only be the command's first word");
+        }
+        if self.first_arg.as_ref().is_some_and(|a| a.trim().is_empty()) {
+            return Err("first argument is empty; leave it out to allow any arguments");
+        }
+
+        Ok(())
+    }
+}
+
 impl Display for CmdPattern {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match &self.first_arg {
@@ -45,9 +63,21 @@ impl ApprovedCmds {
             .unwrap_or(false)
     }
 
+    pub fn iter(&self) -> impl Iterator<Item = &CmdPattern> {
+        self.0.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
     pub fn insert(&mut self, pattern: &CmdPattern) {
         self.0.insert(pattern.clone());
     }
+
+    pub fn extend(&mut self, other: ApprovedCmds) {
+        self.0.extend(other.0);
+    }
 }
 
 impl Display for ApprovedCmds {
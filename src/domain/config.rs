@@ -1,8 +1,634 @@
-use super::ApprovedCmds;
+use super::{AnthropicConfig, ApprovedCmds, GeminiConfig, OpenRouterConfig};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Duration;
 
-#[derive(Debug, Default, Serialize, Deserialize)]
+pub const DEFAULT_MAX_WRITE_BYTES: usize = 100_000;
+const DEFAULT_FIRST_TOKEN_NOTICE_SECS: u64 = 10;
+const DEFAULT_FIRST_TOKEN_RETRY_SECS: u64 = 60;
+const DEFAULT_CONNECT_TIMEOUT_SECS: u64 = 30;
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct Config {
     #[serde(default)]
     pub approved_commands: ApprovedCmds,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anthropic: Option<AnthropicConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub openrouter: Option<OpenRouterConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gemini: Option<GeminiConfig>,
+    /// Package directory (relative to the project root) to scope the agent to; useful in
+    /// monorepos.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workspace: Option<String>,
+    /// Code forge hosting the project; detected via the "origin" remote if not specified.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub forge: Option<ForgeConfig>,
+    /// Settings for the debug server (enabled via AGX_DEBUG_SERVER=1). Ignored in the team
+    /// config.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub debug_server: Option<DebugServerConfig>,
+    /// Guardrails for sessions started via `agx auto`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub autopilot: Option<AutopilotConfig>,
+    /// Settings for specific models, keyed by model name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub models: BTreeMap<String, ModelConfig>,
+    /// Whether the model is reminded to change course when a tool keeps failing; on by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_failure_reminders: Option<bool>,
+    /// Whether the terminal's title is set to the project and the conversation (along with what
+    /// the session is doing); on by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub terminal_title: Option<bool>,
+    /// Environment variables set for commands run via run_cmd (eg. RUST_BACKTRACE=1). Ignored
+    /// in the team config, as they can change what commands run (eg. via BASH_ENV or PATH).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub command_env: BTreeMap<String, String>,
+    /// Whether escape codes (eg. colors) and progress output are stripped from the output of
+    /// commands before it's sent to the model; on by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clean_command_output: Option<bool>,
+    /// Webhooks notified of session events, so that long unattended runs can be monitored.
+    /// Ignored in the team config.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub webhooks: Vec<WebhookConfig>,
+    /// What to do when the workspace has uncommitted changes as a session starts; defaults to
+    /// "warn".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dirty_workspace: Option<DirtyWorkspacePolicy>,
+    /// Largest write (in bytes) create_file and edit_file make in a single call: the contents of
+    /// a file being created, or the strings an edit replaces; files larger than this can't be
+    /// overwritten either. Larger writes are refused, so that big files are changed in parts
+    /// rather than rewritten whole. Defaults to 100000.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_write_bytes: Option<usize>,
+    /// What to do when a session is started in a project another session is running in;
+    /// defaults to "read-only".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub concurrent_sessions: Option<ConcurrentSessionsPolicy>,
+    /// How changes to files are shown (when they're confirmed, and via /diff); defaults to
+    /// "line". Word diffs suit prose (eg. documentation) and files with long lines better.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diff_mode: Option<DiffMode>,
+    /// Seconds to wait for the first token of a streamed response before printing that it's
+    /// still awaited (repeated as long as it is); defaults to 10, and 0 turns the notices off.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first_token_notice_secs: Option<u64>,
+    /// Seconds to wait for the first token of a streamed response before offering to retry the
+    /// request, in case the connection is stuck; defaults to 60, and 0 turns the offer off.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first_token_retry_secs: Option<u64>,
+    /// Timeouts and connection reuse for requests made to providers. Ignored in the team config.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http: Option<HttpConfig>,
+    /// A model served locally, that sessions can switch to when the provider can't be reached.
+    /// Ignored in the team config.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_model: Option<LocalModelConfig>,
+    /// Commands that format files after create_file and edit_file write them, keyed by file
+    /// extension (eg. rs = "rustfmt --edition 2024"). The file's path is appended to the
+    /// command, which is expected to format it in place; the changes it makes are reported to
+    /// the model. Ignored in the team config, as they're run without confirmation.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub formatters: BTreeMap<String, String>,
+    /// Commands that lint files after create_file and edit_file write them (and after they're
+    /// formatted), keyed by file extension (eg. py = "ruff check -q"). The file's path is
+    /// appended to the command; the warnings and errors it reports are sent to the model along
+    /// with the tool's result. Ignored in the team config, like formatters.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub linters: BTreeMap<String, String>,
+    /// MCP servers whose tools are offered to the model alongside the builtin ones, keyed by
+    /// name; tools are named "<server>__<tool>". Only read from the local and user config: ones
+    /// in the team config aren't started, since it's committed.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub mcp_servers: BTreeMap<String, McpServerConfig>,
+}
+
+impl Config {
+    /// Layers `local` over this config: fields set in `local` take precedence, settings for a
+    /// model (or a command environment variable, a formatter or linter, or an MCP server) in
+    /// `local` replace the ones for the same model (or variable, extension, or server) here, and
+    /// approved commands and webhooks from both are kept.
+    pub fn layered_with(self, local: Config) -> Config {
+        let mut approved_commands = self.approved_commands;
+        approved_commands.extend(local.approved_commands);
+
+        let mut models = self.models;
+        models.extend(local.models);
+
+        let mut command_env = self.command_env;
+        command_env.extend(local.command_env);
+
+        let mut webhooks = self.webhooks;
+        webhooks.extend(local.webhooks);
+
+        let mut formatters = self.formatters;
+        formatters.extend(local.formatters);
+
+        let mut linters = self.linters;
+        linters.extend(local.linters);
+
+        let mut mcp_servers = self.mcp_servers;
+        mcp_servers.extend(local.mcp_servers);
+
+        Config {
+            approved_commands,
+            anthropic: local.anthropic.or(self.anthropic),
+            openrouter: local.openrouter.or(self.openrouter),
+            gemini: local.gemini.or(self.gemini),
+            workspace: local.workspace.or(self.workspace),
+            forge: local.forge.or(self.forge),
+            debug_server: local.debug_server.or(self.debug_server),
+            autopilot: local.autopilot.or(self.autopilot),
+            models,
+            tool_failure_reminders: local.tool_failure_reminders.or(self.tool_failure_reminders),
+            terminal_title: local.terminal_title.or(self.terminal_title),
+            command_env,
+            clean_command_output: local.clean_command_output.or(self.clean_command_output),
+            webhooks,
+            dirty_workspace: local.dirty_workspace.or(self.dirty_workspace),
+            max_write_bytes: local.max_write_bytes.or(self.max_write_bytes),
+            concurrent_sessions: local.concurrent_sessions.or(self.concurrent_sessions),
+            diff_mode: local.diff_mode.or(self.diff_mode),
+            first_token_notice_secs: local
+                .first_token_notice_secs
+                .or(self.first_token_notice_secs),
+            first_token_retry_secs: local.first_token_retry_secs.or(self.first_token_retry_secs),
+            http: local.http.or(self.http),
+            local_model: local.local_model.or(self.local_model),
+            formatters,
+            linters,
+            mcp_servers,
+        }
+    }
+
+    pub fn tool_calling_mode(&self, model_name: &str) -> ToolCallingMode {
+        self.models
+            .get(model_name)
+            .and_then(|m| m.tool_calling)
+            .unwrap_or_default()
+    }
+
+    pub fn tool_failure_reminders(&self) -> bool {
+        self.tool_failure_reminders.unwrap_or(true)
+    }
+
+    pub fn terminal_title(&self) -> bool {
+        self.terminal_title.unwrap_or(true)
+    }
+
+    pub fn clean_command_output(&self) -> bool {
+        self.clean_command_output.unwrap_or(true)
+    }
+
+    pub fn dirty_workspace(&self) -> DirtyWorkspacePolicy {
+        self.dirty_workspace.unwrap_or_default()
+    }
+
+    pub fn concurrent_sessions(&self) -> ConcurrentSessionsPolicy {
+        self.concurrent_sessions.unwrap_or_default()
+    }
+
+    pub fn max_write_bytes(&self) -> usize {
+        self.max_write_bytes.unwrap_or(DEFAULT_MAX_WRITE_BYTES)
+    }
+
+    pub fn diff_mode(&self) -> DiffMode {
+        self.diff_mode.unwrap_or_default()
+    }
+
+    pub fn first_token_notice_after(&self) -> Duration {
+        Duration::from_secs(
+            self.first_token_notice_secs
+                .unwrap_or(DEFAULT_FIRST_TOKEN_NOTICE_SECS),
+        )
+    }
+
+    pub fn first_token_retry_after(&self) -> Duration {
+        Duration::from_secs(
+            self.first_token_retry_secs
+                .unwrap_or(DEFAULT_FIRST_TOKEN_RETRY_SECS),
+        )
+    }
+
+    /// A short fingerprint of the config (the first 12 hex digits of its SHA-256 hash), recorded
+    /// in transcripts so that an issue reproduced from one can be tied to the exact config it
+    /// ran with. Secrets (the debug server's token) aren't a part of it.
+    pub fn digest(&self) -> String {
+        let mut value = serde_json::to_value(self).unwrap_or_default();
+        if let Some(debug_server) = value
+            .get_mut("debug_server")
+            .and_then(|d| d.as_object_mut())
+        {
+            debug_server.remove("token");
+        }
+        // approved commands are held in a set, whose order isn't stable
+        if let Some(serde_json::Value::Array(commands)) = value.get_mut("approved_commands") {
+            commands.sort_by_key(|c| c.to_string());
+        }
+
+        ring::digest::digest(&ring::digest::SHA256, value.to_string().as_bytes())
+            .as_ref()
+            .iter()
+            .take(6)
+            .map(|b| format!("{b:02x}"))
+            .collect()
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ModelConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_calling: Option<ToolCallingMode>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pricing: Option<ModelPricing>,
+}
+
+/// Prices in USD, used to track the cost of a session.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelPricing {
+    pub input_per_million_tokens: f64,
+    pub output_per_million_tokens: f64,
+}
+
+impl ModelPricing {
+    pub fn cost(&self, input_tokens: u64, output_tokens: u64) -> f64 {
+        (input_tokens as f64 * self.input_per_million_tokens
+            + output_tokens as f64 * self.output_per_million_tokens)
+            / 1_000_000.0
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct DebugServerConfig {
+    /// Address to bind to, eg. "0.0.0.0:4880"; defaults to "127.0.0.1:4880".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    /// Token clients need to present to receive events. Always required for non-loopback
+    /// addresses; a random one is generated at startup if not set. Needs to be at least 16
+    /// characters long.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ForgeConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<ForgeKind>,
+    /// Base URL for API calls; needed for self-hosted instances. Ignored in the team config,
+    /// since the forge's token is sent to it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ForgeKind {
+    GitHub,
+    GitLab,
+    Bitbucket,
+}
+
+impl ForgeKind {
+    /// Returns the forge that's known to be running on a host, if any.
+    pub fn detect(host: &str) -> Option<Self> {
+        match host {
+            "github.com" => Some(Self::GitHub),
+            "bitbucket.org" => Some(Self::Bitbucket),
+            h if h == "gitlab.com" || h.starts_with("gitlab.") => Some(Self::GitLab),
+            _ => None,
+        }
+    }
+
+    /// What the forge calls a request to merge changes.
+    pub fn pull_request_term(&self) -> &'static str {
+        match self {
+            ForgeKind::GitHub | ForgeKind::Bitbucket => "pull request",
+            ForgeKind::GitLab => "merge request",
+        }
+    }
+}
+
+impl std::fmt::Display for ForgeKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ForgeKind::GitHub => "GitHub",
+            ForgeKind::GitLab => "GitLab",
+            ForgeKind::Bitbucket => "Bitbucket",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct AutopilotConfig {
+    /// Tools the model is allowed to call; all tools are allowed if not specified.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<String>>,
+    /// Maximum number of requests made to the model; defaults to 50.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_iterations: Option<usize>,
+    /// Maximum cost in USD; needs pricing to be configured for the model.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_cost: Option<f64>,
+    /// Glob patterns (relative to the project root) for files that must not be created or
+    /// edited.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub protected_paths: Vec<String>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct HttpConfig {
+    /// Seconds to wait for a connection to be established; defaults to 30.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connect_timeout_secs: Option<u64>,
+    /// Seconds a request may take, including receiving all of its response (which, for
+    /// streamed responses, can take minutes); no limit if not specified.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_timeout_secs: Option<u64>,
+    /// Seconds to wait for more of a response before giving up on it; catches connections that
+    /// stall midway. No limit if not specified.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub read_timeout_secs: Option<u64>,
+    /// Seconds an idle connection is kept around for reuse; defaults to 90, and 0 turns reuse
+    /// off (useful with proxies that silently drop idle connections).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pool_idle_timeout_secs: Option<u64>,
+}
+
+impl HttpConfig {
+    pub fn connect_timeout(&self) -> Duration {
+        Duration::from_secs(
+            self.connect_timeout_secs
+                .unwrap_or(DEFAULT_CONNECT_TIMEOUT_SECS),
+        )
+    }
+
+    pub fn request_timeout(&self) -> Option<Duration> {
+        self.request_timeout_secs.map(Duration::from_secs)
+    }
+
+    pub fn read_timeout(&self) -> Option<Duration> {
+        self.read_timeout_secs.map(Duration::from_secs)
+    }
+
+    pub fn pool_idle_timeout(&self) -> Option<Duration> {
+        self.pool_idle_timeout_secs.map(Duration::from_secs)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LocalModelConfig {
+    /// Base URL of an OpenAI compatible server (eg. "http://localhost:11434/v1" for Ollama).
+    pub base_url: String,
+    pub model: String,
+}
+
+/// An MCP server that agx starts (and talks to over stdio) as a session starts.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct McpServerConfig {
+    pub command: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    /// Environment variables the server is started with, on top of agx's own.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// Whether calls to tools the server marks as read-only go through without being approved.
+    /// Off by default, since servers can mark any tool as read-only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trust_read_only_hints: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// How notifications are formatted; defaults to "generic" (a JSON object describing the
+    /// event).
+    #[serde(default)]
+    pub kind: WebhookKind,
+    /// Events to notify of; all of them if not specified.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<WebhookEvent>,
+    /// How long (in minutes) a tool call has to wait for approval before it's notified of;
+    /// defaults to 5.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub approval_pending_mins: Option<u64>,
+}
+
+impl WebhookConfig {
+    pub fn notifies_of(&self, event: WebhookEvent) -> bool {
+        self.events.is_empty() || self.events.contains(&event)
+    }
+
+    pub fn approval_pending_mins(&self) -> u64 {
+        self.approval_pending_mins.unwrap_or(5)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookKind {
+    #[default]
+    Generic,
+    Slack,
+    Discord,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEvent {
+    SessionEnded,
+    /// A tool call has been waiting for approval for longer than configured.
+    ApprovalPending,
+    /// A guardrail tripped in autopilot mode.
+    GuardrailTripped,
+}
+
+/// Agent edits mixed with uncommitted manual work are hard to untangle; sessions can warn about
+/// such work, or have the first tool call that might change the workspace confirmed regardless
+/// of the approval mode.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DirtyWorkspacePolicy {
+    #[default]
+    Warn,
+    Confirm,
+    Ignore,
+}
+
+/// Two sessions changing the same files (and sharing history and change tracking) get in each
+/// other's way; sessions started while another one is running can either only read the
+/// project, or refuse to start. Sessions in worktrees aren't affected.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConcurrentSessionsPolicy {
+    #[default]
+    ReadOnly,
+    Refuse,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffMode {
+    /// Changed lines are shown removed and added, with the parts that changed emphasized.
+    #[default]
+    Line,
+    /// Changed words are shown in place, within the lines they're in.
+    Word,
+}
+
+impl std::fmt::Display for DiffMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DiffMode::Line => write!(f, "line"),
+            DiffMode::Word => write!(f, "word"),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolCallingMode {
+    /// Tool calls are made via the provider's API.
+    #[default]
+    Native,
+    /// Tool calls are requested via fenced blocks in the model's response text; for models
+    /// that don't support native tool calling.
+    Text,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::CmdPattern;
+    use std::str::FromStr;
+
+    #[test]
+    fn local_config_takes_precedence_over_the_teams() {
+        // GIVEN
+        let mut team = Config {
+            workspace: Some("packages/api".to_string()),
+            tool_failure_reminders: Some(false),
+            command_env: BTreeMap::from([
+                ("RUST_BACKTRACE".to_string(), "1".to_string()),
+                ("DATABASE_URL".to_string(), "postgres://ci/test".to_string()),
+            ]),
+            dirty_workspace: Some(DirtyWorkspacePolicy::Confirm),
+            max_write_bytes: Some(50_000),
+            diff_mode: Some(DiffMode::Word),
+            first_token_retry_secs: Some(120),
+            formatters: BTreeMap::from([
+                ("rs".to_string(), "rustfmt".to_string()),
+                ("py".to_string(), "black -q".to_string()),
+            ]),
+            linters: BTreeMap::from([("py".to_string(), "ruff check -q".to_string())]),
+            ..Default::default()
+        };
+        team.approved_commands
+            .insert(&CmdPattern::from_str("cargo test").expect("pattern should be valid"));
+        let mut local = Config {
+            workspace: Some("packages/web".to_string()),
+            max_write_bytes: Some(200_000),
+            first_token_retry_secs: Some(30),
+            command_env: BTreeMap::from([(
+                "DATABASE_URL".to_string(),
+                "postgres://localhost/test".to_string(),
+            )]),
+            formatters: BTreeMap::from([("py".to_string(), "ruff format -q".to_string())]),
+            mcp_servers: BTreeMap::from([(
+                "postgres".to_string(),
+                McpServerConfig {
+                    command: "npx".to_string(),
+                    args: vec![
+                        "-y".to_string(),
+                        "@modelcontextprotocol/server-postgres".to_string(),
+                    ],
+                    env: BTreeMap::new(),
+                    trust_read_only_hints: None,
+                },
+            )]),
+            ..Default::default()
+        };
+        local
+            .approved_commands
+            .insert(&CmdPattern::from_str("git status").expect("pattern should be valid"));
+
+        // WHEN
+        let config = team.layered_with(local);
+
+        // THEN
+        assert_eq!(config.workspace.as_deref(), Some("packages/web"));
+        assert!(!config.tool_failure_reminders());
+        assert_eq!(config.dirty_workspace(), DirtyWorkspacePolicy::Confirm);
+        assert_eq!(config.max_write_bytes(), 200_000);
+        assert_eq!(config.diff_mode(), DiffMode::Word);
+        assert_eq!(config.first_token_notice_after(), Duration::from_secs(10));
+        assert_eq!(config.first_token_retry_after(), Duration::from_secs(30));
+        assert!(config.approved_commands.is_approved("cargo test --all"));
+        assert!(config.approved_commands.is_approved("git status"));
+        assert_eq!(
+            config.command_env,
+            BTreeMap::from([
+                (
+                    "DATABASE_URL".to_string(),
+                    "postgres://localhost/test".to_string()
+                ),
+                ("RUST_BACKTRACE".to_string(), "1".to_string()),
+            ])
+        );
+        assert_eq!(
+            config.formatters,
+            BTreeMap::from([
+                ("py".to_string(), "ruff format -q".to_string()),
+                ("rs".to_string(), "rustfmt".to_string()),
+            ])
+        );
+        assert_eq!(
+            config.linters,
+            BTreeMap::from([("py".to_string(), "ruff check -q".to_string())])
+        );
+        assert_eq!(
+            config.mcp_servers.keys().collect::<Vec<_>>(),
+            vec!["postgres"]
+        );
+    }
+
+    #[test]
+    fn config_digest_only_changes_with_settings() {
+        // GIVEN
+        let config_with = |token: &str, commands: &[&str], max_write_bytes: Option<usize>| {
+            let mut config = Config {
+                debug_server: Some(DebugServerConfig {
+                    address: None,
+                    token: Some(token.to_string()),
+                }),
+                max_write_bytes,
+                ..Default::default()
+            };
+            for cmd in commands {
+                config
+                    .approved_commands
+                    .insert(&CmdPattern::from_str(cmd).expect("pattern should be valid"));
+            }
+            config
+        };
+        let config = config_with("first-token", &["cargo test", "git status", "ls"], None);
+        let same_settings = config_with("second-token", &["ls", "git status", "cargo test"], None);
+        let changed = config_with(
+            "first-token",
+            &["cargo test", "git status", "ls"],
+            Some(50_000),
+        );
+
+        // WHEN
+        let digest = config.digest();
+
+        // THEN
+        assert_eq!(digest.len(), 12);
+        assert_eq!(digest, same_settings.digest());
+        assert_ne!(digest, changed.digest());
+    }
 }
@@ -0,0 +1,189 @@
+use crate::helpers::parse_duration;
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::str::FromStr;
+
+/// Recurring chores ("jobs"), each run via `agx auto` once its interval has passed since it last
+/// ran. Defined in TOML; for example:
+///
+/// ```toml
+/// [[jobs]]
+/// name = "lockfile"
+/// every = "1w"
+/// prompt = "Update the dependency lockfile, and make sure the tests still pass"
+/// ```
+#[derive(Debug, Clone)]
+pub struct CronManifest {
+    pub jobs: Vec<CronJob>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CronJob {
+    /// Used to name the job's branch and log files, and to track when it last ran.
+    pub name: String,
+    pub every: TimeDelta,
+    pub prompt: String,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CronManifestError {
+    #[error(transparent)]
+    InvalidToml(#[from] toml::de::Error),
+    #[error("invalid job name \"{0}\"; names can only contain letters, numbers, \"-\", and \"_\"")]
+    InvalidJobName(String),
+    #[error("job name \"{0}\" is used more than once")]
+    DuplicateJobName(String),
+    #[error("job \"{0}\": {1}")]
+    InvalidInterval(String, String),
+    #[error("no jobs defined")]
+    NoJobs,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawManifest {
+    #[serde(default)]
+    jobs: Vec<RawJob>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawJob {
+    name: String,
+    every: String,
+    prompt: String,
+}
+
+impl FromStr for CronManifest {
+    type Err = CronManifestError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let raw: RawManifest = toml::from_str(s)?;
+
+        let mut names = HashSet::new();
+        let mut jobs = vec![];
+        for job in raw.jobs {
+            if job.name.is_empty()
+                || !job
+                    .name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            {
+                return Err(CronManifestError::InvalidJobName(job.name));
+            }
+            if !names.insert(job.name.clone()) {
+                return Err(CronManifestError::DuplicateJobName(job.name));
+            }
+            let every = parse_duration(&job.every)
+                .map_err(|e| CronManifestError::InvalidInterval(job.name.clone(), e.to_string()))?;
+
+            jobs.push(CronJob {
+                name: job.name,
+                every,
+                prompt: job.prompt,
+            });
+        }
+
+        if jobs.is_empty() {
+            return Err(CronManifestError::NoJobs);
+        }
+
+        Ok(Self { jobs })
+    }
+}
+
+impl CronJob {
+    /// Returns when the job is due next; jobs that never ran are due right away.
+    pub fn next_run(&self, last_run: Option<DateTime<Utc>>) -> Option<DateTime<Utc>> {
+        last_run.map(|t| t + self.every)
+    }
+
+    pub fn is_due(&self, last_run: Option<DateTime<Utc>>, now: DateTime<Utc>) -> bool {
+        self.next_run(last_run).is_none_or(|t| t <= now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::assert_snapshot;
+
+    #[test]
+    fn jobs_are_due_once_their_interval_has_passed() -> anyhow::Result<()> {
+        // GIVEN
+        let manifest: CronManifest = r#"
+[[jobs]]
+name = "lockfile"
+every = "1w"
+prompt = "Update the dependency lockfile"
+
+[[jobs]]
+name = "todos"
+every = "1d"
+prompt = """
+Find TODO comments that have been resolved,
+and remove them.
+"""
+"#
+        .parse()?;
+        let now = DateTime::parse_from_rfc3339("2025-06-10T09:00:00Z")?.to_utc();
+        let last_run = Some(DateTime::parse_from_rfc3339("2025-06-05T09:00:00Z")?.to_utc());
+
+        // WHEN
+        let due = manifest
+            .jobs
+            .iter()
+            .map(|job| {
+                format!(
+                    "{}: never ran: {}, ran 5 days ago: {}",
+                    job.name,
+                    job.is_due(None, now),
+                    job.is_due(last_run, now)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        // THEN
+        assert_snapshot!(due, @r"
+        lockfile: never ran: true, ran 5 days ago: false
+        todos: never ran: true, ran 5 days ago: true
+        ");
+        assert_eq!(
+            manifest.jobs[1].prompt,
+            "Find TODO comments that have been resolved,\nand remove them.\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_manifests_are_rejected() {
+        // GIVEN
+        let cases = [
+            r#"jobs = [{ name = "lock file", every = "1w", prompt = "update" }]"#,
+            r#"jobs = [{ name = "a", every = "1w", prompt = "x" }, { name = "a", every = "1d", prompt = "y" }]"#,
+            r#"jobs = [{ name = "lockfile", every = "weekly", prompt = "update" }]"#,
+            "jobs = []",
+        ];
+
+        // WHEN
+        let errors = cases
+            .iter()
+            .map(|c| match c.parse::<CronManifest>() {
+                Ok(_) => "parsed".to_string(),
+                Err(e) => e.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        // THEN
+        assert_snapshot!(errors, @r#"
+        invalid job name "lock file"; names can only contain letters, numbers, "-", and "_"
+        job name "a" is used more than once
+        job "lockfile": invalid duration "weekly"; expected something like "7d" (units: m, h, d, w)
+        no jobs defined
+        "#);
+    }
+}
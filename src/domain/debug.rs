@@ -1,7 +1,14 @@
 use chrono::{DateTime, Utc};
 use rig::message::{Message, Reasoning, ToolCall, ToolResult};
 use serde::Serialize;
-use tokio::sync::broadcast::{Receiver, Sender};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast::{self, Receiver, Sender};
+
+const CHANNEL_CAPACITY: usize = 64;
+/// Number of recent events kept around for clients that (re)connect.
+const HISTORY_LEN: usize = 256;
 
 #[derive(Debug, Serialize, Clone)]
 pub struct DebugEvent {
@@ -32,6 +39,68 @@ pub enum DebugEventPayload {
     },
     Interrupted,
     NewSession,
+    /// A tool call is waiting on the user's confirmation.
+    ApprovalRequested {
+        tool_call_id: String,
+        tool_call: String,
+        details: Option<String>,
+    },
+    ApprovalResolved {
+        tool_call_id: String,
+        decision: ApprovalDecision,
+        waited_ms: u64,
+    },
+    Error {
+        category: ErrorCategory,
+        message: String,
+    },
+    /// Files tools changed during a turn, so that changes can be followed turn by turn.
+    TurnDiffStat {
+        turn: usize,
+        files: Vec<FileDiffStat>,
+        lines_added: usize,
+        lines_removed: usize,
+    },
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum FileChangeKind {
+    Created,
+    Edited,
+    Deleted,
+}
+
+/// Lines are unknown for binary files.
+#[derive(Debug, Serialize, Clone)]
+pub struct FileDiffStat {
+    pub path: String,
+    pub change: FileChangeKind,
+    pub lines_added: Option<usize>,
+    pub lines_removed: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCategory {
+    /// Requesting a response from the model failed.
+    Provider,
+    /// A streamed response failed midway; the session recovered from it.
+    Stream,
+    /// A tool call failed.
+    Tool,
+    /// Anything else, eg. failing to update the config or to refresh the semantic index.
+    Session,
+}
+
+#[derive(Debug, Serialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalDecision {
+    Approved,
+    AlwaysApproved,
+    PartiallyApproved,
+    Rejected,
+    FeedbackProvided,
 }
 
 impl DebugEvent {
@@ -76,6 +145,94 @@ impl DebugEvent {
         Self::new(DebugEventPayload::NewSession)
     }
 
+    pub fn approval_requested(
+        tool_call_id: impl Into<String>,
+        tool_call: impl Into<String>,
+        details: Option<String>,
+    ) -> Self {
+        Self::new(DebugEventPayload::ApprovalRequested {
+            tool_call_id: tool_call_id.into(),
+            tool_call: tool_call.into(),
+            details,
+        })
+    }
+
+    pub fn approval_resolved(
+        tool_call_id: impl Into<String>,
+        decision: ApprovalDecision,
+        waited: Duration,
+    ) -> Self {
+        Self::new(DebugEventPayload::ApprovalResolved {
+            tool_call_id: tool_call_id.into(),
+            decision,
+            waited_ms: waited.as_millis() as u64,
+        })
+    }
+
+    pub fn error(category: ErrorCategory, message: impl Into<String>) -> Self {
+        Self::new(DebugEventPayload::Error {
+            category,
+            message: message.into(),
+        })
+    }
+
+    pub fn turn_diff_stat(turn: usize, files: Vec<FileDiffStat>) -> Self {
+        let lines_added = files.iter().filter_map(|f| f.lines_added).sum();
+        let lines_removed = files.iter().filter_map(|f| f.lines_removed).sum();
+        Self::new(DebugEventPayload::TurnDiffStat {
+            turn,
+            files,
+            lines_added,
+            lines_removed,
+        })
+    }
+
+    /// A one-line summary of the event that leaves out its contents (prompts, file contents,
+    /// command output, etc.), eg. for crash reports.
+    pub fn summary(&self) -> String {
+        let summary = match &self.payload {
+            DebugEventPayload::LlmRequest { history, .. } => {
+                format!("llm_request ({} messages in history)", history.len())
+            }
+            DebugEventPayload::AssistantText { text } => {
+                format!("assistant_text ({} chars)", text.len())
+            }
+            DebugEventPayload::ToolCall { tool_call } => {
+                format!("tool_call {}", tool_call.function.name)
+            }
+            DebugEventPayload::Reasoning { .. } => "reasoning".to_string(),
+            DebugEventPayload::ToolResult(result) => format!("tool_result {}", result.id),
+            DebugEventPayload::StreamComplete => "stream_complete".to_string(),
+            DebugEventPayload::TurnComplete { history } => {
+                format!("turn_complete ({} messages in history)", history.len())
+            }
+            DebugEventPayload::Interrupted => "interrupted".to_string(),
+            DebugEventPayload::NewSession => "new_session".to_string(),
+            DebugEventPayload::ApprovalRequested { tool_call, .. } => {
+                format!("approval_requested {tool_call}")
+            }
+            DebugEventPayload::ApprovalResolved {
+                decision,
+                waited_ms,
+                ..
+            } => format!("approval_resolved {decision:?} after {waited_ms}ms"),
+            DebugEventPayload::Error { category, message } => {
+                format!("error {category:?} ({} chars)", message.len())
+            }
+            DebugEventPayload::TurnDiffStat {
+                turn,
+                files,
+                lines_added,
+                lines_removed,
+            } => format!(
+                "turn_diff_stat turn {turn} ({} files, +{lines_added} -{lines_removed})",
+                files.len()
+            ),
+        };
+
+        format!("{} {summary}", self.timestamp.to_rfc3339())
+    }
+
     fn new(payload: DebugEventPayload) -> Self {
         Self {
             timestamp: Utc::now(),
@@ -84,28 +241,207 @@ impl DebugEvent {
     }
 }
 
+/// A debug event along with its position in the stream of events; IDs increase monotonically.
+#[derive(Debug, Clone)]
+pub struct SequencedDebugEvent {
+    pub id: u64,
+    pub event: DebugEvent,
+}
+
+struct DebugChannel {
+    tx: Sender<SequencedDebugEvent>,
+    // also guards the assignment of IDs, so that subscribers get events without gaps or
+    // duplicates
+    history: Mutex<History>,
+}
+
+struct History {
+    next_id: u64,
+    len: usize,
+    events: VecDeque<SequencedDebugEvent>,
+}
+
+pub fn debug_channel() -> (DebugEventSender, DebugEventReceiver) {
+    debug_channel_with_history(HISTORY_LEN)
+}
+
+/// A debug channel that keeps `history_len` recent events around.
+pub fn debug_channel_with_history(history_len: usize) -> (DebugEventSender, DebugEventReceiver) {
+    let (tx, _) = broadcast::channel(CHANNEL_CAPACITY);
+    let channel = Arc::new(DebugChannel {
+        tx,
+        history: Mutex::new(History {
+            next_id: 1,
+            len: history_len,
+            events: VecDeque::with_capacity(history_len),
+        }),
+    });
+
+    (
+        DebugEventSender(Arc::clone(&channel)),
+        DebugEventReceiver(channel),
+    )
+}
+
 #[derive(Clone)]
-pub struct DebugEventSender(Sender<DebugEvent>);
+pub struct DebugEventSender(Arc<DebugChannel>);
 
 impl DebugEventSender {
-    pub fn new(sender: Sender<DebugEvent>) -> Self {
-        Self(sender)
-    }
-
     pub fn send(&self, event: DebugEvent) {
-        let _ = self.0.send(event);
+        let Ok(mut history) = self.0.history.lock() else {
+            return;
+        };
+
+        let event = SequencedDebugEvent {
+            id: history.next_id,
+            event,
+        };
+        history.next_id += 1;
+        if history.events.len() >= history.len {
+            history.events.pop_front();
+        }
+        history.events.push_back(event.clone());
+
+        let _ = self.0.tx.send(event);
     }
 }
 
 #[derive(Clone)]
-pub struct DebugEventReceiver(Sender<DebugEvent>);
+pub struct DebugEventReceiver(Arc<DebugChannel>);
 
 impl DebugEventReceiver {
-    pub fn new(sender: Sender<DebugEvent>) -> Self {
-        Self(sender)
+    /// Returns the buffered events that come after `last_event_id` (all of them if it's not
+    /// provided), along with a receiver for events sent from here on.
+    pub fn subscribe(
+        &self,
+        last_event_id: Option<u64>,
+    ) -> (Vec<SequencedDebugEvent>, Receiver<SequencedDebugEvent>) {
+        let history = match self.0.history.lock() {
+            Ok(h) => h,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        let missed = history
+            .events
+            .iter()
+            .filter(|e| last_event_id.is_none_or(|id| e.id > id))
+            .cloned()
+            .collect();
+
+        (missed, self.0.tx.subscribe())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::assert_snapshot;
+
+    #[test]
+    fn subscribing_with_a_last_event_id_returns_events_missed_since() {
+        // GIVEN
+        let (tx, rx) = debug_channel();
+        tx.send(DebugEvent::new_session());
+        tx.send(DebugEvent::assistant_text("one"));
+        tx.send(DebugEvent::assistant_text("two"));
+
+        // WHEN
+        let (missed, mut live) = rx.subscribe(Some(1));
+        tx.send(DebugEvent::interrupted());
+
+        // THEN
+        let missed_ids = missed.iter().map(|e| e.id).collect::<Vec<_>>();
+        assert_eq!(missed_ids, vec![2, 3]);
+        assert_eq!(live.try_recv().map(|e| e.id).ok(), Some(4));
     }
 
-    pub fn subscribe(&self) -> Receiver<DebugEvent> {
-        self.0.subscribe()
+    #[test]
+    fn turn_diff_stats_add_up_lines_of_text_files() {
+        // GIVEN
+        let files = vec![
+            FileDiffStat {
+                path: "src/main.rs".to_string(),
+                change: FileChangeKind::Edited,
+                lines_added: Some(3),
+                lines_removed: Some(1),
+            },
+            FileDiffStat {
+                path: "src/cli.rs".to_string(),
+                change: FileChangeKind::Created,
+                lines_added: Some(40),
+                lines_removed: Some(0),
+            },
+            FileDiffStat {
+                path: "logo.png".to_string(),
+                change: FileChangeKind::Deleted,
+                lines_added: None,
+                lines_removed: None,
+            },
+        ];
+
+        // WHEN
+        let event = DebugEvent::turn_diff_stat(2, files);
+
+        // THEN
+        let payload = serde_json::to_string_pretty(&event.payload)
+            .expect("payload should've been serialized");
+        assert_snapshot!(payload, @r#"
+        {
+          "kind": "turn_diff_stat",
+          "turn": 2,
+          "files": [
+            {
+              "path": "src/main.rs",
+              "change": "edited",
+              "lines_added": 3,
+              "lines_removed": 1
+            },
+            {
+              "path": "src/cli.rs",
+              "change": "created",
+              "lines_added": 40,
+              "lines_removed": 0
+            },
+            {
+              "path": "logo.png",
+              "change": "deleted",
+              "lines_added": null,
+              "lines_removed": null
+            }
+          ],
+          "lines_added": 43,
+          "lines_removed": 1
+        }
+        "#);
+    }
+
+    #[test]
+    fn summaries_leave_out_contents() {
+        // GIVEN
+        let events = [
+            DebugEvent::assistant_text("here's the API key: sk-ant-0123456789"),
+            DebugEvent::error(
+                ErrorCategory::Provider,
+                "invalid request: {\"messages\":[{\"content\":\"my password is hunter22\"}]}",
+            ),
+        ];
+
+        // WHEN
+        let summaries = events
+            .iter()
+            .map(|e| {
+                e.summary()
+                    .split_once(' ')
+                    .map(|(_, summary)| summary.to_string())
+                    .unwrap_or_default()
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        // THEN
+        assert_snapshot!(summaries, @r"
+        assistant_text (37 chars)
+        error Provider (69 chars)
+        ");
     }
 }
@@ -0,0 +1,386 @@
+use super::task_list::{read_block_scalar, unquote};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Scripted tasks for evaluating a model on a codebase, defined via the same subset of YAML as
+/// task lists. For example:
+///
+/// ```yaml
+/// tasks:
+///   - name: add-fn
+///     prompt: Add a function `add` to src/lib.rs that adds two numbers
+///     expect:
+///       - src/lib.rs contains "pub fn add"
+///       - src/lib.rs lacks "todo!()"
+///       - src/add.rs is absent
+/// ```
+///
+/// Each expectation is a path followed by one of "exists", "is absent", "contains <text>", or
+/// "lacks <text>"; texts can be quoted.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EvalSuite {
+    pub tasks: Vec<EvalTask>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EvalTask {
+    /// Used to name the task's branch and log file.
+    pub name: String,
+    pub prompt: String,
+    pub expect: Vec<FileAssertion>,
+}
+
+/// What's expected of a file (relative to the project directory) once a task is done.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FileAssertion {
+    pub path: String,
+    pub check: FileCheck,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileCheck {
+    Exists,
+    Absent,
+    Contains(String),
+    Lacks(String),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum EvalSuiteError {
+    #[error("line {0}: expected \"key: value\"")]
+    InvalidLine(usize),
+    #[error("line {0}: unknown key \"{1}\"; allowed keys: [tasks]")]
+    UnknownKey(usize, String),
+    #[error("line {0}: unknown task key \"{1}\"; allowed keys: [name, prompt, expect]")]
+    UnknownTaskKey(usize, String),
+    #[error("line {0}: expected a task (\"- name: ...\")")]
+    ExpectedTask(usize),
+    #[error(
+        "line {0}: invalid expectation; expected \"<path> exists\", \"<path> is absent\", \"<path> contains <text>\", or \"<path> lacks <text>\""
+    )]
+    InvalidAssertion(usize),
+    #[error("task {0} is missing \"{1}\"")]
+    MissingTaskKey(usize, &'static str),
+    #[error("task \"{0}\" doesn't expect anything; add expectations under \"expect\"")]
+    NoAssertions(String),
+    #[error("invalid task name \"{0}\"; names can only contain letters, numbers, \"-\", and \"_\"")]
+    InvalidTaskName(String),
+    #[error("task name \"{0}\" is used more than once")]
+    DuplicateTaskName(String),
+    #[error("no tasks defined")]
+    NoTasks,
+}
+
+#[derive(Default)]
+struct PartialTask {
+    name: Option<String>,
+    prompt: Option<String>,
+    expect: Vec<FileAssertion>,
+    /// The column of the "expect" key, if the lines following it are expectations.
+    expect_indent: Option<usize>,
+}
+
+impl FromStr for EvalSuite {
+    type Err = EvalSuiteError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lines = s.lines().collect::<Vec<_>>();
+        let mut partial_tasks: Vec<PartialTask> = vec![];
+        let mut in_tasks = false;
+
+        let mut i = 0;
+        while i < lines.len() {
+            let line = lines[i];
+            let line_number = i + 1;
+            i += 1;
+
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+
+            let indent = line.len() - line.trim_start().len();
+            if indent == 0 {
+                let (key, value) = split_key_value(trimmed, line_number)?;
+                match key {
+                    "tasks" if value.is_empty() => in_tasks = true,
+                    "tasks" => return Err(EvalSuiteError::ExpectedTask(line_number)),
+                    _ => return Err(EvalSuiteError::UnknownKey(line_number, key.to_string())),
+                }
+                continue;
+            }
+
+            if !in_tasks {
+                return Err(EvalSuiteError::InvalidLine(line_number));
+            }
+
+            if let Some(task) = partial_tasks.last_mut()
+                && let Some(expect_indent) = task.expect_indent
+            {
+                if indent > expect_indent {
+                    let assertion = trimmed
+                        .strip_prefix("- ")
+                        .and_then(|a| parse_assertion(a.trim()))
+                        .ok_or(EvalSuiteError::InvalidAssertion(line_number))?;
+                    task.expect.push(assertion);
+                    continue;
+                }
+                task.expect_indent = None;
+            }
+
+            // the key's column, which block scalars need to be indented beyond
+            let (entry, key_indent) = match trimmed.strip_prefix("- ") {
+                Some(rest) => {
+                    partial_tasks.push(PartialTask::default());
+                    (rest.trim_start(), line.len() - rest.trim_start().len())
+                }
+                None => (trimmed, indent),
+            };
+
+            let task = partial_tasks
+                .last_mut()
+                .ok_or(EvalSuiteError::ExpectedTask(line_number))?;
+            let (key, value) = split_key_value(entry, line_number)?;
+            if key == "expect" {
+                if !value.is_empty() {
+                    return Err(EvalSuiteError::InvalidAssertion(line_number));
+                }
+                task.expect_indent = Some(key_indent);
+                continue;
+            }
+
+            let value = match value {
+                "|" | ">" => {
+                    let (block, consumed) = read_block_scalar(&lines[i..], key_indent, value);
+                    i += consumed;
+                    block
+                }
+                _ => unquote(value),
+            };
+
+            match key {
+                "name" => task.name = Some(value),
+                "prompt" => task.prompt = Some(value),
+                _ => return Err(EvalSuiteError::UnknownTaskKey(line_number, key.to_string())),
+            }
+        }
+
+        let mut names = HashSet::new();
+        let mut tasks = Vec::with_capacity(partial_tasks.len());
+        for (index, task) in partial_tasks.into_iter().enumerate() {
+            let name = task
+                .name
+                .ok_or(EvalSuiteError::MissingTaskKey(index + 1, "name"))?;
+            let prompt = task
+                .prompt
+                .filter(|p| !p.trim().is_empty())
+                .ok_or(EvalSuiteError::MissingTaskKey(index + 1, "prompt"))?;
+
+            if name.is_empty()
+                || !name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            {
+                return Err(EvalSuiteError::InvalidTaskName(name));
+            }
+            if !names.insert(name.clone()) {
+                return Err(EvalSuiteError::DuplicateTaskName(name));
+            }
+            if task.expect.is_empty() {
+                return Err(EvalSuiteError::NoAssertions(name));
+            }
+
+            tasks.push(EvalTask {
+                name,
+                prompt,
+                expect: task.expect,
+            });
+        }
+
+        if tasks.is_empty() {
+            return Err(EvalSuiteError::NoTasks);
+        }
+
+        Ok(Self { tasks })
+    }
+}
+
+impl FileAssertion {
+    /// Checks the assertion against the files in `dir`; returns why it doesn't hold, if it
+    /// doesn't.
+    pub fn failure(&self, dir: &Path) -> Option<String> {
+        let path = dir.join(&self.path);
+        let contents = || std::fs::read_to_string(&path).ok();
+
+        match &self.check {
+            FileCheck::Exists if !path.exists() => Some(format!("{} doesn't exist", self.path)),
+            FileCheck::Absent if path.exists() => Some(format!("{} exists", self.path)),
+            FileCheck::Contains(text) => match contents() {
+                None => Some(format!("{} couldn't be read", self.path)),
+                Some(c) if !c.contains(text.as_str()) => {
+                    Some(format!("{} doesn't contain {text:?}", self.path))
+                }
+                Some(_) => None,
+            },
+            FileCheck::Lacks(text) => match contents() {
+                Some(c) if c.contains(text.as_str()) => {
+                    Some(format!("{} contains {text:?}", self.path))
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for FileAssertion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.check {
+            FileCheck::Exists => write!(f, "{} exists", self.path),
+            FileCheck::Absent => write!(f, "{} is absent", self.path),
+            FileCheck::Contains(text) => write!(f, "{} contains {text:?}", self.path),
+            FileCheck::Lacks(text) => write!(f, "{} lacks {text:?}", self.path),
+        }
+    }
+}
+
+fn parse_assertion(assertion: &str) -> Option<FileAssertion> {
+    let (path, rest) = assertion.split_once(char::is_whitespace)?;
+    let rest = rest.trim();
+    let check = match rest {
+        "exists" => FileCheck::Exists,
+        "is absent" => FileCheck::Absent,
+        _ => match rest.split_once(char::is_whitespace)? {
+            ("contains", text) => FileCheck::Contains(unquote(text.trim())),
+            ("lacks", text) => FileCheck::Lacks(unquote(text.trim())),
+            _ => return None,
+        },
+    };
+
+    Some(FileAssertion {
+        path: unquote(path),
+        check,
+    })
+}
+
+fn split_key_value(entry: &str, line_number: usize) -> Result<(&str, &str), EvalSuiteError> {
+    entry
+        .split_once(':')
+        .map(|(k, v)| (k.trim(), v.trim()))
+        .filter(|(k, _)| !k.is_empty())
+        .ok_or(EvalSuiteError::InvalidLine(line_number))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::{assert_snapshot, assert_yaml_snapshot};
+
+    #[test]
+    fn parsing_an_eval_suite_works() {
+        // GIVEN
+        let contents = r#"# arithmetic
+tasks:
+  - name: add-fn
+    prompt: Add a function `add` to src/lib.rs
+    expect:
+      - src/lib.rs contains "pub fn add"
+      - src/lib.rs lacks todo!()
+      - src/add.rs is absent
+  - name: readme
+    expect:
+      - README.md exists
+    prompt: |
+      Add a README.
+
+      Keep it short.
+"#;
+
+        // WHEN
+        let suite = EvalSuite::from_str(contents).expect("eval suite should've been parsed");
+
+        // THEN
+        assert_yaml_snapshot!(suite, @r#"
+        tasks:
+          - name: add-fn
+            prompt: "Add a function `add` to src/lib.rs"
+            expect:
+              - path: src/lib.rs
+                check:
+                  contains: pub fn add
+              - path: src/lib.rs
+                check:
+                  lacks: todo!()
+              - path: src/add.rs
+                check: absent
+          - name: readme
+            prompt: "Add a README.\n\nKeep it short."
+            expect:
+              - path: README.md
+                check: exists
+        "#);
+    }
+
+    #[test]
+    fn parsing_an_eval_suite_fails_for_invalid_expectations() {
+        // GIVEN
+        let contents = r#"tasks:
+  - name: add-fn
+    prompt: Add a function `add` to src/lib.rs
+    expect:
+      - src/lib.rs has "pub fn add"
+"#;
+
+        // WHEN
+        let result = EvalSuite::from_str(contents);
+
+        // THEN
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            r#"line 5: invalid expectation; expected "<path> exists", "<path> is absent", "<path> contains <text>", or "<path> lacks <text>""#
+        );
+    }
+
+    #[test]
+    fn file_assertions_are_checked_against_a_directory() -> anyhow::Result<()> {
+        // GIVEN
+        let temp_dir = tempfile::tempdir()?;
+        let dir = temp_dir.path();
+        std::fs::write(
+            dir.join("lib.rs"),
+            "pub fn add(a: u8, b: u8) -> u8 { todo!() }",
+        )?;
+        let assertion = |path: &str, check| FileAssertion {
+            path: path.to_string(),
+            check,
+        };
+        let assertions = [
+            assertion("lib.rs", FileCheck::Exists),
+            assertion("lib.rs", FileCheck::Contains("pub fn add".to_string())),
+            assertion("lib.rs", FileCheck::Lacks("todo!()".to_string())),
+            assertion("add.rs", FileCheck::Absent),
+            assertion("add.rs", FileCheck::Contains("fn add".to_string())),
+        ];
+
+        // WHEN
+        let failures = assertions
+            .iter()
+            .map(|a| format!("{a}: {:?}", a.failure(dir)))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        // THEN
+        assert_snapshot!(failures, @r#"
+        lib.rs exists: None
+        lib.rs contains "pub fn add": None
+        lib.rs lacks "todo!()": Some("lib.rs contains \"todo!()\"")
+        add.rs is absent: None
+        add.rs contains "fn add": Some("add.rs couldn't be read")
+        "#);
+
+        Ok(())
+    }
+}
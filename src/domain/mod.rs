@@ -1,11 +1,27 @@
+//! Types shared across agx: config, providers, templates, debug events, etc.
+
 mod cmd;
 mod config;
+mod cron;
 mod debug;
+mod eval_suite;
 mod message;
+mod output;
 mod provider;
+mod provider_config;
+mod task_list;
+mod template;
+mod user_config;
 
 pub use cmd::*;
 pub use config::*;
+pub use cron::*;
 pub use debug::*;
+pub use eval_suite::*;
 pub use message::*;
+pub use output::*;
 pub use provider::*;
+pub use provider_config::*;
+pub use task_list::*;
+pub use template::*;
+pub use user_config::*;
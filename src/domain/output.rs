@@ -0,0 +1,254 @@
+use colored::Colorize;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::io::Write;
+use std::sync::Mutex;
+
+/// Kinds of output a session produces, so that sinks can present them differently.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputKind {
+    /// The model's response; streamed in chunks.
+    Response,
+    Reasoning,
+    /// The prompt being worked on.
+    Prompt,
+    /// Tool calls, as they're executed.
+    ToolCall,
+    /// Tool calls that need the user's approval.
+    ApprovalRequest,
+    /// The banner shown when an interactive session starts.
+    Banner,
+    /// The line shown above the input prompt; it's colored by the session itself.
+    Status,
+    Info,
+    Success,
+    Warning,
+    Error,
+}
+
+/// Where a session's output goes. Text is emitted as is; line breaks are a part of it.
+pub trait OutputSink: Send + Sync {
+    fn emit(&self, kind: OutputKind, text: &str);
+}
+
+/// Prints output to stdout, colored according to its kind.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TerminalSink;
+
+impl OutputSink for TerminalSink {
+    fn emit(&self, kind: OutputKind, text: &str) {
+        let text = match kind {
+            OutputKind::Response | OutputKind::Status => text.normal(),
+            OutputKind::Reasoning | OutputKind::ToolCall => text.cyan(),
+            OutputKind::Prompt => text.bright_blue(),
+            OutputKind::ApprovalRequest => text.bright_purple(),
+            OutputKind::Banner => text.purple(),
+            OutputKind::Info => text.blue(),
+            OutputKind::Success => text.green(),
+            OutputKind::Warning => text.yellow(),
+            OutputKind::Error => text.red(),
+        };
+
+        print!("{text}");
+        let _ = std::io::stdout().flush();
+    }
+}
+
+/// Writes output as JSON lines (eg. `{"kind":"response","text":"..."}`), one per chunk, for
+/// programs driving agx. ANSI escape codes are stripped.
+pub struct JsonStreamSink<W> {
+    writer: Mutex<W>,
+}
+
+#[derive(Serialize)]
+struct JsonOutput<'a> {
+    kind: OutputKind,
+    text: &'a str,
+}
+
+impl<W: Write + Send> JsonStreamSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: Mutex::new(writer),
+        }
+    }
+}
+
+impl<W: Write + Send> OutputSink for JsonStreamSink<W> {
+    fn emit(&self, kind: OutputKind, text: &str) {
+        let text = console::strip_ansi_codes(text);
+        let Ok(line) = serde_json::to_string(&JsonOutput { kind, text: &text }) else {
+            return;
+        };
+
+        if let Ok(mut writer) = self.writer.lock() {
+            let _ = writeln!(writer, "{line}").and_then(|_| writer.flush());
+        }
+    }
+}
+
+/// Writes output as plain text, with ANSI escape codes stripped; for logs of unattended runs.
+pub struct PlainTextSink<W> {
+    writer: Mutex<W>,
+}
+
+impl<W: Write + Send> PlainTextSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: Mutex::new(writer),
+        }
+    }
+}
+
+impl<W: Write + Send> OutputSink for PlainTextSink<W> {
+    fn emit(&self, _kind: OutputKind, text: &str) {
+        let text = console::strip_ansi_codes(text);
+        if let Ok(mut writer) = self.writer.lock() {
+            let _ = writer
+                .write_all(text.as_bytes())
+                .and_then(|_| writer.flush());
+        }
+    }
+}
+
+/// A contiguous stretch of output of a single kind.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OutputBlock {
+    pub kind: OutputKind,
+    pub text: String,
+}
+
+/// Keeps the most recent output in memory as blocks, for UIs that render it themselves (eg. a
+/// TUI). Consecutive chunks of the same kind (eg. a streamed response) are merged into a single
+/// block.
+pub struct BufferSink {
+    blocks: Mutex<VecDeque<OutputBlock>>,
+    capacity: usize,
+}
+
+impl BufferSink {
+    /// Creates a buffer that keeps at most `capacity` blocks, dropping the oldest ones first.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            blocks: Mutex::new(VecDeque::new()),
+            capacity,
+        }
+    }
+
+    pub fn blocks(&self) -> Vec<OutputBlock> {
+        self.blocks
+            .lock()
+            .map(|b| b.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Removes and returns the buffered blocks.
+    pub fn drain(&self) -> Vec<OutputBlock> {
+        self.blocks
+            .lock()
+            .map(|mut b| b.drain(..).collect())
+            .unwrap_or_default()
+    }
+}
+
+impl OutputSink for BufferSink {
+    fn emit(&self, kind: OutputKind, text: &str) {
+        let Ok(mut blocks) = self.blocks.lock() else {
+            return;
+        };
+
+        match blocks.back_mut() {
+            Some(last) if last.kind == kind => last.text.push_str(text),
+            _ => {
+                blocks.push_back(OutputBlock {
+                    kind,
+                    text: text.to_string(),
+                });
+                while blocks.len() > self.capacity {
+                    blocks.pop_front();
+                }
+            }
+        }
+    }
+}
+
+/// Records every chunk of output as it's emitted; meant for asserting on a session's output in
+/// tests.
+#[derive(Default)]
+pub struct CollectingSink {
+    chunks: Mutex<Vec<(OutputKind, String)>>,
+}
+
+impl CollectingSink {
+    pub fn chunks(&self) -> Vec<(OutputKind, String)> {
+        self.chunks.lock().map(|c| c.clone()).unwrap_or_default()
+    }
+
+    /// All output of a kind, concatenated.
+    pub fn text_of(&self, kind: OutputKind) -> String {
+        self.chunks()
+            .into_iter()
+            .filter(|(k, _)| *k == kind)
+            .map(|(_, text)| text)
+            .collect()
+    }
+}
+
+impl OutputSink for CollectingSink {
+    fn emit(&self, kind: OutputKind, text: &str) {
+        if let Ok(mut chunks) = self.chunks.lock() {
+            chunks.push((kind, text.to_string()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::{assert_snapshot, assert_yaml_snapshot};
+
+    #[test]
+    fn buffer_sink_merges_consecutive_chunks_of_the_same_kind() {
+        // GIVEN
+        let sink = BufferSink::new(2);
+
+        // WHEN
+        sink.emit(OutputKind::Prompt, "> hi\n");
+        sink.emit(OutputKind::ToolCall, "read_file: src/main.rs ");
+        sink.emit(OutputKind::Success, "✓\n");
+        sink.emit(OutputKind::Response, "Hello");
+        sink.emit(OutputKind::Response, ", there!\n");
+
+        // THEN
+        assert_yaml_snapshot!(sink.blocks(), @r#"
+        - kind: success
+          text: "✓\n"
+        - kind: response
+          text: "Hello, there!\n"
+        "#);
+    }
+
+    #[test]
+    fn json_stream_sink_writes_a_line_per_chunk() {
+        // GIVEN
+        let sink = JsonStreamSink::new(Vec::new());
+
+        // WHEN
+        sink.emit(OutputKind::Response, "Hello\n");
+        sink.emit(
+            OutputKind::Status,
+            "\u{1b}[33m[anthropic/claude-sonnet-4-5]\u{1b}[0m\n",
+        );
+
+        // THEN
+        let written = sink
+            .writer
+            .into_inner()
+            .expect("lock shouldn't be poisoned");
+        assert_snapshot!(String::from_utf8_lossy(&written), @r#"
+        {"kind":"response","text":"Hello\n"}
+        {"kind":"status","text":"[anthropic/claude-sonnet-4-5]\n"}
+        "#);
+    }
+}
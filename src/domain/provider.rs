@@ -1,13 +1,65 @@
+use serde_json::{Map, Value, json};
 use std::fmt::Display;
 use std::str::FromStr;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum Provider {
     Anthropic,
     Gemini,
     GitHubCopilot,
+    Mistral,
     OpenAI,
+    OpenAICompatible,
     Openrouter,
+    XAi,
+}
+
+impl Provider {
+    /// Some OpenAI compatible servers (eg. LM Studio, llama.cpp) don't support tool calls in
+    /// streaming mode; for these, agx falls back to non-streaming completions.
+    pub fn may_lack_streaming_tool_calls(&self) -> bool {
+        matches!(self, Provider::OpenAICompatible)
+    }
+
+    /// Whether the provider accepts OpenAI's "response_format" request parameter for
+    /// constraining responses to a JSON schema.
+    pub fn supports_json_schema_response_format(&self) -> bool {
+        matches!(
+            self,
+            Provider::OpenAI | Provider::OpenAICompatible | Provider::Openrouter
+        )
+    }
+
+    /// Returns request parameters for limiting the output of a response, in the provider's
+    /// format; `None` if there aren't any to add. The max output tokens are only included for
+    /// providers that don't respect the generic max tokens setting on its own. Stop sequences are
+    /// left out when there aren't any, since some APIs reject an empty list.
+    pub fn output_limit_params(
+        &self,
+        max_output: Option<u64>,
+        stop_sequences: &[String],
+    ) -> Option<Value> {
+        let stop_sequences = (!stop_sequences.is_empty()).then(|| json!(stop_sequences));
+        let params = match self {
+            Provider::Anthropic => json!({ "stop_sequences": stop_sequences? }),
+            Provider::Gemini => {
+                let mut config = Map::new();
+                if let Some(stop_sequences) = stop_sequences {
+                    config.insert("stopSequences".to_string(), stop_sequences);
+                }
+                if let Some(max_output) = max_output {
+                    config.insert("maxOutputTokens".to_string(), json!(max_output));
+                }
+                if config.is_empty() {
+                    return None;
+                }
+                json!({ "generationConfig": config })
+            }
+            _ => json!({ "stop": stop_sequences? }),
+        };
+
+        Some(params)
+    }
 }
 
 impl FromStr for Provider {
@@ -18,10 +70,13 @@ impl FromStr for Provider {
             "anthropic" => Ok(Self::Anthropic),
             "gemini" => Ok(Self::Gemini),
             "github-copilot" => Ok(Self::GitHubCopilot),
+            "mistral" => Ok(Self::Mistral),
             "openai" => Ok(Self::OpenAI),
+            "openai-compatible" => Ok(Self::OpenAICompatible),
             "openrouter" => Ok(Self::Openrouter),
+            "xai" => Ok(Self::XAi),
             _ => Err(
-                "invalid provider; allowed values: [anthropic, gemini, github-copilot, openai, openrouter]",
+                "invalid provider; allowed values: [anthropic, gemini, github-copilot, mistral, openai, openai-compatible, openrouter, xai]",
             ),
         }
     }
@@ -33,10 +88,54 @@ impl Display for Provider {
             Provider::Anthropic => "anthropic",
             Provider::Gemini => "gemini",
             Provider::GitHubCopilot => "github-copilot",
+            Provider::Mistral => "mistral",
             Provider::OpenAI => "openai",
+            Provider::OpenAICompatible => "openai-compatible",
             Provider::Openrouter => "openrouter",
+            Provider::XAi => "xai",
         };
 
         write!(f, "{}", name)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::assert_snapshot;
+
+    #[test]
+    fn output_limit_params_leave_out_stop_sequences_when_there_arent_any() {
+        // GIVEN
+        let providers = [
+            Provider::Anthropic,
+            Provider::Gemini,
+            Provider::OpenAICompatible,
+        ];
+        let stop_sequences = ["END".to_string()];
+        let describe = |params: Option<Value>| params.map_or("none".to_string(), |p| p.to_string());
+
+        // WHEN
+        let mut params = vec![];
+        for provider in providers {
+            params.push(format!(
+                "{provider} (max output only): {}",
+                describe(provider.output_limit_params(Some(1024), &[]))
+            ));
+            params.push(format!(
+                "{provider} (stop sequences only): {}",
+                describe(provider.output_limit_params(None, &stop_sequences))
+            ));
+        }
+
+        // THEN
+        assert_snapshot!(params.join("\n"), @r#"
+        anthropic (max output only): none
+        anthropic (stop sequences only): {"stop_sequences":["END"]}
+        gemini (max output only): {"generationConfig":{"maxOutputTokens":1024}}
+        gemini (stop sequences only): {"generationConfig":{"stopSequences":["END"]}}
+        openai-compatible (max output only): none
+        openai-compatible (stop sequences only): {"stop":["END"]}
+        "#);
+    }
+}
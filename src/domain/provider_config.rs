@@ -0,0 +1,317 @@
+use rig::message::ToolChoice;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+
+/// Settings only applicable when the provider is "openrouter".
+/// See <https://openrouter.ai/docs/features/provider-routing>.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct OpenRouterConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<OpenRouterProviderPrefs>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub transforms: Vec<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct OpenRouterProviderPrefs {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub order: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allow_fallbacks: Option<bool>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub only: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ignore: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub quantizations: Vec<String>,
+}
+
+impl OpenRouterConfig {
+    /// Extra fields to be merged into the body of every completion request.
+    pub fn additional_params(&self) -> Option<Value> {
+        let mut params = serde_json::Map::new();
+
+        if let Some(provider) = &self.provider {
+            params.insert("provider".to_string(), json!(provider));
+        }
+
+        if !self.transforms.is_empty() {
+            params.insert("transforms".to_string(), json!(self.transforms));
+        }
+
+        if params.is_empty() {
+            return None;
+        }
+
+        Some(Value::Object(params))
+    }
+}
+
+/// Settings only applicable when the provider is "gemini".
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct GeminiConfig {
+    /// See <https://ai.google.dev/gemini-api/docs/safety-settings>.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub safety_settings: Vec<GeminiSafetySetting>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub function_calling: Option<GeminiFunctionCalling>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GeminiSafetySetting {
+    /// eg. "HARM_CATEGORY_DANGEROUS_CONTENT"
+    pub category: String,
+    /// eg. "BLOCK_ONLY_HIGH", "BLOCK_NONE"
+    pub threshold: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GeminiFunctionCalling {
+    pub mode: GeminiFunctionCallingMode,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_function_names: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GeminiFunctionCallingMode {
+    Auto,
+    Any,
+    None,
+}
+
+impl GeminiConfig {
+    /// Extra fields to be merged into the body of every completion request.
+    pub fn additional_params(&self) -> Option<Value> {
+        if self.safety_settings.is_empty() {
+            return None;
+        }
+
+        Some(json!({ "safetySettings": self.safety_settings }))
+    }
+
+    pub fn tool_choice(&self) -> Option<ToolChoice> {
+        let function_calling = self.function_calling.as_ref()?;
+
+        let tool_choice = match function_calling.mode {
+            GeminiFunctionCallingMode::Auto => ToolChoice::Auto,
+            GeminiFunctionCallingMode::None => ToolChoice::None,
+            GeminiFunctionCallingMode::Any
+                if function_calling.allowed_function_names.is_empty() =>
+            {
+                ToolChoice::Required
+            }
+            GeminiFunctionCallingMode::Any => ToolChoice::Specific {
+                function_names: function_calling.allowed_function_names.clone(),
+            },
+        };
+
+        Some(tool_choice)
+    }
+}
+
+pub const ANTHROPIC_DEFAULT_MAX_TOKENS: u64 = 200_000;
+const ANTHROPIC_FINE_GRAINED_TOOL_STREAMING_BETA: &str = "fine-grained-tool-streaming-2025-05-14";
+
+/// Settings only applicable when the provider is "anthropic". Top level settings
+/// apply to all models, and can be overridden for specific models via "models".
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct AnthropicConfig {
+    #[serde(flatten)]
+    pub defaults: AnthropicModelConfig,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub models: BTreeMap<String, AnthropicModelConfig>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct AnthropicModelConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u64>,
+    /// Enables extended thinking with the given token budget.
+    /// See <https://docs.claude.com/en/docs/build-with-claude/extended-thinking>.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thinking_budget_tokens: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fine_grained_tool_streaming: Option<bool>,
+}
+
+impl AnthropicConfig {
+    pub fn for_model(&self, model_name: &str) -> AnthropicModelConfig {
+        let defaults = self.defaults.clone();
+        match self.models.get(model_name) {
+            Some(m) => AnthropicModelConfig {
+                max_tokens: m.max_tokens.or(defaults.max_tokens),
+                thinking_budget_tokens: m
+                    .thinking_budget_tokens
+                    .or(defaults.thinking_budget_tokens),
+                fine_grained_tool_streaming: m
+                    .fine_grained_tool_streaming
+                    .or(defaults.fine_grained_tool_streaming),
+            },
+            None => defaults,
+        }
+    }
+}
+
+impl AnthropicModelConfig {
+    pub fn max_tokens(&self) -> u64 {
+        self.max_tokens.unwrap_or(ANTHROPIC_DEFAULT_MAX_TOKENS)
+    }
+
+    /// Extra fields to be merged into the body of every completion request.
+    pub fn additional_params(&self) -> Option<Value> {
+        let budget_tokens = self.thinking_budget_tokens?;
+
+        Some(json!({
+            "thinking": {
+                "type": "enabled",
+                "budget_tokens": budget_tokens,
+            }
+        }))
+    }
+
+    pub fn betas(&self) -> Vec<&'static str> {
+        let mut betas = vec![];
+        if self.fine_grained_tool_streaming.unwrap_or(false) {
+            betas.push(ANTHROPIC_FINE_GRAINED_TOOL_STREAMING_BETA);
+        }
+
+        betas
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::assert_yaml_snapshot;
+
+    #[test]
+    fn openrouter_additional_params_are_none_by_default() {
+        // GIVEN
+        let config = OpenRouterConfig::default();
+
+        // WHEN
+        let params = config.additional_params();
+
+        // THEN
+        assert!(params.is_none());
+    }
+
+    #[test]
+    fn openrouter_additional_params_include_routing_and_transforms() -> anyhow::Result<()> {
+        // GIVEN
+        let config: OpenRouterConfig = serde_json::from_str(
+            r#"
+{
+  "provider": {
+    "order": ["anthropic", "amazon-bedrock"],
+    "allow_fallbacks": false,
+    "quantizations": ["fp8"]
+  },
+  "transforms": ["middle-out"],
+  "headers": {
+    "X-Title": "agx"
+  }
+}
+"#,
+        )?;
+
+        // WHEN
+        let params = config.additional_params();
+
+        // THEN
+        assert_yaml_snapshot!(params, @r"
+        provider:
+          allow_fallbacks: false
+          order:
+            - anthropic
+            - amazon-bedrock
+          quantizations:
+            - fp8
+        transforms:
+          - middle-out
+        ");
+
+        Ok(())
+    }
+
+    #[test]
+    fn gemini_config_is_converted_to_request_params() -> anyhow::Result<()> {
+        // GIVEN
+        let config: GeminiConfig = serde_json::from_str(
+            r#"
+{
+  "safety_settings": [
+    {
+      "category": "HARM_CATEGORY_DANGEROUS_CONTENT",
+      "threshold": "BLOCK_ONLY_HIGH"
+    }
+  ],
+  "function_calling": {
+    "mode": "any",
+    "allowed_function_names": ["read_file"]
+  }
+}
+"#,
+        )?;
+
+        // WHEN
+        let params = config.additional_params();
+        let tool_choice = config.tool_choice();
+
+        // THEN
+        assert_yaml_snapshot!(params, @r"
+        safetySettings:
+          - category: HARM_CATEGORY_DANGEROUS_CONTENT
+            threshold: BLOCK_ONLY_HIGH
+        ");
+        assert_eq!(
+            tool_choice,
+            Some(ToolChoice::Specific {
+                function_names: vec!["read_file".to_string()]
+            })
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn anthropic_model_config_overrides_defaults() -> anyhow::Result<()> {
+        // GIVEN
+        let config: AnthropicConfig = serde_json::from_str(
+            r#"
+{
+  "max_tokens": 32000,
+  "fine_grained_tool_streaming": true,
+  "models": {
+    "claude-opus-4-1": {
+      "thinking_budget_tokens": 10000
+    }
+  }
+}
+"#,
+        )?;
+
+        // WHEN
+        let opus = config.for_model("claude-opus-4-1");
+        let sonnet = config.for_model("claude-sonnet-4-5");
+
+        // THEN
+        assert_eq!(opus.max_tokens(), 32000);
+        assert_eq!(
+            opus.betas(),
+            vec![ANTHROPIC_FINE_GRAINED_TOOL_STREAMING_BETA]
+        );
+        assert_yaml_snapshot!(opus.additional_params(), @r"
+        thinking:
+          budget_tokens: 10000
+          type: enabled
+        ");
+        assert!(sonnet.additional_params().is_none());
+
+        Ok(())
+    }
+}
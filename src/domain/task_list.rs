@@ -0,0 +1,290 @@
+use serde::Serialize;
+use std::collections::HashSet;
+use std::str::FromStr;
+
+const DEFAULT_CONCURRENCY: usize = 4;
+
+/// Independent tasks to be run concurrently, defined via a small subset of YAML. For example:
+///
+/// ```yaml
+/// concurrency: 2
+/// tasks:
+///   - name: api
+///     prompt: Fix all clippy warnings in crates/api
+///   - name: web
+///     prompt: |
+///       Fix all clippy warnings in crates/web.
+///       Don't change any public APIs.
+/// ```
+///
+/// Values can be plain, quoted, or block scalars ("|" keeps line breaks, ">" folds them).
+/// `concurrency` is optional, and defaults to 4.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TaskList {
+    pub concurrency: usize,
+    pub tasks: Vec<TaskSpec>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TaskSpec {
+    /// Used to name the task's branch and log file.
+    pub name: String,
+    pub prompt: String,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TaskListError {
+    #[error("line {0}: expected \"key: value\"")]
+    InvalidLine(usize),
+    #[error("line {0}: unknown key \"{1}\"; allowed keys: [concurrency, tasks]")]
+    UnknownKey(usize, String),
+    #[error("line {0}: unknown task key \"{1}\"; allowed keys: [name, prompt]")]
+    UnknownTaskKey(usize, String),
+    #[error("line {0}: expected a task (\"- name: ...\")")]
+    ExpectedTask(usize),
+    #[error("line {0}: concurrency needs to be a positive number")]
+    InvalidConcurrency(usize),
+    #[error("task {0} is missing \"{1}\"")]
+    MissingTaskKey(usize, &'static str),
+    #[error("invalid task name \"{0}\"; names can only contain letters, numbers, \"-\", and \"_\"")]
+    InvalidTaskName(String),
+    #[error("task name \"{0}\" is used more than once")]
+    DuplicateTaskName(String),
+    #[error("no tasks defined")]
+    NoTasks,
+}
+
+#[derive(Default)]
+struct PartialTask {
+    name: Option<String>,
+    prompt: Option<String>,
+}
+
+impl FromStr for TaskList {
+    type Err = TaskListError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lines = s.lines().collect::<Vec<_>>();
+        let mut concurrency = DEFAULT_CONCURRENCY;
+        let mut partial_tasks: Vec<PartialTask> = vec![];
+        let mut in_tasks = false;
+
+        let mut i = 0;
+        while i < lines.len() {
+            let line = lines[i];
+            let line_number = i + 1;
+            i += 1;
+
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+
+            let indent = line.len() - line.trim_start().len();
+            if indent == 0 {
+                let (key, value) = split_key_value(trimmed, line_number)?;
+                in_tasks = false;
+                match key {
+                    "concurrency" => {
+                        concurrency = value
+                            .parse()
+                            .ok()
+                            .filter(|c| *c > 0)
+                            .ok_or(TaskListError::InvalidConcurrency(line_number))?;
+                    }
+                    "tasks" if value.is_empty() => in_tasks = true,
+                    "tasks" => return Err(TaskListError::ExpectedTask(line_number)),
+                    _ => return Err(TaskListError::UnknownKey(line_number, key.to_string())),
+                }
+                continue;
+            }
+
+            if !in_tasks {
+                return Err(TaskListError::InvalidLine(line_number));
+            }
+
+            // the key's column, which block scalars need to be indented beyond
+            let (entry, key_indent) = match trimmed.strip_prefix("- ") {
+                Some(rest) => {
+                    partial_tasks.push(PartialTask::default());
+                    (rest.trim_start(), line.len() - rest.trim_start().len())
+                }
+                None => (trimmed, indent),
+            };
+
+            let task = partial_tasks
+                .last_mut()
+                .ok_or(TaskListError::ExpectedTask(line_number))?;
+            let (key, value) = split_key_value(entry, line_number)?;
+            let value = match value {
+                "|" | ">" => {
+                    let (block, consumed) = read_block_scalar(&lines[i..], key_indent, value);
+                    i += consumed;
+                    block
+                }
+                _ => unquote(value),
+            };
+
+            match key {
+                "name" => task.name = Some(value),
+                "prompt" => task.prompt = Some(value),
+                _ => return Err(TaskListError::UnknownTaskKey(line_number, key.to_string())),
+            }
+        }
+
+        let mut names = HashSet::new();
+        let mut tasks = Vec::with_capacity(partial_tasks.len());
+        for (index, task) in partial_tasks.into_iter().enumerate() {
+            let name = task
+                .name
+                .ok_or(TaskListError::MissingTaskKey(index + 1, "name"))?;
+            let prompt = task
+                .prompt
+                .filter(|p| !p.trim().is_empty())
+                .ok_or(TaskListError::MissingTaskKey(index + 1, "prompt"))?;
+
+            if name.is_empty()
+                || !name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            {
+                return Err(TaskListError::InvalidTaskName(name));
+            }
+            if !names.insert(name.clone()) {
+                return Err(TaskListError::DuplicateTaskName(name));
+            }
+
+            tasks.push(TaskSpec { name, prompt });
+        }
+
+        if tasks.is_empty() {
+            return Err(TaskListError::NoTasks);
+        }
+
+        Ok(Self { concurrency, tasks })
+    }
+}
+
+fn split_key_value(entry: &str, line_number: usize) -> Result<(&str, &str), TaskListError> {
+    entry
+        .split_once(':')
+        .map(|(k, v)| (k.trim(), v.trim()))
+        .filter(|(k, _)| !k.is_empty())
+        .ok_or(TaskListError::InvalidLine(line_number))
+}
+
+/// Reads the lines of a block scalar that are indented beyond `parent_indent`. Returns the
+/// scalar's value, and the number of lines consumed.
+pub(super) fn read_block_scalar(
+    lines: &[&str],
+    parent_indent: usize,
+    style: &str,
+) -> (String, usize) {
+    let mut block = vec![];
+    let mut block_indent = None;
+
+    for line in lines {
+        let indent = line.len() - line.trim_start().len();
+        if line.trim().is_empty() {
+            block.push("");
+            continue;
+        }
+        if indent <= parent_indent {
+            break;
+        }
+
+        let block_indent = *block_indent.get_or_insert(indent);
+        block.push(line.get(block_indent.min(indent)..).unwrap_or_default());
+    }
+
+    let consumed = block.len();
+    while block.last().is_some_and(|l| l.is_empty()) {
+        block.pop();
+    }
+
+    let value = match style {
+        ">" => block
+            .split(|l| l.is_empty())
+            .map(|paragraph| paragraph.join(" "))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => block.join("\n"),
+    };
+
+    (value, consumed)
+}
+
+pub(super) fn unquote(value: &str) -> String {
+    if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        return serde_json::from_str(value)
+            .unwrap_or_else(|_| value[1..value.len() - 1].to_string());
+    }
+
+    if value.len() >= 2 && value.starts_with('\'') && value.ends_with('\'') {
+        return value[1..value.len() - 1].replace("''", "'");
+    }
+
+    value.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::assert_yaml_snapshot;
+
+    #[test]
+    fn parsing_a_task_list_works() {
+        // GIVEN
+        let contents = r#"# lint fixes
+concurrency: 2
+tasks:
+  - name: api
+    prompt: "Fix all clippy warnings in \"crates/api\""
+  - name: web
+    prompt: |
+      Fix all clippy warnings in crates/web.
+
+      Don't change any public APIs.
+
+  - prompt: >
+      Fix all clippy warnings
+      in crates/cli.
+    name: 'cli'
+"#;
+
+        // WHEN
+        let task_list = TaskList::from_str(contents).expect("task list should've been parsed");
+
+        // THEN
+        assert_yaml_snapshot!(task_list, @r#"
+        concurrency: 2
+        tasks:
+          - name: api
+            prompt: "Fix all clippy warnings in \"crates/api\""
+          - name: web
+            prompt: "Fix all clippy warnings in crates/web.\n\nDon't change any public APIs."
+          - name: cli
+            prompt: Fix all clippy warnings in crates/cli.
+        "#);
+    }
+
+    #[test]
+    fn parsing_a_task_list_fails_for_duplicate_task_names() {
+        // GIVEN
+        let contents = r#"tasks:
+  - name: api
+    prompt: fix lints
+  - name: api
+    prompt: fix tests
+"#;
+
+        // WHEN
+        let result = TaskList::from_str(contents);
+
+        // THEN
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            r#"task name "api" is used more than once"#
+        );
+    }
+}
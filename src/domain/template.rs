@@ -0,0 +1,279 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// How tool calls are approved in a session.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ApprovalMode {
+    /// File changes and commands need confirmation, unless approved via config or during the
+    /// session.
+    #[default]
+    Default,
+    /// Every file change and command needs confirmation; approvals from config aren't applied,
+    /// and none can be saved.
+    Strict,
+    /// File changes and commands are approved without confirmation.
+    Permissive,
+}
+
+impl FromStr for ApprovalMode {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "default" => Ok(Self::Default),
+            "strict" => Ok(Self::Strict),
+            "permissive" => Ok(Self::Permissive),
+            _ => Err("invalid approval mode; allowed values: [default, strict, permissive]"),
+        }
+    }
+}
+
+impl Display for ApprovalMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mode = match self {
+            ApprovalMode::Default => "default",
+            ApprovalMode::Strict => "strict",
+            ApprovalMode::Permissive => "permissive",
+        };
+
+        write!(f, "{mode}")
+    }
+}
+
+/// A reusable setup for a recurring kind of task, defined as a markdown file with a front
+/// matter. For example:
+///
+/// ```markdown
+/// ---
+/// description: upgrade a dependency
+/// tools: [read_file, read_dir, edit_file, run_cmd]
+/// approvals: strict
+/// ---
+///
+/// # system
+///
+/// Only change dependency manifests and lock files.
+///
+/// # prompt
+///
+/// Upgrade {{1}} to its latest version, and fix any breaking changes.
+/// ```
+///
+/// The front matter and the "# system" section are optional; without section headings, the
+/// whole body is used as the prompt. `{{1}}`, `{{2}}`, ... in the prompt are replaced by the
+/// template's arguments, and `{{args}}` by all of them.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct Template {
+    pub description: Option<String>,
+    /// Tools the model is allowed to call; all tools are allowed if not specified.
+    pub tools: Option<Vec<String>>,
+    pub approvals: ApprovalMode,
+    /// Added to the system prompt.
+    pub system: Option<String>,
+    pub prompt: Option<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TemplateError {
+    #[error("front matter is not terminated by \"---\"")]
+    UnterminatedFrontMatter,
+    #[error("invalid front matter line: {0}")]
+    InvalidFrontMatterLine(String),
+    #[error("unknown front matter key: {0}; allowed keys: [description, tools, approvals]")]
+    UnknownKey(String),
+    #[error("{0}")]
+    InvalidApprovalMode(&'static str),
+    #[error("template expects argument {{{{{0}}}}}, but only {1} were provided")]
+    MissingArgument(usize, usize),
+}
+
+#[derive(Clone, Copy)]
+enum Section {
+    System,
+    Prompt,
+}
+
+impl FromStr for Template {
+    type Err = TemplateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut template = Template::default();
+
+        let body = match s.strip_prefix("---\n") {
+            Some(rest) => {
+                let (front_matter, body) = rest
+                    .split_once("\n---\n")
+                    .or_else(|| rest.strip_suffix("\n---").map(|f| (f, "")))
+                    .ok_or(TemplateError::UnterminatedFrontMatter)?;
+                template.parse_front_matter(front_matter)?;
+                body
+            }
+            None => s,
+        };
+
+        let mut untitled = vec![];
+        let mut system = vec![];
+        let mut prompt = vec![];
+        let mut section = None;
+        for line in body.lines() {
+            match line.trim().to_lowercase().as_str() {
+                "# system" => section = Some(Section::System),
+                "# prompt" => section = Some(Section::Prompt),
+                _ => match section {
+                    Some(Section::System) => system.push(line),
+                    Some(Section::Prompt) => prompt.push(line),
+                    None => untitled.push(line),
+                },
+            }
+        }
+
+        // text before the first section is only used if there are no sections
+        if section.is_none() {
+            prompt = untitled;
+        }
+
+        template.system = non_empty(&system);
+        template.prompt = non_empty(&prompt);
+
+        Ok(template)
+    }
+}
+
+impl Template {
+    fn parse_front_matter(&mut self, front_matter: &str) -> Result<(), TemplateError> {
+        for line in front_matter.lines().filter(|l| !l.trim().is_empty()) {
+            let (key, value) = line
+                .split_once(':')
+                .map(|(k, v)| (k.trim(), v.trim()))
+                .ok_or_else(|| TemplateError::InvalidFrontMatterLine(line.to_string()))?;
+
+            match key {
+                "description" => self.description = Some(unquote(value).to_string()),
+                "tools" => {
+                    let tools = value
+                        .trim_start_matches('[')
+                        .trim_end_matches(']')
+                        .split(',')
+                        .map(|t| unquote(t.trim()).to_string())
+                        .filter(|t| !t.is_empty())
+                        .collect();
+                    self.tools = Some(tools);
+                }
+                "approvals" => {
+                    self.approvals = ApprovalMode::from_str(unquote(value))
+                        .map_err(TemplateError::InvalidApprovalMode)?;
+                }
+                _ => return Err(TemplateError::UnknownKey(key.to_string())),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the prompt with placeholders replaced by the provided arguments.
+    pub fn render_prompt(&self, args: &[String]) -> Result<Option<String>, TemplateError> {
+        let Some(prompt) = &self.prompt else {
+            return Ok(None);
+        };
+
+        let mut rendered = String::with_capacity(prompt.len());
+        let mut rest = prompt.as_str();
+        while let Some(start) = rest.find("{{") {
+            rendered.push_str(&rest[..start]);
+            rest = &rest[start..];
+
+            let Some(end) = rest.find("}}") else {
+                break;
+            };
+
+            match &rest[2..end] {
+                "args" => rendered.push_str(&args.join(" ")),
+                n => match n.parse::<usize>() {
+                    Ok(n) if n > 0 => rendered.push_str(
+                        args.get(n - 1)
+                            .ok_or(TemplateError::MissingArgument(n, args.len()))?,
+                    ),
+                    _ => rendered.push_str(&rest[..end + 2]),
+                },
+            }
+            rest = &rest[end + 2..];
+        }
+        rendered.push_str(rest);
+
+        Ok(Some(rendered))
+    }
+}
+
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+fn non_empty(lines: &[&str]) -> Option<String> {
+    let text = lines.join("\n").trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::assert_yaml_snapshot;
+
+    #[test]
+    fn parsing_a_template_with_front_matter_and_sections_works() {
+        // GIVEN
+        let contents = r#"---
+description: "upgrade a dependency"
+tools: [read_file, edit_file, run_cmd]
+approvals: strict
+---
+
+# system
+
+Only change dependency manifests and lock files.
+
+# prompt
+
+Upgrade {{1}} to version {{2}}.
+"#;
+
+        // WHEN
+        let template = Template::from_str(contents).expect("template should've been parsed");
+        let prompt = template
+            .render_prompt(&["serde".to_string(), "1.0.228".to_string()])
+            .expect("prompt should've been rendered");
+
+        // THEN
+        assert_yaml_snapshot!((template, prompt), @r#"
+        - description: upgrade a dependency
+          tools:
+            - read_file
+            - edit_file
+            - run_cmd
+          approvals: strict
+          system: Only change dependency manifests and lock files.
+          prompt: "Upgrade {{1}} to version {{2}}."
+        - Upgrade serde to version 1.0.228.
+        "#);
+    }
+
+    #[test]
+    fn rendering_a_prompt_fails_if_arguments_are_missing() {
+        // GIVEN
+        let template = Template::from_str("Upgrade {{1}} to version {{2}}.")
+            .expect("template should've been parsed");
+
+        // WHEN
+        let result = template.render_prompt(&["serde".to_string()]);
+
+        // THEN
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "template expects argument {{2}}, but only 1 were provided"
+        );
+    }
+}
@@ -0,0 +1,111 @@
+use super::{ApprovalMode, Config};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// User level config (`config.toml` in agx's XDG config directory), for defaults that apply
+/// across projects. Project config is layered over it.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct UserConfig {
+    /// Approval mode sessions start in; templates set their own.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub approval_mode: Option<ApprovalMode>,
+    /// Provider profile used when the provider isn't set via environment variables; AGX_PROFILE
+    /// takes precedence.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, ProviderProfile>,
+    /// Whether `agx self-update` is allowed; turn it off where agx is installed and updated by
+    /// other means (eg. a package manager, or in managed environments). On by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub self_update: Option<bool>,
+    /// Anything that can be set in project config.
+    #[serde(flatten)]
+    pub defaults: Config,
+}
+
+/// A named provider setup, so that switching between providers doesn't involve juggling
+/// environment variables.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderProfile {
+    pub provider: String,
+    pub model: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_url: Option<String>,
+    /// Environment variable holding the API key; API_KEY is used if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key_env: Option<String>,
+}
+
+impl UserConfig {
+    pub fn self_update(&self) -> bool {
+        self.self_update.unwrap_or(true)
+    }
+
+    pub fn profile(&self, name: &str) -> anyhow::Result<&ProviderProfile> {
+        self.profiles.get(name).ok_or_else(|| {
+            anyhow::anyhow!(
+                r#"provider profile "{name}" isn't defined; available: [{}]"#,
+                self.profiles
+                    .keys()
+                    .map(String::as_str)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{WebhookEvent, WebhookKind};
+
+    #[test]
+    fn user_config_can_be_parsed_from_toml() -> anyhow::Result<()> {
+        // GIVEN
+        let contents = r#"
+approval_mode = "strict"
+profile = "work"
+tool_failure_reminders = false
+
+[profiles.work]
+provider = "anthropic"
+model = "claude-sonnet-4-5"
+api_key_env = "WORK_ANTHROPIC_API_KEY"
+
+[models.claude-sonnet-4-5.pricing]
+input_per_million_tokens = 3.0
+output_per_million_tokens = 15.0
+
+[[webhooks]]
+url = "https://hooks.slack.com/services/T000/B000/XXXX"
+kind = "slack"
+events = ["approval_pending", "guardrail_tripped"]
+approval_pending_mins = 10
+"#;
+
+        // WHEN
+        let config: UserConfig = toml::from_str(contents)?;
+
+        // THEN
+        assert_eq!(config.approval_mode, Some(ApprovalMode::Strict));
+        let profile = config.profile("work")?;
+        assert_eq!(
+            (profile.provider.as_str(), profile.model.as_str()),
+            ("anthropic", "claude-sonnet-4-5")
+        );
+        assert!(!config.defaults.tool_failure_reminders());
+        assert!(config.defaults.models.contains_key("claude-sonnet-4-5"));
+        let webhook = &config.defaults.webhooks[0];
+        assert_eq!(webhook.kind, WebhookKind::Slack);
+        assert!(!webhook.notifies_of(WebhookEvent::SessionEnded));
+        assert_eq!(webhook.approval_pending_mins(), 10);
+        assert_eq!(
+            config.profile("home").err().map(|e| e.to_string()),
+            Some(r#"provider profile "home" isn't defined; available: [work]"#.to_string())
+        );
+
+        Ok(())
+    }
+}
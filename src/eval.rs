@@ -0,0 +1,213 @@
+use agx_core::domain::{Config, EvalSuite, EvalTask, PlainTextSink, Provider};
+use agx_core::helpers::get_project_context;
+use agx_core::providers::{ProviderAccess, agent_for};
+use agx_core::session::Session;
+use agx_core::worktree::Worktree;
+use anyhow::Context;
+use chrono::Local;
+use colored::Colorize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// The model tasks are evaluated against, along with the config sessions are run with.
+pub struct EvalTarget {
+    pub provider: Provider,
+    pub access: ProviderAccess,
+    pub model_name: String,
+    pub config: Config,
+}
+
+struct EvalOutcome {
+    name: String,
+    elapsed: Duration,
+    log_path: PathBuf,
+    /// The session's error, and the expectations that weren't met.
+    failures: Vec<String>,
+    /// Where the changes of a failed task were kept, for inspection.
+    kept: Option<String>,
+}
+
+/// Runs the tasks in an eval suite one after another, each in a session of its own (in autopilot
+/// mode, in a git worktree of its own), and checks the files they leave behind against what the
+/// suite expects. The output of each session is written to a log file; worktrees of tasks that
+/// failed are kept for inspection.
+pub async fn run_eval(
+    suite_path: &Path,
+    target: EvalTarget,
+    project_dir: &Path,
+    project_log_dir: &Path,
+) -> anyhow::Result<()> {
+    let contents = tokio::fs::read_to_string(suite_path)
+        .await
+        .with_context(|| {
+            format!(
+                "couldn't read eval suite from {}",
+                suite_path.to_string_lossy()
+            )
+        })?;
+    let suite: EvalSuite = contents.parse().context("couldn't parse eval suite")?;
+
+    let run_id = Local::now().format("%Y-%m-%d-%H-%M-%S").to_string();
+    let log_dir = project_log_dir.join("evals").join(&run_id);
+    tokio::fs::create_dir_all(&log_dir).await.with_context(|| {
+        format!(
+            "couldn't create directory for eval logs: {}",
+            log_dir.to_string_lossy()
+        )
+    })?;
+
+    println!(
+        "{}",
+        format!(
+            "evaluating {}/{} on {} tasks; logs: {}",
+            target.provider,
+            target.model_name,
+            suite.tasks.len(),
+            log_dir.to_string_lossy()
+        )
+        .blue()
+    );
+
+    let mut outcomes = Vec::with_capacity(suite.tasks.len());
+    for task in &suite.tasks {
+        println!("{}", format!("started {}", task.name).blue());
+        let branch = format!("agx/eval-{run_id}/{}", task.name);
+        let log_path = log_dir.join(format!("{}.log", task.name));
+        let outcome = run_eval_task(
+            task,
+            &target,
+            &branch,
+            project_dir,
+            project_log_dir,
+            log_path,
+        )
+        .await
+        .with_context(|| format!("couldn't run task {}", task.name))?;
+        print_outcome(&outcome);
+        outcomes.push(outcome);
+    }
+
+    let passed = outcomes.iter().filter(|o| o.failures.is_empty()).count();
+    println!(
+        "\n{}",
+        format!(
+            "{}/{}: passed {passed} of {} tasks",
+            target.provider,
+            target.model_name,
+            outcomes.len()
+        )
+        .bold()
+    );
+    for outcome in &outcomes {
+        print_outcome(outcome);
+    }
+
+    if passed < outcomes.len() {
+        anyhow::bail!(
+            "{} of {} tasks failed",
+            outcomes.len() - passed,
+            outcomes.len()
+        );
+    }
+
+    Ok(())
+}
+
+async fn run_eval_task(
+    task: &EvalTask,
+    target: &EvalTarget,
+    branch: &str,
+    project_dir: &Path,
+    project_log_dir: &Path,
+    log_path: PathBuf,
+) -> anyhow::Result<EvalOutcome> {
+    let log_file = std::fs::File::create(&log_path).context("couldn't create log file")?;
+    let agent = agent_for(
+        &target.provider,
+        target.access.clone(),
+        &target.model_name,
+        &target.config,
+    )
+    .await?;
+    let worktree = Worktree::create(project_dir, Some(branch.to_string())).await?;
+    let task_dir = worktree.project_dir().to_path_buf();
+
+    let project_context = match get_project_context(&task_dir).await {
+        Ok(context) => context,
+        Err(e) => {
+            worktree.discard().await?;
+            return Err(e);
+        }
+    };
+    let session = Session::builder(
+        agent,
+        target.provider,
+        &target.model_name,
+        task_dir.clone(),
+        project_log_dir.to_path_buf(),
+    )
+    .config(target.config.clone())
+    .project_context(project_context)
+    .output(Arc::new(PlainTextSink::new(log_file)))
+    .build();
+    let mut session = match session {
+        Ok(session) => session,
+        Err(e) => {
+            worktree.discard().await?;
+            return Err(e);
+        }
+    };
+
+    let start = Instant::now();
+    let result = session.run_task(&task.prompt).await;
+    let elapsed = start.elapsed();
+
+    let mut failures = vec![];
+    if let Err(e) = result {
+        failures.push(format!("{e:#}"));
+    }
+    failures.extend(task.expect.iter().filter_map(|a| a.failure(&task_dir)));
+
+    // there's nothing to inspect in worktrees of tasks that passed, or that made no changes
+    let kept = if failures.is_empty() || !worktree.has_changes().await? {
+        worktree.discard().await?;
+        None
+    } else {
+        Some(format!(
+            "{} (branch {})",
+            worktree.path().to_string_lossy(),
+            worktree.branch()
+        ))
+    };
+
+    Ok(EvalOutcome {
+        name: task.name.clone(),
+        elapsed,
+        log_path,
+        failures,
+        kept,
+    })
+}
+
+fn print_outcome(outcome: &EvalOutcome) {
+    let elapsed = format!("{:.1}s", outcome.elapsed.as_secs_f64());
+    if outcome.failures.is_empty() {
+        println!("{}", format!("✓ {} ({elapsed})", outcome.name).green());
+        return;
+    }
+
+    println!(
+        "{}",
+        format!(
+            "✗ {} ({elapsed}): {} (see {})",
+            outcome.name,
+            outcome.failures.join("; "),
+            outcome.log_path.to_string_lossy()
+        )
+        .red()
+    );
+    if let Some(kept) = &outcome.kept {
+        println!("{}", format!("  changes kept in worktree {kept}").red());
+    }
+}
@@ -0,0 +1,171 @@
+use crate::domain::ForgeKind;
+use crate::tools::{BoxFuture, ToolErrorKind};
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::Value;
+
+pub(super) const MAX_ISSUE_COMMENTS: usize = 30;
+
+/// A code forge's API, scoped to a single repository.
+pub trait Forge: Send + Sync {
+    fn kind(&self) -> ForgeKind;
+
+    fn repo(&self) -> &str;
+
+    fn get_issue(&self, number: u64) -> BoxFuture<'_, Result<Issue, ForgeError>>;
+
+    /// Opens a pull request from `head` into `base` (or the repository's default branch).
+    fn create_pull_request<'a>(
+        &'a self,
+        title: &'a str,
+        body: &'a str,
+        head: &'a str,
+        base: Option<&'a str>,
+        draft: bool,
+    ) -> BoxFuture<'a, Result<PullRequest, ForgeError>>;
+
+    fn comment_on_pull_request<'a>(
+        &'a self,
+        number: u64,
+        body: &'a str,
+    ) -> BoxFuture<'a, Result<Comment, ForgeError>>;
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ForgeError {
+    #[error("couldn't send request: {0}")]
+    CouldntSendRequest(#[from] reqwest::Error),
+    #[error("{forge} API returned {status}: {message}")]
+    Api {
+        forge: ForgeKind,
+        status: reqwest::StatusCode,
+        message: String,
+    },
+}
+
+impl ToolErrorKind for ForgeError {
+    fn code(&self) -> &'static str {
+        match self {
+            ForgeError::CouldntSendRequest(_) => "couldnt_send_request",
+            ForgeError::Api { status, .. } => match status.as_u16() {
+                404 => "not_found",
+                400 | 409 | 422 => "validation_failed",
+                _ => "api_error",
+            },
+        }
+    }
+
+    fn hint(&self) -> Option<&'static str> {
+        match self {
+            ForgeError::Api { status, .. } if matches!(status.as_u16(), 400 | 409 | 422) => {
+                Some("for pull requests, make sure the head branch has been pushed to the remote")
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Issue {
+    pub number: u64,
+    pub title: String,
+    pub state: String,
+    pub author: String,
+    pub labels: Vec<String>,
+    pub body: String,
+    pub comments: Vec<IssueComment>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IssueComment {
+    pub author: String,
+    pub body: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PullRequest {
+    pub number: u64,
+    pub url: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Comment {
+    pub url: String,
+}
+
+pub(super) async fn parse_response<T: for<'de> Deserialize<'de>>(
+    forge: ForgeKind,
+    response: reqwest::Response,
+) -> Result<T, ForgeError> {
+    let status = response.status();
+    if !status.is_success() {
+        let text = response.text().await.unwrap_or_default();
+        let message = serde_json::from_str::<Value>(&text)
+            .ok()
+            .and_then(|body| error_message(&body))
+            .unwrap_or(text);
+        return Err(ForgeError::Api {
+            forge,
+            status,
+            message,
+        });
+    }
+
+    Ok(response.json().await?)
+}
+
+/// Extracts the error message from an error response's body. Forges differ in how they report
+/// errors:
+/// - GitHub: {"message": "..."}
+/// - GitLab: {"message": "..." | ["..."] | {"field": ["..."]}} or {"error": "..."}
+/// - Bitbucket: {"error": {"message": "..."}}
+fn error_message(body: &Value) -> Option<String> {
+    let value = body
+        .get("message")
+        .or_else(|| body.get("error").and_then(|e| e.get("message")))
+        .or_else(|| body.get("error"))?;
+
+    match value {
+        Value::String(message) => Some(message.clone()),
+        Value::Array(messages) if messages.iter().all(|m| m.is_string()) => Some(
+            messages
+                .iter()
+                .filter_map(|m| m.as_str())
+                .collect::<Vec<_>>()
+                .join("; "),
+        ),
+        Value::Null => None,
+        other => Some(other.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::assert_yaml_snapshot;
+    use serde_json::json;
+
+    #[test]
+    fn error_messages_are_extracted_from_responses_of_all_forges() {
+        // GIVEN
+        let bodies = [
+            json!({"message": "Not Found", "documentation_url": "https://docs.github.com"}),
+            json!({"message": ["Another open merge request already exists"]}),
+            json!({"error": "invalid_token"}),
+            json!({"type": "error", "error": {"message": "Repository not found"}}),
+            json!({"status": 500}),
+        ];
+
+        // WHEN
+        let messages = bodies.iter().map(error_message).collect::<Vec<_>>();
+
+        // THEN
+        assert_yaml_snapshot!(messages, @r"
+        - Not Found
+        - Another open merge request already exists
+        - invalid_token
+        - Repository not found
+        - ~
+        ");
+    }
+}
@@ -0,0 +1,247 @@
+use super::api::{MAX_ISSUE_COMMENTS, parse_response};
+use super::{Comment, Forge, ForgeError, Issue, IssueComment, PullRequest, RemoteRepo};
+use crate::domain::ForgeKind;
+use crate::tools::BoxFuture;
+use reqwest::header::{HeaderMap, HeaderValue, USER_AGENT};
+use serde::Deserialize;
+use serde_json::json;
+
+const DEFAULT_API_URL: &str = "https://api.bitbucket.org/2.0";
+
+/// A client for Bitbucket Cloud's REST API (2.0), scoped to a single repository.
+#[derive(Debug)]
+pub struct BitbucketClient {
+    http: reqwest::Client,
+    api_url: String,
+    repo: String,
+    auth: BitbucketAuth,
+}
+
+#[derive(Debug)]
+pub enum BitbucketAuth {
+    /// A repository, project, or workspace access token.
+    AccessToken(String),
+    /// A username along with an app password (or an API token).
+    Basic { username: String, password: String },
+}
+
+#[derive(Deserialize)]
+struct ApiUser {
+    display_name: String,
+}
+
+#[derive(Deserialize)]
+struct ApiContent {
+    raw: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ApiIssue {
+    id: u64,
+    title: String,
+    state: String,
+    reporter: Option<ApiUser>,
+    content: Option<ApiContent>,
+}
+
+#[derive(Deserialize)]
+struct ApiPage<T> {
+    values: Vec<T>,
+}
+
+#[derive(Deserialize)]
+struct ApiComment {
+    user: Option<ApiUser>,
+    content: Option<ApiContent>,
+    links: ApiLinks,
+}
+
+#[derive(Deserialize)]
+struct ApiLinks {
+    html: ApiLink,
+}
+
+#[derive(Deserialize)]
+struct ApiLink {
+    href: String,
+}
+
+#[derive(Deserialize)]
+struct ApiPullRequest {
+    id: u64,
+    links: ApiLinks,
+}
+
+impl BitbucketClient {
+    pub fn new(
+        remote: &RemoteRepo,
+        auth: BitbucketAuth,
+        api_url: Option<String>,
+    ) -> anyhow::Result<Self> {
+        let api_url = api_url
+            .map(|u| u.trim_end_matches('/').to_string())
+            .unwrap_or_else(|| DEFAULT_API_URL.to_string());
+
+        let mut headers = HeaderMap::new();
+        headers.insert(USER_AGENT, HeaderValue::from_static("agx"));
+
+        let http = reqwest::Client::builder()
+            .default_headers(headers)
+            .build()?;
+
+        Ok(Self {
+            http,
+            api_url,
+            repo: remote.path.clone(),
+            auth,
+        })
+    }
+
+    async fn fetch_issue(&self, number: u64) -> Result<Issue, ForgeError> {
+        let issue: ApiIssue = self
+            .get(&format!("/repositories/{}/issues/{number}", self.repo))
+            .await?;
+        let comments: ApiPage<ApiComment> = self
+            .get(&format!(
+                "/repositories/{}/issues/{number}/comments?pagelen={MAX_ISSUE_COMMENTS}",
+                self.repo
+            ))
+            .await?;
+
+        Ok(Issue {
+            number: issue.id,
+            title: issue.title,
+            state: issue.state,
+            author: display_name(issue.reporter),
+            // Bitbucket issues don't have labels
+            labels: vec![],
+            body: raw_content(issue.content),
+            comments: comments
+                .values
+                .into_iter()
+                .map(|c| IssueComment {
+                    author: display_name(c.user),
+                    body: raw_content(c.content),
+                })
+                .collect(),
+        })
+    }
+
+    async fn open_pull_request(
+        &self,
+        title: &str,
+        body: &str,
+        head: &str,
+        base: Option<&str>,
+        draft: bool,
+    ) -> Result<PullRequest, ForgeError> {
+        let mut request = json!({
+            "title": title,
+            "description": body,
+            "source": { "branch": { "name": head } },
+            "draft": draft,
+        });
+        // Bitbucket uses the repository's main branch if a destination isn't provided
+        if let Some(base) = base {
+            request["destination"] = json!({ "branch": { "name": base } });
+        }
+
+        let pull_request: ApiPullRequest = self
+            .post(
+                &format!("/repositories/{}/pullrequests", self.repo),
+                request,
+            )
+            .await?;
+
+        Ok(PullRequest {
+            number: pull_request.id,
+            url: pull_request.links.html.href,
+        })
+    }
+
+    async fn comment(&self, number: u64, body: &str) -> Result<Comment, ForgeError> {
+        let comment: ApiComment = self
+            .post(
+                &format!("/repositories/{}/pullrequests/{number}/comments", self.repo),
+                json!({ "content": { "raw": body } }),
+            )
+            .await?;
+
+        Ok(Comment {
+            url: comment.links.html.href,
+        })
+    }
+
+    fn authenticate(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match &self.auth {
+            BitbucketAuth::AccessToken(token) => request.bearer_auth(token),
+            BitbucketAuth::Basic { username, password } => {
+                request.basic_auth(username, Some(password))
+            }
+        }
+    }
+
+    async fn get<T: for<'de> Deserialize<'de>>(&self, path: &str) -> Result<T, ForgeError> {
+        let response = self
+            .authenticate(self.http.get(format!("{}{path}", self.api_url)))
+            .send()
+            .await?;
+
+        parse_response(ForgeKind::Bitbucket, response).await
+    }
+
+    async fn post<T: for<'de> Deserialize<'de>>(
+        &self,
+        path: &str,
+        body: serde_json::Value,
+    ) -> Result<T, ForgeError> {
+        let response = self
+            .authenticate(self.http.post(format!("{}{path}", self.api_url)))
+            .json(&body)
+            .send()
+            .await?;
+
+        parse_response(ForgeKind::Bitbucket, response).await
+    }
+}
+
+impl Forge for BitbucketClient {
+    fn kind(&self) -> ForgeKind {
+        ForgeKind::Bitbucket
+    }
+
+    fn repo(&self) -> &str {
+        &self.repo
+    }
+
+    fn get_issue(&self, number: u64) -> BoxFuture<'_, Result<Issue, ForgeError>> {
+        Box::pin(self.fetch_issue(number))
+    }
+
+    fn create_pull_request<'a>(
+        &'a self,
+        title: &'a str,
+        body: &'a str,
+        head: &'a str,
+        base: Option<&'a str>,
+        draft: bool,
+    ) -> BoxFuture<'a, Result<PullRequest, ForgeError>> {
+        Box::pin(self.open_pull_request(title, body, head, base, draft))
+    }
+
+    fn comment_on_pull_request<'a>(
+        &'a self,
+        number: u64,
+        body: &'a str,
+    ) -> BoxFuture<'a, Result<Comment, ForgeError>> {
+        Box::pin(self.comment(number, body))
+    }
+}
+
+fn display_name(user: Option<ApiUser>) -> String {
+    user.map(|u| u.display_name).unwrap_or_default()
+}
+
+fn raw_content(content: Option<ApiContent>) -> String {
+    content.and_then(|c| c.raw).unwrap_or_default()
+}
@@ -0,0 +1,223 @@
+use super::api::{MAX_ISSUE_COMMENTS, parse_response};
+use super::{Comment, Forge, ForgeError, Issue, IssueComment, PullRequest, RemoteRepo};
+use crate::domain::ForgeKind;
+use crate::tools::BoxFuture;
+use reqwest::header::{ACCEPT, AUTHORIZATION, HeaderMap, HeaderValue, USER_AGENT};
+use serde::Deserialize;
+use serde_json::json;
+
+const DEFAULT_API_URL: &str = "https://api.github.com";
+
+/// A client for GitHub's REST API, scoped to a single repository.
+#[derive(Debug)]
+pub struct GitHubClient {
+    http: reqwest::Client,
+    api_url: String,
+    repo: String,
+}
+
+#[derive(Deserialize)]
+struct ApiUser {
+    login: String,
+}
+
+#[derive(Deserialize)]
+struct ApiLabel {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct ApiIssue {
+    number: u64,
+    title: String,
+    state: String,
+    user: ApiUser,
+    #[serde(default)]
+    labels: Vec<ApiLabel>,
+    body: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ApiComment {
+    user: ApiUser,
+    body: Option<String>,
+    html_url: String,
+}
+
+#[derive(Deserialize)]
+struct ApiPullRequest {
+    number: u64,
+    html_url: String,
+}
+
+#[derive(Deserialize)]
+struct ApiRepo {
+    default_branch: String,
+}
+
+impl GitHubClient {
+    /// `api_url` needs to be provided for GitHub Enterprise hosts.
+    pub fn new(remote: &RemoteRepo, token: &str, api_url: Option<String>) -> anyhow::Result<Self> {
+        let api_url = api_url
+            .map(|u| u.trim_end_matches('/').to_string())
+            .unwrap_or_else(|| DEFAULT_API_URL.to_string());
+
+        let mut headers = HeaderMap::new();
+        headers.insert(USER_AGENT, HeaderValue::from_static("agx"));
+        headers.insert(
+            ACCEPT,
+            HeaderValue::from_static("application/vnd.github+json"),
+        );
+        headers.insert(
+            AUTHORIZATION,
+            HeaderValue::from_str(&format!("Bearer {token}"))?,
+        );
+
+        let http = reqwest::Client::builder()
+            .default_headers(headers)
+            .build()?;
+
+        Ok(Self {
+            http,
+            api_url,
+            repo: remote.path.clone(),
+        })
+    }
+
+    async fn fetch_issue(&self, number: u64) -> Result<Issue, ForgeError> {
+        let issue: ApiIssue = self
+            .get(&format!("/repos/{}/issues/{number}", self.repo))
+            .await?;
+        let comments: Vec<ApiComment> = self
+            .get(&format!(
+                "/repos/{}/issues/{number}/comments?per_page={MAX_ISSUE_COMMENTS}",
+                self.repo
+            ))
+            .await?;
+
+        Ok(Issue {
+            number: issue.number,
+            title: issue.title,
+            state: issue.state,
+            author: issue.user.login,
+            labels: issue.labels.into_iter().map(|l| l.name).collect(),
+            body: issue.body.unwrap_or_default(),
+            comments: comments
+                .into_iter()
+                .map(|c| IssueComment {
+                    author: c.user.login,
+                    body: c.body.unwrap_or_default(),
+                })
+                .collect(),
+        })
+    }
+
+    async fn open_pull_request(
+        &self,
+        title: &str,
+        body: &str,
+        head: &str,
+        base: Option<&str>,
+        draft: bool,
+    ) -> Result<PullRequest, ForgeError> {
+        let base = match base {
+            Some(b) => b.to_string(),
+            None => {
+                self.get::<ApiRepo>(&format!("/repos/{}", self.repo))
+                    .await?
+                    .default_branch
+            }
+        };
+
+        let pull_request: ApiPullRequest = self
+            .post(
+                &format!("/repos/{}/pulls", self.repo),
+                json!({
+                    "title": title,
+                    "body": body,
+                    "head": head,
+                    "base": base,
+                    "draft": draft,
+                }),
+            )
+            .await?;
+
+        Ok(PullRequest {
+            number: pull_request.number,
+            url: pull_request.html_url,
+        })
+    }
+
+    /// Pull requests share their numbers (and comments) with issues on GitHub, so this works for
+    /// both.
+    async fn comment(&self, number: u64, body: &str) -> Result<Comment, ForgeError> {
+        let comment: ApiComment = self
+            .post(
+                &format!("/repos/{}/issues/{number}/comments", self.repo),
+                json!({ "body": body }),
+            )
+            .await?;
+
+        Ok(Comment {
+            url: comment.html_url,
+        })
+    }
+
+    async fn get<T: for<'de> Deserialize<'de>>(&self, path: &str) -> Result<T, ForgeError> {
+        let response = self
+            .http
+            .get(format!("{}{path}", self.api_url))
+            .send()
+            .await?;
+
+        parse_response(ForgeKind::GitHub, response).await
+    }
+
+    async fn post<T: for<'de> Deserialize<'de>>(
+        &self,
+        path: &str,
+        body: serde_json::Value,
+    ) -> Result<T, ForgeError> {
+        let response = self
+            .http
+            .post(format!("{}{path}", self.api_url))
+            .json(&body)
+            .send()
+            .await?;
+
+        parse_response(ForgeKind::GitHub, response).await
+    }
+}
+
+impl Forge for GitHubClient {
+    fn kind(&self) -> ForgeKind {
+        ForgeKind::GitHub
+    }
+
+    fn repo(&self) -> &str {
+        &self.repo
+    }
+
+    fn get_issue(&self, number: u64) -> BoxFuture<'_, Result<Issue, ForgeError>> {
+        Box::pin(self.fetch_issue(number))
+    }
+
+    fn create_pull_request<'a>(
+        &'a self,
+        title: &'a str,
+        body: &'a str,
+        head: &'a str,
+        base: Option<&'a str>,
+        draft: bool,
+    ) -> BoxFuture<'a, Result<PullRequest, ForgeError>> {
+        Box::pin(self.open_pull_request(title, body, head, base, draft))
+    }
+
+    fn comment_on_pull_request<'a>(
+        &'a self,
+        number: u64,
+        body: &'a str,
+    ) -> BoxFuture<'a, Result<Comment, ForgeError>> {
+        Box::pin(self.comment(number, body))
+    }
+}
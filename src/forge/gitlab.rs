@@ -0,0 +1,223 @@
+use super::api::{MAX_ISSUE_COMMENTS, parse_response};
+use super::{Comment, Forge, ForgeError, Issue, IssueComment, PullRequest, RemoteRepo};
+use crate::domain::ForgeKind;
+use crate::tools::BoxFuture;
+use reqwest::header::{AUTHORIZATION, HeaderMap, HeaderValue, USER_AGENT};
+use serde::Deserialize;
+use serde_json::json;
+
+/// A client for GitLab's REST API (v4), scoped to a single project.
+#[derive(Debug)]
+pub struct GitLabClient {
+    http: reqwest::Client,
+    api_url: String,
+    repo: String,
+    /// The project's path, URL encoded; GitLab accepts it in place of the project's ID.
+    project_id: String,
+}
+
+#[derive(Deserialize)]
+struct ApiUser {
+    username: String,
+}
+
+#[derive(Deserialize)]
+struct ApiIssue {
+    iid: u64,
+    title: String,
+    state: String,
+    author: ApiUser,
+    #[serde(default)]
+    labels: Vec<String>,
+    description: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ApiNote {
+    id: u64,
+    author: ApiUser,
+    body: String,
+    /// System notes record events (eg. label changes) rather than comments.
+    #[serde(default)]
+    system: bool,
+}
+
+#[derive(Deserialize)]
+struct ApiMergeRequest {
+    iid: u64,
+    web_url: String,
+}
+
+#[derive(Deserialize)]
+struct ApiProject {
+    default_branch: String,
+}
+
+impl GitLabClient {
+    /// `api_url` defaults to the v4 API on the remote's host, which works for self-hosted
+    /// instances as well.
+    pub fn new(remote: &RemoteRepo, token: &str, api_url: Option<String>) -> anyhow::Result<Self> {
+        let api_url = api_url
+            .map(|u| u.trim_end_matches('/').to_string())
+            .unwrap_or_else(|| format!("https://{}/api/v4", remote.host));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(USER_AGENT, HeaderValue::from_static("agx"));
+        headers.insert(
+            AUTHORIZATION,
+            HeaderValue::from_str(&format!("Bearer {token}"))?,
+        );
+
+        let http = reqwest::Client::builder()
+            .default_headers(headers)
+            .build()?;
+
+        Ok(Self {
+            http,
+            api_url,
+            repo: remote.path.clone(),
+            project_id: remote.path.replace('/', "%2F"),
+        })
+    }
+
+    async fn fetch_issue(&self, number: u64) -> Result<Issue, ForgeError> {
+        let issue: ApiIssue = self
+            .get(&format!("/projects/{}/issues/{number}", self.project_id))
+            .await?;
+        let notes: Vec<ApiNote> = self
+            .get(&format!(
+                "/projects/{}/issues/{number}/notes?sort=asc&per_page={MAX_ISSUE_COMMENTS}",
+                self.project_id
+            ))
+            .await?;
+
+        Ok(Issue {
+            number: issue.iid,
+            title: issue.title,
+            state: issue.state,
+            author: issue.author.username,
+            labels: issue.labels,
+            body: issue.description.unwrap_or_default(),
+            comments: notes
+                .into_iter()
+                .filter(|n| !n.system)
+                .map(|n| IssueComment {
+                    author: n.author.username,
+                    body: n.body,
+                })
+                .collect(),
+        })
+    }
+
+    async fn open_merge_request(
+        &self,
+        title: &str,
+        body: &str,
+        head: &str,
+        base: Option<&str>,
+        draft: bool,
+    ) -> Result<PullRequest, ForgeError> {
+        let base = match base {
+            Some(b) => b.to_string(),
+            None => {
+                self.get::<ApiProject>(&format!("/projects/{}", self.project_id))
+                    .await?
+                    .default_branch
+            }
+        };
+
+        // merge requests are marked as drafts via their title
+        let title = if draft {
+            format!("Draft: {title}")
+        } else {
+            title.to_string()
+        };
+
+        let merge_request: ApiMergeRequest = self
+            .post(
+                &format!("/projects/{}/merge_requests", self.project_id),
+                json!({
+                    "title": title,
+                    "description": body,
+                    "source_branch": head,
+                    "target_branch": base,
+                }),
+            )
+            .await?;
+
+        Ok(PullRequest {
+            number: merge_request.iid,
+            url: merge_request.web_url,
+        })
+    }
+
+    async fn comment(&self, number: u64, body: &str) -> Result<Comment, ForgeError> {
+        let path = format!("/projects/{}/merge_requests/{number}", self.project_id);
+        let merge_request: ApiMergeRequest = self.get(&path).await?;
+        let note: ApiNote = self
+            .post(&format!("{path}/notes"), json!({ "body": body }))
+            .await?;
+
+        Ok(Comment {
+            url: format!("{}#note_{}", merge_request.web_url, note.id),
+        })
+    }
+
+    async fn get<T: for<'de> Deserialize<'de>>(&self, path: &str) -> Result<T, ForgeError> {
+        let response = self
+            .http
+            .get(format!("{}{path}", self.api_url))
+            .send()
+            .await?;
+
+        parse_response(ForgeKind::GitLab, response).await
+    }
+
+    async fn post<T: for<'de> Deserialize<'de>>(
+        &self,
+        path: &str,
+        body: serde_json::Value,
+    ) -> Result<T, ForgeError> {
+        let response = self
+            .http
+            .post(format!("{}{path}", self.api_url))
+            .json(&body)
+            .send()
+            .await?;
+
+        parse_response(ForgeKind::GitLab, response).await
+    }
+}
+
+impl Forge for GitLabClient {
+    fn kind(&self) -> ForgeKind {
+        ForgeKind::GitLab
+    }
+
+    fn repo(&self) -> &str {
+        &self.repo
+    }
+
+    fn get_issue(&self, number: u64) -> BoxFuture<'_, Result<Issue, ForgeError>> {
+        Box::pin(self.fetch_issue(number))
+    }
+
+    fn create_pull_request<'a>(
+        &'a self,
+        title: &'a str,
+        body: &'a str,
+        head: &'a str,
+        base: Option<&'a str>,
+        draft: bool,
+    ) -> BoxFuture<'a, Result<PullRequest, ForgeError>> {
+        Box::pin(self.open_merge_request(title, body, head, base, draft))
+    }
+
+    fn comment_on_pull_request<'a>(
+        &'a self,
+        number: u64,
+        body: &'a str,
+    ) -> BoxFuture<'a, Result<Comment, ForgeError>> {
+        Box::pin(self.comment(number, body))
+    }
+}
@@ -0,0 +1,15 @@
+//! Clients for code forges (GitHub, GitLab, and Bitbucket), behind the [`Forge`] trait.
+
+mod api;
+mod bitbucket;
+mod github;
+mod gitlab;
+mod remote;
+mod setup;
+
+pub use api::*;
+pub use bitbucket::*;
+pub use github::*;
+pub use gitlab::*;
+pub use remote::*;
+pub use setup::*;
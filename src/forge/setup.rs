@@ -0,0 +1,85 @@
+use super::{BitbucketAuth, BitbucketClient, Forge, GitHubClient, GitLabClient, get_origin_remote};
+use crate::domain::{ForgeConfig, ForgeKind};
+use crate::env::get_optional_env_var;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Returns a client for the forge hosting the project's "origin" remote, if the forge is known
+/// (or configured) and a token is available. Tokens are read from the following env vars:
+/// - GitHub: "GITHUB_TOKEN", falling back to `github_fallback_token` (eg. the OAuth token used
+///   for GitHub Copilot)
+/// - GitLab: "GITLAB_TOKEN"
+/// - Bitbucket: "BITBUCKET_TOKEN" (an access token, or an app password if "BITBUCKET_USERNAME"
+///   is set as well)
+pub async fn get_forge(
+    project_dir: &Path,
+    config: Option<&ForgeConfig>,
+    github_fallback_token: Option<&str>,
+) -> anyhow::Result<Option<Arc<dyn Forge>>> {
+    let Some(remote) = get_origin_remote(project_dir).await else {
+        return Ok(None);
+    };
+
+    let api_url = config.and_then(|c| c.api_url.clone());
+    let github_api_url = get_optional_env_var("GITHUB_API_URL")?;
+
+    let kind = match config.and_then(|c| c.kind) {
+        Some(kind) => kind,
+        None => match ForgeKind::detect(&remote.host) {
+            Some(kind) => kind,
+            // GitHub Enterprise hosts can have any name
+            None if github_api_url.is_some() => ForgeKind::GitHub,
+            None => return Ok(None),
+        },
+    };
+
+    let forge: Arc<dyn Forge> = match kind {
+        ForgeKind::GitHub => {
+            let token = match get_optional_env_var("GITHUB_TOKEN")? {
+                Some(t) => t,
+                None => match github_fallback_token {
+                    Some(t) => t.to_string(),
+                    None => return Ok(None),
+                },
+            };
+
+            Arc::new(GitHubClient::new(
+                &remote,
+                &token,
+                api_url.or(github_api_url),
+            )?)
+        }
+        ForgeKind::GitLab => {
+            let Some(token) = get_optional_env_var("GITLAB_TOKEN")? else {
+                return Ok(None);
+            };
+
+            Arc::new(GitLabClient::new(&remote, &token, api_url)?)
+        }
+        ForgeKind::Bitbucket => {
+            let Some(auth) = get_bitbucket_auth()? else {
+                return Ok(None);
+            };
+
+            Arc::new(BitbucketClient::new(&remote, auth, api_url)?)
+        }
+    };
+
+    Ok(Some(forge))
+}
+
+fn get_bitbucket_auth() -> anyhow::Result<Option<BitbucketAuth>> {
+    let Some(token) = get_optional_env_var("BITBUCKET_TOKEN")? else {
+        return Ok(None);
+    };
+
+    let auth = match get_optional_env_var("BITBUCKET_USERNAME")? {
+        Some(username) => BitbucketAuth::Basic {
+            username,
+            password: token,
+        },
+        None => BitbucketAuth::AccessToken(token),
+    };
+
+    Ok(Some(auth))
+}
@@ -0,0 +1,90 @@
+use anyhow::Context;
+
+/// Reads text from the system clipboard via the platform's clipboard utility.
+pub async fn read_clipboard() -> anyhow::Result<String> {
+    let candidates = clipboard_commands();
+
+    for (program, args) in &candidates {
+        let output = match tokio::process::Command::new(program)
+            .args(*args)
+            .output()
+            .await
+        {
+            Ok(o) => o,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e).with_context(|| format!("couldn't run {program}")),
+        };
+
+        if !output.status.success() {
+            anyhow::bail!(
+                "{program} failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        return String::from_utf8(output.stdout).context("clipboard doesn't contain text");
+    }
+
+    anyhow::bail!(
+        "couldn't find a clipboard utility; install one of: {}",
+        candidates
+            .iter()
+            .map(|(program, _)| *program)
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
+
+fn clipboard_commands() -> Vec<(&'static str, &'static [&'static str])> {
+    if cfg!(target_os = "macos") {
+        vec![("pbpaste", &[])]
+    } else if cfg!(target_os = "windows") {
+        vec![("powershell", &["-NoProfile", "-Command", "Get-Clipboard"])]
+    } else {
+        let mut commands: Vec<(&'static str, &'static [&'static str])> = vec![
+            ("xclip", &["-selection", "clipboard", "-out"]),
+            ("xsel", &["--clipboard", "--output"]),
+        ];
+        if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            commands.insert(0, ("wl-paste", &["--no-newline"]));
+        }
+        commands
+    }
+}
+
+/// Wraps text in a markdown code fence that's longer than any run of backticks within it.
+pub fn fence(text: &str) -> String {
+    let longest_run = text
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or_default();
+    let fence = "`".repeat(longest_run.max(2) + 1);
+
+    format!("{fence}\n{}\n{fence}", text.trim_end_matches('\n'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::assert_snapshot;
+
+    #[test]
+    fn fence_is_longer_than_backticks_in_the_text() {
+        // GIVEN
+        let text = "see:\n```rust\nfn main() {}\n```\n";
+
+        // WHEN
+        let fenced = fence(text);
+
+        // THEN
+        assert_snapshot!(fenced, @r"
+        ````
+        see:
+        ```rust
+        fn main() {}
+        ```
+        ````
+        ");
+    }
+}
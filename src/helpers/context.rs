@@ -3,13 +3,17 @@ use std::path::Path;
 use tokio::io::AsyncReadExt;
 
 const AGENTS_CONTEXT_FILE_MAX_SIZE: u64 = 50 * 1024;
-const AGENTS_CONTEXT_FILE: &str = "AGENTS.md";
+pub const AGENTS_CONTEXT_FILE: &str = "AGENTS.md";
 
-pub async fn get_project_context() -> anyhow::Result<Option<String>> {
+pub async fn get_project_context<P>(dir: P) -> anyhow::Result<Option<String>>
+where
+    P: AsRef<Path>,
+{
     // TODO: read other context files, and follow links in the main file
-    read_file_with_limit(AGENTS_CONTEXT_FILE, AGENTS_CONTEXT_FILE_MAX_SIZE)
+    let path = dir.as_ref().join(AGENTS_CONTEXT_FILE);
+    read_file_with_limit(&path, AGENTS_CONTEXT_FILE_MAX_SIZE)
         .await
-        .with_context(|| format!("couldn't read context from {}", AGENTS_CONTEXT_FILE))
+        .with_context(|| format!("couldn't read context from {}", path.to_string_lossy()))
 }
 
 async fn read_file_with_limit<P>(path: P, limit: u64) -> anyhow::Result<Option<String>>
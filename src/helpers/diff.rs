@@ -1,11 +1,34 @@
+use crate::domain::DiffMode;
 use console::{Color, style};
-use similar::ChangeTag;
 use similar::TextDiff;
+use similar::{ChangeTag, DiffOp, DiffTag};
 use std::cmp::max;
 
+/// Unchanged lines shown around changes, unless set otherwise (via `/set diff_context`).
+pub const DEFAULT_CONTEXT_LINES: usize = 3;
+
+/// How a [`Diff`] is computed and shown.
+#[derive(Clone, Copy, Debug)]
+pub struct DiffOptions {
+    pub mode: DiffMode,
+    /// Unchanged lines shown around changes; changes closer than twice this are in the same
+    /// hunk.
+    pub context_lines: usize,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            mode: DiffMode::default(),
+            context_lines: DEFAULT_CONTEXT_LINES,
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Diff {
     pub hunks: Vec<DiffHunk>,
+    pub mode: DiffMode,
 }
 
 #[derive(Clone, Debug)]
@@ -25,6 +48,7 @@ pub struct DiffLine {
 pub struct InlineChange {
     pub value: String,
     pub emphasized: bool,
+    pub kind: DiffOperation,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -32,6 +56,8 @@ pub enum DiffOperation {
     Insert,
     Delete,
     Equal,
+    /// Words were both removed from and added to the line; only in word diffs.
+    Replace,
 }
 
 impl DiffOperation {
@@ -40,6 +66,7 @@ impl DiffOperation {
             DiffOperation::Delete => "-",
             DiffOperation::Insert => "+",
             DiffOperation::Equal => " ",
+            DiffOperation::Replace => "~",
         }
     }
 }
@@ -56,6 +83,17 @@ impl From<ChangeTag> for DiffOperation {
 
 impl Diff {
     pub fn new(old: &str, new: &str) -> Option<Self> {
+        Self::with_options(old, new, DiffOptions::default())
+    }
+
+    /// Hunks are the same in both modes (so that they can be accepted one by one either way);
+    /// in word mode, the lines in them show the words that changed in place, which reads better
+    /// for prose and long lines.
+    pub fn with_options(old: &str, new: &str, options: DiffOptions) -> Option<Self> {
+        let DiffOptions {
+            mode,
+            context_lines,
+        } = options;
         let diff = TextDiff::from_lines(old, new);
 
         if diff.ops().is_empty() {
@@ -63,7 +101,14 @@ impl Diff {
         }
 
         let mut hunks = Vec::new();
-        for group in diff.grouped_ops(3) {
+        for group in diff.grouped_ops(context_lines) {
+            if mode == DiffMode::Word {
+                hunks.push(DiffHunk {
+                    lines: word_diff_lines(&diff, &group),
+                });
+                continue;
+            }
+
             let mut lines = Vec::new();
 
             for op in group {
@@ -75,6 +120,7 @@ impl Diff {
                         inline_changes.push(InlineChange {
                             value: value.to_string(),
                             emphasized,
+                            kind: operation.clone(),
                         });
                     }
 
@@ -94,7 +140,7 @@ impl Diff {
             return None;
         }
 
-        Some(Diff { hunks })
+        Some(Diff { hunks, mode })
     }
 
     pub fn line_num_padding(&self) -> usize {
@@ -118,6 +164,15 @@ impl Diff {
         self.get_output(true)
     }
 
+    pub fn get_terminal_output_for_hunk(&self, index: usize) -> Option<String> {
+        let hunk = self.hunks.get(index)?;
+
+        Some(
+            self.get_hunk_lines(hunk, self.line_num_padding(), true)
+                .join("\n"),
+        )
+    }
+
     fn get_output(&self, color: bool) -> String {
         if self.hunks.is_empty() {
             return String::new();
@@ -131,72 +186,238 @@ impl Diff {
                 lines.push(format!("{:-^80}", "-"));
             }
 
-            for diff_line in &hunk.lines {
-                let sign = diff_line.kind.sign();
-                let old_line = diff_line
-                    .old_line_num
-                    .map(|n| format!("{:<padding$}", n + 1, padding = line_number_padding))
-                    .unwrap_or_else(|| " ".repeat(line_number_padding));
-
-                let new_line = diff_line
-                    .new_line_num
-                    .map(|n| format!("{:<padding$}", n + 1, padding = line_number_padding))
-                    .unwrap_or_else(|| " ".repeat(line_number_padding));
-
-                if color {
-                    let (line_color, sign_str) = match diff_line.kind {
-                        DiffOperation::Delete => (Some(Color::Red), sign),
-                        DiffOperation::Insert => (Some(Color::Green), sign),
-                        DiffOperation::Equal => (None, sign),
-                    };
+            lines.extend(self.get_hunk_lines(hunk, line_number_padding, color));
+        }
 
-                    let old_line_styled = style(old_line.clone()).dim().to_string();
-                    let new_line_styled = style(new_line.clone()).dim().to_string();
-                    let sign_styled = if let Some(c) = line_color {
-                        style(&sign_str).fg(c).bold().to_string()
-                    } else {
-                        sign_str.to_string()
-                    };
+        lines.join("\n")
+    }
 
-                    let mut line_content =
-                        format!("{}{}|{}", old_line_styled, new_line_styled, sign_styled);
+    fn get_hunk_lines(
+        &self,
+        hunk: &DiffHunk,
+        line_number_padding: usize,
+        color: bool,
+    ) -> Vec<String> {
+        let mut lines = Vec::new();
 
-                    for inline_change in &diff_line.inline_changes {
-                        let value = inline_change.value.trim_end_matches('\n');
-                        let formatted_value = if inline_change.emphasized {
-                            if let Some(c) = line_color {
-                                style(value).fg(c).underlined().on_black().to_string()
-                            } else {
-                                style(value).underlined().on_black().to_string()
+        for diff_line in &hunk.lines {
+            let sign = diff_line.kind.sign();
+            let old_line = diff_line
+                .old_line_num
+                .map(|n| format!("{:<padding$}", n + 1, padding = line_number_padding))
+                .unwrap_or_else(|| " ".repeat(line_number_padding));
+
+            let new_line = diff_line
+                .new_line_num
+                .map(|n| format!("{:<padding$}", n + 1, padding = line_number_padding))
+                .unwrap_or_else(|| " ".repeat(line_number_padding));
+
+            if color {
+                let line_color = operation_color(&diff_line.kind);
+                let sign_str = sign;
+
+                let old_line_styled = style(old_line.clone()).dim().to_string();
+                let new_line_styled = style(new_line.clone()).dim().to_string();
+                let sign_styled = if let Some(c) = line_color {
+                    style(&sign_str).fg(c).bold().to_string()
+                } else {
+                    sign_str.to_string()
+                };
+
+                let mut line_content =
+                    format!("{}{}|{}", old_line_styled, new_line_styled, sign_styled);
+
+                for inline_change in &diff_line.inline_changes {
+                    let value = inline_change.value.trim_end_matches('\n');
+                    let span_color = operation_color(&inline_change.kind);
+                    let formatted_value = if self.mode == DiffMode::Word {
+                        match (&inline_change.kind, span_color) {
+                            (DiffOperation::Delete, Some(c)) => {
+                                style(value).fg(c).strikethrough().to_string()
                             }
-                        } else if let Some(c) = line_color {
-                            style(value).fg(c).to_string()
+                            (_, Some(c)) => style(value).fg(c).underlined().to_string(),
+                            (_, None) => value.to_string(),
+                        }
+                    } else if inline_change.emphasized {
+                        if let Some(c) = line_color {
+                            style(value).fg(c).underlined().on_black().to_string()
                         } else {
-                            value.to_string()
-                        };
-                        line_content.push_str(&formatted_value);
-                    }
+                            style(value).underlined().on_black().to_string()
+                        }
+                    } else if let Some(c) = line_color {
+                        style(value).fg(c).to_string()
+                    } else {
+                        value.to_string()
+                    };
+                    line_content.push_str(&formatted_value);
+                }
 
-                    lines.push(line_content);
-                } else {
-                    let mut line_spans = vec![old_line, new_line, format!("|{sign}")];
+                lines.push(line_content);
+            } else {
+                let mut line_spans = vec![old_line, new_line, format!("|{sign}")];
 
-                    for inline_change in &diff_line.inline_changes {
-                        let value = inline_change.value.trim_end_matches('\n');
-                        if inline_change.emphasized {
-                            line_spans.push(format!("⸢{}⸣", value));
-                        } else {
-                            line_spans.push(value.to_string());
+                for inline_change in &diff_line.inline_changes {
+                    let value = inline_change.value.trim_end_matches('\n');
+                    match (&self.mode, &inline_change.kind) {
+                        (DiffMode::Word, DiffOperation::Delete) => {
+                            line_spans.push(format!("[-{value}-]"))
+                        }
+                        (DiffMode::Word, DiffOperation::Insert) => {
+                            line_spans.push(format!("{{+{value}+}}"))
                         }
+                        (DiffMode::Line, _) if inline_change.emphasized => {
+                            line_spans.push(format!("⸢{}⸣", value))
+                        }
+                        _ => line_spans.push(value.to_string()),
                     }
-
-                    lines.push(line_spans.join(""));
                 }
+
+                lines.push(line_spans.join(""));
             }
         }
 
-        lines.join("\n")
+        lines
+    }
+}
+
+fn operation_color(operation: &DiffOperation) -> Option<Color> {
+    match operation {
+        DiffOperation::Delete => Some(Color::Red),
+        DiffOperation::Insert => Some(Color::Green),
+        DiffOperation::Replace => Some(Color::Yellow),
+        DiffOperation::Equal => None,
+    }
+}
+
+/// Diffs the words in a hunk's lines, and splits the result back into lines.
+fn word_diff_lines<'a>(diff: &TextDiff<'a, 'a, 'a, str>, group: &[DiffOp]) -> Vec<DiffLine> {
+    let (Some(first), Some(last)) = (group.first(), group.last()) else {
+        return vec![];
+    };
+    let old_range = first.old_range().start..last.old_range().end;
+    let new_range = first.new_range().start..last.new_range().end;
+    let old_text = diff.old_slices()[old_range.clone()].concat();
+    let new_text = diff.new_slices()[new_range.clone()].concat();
+
+    let mut lines = vec![];
+    let mut spans: Vec<InlineChange> = vec![];
+    let (mut old_line_num, mut new_line_num) = (old_range.start, new_range.start);
+    for change in TextDiff::from_words(&old_text, &new_text).iter_all_changes() {
+        let kind = DiffOperation::from(change.tag());
+        for piece in change.value().split_inclusive('\n') {
+            let value = piece.trim_end_matches('\n');
+            match spans.last_mut() {
+                _ if value.is_empty() => {}
+                Some(span) if span.kind == kind => span.value.push_str(value),
+                _ => spans.push(InlineChange {
+                    value: value.to_string(),
+                    emphasized: kind != DiffOperation::Equal,
+                    kind: kind.clone(),
+                }),
+            }
+            if !piece.ends_with('\n') {
+                continue;
+            }
+
+            lines.push(word_diff_line(
+                std::mem::take(&mut spans),
+                &kind,
+                old_line_num,
+                new_line_num,
+            ));
+            old_line_num += usize::from(kind != DiffOperation::Insert);
+            new_line_num += usize::from(kind != DiffOperation::Delete);
+        }
     }
+
+    if !spans.is_empty() {
+        lines.push(word_diff_line(
+            spans,
+            &DiffOperation::Equal,
+            old_line_num,
+            new_line_num,
+        ));
+    }
+
+    lines
+}
+
+/// A line is numbered on the sides its words come from; blank lines, on the side the newline
+/// ending them comes from.
+fn word_diff_line(
+    spans: Vec<InlineChange>,
+    ended_by: &DiffOperation,
+    old_line_num: usize,
+    new_line_num: usize,
+) -> DiffLine {
+    let has = |kind: DiffOperation| match spans.is_empty() {
+        true => ended_by == &kind,
+        false => spans.iter().any(|s| s.kind == kind),
+    };
+    let (equal, inserted, deleted) = (
+        has(DiffOperation::Equal),
+        has(DiffOperation::Insert),
+        has(DiffOperation::Delete),
+    );
+
+    let kind = match (equal, inserted, deleted) {
+        (false, true, false) => DiffOperation::Insert,
+        (false, false, true) => DiffOperation::Delete,
+        (_, false, false) => DiffOperation::Equal,
+        _ => DiffOperation::Replace,
+    };
+
+    DiffLine {
+        kind,
+        old_line_num: (equal || deleted).then_some(old_line_num),
+        new_line_num: (equal || inserted).then_some(new_line_num),
+        inline_changes: spans,
+    }
+}
+
+/// Returns a plain unified diff of the changes between `old` and `new`.
+pub fn unified_diff(old: &str, new: &str, path: &str) -> String {
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(DEFAULT_CONTEXT_LINES)
+        .header(path, path)
+        .to_string()
+}
+
+/// Applies only the accepted hunks (as grouped by [`Diff::with_options`], with the same number
+/// of context lines) of the changes between `old` and `new`, and returns the resulting contents.
+pub fn apply_hunks(old: &str, new: &str, accepted: &[bool], context_lines: usize) -> String {
+    let diff = TextDiff::from_lines(old, new);
+    let old_lines = diff.old_slices();
+    let new_lines = diff.new_slices();
+
+    let mut result = String::with_capacity(old.len());
+    let mut old_idx = 0;
+
+    for (group, is_accepted) in diff.grouped_ops(context_lines).iter().zip(accepted) {
+        for op in group.iter().filter(|op| op.tag() != DiffTag::Equal) {
+            let old_range = op.old_range();
+            old_lines[old_idx..old_range.start]
+                .iter()
+                .for_each(|l| result.push_str(l));
+
+            if *is_accepted {
+                new_lines[op.new_range()]
+                    .iter()
+                    .for_each(|l| result.push_str(l));
+            } else {
+                old_lines[old_range.clone()]
+                    .iter()
+                    .for_each(|l| result.push_str(l));
+            }
+
+            old_idx = old_range.end;
+        }
+    }
+
+    old_lines[old_idx..].iter().for_each(|l| result.push_str(l));
+
+    result
 }
 
 fn num_digits(n: usize) -> usize {
@@ -340,6 +561,92 @@ line 8
         ");
     }
 
+    #[test]
+    fn applying_a_subset_of_hunks_works() {
+        // GIVEN
+        let old = (1..=20).map(|n| format!("line {n}\n")).collect::<String>();
+        let new = old
+            .replace("line 2\n", "line 2 (changed)\n")
+            .replace("line 18\n", "line 18 (changed)\nnew line\n");
+        let diff = Diff::new(&old, &new).expect("diff should've been created");
+        assert_eq!(diff.hunks.len(), 2);
+
+        // WHEN
+        let first_only = apply_hunks(&old, &new, &[true, false], DEFAULT_CONTEXT_LINES);
+        let second_only = apply_hunks(&old, &new, &[false, true], DEFAULT_CONTEXT_LINES);
+        let both = apply_hunks(&old, &new, &[true, true], DEFAULT_CONTEXT_LINES);
+        let none = apply_hunks(&old, &new, &[false, false], DEFAULT_CONTEXT_LINES);
+
+        // THEN
+        assert_eq!(first_only, old.replace("line 2\n", "line 2 (changed)\n"));
+        assert_eq!(
+            second_only,
+            old.replace("line 18\n", "line 18 (changed)\nnew line\n")
+        );
+        assert_eq!(both, new);
+        assert_eq!(none, old);
+    }
+
+    #[test]
+    fn word_diffs_show_changed_words_in_place() {
+        // GIVEN
+        let old = "# Usage
+
+Run the tool with a config file, and it prints a report.
+Reports are written to stdout.
+
+See the FAQ for more.
+";
+        let new = "# Usage
+
+Run the tool with a TOML config file, and it prints a summary.
+Reports are written to stdout,
+or to a file via --output.
+
+See the FAQ for more.
+";
+        let options = DiffOptions {
+            mode: DiffMode::Word,
+            ..Default::default()
+        };
+
+        // WHEN
+        let diff = Diff::with_options(old, new, options).expect("diff should've been created");
+
+        // THEN
+        assert_snapshot!(diff.get_output(false), @r"
+        1   1   | # Usage
+        2   2   | 
+        3   3   |~Run the tool with a {+TOML +}config file, and it prints a [-report.-]{+summary.+}
+        4   4   |~Reports are written to [-stdout.-]{+stdout,+}
+            5   |+{+or to a file via --output.+}
+        5   6   | 
+        6   7   | See the FAQ for more.
+        ");
+    }
+
+    #[test]
+    fn more_context_lines_merge_nearby_changes_into_one_hunk() {
+        // GIVEN
+        let old = (1..=20).map(|n| format!("line {n}\n")).collect::<String>();
+        let new = old
+            .replace("line 2\n", "line 2 (changed)\n")
+            .replace("line 18\n", "line 18 (changed)\n");
+        let options = DiffOptions {
+            context_lines: 10,
+            ..Default::default()
+        };
+
+        // WHEN
+        let diff = Diff::with_options(&old, &new, options).expect("diff should've been created");
+        let rejected = apply_hunks(&old, &new, &[false], options.context_lines);
+
+        // THEN
+        assert_eq!(diff.hunks.len(), 1);
+        assert_eq!(diff.hunks[0].lines.len(), 22);
+        assert_eq!(rejected, old);
+    }
+
     #[test]
     fn creating_a_diff_with_no_changes_works() {
         // GIVEN
@@ -0,0 +1,60 @@
+use anyhow::Context;
+use chrono::TimeDelta;
+
+/// Parses a duration like "30m", "12h", "7d", or "2w".
+pub fn parse_duration(value: &str) -> anyhow::Result<TimeDelta> {
+    let invalid = || {
+        format!(r#"invalid duration "{value}"; expected something like "7d" (units: m, h, d, w)"#)
+    };
+
+    let unit_start = value
+        .find(|c: char| !c.is_ascii_digit())
+        .with_context(invalid)?;
+    let (amount, unit) = value.split_at(unit_start);
+    let amount = amount.parse::<i64>().with_context(invalid)?;
+    let unit_seconds = match unit {
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => anyhow::bail!(invalid()),
+    };
+
+    amount
+        .checked_mul(unit_seconds)
+        .and_then(TimeDelta::try_seconds)
+        .with_context(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::assert_snapshot;
+
+    #[test]
+    fn durations_can_be_parsed() {
+        // GIVEN
+        let cases = ["30m", "12h", "7d", "2w", "7", "d", "7y"];
+
+        // WHEN
+        let results = cases
+            .iter()
+            .map(|c| match parse_duration(c) {
+                Ok(d) => format!("{c}: {}s", d.num_seconds()),
+                Err(e) => format!("{c}: {e}"),
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        // THEN
+        assert_snapshot!(results, @r#"
+        30m: 1800s
+        12h: 43200s
+        7d: 604800s
+        2w: 1209600s
+        7: invalid duration "7"; expected something like "7d" (units: m, h, d, w)
+        d: invalid duration "d"; expected something like "7d" (units: m, h, d, w)
+        7y: invalid duration "7y"; expected something like "7d" (units: m, h, d, w)
+        "#);
+    }
+}
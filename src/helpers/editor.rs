@@ -0,0 +1,99 @@
+use anyhow::Context;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const DEFAULT_EDITOR: &str = "vi";
+
+/// Opens the user's editor ($VISUAL, or $EDITOR) on a temporary file containing `draft`, and
+/// returns what the file contains once the editor exits.
+pub async fn compose_in_editor(draft: &str) -> anyhow::Result<String> {
+    let (program, args) =
+        editor_command(std::env::var("VISUAL").ok(), std::env::var("EDITOR").ok())?;
+
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or_default();
+    let path = std::env::temp_dir().join(format!("agx-prompt-{}-{nanos}.md", std::process::id()));
+    tokio::fs::write(&path, draft)
+        .await
+        .with_context(|| format!("couldn't create {}", path.to_string_lossy()))?;
+
+    let result = async {
+        let status = tokio::process::Command::new(&program)
+            .args(&args)
+            .arg(&path)
+            .status()
+            .await
+            .with_context(|| format!("couldn't run editor {program}"))?;
+        if !status.success() {
+            anyhow::bail!("editor {program} exited with {status}");
+        }
+
+        tokio::fs::read_to_string(&path)
+            .await
+            .with_context(|| format!("couldn't read {}", path.to_string_lossy()))
+    }
+    .await;
+
+    _ = tokio::fs::remove_file(&path).await;
+
+    result
+}
+
+/// Returns the program (and arguments) to edit files with; editors can be set along with
+/// arguments, eg. "code --wait".
+fn editor_command(
+    visual: Option<String>,
+    editor: Option<String>,
+) -> anyhow::Result<(String, Vec<String>)> {
+    let command = visual
+        .into_iter()
+        .chain(editor)
+        .find(|e| !e.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_EDITOR.to_string());
+
+    let mut parts = shlex::split(&command)
+        .filter(|p| !p.is_empty())
+        .with_context(|| format!("couldn't parse editor command \"{command}\""))?;
+    let program = parts.remove(0);
+
+    Ok((program, parts))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn editor_is_picked_from_visual_then_editor() -> anyhow::Result<()> {
+        // GIVEN
+        let cases = [
+            (Some("code --wait"), Some("vim")),
+            (Some(" "), Some("nvim -u NONE")),
+            (None, None),
+        ];
+
+        // WHEN
+        let commands = cases
+            .into_iter()
+            .map(|(visual, editor)| {
+                editor_command(visual.map(String::from), editor.map(String::from))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        // THEN
+        assert_eq!(
+            commands,
+            vec![
+                ("code".to_string(), vec!["--wait".to_string()]),
+                (
+                    "nvim".to_string(),
+                    vec!["-u".to_string(), "NONE".to_string()]
+                ),
+                ("vi".to_string(), vec![]),
+            ]
+        );
+
+        Ok(())
+    }
+}
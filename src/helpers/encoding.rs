@@ -0,0 +1,192 @@
+use encoding_rs::{UTF_16BE, UTF_16LE, WINDOWS_1252};
+use std::fmt::Display;
+use std::path::Path;
+
+/// Files without a BOM are taken to be UTF-16 if at least this share of the bytes at odd (or
+/// even) positions are zero, as the high bytes of ASCII characters are.
+const MIN_UTF16_ZERO_SHARE: f32 = 0.3;
+
+/// The encoding a text file was read in; files are written back in the same one, so that edits
+/// don't turn them into UTF-8 behind the user's back.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextEncoding {
+    Utf8,
+    Utf16Le {
+        bom: bool,
+    },
+    Utf16Be {
+        bom: bool,
+    },
+    /// Strictly, windows-1252 (a superset of ISO-8859-1, which is what "Latin-1" files
+    /// usually turn out to be).
+    Latin1,
+}
+
+impl Display for TextEncoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TextEncoding::Utf8 => write!(f, "UTF-8"),
+            TextEncoding::Utf16Le { .. } => write!(f, "UTF-16LE"),
+            TextEncoding::Utf16Be { .. } => write!(f, "UTF-16BE"),
+            TextEncoding::Latin1 => write!(f, "Latin-1"),
+        }
+    }
+}
+
+impl TextEncoding {
+    /// Encodes `text`; fails if it has characters the encoding can't represent.
+    pub fn encode(&self, text: &str) -> std::io::Result<Vec<u8>> {
+        let utf16 = |bom: bool, to_bytes: fn(u16) -> [u8; 2]| {
+            let bom = bom.then_some(0xfeff);
+            bom.into_iter()
+                .chain(text.encode_utf16())
+                .flat_map(to_bytes)
+                .collect()
+        };
+
+        match self {
+            TextEncoding::Utf8 => Ok(text.as_bytes().to_vec()),
+            TextEncoding::Utf16Le { bom } => Ok(utf16(*bom, u16::to_le_bytes)),
+            TextEncoding::Utf16Be { bom } => Ok(utf16(*bom, u16::to_be_bytes)),
+            TextEncoding::Latin1 => {
+                let (bytes, _, unmappable) = WINDOWS_1252.encode(text);
+                if unmappable {
+                    let c = text
+                        .chars()
+                        .find(|c| WINDOWS_1252.encode(&c.to_string()).2)
+                        .unwrap_or_default();
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("{c:?} can't be represented in {self}"),
+                    ));
+                }
+
+                Ok(bytes.into_owned())
+            }
+        }
+    }
+}
+
+/// Decodes the contents of a text file, detecting its encoding; returns `None` for binary
+/// files.
+pub fn decode_text(bytes: &[u8]) -> Option<(String, TextEncoding)> {
+    if let Some(rest) = bytes.strip_prefix(&[0xff, 0xfe]) {
+        return decode_utf16(rest, TextEncoding::Utf16Le { bom: true });
+    }
+    if let Some(rest) = bytes.strip_prefix(&[0xfe, 0xff]) {
+        return decode_utf16(rest, TextEncoding::Utf16Be { bom: true });
+    }
+    // NUL bytes are valid UTF-8, but they're rarely found in text files (unlike in UTF-16 ones)
+    if let Ok(text) = std::str::from_utf8(bytes)
+        && !text.contains('\0')
+    {
+        return Some((text.to_string(), TextEncoding::Utf8));
+    }
+
+    let zero_share = |offset: usize| {
+        let zeros = bytes.iter().skip(offset).step_by(2).filter(|b| **b == 0);
+        zeros.count() as f32 / (bytes.len() / 2).max(1) as f32
+    };
+    if bytes.len().is_multiple_of(2) {
+        if zero_share(1) >= MIN_UTF16_ZERO_SHARE && zero_share(0) == 0.0 {
+            return decode_utf16(bytes, TextEncoding::Utf16Le { bom: false });
+        }
+        if zero_share(0) >= MIN_UTF16_ZERO_SHARE && zero_share(1) == 0.0 {
+            return decode_utf16(bytes, TextEncoding::Utf16Be { bom: false });
+        }
+    }
+
+    if bytes.contains(&0) {
+        return None;
+    }
+
+    let (text, _) = WINDOWS_1252.decode_without_bom_handling(bytes);
+    Some((text.into_owned(), TextEncoding::Latin1))
+}
+
+fn decode_utf16(bytes: &[u8], encoding: TextEncoding) -> Option<(String, TextEncoding)> {
+    let decoder = match encoding {
+        TextEncoding::Utf16Be { .. } => UTF_16BE,
+        _ => UTF_16LE,
+    };
+    let text = decoder.decode_without_bom_handling_and_without_replacement(bytes)?;
+
+    Some((text.into_owned(), encoding))
+}
+
+/// Reads a text file in whichever encoding it's in.
+pub async fn read_text_file<P>(path: P) -> std::io::Result<(String, TextEncoding)>
+where
+    P: AsRef<Path>,
+{
+    let bytes = tokio::fs::read(path).await?;
+
+    decode_text(&bytes).ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "file doesn't seem to be text (it's not in UTF-8, UTF-16, or Latin-1)",
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn files_are_written_back_in_the_encoding_they_were_read_in() {
+        // GIVEN
+        let text = "naïve café\r\nüber\n";
+        let utf16le = text
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect::<Vec<_>>();
+        let with_bom = |bom: [u8; 2], bytes: &[u8]| [bom.as_slice(), bytes].concat();
+        let files = [
+            text.as_bytes().to_vec(),
+            with_bom([0xff, 0xfe], &utf16le),
+            utf16le.clone(),
+            with_bom(
+                [0xfe, 0xff],
+                &text
+                    .encode_utf16()
+                    .flat_map(u16::to_be_bytes)
+                    .collect::<Vec<_>>(),
+            ),
+            b"na\xefve caf\xe9\r\n\xfcber\n".to_vec(),
+        ];
+
+        // WHEN
+        let decoded = files
+            .iter()
+            .map(|f| decode_text(f).expect("file should be decoded"))
+            .collect::<Vec<_>>();
+
+        // THEN
+        assert!(decoded.iter().all(|(t, _)| t == text));
+        assert_eq!(
+            decoded.iter().map(|(_, e)| *e).collect::<Vec<_>>(),
+            vec![
+                TextEncoding::Utf8,
+                TextEncoding::Utf16Le { bom: true },
+                TextEncoding::Utf16Le { bom: false },
+                TextEncoding::Utf16Be { bom: true },
+                TextEncoding::Latin1,
+            ]
+        );
+        for (file, (text, encoding)) in files.iter().zip(&decoded) {
+            assert_eq!(
+                &encoding.encode(text).expect("text should be encoded"),
+                file
+            );
+        }
+        assert_eq!(decode_text(b"\x7fELF\x02\x01\x01\x00\x00\x00\xb3"), None);
+        assert_eq!(
+            TextEncoding::Latin1
+                .encode("→ café")
+                .expect_err("arrow can't be encoded")
+                .to_string(),
+            "'→' can't be represented in Latin-1"
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use anyhow::Context;
 use std::path::{Component, Path};
 
 pub fn is_path_in_workspace<P>(path: P) -> bool
@@ -32,6 +33,24 @@ where
         .join("-")
 }
 
+/// Reads a prompt written in a file (eg. a long task description in markdown).
+pub async fn read_prompt_file<P>(path: P) -> anyhow::Result<String>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let prompt = tokio::fs::read_to_string(path)
+        .await
+        .with_context(|| format!("couldn't read prompt from {}", path.to_string_lossy()))?;
+
+    let prompt = prompt.trim();
+    if prompt.is_empty() {
+        anyhow::bail!("prompt file {} is empty", path.to_string_lossy());
+    }
+
+    Ok(prompt.to_string())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,103 @@
+use std::path::{Component, Path};
+
+const LOCKFILES: [&str; 12] = [
+    "Cargo.lock",
+    "package-lock.json",
+    "npm-shrinkwrap.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "bun.lock",
+    "bun.lockb",
+    "poetry.lock",
+    "uv.lock",
+    "Gemfile.lock",
+    "composer.lock",
+    "go.sum",
+];
+
+const MINIFIED_SUFFIXES: [&str; 3] = [".min.js", ".min.css", ".min.mjs"];
+
+/// Directories whose contents are produced by tools (build output, or installed dependencies).
+const GENERATED_DIRS: [(&str, &str); 7] = [
+    ("target", "build output"),
+    ("dist", "build output"),
+    ("__pycache__", "build output"),
+    (".next", "build output"),
+    ("node_modules", "vendored dependencies"),
+    ("vendor", "vendored dependencies"),
+    (".venv", "vendored dependencies"),
+];
+
+/// Returns why a file is likely generated or vendored rather than written by hand (eg. "a
+/// lockfile"), going by its path alone; such files get regenerated by tools, which discards
+/// edits made to them by hand.
+pub fn generated_file_reason(path: impl AsRef<Path>) -> Option<String> {
+    let path = path.as_ref();
+    let file_name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default();
+
+    if LOCKFILES.contains(&file_name) {
+        return Some("a lockfile".to_string());
+    }
+
+    if MINIFIED_SUFFIXES.iter().any(|s| file_name.ends_with(s)) {
+        return Some("a minified file".to_string());
+    }
+
+    path.parent()?.components().find_map(|c| match c {
+        Component::Normal(dir) => GENERATED_DIRS
+            .iter()
+            .find(|(name, _)| dir.to_str() == Some(name))
+            .map(|(name, kind)| format!("in {kind} ({name}/)")),
+        _ => None,
+    })
+}
+
+/// A note for the model (and the user) about changing a generated file.
+pub fn generated_file_warning(path: &str) -> Option<String> {
+    generated_file_reason(path).map(|reason| {
+        format!(
+            "{path} is {reason}; it's likely generated by tooling, so changes should be made to its source (or by running the tool that generates it) rather than by hand"
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::assert_snapshot;
+
+    #[test]
+    fn generated_files_are_detected_by_path() {
+        // GIVEN
+        let paths = [
+            "Cargo.lock",
+            "web/package-lock.json",
+            "static/app.min.js",
+            "target/debug/build/out.rs",
+            "web/node_modules/react/index.js",
+            "src/target.rs",
+            "src/dist/mod.rs",
+            "README.md",
+        ];
+
+        // WHEN
+        let reasons = paths
+            .map(|p| format!("{p}: {:?}", generated_file_reason(p)))
+            .join("\n");
+
+        // THEN
+        assert_snapshot!(reasons, @r#"
+        Cargo.lock: Some("a lockfile")
+        web/package-lock.json: Some("a lockfile")
+        static/app.min.js: Some("a minified file")
+        target/debug/build/out.rs: Some("in build output (target/)")
+        web/node_modules/react/index.js: Some("in vendored dependencies (node_modules/)")
+        src/target.rs: None
+        src/dist/mod.rs: Some("in build output (dist/)")
+        README.md: None
+        "#);
+    }
+}
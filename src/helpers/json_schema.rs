@@ -0,0 +1,167 @@
+use serde_json::Value;
+
+/// Validates a JSON value against a JSON schema. Only a subset of JSON schema is supported:
+/// "type", "enum", "const", "properties", "required", "additionalProperties" (as a boolean),
+/// and "items"; other keywords are ignored.
+///
+/// Returns a list of violations (empty if the value is valid).
+pub fn validate_against_schema(value: &Value, schema: &Value) -> Vec<String> {
+    let mut errors = vec![];
+    validate(value, schema, "$", &mut errors);
+
+    errors
+}
+
+fn validate(value: &Value, schema: &Value, path: &str, errors: &mut Vec<String>) {
+    let Some(schema) = schema.as_object() else {
+        return;
+    };
+
+    if let Some(expected) = schema.get("type") {
+        let allowed = match expected {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(types) => types.iter().filter_map(|t| t.as_str()).collect(),
+            _ => vec![],
+        };
+
+        if !allowed.is_empty() && !allowed.iter().any(|t| is_of_type(value, t)) {
+            errors.push(format!(
+                "{path}: expected type {}, got {}",
+                allowed.join(" | "),
+                type_name(value)
+            ));
+            return;
+        }
+    }
+
+    if let Some(Value::Array(variants)) = schema.get("enum")
+        && !variants.contains(value)
+    {
+        errors.push(format!("{path}: value is not one of the allowed values"));
+    }
+
+    if let Some(constant) = schema.get("const")
+        && constant != value
+    {
+        errors.push(format!("{path}: value doesn't equal {constant}"));
+    }
+
+    if let Value::Object(map) = value {
+        let properties = schema.get("properties").and_then(|p| p.as_object());
+
+        if let Some(Value::Array(required)) = schema.get("required") {
+            for key in required.iter().filter_map(|k| k.as_str()) {
+                if !map.contains_key(key) {
+                    errors.push(format!(r#"{path}: missing required property "{key}""#));
+                }
+            }
+        }
+
+        for (key, v) in map {
+            match properties.and_then(|p| p.get(key)) {
+                Some(property_schema) => {
+                    validate(v, property_schema, &format!("{path}.{key}"), errors)
+                }
+                None => {
+                    if schema.get("additionalProperties") == Some(&Value::Bool(false)) {
+                        errors.push(format!(r#"{path}: unexpected property "{key}""#));
+                    }
+                }
+            }
+        }
+    }
+
+    if let Value::Array(items) = value
+        && let Some(items_schema) = schema.get("items")
+    {
+        for (i, item) in items.iter().enumerate() {
+            validate(item, items_schema, &format!("{path}[{i}]"), errors);
+        }
+    }
+}
+
+fn is_of_type(value: &Value, type_name: &str) -> bool {
+    match type_name {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::assert_yaml_snapshot;
+    use serde_json::json;
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": "string" },
+                "kind": { "enum": ["bin", "lib"] },
+                "deps": {
+                    "type": "array",
+                    "items": { "type": "string" }
+                },
+                "version": { "type": ["string", "null"] }
+            },
+            "required": ["name", "kind"],
+            "additionalProperties": false
+        })
+    }
+
+    #[test]
+    fn validating_a_conforming_value_works() {
+        // GIVEN
+        let value = json!({
+            "name": "agx",
+            "kind": "bin",
+            "deps": ["rig-core", "tokio"],
+            "version": null
+        });
+
+        // WHEN
+        let errors = validate_against_schema(&value, &schema());
+
+        // THEN
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn validating_a_non_conforming_value_reports_all_violations() {
+        // GIVEN
+        let value = json!({
+            "kind": "dylib",
+            "deps": ["tokio", 1],
+            "extra": true
+        });
+
+        // WHEN
+        let errors = validate_against_schema(&value, &schema());
+
+        // THEN
+        assert_yaml_snapshot!(errors, @r#"
+        - "$: missing required property \"name\""
+        - "$.deps[1]: expected type string, got number"
+        - "$: unexpected property \"extra\""
+        - "$.kind: value is not one of the allowed values"
+        "#);
+    }
+}
@@ -0,0 +1,87 @@
+use anyhow::Context;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{Read, Seek, Write};
+use std::path::Path;
+
+pub const PROJECT_LOCK_FILE: &str = "session.lock";
+
+/// Held by the session running in a project, so that other sessions started in it can tell; the
+/// lock is released when dropped, or when the process exits (even if it crashes).
+pub struct ProjectLock {
+    _file: File,
+}
+
+pub enum LockAttempt {
+    Acquired(ProjectLock),
+    /// Another process holds the lock; its pid is known if it could be read from the lock file.
+    Held {
+        pid: Option<u32>,
+    },
+}
+
+impl ProjectLock {
+    /// Tries to take the lock for the project whose state lives in `project_log_dir`.
+    pub fn try_acquire(project_log_dir: &Path) -> anyhow::Result<LockAttempt> {
+        let path = project_log_dir.join(PROJECT_LOCK_FILE);
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .with_context(|| format!("couldn't open lock file {}", path.to_string_lossy()))?;
+
+        match file.try_lock() {
+            Ok(()) => {
+                file.set_len(0)
+                    .and_then(|_| file.rewind())
+                    .and_then(|_| write!(file, "{}", std::process::id()))
+                    .with_context(|| {
+                        format!("couldn't write to lock file {}", path.to_string_lossy())
+                    })?;
+
+                Ok(LockAttempt::Acquired(ProjectLock { _file: file }))
+            }
+            Err(TryLockError::WouldBlock) => {
+                let mut contents = String::new();
+                let pid = file
+                    .read_to_string(&mut contents)
+                    .ok()
+                    .and_then(|_| contents.trim().parse().ok());
+
+                Ok(LockAttempt::Held { pid })
+            }
+            Err(TryLockError::Error(e)) => {
+                Err(anyhow::Error::from(e)
+                    .context(format!("couldn't lock {}", path.to_string_lossy())))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn project_lock_can_only_be_held_once_at_a_time() -> anyhow::Result<()> {
+        // GIVEN
+        let temp_dir = tempfile::tempdir()?;
+        let dir = temp_dir.path();
+
+        // WHEN
+        let first = ProjectLock::try_acquire(dir)?;
+        let second = ProjectLock::try_acquire(dir)?;
+        drop(first);
+        let third = ProjectLock::try_acquire(dir)?;
+
+        // THEN
+        assert!(matches!(
+            second,
+            LockAttempt::Held { pid: Some(pid) } if pid == std::process::id()
+        ));
+        assert!(matches!(third, LockAttempt::Acquired(_)));
+
+        Ok(())
+    }
+}
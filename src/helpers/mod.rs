@@ -1,7 +1,25 @@
+mod clipboard;
 mod context;
 mod diff;
+mod duration;
+mod editor;
+mod encoding;
 mod fs;
+mod generated;
+mod json_schema;
+mod lock;
+mod toolchain;
+mod view;
 
+pub use clipboard::*;
 pub use context::*;
 pub use diff::*;
+pub use duration::*;
+pub use editor::*;
+pub use encoding::*;
 pub use fs::*;
+pub use generated::*;
+pub use json_schema::*;
+pub use lock::*;
+pub use toolchain::*;
+pub use view::*;
@@ -0,0 +1,142 @@
+use std::fmt::Display;
+use std::path::Path;
+
+/// A language toolchain a project uses, detected via its manifest, along with the commands that
+/// are likely used to build, test, and lint the project.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Toolchain {
+    pub name: &'static str,
+    pub manifest: &'static str,
+    pub build: Option<String>,
+    pub test: Option<String>,
+    pub lint: Option<String>,
+}
+
+impl Display for Toolchain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.name, self.manifest)?;
+        for (kind, command) in [
+            ("build", &self.build),
+            ("test", &self.test),
+            ("lint", &self.lint),
+        ] {
+            if let Some(command) = command {
+                write!(f, "; {kind}: `{command}`")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Detects the toolchains used in a directory, via the manifests in it (Cargo.toml,
+/// package.json, pyproject.toml, and go.mod). Commands are inferred from the manifests (eg.
+/// package.json's scripts) and lockfiles (eg. to pick the package manager).
+pub async fn detect_toolchains(dir: &Path) -> Vec<Toolchain> {
+    let read = |file: &str| tokio::fs::read_to_string(dir.join(file));
+    let exists = |file: &str| dir.join(file).exists();
+
+    let mut toolchains = vec![];
+
+    if exists("Cargo.toml") {
+        toolchains.push(Toolchain {
+            name: "Rust",
+            manifest: "Cargo.toml",
+            build: Some("cargo build".to_string()),
+            test: Some("cargo test".to_string()),
+            lint: Some("cargo clippy --all-targets".to_string()),
+        });
+    }
+
+    if let Ok(package_json) = read("package.json").await {
+        let manager = [
+            ("pnpm-lock.yaml", "pnpm"),
+            ("yarn.lock", "yarn"),
+            ("bun.lockb", "bun"),
+            ("bun.lock", "bun"),
+        ]
+        .into_iter()
+        .find(|(lockfile, _)| exists(lockfile))
+        .map(|(_, manager)| manager)
+        .unwrap_or("npm");
+        toolchains.push(node_toolchain(&package_json, manager));
+    }
+
+    if let Ok(pyproject) = read("pyproject.toml").await {
+        let runner = if exists("uv.lock") {
+            "uv run "
+        } else if exists("poetry.lock") {
+            "poetry run "
+        } else {
+            ""
+        };
+        toolchains.push(Toolchain {
+            name: "Python",
+            manifest: "pyproject.toml",
+            build: None,
+            test: Some(format!("{runner}pytest")),
+            lint: pyproject
+                .contains("[tool.ruff")
+                .then(|| format!("{runner}ruff check .")),
+        });
+    }
+
+    if exists("go.mod") {
+        toolchains.push(Toolchain {
+            name: "Go",
+            manifest: "go.mod",
+            build: Some("go build ./...".to_string()),
+            test: Some("go test ./...".to_string()),
+            lint: Some("go vet ./...".to_string()),
+        });
+    }
+
+    toolchains
+}
+
+/// Commands are only inferred for the scripts package.json defines.
+fn node_toolchain(package_json: &str, manager: &str) -> Toolchain {
+    let scripts = serde_json::from_str::<serde_json::Value>(package_json)
+        .ok()
+        .and_then(|p| p.get("scripts").cloned())
+        .unwrap_or_default();
+    let script = |name: &str| {
+        scripts.get(name).map(|_| match (manager, name) {
+            ("npm", "test") => "npm test".to_string(),
+            ("npm", _) => format!("npm run {name}"),
+            _ => format!("{manager} {name}"),
+        })
+    };
+
+    Toolchain {
+        name: "JavaScript/TypeScript",
+        manifest: "package.json",
+        build: script("build"),
+        test: script("test"),
+        lint: script("lint"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::assert_snapshot;
+
+    #[test]
+    fn node_commands_are_inferred_from_scripts() {
+        // GIVEN
+        let package_json =
+            r#"{"name": "web", "scripts": {"build": "vite build", "test": "vitest"}}"#;
+
+        // WHEN
+        let toolchains = ["npm", "pnpm"]
+            .map(|manager| node_toolchain(package_json, manager).to_string())
+            .join("\n");
+
+        // THEN
+        assert_snapshot!(toolchains, @r"
+        JavaScript/TypeScript (package.json); build: `npm run build`; test: `npm test`
+        JavaScript/TypeScript (package.json); build: `pnpm build`; test: `pnpm test`
+        ");
+    }
+}
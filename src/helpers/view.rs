@@ -0,0 +1,356 @@
+use console::{Style, style};
+use std::path::Path;
+
+/// Renders a file's lines (all of them, or those in a range like "10:20", "10:", ":20", or "10")
+/// with line numbers, highlighting keywords, strings, comments, and numbers in languages it
+/// recognizes (by the file's extension).
+pub fn render_file(path: &Path, contents: &str, range: Option<&str>) -> anyhow::Result<String> {
+    let lines = contents.lines().collect::<Vec<_>>();
+    let (start, end) = match range {
+        Some(range) => parse_line_range(range, lines.len())?,
+        None => (1, lines.len()),
+    };
+
+    let language = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .and_then(language_for);
+    let number_width = end.to_string().len();
+    let mut in_block_comment = false;
+    let mut rendered = vec![];
+    // block comments can start before the range, so lines before it are tokenized as well
+    for (i, line) in lines.iter().enumerate().take(end) {
+        let highlighted = match language {
+            Some(language) => tokenize(line, language, &mut in_block_comment)
+                .into_iter()
+                .map(|(kind, text)| kind.style().apply_to(text).to_string())
+                .collect(),
+            None => line.to_string(),
+        };
+        if i + 1 >= start {
+            rendered.push(format!(
+                "{}  {highlighted}",
+                style(format!("{:>number_width$}", i + 1)).dim()
+            ));
+        }
+    }
+
+    Ok(rendered.join("\n"))
+}
+
+/// Returns the (1-based, inclusive) lines a range covers in a file with `line_count` lines.
+fn parse_line_range(range: &str, line_count: usize) -> anyhow::Result<(usize, usize)> {
+    let invalid = || {
+        anyhow::anyhow!(
+            r#"invalid line range "{range}"; expected something like "10:20", "10:", ":20", or "10""#
+        )
+    };
+    let parse = |n: &str, default: usize| match n.trim() {
+        "" => Ok(default),
+        n => n.parse::<usize>().map_err(|_| invalid()),
+    };
+
+    let (start, end) = match range.split_once(':') {
+        Some((start, end)) => (parse(start, 1)?, parse(end, line_count)?),
+        None => {
+            let line = parse(range, 0)?;
+            (line, line)
+        }
+    };
+
+    if start == 0 || start > end {
+        return Err(invalid());
+    }
+    if start > line_count {
+        anyhow::bail!("the file only has {line_count} lines");
+    }
+
+    Ok((start, end.min(line_count)))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TokenKind {
+    Plain,
+    Keyword,
+    String,
+    Comment,
+    Number,
+}
+
+impl TokenKind {
+    fn style(self) -> Style {
+        match self {
+            TokenKind::Plain => Style::new(),
+            TokenKind::Keyword => Style::new().magenta(),
+            TokenKind::String => Style::new().green(),
+            TokenKind::Comment => Style::new().dim(),
+            TokenKind::Number => Style::new().cyan(),
+        }
+    }
+}
+
+struct Language {
+    line_comments: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+    string_delimiters: &'static [char],
+    /// Separated by spaces.
+    keywords: &'static str,
+}
+
+const RUST: Language = Language {
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    string_delimiters: &['"'],
+    keywords: "\
+        as async await break const continue crate dyn else enum extern false fn for if impl \
+        in let loop match mod move mut pub ref return self Self static struct super trait \
+        true type unsafe use where while",
+};
+
+const GO: Language = Language {
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    string_delimiters: &['"', '`', '\''],
+    keywords: "\
+        break case chan const continue default defer else fallthrough false for func go goto \
+        if import interface map nil package range return select struct switch true type var",
+};
+
+const PYTHON: Language = Language {
+    line_comments: &["#"],
+    block_comment: None,
+    string_delimiters: &['"', '\''],
+    keywords: "\
+        and as assert async await break class continue def del elif else except False \
+        finally for from global if import in is lambda None nonlocal not or pass raise \
+        return True try while with yield",
+};
+
+const JAVASCRIPT: Language = Language {
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    string_delimiters: &['"', '\'', '`'],
+    keywords: "\
+        async await break case catch class const continue default delete else export extends \
+        false finally for from function if import in instanceof interface let new null of \
+        return switch this throw true try type typeof undefined var void while yield",
+};
+
+const C_LIKE: Language = Language {
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    string_delimiters: &['"', '\''],
+    keywords: "\
+        abstract auto bool break case catch char class const continue default do double else \
+        enum extends false final float for if implements import int long namespace new null \
+        nullptr package private protected public return short static struct switch this \
+        throw true try typedef unsigned void while",
+};
+
+const SHELL: Language = Language {
+    line_comments: &["#"],
+    block_comment: None,
+    string_delimiters: &['"', '\''],
+    keywords: "\
+        case do done elif else esac export fi for function if in local return then until \
+        while",
+};
+
+const CONFIG: Language = Language {
+    line_comments: &["#"],
+    block_comment: None,
+    string_delimiters: &['"', '\''],
+    keywords: "true false null",
+};
+
+const JSON: Language = Language {
+    line_comments: &[],
+    block_comment: None,
+    string_delimiters: &['"'],
+    keywords: "true false null",
+};
+
+fn language_for(extension: &str) -> Option<&'static Language> {
+    let language = match extension {
+        "rs" => &RUST,
+        "go" => &GO,
+        "py" => &PYTHON,
+        "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" => &JAVASCRIPT,
+        "c" | "h" | "cc" | "cpp" | "hpp" | "java" | "kt" | "cs" => &C_LIKE,
+        "sh" | "bash" | "zsh" => &SHELL,
+        "toml" | "yaml" | "yml" => &CONFIG,
+        "json" => &JSON,
+        _ => return None,
+    };
+
+    Some(language)
+}
+
+/// Splits a line into tokens; `in_block_comment` carries whether a block comment is open from
+/// one line to the next.
+fn tokenize<'a>(
+    line: &'a str,
+    language: &Language,
+    in_block_comment: &mut bool,
+) -> Vec<(TokenKind, &'a str)> {
+    let mut tokens: Vec<(TokenKind, &'a str)> = vec![];
+    let mut push = |kind: TokenKind, start: usize, end: usize| match tokens.last_mut() {
+        Some((last_kind, text)) if *last_kind == kind => {
+            *text = &line[start - text.len()..end];
+        }
+        _ => tokens.push((kind, &line[start..end])),
+    };
+
+    let mut i = 0;
+    while i < line.len() {
+        let rest = &line[i..];
+
+        if let Some((start, end)) = language.block_comment {
+            let search_from = if *in_block_comment {
+                Some(0)
+            } else {
+                rest.starts_with(start).then_some(start.len())
+            };
+            if let Some(from) = search_from {
+                let comment_end = match rest[from..].find(end) {
+                    Some(pos) => {
+                        *in_block_comment = false;
+                        i + from + pos + end.len()
+                    }
+                    None => {
+                        *in_block_comment = true;
+                        line.len()
+                    }
+                };
+                push(TokenKind::Comment, i, comment_end);
+                i = comment_end;
+                continue;
+            }
+        }
+
+        if language.line_comments.iter().any(|c| rest.starts_with(c)) {
+            push(TokenKind::Comment, i, line.len());
+            break;
+        }
+
+        let Some(c) = rest.chars().next() else {
+            break;
+        };
+
+        let end = if language.string_delimiters.contains(&c) {
+            let mut escaped = false;
+            let closing = rest
+                .char_indices()
+                .skip(1)
+                .find(|&(_, ch)| {
+                    let closes = ch == c && !escaped;
+                    escaped = ch == '\\' && !escaped;
+                    closes
+                })
+                .map(|(pos, ch)| i + pos + ch.len_utf8());
+            push(TokenKind::String, i, closing.unwrap_or(line.len()));
+            closing.unwrap_or(line.len())
+        } else if c.is_alphanumeric() || c == '_' {
+            let word_end = rest
+                .find(|ch: char| {
+                    !(ch.is_alphanumeric() || ch == '_' || (c.is_ascii_digit() && ch == '.'))
+                })
+                .map(|pos| i + pos)
+                .unwrap_or(line.len());
+            let word = &line[i..word_end];
+            let kind = if c.is_ascii_digit() {
+                TokenKind::Number
+            } else if language.keywords.split_whitespace().any(|k| k == word) {
+                TokenKind::Keyword
+            } else {
+                TokenKind::Plain
+            };
+            push(kind, i, word_end);
+            word_end
+        } else {
+            push(TokenKind::Plain, i, i + c.len_utf8());
+            i + c.len_utf8()
+        };
+
+        i = end;
+    }
+
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::assert_snapshot;
+
+    #[test]
+    fn lines_are_split_into_highlighted_tokens() {
+        // GIVEN
+        let lines = [
+            r#"pub fn greet(name: &str) -> String { // says hi"#,
+            r#"    format!("hello, \"{name}\"", 42) /* a"#,
+            r#"    multi-line comment */ let x = 1.5;"#,
+        ];
+
+        // WHEN
+        let mut in_block_comment = false;
+        let tokenized = lines
+            .iter()
+            .map(|line| {
+                tokenize(line, &RUST, &mut in_block_comment)
+                    .into_iter()
+                    .filter(|(kind, _)| *kind != TokenKind::Plain)
+                    .map(|(kind, text)| format!("{kind:?}({text})"))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        // THEN
+        assert_snapshot!(tokenized, @r#"
+        Keyword(pub) Keyword(fn) Comment(// says hi)
+        String("hello, \"{name}\"") Number(42) Comment(/* a)
+        Comment(    multi-line comment */) Keyword(let) Number(1.5)
+        "#);
+    }
+
+    #[test]
+    fn line_ranges_can_be_viewed() -> anyhow::Result<()> {
+        // GIVEN
+        let contents = (1..=12)
+            .map(|n| format!("line {n}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        // WHEN
+        let viewed = ["8:10", "11:", "12", "0:3", "5:4", "20"]
+            .map(
+                |range| match render_file(Path::new("notes.txt"), &contents, Some(range)) {
+                    Ok(rendered) => rendered,
+                    Err(e) => format!("error: {e}"),
+                },
+            )
+            .join("\n---\n");
+
+        // THEN
+        assert_snapshot!(viewed, @r#"
+         8  line 8
+         9  line 9
+        10  line 10
+        ---
+        11  line 11
+        12  line 12
+        ---
+        12  line 12
+        ---
+        error: invalid line range "0:3"; expected something like "10:20", "10:", ":20", or "10"
+        ---
+        error: invalid line range "5:4"; expected something like "10:20", "10:", ":20", or "10"
+        ---
+        error: the file only has 12 lines
+        "#);
+
+        Ok(())
+    }
+}
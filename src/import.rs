@@ -0,0 +1,471 @@
+//! Imports conversations from other agent CLIs (Claude Code, Codex CLI), converting their session
+//! logs into agx's transcript format, so that work can be continued in agx.
+
+use crate::session::{RecordedResponse, TranscriptEvent};
+use rig::message::{Message, Reasoning, ToolCall, ToolFunction};
+use serde_json::Value;
+
+/// CLIs whose session logs can be imported.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImportSource {
+    /// JSON lines, as saved in ~/.claude/projects.
+    ClaudeCode,
+    /// JSON lines ("rollouts"), as saved in ~/.codex/sessions.
+    Codex,
+}
+
+impl ImportSource {
+    /// Guesses the source of a session log from its contents.
+    pub fn detect(contents: &str) -> Option<Self> {
+        contents.lines().find_map(|line| {
+            let entry = serde_json::from_str::<Value>(line).ok()?;
+            match entry["type"].as_str()? {
+                "user" | "assistant" if entry["message"].is_object() => Some(Self::ClaudeCode),
+                "session_meta" | "response_item" | "turn_context" | "event_msg" => {
+                    Some(Self::Codex)
+                }
+                // rollouts written by older versions of Codex CLI list items without a wrapper
+                "message" | "function_call" | "function_call_output" => Some(Self::Codex),
+                _ => None,
+            }
+        })
+    }
+}
+
+impl std::fmt::Display for ImportSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ImportSource::ClaudeCode => "claude-code",
+            ImportSource::Codex => "codex",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+/// Converts a session log into transcript events, starting with a [`TranscriptEvent::Started`]
+/// that records where the conversation came from. Tool calls keep the names and arguments they
+/// had in the other CLI; entries that aren't a part of the conversation (eg. metadata, or the
+/// messages of subagents) are skipped.
+pub fn import_transcript(
+    contents: &str,
+    source: ImportSource,
+) -> anyhow::Result<Vec<TranscriptEvent>> {
+    let mut importer = Importer::default();
+    for line in contents.lines().filter(|l| !l.trim().is_empty()) {
+        // logs of sessions that are still running can end with a partial line
+        let Ok(entry) = serde_json::from_str::<Value>(line) else {
+            continue;
+        };
+        match source {
+            ImportSource::ClaudeCode => importer.claude_code_entry(&entry),
+            ImportSource::Codex => importer.codex_entry(&entry),
+        }
+    }
+    importer.flush_response();
+
+    if !importer
+        .events
+        .iter()
+        .any(|e| matches!(e, TranscriptEvent::Prompt { .. }))
+    {
+        anyhow::bail!("no conversation found; is this a {source} session log?");
+    }
+
+    let mut events = vec![TranscriptEvent::Started {
+        provider: source.to_string(),
+        model: importer.model.unwrap_or_else(|| "unknown".to_string()),
+        text_tool_calls: false,
+        agx_version: None,
+        config_digest: None,
+    }];
+    events.extend(importer.events);
+
+    Ok(events)
+}
+
+/// Builds the chat history to continue an imported conversation with. The other CLI's tools
+/// don't exist in agx, so tool calls and their results are included as text rather than as tool
+/// calls the model would have made.
+pub fn chat_history(events: &[TranscriptEvent]) -> Vec<Message> {
+    let mut history = vec![];
+    let mut user_text = String::new();
+    let push_user_text = |history: &mut Vec<Message>, text: &mut String| {
+        if !text.is_empty() {
+            history.push(Message::user(std::mem::take(text)));
+        }
+    };
+
+    for event in events {
+        match event {
+            TranscriptEvent::Started { .. } | TranscriptEvent::Bookmark(_) => {}
+            TranscriptEvent::Prompt { text } => append_paragraph(&mut user_text, text),
+            TranscriptEvent::ToolResult { id, output } => {
+                append_paragraph(&mut user_text, &format!("[tool result {id}]\n{output}"));
+            }
+            TranscriptEvent::Response(response) => {
+                let mut text = response.text.clone();
+                for call in &response.tool_calls {
+                    append_paragraph(
+                        &mut text,
+                        &format!(
+                            "[tool call {}: {} {}]",
+                            call.id, call.function.name, call.function.arguments
+                        ),
+                    );
+                }
+                if text.is_empty() {
+                    continue;
+                }
+
+                push_user_text(&mut history, &mut user_text);
+                history.push(Message::assistant(text));
+            }
+        }
+    }
+    push_user_text(&mut history, &mut user_text);
+
+    history
+}
+
+#[derive(Default)]
+struct Importer {
+    model: Option<String>,
+    events: Vec<TranscriptEvent>,
+    /// Both CLIs log a response's parts (text, tool calls) as separate entries; they're put
+    /// back together here until something else comes along.
+    response: Option<RecordedResponse>,
+}
+
+impl Importer {
+    fn claude_code_entry(&mut self, entry: &Value) {
+        if entry["isSidechain"].as_bool() == Some(true) || entry["isMeta"].as_bool() == Some(true) {
+            return;
+        }
+
+        let message = &entry["message"];
+        match entry["type"].as_str() {
+            Some("user") => match &message["content"] {
+                Value::String(text) => self.prompt(text),
+                Value::Array(blocks) => {
+                    for block in blocks {
+                        match block["type"].as_str() {
+                            Some("text") => self.prompt(block["text"].as_str().unwrap_or_default()),
+                            Some("tool_result") => {
+                                let output = match &block["content"] {
+                                    Value::String(s) => s.clone(),
+                                    Value::Array(parts) => parts
+                                        .iter()
+                                        .filter_map(|p| p["text"].as_str())
+                                        .collect::<Vec<_>>()
+                                        .join("\n"),
+                                    _ => String::new(),
+                                };
+                                self.tool_result(
+                                    block["tool_use_id"].as_str().unwrap_or_default(),
+                                    output,
+                                );
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                _ => {}
+            },
+            Some("assistant") => {
+                if let Some(model) = message["model"].as_str() {
+                    self.model = Some(model.to_string());
+                }
+                let response = self.response();
+                response.input_tokens += message["usage"]["input_tokens"].as_u64().unwrap_or(0);
+                response.output_tokens += message["usage"]["output_tokens"].as_u64().unwrap_or(0);
+
+                for block in message["content"].as_array().into_iter().flatten() {
+                    match block["type"].as_str() {
+                        Some("text") => {
+                            append_text(response, block["text"].as_str().unwrap_or_default())
+                        }
+                        Some("thinking") => response.reasoning.push(Reasoning::new(
+                            block["thinking"].as_str().unwrap_or_default(),
+                        )),
+                        Some("tool_use") => response.tool_calls.push(ToolCall::new(
+                            block["id"].as_str().unwrap_or_default().to_string(),
+                            ToolFunction::new(
+                                block["name"].as_str().unwrap_or_default().to_string(),
+                                block["input"].clone(),
+                            ),
+                        )),
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn codex_entry(&mut self, entry: &Value) {
+        let item = match entry["type"].as_str() {
+            Some("response_item") => &entry["payload"],
+            Some("turn_context") => {
+                if let Some(model) = entry["payload"]["model"].as_str() {
+                    self.model = Some(model.to_string());
+                }
+                return;
+            }
+            Some("session_meta" | "event_msg") => return,
+            _ => entry,
+        };
+
+        match item["type"].as_str() {
+            Some("message") => {
+                let text = item["content"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|c| c["text"].as_str())
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                match item["role"].as_str() {
+                    // context Codex CLI adds to the conversation by itself
+                    Some("user")
+                        if !text.starts_with("<environment_context>")
+                            && !text.starts_with("<user_instructions>") =>
+                    {
+                        self.prompt(&text)
+                    }
+                    Some("assistant") => append_text(self.response(), &text),
+                    _ => {}
+                }
+            }
+            Some("reasoning") => {
+                let summary = item["summary"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|s| s["text"].as_str())
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                if !summary.is_empty() {
+                    self.response().reasoning.push(Reasoning::new(&summary));
+                }
+            }
+            Some("function_call") => {
+                let arguments = item["arguments"]
+                    .as_str()
+                    .and_then(|a| serde_json::from_str(a).ok())
+                    .unwrap_or_else(|| item["arguments"].clone());
+                self.response().tool_calls.push(ToolCall::new(
+                    item["call_id"].as_str().unwrap_or_default().to_string(),
+                    ToolFunction::new(
+                        item["name"].as_str().unwrap_or_default().to_string(),
+                        arguments,
+                    ),
+                ));
+            }
+            Some("function_call_output") => {
+                let output = match &item["output"] {
+                    // shell commands' output is wrapped in JSON, along with metadata
+                    Value::String(s) => serde_json::from_str::<Value>(s)
+                        .ok()
+                        .and_then(|v| v["output"].as_str().map(str::to_string))
+                        .unwrap_or_else(|| s.clone()),
+                    other => other["content"]
+                        .as_str()
+                        .map(str::to_string)
+                        .unwrap_or_else(|| other.to_string()),
+                };
+                self.tool_result(item["call_id"].as_str().unwrap_or_default(), output);
+            }
+            _ => {}
+        }
+    }
+
+    fn prompt(&mut self, text: &str) {
+        if text.trim().is_empty() {
+            return;
+        }
+
+        self.flush_response();
+        self.events.push(TranscriptEvent::Prompt {
+            text: text.to_string(),
+        });
+    }
+
+    fn tool_result(&mut self, id: &str, output: String) {
+        self.flush_response();
+        self.events.push(TranscriptEvent::ToolResult {
+            id: id.to_string(),
+            output,
+        });
+    }
+
+    fn response(&mut self) -> &mut RecordedResponse {
+        self.response.get_or_insert_with(|| RecordedResponse {
+            text: String::new(),
+            reasoning: vec![],
+            tool_calls: vec![],
+            input_tokens: 0,
+            output_tokens: 0,
+        })
+    }
+
+    fn flush_response(&mut self) {
+        if let Some(response) = self.response.take() {
+            self.events.push(TranscriptEvent::Response(response));
+        }
+    }
+}
+
+fn append_text(response: &mut RecordedResponse, text: &str) {
+    append_paragraph(&mut response.text, text);
+}
+
+fn append_paragraph(text: &mut String, paragraph: &str) {
+    if paragraph.is_empty() {
+        return;
+    }
+
+    if !text.is_empty() {
+        text.push_str("\n\n");
+    }
+    text.push_str(paragraph);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::assert_snapshot;
+
+    fn to_jsonl(events: &[TranscriptEvent]) -> String {
+        events
+            .iter()
+            .filter_map(|e| serde_json::to_string(e).ok())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn claude_code_sessions_can_be_imported() -> anyhow::Result<()> {
+        // GIVEN
+        let log = r#"{"type":"summary","summary":"Fix failing test"}
+{"type":"user","message":{"role":"user","content":"fix the failing test"}}
+{"type":"assistant","message":{"model":"claude-sonnet-4-5","role":"assistant","content":[{"type":"text","text":"Let me run the tests."}],"usage":{"input_tokens":1200,"output_tokens":20}}}
+{"type":"assistant","message":{"model":"claude-sonnet-4-5","role":"assistant","content":[{"type":"tool_use","id":"toolu_1","name":"Bash","input":{"command":"cargo test"}}],"usage":{"input_tokens":0,"output_tokens":15}}}
+{"type":"user","isSidechain":true,"message":{"role":"user","content":"subagent prompt"}}
+{"type":"user","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"toolu_1","content":"test result: FAILED. 1 failed"}]}}
+{"type":"assistant","message":{"model":"claude-sonnet-4-5","role":"assistant","content":[{"type":"text","text":"One test fails."}],"usage":{"input_tokens":1400,"output_tokens":10}}}
+{"type":"assistant","message":{"#;
+
+        // WHEN
+        let source = ImportSource::detect(log);
+        let events = import_transcript(log, ImportSource::ClaudeCode)?;
+
+        // THEN
+        assert_eq!(source, Some(ImportSource::ClaudeCode));
+        assert_snapshot!(to_jsonl(&events), @r#"
+        {"event":"started","provider":"claude-code","model":"claude-sonnet-4-5","text_tool_calls":false}
+        {"event":"prompt","text":"fix the failing test"}
+        {"event":"response","text":"Let me run the tests.","tool_calls":[{"id":"toolu_1","call_id":null,"function":{"name":"Bash","arguments":{"command":"cargo test"}},"signature":null,"additional_params":null}],"input_tokens":1200,"output_tokens":35}
+        {"event":"tool_result","id":"toolu_1","output":"test result: FAILED. 1 failed"}
+        {"event":"response","text":"One test fails.","input_tokens":1400,"output_tokens":10}
+        "#);
+
+        Ok(())
+    }
+
+    #[test]
+    fn tool_calls_of_imported_conversations_are_included_as_text() -> anyhow::Result<()> {
+        // GIVEN
+        let log = r#"{"type":"response_item","payload":{"type":"message","role":"user","content":[{"type":"input_text","text":"bump the version"}]}}
+{"type":"response_item","payload":{"type":"function_call","name":"shell","arguments":"{\"command\":[\"cat\",\"VERSION\"]}","call_id":"call_1"}}
+{"type":"response_item","payload":{"type":"function_call_output","call_id":"call_1","output":"1.2.0"}}
+{"type":"response_item","payload":{"type":"message","role":"assistant","content":[{"type":"output_text","text":"It's 1.2.0; bump to 1.3.0?"}]}}
+{"type":"response_item","payload":{"type":"message","role":"user","content":[{"type":"input_text","text":"yes"}]}}"#;
+        let events = import_transcript(log, ImportSource::Codex)?;
+
+        // WHEN
+        let history = chat_history(&events);
+
+        // THEN
+        assert_snapshot!(serde_json::to_string_pretty(&history)?, @r#"
+        [
+          {
+            "role": "user",
+            "content": [
+              {
+                "type": "text",
+                "text": "bump the version"
+              }
+            ]
+          },
+          {
+            "role": "assistant",
+            "id": null,
+            "content": [
+              {
+                "text": "[tool call call_1: shell {\"command\":[\"cat\",\"VERSION\"]}]"
+              }
+            ]
+          },
+          {
+            "role": "user",
+            "content": [
+              {
+                "type": "text",
+                "text": "[tool result call_1]\n1.2.0"
+              }
+            ]
+          },
+          {
+            "role": "assistant",
+            "id": null,
+            "content": [
+              {
+                "text": "It's 1.2.0; bump to 1.3.0?"
+              }
+            ]
+          },
+          {
+            "role": "user",
+            "content": [
+              {
+                "type": "text",
+                "text": "yes"
+              }
+            ]
+          }
+        ]
+        "#);
+
+        Ok(())
+    }
+
+    #[test]
+    fn codex_sessions_can_be_imported() -> anyhow::Result<()> {
+        // GIVEN
+        let log = r##"{"timestamp":"2025-09-01T10:00:00Z","type":"session_meta","payload":{"id":"abc","cwd":"/projects/agx"}}
+{"timestamp":"2025-09-01T10:00:00Z","type":"response_item","payload":{"type":"message","role":"user","content":[{"type":"input_text","text":"<environment_context>\n  <cwd>/projects/agx</cwd>\n</environment_context>"}]}}
+{"timestamp":"2025-09-01T10:00:01Z","type":"turn_context","payload":{"cwd":"/projects/agx","model":"gpt-5-codex"}}
+{"timestamp":"2025-09-01T10:00:01Z","type":"response_item","payload":{"type":"message","role":"user","content":[{"type":"input_text","text":"what's in the readme?"}]}}
+{"timestamp":"2025-09-01T10:00:02Z","type":"response_item","payload":{"type":"reasoning","summary":[{"type":"summary_text","text":"Reading the README"}]}}
+{"timestamp":"2025-09-01T10:00:02Z","type":"response_item","payload":{"type":"function_call","name":"shell","arguments":"{\"command\":[\"cat\",\"README.md\"]}","call_id":"call_1"}}
+{"timestamp":"2025-09-01T10:00:03Z","type":"response_item","payload":{"type":"function_call_output","call_id":"call_1","output":"{\"output\":\"# agx\\n\",\"metadata\":{\"exit_code\":0}}"}}
+{"timestamp":"2025-09-01T10:00:04Z","type":"response_item","payload":{"type":"message","role":"assistant","content":[{"type":"output_text","text":"It's just a title."}]}}"##;
+
+        // WHEN
+        let source = ImportSource::detect(log);
+        let events = import_transcript(log, ImportSource::Codex)?;
+
+        // THEN
+        assert_eq!(source, Some(ImportSource::Codex));
+        assert_snapshot!(to_jsonl(&events), @r##"
+        {"event":"started","provider":"codex","model":"gpt-5-codex","text_tool_calls":false}
+        {"event":"prompt","text":"what's in the readme?"}
+        {"event":"response","text":"","reasoning":[{"id":null,"reasoning":["Reading the README"]}],"tool_calls":[{"id":"call_1","call_id":null,"function":{"name":"shell","arguments":{"command":["cat","README.md"]}},"signature":null,"additional_params":null}],"input_tokens":0,"output_tokens":0}
+        {"event":"tool_result","id":"call_1","output":"# agx\n"}
+        {"event":"response","text":"It's just a title.","input_tokens":0,"output_tokens":0}
+        "##);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,68 @@
+const CHUNK_LINES: usize = 60;
+const CHUNK_OVERLAP_LINES: usize = 10;
+
+/// A contiguous range of lines from a file; line numbers are 1-indexed and inclusive.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk {
+    pub start_line: usize,
+    pub end_line: usize,
+    pub text: String,
+}
+
+/// Splits a file's contents into overlapping windows of lines, so that each chunk is small enough
+/// to be embedded on its own while retaining some of its surrounding context.
+pub fn chunk_lines(contents: &str) -> Vec<Chunk> {
+    let lines = contents.lines().collect::<Vec<_>>();
+    let mut chunks = vec![];
+    let mut start = 0;
+
+    while start < lines.len() {
+        let end = (start + CHUNK_LINES).min(lines.len());
+        let text = lines[start..end].join("\n");
+        if !text.trim().is_empty() {
+            chunks.push(Chunk {
+                start_line: start + 1,
+                end_line: end,
+                text,
+            });
+        }
+
+        if end == lines.len() {
+            break;
+        }
+        start = end - CHUNK_OVERLAP_LINES;
+    }
+
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::assert_yaml_snapshot;
+
+    #[test]
+    fn chunking_splits_contents_into_overlapping_windows() {
+        // GIVEN
+        let contents = (1..=130)
+            .map(|i| format!("line {i}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        // WHEN
+        let chunks = chunk_lines(&contents)
+            .into_iter()
+            .map(|c| (c.start_line, c.end_line))
+            .collect::<Vec<_>>();
+
+        // THEN
+        assert_yaml_snapshot!(chunks, @r"
+        - - 1
+          - 60
+        - - 51
+          - 110
+        - - 101
+          - 130
+        ");
+    }
+}
@@ -0,0 +1,138 @@
+use crate::domain::Provider;
+use crate::env::{get_env_var, get_optional_env_var};
+use crate::tools::BoxFuture;
+use anyhow::Context;
+use rig::client::{Client, EmbeddingsClient};
+use rig::embeddings::{EmbeddingError, EmbeddingModel};
+use rig::providers::gemini::client::GeminiExt;
+use rig::providers::mistral::MistralExt;
+use rig::providers::openai::OpenAIResponsesExt;
+use rig::providers::{gemini, mistral, openai};
+use std::str::FromStr;
+
+// Embedding requests are batched so that progress can be reported while indexing large projects.
+const MAX_BATCH_SIZE: usize = 64;
+
+/// Generates embeddings for text; used to build the index and to embed search queries.
+pub trait Embedder: Send + Sync {
+    fn model_name(&self) -> &str;
+
+    fn batch_size(&self) -> usize;
+
+    fn embed(&self, texts: Vec<String>) -> BoxFuture<'_, Result<Vec<Vec<f32>>, EmbeddingError>>;
+}
+
+struct RigEmbedder<M: EmbeddingModel> {
+    model: M,
+    model_name: String,
+}
+
+impl<M> Embedder for RigEmbedder<M>
+where
+    M: EmbeddingModel + 'static,
+{
+    fn model_name(&self) -> &str {
+        &self.model_name
+    }
+
+    fn batch_size(&self) -> usize {
+        M::MAX_DOCUMENTS.clamp(1, MAX_BATCH_SIZE)
+    }
+
+    fn embed(&self, texts: Vec<String>) -> BoxFuture<'_, Result<Vec<Vec<f32>>, EmbeddingError>> {
+        Box::pin(async move {
+            let embeddings = self.model.embed_texts(texts).await?;
+
+            Ok(embeddings
+                .into_iter()
+                .map(|e| e.vec.into_iter().map(|v| v as f32).collect())
+                .collect())
+        })
+    }
+}
+
+/// Builds an embedder from the environment. The embeddings provider, API key, and base URL fall
+/// back to the ones used for completions, since the embeddings are often served by the same
+/// provider.
+pub fn get_embedder() -> anyhow::Result<Box<dyn Embedder>> {
+    let provider = match get_optional_env_var("EMBEDDINGS_PROVIDER")? {
+        Some(p) => p,
+        None => get_env_var("PROVIDER")?,
+    };
+    let provider = Provider::from_str(&provider).map_err(|e| anyhow::anyhow!(e))?;
+    let model_name = get_env_var("EMBEDDINGS_MODEL")?;
+    let api_key = match get_optional_env_var("EMBEDDINGS_API_KEY")? {
+        Some(k) => Some(k),
+        None => get_optional_env_var("API_KEY")?,
+    };
+    let base_url = match get_optional_env_var("EMBEDDINGS_BASE_URL")? {
+        Some(u) => Some(u),
+        None => get_optional_env_var("BASE_URL")?,
+    };
+
+    let embedder: Box<dyn Embedder> = match provider {
+        Provider::Gemini => {
+            let mut builder = gemini::Client::builder().api_key(api_key.context(
+                r#"environment variable "EMBEDDINGS_API_KEY" (or "API_KEY") is not set"#,
+            )?);
+            if let Some(u) = base_url {
+                builder = builder.base_url(u);
+            }
+            let client: Client<GeminiExt> = builder.build().context("couldn't build client")?;
+
+            Box::new(RigEmbedder {
+                model: client.embedding_model(&model_name),
+                model_name,
+            })
+        }
+        Provider::Mistral => {
+            let mut builder = mistral::Client::builder().api_key(api_key.context(
+                r#"environment variable "EMBEDDINGS_API_KEY" (or "API_KEY") is not set"#,
+            )?);
+            if let Some(u) = base_url {
+                builder = builder.base_url(u);
+            }
+            let client: Client<MistralExt> = builder.build().context("couldn't build client")?;
+
+            Box::new(RigEmbedder {
+                model: client.embedding_model(&model_name),
+                model_name,
+            })
+        }
+        Provider::OpenAI => {
+            let mut builder = openai::Client::builder().api_key(api_key.context(
+                r#"environment variable "EMBEDDINGS_API_KEY" (or "API_KEY") is not set"#,
+            )?);
+            if let Some(u) = base_url {
+                builder = builder.base_url(u);
+            }
+            let client: Client<OpenAIResponsesExt> =
+                builder.build().context("couldn't build client")?;
+
+            Box::new(RigEmbedder {
+                model: client.embedding_model(&model_name),
+                model_name,
+            })
+        }
+        Provider::OpenAICompatible => {
+            let base_url = base_url.context(
+                r#"environment variable "EMBEDDINGS_BASE_URL" (or "BASE_URL") needs to be set for the "openai-compatible" provider"#,
+            )?;
+            let client: Client<OpenAIResponsesExt> = openai::Client::builder()
+                .api_key(api_key.unwrap_or_default())
+                .base_url(base_url)
+                .build()
+                .context("couldn't build client")?;
+
+            Box::new(RigEmbedder {
+                model: client.embedding_model(&model_name),
+                model_name,
+            })
+        }
+        _ => anyhow::bail!(
+            r#"provider "{provider}" doesn't support embeddings; set "EMBEDDINGS_PROVIDER" to one of [gemini, mistral, openai, openai-compatible]"#
+        ),
+    };
+
+    Ok(embedder)
+}
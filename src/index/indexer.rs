@@ -0,0 +1,297 @@
+use super::{Chunk, Embedder, IndexStore, IndexedChunk, IndexedFile, chunk_lines, get_embedder};
+use crate::config::{ensure_gitignore, get_index_file_path};
+use anyhow::Context;
+use colored::Colorize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Display;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+// Larger files are usually generated or vendored, and not worth embedding.
+const MAX_FILE_SIZE_BYTES: u64 = 256 * 1024;
+
+#[derive(Debug, Default)]
+pub struct RefreshSummary {
+    pub files_updated: usize,
+    pub files_removed: usize,
+    pub chunks_embedded: usize,
+}
+
+impl RefreshSummary {
+    pub fn is_empty(&self) -> bool {
+        self.files_updated == 0 && self.files_removed == 0
+    }
+}
+
+impl Display for RefreshSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return write!(f, "index is up to date");
+        }
+
+        write!(
+            f,
+            "{} files updated, {} removed; {} chunks embedded",
+            self.files_updated, self.files_removed, self.chunks_embedded
+        )
+    }
+}
+
+/// Builds (or refreshes) the embedding index for the project and saves it to disk.
+pub async fn run_index(root: &Path) -> anyhow::Result<()> {
+    let embedder = get_embedder()?;
+    let index_file_path = get_index_file_path();
+
+    // an index built with a different model can't be reused
+    let previous = IndexStore::load(&index_file_path)
+        .await
+        .ok()
+        .flatten()
+        .filter(|s| s.model == embedder.model_name());
+
+    let (store, summary) = refresh_index(root, previous.as_ref(), embedder.as_ref()).await?;
+
+    store.save(&index_file_path).await.with_context(|| {
+        format!(
+            r#"couldn't save index (to "{}")"#,
+            index_file_path.to_string_lossy()
+        )
+    })?;
+    ensure_gitignore().await?;
+
+    println!(
+        "{}",
+        format!(
+            r#"{summary}; index has {} chunks from {} files (saved to "{}")"#,
+            store.chunks.len(),
+            store.files.len(),
+            index_file_path.to_string_lossy()
+        )
+        .green()
+    );
+
+    Ok(())
+}
+
+/// Brings the index in sync with the project's files. Files whose modification time and size
+/// haven't changed are skipped, and chunks of changed files whose contents match a previously
+/// indexed chunk reuse its embedding; only the remaining chunks are embedded.
+pub async fn refresh_index(
+    root: &Path,
+    previous: Option<&IndexStore>,
+    embedder: &dyn Embedder,
+) -> anyhow::Result<(IndexStore, RefreshSummary)> {
+    let mut summary = RefreshSummary::default();
+    let mut files = BTreeMap::new();
+    let mut chunks = vec![];
+    let mut pending = vec![];
+
+    let previous_chunks = previous.map(|s| s.chunks.as_slice()).unwrap_or_default();
+    let embeddings_by_hash = previous_chunks
+        .iter()
+        .map(|c| (c.hash, &c.embedding))
+        .collect::<HashMap<_, _>>();
+
+    for (path, full_path, file) in walk_files(root)? {
+        let unchanged = previous.and_then(|s| s.files.get(&path)) == Some(&file);
+        files.insert(path.clone(), file);
+
+        if unchanged {
+            chunks.extend(previous_chunks.iter().filter(|c| c.path == path).cloned());
+            continue;
+        }
+
+        // binary files and files with invalid UTF-8 are skipped
+        let Ok(contents) = std::fs::read_to_string(&full_path) else {
+            continue;
+        };
+        summary.files_updated += 1;
+
+        for chunk in chunk_lines(&contents) {
+            let text = embedding_text(&path, &chunk);
+            let hash = hash_text(&text);
+            match embeddings_by_hash.get(&hash) {
+                Some(embedding) => {
+                    chunks.push(indexed_chunk(&path, chunk, hash, (*embedding).clone()))
+                }
+                None => pending.push((path.clone(), chunk, hash, text)),
+            }
+        }
+    }
+
+    summary.files_removed = previous
+        .map(|s| s.files.keys().filter(|p| !files.contains_key(*p)).count())
+        .unwrap_or_default();
+
+    let total = pending.len();
+    for batch in pending.chunks(embedder.batch_size()) {
+        print!(
+            "\r{}",
+            format!("embedding chunks: {}/{}", summary.chunks_embedded, total).blue()
+        );
+        let _ = std::io::stdout().flush();
+
+        let texts = batch.iter().map(|(.., text)| text.clone()).collect();
+        let embeddings = embedder
+            .embed(texts)
+            .await
+            .context("couldn't embed chunks")?;
+
+        for ((path, chunk, hash, _), embedding) in batch.iter().zip(embeddings) {
+            chunks.push(indexed_chunk(path, chunk.clone(), *hash, embedding));
+            summary.chunks_embedded += 1;
+        }
+    }
+    if total > 0 {
+        println!();
+    }
+
+    chunks.sort_by(|a, b| (&a.path, a.start_line).cmp(&(&b.path, b.start_line)));
+
+    let store = IndexStore {
+        model: embedder.model_name().to_string(),
+        files,
+        chunks,
+    };
+
+    Ok((store, summary))
+}
+
+fn indexed_chunk(path: &str, chunk: Chunk, hash: u64, embedding: Vec<f32>) -> IndexedChunk {
+    IndexedChunk {
+        path: path.to_string(),
+        start_line: chunk.start_line,
+        end_line: chunk.end_line,
+        text: chunk.text,
+        hash,
+        embedding,
+    }
+}
+
+// The path is embedded along with the chunk, since it often says a lot about what the code does.
+fn embedding_text(path: &str, chunk: &Chunk) -> String {
+    format!("{path}\n\n{}", chunk.text)
+}
+
+// The hasher's output isn't guaranteed to be stable across Rust releases; if it changes, chunks
+// are simply embedded again.
+fn hash_text(text: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Returns the files to be indexed (skipping the ones ignored by git), along with their paths
+/// relative to the root.
+fn walk_files(root: &Path) -> anyhow::Result<Vec<(String, PathBuf, IndexedFile)>> {
+    let mut files = vec![];
+
+    for entry in ignore::WalkBuilder::new(root).build() {
+        let entry = entry.context("couldn't walk project directory")?;
+        if !entry.file_type().is_some_and(|t| t.is_file()) {
+            continue;
+        }
+
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if metadata.len() > MAX_FILE_SIZE_BYTES {
+            continue;
+        }
+
+        let modified_ms = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+
+        let path = entry
+            .path()
+            .strip_prefix(root)
+            .unwrap_or(entry.path())
+            .to_string_lossy()
+            .to_string();
+
+        files.push((
+            path,
+            entry.path().to_path_buf(),
+            IndexedFile {
+                modified_ms,
+                size: metadata.len(),
+            },
+        ));
+    }
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::BoxFuture;
+    use rig::embeddings::EmbeddingError;
+
+    struct FakeEmbedder;
+
+    impl Embedder for FakeEmbedder {
+        fn model_name(&self) -> &str {
+            "fake"
+        }
+
+        fn batch_size(&self) -> usize {
+            1
+        }
+
+        fn embed(
+            &self,
+            texts: Vec<String>,
+        ) -> BoxFuture<'_, Result<Vec<Vec<f32>>, EmbeddingError>> {
+            Box::pin(async move { Ok(texts.iter().map(|t| vec![t.len() as f32]).collect()) })
+        }
+    }
+
+    #[tokio::test]
+    async fn refreshing_only_embeds_chunks_that_changed() {
+        // GIVEN
+        let root = Path::new("src/index/testdata");
+        let (mut previous, summary) = refresh_index(root, None, &FakeEmbedder)
+            .await
+            .expect("initial index should've been built");
+        assert_eq!(summary.chunks_embedded, 2);
+
+        // a file was touched without changing its contents, and another one was edited
+        previous
+            .files
+            .get_mut("greet.rs")
+            .expect("greet.rs should've been indexed")
+            .modified_ms = 0;
+        let notes = previous
+            .files
+            .get_mut("notes.md")
+            .expect("notes.md should've been indexed");
+        notes.modified_ms = 0;
+        previous
+            .chunks
+            .iter_mut()
+            .filter(|c| c.path == "notes.md")
+            .for_each(|c| c.hash = 0);
+
+        // WHEN
+        let (store, summary) = refresh_index(root, Some(&previous), &FakeEmbedder)
+            .await
+            .expect("index should've been refreshed");
+
+        // THEN
+        assert_eq!(summary.files_updated, 2);
+        assert_eq!(summary.chunks_embedded, 1);
+        assert_eq!(store.chunks.len(), 2);
+
+        let (_, summary) = refresh_index(root, Some(&store), &FakeEmbedder)
+            .await
+            .expect("index should've been refreshed again");
+        assert!(summary.is_empty());
+    }
+}
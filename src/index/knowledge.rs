@@ -0,0 +1,332 @@
+use super::{Chunk, chunk_lines};
+use anyhow::Context;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::RwLock;
+use std::time::Duration;
+
+const MAX_DOCUMENT_BYTES: usize = 1024 * 1024;
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Documents attached by the user (eg. design docs, API references) for the current session.
+/// Unlike the embedding index, retrieval is keyword based, so that attaching documents doesn't
+/// require an embeddings provider.
+#[derive(Debug, Default)]
+pub struct KnowledgeStore {
+    documents: RwLock<Vec<KnowledgeDocument>>,
+}
+
+#[derive(Debug)]
+struct KnowledgeDocument {
+    source: String,
+    chunks: Vec<Chunk>,
+}
+
+#[derive(Debug)]
+pub struct AttachedDocument {
+    pub source: String,
+    pub bytes: usize,
+    pub chunks: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct KnowledgeHit {
+    pub source: String,
+    pub start_line: usize,
+    pub end_line: usize,
+    pub text: String,
+}
+
+impl KnowledgeStore {
+    /// Ingests a local file or a URL; paths are resolved relative to `dir`. Attaching a source
+    /// again replaces its previous contents.
+    pub async fn attach(&self, source: &str, dir: &Path) -> anyhow::Result<AttachedDocument> {
+        let contents = if source.starts_with("http://") || source.starts_with("https://") {
+            fetch_url(source).await?
+        } else {
+            let bytes = tokio::fs::read(dir.join(source))
+                .await
+                .context("couldn't read file")?;
+            ensure_size(bytes.len())?;
+            String::from_utf8(bytes).context("file is not valid UTF-8")?
+        };
+
+        let chunks = chunk_lines(&contents);
+        if chunks.is_empty() {
+            anyhow::bail!("document is empty");
+        }
+
+        let attached = AttachedDocument {
+            source: source.to_string(),
+            bytes: contents.len(),
+            chunks: chunks.len(),
+        };
+
+        if let Ok(mut documents) = self.documents.write() {
+            documents.retain(|d| d.source != source);
+            documents.push(KnowledgeDocument {
+                source: source.to_string(),
+                chunks,
+            });
+        }
+
+        Ok(attached)
+    }
+
+    pub fn sources(&self) -> Vec<String> {
+        self.documents
+            .read()
+            .map(|documents| documents.iter().map(|d| d.source.clone()).collect())
+            .unwrap_or_default()
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut documents) = self.documents.write() {
+            documents.clear();
+        }
+    }
+
+    /// Returns the chunks that best match the query's terms, weighing rarer terms higher.
+    pub fn search(&self, query: &str, limit: usize) -> Vec<KnowledgeHit> {
+        let Ok(documents) = self.documents.read() else {
+            return vec![];
+        };
+
+        let query_terms = terms(query).into_iter().collect::<HashSet<_>>();
+        let chunks = documents
+            .iter()
+            .flat_map(|d| d.chunks.iter().map(move |c| (d, c, terms(&c.text))))
+            .collect::<Vec<_>>();
+
+        let mut scored = chunks
+            .iter()
+            .map(|(document, chunk, chunk_terms)| {
+                let score = query_terms
+                    .iter()
+                    .map(|term| {
+                        let count = chunk_terms.iter().filter(|t| *t == term).count();
+                        if count == 0 {
+                            return 0.0;
+                        }
+
+                        let chunks_with_term = chunks
+                            .iter()
+                            .filter(|(_, _, other)| other.contains(term))
+                            .count();
+                        let idf = (1.0 + chunks.len() as f64 / chunks_with_term as f64).ln();
+
+                        (1.0 + count as f64).ln() * idf
+                    })
+                    .sum::<f64>();
+
+                (score, document, chunk)
+            })
+            .filter(|(score, ..)| *score > 0.0)
+            .collect::<Vec<_>>();
+
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        scored
+            .into_iter()
+            .take(limit)
+            .map(|(_, document, chunk)| KnowledgeHit {
+                source: document.source.clone(),
+                start_line: chunk.start_line,
+                end_line: chunk.end_line,
+                text: chunk.text.clone(),
+            })
+            .collect()
+    }
+}
+
+fn ensure_size(bytes: usize) -> anyhow::Result<()> {
+    if bytes > MAX_DOCUMENT_BYTES {
+        anyhow::bail!(
+            "document is too large ({} KB); only documents up to {} KB can be attached",
+            bytes / 1024,
+            MAX_DOCUMENT_BYTES / 1024
+        );
+    }
+
+    Ok(())
+}
+
+async fn fetch_url(url: &str) -> anyhow::Result<String> {
+    let http = reqwest::Client::builder()
+        .timeout(FETCH_TIMEOUT)
+        .build()
+        .context("couldn't build HTTP client")?;
+    let response = http
+        .get(url)
+        .send()
+        .await
+        .context("couldn't send request")?;
+    if !response.status().is_success() {
+        anyhow::bail!("server sent a non-success response: {}", response.status());
+    }
+
+    if let Some(length) = response.content_length() {
+        ensure_size(length as usize)?;
+    }
+
+    let is_html = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.contains("text/html"));
+
+    let body = response.text().await.context("couldn't read response")?;
+    ensure_size(body.len())?;
+
+    Ok(if is_html { html_to_text(&body) } else { body })
+}
+
+/// Strips markup from an HTML page, keeping its text; scripts and styles are dropped.
+fn html_to_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let tag = rest
+            .get(1..)
+            .unwrap_or_default()
+            .split(|c: char| c.is_whitespace() || c == '>')
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+
+        let end = match tag.as_str() {
+            // unlike to_lowercase, this keeps byte offsets the same
+            "script" | "style" => rest
+                .to_ascii_lowercase()
+                .find(&format!("</{tag}>"))
+                .map(|i| i + tag.len() + 3),
+            _ => rest.find('>').map(|i| i + 1),
+        };
+
+        match end {
+            Some(end) => {
+                if matches!(
+                    tag.as_str(),
+                    "p" | "/p" | "br" | "br/" | "div" | "/div" | "li" | "tr" | "/pre"
+                ) || tag.starts_with('h')
+                    || tag.starts_with("/h")
+                {
+                    text.push('\n');
+                }
+                rest = &rest[end..];
+            }
+            None => {
+                rest = "";
+            }
+        }
+    }
+    text.push_str(rest);
+
+    let text = text
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+
+    let mut lines = vec![];
+    for line in text.lines().map(str::trim_end) {
+        if line.trim().is_empty() && lines.last().is_none_or(|l: &&str| l.trim().is_empty()) {
+            continue;
+        }
+        lines.push(line);
+    }
+
+    lines.join("\n")
+}
+
+fn terms(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|t| t.len() > 1)
+        .map(str::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::{assert_snapshot, assert_yaml_snapshot};
+
+    #[test]
+    fn searching_knowledge_prefers_chunks_with_rarer_matching_terms() {
+        // GIVEN
+        let store = KnowledgeStore::default();
+        if let Ok(mut documents) = store.documents.write() {
+            documents.push(KnowledgeDocument {
+                source: "design.md".to_string(),
+                chunks: chunk_lines("the API uses pagination via cursors"),
+            });
+            documents.push(KnowledgeDocument {
+                source: "https://example.com/api".to_string(),
+                chunks: chunk_lines("the API returns errors as JSON objects"),
+            });
+            documents.push(KnowledgeDocument {
+                source: "notes.txt".to_string(),
+                chunks: chunk_lines("unrelated"),
+            });
+        }
+
+        // WHEN
+        let hits = store
+            .search("how are API errors returned?", 5)
+            .into_iter()
+            .map(|h| h.source)
+            .collect::<Vec<_>>();
+
+        // THEN
+        assert_yaml_snapshot!(hits, @r#"
+        - "https://example.com/api"
+        - design.md
+        "#);
+    }
+
+    #[test]
+    fn converting_html_to_text_drops_markup() {
+        // GIVEN
+        let html = r#"<html><head><style>p { color: red; }</style><script>let x = "<p>";</script></head>
+<body><h1>Errors</h1><p>Errors are returned as <code>{"error": ...}</code> &amp; logged.</p>
+
+
+<ul><li>400: bad request</li><li>500: server error</li></ul></body></html>"#;
+
+        // WHEN
+        let text = html_to_text(html);
+
+        // THEN
+        assert_snapshot!(text, @r#"
+        Errors
+
+        Errors are returned as {"error": ...} & logged.
+
+        400: bad request
+        500: server error
+        "#);
+    }
+
+    #[test]
+    fn scripts_and_styles_are_dropped_from_pages_with_non_ascii_text() {
+        // GIVEN
+        let html = "<p>İstanbul</p><SCRIPT>let city = \"İ\";</SCRIPT><p>çay</p><style>İ</style>";
+
+        // WHEN
+        let text = html_to_text(html);
+
+        // THEN
+        assert_snapshot!(text, @r"
+        İstanbul
+
+        çay
+        ");
+    }
+}
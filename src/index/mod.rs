@@ -0,0 +1,15 @@
+//! The semantic index of a project's files, and the knowledge store built up during a session.
+
+mod chunking;
+mod embedder;
+mod indexer;
+mod knowledge;
+mod semantic_index;
+mod store;
+
+pub use chunking::*;
+pub use embedder::*;
+pub use indexer::*;
+pub use knowledge::*;
+pub use semantic_index::*;
+pub use store::*;
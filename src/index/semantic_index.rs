@@ -0,0 +1,102 @@
+use super::{Embedder, IndexStore, RefreshSummary, SearchHit, get_embedder, refresh_index};
+use crate::config::get_index_file_path;
+use anyhow::Context;
+use rig::embeddings::EmbeddingError;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use tokio::sync::RwLock;
+
+/// The project's embedding index, along with the embedder needed to query it.
+pub struct SemanticIndex {
+    root: PathBuf,
+    store: RwLock<IndexStore>,
+    embedder: Box<dyn Embedder>,
+}
+
+pub struct IndexStatus {
+    model: String,
+    files: usize,
+    chunks: usize,
+}
+
+impl Display for IndexStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            r#"index: {} chunks from {} files (embedded using "{}")"#,
+            self.chunks, self.files, self.model
+        )
+    }
+}
+
+impl SemanticIndex {
+    /// Loads the index built via `agx index`, if there is one.
+    pub async fn load(root: impl Into<PathBuf>) -> anyhow::Result<Option<Self>> {
+        let Some(store) = IndexStore::load(get_index_file_path()).await? else {
+            return Ok(None);
+        };
+
+        let embedder = get_embedder()?;
+        if embedder.model_name() != store.model {
+            anyhow::bail!(
+                r#"index was built using the embedding model "{}", but "{}" is configured; run "agx index" to rebuild it"#,
+                store.model,
+                embedder.model_name()
+            );
+        }
+
+        Ok(Some(Self {
+            root: root.into(),
+            store: RwLock::new(store),
+            embedder,
+        }))
+    }
+
+    pub async fn status(&self) -> IndexStatus {
+        let store = self.store.read().await;
+
+        IndexStatus {
+            model: store.model.clone(),
+            files: store.files.len(),
+            chunks: store.chunks.len(),
+        }
+    }
+
+    /// Re-embeds chunks of files that changed since the index was last updated, and saves the
+    /// index to disk.
+    pub async fn refresh(&self) -> anyhow::Result<RefreshSummary> {
+        let mut store = self.store.write().await;
+        let (refreshed, summary) =
+            refresh_index(&self.root, Some(&store), self.embedder.as_ref()).await?;
+
+        if !summary.is_empty() {
+            let index_file_path = get_index_file_path();
+            refreshed.save(&index_file_path).await.with_context(|| {
+                format!(
+                    r#"couldn't save index (to "{}")"#,
+                    index_file_path.to_string_lossy()
+                )
+            })?;
+        }
+        *store = refreshed;
+
+        Ok(summary)
+    }
+
+    pub async fn search(
+        &self,
+        query: &str,
+        limit: usize,
+        within: Option<&Path>,
+    ) -> Result<Vec<SearchHit>, EmbeddingError> {
+        let embedding = self
+            .embedder
+            .embed(vec![query.to_string()])
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| EmbeddingError::ResponseError("no embedding returned".to_string()))?;
+
+        Ok(self.store.read().await.search(&embedding, limit, within))
+    }
+}
@@ -0,0 +1,202 @@
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// A local vector store holding embeddings for chunks of the project's files.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IndexStore {
+    /// The embedding model used to build the index; queries need to be embedded using the same
+    /// model.
+    pub model: String,
+    /// Files that were indexed, keyed by their path relative to the project root; used to
+    /// determine which files have changed since.
+    #[serde(default)]
+    pub files: BTreeMap<String, IndexedFile>,
+    pub chunks: Vec<IndexedChunk>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexedFile {
+    pub modified_ms: u64,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexedChunk {
+    /// Path of the file, relative to the project root.
+    pub path: String,
+    pub start_line: usize,
+    pub end_line: usize,
+    pub text: String,
+    /// Hash of the embedded text; lets unchanged chunks of modified files reuse their embeddings.
+    #[serde(default)]
+    pub hash: u64,
+    pub embedding: Vec<f32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchHit {
+    pub path: String,
+    pub start_line: usize,
+    pub end_line: usize,
+    pub score: f32,
+    pub text: String,
+}
+
+impl IndexStore {
+    pub async fn load<P>(path: P) -> anyhow::Result<Option<Self>>
+    where
+        P: AsRef<Path>,
+    {
+        match tokio::fs::read(path).await {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map(Some)
+                .context("couldn't parse index"),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).context("couldn't read index"),
+        }
+    }
+
+    pub async fn save<P>(&self, path: P) -> anyhow::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let contents = serde_json::to_vec(self).context("couldn't serialize index to JSON")?;
+
+        if let Some(parent) = path.as_ref().parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .context("couldn't create directory")?;
+        }
+
+        tokio::fs::write(path, contents)
+            .await
+            .context("couldn't write index to file")?;
+
+        Ok(())
+    }
+
+    /// Returns the chunks most similar to the query embedding, best match first. If `within` is
+    /// provided, only chunks from files under that directory are considered, and their paths are
+    /// returned relative to it.
+    pub fn search(&self, query: &[f32], limit: usize, within: Option<&Path>) -> Vec<SearchHit> {
+        let mut hits = self
+            .chunks
+            .iter()
+            .filter_map(|chunk| {
+                let path = match within {
+                    Some(dir) => Path::new(&chunk.path)
+                        .strip_prefix(dir)
+                        .ok()?
+                        .to_string_lossy()
+                        .to_string(),
+                    None => chunk.path.clone(),
+                };
+
+                Some(SearchHit {
+                    path,
+                    start_line: chunk.start_line,
+                    end_line: chunk.end_line,
+                    score: cosine_similarity(query, &chunk.embedding),
+                    text: chunk.text.clone(),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+        hits.truncate(limit);
+
+        hits
+    }
+}
+
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+
+    let dot = a.iter().zip(b).map(|(x, y)| x * y).sum::<f32>();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+
+    dot / (norm_a * norm_b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::assert_yaml_snapshot;
+
+    fn chunk(path: &str, embedding: Vec<f32>) -> IndexedChunk {
+        IndexedChunk {
+            path: path.to_string(),
+            start_line: 1,
+            end_line: 10,
+            text: String::new(),
+            hash: 0,
+            embedding,
+        }
+    }
+
+    fn store() -> IndexStore {
+        IndexStore {
+            model: "embedding-model".to_string(),
+            files: BTreeMap::new(),
+            chunks: vec![
+                chunk("src/main.rs", vec![1.0, 0.0]),
+                chunk("crates/core/src/lib.rs", vec![0.6, 0.8]),
+                chunk("crates/core/src/parse.rs", vec![0.0, 1.0]),
+                chunk("README.md", vec![-1.0, 0.0]),
+            ],
+        }
+    }
+
+    #[test]
+    fn searching_returns_the_most_similar_chunks_first() {
+        // GIVEN
+        let store = store();
+
+        // WHEN
+        let hits = store
+            .search(&[1.0, 0.0], 3, None)
+            .into_iter()
+            .map(|h| (h.path, h.score))
+            .collect::<Vec<_>>();
+
+        // THEN
+        assert_yaml_snapshot!(hits, @r"
+        - - src/main.rs
+          - 1
+        - - crates/core/src/lib.rs
+          - 0.6
+        - - crates/core/src/parse.rs
+          - 0
+        ");
+    }
+
+    #[test]
+    fn searching_within_a_directory_only_returns_chunks_under_it() {
+        // GIVEN
+        let store = store();
+
+        // WHEN
+        let hits = store
+            .search(&[0.0, 1.0], 5, Some(Path::new("crates/core")))
+            .into_iter()
+            .map(|h| (h.path, h.score))
+            .collect::<Vec<_>>();
+
+        // THEN
+        assert_yaml_snapshot!(hits, @r"
+        - - src/parse.rs
+          - 1
+        - - src/lib.rs
+          - 0.8
+        ");
+    }
+}
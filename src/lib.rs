@@ -0,0 +1,71 @@
+//! The core of agx: an agent loop that drives a model through tool calls on a project, along
+//! with the tools, providers, and configuration it relies on. The `agx` binary is a thin CLI on
+//! top of this library.
+//!
+//! The main entry point is [`session::Session`], which wraps a [`rig`] agent. Sessions can be
+//! run interactively ([`Session::run`](session::Session::run)), or for a single task without
+//! human oversight ([`Session::run_task`](session::Session::run_task)), in which case the
+//! guardrails in the project's [`Config`](domain::Config) apply. Sessions are put together via
+//! a [`SessionBuilder`](session::SessionBuilder), which also allows choosing the builtin tools,
+//! adding custom rig tools, approving tool calls via a callback, and redirecting output to an
+//! [`OutputSink`](domain::OutputSink).
+//!
+//! ```no_run
+//! use agx_core::domain::Provider;
+//! use agx_core::session::{ApprovalResponse, Session};
+//! use rig::client::{Client, CompletionClient};
+//! use rig::providers::anthropic::{self, client::AnthropicExt};
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! let project_dir = std::env::current_dir()?;
+//! let config = agx_core::config::get_project_config().await?;
+//!
+//! let client: Client<AnthropicExt> = anthropic::Client::builder().api_key("<API_KEY>").build()?;
+//! let agent = client.agent("claude-sonnet-4-5").build();
+//!
+//! let mut session = Session::builder(
+//!     agent,
+//!     Provider::Anthropic,
+//!     "claude-sonnet-4-5",
+//!     project_dir.clone(),
+//!     project_dir.join(".agx-logs"),
+//! )
+//! .config(config)
+//! .builtin_tools(["read_file", "read_dir", "edit_file"])
+//! .approval_callback(|request| {
+//!     if request.tool_name == "edit_file" {
+//!         ApprovalResponse::Approve
+//!     } else {
+//!         ApprovalResponse::Reject
+//!     }
+//! })
+//! .build()?;
+//! session.run_task("fix all clippy warnings").await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Modules marked as hidden are shared with the binary, and aren't a part of the public API.
+
+pub mod config;
+#[doc(hidden)]
+pub mod debug;
+pub mod domain;
+#[doc(hidden)]
+pub mod env;
+pub mod forge;
+#[doc(hidden)]
+pub mod helpers;
+pub mod import;
+pub mod index;
+pub mod mcp;
+pub mod providers;
+pub mod report;
+pub mod session;
+pub mod share;
+#[doc(hidden)]
+pub mod telemetry;
+pub mod tools;
+pub mod worktree;
+
+pub use rig;
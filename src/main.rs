@@ -1,17 +1,17 @@
 mod app;
-mod config;
-mod debug;
-mod domain;
-mod env;
-mod helpers;
-mod providers;
-mod session;
-mod telemetry;
-mod tools;
+mod cli;
+mod crash;
+mod cron;
+mod eval;
+mod self_update;
+mod tasks;
+
+use clap::Parser;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    app::run().await?;
+    let args = cli::Args::parse();
+    app::run(args).await?;
 
     Ok(())
 }
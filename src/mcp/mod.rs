@@ -0,0 +1,125 @@
+//! A client for MCP (Model Context Protocol) servers declared in config, whose tools are offered
+//! to the model alongside agx's own (see [`crate::tools::ToolRegistry::register_mcp`]).
+
+use crate::domain::McpServerConfig;
+use anyhow::Context;
+use rmcp::model::{CallToolRequestParams, CallToolResult, Tool};
+use rmcp::service::RunningService;
+use rmcp::transport::TokioChildProcess;
+use rmcp::{RoleClient, ServiceExt};
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+
+/// Servers that haven't completed the MCP handshake by then are considered to have failed.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A running MCP server; it's stopped once dropped.
+pub struct McpServer {
+    name: String,
+    trusts_read_only_hints: bool,
+    service: RunningService<RoleClient, ()>,
+}
+
+impl std::fmt::Debug for McpServer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("McpServer")
+            .field("name", &self.name)
+            .finish()
+    }
+}
+
+impl McpServer {
+    /// Starts the server in `dir`, and completes the MCP handshake with it. What the server
+    /// writes to stderr goes to `stderr_log`.
+    pub async fn start(
+        name: &str,
+        config: &McpServerConfig,
+        dir: &Path,
+        stderr_log: &Path,
+    ) -> anyhow::Result<Self> {
+        if let Some(parent) = stderr_log.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .context("couldn't create directory for the server's logs")?;
+        }
+        let stderr =
+            std::fs::File::create(stderr_log).context("couldn't create the server's log file")?;
+
+        let mut command = tokio::process::Command::new(&config.command);
+        command
+            .args(&config.args)
+            .envs(&config.env)
+            .current_dir(dir);
+        let (transport, _) = TokioChildProcess::builder(command)
+            .stderr(Stdio::from(stderr))
+            .spawn()
+            .with_context(|| format!("couldn't run {}", config.command))?;
+
+        let service = tokio::time::timeout(STARTUP_TIMEOUT, ().serve(transport))
+            .await
+            .map_err(|_| {
+                anyhow::anyhow!(
+                    "server didn't respond within {}s",
+                    STARTUP_TIMEOUT.as_secs()
+                )
+            })?
+            .context("couldn't initialize a connection with the server")?;
+
+        Ok(Self {
+            name: name.to_string(),
+            trusts_read_only_hints: config.trust_read_only_hints.unwrap_or(false),
+            service,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Whether the user trusts the server to mark tools as read-only correctly.
+    pub fn trusts_read_only_hints(&self) -> bool {
+        self.trusts_read_only_hints
+    }
+
+    pub async fn tools(&self) -> anyhow::Result<Vec<Tool>> {
+        self.service
+            .list_all_tools()
+            .await
+            .context("couldn't list the server's tools")
+    }
+
+    pub async fn call_tool(
+        &self,
+        tool: &str,
+        arguments: serde_json::Map<String, serde_json::Value>,
+    ) -> anyhow::Result<CallToolResult> {
+        self.service
+            .call_tool(CallToolRequestParams {
+                meta: None,
+                name: tool.to_string().into(),
+                arguments: Some(arguments),
+                task: None,
+            })
+            .await
+            .with_context(|| format!("couldn't call {tool} on the {} MCP server", self.name))
+    }
+}
+
+/// Returns what a tool call's result has to say, as text for the model.
+pub fn result_text(result: &CallToolResult) -> String {
+    let texts = result
+        .content
+        .iter()
+        .filter_map(|c| c.as_text().map(|t| t.text.as_str()))
+        .collect::<Vec<_>>();
+
+    if !texts.is_empty() && texts.len() == result.content.len() {
+        return texts.join("\n");
+    }
+
+    match &result.structured_content {
+        Some(structured) => structured.to_string(),
+        None => serde_json::to_string(&result.content).unwrap_or_default(),
+    }
+}
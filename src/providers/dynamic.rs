@@ -0,0 +1,281 @@
+use crate::tools::BoxFuture;
+use futures::StreamExt;
+use rig::agent::{Agent, AgentBuilder};
+use rig::completion::{
+    CompletionError, CompletionModel, CompletionRequest, CompletionResponse, GetTokenUsage, Usage,
+};
+use rig::streaming::{
+    RawStreamingChoice, RawStreamingToolCall, StreamedAssistantContent, StreamingCompletionResponse,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+
+/// A completion model of any provider; lets sessions work with models picked at runtime
+/// without being generic over them.
+#[derive(Clone)]
+pub struct DynModel {
+    inner: Arc<dyn ErasedModel>,
+}
+
+impl DynModel {
+    pub fn new<M>(model: M) -> Self
+    where
+        M: CompletionModel + 'static,
+    {
+        Self {
+            inner: Arc::new(model),
+        }
+    }
+
+    /// Rebuilds an agent around a [`DynModel`]. Only the model and its settings (preamble,
+    /// temperature, max tokens, additional params, and tool choice) are kept; sessions bring
+    /// their own tools and context.
+    pub fn agent<M>(agent: Agent<M>) -> Agent<DynModel>
+    where
+        M: CompletionModel + 'static,
+    {
+        let mut builder = AgentBuilder::new(Self::new((*agent.model).clone()));
+        builder = match agent.preamble {
+            Some(preamble) => builder.preamble(&preamble),
+            None => builder.without_preamble(),
+        };
+        if let Some(temperature) = agent.temperature {
+            builder = builder.temperature(temperature);
+        }
+        if let Some(max_tokens) = agent.max_tokens {
+            builder = builder.max_tokens(max_tokens);
+        }
+        if let Some(params) = agent.additional_params {
+            builder = builder.additional_params(params);
+        }
+        if let Some(tool_choice) = agent.tool_choice {
+            builder = builder.tool_choice(tool_choice);
+        }
+
+        builder.build()
+    }
+}
+
+/// The final response of a stream, of any provider.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FinalResponse {
+    pub usage: Option<Usage>,
+    /// The provider's raw final response, as JSON; some providers say why the response
+    /// finished in it.
+    pub raw_response: Value,
+}
+
+impl GetTokenUsage for FinalResponse {
+    fn token_usage(&self) -> Option<Usage> {
+        self.usage
+    }
+}
+
+/// The object safe subset of [`CompletionModel`].
+trait ErasedModel: Send + Sync {
+    fn completion(
+        &self,
+        request: CompletionRequest,
+    ) -> BoxFuture<'_, Result<CompletionResponse<Value>, CompletionError>>;
+
+    fn stream(
+        &self,
+        request: CompletionRequest,
+    ) -> BoxFuture<'_, Result<StreamingCompletionResponse<FinalResponse>, CompletionError>>;
+}
+
+impl<M> ErasedModel for M
+where
+    M: CompletionModel + 'static,
+{
+    fn completion(
+        &self,
+        request: CompletionRequest,
+    ) -> BoxFuture<'_, Result<CompletionResponse<Value>, CompletionError>> {
+        Box::pin(async move {
+            let response = CompletionModel::completion(self, request).await?;
+
+            Ok(CompletionResponse {
+                choice: response.choice,
+                usage: response.usage,
+                raw_response: serde_json::to_value(&response.raw_response).unwrap_or_default(),
+            })
+        })
+    }
+
+    fn stream(
+        &self,
+        request: CompletionRequest,
+    ) -> BoxFuture<'_, Result<StreamingCompletionResponse<FinalResponse>, CompletionError>> {
+        Box::pin(async move {
+            let response = CompletionModel::stream(self, request).await?;
+            let chunks = response.map(|chunk| chunk.map(to_raw_choice));
+
+            Ok(StreamingCompletionResponse::stream(Box::pin(chunks)))
+        })
+    }
+}
+
+/// Turns a streamed chunk back into the form providers yield, so that it can be streamed again
+/// with a provider agnostic final response.
+fn to_raw_choice<R>(content: StreamedAssistantContent<R>) -> RawStreamingChoice<FinalResponse>
+where
+    R: GetTokenUsage + Serialize,
+{
+    match content {
+        StreamedAssistantContent::Text(text) => RawStreamingChoice::Message(text.text),
+        StreamedAssistantContent::ToolCall(tool_call) => {
+            RawStreamingChoice::ToolCall(RawStreamingToolCall {
+                id: tool_call.id,
+                call_id: tool_call.call_id,
+                name: tool_call.function.name,
+                arguments: tool_call.function.arguments,
+                signature: tool_call.signature,
+                additional_params: tool_call.additional_params,
+            })
+        }
+        StreamedAssistantContent::ToolCallDelta { id, content } => {
+            RawStreamingChoice::ToolCallDelta { id, content }
+        }
+        StreamedAssistantContent::Reasoning(reasoning) => RawStreamingChoice::Reasoning {
+            id: reasoning.id,
+            reasoning: reasoning.reasoning.join(""),
+            signature: reasoning.signature,
+        },
+        StreamedAssistantContent::ReasoningDelta { id, reasoning } => {
+            RawStreamingChoice::ReasoningDelta { id, reasoning }
+        }
+        StreamedAssistantContent::Final(response) => {
+            RawStreamingChoice::FinalResponse(FinalResponse {
+                usage: response.token_usage(),
+                raw_response: serde_json::to_value(&response).unwrap_or_default(),
+            })
+        }
+    }
+}
+
+/// Responds to every request with an error; [`DynModel`]s can't be made from a client.
+struct Unavailable;
+
+impl ErasedModel for Unavailable {
+    fn completion(
+        &self,
+        _request: CompletionRequest,
+    ) -> BoxFuture<'_, Result<CompletionResponse<Value>, CompletionError>> {
+        Box::pin(async { Err(unavailable()) })
+    }
+
+    fn stream(
+        &self,
+        _request: CompletionRequest,
+    ) -> BoxFuture<'_, Result<StreamingCompletionResponse<FinalResponse>, CompletionError>> {
+        Box::pin(async { Err(unavailable()) })
+    }
+}
+
+fn unavailable() -> CompletionError {
+    CompletionError::ProviderError("no model has been set up".to_string())
+}
+
+impl CompletionModel for DynModel {
+    /// The provider's raw response, as JSON.
+    type Response = Value;
+    type StreamingResponse = FinalResponse;
+    type Client = ();
+
+    fn make(_client: &Self::Client, _model: impl Into<String>) -> Self {
+        Self {
+            inner: Arc::new(Unavailable),
+        }
+    }
+
+    async fn completion(
+        &self,
+        request: CompletionRequest,
+    ) -> Result<CompletionResponse<Self::Response>, CompletionError> {
+        self.inner.completion(request).await
+    }
+
+    async fn stream(
+        &self,
+        request: CompletionRequest,
+    ) -> Result<StreamingCompletionResponse<Self::StreamingResponse>, CompletionError> {
+        self.inner.stream(request).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::replay::ReplayModel;
+    use crate::session::RecordedResponse;
+    use rig::OneOrMany;
+    use rig::completion::{AssistantContent, Message};
+    use std::collections::VecDeque;
+
+    #[tokio::test]
+    async fn completions_are_passed_through_a_dyn_model() {
+        // GIVEN
+        let responses = VecDeque::from([RecordedResponse {
+            text: "It does nothing.".to_string(),
+            reasoning: vec![],
+            tool_calls: vec![],
+            input_tokens: 1250,
+            output_tokens: 5,
+        }]);
+        let model = DynModel::new(ReplayModel::new(responses));
+        let request = CompletionRequest {
+            preamble: None,
+            chat_history: OneOrMany::one(Message::user("what does main.rs do?")),
+            documents: vec![],
+            tools: vec![],
+            temperature: None,
+            max_tokens: None,
+            tool_choice: None,
+            additional_params: None,
+        };
+
+        // WHEN
+        let response = CompletionModel::completion(&model, request)
+            .await
+            .expect("completion should've succeeded");
+
+        // THEN
+        assert_eq!(
+            response.choice.first(),
+            AssistantContent::text("It does nothing.")
+        );
+        assert_eq!(
+            (response.usage.input_tokens, response.usage.output_tokens),
+            (1250, 5)
+        );
+    }
+
+    #[test]
+    fn final_streamed_responses_keep_the_providers_raw_response() {
+        // GIVEN
+        let response = rig::providers::ollama::StreamingCompletionResponse {
+            done_reason: Some("length".to_string()),
+            total_duration: None,
+            load_duration: None,
+            prompt_eval_count: Some(1250),
+            prompt_eval_duration: None,
+            eval_count: Some(4096),
+            eval_duration: None,
+        };
+
+        // WHEN
+        let choice = to_raw_choice(StreamedAssistantContent::Final(response));
+
+        // THEN
+        let RawStreamingChoice::FinalResponse(response) = choice else {
+            panic!("final response should've been kept");
+        };
+        assert_eq!(
+            response.usage.map(|u| (u.input_tokens, u.output_tokens)),
+            Some((1250, 4096))
+        );
+        assert_eq!(response.raw_response["done_reason"], "length");
+    }
+}
@@ -1 +1,12 @@
+//! Setting up agents for the supported providers, along with providers that need more than
+//! what [`rig`] offers out of the box.
+
+mod dynamic;
+mod setup;
+
 pub mod copilot;
+pub mod openrouter;
+pub mod replay;
+
+pub use dynamic::DynModel;
+pub use setup::{ProviderAccess, agent_for};
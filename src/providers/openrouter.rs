@@ -0,0 +1,17 @@
+use crate::domain::OpenRouterConfig;
+use anyhow::Context;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+
+pub fn get_headers(config: &OpenRouterConfig) -> anyhow::Result<HeaderMap> {
+    let mut headers = HeaderMap::new();
+    for (name, value) in &config.headers {
+        let header_name = HeaderName::from_bytes(name.as_bytes())
+            .with_context(|| format!(r#"invalid header name "{name}" in openrouter config"#))?;
+        let header_value = HeaderValue::from_str(value).with_context(|| {
+            format!(r#"invalid value for header "{name}" in openrouter config"#)
+        })?;
+        headers.insert(header_name, header_value);
+    }
+
+    Ok(headers)
+}
@@ -0,0 +1,96 @@
+use crate::session::RecordedResponse;
+use rig::OneOrMany;
+use rig::completion::{
+    CompletionError, CompletionModel, CompletionRequest, CompletionResponse, Usage,
+};
+use rig::message::AssistantContent;
+use rig::streaming::StreamingCompletionResponse;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// A model that responds with the responses recorded in a transcript, in order, regardless of
+/// the request. Responses aren't streamed.
+#[derive(Clone, Default)]
+pub struct ReplayModel {
+    responses: Arc<Mutex<VecDeque<RecordedResponse>>>,
+}
+
+impl ReplayModel {
+    pub fn new(responses: VecDeque<RecordedResponse>) -> Self {
+        Self {
+            responses: Arc::new(Mutex::new(responses)),
+        }
+    }
+
+    /// Number of recorded responses that haven't been replayed yet.
+    pub fn remaining(&self) -> usize {
+        self.responses.lock().map(|r| r.len()).unwrap_or_default()
+    }
+
+    fn next_response(&self) -> Result<RecordedResponse, CompletionError> {
+        self.responses
+            .lock()
+            .map_err(|_| CompletionError::ProviderError("replay state is poisoned".to_string()))?
+            .pop_front()
+            .ok_or_else(|| {
+                CompletionError::ProviderError(
+                    "the transcript has no more responses; the replay has diverged from the recording"
+                        .to_string(),
+                )
+            })
+    }
+}
+
+impl CompletionModel for ReplayModel {
+    type Response = ();
+    type StreamingResponse = ();
+    type Client = ();
+
+    fn make(_client: &Self::Client, _model: impl Into<String>) -> Self {
+        Self::default()
+    }
+
+    async fn completion(
+        &self,
+        _request: CompletionRequest,
+    ) -> Result<CompletionResponse<Self::Response>, CompletionError> {
+        let response = self.next_response()?;
+
+        let mut contents = response
+            .reasoning
+            .into_iter()
+            .map(AssistantContent::Reasoning)
+            .collect::<Vec<_>>();
+        if !response.text.is_empty() {
+            contents.push(AssistantContent::text(response.text));
+        }
+        contents.extend(
+            response
+                .tool_calls
+                .into_iter()
+                .map(AssistantContent::ToolCall),
+        );
+
+        let choice = OneOrMany::many(contents)
+            .unwrap_or_else(|_| OneOrMany::one(AssistantContent::text("")));
+
+        Ok(CompletionResponse {
+            choice,
+            usage: Usage {
+                input_tokens: response.input_tokens,
+                output_tokens: response.output_tokens,
+                total_tokens: response.input_tokens + response.output_tokens,
+            },
+            raw_response: (),
+        })
+    }
+
+    async fn stream(
+        &self,
+        _request: CompletionRequest,
+    ) -> Result<StreamingCompletionResponse<Self::StreamingResponse>, CompletionError> {
+        Err(CompletionError::ProviderError(
+            "replayed responses aren't streamed".to_string(),
+        ))
+    }
+}
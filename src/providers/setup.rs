@@ -0,0 +1,228 @@
+use super::{DynModel, copilot, openrouter as openrouter_provider};
+use crate::domain::{Config, HttpConfig, Provider};
+use anyhow::Context;
+use rig::agent::{Agent, AgentBuilder};
+use rig::client::{Client, CompletionClient};
+use rig::completion::CompletionModel;
+use rig::message::ToolChoice;
+use rig::providers::anthropic::client::AnthropicExt;
+use rig::providers::gemini::client::GeminiExt;
+use rig::providers::mistral::MistralExt;
+use rig::providers::openai::OpenAICompletionsExt;
+use rig::providers::openrouter::client::OpenRouterExt;
+use rig::providers::xai::client::XAiExt;
+use rig::providers::{anthropic, gemini, mistral, openai, openrouter, xai};
+use serde_json::Value;
+
+/// What's needed to reach a provider's API.
+#[derive(Debug, Clone, Default)]
+pub struct ProviderAccess {
+    pub api_key: String,
+    pub base_url: Option<String>,
+}
+
+/// A model, along with the provider specific settings agents for it need.
+struct ModelSetup {
+    model: DynModel,
+    max_tokens: Option<u64>,
+    additional_params: Option<Value>,
+    tool_choice: Option<ToolChoice>,
+}
+
+impl ModelSetup {
+    fn new<M: CompletionModel + 'static>(model: M) -> Self {
+        Self {
+            model: DynModel::new(model),
+            max_tokens: None,
+            additional_params: None,
+            tool_choice: None,
+        }
+    }
+}
+
+/// Sets up an agent for a provider's model, as per agx's config. The agent has no preamble;
+/// sessions provide their own.
+pub async fn agent_for(
+    provider: &Provider,
+    access: ProviderAccess,
+    model_name: &str,
+    config: &Config,
+) -> anyhow::Result<Agent<DynModel>> {
+    let setup = connect(provider, access, model_name, config).await?;
+
+    let mut builder = AgentBuilder::new(setup.model).without_preamble();
+    if let Some(max_tokens) = setup.max_tokens {
+        builder = builder.max_tokens(max_tokens);
+    }
+    if let Some(params) = setup.additional_params {
+        builder = builder.additional_params(params);
+    }
+    if let Some(tool_choice) = setup.tool_choice {
+        builder = builder.tool_choice(tool_choice);
+    }
+
+    Ok(builder.build())
+}
+
+/// Builds a client for the provider; supporting a new provider only requires an arm here.
+async fn connect(
+    provider: &Provider,
+    access: ProviderAccess,
+    model_name: &str,
+    config: &Config,
+) -> anyhow::Result<ModelSetup> {
+    let ProviderAccess { api_key, base_url } = access;
+    let http_config = config.http.clone().unwrap_or_default();
+    let http_client = || {
+        http_client_builder(&http_config)
+            .build()
+            .context("couldn't build http client")
+    };
+
+    let setup = match provider {
+        Provider::Anthropic => {
+            let anthropic_config = config
+                .anthropic
+                .clone()
+                .unwrap_or_default()
+                .for_model(model_name);
+
+            let mut builder = anthropic::Client::<reqwest::Client>::builder()
+                .http_client(http_client()?)
+                .api_key(api_key)
+                .anthropic_betas(&anthropic_config.betas());
+            if let Some(u) = base_url {
+                builder = builder.base_url(u);
+            }
+            let client: Client<AnthropicExt> = builder.build().context("couldn't build client")?;
+
+            ModelSetup {
+                max_tokens: Some(anthropic_config.max_tokens()),
+                additional_params: anthropic_config.additional_params(),
+                ..ModelSetup::new(client.completion_model(model_name))
+            }
+        }
+        Provider::Gemini => {
+            let mut builder = gemini::Client::<reqwest::Client>::builder()
+                .http_client(http_client()?)
+                .api_key(api_key);
+            if let Some(u) = base_url {
+                builder = builder.base_url(u);
+            }
+            let client: Client<GeminiExt> = builder.build().context("couldn't build client")?;
+            let gemini_config = config.gemini.clone().unwrap_or_default();
+
+            ModelSetup {
+                additional_params: gemini_config.additional_params(),
+                tool_choice: gemini_config.tool_choice(),
+                ..ModelSetup::new(client.completion_model(model_name))
+            }
+        }
+        Provider::GitHubCopilot => {
+            let http_client = http_client_builder(&http_config)
+                .default_headers(copilot::get_headers())
+                .build()
+                .context("couldn't build http client for copilot API calls")?;
+
+            let copilot_auth = copilot::get_auth_token(&http_client, &api_key)
+                .await
+                .context("couldn't get a short lived GitHub Copilot token")?;
+
+            let client: Client<OpenAICompletionsExt> = openai::Client::<reqwest::Client>::builder()
+                .base_url(&copilot_auth.endpoints.api)
+                .api_key(&copilot_auth.token)
+                .http_client(http_client)
+                .build()
+                .context("couldn't build client")?
+                .completions_api(); // This is to maintain consistency with the other clients
+
+            ModelSetup::new(client.completion_model(model_name))
+        }
+        Provider::Mistral => {
+            let mut builder = mistral::Client::<reqwest::Client>::builder()
+                .http_client(http_client()?)
+                .api_key(api_key);
+            if let Some(u) = base_url {
+                builder = builder.base_url(u);
+            }
+            let client: Client<MistralExt> = builder.build().context("couldn't build client")?;
+
+            ModelSetup::new(client.completion_model(model_name))
+        }
+        Provider::OpenAI => {
+            let mut builder = openai::Client::<reqwest::Client>::builder()
+                .http_client(http_client()?)
+                .api_key(api_key);
+            if let Some(u) = base_url {
+                builder = builder.base_url(u);
+            }
+            let client: Client<OpenAICompletionsExt> = builder
+                .build()
+                .context("couldn't build client")?
+                .completions_api();
+
+            ModelSetup::new(client.completion_model(model_name))
+        }
+        Provider::OpenAICompatible => {
+            let base_url = base_url.context(
+                r#"a base URL needs to be set for the "openai-compatible" provider (via --base-url, or BASE_URL)"#,
+            )?;
+            let client: Client<OpenAICompletionsExt> = openai::Client::<reqwest::Client>::builder()
+                .http_client(http_client()?)
+                .api_key(api_key)
+                .base_url(base_url)
+                .build()
+                .context("couldn't build client")?
+                .completions_api();
+
+            ModelSetup::new(client.completion_model(model_name))
+        }
+        Provider::Openrouter => {
+            let openrouter_config = config.openrouter.clone().unwrap_or_default();
+
+            let mut builder = openrouter::Client::<reqwest::Client>::builder()
+                .http_client(http_client()?)
+                .api_key(api_key)
+                .http_headers(openrouter_provider::get_headers(&openrouter_config)?);
+            if let Some(u) = base_url {
+                builder = builder.base_url(u);
+            }
+            let client: Client<OpenRouterExt> = builder.build().context("couldn't build client")?;
+
+            ModelSetup {
+                additional_params: openrouter_config.additional_params(),
+                ..ModelSetup::new(client.completion_model(model_name))
+            }
+        }
+        Provider::XAi => {
+            let mut builder = xai::Client::<reqwest::Client>::builder()
+                .http_client(http_client()?)
+                .api_key(api_key);
+            if let Some(u) = base_url {
+                builder = builder.base_url(u);
+            }
+            let client: Client<XAiExt> = builder.build().context("couldn't build client")?;
+
+            ModelSetup::new(client.completion_model(model_name))
+        }
+    };
+
+    Ok(setup)
+}
+
+/// Applies the configured timeouts (and connection reuse) to requests made to providers, so
+/// that unreachable endpoints and stalled connections fail instead of hanging.
+fn http_client_builder(config: &HttpConfig) -> reqwest::ClientBuilder {
+    let mut builder = reqwest::Client::builder().connect_timeout(config.connect_timeout());
+    if let Some(timeout) = config.request_timeout() {
+        builder = builder.timeout(timeout);
+    }
+    if let Some(timeout) = config.read_timeout() {
+        builder = builder.read_timeout(timeout);
+    }
+    match config.pool_idle_timeout() {
+        Some(timeout) if timeout.is_zero() => builder.pool_max_idle_per_host(0),
+        Some(timeout) => builder.pool_idle_timeout(timeout),
+        None => builder,
+    }
+}
@@ -0,0 +1,278 @@
+//! Usage reports: token usage and cost, per project and model, aggregated from the transcripts
+//! sessions leave behind in agx's log directory.
+
+use crate::domain::{ModelConfig, ModelPricing};
+use crate::session::TranscriptEvent;
+use anyhow::Context;
+use chrono::NaiveDateTime;
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::path::Path;
+
+/// Sessions are recorded in directories named after the (local) time they were started at.
+const SESSION_DIR_FORMAT: &str = "%Y-%m-%d-%H-%M-%S";
+const TRANSCRIPT_FILE: &str = "transcript.jsonl";
+const UNKNOWN_MODEL: &str = "(unknown)";
+
+#[derive(Debug, Default)]
+pub struct UsageReport {
+    /// Keyed by project and model.
+    usage: BTreeMap<(String, String), Usage>,
+    pricing: BTreeMap<String, ModelPricing>,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Usage {
+    sessions: usize,
+    input_tokens: u64,
+    output_tokens: u64,
+}
+
+impl UsageReport {
+    /// Costs are computed as per the pricing configured for models; usage of other models is
+    /// reported without a cost.
+    pub fn new(models: &BTreeMap<String, ModelConfig>) -> Self {
+        Self {
+            usage: BTreeMap::new(),
+            pricing: models
+                .iter()
+                .filter_map(|(name, model)| model.pricing.clone().map(|p| (name.clone(), p)))
+                .collect(),
+        }
+    }
+
+    /// Adds up the usage of sessions started after `since`, across all the projects in
+    /// `projects_dir` (agx's log directory for projects).
+    pub async fn collect(
+        &mut self,
+        projects_dir: &Path,
+        since: NaiveDateTime,
+    ) -> anyhow::Result<()> {
+        if !projects_dir.exists() {
+            return Ok(());
+        }
+
+        let mut projects = tokio::fs::read_dir(projects_dir)
+            .await
+            .with_context(|| format!("couldn't read {}", projects_dir.to_string_lossy()))?;
+        while let Some(project) = projects.next_entry().await? {
+            let Ok(mut sessions) = tokio::fs::read_dir(project.path().join("chats")).await else {
+                continue;
+            };
+            let project = project.file_name().to_string_lossy().to_string();
+
+            while let Some(session) = sessions.next_entry().await? {
+                let started_at = NaiveDateTime::parse_from_str(
+                    &session.file_name().to_string_lossy(),
+                    SESSION_DIR_FORMAT,
+                );
+                if !started_at.is_ok_and(|t| t >= since) {
+                    continue;
+                }
+
+                if let Ok(transcript) =
+                    tokio::fs::read_to_string(session.path().join(TRANSCRIPT_FILE)).await
+                {
+                    self.record_session(&project, &transcript);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Adds up the usage recorded in a session's transcript. Lines that can't be parsed (eg. the
+    /// last one, if agx was killed while writing it) are skipped.
+    pub fn record_session(&mut self, project: &str, transcript: &str) {
+        let mut model = None;
+        let mut usage = Usage {
+            sessions: 1,
+            ..Usage::default()
+        };
+
+        for event in transcript
+            .lines()
+            .filter_map(|l| serde_json::from_str::<TranscriptEvent>(l).ok())
+        {
+            match event {
+                TranscriptEvent::Started { model: m, .. } => model = Some(m),
+                TranscriptEvent::Response(response) => {
+                    usage.input_tokens += response.input_tokens;
+                    usage.output_tokens += response.output_tokens;
+                }
+                TranscriptEvent::Prompt { .. }
+                | TranscriptEvent::ToolResult { .. }
+                | TranscriptEvent::Bookmark(_) => {}
+            }
+        }
+
+        let key = (
+            project.to_string(),
+            model.unwrap_or_else(|| UNKNOWN_MODEL.to_string()),
+        );
+        let total = self.usage.entry(key).or_default();
+        total.sessions += usage.sessions;
+        total.input_tokens += usage.input_tokens;
+        total.output_tokens += usage.output_tokens;
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut lines =
+            vec!["project,model,sessions,input_tokens,output_tokens,cost_usd".to_string()];
+        for ((project, model), usage) in &self.usage {
+            lines.push(format!(
+                "{},{},{},{},{},{}",
+                csv_field(project),
+                csv_field(model),
+                usage.sessions,
+                usage.input_tokens,
+                usage.output_tokens,
+                self.cost(model, usage)
+                    .map(|c| format!("{c:.4}"))
+                    .unwrap_or_default(),
+            ));
+        }
+
+        format!("{}\n", lines.join("\n"))
+    }
+
+    fn cost(&self, model: &str, usage: &Usage) -> Option<f64> {
+        self.pricing
+            .get(model)
+            .map(|p| p.cost(usage.input_tokens, usage.output_tokens))
+    }
+}
+
+impl Display for UsageReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.usage.is_empty() {
+            return writeln!(f, "no sessions were recorded in this period");
+        }
+
+        let project_width = self
+            .usage
+            .keys()
+            .map(|(p, _)| p.len())
+            .max()
+            .unwrap_or_default()
+            .max("project".len());
+        let model_width = self
+            .usage
+            .keys()
+            .map(|(_, m)| m.len())
+            .max()
+            .unwrap_or_default()
+            .max("model".len());
+
+        writeln!(
+            f,
+            "{:<project_width$}  {:<model_width$}  {:>8}  {:>13}  {:>13}  {:>10}",
+            "project", "model", "sessions", "input tokens", "output tokens", "cost (USD)"
+        )?;
+
+        let mut total = Usage::default();
+        let mut total_cost = 0.0;
+        let mut unpriced = vec![];
+        for ((project, model), usage) in &self.usage {
+            let cost = match self.cost(model, usage) {
+                Some(cost) => {
+                    total_cost += cost;
+                    format!("{cost:.2}")
+                }
+                None => {
+                    unpriced.push(model.as_str());
+                    "-".to_string()
+                }
+            };
+            writeln!(
+                f,
+                "{:<project_width$}  {:<model_width$}  {:>8}  {:>13}  {:>13}  {:>10}",
+                project, model, usage.sessions, usage.input_tokens, usage.output_tokens, cost
+            )?;
+
+            total.sessions += usage.sessions;
+            total.input_tokens += usage.input_tokens;
+            total.output_tokens += usage.output_tokens;
+        }
+
+        writeln!(
+            f,
+            "{:<project_width$}  {:<model_width$}  {:>8}  {:>13}  {:>13}  {:>10}",
+            "total",
+            "",
+            total.sessions,
+            total.input_tokens,
+            total.output_tokens,
+            format!("{total_cost:.2}")
+        )?;
+
+        if !unpriced.is_empty() {
+            unpriced.sort();
+            unpriced.dedup();
+            writeln!(
+                f,
+                "\ncost isn't included for models without pricing in config: {}",
+                unpriced.join(", ")
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::assert_snapshot;
+
+    const SONNET_SESSION: &str = r#"{"event":"started","provider":"anthropic","model":"claude-sonnet-4-5","text_tool_calls":false}
+{"event":"prompt","text":"add a test"}
+{"event":"response","text":"","tool_calls":[],"input_tokens":12000,"output_tokens":800}
+{"event":"response","text":"done","input_tokens":14000,"output_tokens":200}
+"#;
+    const LOCAL_SESSION: &str = r#"{"event":"started","provider":"ollama","model":"qwen3:8b","text_tool_calls":true}
+{"event":"response","text":"hello","input_tokens":3000,"output_tokens":50}
+{"event":"respo"#;
+
+    #[test]
+    fn usage_is_aggregated_per_project_and_model() -> anyhow::Result<()> {
+        // GIVEN
+        let models: BTreeMap<String, ModelConfig> = serde_json::from_str(
+            r#"{"claude-sonnet-4-5": {"pricing": {"input_per_million_tokens": 3.0, "output_per_million_tokens": 15.0}}}"#,
+        )?;
+        let mut report = UsageReport::new(&models);
+
+        // WHEN
+        report.record_session("Users-user-projects-agx", SONNET_SESSION);
+        report.record_session("Users-user-projects-agx", SONNET_SESSION);
+        report.record_session("Users-user-projects-agx", LOCAL_SESSION);
+        report.record_session("Users-user-projects-web", SONNET_SESSION);
+
+        // THEN
+        assert_snapshot!(report.to_string(), @r"
+        project                  model              sessions   input tokens  output tokens  cost (USD)
+        Users-user-projects-agx  claude-sonnet-4-5         2          52000           2000        0.19
+        Users-user-projects-agx  qwen3:8b                  1           3000             50           -
+        Users-user-projects-web  claude-sonnet-4-5         1          26000           1000        0.09
+        total                                              4          81000           3050        0.28
+
+        cost isn't included for models without pricing in config: qwen3:8b
+        ");
+        assert_snapshot!(report.to_csv(), @r"
+        project,model,sessions,input_tokens,output_tokens,cost_usd
+        Users-user-projects-agx,claude-sonnet-4-5,2,52000,2000,0.1860
+        Users-user-projects-agx,qwen3:8b,1,3000,50,
+        Users-user-projects-web,claude-sonnet-4-5,1,26000,1000,0.0930
+        ");
+
+        Ok(())
+    }
+}
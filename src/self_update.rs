@@ -0,0 +1,368 @@
+use anyhow::Context;
+use colored::Colorize;
+use reqwest::header::{ACCEPT, HeaderMap, HeaderValue, USER_AGENT};
+use serde::Deserialize;
+use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
+
+const LATEST_RELEASE_URL: &str = "https://api.github.com/repos/dhth/agx/releases/latest";
+const BINARY_NAME: &str = "agx";
+
+#[derive(Debug, Deserialize)]
+struct Release {
+    tag_name: String,
+    assets: Vec<Asset>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+}
+
+/// Replaces the running executable with the binary for this platform from agx's latest GitHub
+/// release, if it's newer; the archive's checksum (published alongside it) is verified before
+/// anything is replaced. Since the checksum comes from the same release, it only guards against
+/// corrupted downloads; it doesn't prove that the release wasn't tampered with. With `check`,
+/// only reports whether an update is available.
+pub async fn run_self_update(check: bool, enabled: bool) -> anyhow::Result<()> {
+    if !enabled {
+        anyhow::bail!(
+            "self-update is disabled in config (self_update = false); update agx the way it was installed instead"
+        );
+    }
+
+    let client = client()?;
+    let release = client
+        .get(LATEST_RELEASE_URL)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .context("couldn't fetch the latest release")?
+        .json::<Release>()
+        .await
+        .context("couldn't parse the latest release")?;
+
+    let current = env!("CARGO_PKG_VERSION");
+    let latest = release.tag_name.trim_start_matches('v');
+    if compare_versions(latest, current) != Ordering::Greater {
+        println!("{}", format!("agx is up to date ({current})").green());
+        return Ok(());
+    }
+
+    if check {
+        println!(
+            "{}",
+            format!("agx {latest} is available (current: {current}); run `agx self-update` to install it").blue()
+        );
+        return Ok(());
+    }
+
+    let target = target_triple()?;
+    let archive_name = format!("{BINARY_NAME}-{target}.tar.xz");
+    let find_asset = |name: &str| {
+        release
+            .assets
+            .iter()
+            .find(|a| a.name == name)
+            .with_context(|| format!("release {} doesn't have {name}", release.tag_name))
+    };
+    let archive = find_asset(&archive_name)?;
+    let checksum = find_asset(&format!("{archive_name}.sha256"))?;
+
+    println!("{}", format!("downloading agx {latest} ({target})").blue());
+    let archive_bytes = download(&client, &archive.browser_download_url).await?;
+    let checksum =
+        String::from_utf8_lossy(&download(&client, &checksum.browser_download_url).await?)
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+    let actual = sha256(&archive_bytes);
+    if actual != checksum {
+        anyhow::bail!(
+            "checksum of {archive_name} doesn't match the published one (expected {checksum}, got {actual}); nothing was replaced"
+        );
+    }
+
+    // a new directory only this user can write to (removed once dropped), so that the binary
+    // installed can only be the one extracted from the verified archive
+    let dir = tempfile::Builder::new()
+        .prefix("agx-update-")
+        .tempdir()
+        .context("couldn't create a temporary directory for the update")?;
+    let binary = extract_binary(dir.path(), &archive_name, &archive_bytes).await?;
+    let installed_at = install(&binary).await?;
+
+    println!(
+        "{}",
+        format!(
+            "updated agx from {current} to {latest} ({})",
+            installed_at.to_string_lossy()
+        )
+        .green()
+    );
+
+    Ok(())
+}
+
+fn client() -> anyhow::Result<reqwest::Client> {
+    let mut headers = HeaderMap::new();
+    headers.insert(USER_AGENT, HeaderValue::from_static("agx"));
+    headers.insert(
+        ACCEPT,
+        HeaderValue::from_static("application/vnd.github+json"),
+    );
+
+    reqwest::Client::builder()
+        .default_headers(headers)
+        .build()
+        .context("couldn't build HTTP client")
+}
+
+async fn download(client: &reqwest::Client, url: &str) -> anyhow::Result<Vec<u8>> {
+    let bytes = client
+        .get(url)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .with_context(|| format!("couldn't download {url}"))?
+        .bytes()
+        .await
+        .with_context(|| format!("couldn't download {url}"))?;
+
+    Ok(bytes.to_vec())
+}
+
+/// Extracts the release archive into a directory of its own within `dir`, and returns the path
+/// of the binary in it.
+async fn extract_binary(dir: &Path, archive_name: &str, archive: &[u8]) -> anyhow::Result<PathBuf> {
+    let archive_path = dir.join(archive_name);
+    tokio::fs::write(&archive_path, archive)
+        .await
+        .with_context(|| format!("couldn't write {}", archive_path.to_string_lossy()))?;
+    let extracted_dir = dir.join("extracted");
+    tokio::fs::create_dir(&extracted_dir)
+        .await
+        .with_context(|| format!("couldn't create {}", extracted_dir.to_string_lossy()))?;
+
+    let output = tokio::process::Command::new("tar")
+        .arg("-xJf")
+        .arg(&archive_path)
+        .arg("-C")
+        .arg(&extracted_dir)
+        .output()
+        .await
+        .context("couldn't run tar to extract the release archive")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "couldn't extract {archive_name}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    find_binary(&extracted_dir)
+        .await?
+        .with_context(|| format!("{archive_name} doesn't contain the {BINARY_NAME} binary"))
+}
+
+/// Moves `binary` in place of the running executable; the move is a rename within the
+/// executable's directory, so that the executable is never left half-written.
+async fn install(binary: &Path) -> anyhow::Result<PathBuf> {
+    let current_exe = std::env::current_exe()
+        .and_then(|p| p.canonicalize())
+        .context("couldn't determine the path of the running executable")?;
+    let staged = current_exe.with_extension("new");
+    tokio::fs::copy(binary, &staged)
+        .await
+        .with_context(|| format!("couldn't write {}", staged.to_string_lossy()))?;
+    make_executable(&staged).await?;
+    tokio::fs::rename(&staged, &current_exe)
+        .await
+        .with_context(|| format!("couldn't replace {}", current_exe.to_string_lossy()))?;
+
+    Ok(current_exe)
+}
+
+/// Archives contain the binary either at the top level, or in a directory named after the
+/// archive.
+async fn find_binary(dir: &Path) -> anyhow::Result<Option<PathBuf>> {
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let mut entries = tokio::fs::read_dir(&dir)
+            .await
+            .with_context(|| format!("couldn't read {}", dir.to_string_lossy()))?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            let file_type = entry.file_type().await?;
+            if file_type.is_dir() {
+                dirs.push(path);
+            } else if file_type.is_file() && entry.file_name() == BINARY_NAME {
+                return Ok(Some(path));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(unix)]
+async fn make_executable(path: &Path) -> anyhow::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    tokio::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))
+        .await
+        .with_context(|| format!("couldn't make {} executable", path.to_string_lossy()))
+}
+
+#[cfg(not(unix))]
+async fn make_executable(_path: &Path) -> anyhow::Result<()> {
+    Ok(())
+}
+
+/// Release archives are built for these platforms.
+fn target_triple() -> anyhow::Result<&'static str> {
+    let target = match (std::env::consts::ARCH, std::env::consts::OS) {
+        ("x86_64", "linux") => "x86_64-unknown-linux-gnu",
+        ("aarch64", "linux") => "aarch64-unknown-linux-gnu",
+        ("x86_64", "macos") => "x86_64-apple-darwin",
+        ("aarch64", "macos") => "aarch64-apple-darwin",
+        (arch, os) => anyhow::bail!(
+            "self-update isn't supported on {os} ({arch}); download agx from https://github.com/dhth/agx/releases instead"
+        ),
+    };
+
+    Ok(target)
+}
+
+fn sha256(bytes: &[u8]) -> String {
+    ring::digest::digest(&ring::digest::SHA256, bytes)
+        .as_ref()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// Compares versions like "1.2.0" and "1.2.0-alpha.1"; pre-releases come before the release
+/// they precede.
+fn compare_versions(a: &str, b: &str) -> Ordering {
+    let parse = |version: &str| {
+        let (core, pre) = match version.split_once('-') {
+            Some((core, pre)) => (core, Some(pre.to_string())),
+            None => (version, None),
+        };
+        let core = core
+            .split('.')
+            .map(|n| n.parse::<u64>().unwrap_or_default())
+            .collect::<Vec<_>>();
+        (core, pre)
+    };
+    let ((a_core, a_pre), (b_core, b_pre)) = (parse(a), parse(b));
+
+    a_core.cmp(&b_core).then_with(|| match (a_pre, b_pre) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+        (Some(a), Some(b)) => compare_pre_releases(&a, &b),
+    })
+}
+
+/// Numeric parts are compared as numbers, so that "alpha.10" comes after "alpha.9".
+fn compare_pre_releases(a: &str, b: &str) -> Ordering {
+    for (a, b) in a.split('.').zip(b.split('.')) {
+        let ordering = match (a.parse::<u64>(), b.parse::<u64>()) {
+            (Ok(a), Ok(b)) => a.cmp(&b),
+            _ => a.cmp(b),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+
+    a.split('.').count().cmp(&b.split('.').count())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions_are_compared_by_their_numeric_parts() {
+        // GIVEN
+        let cases = [
+            ("1.10.0", "1.9.0", Ordering::Greater),
+            ("0.2.0", "0.10.0", Ordering::Less),
+            ("1.2.3", "1.2.3", Ordering::Equal),
+            ("1.0.0", "1.0.0-rc.1", Ordering::Greater),
+            ("0.1.0-alpha.1", "0.1.0", Ordering::Less),
+            ("0.1.0-alpha.10", "0.1.0-alpha.9", Ordering::Greater),
+            ("0.1.0-beta.1", "0.1.0-alpha.2", Ordering::Greater),
+            ("0.1.0-alpha", "0.1.0-alpha.1", Ordering::Less),
+        ];
+
+        // WHEN
+        // THEN
+        for (a, b, expected) in cases {
+            assert_eq!(compare_versions(a, b), expected, "comparing {a} with {b}");
+            assert_eq!(
+                compare_versions(b, a),
+                expected.reverse(),
+                "comparing {b} with {a}"
+            );
+        }
+    }
+
+    #[test]
+    fn numeric_pre_release_parts_are_compared_as_numbers() {
+        // GIVEN
+        let cases = [
+            ("alpha.10", "alpha.9", Ordering::Greater),
+            ("rc.1", "rc.1", Ordering::Equal),
+            ("alpha.2", "beta.1", Ordering::Less),
+        ];
+
+        // WHEN
+        // THEN
+        for (a, b, expected) in cases {
+            assert_eq!(
+                compare_pre_releases(a, b),
+                expected,
+                "comparing {a} with {b}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn only_the_binary_in_the_release_archive_is_picked() -> anyhow::Result<()> {
+        // GIVEN
+        let release_dir = tempfile::tempdir()?;
+        let packaged = release_dir.path().join("agx-x86_64-unknown-linux-gnu");
+        tokio::fs::create_dir(&packaged).await?;
+        tokio::fs::write(packaged.join("agx"), "released binary").await?;
+        tokio::fs::write(packaged.join("README.md"), "# agx").await?;
+        let archive_name = "agx-x86_64-unknown-linux-gnu.tar.xz";
+        let status = tokio::process::Command::new("tar")
+            .args(["-cJf", archive_name, "agx-x86_64-unknown-linux-gnu"])
+            .current_dir(release_dir.path())
+            .status()
+            .await?;
+        assert!(status.success(), "archive should've been created");
+        let archive = tokio::fs::read(release_dir.path().join(archive_name)).await?;
+
+        let temp_dir = tempfile::tempdir()?;
+        let dir = temp_dir.path();
+        tokio::fs::write(dir.join("agx"), "planted binary").await?;
+
+        // WHEN
+        let binary = extract_binary(dir, archive_name, &archive).await?;
+
+        // THEN
+        assert_eq!(
+            binary,
+            dir.join("extracted/agx-x86_64-unknown-linux-gnu/agx")
+        );
+        assert_eq!(tokio::fs::read_to_string(&binary).await?, "released binary");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,231 @@
+use crate::domain::ApprovalDecision;
+use anyhow::Context;
+use chrono::Utc;
+use serde::Serialize;
+use serde_json::Value;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+use tracing::warn;
+
+/// Records everything that happens in an unattended session as JSON lines, so that it can be
+/// reviewed afterwards.
+pub struct AuditLog {
+    file: File,
+    path: PathBuf,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum AuditEvent<'a> {
+    Started {
+        task: &'a str,
+        model: &'a str,
+        guardrails: Value,
+    },
+    Response {
+        text: &'a str,
+        total_input_tokens: u64,
+        total_output_tokens: u64,
+    },
+    ToolCall {
+        id: &'a str,
+        name: &'a str,
+        arguments: &'a Value,
+    },
+    ToolResult {
+        id: &'a str,
+        result: &'a str,
+    },
+    GuardrailTripped {
+        reason: String,
+    },
+    Finished {
+        success: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+}
+
+#[derive(Serialize)]
+struct AuditEntry<'a> {
+    timestamp: String,
+    #[serde(flatten)]
+    event: AuditEvent<'a>,
+}
+
+impl AuditLog {
+    pub fn create(path: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let path = path.into();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = File::create(&path)?;
+
+        Ok(Self { file, path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn record(&self, event: AuditEvent<'_>) {
+        let entry = AuditEntry {
+            timestamp: Utc::now().to_rfc3339(),
+            event,
+        };
+
+        let result = serde_json::to_string(&entry)
+            .map_err(std::io::Error::from)
+            .and_then(|line| writeln!(&self.file, "{line}"));
+
+        if let Err(e) = result {
+            warn!(error = %e, "couldn't write to audit log");
+        }
+    }
+}
+
+/// Name of the tool audit log's file, within the project's log directory.
+pub const TOOL_AUDIT_LOG_FILE: &str = "tool-audit.jsonl";
+
+/// Records every mutating tool call (file changes, commands, and remote actions) that's executed,
+/// in any kind of session, along with hashes of the files it changed. There's a single log per
+/// project, which is only ever appended to.
+pub struct ToolAuditLog {
+    path: PathBuf,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ToolExecution<'a> {
+    /// The session's chat directory name (the time it started).
+    pub session: &'a str,
+    pub tool: &'a str,
+    pub id: &'a str,
+    pub arguments: &'a Value,
+    pub approval: ApprovalDecision,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<FileHashes>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    pub succeeded: bool,
+    /// Code of the error the tool call failed with, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<&'a str>,
+}
+
+/// SHA-256 hashes of a file's contents before and after a tool call; a hash is missing if the
+/// file didn't exist.
+#[derive(Debug, Clone, Serialize)]
+pub struct FileHashes {
+    pub path: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ToolAuditEntry<'a> {
+    timestamp: String,
+    #[serde(flatten)]
+    execution: &'a ToolExecution<'a>,
+}
+
+impl ToolAuditLog {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub async fn record(&self, execution: &ToolExecution<'_>) -> anyhow::Result<()> {
+        let entry = ToolAuditEntry {
+            timestamp: Utc::now().to_rfc3339(),
+            execution,
+        };
+        let mut line = serde_json::to_string(&entry).context("couldn't serialize entry")?;
+        line.push('\n');
+
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .context("couldn't create directory")?;
+        }
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .context("couldn't open file")?;
+        file.write_all(line.as_bytes())
+            .await
+            .context("couldn't write to file")?;
+
+        Ok(())
+    }
+}
+
+/// Returns the SHA-256 hash of a file's contents (as "sha256:<hex>"), or `None` if it can't be
+/// read (eg. because it doesn't exist).
+pub async fn hash_file(path: &Path) -> Option<String> {
+    let contents = tokio::fs::read(path).await.ok()?;
+    let digest = ring::digest::digest(&ring::digest::SHA256, &contents);
+    let hex = digest
+        .as_ref()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<String>();
+
+    Some(format!("sha256:{hex}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn tool_executions_are_appended_to_the_log() -> anyhow::Result<()> {
+        // GIVEN
+        let temp_dir = tempfile::tempdir()?;
+        let dir = temp_dir.path();
+        let file_path = dir.join("notes.txt");
+        tokio::fs::write(&file_path, "hello\n").await?;
+        let log = ToolAuditLog::new(dir.join("tool-audit.jsonl"));
+        let arguments = serde_json::json!({"path": "notes.txt"});
+        let execution = ToolExecution {
+            session: "2025-01-01-10-00-00",
+            tool: "create_file",
+            id: "call_1",
+            arguments: &arguments,
+            approval: ApprovalDecision::Approved,
+            file: Some(FileHashes {
+                path: "notes.txt".to_string(),
+                before: None,
+                after: hash_file(&file_path).await,
+            }),
+            exit_code: None,
+            succeeded: true,
+            error: None,
+        };
+
+        // WHEN
+        log.record(&execution).await?;
+        log.record(&execution).await?;
+
+        // THEN
+        let contents = tokio::fs::read_to_string(dir.join("tool-audit.jsonl")).await?;
+        let entries = contents
+            .lines()
+            .map(serde_json::from_str::<Value>)
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            entries[0]["file"],
+            serde_json::json!({
+                "path": "notes.txt",
+                "before": null,
+                "after": "sha256:5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03",
+            })
+        );
+        assert_eq!(entries[0]["approval"], "approved");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,383 @@
+use super::Session;
+use super::audit::{TOOL_AUDIT_LOG_FILE, ToolAuditLog};
+use super::compose::EditorRequest;
+use super::confirmations::{
+    ApprovalRequest, ApprovalResponse, ConfirmationProvider, TerminalConfirmations,
+};
+use super::corrections::{CORRECTIONS_LOG_FILE, CorrectionLog};
+use super::hitl::Approvals;
+use super::overrides::OutputOverrides;
+use super::queue::PromptQueue;
+use super::stats::SessionStats;
+use super::title::TerminalTitle;
+use super::webhooks::Webhooks;
+use crate::domain::{
+    ApprovalMode, Config, DebugEventSender, OutputSink, Provider, TerminalSink, ToolCallingMode,
+};
+use crate::index::{KnowledgeStore, SemanticIndex};
+use crate::providers::{DynModel, ProviderAccess};
+use crate::tools::{
+    ArtifactStore, CommandSettings, ConfirmationPolicy, SemanticSearchTool, ToolRegistry, Workspace,
+};
+use chrono::Local;
+use rig::agent::Agent;
+use rig::completion::CompletionModel;
+use rig::tool::Tool;
+use rustyline::DefaultEditor;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Tools available in read-only sessions.
+const READ_ONLY_TOOLS: [&str; 6] = [
+    "read_file",
+    "read_dir",
+    "read_artifact",
+    "semantic_search",
+    "search_knowledge",
+    "issue_view",
+];
+
+/// Builds a [`Session`]. Everything apart from the model and the project's directories is
+/// optional; by default, all builtin tools are available, tool calls are approved on the
+/// terminal, and output is printed to stdout.
+pub struct SessionBuilder {
+    agent: Agent<DynModel>,
+    provider: Provider,
+    provider_access: Option<ProviderAccess>,
+    model_name: String,
+    project_dir: PathBuf,
+    project_log_dir: PathBuf,
+    config: Config,
+    user_config_dir: Option<PathBuf>,
+    approval_mode: ApprovalMode,
+    project_context: Option<String>,
+    semantic_index: Option<Arc<SemanticIndex>>,
+    debug_tx: Option<DebugEventSender>,
+    builtin_tools: Option<Vec<String>>,
+    read_only: bool,
+    custom_tools: ToolRegistry,
+    confirmations: Option<Arc<dyn ConfirmationProvider>>,
+    output: Arc<dyn OutputSink>,
+}
+
+impl SessionBuilder {
+    /// Only the agent's model and its settings are used (see [`DynModel::agent`]); sessions
+    /// bring their own tools.
+    pub fn new<M>(
+        agent: Agent<M>,
+        provider: Provider,
+        model_name: impl Into<String>,
+        project_dir: PathBuf,
+        project_log_dir: PathBuf,
+    ) -> Self
+    where
+        M: CompletionModel + 'static,
+    {
+        Self {
+            agent: DynModel::agent(agent),
+            provider,
+            provider_access: None,
+            model_name: model_name.into(),
+            project_dir,
+            project_log_dir,
+            config: Config::default(),
+            user_config_dir: None,
+            approval_mode: ApprovalMode::default(),
+            project_context: None,
+            semantic_index: None,
+            debug_tx: None,
+            builtin_tools: None,
+            read_only: false,
+            custom_tools: ToolRegistry::default(),
+            confirmations: None,
+            output: Arc::new(TerminalSink),
+        }
+    }
+
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// What's needed to reach the provider's API; lets the model be compared with other models
+    /// of the provider, via `/compare`.
+    pub fn provider_access(mut self, access: ProviderAccess) -> Self {
+        self.provider_access = Some(access);
+        self
+    }
+
+    /// Directory user level config was read from (see [`get_user_config`]); it's read again,
+    /// along with project config, when config is reloaded via `/reload`.
+    ///
+    /// [`get_user_config`]: crate::config::get_user_config
+    pub fn user_config_dir(mut self, dir: Option<PathBuf>) -> Self {
+        self.user_config_dir = dir;
+        self
+    }
+
+    /// Mode tool calls are approved in, until a template or `/auto` changes it.
+    pub fn approval_mode(mut self, approval_mode: ApprovalMode) -> Self {
+        self.approval_mode = approval_mode;
+        self
+    }
+
+    /// Contents of the project's AGENTS.md (or equivalent), added to the system prompt.
+    pub fn project_context(mut self, project_context: Option<String>) -> Self {
+        self.project_context = project_context;
+        self
+    }
+
+    /// Makes semantic search available to the model.
+    pub fn semantic_index(mut self, semantic_index: Option<Arc<SemanticIndex>>) -> Self {
+        self.semantic_index = semantic_index;
+        self
+    }
+
+    pub fn debug_tx(mut self, debug_tx: Option<DebugEventSender>) -> Self {
+        self.debug_tx = debug_tx;
+        self
+    }
+
+    /// Limits the builtin tools registered to the ones provided; building the session fails if
+    /// any of them isn't a builtin tool.
+    pub fn builtin_tools<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.builtin_tools = Some(names.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Limits the model to tools that only read (eg. because another session is running in the
+    /// project); changes aren't tracked, and input history isn't saved either, since those are
+    /// shared with other sessions in the project.
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Registers a rig tool alongside the builtin ones; calls to it are confirmed as per
+    /// `policy`. Replaces a builtin tool with the same name.
+    pub fn tool<T>(mut self, tool: T, policy: ConfirmationPolicy) -> Self
+    where
+        T: Tool + 'static,
+    {
+        self.custom_tools.register_custom(tool, policy);
+        self
+    }
+
+    /// Decides on tool calls that need approval via `confirmations`, instead of asking on the
+    /// terminal.
+    pub fn confirmations(mut self, confirmations: Arc<dyn ConfirmationProvider>) -> Self {
+        self.confirmations = Some(confirmations);
+        self
+    }
+
+    /// Decides on tool calls that need approval via a callback, instead of asking on the
+    /// terminal.
+    pub fn approval_callback<F>(self, callback: F) -> Self
+    where
+        F: Fn(&ApprovalRequest) -> ApprovalResponse + Send + Sync + 'static,
+    {
+        self.confirmations(Arc::new(callback))
+    }
+
+    /// Sends the session's output to a sink other than stdout.
+    pub fn output(mut self, output: Arc<dyn OutputSink>) -> Self {
+        self.output = output;
+        self
+    }
+
+    pub fn build(self) -> anyhow::Result<Session> {
+        let chats_dir = self
+            .project_log_dir
+            .join("chats")
+            .join(Local::now().format("%Y-%m-%d-%H-%M-%S").to_string());
+
+        let config = self.config;
+        let text_tool_calls = config.tool_calling_mode(&self.model_name) == ToolCallingMode::Text;
+
+        let workspace = Arc::new(Workspace::new(self.project_dir.clone()));
+        workspace.set_command_settings(CommandSettings::from(&config));
+        workspace.set_max_write_bytes(config.max_write_bytes());
+        workspace.set_formatters(config.formatters.clone());
+        workspace.set_linters(config.linters.clone());
+        workspace.set_diff_mode(config.diff_mode());
+        let artifacts = Arc::new(ArtifactStore::new(chats_dir.join("artifacts")));
+        let mut tools = ToolRegistry::builtin(Arc::clone(&workspace), Arc::clone(&artifacts));
+        if let Some(index) = &self.semantic_index {
+            tools.register(SemanticSearchTool::new(
+                Arc::clone(index),
+                Arc::clone(&workspace),
+            ));
+        }
+
+        if let Some(builtin_tools) = &self.builtin_tools {
+            let available = tools.names();
+            let unknown = builtin_tools
+                .iter()
+                .filter(|name| !available.contains(&name.as_str()))
+                .map(String::as_str)
+                .collect::<Vec<_>>();
+            if !unknown.is_empty() {
+                anyhow::bail!(
+                    "unknown builtin tools: {}; available: {}",
+                    unknown.join(", "),
+                    available.join(", ")
+                );
+            }
+
+            for name in available {
+                if !builtin_tools.iter().any(|n| n == name) {
+                    tools.unregister(name);
+                }
+            }
+        }
+        tools.extend(self.custom_tools);
+        if self.read_only {
+            // also applies to tools registered later on (eg. forge tools)
+            tools.restrict(&READ_ONLY_TOOLS.map(String::from));
+        }
+
+        let mut editor = DefaultEditor::new()?;
+        let editor_request = EditorRequest::default();
+        editor_request.bind(&mut editor);
+        let confirmations = match self.confirmations {
+            Some(c) => c,
+            None => Arc::new(TerminalConfirmations::new()?),
+        };
+        // approvals from config aren't applied in strict mode
+        let tool_audit_log = ToolAuditLog::new(self.project_log_dir.join(TOOL_AUDIT_LOG_FILE));
+        let corrections = CorrectionLog::new(self.project_log_dir.join(CORRECTIONS_LOG_FILE));
+        let approvals = match self.approval_mode {
+            ApprovalMode::Strict => Approvals::default(),
+            _ => Approvals {
+                fs_changes: false,
+                remote_actions: false,
+                approved_commands: config.approved_commands.clone(),
+            },
+        };
+
+        let project_name = self
+            .project_dir
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let webhooks = Webhooks::new(config.webhooks.clone(), project_name.clone());
+
+        Ok(Session {
+            config,
+            user_config_dir: self.user_config_dir,
+            agent: self.agent,
+            project_context: self.project_context,
+            package_context: None,
+            dirty_workspace: None,
+            read_only: self.read_only,
+            toolchains: vec![],
+            workspace,
+            editor,
+            editor_request,
+            approvals,
+            project_dir: self.project_dir,
+            project_log_dir: self.project_log_dir,
+            chats_dir,
+            provider: self.provider,
+            provider_access: self.provider_access,
+            model_name: self.model_name,
+            tokens_in_context: 0,
+            streaming: true,
+            text_tool_calls,
+            response_schema: None,
+            debug_tx: self.debug_tx,
+            tools,
+            artifacts,
+            semantic_index: self.semantic_index,
+            knowledge: Arc::new(KnowledgeStore::default()),
+            chat_history: Vec::new(),
+            stats: SessionStats::default(),
+            response_truncated: false,
+            output_overrides: OutputOverrides::default(),
+            approval_mode: self.approval_mode,
+            approval_mode_before_auto: None,
+            task_instructions: None,
+            initial_prompt: None,
+            pasted: None,
+            guardrails: None,
+            guardrail_violation: None,
+            audit_log: None,
+            tool_audit_log,
+            corrections,
+            learn_suggested: false,
+            shadow: None,
+            undo_reminder: None,
+            turn_error: None,
+            webhooks,
+            transcript: None,
+            replayed_tool_results: None,
+            turns: 0,
+            bookmarks: vec![],
+            imported: vec![],
+            resumed: None,
+            print_newline_before_prompt: false,
+            interactive: false,
+            connection_failures: 0,
+            queued: PromptQueue::default(),
+            sending_queued: false,
+            turn_queued: false,
+            title: TerminalTitle::new(project_name),
+            confirmations,
+            output: self.output,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::replay::ReplayModel;
+    use rig::agent::AgentBuilder;
+
+    fn builder() -> SessionBuilder {
+        let agent = AgentBuilder::new(ReplayModel::default()).build();
+        let project_dir = std::env::temp_dir();
+
+        SessionBuilder::new(
+            agent,
+            Provider::Anthropic,
+            "claude-sonnet-4-5",
+            project_dir.clone(),
+            project_dir.join(".agx-logs"),
+        )
+    }
+
+    #[tokio::test]
+    async fn only_the_chosen_builtin_tools_are_registered() {
+        // GIVEN
+        let builder = builder().builtin_tools(["read_file", "read_dir"]);
+
+        // WHEN
+        let session = builder.build().expect("session should've been built");
+
+        // THEN
+        assert_eq!(session.tools.names(), vec!["read_dir", "read_file"]);
+    }
+
+    #[tokio::test]
+    async fn building_a_session_fails_for_unknown_builtin_tools() {
+        // GIVEN
+        let builder = builder().builtin_tools(["read_file", "delete_file"]);
+
+        // WHEN
+        let result = builder.build();
+
+        // THEN
+        assert_eq!(
+            result.err().map(|e| e.to_string()),
+            Some(
+                "unknown builtin tools: delete_file; available: create_file, edit_file, read_artifact, read_dir, read_file, replace_in_files, run_cmd"
+                    .to_string()
+            )
+        );
+    }
+}
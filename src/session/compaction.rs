@@ -0,0 +1,167 @@
+use rig::OneOrMany;
+use rig::message::{Message, ToolResultContent, UserContent};
+use std::collections::HashMap;
+
+// Used to free up context when a provider rejects a request for exceeding the model's context
+// length. Outputs of older tool calls usually take up most of the context, and are the least
+// relevant to the conversation going forward.
+
+const ELIDED_TOOL_OUTPUT: &str =
+    "[output elided to free up context; call the tool again if needed]";
+// shorter outputs cost less than the reference that'd replace them
+const MIN_DEDUPED_OUTPUT_CHARS: usize = 200;
+
+/// Replaces the outputs of tool calls in all but the last `keep_recent` messages with a
+/// placeholder. Returns the number of tool outputs that were elided.
+pub fn elide_old_tool_outputs(history: &mut [Message], keep_recent: usize) -> usize {
+    let end = history.len().saturating_sub(keep_recent);
+    let mut num_elided = 0;
+
+    for message in &mut history[..end] {
+        let Message::User { content } = message else {
+            continue;
+        };
+
+        for c in content.iter_mut() {
+            if let UserContent::ToolResult(result) = c
+                && !is_elided(&result.content)
+            {
+                result.content = OneOrMany::one(ToolResultContent::text(ELIDED_TOOL_OUTPUT));
+                num_elided += 1;
+            }
+        }
+    }
+
+    num_elided
+}
+
+fn is_elided(content: &OneOrMany<ToolResultContent>) -> bool {
+    matches!(content.first(), ToolResultContent::Text(t) if t.text == ELIDED_TOOL_OUTPUT)
+}
+
+/// Replaces tool outputs that show up again later in the conversation (eg. a file that was read
+/// twice, unchanged) with a reference to the latest copy; `latest` is the message sent along
+/// with `history`. Returns the number of outputs replaced.
+pub fn dedupe_tool_outputs(history: &mut [Message], latest: &Message) -> usize {
+    let mut seen = HashMap::new();
+    let mut num_deduped = 0;
+
+    if let Message::User { content } = latest {
+        for c in content.iter().collect::<Vec<_>>().into_iter().rev() {
+            if let UserContent::ToolResult(result) = c
+                && let Some(output) = dedupable_output(&result.content)
+            {
+                seen.entry(output).or_insert_with(|| result.id.clone());
+            }
+        }
+    }
+
+    for message in history.iter_mut().rev() {
+        let Message::User { content } = message else {
+            continue;
+        };
+
+        for c in content.iter_mut().collect::<Vec<_>>().into_iter().rev() {
+            let UserContent::ToolResult(result) = c else {
+                continue;
+            };
+            let Some(output) = dedupable_output(&result.content) else {
+                continue;
+            };
+
+            match seen.get(&output) {
+                Some(later_id) => {
+                    result.content = OneOrMany::one(ToolResultContent::text(format!(
+                        "[same output as tool call {later_id}, later in the conversation; omitted to save context]"
+                    )));
+                    num_deduped += 1;
+                }
+                None => {
+                    seen.insert(output, result.id.clone());
+                }
+            }
+        }
+    }
+
+    num_deduped
+}
+
+fn dedupable_output(content: &OneOrMany<ToolResultContent>) -> Option<String> {
+    let output = content
+        .iter()
+        .map(|c| match c {
+            ToolResultContent::Text(t) => Some(t.text.as_str()),
+            ToolResultContent::Image(_) => None,
+        })
+        .collect::<Option<Vec<_>>>()?
+        .join("\n");
+
+    (output.chars().count() >= MIN_DEDUPED_OUTPUT_CHARS).then_some(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rig::message::{ToolResult, UserContent};
+
+    fn tool_result_message(id: &str, output: &str) -> Message {
+        Message::User {
+            content: OneOrMany::one(UserContent::ToolResult(ToolResult {
+                id: id.to_string(),
+                call_id: None,
+                content: OneOrMany::one(ToolResultContent::text(output)),
+            })),
+        }
+    }
+
+    #[test]
+    fn only_older_tool_outputs_are_elided() {
+        // GIVEN
+        let mut history = vec![
+            Message::user("read the files"),
+            tool_result_message("1", "contents of file 1"),
+            tool_result_message("2", "contents of file 2"),
+            Message::user("now summarize them"),
+            tool_result_message("3", "contents of file 3"),
+        ];
+
+        // WHEN
+        let num_elided = elide_old_tool_outputs(&mut history, 2);
+        let num_elided_again = elide_old_tool_outputs(&mut history, 2);
+
+        // THEN
+        assert_eq!(num_elided, 2);
+        assert_eq!(num_elided_again, 0);
+        assert_eq!(history[4], tool_result_message("3", "contents of file 3"));
+        assert_eq!(history[1], tool_result_message("1", ELIDED_TOOL_OUTPUT));
+    }
+
+    #[test]
+    fn only_the_latest_copy_of_a_repeated_tool_output_is_kept() {
+        // GIVEN
+        let contents = "fn main() {}\n".repeat(20);
+        let mut history = vec![
+            Message::user("read main.rs"),
+            tool_result_message("1", &contents),
+            Message::user("read it again"),
+            tool_result_message("2", &contents),
+            tool_result_message("3", "short output"),
+        ];
+        let latest = tool_result_message("4", "short output");
+
+        // WHEN
+        let num_deduped = dedupe_tool_outputs(&mut history, &latest);
+
+        // THEN
+        assert_eq!(num_deduped, 1);
+        assert_eq!(
+            history[1],
+            tool_result_message(
+                "1",
+                "[same output as tool call 2, later in the conversation; omitted to save context]"
+            )
+        );
+        assert_eq!(history[3], tool_result_message("2", &contents));
+        assert_eq!(history[4], tool_result_message("3", "short output"));
+    }
+}
@@ -0,0 +1,135 @@
+use rig::message::ToolCall;
+
+// Responses of two models to the same prompt (via /compare) are shown side by side. Tool calls
+// aren't executed; they're listed along with the response that requested them.
+
+/// Columns narrower than this are unreadable, so narrow terminals get one column below the other.
+const MIN_COLUMN_WIDTH: usize = 30;
+const SEPARATOR: &str = " │ ";
+
+/// A response's text, followed by the tool calls it requested (which weren't executed).
+pub fn describe_response(text: &str, tool_calls: &[ToolCall]) -> String {
+    let mut lines = vec![];
+    if !text.trim().is_empty() {
+        lines.push(text.trim().to_string());
+    }
+
+    for tool_call in tool_calls {
+        lines.push(format!(
+            "→ would call {} {}",
+            tool_call.function.name, tool_call.function.arguments
+        ));
+    }
+
+    if lines.is_empty() {
+        return "(empty response)".to_string();
+    }
+
+    lines.join("\n")
+}
+
+/// Lays out two titled texts side by side in `width` columns, wrapping their lines.
+pub fn side_by_side(left: (&str, &str), right: (&str, &str), width: usize) -> String {
+    let column_width = width.saturating_sub(SEPARATOR.chars().count()) / 2;
+    if column_width < MIN_COLUMN_WIDTH {
+        return format!(
+            "── {} ──\n{}\n\n── {} ──\n{}",
+            left.0, left.1, right.0, right.1
+        );
+    }
+
+    let column = |(title, text): (&str, &str)| {
+        let mut lines = wrap(title, column_width);
+        lines.push("─".repeat(column_width));
+        lines.extend(wrap(text, column_width));
+        lines
+    };
+    let (left, right) = (column(left), column(right));
+
+    (0..left.len().max(right.len()))
+        .map(|i| {
+            let left = left.get(i).map(String::as_str).unwrap_or_default();
+            let right = right.get(i).map(String::as_str).unwrap_or_default();
+            let padding = column_width.saturating_sub(left.chars().count());
+            format!("{left}{}{SEPARATOR}{right}", " ".repeat(padding))
+                .trim_end()
+                .to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Wraps lines at word boundaries; words longer than `width` are split.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut wrapped = vec![];
+    for line in text.lines() {
+        let mut current = String::new();
+        for word in line.split(' ') {
+            let mut word = word.to_string();
+            while word.chars().count() > width {
+                if !current.is_empty() {
+                    wrapped.push(std::mem::take(&mut current));
+                }
+                let rest = word.chars().skip(width).collect::<String>();
+                wrapped.push(word.chars().take(width).collect());
+                word = rest;
+            }
+
+            let needed = current.chars().count() + word.chars().count() + 1;
+            if !current.is_empty() && needed > width {
+                wrapped.push(std::mem::take(&mut current));
+            }
+            if !current.is_empty() {
+                current.push(' ');
+            }
+            current.push_str(&word);
+        }
+        wrapped.push(current);
+    }
+
+    wrapped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::assert_snapshot;
+    use rig::message::ToolFunction;
+
+    #[test]
+    fn responses_are_laid_out_side_by_side() {
+        // GIVEN
+        let left = describe_response(
+            "I'll check how errors are handled first.",
+            &[ToolCall {
+                id: "1".to_string(),
+                call_id: None,
+                function: ToolFunction {
+                    name: "read_file".to_string(),
+                    arguments: serde_json::json!({"path": "src/errors.rs"}),
+                },
+                signature: None,
+                additional_params: None,
+            }],
+        );
+        let right = describe_response(
+            "Wrap the call in a retry loop, with exponential backoff between attempts.\n\nThat keeps transient failures from surfacing.",
+            &[],
+        );
+
+        // WHEN
+        let rendered = side_by_side(("a: model-a", &left), ("b: model-b", &right), 70);
+
+        // THEN
+        assert_snapshot!(rendered, @r#"
+        a: model-a                        │ b: model-b
+        ───────────────────────────────── │ ─────────────────────────────────
+        I'll check how errors are handled │ Wrap the call in a retry loop,
+        first.                            │ with exponential backoff between
+        → would call read_file            │ attempts.
+        {"path":"src/errors.rs"}          │
+                                          │ That keeps transient failures
+                                          │ from surfacing.
+        "#);
+    }
+}
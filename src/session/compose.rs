@@ -0,0 +1,32 @@
+use rustyline::{
+    Cmd, ConditionalEventHandler, DefaultEditor, Event, EventContext, EventHandler, KeyEvent,
+    RepeatCount,
+};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Tracks requests (via Ctrl+X Ctrl+E) to compose the prompt being typed in the user's editor.
+/// The key binding accepts the line as is; the session then opens the editor on it.
+#[derive(Clone, Default)]
+pub(super) struct EditorRequest(Arc<AtomicBool>);
+
+impl EditorRequest {
+    pub(super) fn bind(&self, editor: &mut DefaultEditor) {
+        editor.bind_sequence(
+            Event::KeySeq(vec![KeyEvent::ctrl('X'), KeyEvent::ctrl('E')]),
+            EventHandler::Conditional(Box::new(self.clone())),
+        );
+    }
+
+    /// Returns whether the editor was requested for the line just read, resetting the request.
+    pub(super) fn take(&self) -> bool {
+        self.0.swap(false, Ordering::Relaxed)
+    }
+}
+
+impl ConditionalEventHandler for EditorRequest {
+    fn handle(&self, _: &Event, _: RepeatCount, _: bool, _: &EventContext) -> Option<Cmd> {
+        self.0.store(true, Ordering::Relaxed);
+        Some(Cmd::AcceptLine)
+    }
+}
@@ -0,0 +1,304 @@
+use crate::domain::{ApprovedCmds, CmdPattern, OutputKind, OutputSink, TerminalSink};
+use crate::tools::{BoxFuture, ConfirmationPolicy};
+use rustyline::DefaultEditor;
+use std::str::FromStr;
+use std::sync::Mutex;
+use tokio::sync::{mpsc, oneshot};
+
+/// A tool call that needs approval before it's executed.
+#[derive(Debug, Clone)]
+pub struct ApprovalRequest {
+    pub tool_call_id: String,
+    pub tool_name: String,
+    /// A one line description of the tool call.
+    pub repr: String,
+    /// Further information about the tool call (eg. the diff of a file edit), if any. Rendered
+    /// for a terminal, so it can contain ANSI escape codes.
+    pub details: Option<String>,
+    pub policy: ConfirmationPolicy,
+    /// Hunks of a file change that has more than one, rendered like `details`; these can be
+    /// approved selectively via [`ApprovalResponse::ApproveHunks`].
+    pub hunks: Vec<String>,
+    /// Whether the approval can be remembered for the rest of the session (it can't in strict
+    /// mode).
+    pub can_always_approve: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApprovalResponse {
+    Approve,
+    /// Approves the tool call, along with similar ones for the rest of the session (eg. all
+    /// file changes); the same as `Approve` if the request doesn't allow it.
+    AlwaysApprove,
+    /// Approves the hunks marked as accepted; rejects the tool call if none are.
+    ApproveHunks(Vec<bool>),
+    Reject,
+    /// Rejects the tool call, and passes the feedback on to the model.
+    Feedback(String),
+}
+
+/// Decides on tool calls that need approval. Closures taking an [`ApprovalRequest`] and
+/// returning an [`ApprovalResponse`] are providers as well.
+pub trait ConfirmationProvider: Send + Sync {
+    fn confirm<'a>(&'a self, request: &'a ApprovalRequest) -> BoxFuture<'a, ApprovalResponse>;
+}
+
+impl<F> ConfirmationProvider for F
+where
+    F: Fn(&ApprovalRequest) -> ApprovalResponse + Send + Sync,
+{
+    fn confirm<'a>(&'a self, request: &'a ApprovalRequest) -> BoxFuture<'a, ApprovalResponse> {
+        Box::pin(std::future::ready(self(request)))
+    }
+}
+
+/// Asks the user on the terminal.
+pub struct TerminalConfirmations {
+    editor: Mutex<DefaultEditor>,
+}
+
+impl TerminalConfirmations {
+    pub fn new() -> anyhow::Result<Self> {
+        Ok(Self {
+            editor: Mutex::new(DefaultEditor::new()?),
+        })
+    }
+
+    fn readline(&self, prompt: &str) -> Option<String> {
+        self.editor.lock().ok()?.readline(prompt).ok()
+    }
+
+    fn ask(&self, request: &ApprovalRequest) -> ApprovalResponse {
+        let approval_line = match &request.policy {
+            ConfirmationPolicy::FileChange(_) => {
+                Some("to allow all edits in this session".to_string())
+            }
+            ConfirmationPolicy::Command(command) => {
+                if let Ok(cmd_pattern) = CmdPattern::from_str(command) {
+                    Some(format!(r#"to always allow "{cmd_pattern}" commands"#,))
+                } else {
+                    // TODO: this error shouldn't happen this deep in the call stack
+                    None
+                }
+            }
+            ConfirmationPolicy::RemoteAction => {
+                Some("to allow all remote actions in this session".to_string())
+            }
+            ConfirmationPolicy::NotNeeded => None,
+        };
+
+        let approval_option = if request.can_always_approve {
+            format!(
+                "\n- a           {}",
+                approval_line.unwrap_or("to always approve this tool call".to_string())
+            )
+        } else {
+            String::new()
+        };
+
+        let confirmation_prompt = format!(
+            "
+type:
+- y / <enter> to proceed{}{}
+- n / no      to reject
+- reject and provide feedback: ",
+            approval_option,
+            if request.hunks.is_empty() {
+                ""
+            } else {
+                "\n- p           to select which hunks to apply"
+            },
+        );
+
+        let Some(input) = self.readline(&confirmation_prompt) else {
+            return ApprovalResponse::Reject;
+        };
+
+        match input.trim() {
+            "" | "y" => ApprovalResponse::Approve,
+            "a" if request.can_always_approve => ApprovalResponse::AlwaysApprove,
+            "n" | "no" => ApprovalResponse::Reject,
+            "p" if !request.hunks.is_empty() => self.select_hunks(&request.hunks),
+            feedback => ApprovalResponse::Feedback(feedback.to_string()),
+        }
+    }
+
+    fn select_hunks(&self, hunks: &[String]) -> ApprovalResponse {
+        let mut accepted = Vec::with_capacity(hunks.len());
+
+        for (i, hunk) in hunks.iter().enumerate() {
+            TerminalSink.emit(
+                OutputKind::ApprovalRequest,
+                &format!("\n[hunk {}/{}]\n", i + 1, hunks.len()),
+            );
+            TerminalSink.emit(OutputKind::Response, &format!("{hunk}\n"));
+
+            match self.readline("apply this hunk? (y/n) ") {
+                Some(input) => accepted.push(matches!(input.trim(), "" | "y" | "yes")),
+                None => return ApprovalResponse::Reject,
+            }
+        }
+
+        ApprovalResponse::ApproveHunks(accepted)
+    }
+}
+
+impl ConfirmationProvider for TerminalConfirmations {
+    fn confirm<'a>(&'a self, request: &'a ApprovalRequest) -> BoxFuture<'a, ApprovalResponse> {
+        Box::pin(std::future::ready(self.ask(request)))
+    }
+}
+
+/// Decides without asking anyone: tool calls allowed by the policy are approved, and the
+/// model is told that the others aren't allowed.
+#[derive(Debug, Default)]
+pub struct PolicyConfirmations {
+    pub file_changes: bool,
+    pub remote_actions: bool,
+    pub commands: ApprovedCmds,
+}
+
+impl PolicyConfirmations {
+    fn is_allowed(&self, policy: &ConfirmationPolicy) -> bool {
+        match policy {
+            ConfirmationPolicy::FileChange(_) => self.file_changes,
+            ConfirmationPolicy::Command(command) => self.commands.is_approved(command),
+            ConfirmationPolicy::RemoteAction => self.remote_actions,
+            ConfirmationPolicy::NotNeeded => true,
+        }
+    }
+}
+
+impl ConfirmationProvider for PolicyConfirmations {
+    fn confirm<'a>(&'a self, request: &'a ApprovalRequest) -> BoxFuture<'a, ApprovalResponse> {
+        let response = if self.is_allowed(&request.policy) {
+            ApprovalResponse::Approve
+        } else {
+            ApprovalResponse::Feedback(format!(
+                "{} isn't allowed in this session; find another way, or stop",
+                request.tool_name
+            ))
+        };
+
+        Box::pin(std::future::ready(response))
+    }
+}
+
+/// Hands requests off to another part of the program (eg. an API), which responds to them
+/// whenever it can. Requests are rejected if the receiving end goes away.
+#[derive(Clone)]
+pub struct ChannelConfirmations {
+    tx: mpsc::Sender<PendingApproval>,
+}
+
+/// A request awaiting a response via [`PendingApproval::respond`]; dropping it rejects the
+/// tool call.
+#[derive(Debug)]
+pub struct PendingApproval {
+    pub request: ApprovalRequest,
+    responder: oneshot::Sender<ApprovalResponse>,
+}
+
+impl PendingApproval {
+    pub fn respond(self, response: ApprovalResponse) {
+        let _ = self.responder.send(response);
+    }
+}
+
+impl ChannelConfirmations {
+    pub fn new() -> (Self, mpsc::Receiver<PendingApproval>) {
+        let (tx, rx) = mpsc::channel(1);
+
+        (Self { tx }, rx)
+    }
+}
+
+impl ConfirmationProvider for ChannelConfirmations {
+    fn confirm<'a>(&'a self, request: &'a ApprovalRequest) -> BoxFuture<'a, ApprovalResponse> {
+        Box::pin(async move {
+            let (responder, response) = oneshot::channel();
+            let pending = PendingApproval {
+                request: request.clone(),
+                responder,
+            };
+
+            if self.tx.send(pending).await.is_err() {
+                return ApprovalResponse::Reject;
+            }
+
+            response.await.unwrap_or(ApprovalResponse::Reject)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(policy: ConfirmationPolicy) -> ApprovalRequest {
+        ApprovalRequest {
+            tool_call_id: "call_1".to_string(),
+            tool_name: "run_cmd".to_string(),
+            repr: "run_cmd: cargo test".to_string(),
+            details: None,
+            policy,
+            hunks: vec![],
+            can_always_approve: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn policy_confirmations_only_approve_allowed_tool_calls() {
+        // GIVEN
+        let mut commands = ApprovedCmds::default();
+        commands.insert(&CmdPattern::from_str("cargo test").expect("pattern should be valid"));
+        let confirmations = PolicyConfirmations {
+            commands,
+            ..Default::default()
+        };
+
+        // WHEN
+        let allowed = confirmations
+            .confirm(&request(ConfirmationPolicy::Command(
+                "cargo test --all".to_string(),
+            )))
+            .await;
+        let disallowed = confirmations
+            .confirm(&request(ConfirmationPolicy::Command(
+                "rm -rf .".to_string(),
+            )))
+            .await;
+
+        // THEN
+        assert_eq!(allowed, ApprovalResponse::Approve);
+        assert_eq!(
+            disallowed,
+            ApprovalResponse::Feedback(
+                "run_cmd isn't allowed in this session; find another way, or stop".to_string()
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn channel_confirmations_wait_for_a_response() {
+        // GIVEN
+        let (confirmations, mut rx) = ChannelConfirmations::new();
+        tokio::spawn(async move {
+            while let Some(pending) = rx.recv().await {
+                let response = ApprovalResponse::Feedback(format!("not {}", pending.request.repr));
+                pending.respond(response);
+            }
+        });
+
+        // WHEN
+        let response = confirmations
+            .confirm(&request(ConfirmationPolicy::RemoteAction))
+            .await;
+
+        // THEN
+        assert_eq!(
+            response,
+            ApprovalResponse::Feedback("not run_cmd: cargo test".to_string())
+        );
+    }
+}
@@ -0,0 +1,280 @@
+use crate::domain::Provider;
+use crate::env::get_optional_env_var;
+use reqwest::Url;
+use std::time::Duration;
+
+// When the provider can't be reached, prompts fail with errors that say little about why (a
+// chain of HTTP client errors). Reachability is checked up front (via a TCP connection to the
+// provider's API), and repeated connection errors mid-session are treated as the session being
+// offline; either way, the user is told so plainly, and offered a way forward.
+
+/// Consecutive requests that fail to connect before the session is considered offline.
+pub const OFFLINE_AFTER_FAILURES: usize = 2;
+/// How often reachability is checked while a request is queued.
+pub const PROBE_INTERVAL: Duration = Duration::from_secs(5);
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Where requests to a provider go.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiEndpoint {
+    pub host: String,
+    pub port: u16,
+}
+
+impl std::fmt::Display for ApiEndpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.host, self.port)
+    }
+}
+
+impl ApiEndpoint {
+    /// The provider's own API, unless a base URL overrides it; `None` if that isn't known.
+    /// Requests go through a proxy if one is set in the environment, in which case that's what
+    /// needs to be reachable.
+    pub fn of(provider: &Provider, base_url: Option<&str>) -> Option<Self> {
+        let url = match base_url {
+            Some(url) => url.to_string(),
+            None => format!("https://{}", default_host(provider)?),
+        };
+        let proxy_vars = if url.starts_with("https:") {
+            ["HTTPS_PROXY", "https_proxy", "ALL_PROXY", "all_proxy"]
+        } else {
+            ["HTTP_PROXY", "http_proxy", "ALL_PROXY", "all_proxy"]
+        };
+        let proxy = proxy_vars.iter().find_map(|v| {
+            get_optional_env_var(v)
+                .ok()
+                .flatten()
+                .filter(|p| !p.is_empty())
+        });
+
+        Self::reached_via(&url, proxy.as_deref())
+    }
+
+    fn reached_via(url: &str, proxy: Option<&str>) -> Option<Self> {
+        let url = Url::parse(proxy.unwrap_or(url)).ok()?;
+
+        Some(Self {
+            host: url.host_str()?.to_string(),
+            port: url.port_or_known_default()?,
+        })
+    }
+
+    /// Checks whether a connection to the endpoint can be established; returns why not, if it
+    /// can't.
+    pub async fn probe(&self) -> Result<(), String> {
+        let connect = tokio::net::TcpStream::connect((self.host.as_str(), self.port));
+        match tokio::time::timeout(PROBE_TIMEOUT, connect).await {
+            Ok(Ok(_)) => Ok(()),
+            Ok(Err(e)) => Err(e.to_string()),
+            Err(_) => Err(format!("no connection within {}s", PROBE_TIMEOUT.as_secs())),
+        }
+    }
+}
+
+fn default_host(provider: &Provider) -> Option<&'static str> {
+    match provider {
+        Provider::Anthropic => Some("api.anthropic.com"),
+        Provider::Gemini => Some("generativelanguage.googleapis.com"),
+        Provider::GitHubCopilot => Some("api.githubcopilot.com"),
+        Provider::Mistral => Some("api.mistral.ai"),
+        Provider::OpenAI => Some("api.openai.com"),
+        Provider::OpenAICompatible => None,
+        Provider::Openrouter => Some("openrouter.ai"),
+        Provider::XAi => Some("api.x.ai"),
+    }
+}
+
+/// Whether a request failed because the provider couldn't be reached (as opposed to the
+/// provider rejecting it).
+pub fn is_connection_error(error: &anyhow::Error) -> bool {
+    let error = format!("{error:#}").to_lowercase();
+    [
+        "error sending request",
+        "tcp connect error",
+        "dns error",
+        "failed to lookup address",
+        "connection refused",
+        "network is unreachable",
+        "no route to host",
+        "connection reset",
+        "operation timed out",
+    ]
+    .iter()
+    .any(|p| error.contains(p))
+}
+
+/// Whether a request was turned down because too many were made recently.
+pub fn is_rate_limit_error(error: &anyhow::Error) -> bool {
+    let error = format!("{error:#}").to_lowercase();
+    // status codes alone would match token counts and the like
+    [
+        "rate limit",
+        "rate_limit",
+        "too many requests",
+        "resource_exhausted",
+    ]
+    .iter()
+    .any(|p| error.contains(p))
+}
+
+/// Whether a request was turned down for not fitting in the model's context window. Rate limits
+/// on tokens (eg. "too many tokens per minute") don't count; they call for waiting, not for
+/// freeing up context.
+pub fn is_context_length_exceeded_error(error: &anyhow::Error) -> bool {
+    if is_rate_limit_error(error) {
+        return false;
+    }
+
+    let error = format!("{error:#}").to_lowercase();
+    [
+        "context_length_exceeded",
+        "context length",
+        "context window",
+        "prompt is too long",
+        "input is too long",
+        "maximum number of tokens allowed",
+    ]
+    .iter()
+    .any(|p| error.contains(p))
+}
+
+/// Whether a streaming request failed because the server doesn't support tool calls in
+/// streaming mode (some OpenAI compatible servers don't), in which case it's worth retrying
+/// without streaming; other failures (eg. connection or authentication errors) aren't.
+pub fn is_streaming_tool_calls_unsupported(error: &impl std::fmt::Display) -> bool {
+    let error = error.to_string().to_lowercase();
+    let mentions_tools = ["tool", "function"].iter().any(|p| error.contains(p));
+    let unsupported = [
+        "not supported",
+        "unsupported",
+        "not implemented",
+        "not allowed",
+        "cannot",
+        "can't",
+    ]
+    .iter()
+    .any(|p| error.contains(p));
+
+    error.contains("stream") && mentions_tools && unsupported
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn endpoints_are_the_apis_or_the_proxys_they_are_reached_via() {
+        // GIVEN
+        // WHEN
+        let endpoints = [
+            ApiEndpoint::reached_via("https://api.anthropic.com", None),
+            ApiEndpoint::reached_via("http://localhost:11434/v1", None),
+            ApiEndpoint::reached_via(
+                "https://api.anthropic.com",
+                Some("http://proxy.corp.example:3128"),
+            ),
+            ApiEndpoint::reached_via("localhost:11434", None),
+        ];
+
+        // THEN
+        assert_eq!(
+            endpoints.map(|e| e.map(|e| e.to_string())),
+            [
+                Some("api.anthropic.com:443".to_string()),
+                Some("localhost:11434".to_string()),
+                Some("proxy.corp.example:3128".to_string()),
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn connection_and_rate_limit_errors_are_told_apart_from_other_errors() {
+        // GIVEN
+        let connection_errors = [
+            anyhow::anyhow!(
+                "client error (Connect): dns error: failed to lookup address information"
+            )
+            .context(
+                "HttpError: error sending request for url (https://api.anthropic.com/v1/messages)",
+            ),
+            anyhow::anyhow!("tcp connect error: Connection refused (os error 111)"),
+        ];
+        let rate_limit_errors = [
+            anyhow::anyhow!(
+                r#"ProviderError: 429 Too Many Requests: {{"type":"rate_limit_error"}}"#
+            ),
+            anyhow::anyhow!("RESOURCE_EXHAUSTED: quota exceeded for generate_content requests"),
+        ];
+        let other_errors = [
+            anyhow::anyhow!("ProviderError: authentication_error: invalid x-api-key"),
+            anyhow::anyhow!("prompt is too long: 210000 tokens > 200000 maximum"),
+        ];
+
+        // WHEN
+        // THEN
+        assert!(connection_errors.iter().all(is_connection_error));
+        assert!(!connection_errors.iter().any(is_rate_limit_error));
+        assert!(rate_limit_errors.iter().all(is_rate_limit_error));
+        assert!(!rate_limit_errors.iter().any(is_connection_error));
+        assert!(!other_errors.iter().any(is_connection_error));
+        assert!(!other_errors.iter().any(is_rate_limit_error));
+    }
+
+    #[test]
+    fn context_length_errors_are_told_apart_from_token_rate_limits() {
+        // GIVEN
+        let context_length_errors = [
+            anyhow::anyhow!("prompt is too long: 210000 tokens > 200000 maximum"),
+            anyhow::anyhow!(
+                "ProviderError: This model's maximum context length is 128000 tokens (context_length_exceeded)"
+            ),
+            anyhow::anyhow!(
+                "The input token count (1200000) exceeds the maximum number of tokens allowed (1048576)"
+            ),
+        ];
+        let rate_limit_errors = [
+            anyhow::anyhow!(
+                r#"ProviderError: 429 Too Many Requests: {{"type":"rate_limit_error","message":"too many tokens per minute"}}"#
+            ),
+            anyhow::anyhow!(
+                "Rate limit reached for gpt-4o on tokens per min (TPM): Limit 30000, Requested 42000"
+            ),
+            anyhow::anyhow!("too many tokens per minute, please slow down"),
+        ];
+
+        // WHEN
+        // THEN
+        assert!(
+            context_length_errors
+                .iter()
+                .all(is_context_length_exceeded_error)
+        );
+        assert!(
+            !rate_limit_errors
+                .iter()
+                .any(is_context_length_exceeded_error)
+        );
+    }
+
+    #[test]
+    fn only_unsupported_streaming_tool_calls_warrant_falling_back_to_non_streaming() {
+        // GIVEN
+        let unsupported = [
+            "CompletionError: ProviderError: Cannot use tools with stream",
+            "ProviderError: 400 Bad Request: streaming is not supported when tools are provided",
+            "ProviderError: function calling is unsupported in stream mode",
+        ];
+        let other_errors = [
+            "CompletionError: HttpError: error sending request for url (http://localhost:1234/v1/chat/completions)",
+            "ProviderError: 401 Unauthorized: invalid api key",
+            "StreamingError: error decoding response body: stream ended unexpectedly",
+        ];
+
+        // WHEN
+        // THEN
+        assert!(unsupported.iter().all(is_streaming_tool_calls_unsupported));
+        assert!(!other_errors.iter().any(is_streaming_tool_calls_unsupported));
+    }
+}
@@ -0,0 +1,153 @@
+use rig::OneOrMany;
+use rig::message::{AssistantContent, Message, Text};
+
+// Responses that get cut off (by a failed stream, or by hitting the max tokens limit) are kept
+// in history with a marker. When the model is asked to continue, the continuation is stitched
+// onto the cut off response, so that history contains a single assistant message.
+
+pub const TRUNCATION_MARKER: &str = "\n\n[response truncated]";
+
+/// Stitches the continuation that follows the continue prompt at `prompt_index` onto the
+/// truncated assistant message preceding it, and removes the prompt. Returns whether stitching
+/// was possible.
+pub fn stitch_continuation(history: &mut Vec<Message>, prompt_index: usize) -> bool {
+    if prompt_index == 0 || prompt_index + 1 >= history.len() {
+        return false;
+    }
+
+    let (
+        Message::Assistant {
+            content: truncated, ..
+        },
+        Message::User { .. },
+        Message::Assistant {
+            content: continuation,
+            ..
+        },
+    ) = (
+        &history[prompt_index - 1],
+        &history[prompt_index],
+        &history[prompt_index + 1],
+    )
+    else {
+        return false;
+    };
+
+    // signed reasoning needs to precede everything else in a message
+    if continuation
+        .iter()
+        .any(|c| matches!(c, AssistantContent::Reasoning(_)))
+    {
+        return false;
+    }
+
+    let mut contents = truncated.iter().cloned().collect::<Vec<_>>();
+    let mut continuation = continuation.iter().cloned();
+
+    match (contents.last_mut(), continuation.next()) {
+        (Some(AssistantContent::Text(previous)), Some(AssistantContent::Text(next))) => {
+            let previous_text = previous
+                .text
+                .strip_suffix(TRUNCATION_MARKER)
+                .unwrap_or(&previous.text);
+            *previous = Text {
+                text: format!("{previous_text}{}", next.text),
+            };
+        }
+        (_, Some(next)) => contents.push(next),
+        (_, None) => {}
+    }
+    contents.extend(continuation);
+
+    let Ok(content) = OneOrMany::many(contents) else {
+        return false;
+    };
+
+    history[prompt_index - 1] = Message::Assistant { id: None, content };
+    history.drain(prompt_index..=prompt_index + 1);
+
+    true
+}
+
+/// Checks a provider's raw response for a finish reason indicating that the max tokens limit
+/// was hit (eg. openai's "length", anthropic's "max_tokens", gemini's "MAX_TOKENS").
+pub fn stopped_due_to_max_tokens(raw_response: &serde_json::Value) -> bool {
+    match raw_response {
+        serde_json::Value::Object(map) => map.iter().any(|(k, v)| {
+            let is_finish_reason = matches!(
+                k.as_str(),
+                "finish_reason" | "stop_reason" | "finishReason" | "done_reason"
+            );
+            let is_max_tokens = matches!(v.as_str(), Some("length" | "max_tokens" | "MAX_TOKENS"));
+
+            (is_finish_reason && is_max_tokens) || stopped_due_to_max_tokens(v)
+        }),
+        serde_json::Value::Array(values) => values.iter().any(stopped_due_to_max_tokens),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn continuation_is_stitched_onto_truncated_response() {
+        // GIVEN
+        let mut history = vec![
+            Message::user("write a haiku"),
+            Message::assistant(format!("an old silent pond{TRUNCATION_MARKER}")),
+            Message::user("continue"),
+            Message::assistant("\na frog jumps into the pond"),
+        ];
+
+        // WHEN
+        let stitched = stitch_continuation(&mut history, 2);
+
+        // THEN
+        assert!(stitched);
+        assert_eq!(
+            history,
+            vec![
+                Message::user("write a haiku"),
+                Message::assistant("an old silent pond\na frog jumps into the pond"),
+            ]
+        );
+    }
+
+    #[test]
+    fn max_tokens_finish_reasons_of_any_provider_are_detected() {
+        // GIVEN
+        let cases = vec![
+            (
+                serde_json::json!({"choices": [{"finish_reason": "length"}]}),
+                true,
+            ),
+            (
+                serde_json::json!({"done_reason": "length", "eval_count": 4096}),
+                true,
+            ),
+            (
+                serde_json::json!({"candidates": [{"finishReason": "MAX_TOKENS"}]}),
+                true,
+            ),
+            (serde_json::json!({"stop_reason": "max_tokens"}), true),
+            (
+                serde_json::json!({"choices": [{"finish_reason": "stop"}]}),
+                false,
+            ),
+            (serde_json::json!({"done_reason": "stop"}), false),
+            (serde_json::json!({"usage": {"output_tokens": 4096}}), false),
+        ];
+
+        // WHEN
+        // THEN
+        for (raw_response, expected) in cases {
+            assert_eq!(
+                stopped_due_to_max_tokens(&raw_response),
+                expected,
+                "raw response: {raw_response}"
+            );
+        }
+    }
+}
@@ -0,0 +1,224 @@
+use anyhow::Context;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use tokio::io::AsyncWriteExt;
+use tracing::warn;
+
+// Feedback given when rejecting tool calls is kept (per project, across sessions), since the
+// same corrections tend to come up again and again (eg. "use make test, not cargo test"). /learn
+// has the model propose an AGENTS.md update capturing them; once it's made, they're cleared.
+
+/// Name of the corrections log's file, within the project's log directory.
+pub const CORRECTIONS_LOG_FILE: &str = "corrections.jsonl";
+/// /learn is suggested once a correction has been given this many times.
+pub const SUGGEST_LEARN_AFTER: usize = 2;
+/// Corrections are considered the same if at least this fraction of the words in the shorter
+/// one are in the other as well (and there are at least a couple of them).
+const SIMILARITY_THRESHOLD: f64 = 0.6;
+const MIN_SHARED_WORDS: usize = 2;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Correction {
+    pub timestamp: String,
+    /// The session's chat directory name (the time it started).
+    pub session: String,
+    /// The tool whose call was rejected.
+    pub tool: String,
+    pub feedback: String,
+}
+
+impl Correction {
+    pub fn new(session: &str, tool: &str, feedback: &str) -> Self {
+        Self {
+            timestamp: Utc::now().to_rfc3339(),
+            session: session.to_string(),
+            tool: tool.to_string(),
+            feedback: feedback.trim().to_string(),
+        }
+    }
+}
+
+pub struct CorrectionLog {
+    path: PathBuf,
+}
+
+impl CorrectionLog {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub async fn record(&self, correction: &Correction) -> anyhow::Result<()> {
+        let mut line = serde_json::to_string(correction).context("couldn't serialize entry")?;
+        line.push('\n');
+
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .context("couldn't create directory")?;
+        }
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .context("couldn't open file")?;
+        file.write_all(line.as_bytes())
+            .await
+            .context("couldn't write to file")?;
+
+        Ok(())
+    }
+
+    /// Returns the corrections recorded since the log was last cleared, oldest first.
+    pub async fn load(&self) -> anyhow::Result<Vec<Correction>> {
+        let contents = match tokio::fs::read_to_string(&self.path).await {
+            Ok(c) => c,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e).context("couldn't read corrections"),
+        };
+
+        let corrections = contents
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(|line| match serde_json::from_str(line) {
+                Ok(correction) => Some(correction),
+                Err(e) => {
+                    warn!(error = %e, "skipping invalid entry in corrections log");
+                    None
+                }
+            })
+            .collect();
+
+        Ok(corrections)
+    }
+
+    pub async fn clear(&self) -> anyhow::Result<()> {
+        match tokio::fs::remove_file(&self.path).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e).context("couldn't clear corrections"),
+        }
+    }
+}
+
+/// Corrections that (roughly) say the same thing.
+#[derive(Debug)]
+pub struct RecurringCorrection {
+    /// The latest wording of the correction.
+    pub feedback: String,
+    pub count: usize,
+    pub tools: BTreeSet<String>,
+}
+
+/// Groups similar corrections, most repeated first.
+pub fn group_corrections(corrections: &[Correction]) -> Vec<RecurringCorrection> {
+    let mut groups: Vec<RecurringCorrection> = vec![];
+    for correction in corrections {
+        match groups
+            .iter_mut()
+            .find(|g| similar(&g.feedback, &correction.feedback))
+        {
+            Some(group) => {
+                group.feedback = correction.feedback.clone();
+                group.count += 1;
+                group.tools.insert(correction.tool.clone());
+            }
+            None => groups.push(RecurringCorrection {
+                feedback: correction.feedback.clone(),
+                count: 1,
+                tools: BTreeSet::from([correction.tool.clone()]),
+            }),
+        }
+    }
+    // stable, so corrections given equally often stay in the order they were first given in
+    groups.sort_by_key(|g| std::cmp::Reverse(g.count));
+
+    groups
+}
+
+/// Returns how many of `corrections` are similar to `feedback`.
+pub fn times_given(corrections: &[Correction], feedback: &str) -> usize {
+    corrections
+        .iter()
+        .filter(|c| similar(&c.feedback, feedback))
+        .count()
+}
+
+/// The prompt /learn sends, asking the model to capture `corrections` in `context_file`.
+pub fn learn_prompt(corrections: &[RecurringCorrection], context_file: &str) -> String {
+    let list = corrections
+        .iter()
+        .map(|c| {
+            let tools = c.tools.iter().cloned().collect::<Vec<_>>().join(", ");
+            match c.count {
+                1 => format!("- {:?} (when calling {tools})", c.feedback),
+                n => format!("- {:?} ({n} times, when calling {tools})", c.feedback),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    format!(
+        "While working in this project, I've had to correct you with the feedback below (given when rejecting your tool calls), some of it more than once.
+
+{list}
+
+Propose an update to {context_file} that captures the instructions behind these corrections, so that I don't have to repeat them. Focus on the ones that recur, or that are likely to apply again; leave out ones that only made sense for a single task. Keep the additions short, and fit them in with what {context_file} already says. Make the change via edit_file (or create_file, if {context_file} doesn't exist), so that I can review it."
+    )
+}
+
+fn similar(a: &str, b: &str) -> bool {
+    let (a, b) = (words(a), words(b));
+    if a.is_empty() || b.is_empty() {
+        return false;
+    }
+
+    let shared = a.intersection(&b).count();
+    shared >= MIN_SHARED_WORDS
+        && shared as f64 / a.len().min(b.len()) as f64 >= SIMILARITY_THRESHOLD
+}
+
+/// Returns the words in `text` (lowercased), leaving out short ones (eg. "a", "to", "in").
+fn words(text: &str) -> BTreeSet<String> {
+    text.split(|c: char| !c.is_alphanumeric() && !"_-'".contains(c))
+        .filter(|w| w.chars().count() > 2)
+        .map(str::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::assert_snapshot;
+
+    #[test]
+    fn similar_corrections_are_grouped_for_learning() {
+        // GIVEN
+        let corrections = [
+            ("run_cmd", "use make test, not cargo test"),
+            ("create_file", "put new tests in the tests/ directory"),
+            ("run_cmd", "don't run cargo test directly; use make test"),
+            ("run_cmd", "Use `make test`, not `cargo test`!"),
+            ("edit_file", "keep the changelog sorted by date"),
+        ]
+        .map(|(tool, feedback)| Correction::new("2026-10-16-09-00-00", tool, feedback));
+
+        // WHEN
+        let groups = group_corrections(&corrections);
+        let given = times_given(&corrections, "use make test instead of cargo test");
+
+        // THEN
+        assert_eq!(given, 3);
+        assert_snapshot!(learn_prompt(&groups, "AGENTS.md"), @r#"
+        While working in this project, I've had to correct you with the feedback below (given when rejecting your tool calls), some of it more than once.
+
+        - "Use `make test`, not `cargo test`!" (3 times, when calling run_cmd)
+        - "put new tests in the tests/ directory" (when calling create_file)
+        - "keep the changelog sorted by date" (when calling edit_file)
+
+        Propose an update to AGENTS.md that captures the instructions behind these corrections, so that I don't have to repeat them. Focus on the ones that recur, or that are likely to apply again; leave out ones that only made sense for a single task. Keep the additions short, and fit them in with what AGENTS.md already says. Make the change via edit_file (or create_file, if AGENTS.md doesn't exist), so that I can review it.
+        "#);
+    }
+}
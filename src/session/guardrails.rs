@@ -0,0 +1,208 @@
+use crate::domain::{AutopilotConfig, ModelPricing};
+use crate::tools::ConfirmationPolicy;
+use anyhow::Context;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::path::Path;
+
+const DEFAULT_MAX_ITERATIONS: usize = 50;
+
+/// Limits enforced on sessions that run without human oversight (via `agx auto`).
+#[derive(Debug)]
+pub struct Guardrails {
+    allowed_tools: Option<Vec<String>>,
+    max_iterations: usize,
+    max_cost: Option<(f64, ModelPricing)>,
+    protected_paths: GlobSet,
+    /// The patterns `protected_paths` is built from.
+    protected_patterns: Vec<String>,
+    iterations: usize,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum GuardrailViolation {
+    #[error("reached the maximum number of iterations ({0})")]
+    MaxIterations(usize),
+    #[error("cost (${cost:.4}) exceeded the maximum cost (${max:.4})")]
+    MaxCost { cost: f64, max: f64 },
+    #[error(r#"model called "{0}", which is not an allowed tool"#)]
+    ToolNotAllowed(String),
+    #[error(r#"model tried to change "{0}", which is a protected path"#)]
+    ProtectedPath(String),
+    #[error(r#"model tried to change files matching "{0}", which could include protected paths"#)]
+    ProtectedPathsMayMatch(String),
+}
+
+impl Guardrails {
+    pub fn new(config: &AutopilotConfig, pricing: Option<&ModelPricing>) -> anyhow::Result<Self> {
+        let max_cost = match (config.max_cost, pricing) {
+            (Some(max), Some(pricing)) => Some((max, pricing.clone())),
+            (Some(_), None) => anyhow::bail!(
+                "max_cost is set, but pricing isn't configured for the model; add it under models.<model>.pricing in agx's local config"
+            ),
+            (None, _) => None,
+        };
+
+        let mut builder = GlobSetBuilder::new();
+        for pattern in &config.protected_paths {
+            builder.add(
+                Glob::new(pattern)
+                    .with_context(|| format!(r#"invalid protected path pattern "{pattern}""#))?,
+            );
+        }
+        let protected_paths = builder
+            .build()
+            .context("couldn't build matcher for protected paths")?;
+
+        Ok(Self {
+            allowed_tools: config.tools.clone(),
+            max_iterations: config.max_iterations.unwrap_or(DEFAULT_MAX_ITERATIONS),
+            max_cost,
+            protected_paths,
+            protected_patterns: config.protected_paths.clone(),
+            iterations: 0,
+        })
+    }
+
+    /// Returns how many more requests can be made to the model.
+    pub fn remaining_iterations(&self) -> usize {
+        self.max_iterations.saturating_sub(self.iterations)
+    }
+
+    pub fn protected_patterns(&self) -> &[String] {
+        &self.protected_patterns
+    }
+
+    /// Called before each request to the model.
+    pub fn start_iteration(&mut self) -> Result<(), GuardrailViolation> {
+        if self.iterations >= self.max_iterations {
+            return Err(GuardrailViolation::MaxIterations(self.max_iterations));
+        }
+        self.iterations += 1;
+
+        Ok(())
+    }
+
+    pub fn check_cost(
+        &self,
+        input_tokens: u64,
+        output_tokens: u64,
+    ) -> Result<(), GuardrailViolation> {
+        if let Some((max, pricing)) = &self.max_cost {
+            let cost = pricing.cost(input_tokens, output_tokens);
+            if cost > *max {
+                return Err(GuardrailViolation::MaxCost { cost, max: *max });
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn check_tool(&self, name: &str) -> Result<(), GuardrailViolation> {
+        match &self.allowed_tools {
+            Some(allowed) if !allowed.iter().any(|t| t == name) => {
+                Err(GuardrailViolation::ToolNotAllowed(name.to_string()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the files a tool call would change; `package` is the directory (relative to the
+    /// project root) that paths in tool calls are relative to.
+    pub fn check_confirmation_policy(
+        &self,
+        policy: &ConfirmationPolicy,
+        package: Option<&Path>,
+    ) -> Result<(), GuardrailViolation> {
+        if let ConfirmationPolicy::FileChange(path) = policy {
+            let path = match package {
+                Some(package) => package.join(path),
+                None => Path::new(path).to_path_buf(),
+            };
+            let path = path.strip_prefix("./").unwrap_or(&path);
+
+            // tools that change several files at once (eg. replace_in_files) take globs, which
+            // can't be told apart from protected paths without walking the project
+            if !self.protected_paths.is_empty()
+                && path.to_string_lossy().contains(['*', '?', '[', '{'])
+            {
+                return Err(GuardrailViolation::ProtectedPathsMayMatch(
+                    path.to_string_lossy().to_string(),
+                ));
+            }
+
+            if self.protected_paths.is_match(path) {
+                return Err(GuardrailViolation::ProtectedPath(
+                    path.to_string_lossy().to_string(),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::assert_yaml_snapshot;
+
+    #[test]
+    fn guardrails_trip_when_limits_are_exceeded() {
+        // GIVEN
+        let config = AutopilotConfig {
+            tools: Some(vec!["read_file".to_string(), "edit_file".to_string()]),
+            max_iterations: Some(1),
+            max_cost: Some(0.5),
+            protected_paths: vec!["Cargo.lock".to_string(), ".github/**".to_string()],
+        };
+        let pricing = ModelPricing {
+            input_per_million_tokens: 3.0,
+            output_per_million_tokens: 15.0,
+        };
+        let mut guardrails =
+            Guardrails::new(&config, Some(&pricing)).expect("guardrails should've been built");
+
+        // WHEN
+        let results = [
+            guardrails.start_iteration(),
+            guardrails.start_iteration(),
+            guardrails.check_cost(100_000, 10_000),
+            guardrails.check_cost(100_000, 20_000),
+            guardrails.check_tool("edit_file"),
+            guardrails.check_tool("run_cmd"),
+            guardrails.check_confirmation_policy(
+                &ConfirmationPolicy::FileChange("src/main.rs".to_string()),
+                None,
+            ),
+            guardrails.check_confirmation_policy(
+                &ConfirmationPolicy::FileChange("./Cargo.lock".to_string()),
+                None,
+            ),
+            guardrails.check_confirmation_policy(
+                &ConfirmationPolicy::FileChange("workflows/ci.yml".to_string()),
+                Some(Path::new(".github")),
+            ),
+            guardrails.check_confirmation_policy(
+                &ConfirmationPolicy::FileChange("src/**/*.rs".to_string()),
+                None,
+            ),
+        ]
+        .into_iter()
+        .map(|r| r.map_err(|e| e.to_string()))
+        .collect::<Vec<_>>();
+
+        // THEN
+        assert_yaml_snapshot!(results, @r#"
+        - Ok: ~
+        - Err: reached the maximum number of iterations (1)
+        - Ok: ~
+        - Err: cost ($0.6000) exceeded the maximum cost ($0.5000)
+        - Ok: ~
+        - Err: "model called \"run_cmd\", which is not an allowed tool"
+        - Ok: ~
+        - Err: "model tried to change \"Cargo.lock\", which is a protected path"
+        - Err: "model tried to change \".github/workflows/ci.yml\", which is a protected path"
+        - Err: "model tried to change files matching \"src/**/*.rs\", which could include protected paths"
+        "#);
+    }
+}
@@ -0,0 +1,168 @@
+use anyhow::Context;
+use rig::message::Message;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+// Sessions save their chat history (messages, tool calls, and tool results) in their chats
+// directory after every turn, so that a crash or a restart doesn't lose the conversation; it can
+// be picked up again in another session, via /resume or --resume. Sessions are referred to by the
+// name of their chats directory (ie. the time they were started at).
+
+const HISTORY_FILE_NAME: &str = "history.json";
+/// /resume (without a session) lists this many of the most recent sessions.
+pub const MAX_LISTED_SESSIONS: usize = 10;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SavedHistory {
+    pub provider: String,
+    pub model: String,
+    /// The conversation's name (after its first prompt).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// The number of prompts sent in the conversation.
+    pub turns: usize,
+    pub messages: Vec<Message>,
+}
+
+impl SavedHistory {
+    /// Saves the history in `chats_dir`, replacing the one saved before (if any).
+    pub async fn save(&self, chats_dir: &Path) -> anyhow::Result<()> {
+        let contents = serde_json::to_vec(self).context("couldn't serialize chat history")?;
+        // a crash mid-write would otherwise leave behind a history that can't be resumed
+        let temp_path = chats_dir.join(format!(".{HISTORY_FILE_NAME}.tmp"));
+        tokio::fs::write(&temp_path, contents)
+            .await
+            .context("couldn't write chat history")?;
+        tokio::fs::rename(&temp_path, chats_dir.join(HISTORY_FILE_NAME))
+            .await
+            .context("couldn't write chat history")?;
+
+        Ok(())
+    }
+
+    pub async fn load(session_dir: &Path) -> anyhow::Result<Self> {
+        let path = session_dir.join(HISTORY_FILE_NAME);
+        let contents = tokio::fs::read(&path)
+            .await
+            .with_context(|| format!("couldn't read {}", path.to_string_lossy()))?;
+
+        serde_json::from_slice(&contents)
+            .with_context(|| format!("{} is invalid", path.to_string_lossy()))
+    }
+}
+
+/// A session that saved its chat history.
+#[derive(Debug)]
+pub struct SavedSession {
+    pub name: String,
+    pub dir: PathBuf,
+}
+
+/// Lists the sessions in `chats_root` that saved their chat history, most recent first; the
+/// running session (whose chats directory is `current`) is left out.
+pub async fn saved_sessions(
+    chats_root: &Path,
+    current: &Path,
+) -> anyhow::Result<Vec<SavedSession>> {
+    let mut entries = tokio::fs::read_dir(chats_root)
+        .await
+        .context("couldn't find any sessions for this project")?;
+
+    let mut sessions = vec![];
+    while let Some(entry) = entries.next_entry().await? {
+        let dir = entry.path();
+        if dir == current || !dir.join(HISTORY_FILE_NAME).is_file() {
+            continue;
+        }
+
+        sessions.push(SavedSession {
+            name: entry.file_name().to_string_lossy().to_string(),
+            dir,
+        });
+    }
+    // session directories are named after the time they were started at
+    sessions.sort_by(|a, b| b.name.cmp(&a.name));
+
+    Ok(sessions)
+}
+
+/// Finds the session named `name` (or the most recent one, if `name` is "latest").
+pub async fn find_saved_session(
+    chats_root: &Path,
+    name: &str,
+    current: &Path,
+) -> anyhow::Result<SavedSession> {
+    let sessions = saved_sessions(chats_root, current).await?;
+    let found = if name == "latest" {
+        sessions.into_iter().next()
+    } else {
+        sessions.into_iter().find(|s| s.name == name)
+    };
+
+    found.with_context(|| {
+        format!(
+            r#"couldn't find a saved session named "{name}"; sessions are looked up in {}"#,
+            chats_root.to_string_lossy()
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::assert_snapshot;
+
+    #[tokio::test]
+    async fn the_latest_saved_session_is_resumed() -> anyhow::Result<()> {
+        // GIVEN
+        let temp_dir = tempfile::tempdir()?;
+        let chats_root = temp_dir.path();
+        let history = |title: &str, turns: usize| SavedHistory {
+            provider: "anthropic".to_string(),
+            model: "claude-sonnet-4-5".to_string(),
+            title: Some(title.to_string()),
+            turns,
+            messages: vec![Message::user(title), Message::assistant("done")],
+        };
+        for (name, history) in [
+            (
+                "2026-10-14-09-12-40",
+                Some(history("add a --verbose flag", 1)),
+            ),
+            (
+                "2026-10-15-17-03-11",
+                Some(history("fix the flaky test", 3)),
+            ),
+            // crashed before saving anything
+            ("2026-10-16-08-30-02", None),
+        ] {
+            let dir = chats_root.join(name);
+            tokio::fs::create_dir_all(&dir).await?;
+            if let Some(history) = history {
+                history.save(&dir).await?;
+            }
+        }
+        let current = chats_root.join("2026-10-16-10-45-19");
+
+        // WHEN
+        let latest = find_saved_session(chats_root, "latest", &current).await?;
+        let resumed = SavedHistory::load(&latest.dir).await?;
+        let missing = find_saved_session(chats_root, "2026-10-16-08-30-02", &current)
+            .await
+            .expect_err("session without a history shouldn't be found");
+
+        // THEN
+        assert_eq!(latest.name, "2026-10-15-17-03-11");
+        assert_eq!(resumed.title.as_deref(), Some("fix the flaky test"));
+        assert_eq!((resumed.turns, resumed.messages.len()), (3, 2));
+        assert_snapshot!(
+            missing
+                .to_string()
+                .replace(&chats_root.to_string_lossy().to_string(), "<chats>"),
+            @r#"
+        couldn't find a saved session named "2026-10-16-08-30-02"; sessions are looked up in <chats>
+        "#
+        );
+        Ok(())
+    }
+}
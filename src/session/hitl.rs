@@ -1,34 +1,40 @@
 use crate::domain::{ApprovedCmds, CmdPattern};
-use crate::tools::AgxToolCall;
+use crate::tools::{AgxToolCall, ConfirmationPolicy};
 use std::fmt::Display;
+use std::path::{Component, Path, PathBuf};
 use std::str::FromStr;
 
+/// Paths commands commonly refer to that are outside the workspace, but harmless.
+const HARMLESS_PATHS: [&str; 4] = ["/dev/null", "/dev/stdin", "/dev/stdout", "/dev/stderr"];
+
 #[derive(Debug, Default)]
 pub struct Approvals {
     pub fs_changes: bool,
+    pub remote_actions: bool,
     pub approved_commands: ApprovedCmds,
 }
 
 impl Approvals {
     pub fn is_tool_call_approved(&self, tool_call: &AgxToolCall) -> bool {
-        match tool_call {
-            AgxToolCall::CreateFile { .. } | AgxToolCall::EditFile { .. } => self.fs_changes,
-            AgxToolCall::RunCmd { args } => self.approved_commands.is_approved(&args.command),
-            _ => true,
+        match tool_call.confirmation_policy() {
+            ConfirmationPolicy::FileChange(_) => self.fs_changes,
+            ConfirmationPolicy::Command(command) => self.approved_commands.is_approved(&command),
+            ConfirmationPolicy::RemoteAction => self.remote_actions,
+            ConfirmationPolicy::NotNeeded => true,
         }
     }
 
     pub fn save_approval(&mut self, tool_call: &AgxToolCall) -> Option<String> {
-        match tool_call {
-            AgxToolCall::CreateFile { .. } | AgxToolCall::EditFile { .. } => {
+        match tool_call.confirmation_policy() {
+            ConfirmationPolicy::FileChange(_) => {
                 self.fs_changes = true;
                 Some(
                     "will not ask for confirmation for creating/editing files from now on"
                         .to_string(),
                 )
             }
-            AgxToolCall::RunCmd { args } => {
-                if let Ok(cmd_pattern) = CmdPattern::from_str(&args.command) {
+            ConfirmationPolicy::Command(command) => {
+                if let Ok(cmd_pattern) = CmdPattern::from_str(&command) {
                     self.approved_commands.insert(&cmd_pattern);
                     Some(format!(
                         r#"will not ask for confirmation for running "{cmd_pattern}" commands from now on"#,
@@ -38,20 +44,120 @@ impl Approvals {
                     None
                 }
             }
-            _ => None,
+            ConfirmationPolicy::RemoteAction => {
+                self.remote_actions = true;
+                Some(
+                    "will not ask for confirmation for remote actions (eg. opening pull requests) from now on"
+                        .to_string(),
+                )
+            }
+            ConfirmationPolicy::NotNeeded => None,
         }
     }
 }
 
+/// Returns the arguments of a command that refer to paths outside the workspace directory it's
+/// run in (eg. the destination in `cp target/release/agx /usr/local/bin`); approving a binary
+/// shouldn't let it change files elsewhere without the user noticing. Arguments are treated as
+/// paths if they're absolute, start with "~" or "$" (as they could expand to anywhere), or
+/// traverse to a parent directory (including values of flags like `--out=../dist`). Commands
+/// that can't be split into arguments are returned whole, since they can't be checked.
+pub fn paths_outside_workspace(command: &str, workspace_dir: &Path) -> Vec<String> {
+    let Some(args) = shlex::split(command) else {
+        return vec![command.to_string()];
+    };
+    let workspace_dir = normalize(workspace_dir);
+
+    args.into_iter()
+        .skip(1)
+        .filter(|arg| {
+            let path = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with('-') => value,
+                _ => arg,
+            };
+            if HARMLESS_PATHS.contains(&path) {
+                return false;
+            }
+            if path.starts_with('~') || path.starts_with('$') {
+                return true;
+            }
+
+            let path = Path::new(path);
+            let looks_like_path =
+                path.is_absolute() || path.components().any(|c| c == Component::ParentDir);
+            looks_like_path && !normalize(&workspace_dir.join(path)).starts_with(&workspace_dir)
+        })
+        .collect()
+}
+
+/// Resolves "." and ".." in a path lexically; the path doesn't need to exist.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            c => normalized.push(c),
+        }
+    }
+
+    normalized
+}
+
 impl Display for Approvals {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
             r#"approvals:
 - create/edit files: {}
+- remote actions: {}
 - approved commands: {}
 "#,
-            self.fs_changes, self.approved_commands
+            self.fs_changes, self.remote_actions, self.approved_commands
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_outside_the_workspace_are_found_in_commands() {
+        // GIVEN
+        let workspace_dir = Path::new("/home/user/projects/agx");
+        let commands = [
+            "cp target/release/agx /usr/local/bin",
+            "cargo build --target-dir=../shared-target",
+            "cat ~/.ssh/id_rsa",
+            "ls /home/user/projects/agx/src ./src/../docs",
+            "cargo test 2> /dev/null",
+            "cat $HOME/.ssh/id_rsa ${HOME}/.aws/credentials",
+            "cp ~admin/.bashrc --out=$TMPDIR/rc .",
+            "echo 'unbalanced > /etc/hosts",
+        ];
+
+        // WHEN
+        let outside = commands.map(|command| paths_outside_workspace(command, workspace_dir));
+
+        // THEN
+        assert_eq!(
+            outside,
+            [
+                vec!["/usr/local/bin".to_string()],
+                vec!["--target-dir=../shared-target".to_string()],
+                vec!["~/.ssh/id_rsa".to_string()],
+                vec![],
+                vec![],
+                vec![
+                    "$HOME/.ssh/id_rsa".to_string(),
+                    "${HOME}/.aws/credentials".to_string(),
+                ],
+                vec!["~admin/.bashrc".to_string(), "--out=$TMPDIR/rc".to_string()],
+                vec!["echo 'unbalanced > /etc/hosts".to_string()],
+            ]
+        );
+    }
+}
@@ -0,0 +1,199 @@
+use futures::FutureExt;
+use futures::future::{BoxFuture, Fuse};
+use std::future::Future;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+// How turns can be interrupted:
+//
+// - Esc (while a response is streamed): the response stops; what's been received is kept, as a
+//   response that was cut off (which can be resumed via /continue).
+// - Ctrl+C: the response or tool call in progress is cancelled; what the turn produced until
+//   then is kept in history.
+// - Ctrl+C twice (the second within DOUBLE_INTERRUPT_WINDOW): the turn is discarded from history
+//   entirely, as if the prompt was never sent.
+//
+// Space (while a response is streamed) doesn't interrupt the turn: it pauses printing the
+// response, which is still received in the background, and resumes it when pressed again.
+//
+// r (while the first token of a response is awaited, once a retry has been offered): the request
+// is abandoned, and sent again.
+
+/// How long after a Ctrl+C another one discards the turn.
+pub const DOUBLE_INTERRUPT_WINDOW: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interrupt {
+    /// Ctrl+C
+    Cancel,
+    /// Esc
+    StopStreaming,
+    /// Space
+    TogglePause,
+    /// r
+    Retry,
+}
+
+/// Watches for interrupts while a response is being received.
+pub struct Interrupts {
+    ctrl_c: Fuse<BoxFuture<'static, std::io::Result<()>>>,
+    keys: Option<KeyWatcher>,
+}
+
+impl Interrupts {
+    /// Keys (Esc, Space, r) are only watched for if stdin is a terminal.
+    pub fn watch() -> Self {
+        Self {
+            ctrl_c: tokio::signal::ctrl_c().boxed().fuse(),
+            keys: KeyWatcher::start(),
+        }
+    }
+
+    /// Resolves once the user interrupts.
+    pub async fn next(&mut self) -> Interrupt {
+        let key_pressed = async {
+            match &mut self.keys {
+                Some(keys) => keys.pressed().await,
+                None => std::future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            Ok(()) = &mut self.ctrl_c => Interrupt::Cancel,
+            interrupt = key_pressed => interrupt,
+        }
+    }
+}
+
+/// Awaits `future`, unless Ctrl+C is pressed first.
+pub async fn cancellable<F>(future: F) -> Option<F::Output>
+where
+    F: Future,
+{
+    tokio::select! {
+        Ok(()) = tokio::signal::ctrl_c() => None,
+        output = future => Some(output),
+    }
+}
+
+/// Returns whether Ctrl+C is pressed (again) within [`DOUBLE_INTERRUPT_WINDOW`].
+pub async fn pressed_again() -> bool {
+    matches!(
+        tokio::time::timeout(DOUBLE_INTERRUPT_WINDOW, tokio::signal::ctrl_c()).await,
+        Ok(Ok(()))
+    )
+}
+
+/// Reads key presses from the terminal on a thread of its own, with line buffering and echo
+/// turned off (Ctrl+C still raises SIGINT). The terminal is restored when the watcher is
+/// dropped.
+struct KeyWatcher {
+    pressed: mpsc::Receiver<Interrupt>,
+    #[cfg(unix)]
+    stop: std::sync::Arc<std::sync::atomic::AtomicBool>,
+    #[cfg(unix)]
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+impl KeyWatcher {
+    async fn pressed(&mut self) -> Interrupt {
+        match self.pressed.recv().await {
+            Some(interrupt) => interrupt,
+            None => std::future::pending().await,
+        }
+    }
+}
+
+#[cfg(unix)]
+impl KeyWatcher {
+    const POLL_INTERVAL_MS: u16 = 50;
+    const ESC: u8 = 0x1b;
+    const SPACE: u8 = b' ';
+    const RETRY: u8 = b'r';
+    /// Presses that haven't been handled yet; pausing and resuming quickly needs more than one.
+    const PENDING_PRESSES: usize = 8;
+
+    fn start() -> Option<Self> {
+        use nix::sys::termios::{
+            LocalFlags, SetArg, SpecialCharacterIndices, tcgetattr, tcsetattr,
+        };
+        use std::io::IsTerminal;
+        use std::sync::Arc;
+        use std::sync::atomic::AtomicBool;
+
+        let stdin = std::io::stdin();
+        if !stdin.is_terminal() {
+            return None;
+        }
+
+        let original = tcgetattr(&stdin).ok()?;
+        let mut unbuffered = original.clone();
+        unbuffered
+            .local_flags
+            .remove(LocalFlags::ICANON | LocalFlags::ECHO);
+        unbuffered.control_chars[SpecialCharacterIndices::VMIN as usize] = 0;
+        unbuffered.control_chars[SpecialCharacterIndices::VTIME as usize] = 0;
+        tcsetattr(&stdin, SetArg::TCSANOW, &unbuffered).ok()?;
+
+        let (tx, rx) = mpsc::channel(Self::PENDING_PRESSES);
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = std::thread::spawn({
+            let stop = Arc::clone(&stop);
+            move || {
+                Self::watch(&stop, &tx);
+                _ = tcsetattr(std::io::stdin(), SetArg::TCSANOW, &original);
+            }
+        });
+
+        Some(Self {
+            pressed: rx,
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    fn watch(stop: &std::sync::atomic::AtomicBool, tx: &mpsc::Sender<Interrupt>) {
+        use nix::errno::Errno;
+        use nix::poll::{PollFd, PollFlags, poll};
+        use std::os::fd::AsFd;
+        use std::sync::atomic::Ordering;
+
+        let stdin = std::io::stdin();
+        let mut buf = [0; 64];
+        while !stop.load(Ordering::Relaxed) {
+            let mut fds = [PollFd::new(stdin.as_fd(), PollFlags::POLLIN)];
+            match poll(&mut fds, Self::POLL_INTERVAL_MS) {
+                Ok(0) | Err(Errno::EINTR) => continue,
+                Ok(_) => {}
+                Err(_) => return,
+            }
+
+            match nix::unistd::read(stdin.as_fd(), &mut buf) {
+                // keys like arrows send sequences that start with Esc; only a lone one counts
+                Ok(1) if buf[0] == Self::ESC => _ = tx.try_send(Interrupt::StopStreaming),
+                Ok(1) if buf[0] == Self::SPACE => _ = tx.try_send(Interrupt::TogglePause),
+                Ok(1) if buf[0] == Self::RETRY => _ = tx.try_send(Interrupt::Retry),
+                Ok(_) | Err(Errno::EINTR) => {}
+                Err(_) => return,
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for KeyWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, std::sync::atomic::Ordering::Relaxed);
+        // the terminal needs to be restored before it's read from again
+        if let Some(thread) = self.thread.take() {
+            _ = thread.join();
+        }
+    }
+}
+
+#[cfg(not(unix))]
+impl KeyWatcher {
+    fn start() -> Option<Self> {
+        None
+    }
+}
@@ -0,0 +1,129 @@
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// What the user is told while the first token of a response is awaited.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WaitNotice {
+    /// Nothing has been received in this long.
+    StillWaiting(Duration),
+    /// Nothing has been received in this long; the request can be retried.
+    OfferRetry(Duration),
+}
+
+/// Keeps track of how long the first token of a streamed response has been awaited, so that a
+/// slow (or stuck) response doesn't come across as silence: a notice is due every `notice_after`,
+/// and a retry is offered (once) after `retry_after`. A zero duration turns either off.
+pub struct FirstTokenWait {
+    started: Instant,
+    notice_after: Duration,
+    retry_after: Duration,
+    notices_shown: u32,
+    retry_offered: bool,
+    received: bool,
+}
+
+impl FirstTokenWait {
+    pub fn new(notice_after: Duration, retry_after: Duration) -> Self {
+        Self {
+            started: Instant::now(),
+            notice_after,
+            retry_after,
+            notices_shown: 0,
+            retry_offered: false,
+            received: false,
+        }
+    }
+
+    /// No notices are due once something has been received.
+    pub fn mark_received(&mut self) {
+        self.received = true;
+    }
+
+    pub fn has_received(&self) -> bool {
+        self.received
+    }
+
+    pub fn retry_offered(&self) -> bool {
+        self.retry_offered
+    }
+
+    /// Resolves once the next notice is due; never, if none is.
+    pub async fn next_notice(&mut self) -> WaitNotice {
+        let Some((due_after, notice)) = self.next_due() else {
+            return std::future::pending().await;
+        };
+
+        tokio::time::sleep_until(self.started + due_after).await;
+        self.mark_shown(notice);
+        notice
+    }
+
+    /// The next notice, and how long after the wait started it's due.
+    fn next_due(&self) -> Option<(Duration, WaitNotice)> {
+        if self.received {
+            return None;
+        }
+
+        let next_notice =
+            (!self.notice_after.is_zero()).then(|| self.notice_after * (self.notices_shown + 1));
+        let retry =
+            (!self.retry_after.is_zero() && !self.retry_offered).then_some(self.retry_after);
+
+        match (next_notice, retry) {
+            (Some(notice), Some(retry)) if retry <= notice => {
+                Some((retry, WaitNotice::OfferRetry(retry)))
+            }
+            (Some(notice), _) => Some((notice, WaitNotice::StillWaiting(notice))),
+            (None, Some(retry)) => Some((retry, WaitNotice::OfferRetry(retry))),
+            (None, None) => None,
+        }
+    }
+
+    fn mark_shown(&mut self, notice: WaitNotice) {
+        match notice {
+            WaitNotice::StillWaiting(_) => self.notices_shown += 1,
+            WaitNotice::OfferRetry(after) => {
+                self.retry_offered = true;
+                // the offer stands in for any notice due at the same time
+                if !self.notice_after.is_zero() {
+                    self.notices_shown = (after.as_millis() / self.notice_after.as_millis()) as u32;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notices_repeat_until_the_first_token_with_one_retry_offer() {
+        // GIVEN
+        let secs = Duration::from_secs;
+        let mut wait = FirstTokenWait::new(secs(10), secs(30));
+        let mut notices = vec![];
+
+        // WHEN
+        for _ in 0..5 {
+            let (_, notice) = wait.next_due().expect("a notice should be due");
+            wait.mark_shown(notice);
+            notices.push(notice);
+        }
+        wait.mark_received();
+
+        // THEN
+        assert_eq!(
+            notices,
+            vec![
+                WaitNotice::StillWaiting(secs(10)),
+                WaitNotice::StillWaiting(secs(20)),
+                WaitNotice::OfferRetry(secs(30)),
+                WaitNotice::StillWaiting(secs(40)),
+                WaitNotice::StillWaiting(secs(50)),
+            ]
+        );
+        assert!(wait.retry_offered());
+        assert_eq!(wait.next_due(), None);
+    }
+}
@@ -1,101 +1,342 @@
+//! The agent loop: a [`Session`] sends prompts to a model, executes the tool calls it requests
+//! (after getting them approved, if needed), and feeds the results back until the model is done.
+
+mod audit;
+mod builder;
+mod compaction;
+mod compare;
+mod compose;
+mod confirmations;
+mod connectivity;
+mod continuation;
+mod corrections;
+mod guardrails;
+mod history;
 mod hitl;
+mod injection;
+mod interrupts;
+mod latency;
+mod overrides;
+mod queue;
+mod reminders;
+mod shadow;
+mod stats;
+mod text_tools;
+mod title;
+mod transcript;
+mod webhooks;
+
+pub use builder::SessionBuilder;
+pub use confirmations::{
+    ApprovalRequest, ApprovalResponse, ChannelConfirmations, ConfirmationProvider, PendingApproval,
+    PolicyConfirmations, TerminalConfirmations,
+};
+pub use transcript::{Bookmark, RecordedResponse, Transcript, TranscriptEvent};
 
-use crate::config::save_local_config;
-use crate::domain::{CmdPattern, Config, DebugEvent, DebugEventSender, MessageExt, Provider};
-use crate::tools::AgxToolCall;
+use crate::config::{get_project_config, get_user_config, save_approved_command};
+use crate::domain::{
+    ApprovalDecision, ApprovalMode, CmdPattern, Config, DebugEvent, DebugEventSender, DiffMode,
+    DirtyWorkspacePolicy, ErrorCategory, FileDiffStat, MessageExt, OutputKind, OutputSink,
+    Provider, Template, ToolCallingMode,
+};
+use crate::forge::Forge;
+use crate::helpers::{
+    AGENTS_CONTEXT_FILE, DEFAULT_CONTEXT_LINES, Toolchain, compose_in_editor, detect_toolchains,
+    fence, generated_file_warning, get_project_context, is_path_in_workspace, read_clipboard,
+    read_prompt_file, render_file, validate_against_schema,
+};
+use crate::import;
+use crate::index::{KnowledgeStore, SemanticIndex};
+use crate::mcp::McpServer;
+use crate::providers::{DynModel, ProviderAccess, agent_for};
+use crate::tools::{
+    AgxToolCall, ArtifactStore, CommandSettings, ConfirmationPolicy, IssueViewTool, PrCommentTool,
+    PrCreateTool, ReadArtifactTool, SearchKnowledgeTool, ToolCallOutcome, ToolError, ToolRegistry,
+    Workspace,
+};
+use crate::worktree::uncommitted_changes;
 use anyhow::Context;
+use audit::{AuditEvent, AuditLog, FileHashes, ToolAuditLog, ToolExecution, hash_file};
 use chrono::{Local, Utc};
 use colored::Colorize;
+use compose::EditorRequest;
+use connectivity::{
+    ApiEndpoint, OFFLINE_AFTER_FAILURES, PROBE_INTERVAL, is_connection_error,
+    is_context_length_exceeded_error, is_rate_limit_error, is_streaming_tool_calls_unsupported,
+};
+use corrections::{
+    Correction, CorrectionLog, SUGGEST_LEARN_AFTER, group_corrections, learn_prompt, times_given,
+};
 use futures::StreamExt;
-use hitl::Approvals;
+use guardrails::{GuardrailViolation, Guardrails};
+use history::{MAX_LISTED_SESSIONS, SavedHistory, find_saved_session, saved_sessions};
+use hitl::{Approvals, paths_outside_workspace};
+use interrupts::{Interrupt, Interrupts};
+use latency::{FirstTokenWait, WaitNotice};
+use overrides::OutputOverrides;
+use queue::{PromptQueue, RATE_LIMIT_COOLDOWN};
 use rig::OneOrMany;
 use rig::agent::Agent;
-use rig::completion::{Completion, CompletionModel, GetTokenUsage};
+use rig::completion::{Completion, CompletionModel, CompletionRequestBuilder, GetTokenUsage};
 use rig::message::{
-    AssistantContent, Message, ToolCall, ToolResult, ToolResultContent, UserContent,
+    AssistantContent, Message, Reasoning, ToolCall, ToolResult, ToolResultContent, UserContent,
 };
 use rig::streaming::StreamedAssistantContent;
+use rig::tool::Tool;
 use rustyline::DefaultEditor;
+use shadow::{SHADOW_REPO_DIR, ShadowRepo};
+use stats::{Interruption, Rejection, SessionStats};
 use std::borrow::Cow;
-use std::path::PathBuf;
+use std::collections::{HashMap, VecDeque};
+use std::path::{Component, PathBuf};
 use std::str::FromStr;
-use tracing::instrument;
+use std::sync::Arc;
+use title::{TerminalTitle, TitleStatus};
+use tokio::time::Instant;
+use tracing::{debug, info, instrument, warn};
+use transcript::TranscriptWriter;
+use webhooks::{Notification, Webhooks};
 
 const BANNER: &str = include_str!("assets/logo.txt");
 const COMMANDS: &str = include_str!("assets/commands.txt");
 const SYSTEM_PROMPT: &str = include_str!("assets/system-prompt.txt");
+const MAX_JSON_RESPONSE_ATTEMPTS: usize = 3;
+const MESSAGES_TO_KEEP_ON_COMPACTION: usize = 6;
+const TOOL_FAILURE_REMINDER_THRESHOLD: usize = 3;
+
+const MAX_TOKENS_REACHED: &str = "max tokens reached";
+const STOPPED_BY_USER: &str = "stopped by user";
+const CONTINUE_PROMPT: &str = "Your previous response was cut off. Continue exactly from where it stopped, without repeating anything.";
+const NO_SEMANTIC_INDEX: &str = r#"there's no semantic index; run "agx index" to build one"#;
+const NO_CHANGE_TRACKING: &str = "changes aren't being tracked in this session";
+
+struct LlmResponse {
+    text: String,
+    reasoning: Vec<Reasoning>,
+    tool_calls: Vec<ToolCall>,
+    /// Set when the response was cut off midway, with the reason.
+    truncated: Option<String>,
+    /// Set when the user cancelled the response (via Ctrl+C).
+    cancelled: bool,
+}
+
+impl LlmResponse {
+    /// A response that was cancelled before anything was received.
+    fn cancelled() -> Self {
+        Self {
+            text: String::new(),
+            reasoning: vec![],
+            tool_calls: vec![],
+            truncated: None,
+            cancelled: true,
+        }
+    }
+
+    /// A response the user stopped waiting for before anything was received.
+    fn stopped() -> Self {
+        Self {
+            text: String::new(),
+            reasoning: vec![],
+            tool_calls: vec![],
+            truncated: Some(STOPPED_BY_USER.to_string()),
+            cancelled: false,
+        }
+    }
+}
+
+/// What's done with a request that couldn't reach the provider.
+enum UnsentRequest {
+    Retry,
+    Queue,
+    Drop,
+}
+
+/// What's recorded about a mutating tool call once it's executed.
+struct ToolAudit<'a> {
+    tool_call: &'a ToolCall,
+    approval: ApprovalDecision,
+    policy: &'a ConfirmationPolicy,
+    file_before: Option<FileHashes>,
+    output: &'a str,
+    error_code: Option<&'static str>,
+}
 
 enum ToolCallConfirmation {
     Approved,
     AutoApproved,
+    PartiallyApproved(Vec<bool>),
     Rejected,
     FeedbackProvided(String),
 }
 
-pub struct Session<M>
-where
-    M: CompletionModel + 'static,
-{
+impl ToolCallConfirmation {
+    fn decision(&self) -> ApprovalDecision {
+        match self {
+            Self::Approved => ApprovalDecision::Approved,
+            Self::AutoApproved => ApprovalDecision::AlwaysApproved,
+            Self::PartiallyApproved(_) => ApprovalDecision::PartiallyApproved,
+            Self::Rejected => ApprovalDecision::Rejected,
+            Self::FeedbackProvided(_) => ApprovalDecision::FeedbackProvided,
+        }
+    }
+}
+
+pub struct Session {
     config: Config,
-    agent: Agent<M>,
+    /// Directory user level config is read from, when config is reloaded.
+    user_config_dir: Option<PathBuf>,
+    agent: Agent<DynModel>,
     project_context: Option<String>,
+    package_context: Option<String>,
+    /// Uncommitted changes found in the workspace as the session started (as per `git status`);
+    /// only kept until the user acknowledges them, when config asks for that.
+    dirty_workspace: Option<String>,
+    /// Whether the model is limited to tools that only read, since another session is running
+    /// in the project.
+    read_only: bool,
+    /// Toolchains detected in the workspace, along with the commands to build, test, and lint it.
+    toolchains: Vec<Toolchain>,
+    workspace: Arc<Workspace>,
     editor: DefaultEditor,
+    editor_request: EditorRequest,
     approvals: Approvals,
     project_dir: PathBuf,
     project_log_dir: PathBuf,
     chats_dir: PathBuf,
     provider: Provider,
+    /// Needed to set up other models of the provider, to compare the session's model with.
+    provider_access: Option<ProviderAccess>,
     model_name: String,
     tokens_in_context: u64,
+    streaming: bool,
+    text_tool_calls: bool,
+    response_schema: Option<serde_json::Value>,
     debug_tx: Option<DebugEventSender>,
+    tools: ToolRegistry,
+    /// Where oversized tool outputs are saved; the model only gets excerpts of them.
+    artifacts: Arc<ArtifactStore>,
+    semantic_index: Option<Arc<SemanticIndex>>,
+    knowledge: Arc<KnowledgeStore>,
     chat_history: Vec<Message>,
+    stats: SessionStats,
+    response_truncated: bool,
+    output_overrides: OutputOverrides,
+    approval_mode: ApprovalMode,
+    /// The approval mode to return to once auto-approval (via /auto) is turned off.
+    approval_mode_before_auto: Option<ApprovalMode>,
+    task_instructions: Option<String>,
+    initial_prompt: Option<String>,
+    /// Clipboard contents (fenced) to be sent along with the next prompt.
+    pasted: Option<String>,
+    guardrails: Option<Guardrails>,
+    guardrail_violation: Option<GuardrailViolation>,
+    audit_log: Option<AuditLog>,
+    /// Every mutating tool call executed in the project is recorded here.
+    tool_audit_log: ToolAuditLog,
+    /// Feedback the user rejects tool calls with is recorded here, for /learn.
+    corrections: CorrectionLog,
+    /// Set once /learn has been suggested, so that it isn't suggested again.
+    learn_suggested: bool,
+    /// Tracks the changes made by tools, step by step; unset if tracking couldn't be started
+    /// (eg. if git isn't installed).
+    shadow: Option<ShadowRepo>,
+    /// Steps the user undid, to be mentioned along with the next prompt.
+    undo_reminder: Option<String>,
+    turn_error: Option<String>,
+    webhooks: Webhooks,
+    transcript: Option<TranscriptWriter>,
+    /// Outputs of tool calls recorded in a transcript, keyed by the tool call's ID; set when
+    /// replaying a transcript, in which case tools aren't executed.
+    replayed_tool_results: Option<HashMap<String, VecDeque<String>>>,
+    /// The number of prompts sent in the conversation so far.
+    turns: usize,
+    bookmarks: Vec<Bookmark>,
+    /// A conversation imported from another CLI, to be continued once the session starts.
+    imported: Vec<TranscriptEvent>,
+    /// A saved session whose conversation is to be resumed once the session starts.
+    resumed: Option<String>,
     print_newline_before_prompt: bool,
+    /// Whether the user is around to answer questions (as opposed to a task running on its own).
+    interactive: bool,
+    /// Requests in a row that failed because the provider couldn't be reached.
+    connection_failures: usize,
+    /// Prompts held back while the provider can't be used.
+    queued: PromptQueue,
+    /// Set while queued prompts are being sent.
+    sending_queued: bool,
+    /// Set when the last turn's prompt was queued, rather than sent.
+    turn_queued: bool,
+    title: TerminalTitle,
+    confirmations: Arc<dyn ConfirmationProvider>,
+    output: Arc<dyn OutputSink>,
 }
 
-impl<M> Session<M>
-where
-    M: CompletionModel + 'static,
-{
-    #[allow(clippy::too_many_arguments)]
-    pub fn new(
-        config: Config,
+impl Session {
+    /// Starts building a session; see [`SessionBuilder`] for what can be customised.
+    pub fn builder<M>(
         agent: Agent<M>,
-        project_context: Option<String>,
-        project_dir: PathBuf,
-        project_log_dir: PathBuf,
         provider: Provider,
         model_name: impl Into<String>,
-        debug_tx: Option<DebugEventSender>,
-    ) -> anyhow::Result<Self> {
-        let chats_dir = project_log_dir
-            .join("chats")
-            .join(Local::now().format("%Y-%m-%d-%H-%M-%S").to_string());
-
-        let editor = DefaultEditor::new()?;
-        let approvals = Approvals {
-            fs_changes: false,
-            approved_commands: config.approved_commands.clone(),
-        };
-
-        Ok(Self {
-            config,
-            agent,
-            project_context,
-            editor,
-            approvals,
-            project_dir,
-            project_log_dir,
-            chats_dir,
-            provider,
-            model_name: model_name.into(),
-            tokens_in_context: 0,
-            debug_tx,
-            chat_history: Vec::new(),
-            print_newline_before_prompt: false,
-        })
+        project_dir: PathBuf,
+        project_log_dir: PathBuf,
+    ) -> SessionBuilder
+    where
+        M: CompletionModel + 'static,
+    {
+        SessionBuilder::new(agent, provider, model_name, project_dir, project_log_dir)
     }
 
-    pub async fn run(&mut self) -> anyhow::Result<()> {
+    pub fn register_forge_tools(&mut self, forge: Arc<dyn Forge>) {
+        self.tools.register(IssueViewTool::new(Arc::clone(&forge)));
+        self.tools.register(PrCreateTool::new(Arc::clone(&forge)));
+        self.tools.register(PrCommentTool::new(forge));
+    }
+
+    /// Sends a prompt as soon as the session starts.
+    pub fn set_initial_prompt(&mut self, prompt: impl Into<String>) {
+        self.initial_prompt = Some(prompt.into());
+    }
+
+    /// Continues a conversation imported from another CLI (see [`crate::import`]); it's recorded
+    /// at the start of this session's transcript.
+    pub fn set_imported_conversation(&mut self, events: Vec<TranscriptEvent>) {
+        self.imported = events;
+    }
+
+    /// Resumes the conversation of a saved session (see [`history`]) as soon as the session
+    /// starts; `name` can be "latest".
+    pub fn set_resumed_session(&mut self, name: impl Into<String>) {
+        self.resumed = Some(name.into());
+    }
+
+    /// Sets the session up as per a template; the template's prompt (if any) is sent as soon as
+    /// the session starts.
+    pub fn apply_template(&mut self, template: &Template, args: &[String]) -> anyhow::Result<()> {
+        self.initial_prompt = template.render_prompt(args)?;
+        self.task_instructions = template.system.clone();
+
+        if let Some(tools) = &template.tools {
+            let unknown = self.tools.restrict(tools);
+            if !unknown.is_empty() {
+                self.emit_line(
+                    OutputKind::Warning,
+                    format!(
+                        "template allows tools that aren't currently available: {}",
+                        unknown.join(", ")
+                    ),
+                );
+            }
+        }
+
+        self.approval_mode = template.approvals;
+        if self.approval_mode == ApprovalMode::Strict {
+            self.approvals = Approvals::default();
+        }
+
+        Ok(())
+    }
+
+    async fn prepare(&mut self) -> anyhow::Result<()> {
         tokio::fs::create_dir_all(&self.chats_dir)
             .await
             .with_context(|| {
@@ -104,16 +345,252 @@ where
                     &self.chats_dir,
                 )
             })?;
+
+        if let Some(package) = self.config.workspace.clone() {
+            self.switch_workspace(&package)
+                .await
+                .context("couldn't switch to the workspace set in agx's local config")?;
+        } else {
+            // switching the workspace detects its toolchains as well
+            self.refresh_toolchains().await;
+        }
+
+        self.start_mcp_servers().await;
+        self.start_transcript();
+        self.start_change_tracking().await;
+        self.check_dirty_workspace().await;
+        self.continue_imported_conversation();
+        if let Some(name) = self.resumed.take() {
+            self.resume(&name).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Starts the MCP servers in config, and offers their tools to the model; servers that can't
+    /// be started (or whose tools can't be listed) are reported, and left out. Read-only sessions
+    /// don't start any, since they're limited to agx's own tools that only read.
+    async fn start_mcp_servers(&mut self) {
+        if self.read_only || self.config.mcp_servers.is_empty() {
+            return;
+        }
+
+        let logs_dir = self.chats_dir.join("mcp");
+        let dir = self.workspace.root().to_path_buf();
+        let started = futures::future::join_all(self.config.mcp_servers.iter().map(
+            |(name, server_config)| {
+                let log = logs_dir.join(format!("{name}.log"));
+                let dir = &dir;
+                async move {
+                    let result = async {
+                        let server = McpServer::start(name, server_config, dir, &log).await?;
+                        let tools = server.tools().await?;
+                        anyhow::Ok((server, tools))
+                    }
+                    .await;
+                    (name.clone(), log, result)
+                }
+            },
+        ))
+        .await;
+
+        for (name, log, result) in started {
+            let (server, tools) = match result {
+                Ok(started) => started,
+                Err(e) => {
+                    self.report_error(
+                        ErrorCategory::Session,
+                        e.context(format!(
+                            "couldn't start the {name} MCP server, so its tools won't be available; its logs are in {}",
+                            log.to_string_lossy()
+                        )),
+                    );
+                    continue;
+                }
+            };
+
+            let server = Arc::new(server);
+            let registered = tools
+                .into_iter()
+                .filter_map(|tool| self.tools.register_mcp(Arc::clone(&server), tool))
+                .count();
+            info!(server = name, tools = registered, "started MCP server");
+            self.emit_line(
+                OutputKind::Info,
+                format!("started the {name} MCP server: {registered} tool(s) available"),
+            );
+        }
+    }
+
+    fn continue_imported_conversation(&mut self) {
+        let events = std::mem::take(&mut self.imported);
+        let Some(TranscriptEvent::Started {
+            provider, model, ..
+        }) = events.first()
+        else {
+            return;
+        };
+
+        let prompts = events
+            .iter()
+            .filter(|e| matches!(e, TranscriptEvent::Prompt { .. }))
+            .count();
+        self.emit_line(
+            OutputKind::Info,
+            format!(
+                "continuing a conversation imported from {provider} ({model}): {prompts} prompts"
+            ),
+        );
+        self.turns = prompts;
+        self.chat_history = import::chat_history(&events);
+        for event in events.into_iter().skip(1) {
+            self.record_transcript(event);
+        }
+    }
+
+    /// Records the session's transcript in its chats directory; replays aren't recorded.
+    fn start_transcript(&mut self) {
+        if self.replayed_tool_results.is_some() {
+            return;
+        }
+
+        let path = self.chats_dir.join("transcript.jsonl");
+        match TranscriptWriter::create(&path) {
+            Ok(transcript) => {
+                transcript.record(&TranscriptEvent::Started {
+                    provider: self.provider.to_string(),
+                    model: self.model_name.clone(),
+                    text_tool_calls: self.text_tool_calls,
+                    agx_version: Some(env!("CARGO_PKG_VERSION").to_string()),
+                    config_digest: Some(self.config.digest()),
+                });
+                self.transcript = Some(transcript);
+            }
+            Err(e) => {
+                self.transcript = None;
+                self.report_error(
+                    ErrorCategory::Session,
+                    e.context("couldn't create transcript; this session won't be recorded"),
+                );
+            }
+        }
+    }
+
+    /// Replays a recorded session: the transcript's prompts are sent to the model, which
+    /// responds with the recorded responses, and tool calls get their recorded results instead
+    /// of being executed. Used to reproduce rendering and loop bugs deterministically.
+    pub async fn replay(&mut self, transcript: Transcript) -> anyhow::Result<()> {
+        self.replayed_tool_results = Some(transcript.tool_results);
+        self.text_tool_calls = transcript.text_tool_calls;
+        self.streaming = false;
+        self.approval_mode = ApprovalMode::Permissive;
+        self.prepare().await?;
+
+        for prompt in &transcript.prompts {
+            self.emit_line(OutputKind::Prompt, format!("\n> {prompt}"));
+            self.handle_prompt(prompt).await;
+            if let Some(tx) = &self.debug_tx {
+                tx.send(DebugEvent::turn_complete(&self.chat_history));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Runs a single task without human oversight, under the guardrails from agx's config.
+    /// Everything that happens is recorded in an audit log. Returns an error if a guardrail
+    /// trips, or if the task couldn't be completed.
+    pub async fn run_task(&mut self, task: &str) -> anyhow::Result<()> {
+        let result = self.execute_task(task).await;
+        self.notify_session_ended(&result).await;
+
+        result
+    }
+
+    async fn execute_task(&mut self, task: &str) -> anyhow::Result<()> {
+        self.prepare().await?;
+        if self.dirty_workspace.is_some() {
+            anyhow::bail!(
+                "the workspace has uncommitted changes, which need to be acknowledged as per config; commit or stash them, or run in a worktree (via --worktree)"
+            );
+        }
+
+        let autopilot = self.config.autopilot.clone().unwrap_or_default();
+        let pricing = self
+            .config
+            .models
+            .get(&self.model_name)
+            .and_then(|m| m.pricing.as_ref());
+        self.guardrails = Some(Guardrails::new(&autopilot, pricing)?);
+        if let Some(tools) = &autopilot.tools {
+            self.tools.restrict(tools);
+        }
+        self.approval_mode = ApprovalMode::Permissive;
+
+        let audit_log_path = self.project_log_dir.join("autopilot").join(format!(
+            "{}.jsonl",
+            Local::now().format("%Y-%m-%d-%H-%M-%S")
+        ));
+        let audit_log = AuditLog::create(&audit_log_path).with_context(|| {
+            format!(
+                "couldn't create audit log at {}",
+                audit_log_path.to_string_lossy()
+            )
+        })?;
+        self.emit_line(
+            OutputKind::Info,
+            format!("audit log: {}", audit_log.path().to_string_lossy()),
+        );
+        audit_log.record(AuditEvent::Started {
+            task,
+            model: &self.model_name,
+            guardrails: serde_json::to_value(&autopilot).unwrap_or_default(),
+        });
+        self.audit_log = Some(audit_log);
+
+        self.handle_prompt(task).await;
+
+        let error = match (self.guardrail_violation.take(), self.turn_error.take()) {
+            (Some(violation), _) => Some(format!("guardrail tripped: {violation}")),
+            (None, Some(error)) => Some(error),
+            (None, None) => None,
+        };
+        self.record_audit(AuditEvent::Finished {
+            success: error.is_none(),
+            error: error.clone(),
+        });
+
+        match error {
+            Some(e) => anyhow::bail!(e),
+            None => Ok(()),
+        }
+    }
+
+    pub async fn run(&mut self) -> anyhow::Result<()> {
+        let result = self.chat().await;
+        self.notify_session_ended(&result).await;
+
+        result
+    }
+
+    async fn chat(&mut self) -> anyhow::Result<()> {
+        self.prepare().await?;
+        self.interactive = true;
+        self.title.set_enabled(self.config.terminal_title());
+        self.check_connectivity().await;
         let history_file_path = self.project_log_dir.join("history.txt");
 
         let _ = self.editor.load_history(&history_file_path);
 
-        print!(
-            "
-{}
-",
-            BANNER.purple(),
-        );
+        self.emit(OutputKind::Banner, format!("\n{BANNER}\n"));
+
+        if let Some(prompt) = self.initial_prompt.take() {
+            self.emit_line(OutputKind::Prompt, format!("> {prompt}\n"));
+            self.handle_prompt(&prompt).await;
+            if let Some(tx) = &self.debug_tx {
+                tx.send(DebugEvent::turn_complete(&self.chat_history));
+            }
+        }
 
         let prompt_marker = "> ".bright_blue().to_string();
         loop {
@@ -122,11 +599,23 @@ where
             } else {
                 None
             };
+            let auto_approval = if self.skips_confirmation() {
+                Some("  [auto-approve]".red())
+            } else {
+                None
+            };
+            let queue_info = if self.queued.is_empty() {
+                None
+            } else {
+                Some(format!("  [{} queued]", self.queued.len()).yellow())
+            };
             let metadata = format!(
-                "{}  {}{}",
+                "{}  {}{}{}{}",
                 format!("[{}/{}]", &self.provider, &self.model_name).yellow(),
-                self.project_dir.to_string_lossy().blue(),
+                self.workspace.dir().to_string_lossy().blue(),
                 token_info.unwrap_or_default(),
+                auto_approval.unwrap_or_default(),
+                queue_info.unwrap_or_default(),
             );
 
             let prefix = if self.print_newline_before_prompt {
@@ -135,27 +624,38 @@ where
                 self.print_newline_before_prompt = true;
                 ""
             };
-            println!("{}{}", prefix, metadata);
-            let user_input = self
+            self.emit_line(OutputKind::Status, format!("{prefix}{metadata}"));
+            let mut user_input = self
                 .editor
                 .readline(&prompt_marker)
                 .context("couldn't read input")?;
+            if self.editor_request.take() {
+                user_input = format!("/editor {user_input}");
+            }
 
             match user_input.trim() {
-                "" => {}
+                "" => self.send_queued().await,
                 "clear" => {
                     _ = self.editor.clear_screen();
                     self.print_newline_before_prompt = false;
                     continue;
                 }
                 "/help" => {
-                    print!("{}", COMMANDS.green());
+                    self.emit(OutputKind::Success, COMMANDS);
                     continue;
                 }
                 "/new" => {
                     self.chat_history.clear();
+                    self.knowledge.clear();
+                    self.tools.unregister(SearchKnowledgeTool::NAME);
+                    self.stats = SessionStats::default();
+                    self.turns = 0;
+                    self.bookmarks.clear();
+                    self.pasted = None;
+                    self.response_truncated = false;
                     self.tokens_in_context = 0;
                     self.print_newline_before_prompt = false;
+                    self.title.clear_conversation();
                     self.chats_dir = self
                         .project_log_dir
                         .join("chats")
@@ -170,6 +670,10 @@ where
                             )
                         })?;
 
+                    self.start_transcript();
+                    self.undo_reminder = None;
+                    self.start_change_tracking().await;
+
                     if let Some(tx) = &self.debug_tx {
                         tx.send(DebugEvent::new_session());
                     }
@@ -178,66 +682,523 @@ where
                     continue;
                 }
                 "/approvals" => {
-                    print!("{}", self.approvals.to_string().green());
+                    self.emit(OutputKind::Success, self.approvals.to_string());
                     continue;
                 }
-                "/quit" | "/exit" | "bye" | ":q" => {
-                    break;
+                "/reload" => {
+                    match self.reload().await {
+                        Ok(()) => self.emit_line(
+                            OutputKind::Success,
+                            "reloaded config and context files; chat history is unchanged",
+                        ),
+                        Err(e) => self.report_error(ErrorCategory::Session, e),
+                    }
+                    continue;
                 }
-                p => {
+                "/stats" => {
+                    self.emit(OutputKind::Success, self.stats.to_string());
+                    continue;
+                }
+                p if p == "/diffmode" || p.starts_with("/diffmode ") => {
+                    let mode = match p.trim_start_matches("/diffmode").trim() {
+                        "" => match self.workspace.diff_options().mode {
+                            DiffMode::Line => DiffMode::Word,
+                            DiffMode::Word => DiffMode::Line,
+                        },
+                        "line" => DiffMode::Line,
+                        "word" => DiffMode::Word,
+                        _ => {
+                            self.emit_line(
+                                OutputKind::Error,
+                                "error: usage: /diffmode [line|word]",
+                            );
+                            continue;
+                        }
+                    };
+                    self.workspace.set_diff_mode(mode);
+                    self.emit_line(
+                        OutputKind::Success,
+                        format!("changes to files will be shown as {mode} diffs"),
+                    );
+                    continue;
+                }
+                p if p == "/bookmark" || p.starts_with("/bookmark ") => {
+                    let note = p.trim_start_matches("/bookmark").trim();
+                    let bookmark = Bookmark {
+                        turn: self.turns,
+                        note: (!note.is_empty()).then(|| note.to_string()),
+                    };
+                    self.record_transcript(TranscriptEvent::Bookmark(bookmark.clone()));
+                    self.emit_line(OutputKind::Success, format!("bookmarked {bookmark}"));
+                    self.bookmarks.push(bookmark);
+                    continue;
+                }
+                "/bookmarks" => {
+                    if self.bookmarks.is_empty() {
+                        self.emit_line(
+                            OutputKind::Warning,
+                            "there are no bookmarks in this session",
+                        );
+                    } else {
+                        let bookmarks = self
+                            .bookmarks
+                            .iter()
+                            .map(|b| format!("- {b}"))
+                            .collect::<Vec<_>>()
+                            .join("\n");
+                        self.emit_line(OutputKind::Success, bookmarks);
+                    }
+                    continue;
+                }
+                "/changes" => {
+                    self.show_changes().await;
+                    continue;
+                }
+                p if p == "/diff" || p.starts_with("/diff ") => {
+                    match parse_step(p.trim_start_matches("/diff")) {
+                        Ok(step) => self.show_diff(step).await,
+                        Err(()) => self.emit_line(OutputKind::Error, "error: usage: /diff [step]"),
+                    }
+                    continue;
+                }
+                p if p == "/undo" || p.starts_with("/undo ") => {
+                    match parse_step(p.trim_start_matches("/undo")) {
+                        Ok(step) => self.undo(step).await,
+                        Err(()) => self.emit_line(OutputKind::Error, "error: usage: /undo [step]"),
+                    }
+                    continue;
+                }
+                p if p.starts_with("/view ") => {
+                    let mut args = p.trim_start_matches("/view ").split_whitespace();
+                    match (args.next(), args.next(), args.next()) {
+                        (Some(path), range, None) => self.view_file(path, range).await,
+                        _ => self
+                            .emit_line(OutputKind::Error, "error: usage: /view <path> [start:end]"),
+                    }
+                    continue;
+                }
+                p if p == "/auto" || p.starts_with("/auto ") => {
+                    let on = match p.trim_start_matches("/auto").trim() {
+                        "" => self.approval_mode != ApprovalMode::Permissive,
+                        "on" => true,
+                        "off" => false,
+                        _ => {
+                            self.emit_line(OutputKind::Error, "error: usage: /auto [on|off]");
+                            continue;
+                        }
+                    };
+                    self.set_auto_approval(on);
+                    continue;
+                }
+                "/workspace" => {
+                    let package = self
+                        .workspace
+                        .package()
+                        .map(|p| p.to_string_lossy().to_string())
+                        .unwrap_or("(project root)".to_string());
+                    self.emit_line(OutputKind::Success, format!("workspace: {package}"));
+                    continue;
+                }
+                p if p.starts_with("/workspace ") => {
+                    match self
+                        .switch_workspace(p.trim_start_matches("/workspace ").trim())
+                        .await
+                    {
+                        Ok(()) => self.emit_line(
+                            OutputKind::Success,
+                            format!(
+                                "switched workspace to {}",
+                                self.workspace.dir().to_string_lossy()
+                            ),
+                        ),
+                        Err(e) => self.report_error(ErrorCategory::Session, e),
+                    }
+                    continue;
+                }
+                "/index" => {
+                    match &self.semantic_index {
+                        Some(index) => {
+                            self.emit(OutputKind::Success, index.status().await.to_string())
+                        }
+                        None => self.emit_line(OutputKind::Warning, NO_SEMANTIC_INDEX),
+                    }
+                    continue;
+                }
+                "/index refresh" => {
+                    match &self.semantic_index {
+                        Some(index) => match index.refresh().await {
+                            Ok(summary) => self.emit_line(OutputKind::Success, summary.to_string()),
+                            Err(e) => self.report_error(ErrorCategory::Session, e),
+                        },
+                        None => self.emit_line(OutputKind::Warning, NO_SEMANTIC_INDEX),
+                    }
+                    continue;
+                }
+                "/attach" => {
+                    let sources = self.knowledge.sources();
+                    if sources.is_empty() {
+                        self.emit_line(OutputKind::Warning, "no documents are attached");
+                    } else {
+                        self.emit_line(
+                            OutputKind::Success,
+                            format!("attached: {}", sources.join(", ")),
+                        );
+                    }
+                    continue;
+                }
+                p if p.starts_with("/attach ") => {
+                    let source = p.trim_start_matches("/attach ").trim();
+                    match self.knowledge.attach(source, &self.project_dir).await {
+                        Ok(attached) => {
+                            self.tools
+                                .register(SearchKnowledgeTool::new(Arc::clone(&self.knowledge)));
+                            self.emit_line(
+                                OutputKind::Success,
+                                format!(
+                                    "attached {} ({} bytes, {} chunks)",
+                                    attached.source, attached.bytes, attached.chunks
+                                ),
+                            );
+                        }
+                        Err(e) => self.report_error(ErrorCategory::Session, e),
+                    }
+                    continue;
+                }
+                "/paste" => {
+                    self.paste().await;
+                    continue;
+                }
+                p if p.starts_with("/paste ") => {
                     _ = self.editor.add_history_entry(p);
 
-                    self.handle_prompt(p).await;
+                    if !self.paste().await {
+                        continue;
+                    }
+                    let prompt = self.with_pasted(p.trim_start_matches("/paste ").trim());
+                    self.submit_prompt(prompt).await;
                     if let Some(tx) = &self.debug_tx {
                         tx.send(DebugEvent::turn_complete(&self.chat_history));
                     }
                 }
-            }
-        }
-
-        let _ = self.editor.save_history(&history_file_path);
+                "/set" => {
+                    self.emit(OutputKind::Success, self.output_overrides.to_string());
+                    continue;
+                }
+                p if p.starts_with("/set ") => {
+                    match self
+                        .output_overrides
+                        .set(p.trim_start_matches("/set ").trim())
+                    {
+                        Ok(()) => {
+                            self.workspace.set_diff_context_lines(
+                                self.output_overrides
+                                    .diff_context
+                                    .unwrap_or(DEFAULT_CONTEXT_LINES),
+                            );
+                            self.emit(OutputKind::Success, self.output_overrides.to_string())
+                        }
+                        Err(e) => self.report_error(ErrorCategory::Session, e),
+                    }
+                    continue;
+                }
+                "/continue" => {
+                    if !self.response_truncated {
+                        self.emit_line(
+                            OutputKind::Warning,
+                            "there's no cut off response to continue",
+                        );
+                        continue;
+                    }
 
-        Ok(())
-    }
+                    let prompt_index = self.chat_history.len();
+                    self.handle_prompt(CONTINUE_PROMPT).await;
+                    continuation::stitch_continuation(&mut self.chat_history, prompt_index);
+                    if let Some(tx) = &self.debug_tx {
+                        tx.send(DebugEvent::turn_complete(&self.chat_history));
+                    }
+                }
+                "/quit" | "/exit" | "bye" | ":q" => {
+                    break;
+                }
+                p if p.starts_with("/load-prompt ") => {
+                    _ = self.editor.add_history_entry(p);
 
-    #[instrument(skip(self))]
-    async fn handle_prompt(&mut self, prompt: &str) {
-        let mut prompt = Message::user(prompt);
+                    let path = p.trim_start_matches("/load-prompt ").trim();
+                    let prompt = match read_prompt_file(self.project_dir.join(path)).await {
+                        Ok(prompt) => prompt,
+                        Err(e) => {
+                            self.report_error(ErrorCategory::Session, e);
+                            continue;
+                        }
+                    };
 
-        loop {
-            let (response_text, tool_calls) = tokio::select! {
-                Ok(_) = tokio::signal::ctrl_c() => {
-                    println!("{}", "\ninterrupted (prompt discarded)".red());
+                    self.emit_line(
+                        OutputKind::Prompt,
+                        format!(
+                            "> loaded prompt from {path} ({} lines)\n",
+                            prompt.lines().count()
+                        ),
+                    );
+                    let prompt = self.with_pasted(&prompt);
+                    self.submit_prompt(prompt).await;
                     if let Some(tx) = &self.debug_tx {
-                        tx.send(DebugEvent::interrupted());
+                        tx.send(DebugEvent::turn_complete(&self.chat_history));
                     }
-                    return;
                 }
-                result = self.stream_llm_response(prompt.clone()) => {
-                    match result {
-                        Ok(r) => {
-                            self.chat_history.push(prompt);
-                            r
-                        },
+                p if p == "/editor" || p.starts_with("/editor ") => {
+                    let draft = p.trim_start_matches("/editor").trim();
+                    let prompt = match compose_in_editor(draft).await {
+                        Ok(prompt) => prompt.trim().to_string(),
                         Err(e) => {
-                            print_error(e);
-                            break;
+                            self.report_error(ErrorCategory::Session, e);
+                            continue;
                         }
+                    };
+                    if prompt.is_empty() {
+                        self.emit_line(OutputKind::Warning, "prompt is empty; nothing was sent");
+                        continue;
+                    }
+
+                    self.emit_line(
+                        OutputKind::Prompt,
+                        format!(
+                            "> composed prompt in editor ({} lines)\n",
+                            prompt.lines().count()
+                        ),
+                    );
+                    let prompt = self.with_pasted(&prompt);
+                    self.submit_prompt(prompt).await;
+                    if let Some(tx) = &self.debug_tx {
+                        tx.send(DebugEvent::turn_complete(&self.chat_history));
                     }
                 }
-            };
+                p if p == "/compare" || p.starts_with("/compare ") => {
+                    _ = self.editor.add_history_entry(p);
 
-            let mut assistant_contents = vec![];
+                    self.compare_models(p.trim_start_matches("/compare")).await;
+                }
+                p if p.starts_with("/json ") => {
+                    _ = self.editor.add_history_entry(p);
 
-            if !response_text.is_empty() {
-                assistant_contents.push(AssistantContent::text(&response_text));
-            }
+                    self.handle_json_prompt(p.trim_start_matches("/json ").trim())
+                        .await;
+                    if let Some(tx) = &self.debug_tx {
+                        tx.send(DebugEvent::turn_complete(&self.chat_history));
+                    }
+                }
+                "/queue" => {
+                    self.emit(OutputKind::Success, self.queued.to_string());
+                    continue;
+                }
+                "/learn" => {
+                    self.learn().await;
+                }
+                "/resume" => {
+                    self.list_saved_sessions().await;
+                    continue;
+                }
+                p if p.starts_with("/resume ") => {
+                    let name = p.trim_start_matches("/resume").trim();
+                    if let Err(e) = self.resume(name).await {
+                        self.report_error(ErrorCategory::Session, e);
+                    }
+                    continue;
+                }
+                "/queue clear" => {
+                    let dropped = self.queued.clear();
+                    self.emit_line(
+                        OutputKind::Success,
+                        format!("dropped {dropped} queued prompt(s)"),
+                    );
+                    continue;
+                }
+                p => {
+                    _ = self.editor.add_history_entry(p);
 
-            for tc in &tool_calls {
-                assistant_contents.push(AssistantContent::ToolCall(tc.clone()));
+                    let prompt = self.with_pasted(p);
+                    self.submit_prompt(prompt).await;
+                    if let Some(tx) = &self.debug_tx {
+                        tx.send(DebugEvent::turn_complete(&self.chat_history));
+                    }
+                }
             }
+        }
 
-            if !assistant_contents.is_empty() {
+        if !self.read_only {
+            let _ = self.editor.save_history(&history_file_path);
+        }
+
+        Ok(())
+    }
+
+    /// Reads the clipboard, and holds on to its contents until the next prompt is sent;
+    /// returns whether anything was pasted.
+    async fn paste(&mut self) -> bool {
+        let text = match read_clipboard().await {
+            Ok(t) => t,
+            Err(e) => {
+                self.report_error(ErrorCategory::Session, e);
+                return false;
+            }
+        };
+
+        if text.trim().is_empty() {
+            self.emit_line(OutputKind::Warning, "clipboard is empty");
+            return false;
+        }
+
+        let lines = text.lines().count();
+        let fenced = fence(&text);
+        self.pasted = Some(match self.pasted.take() {
+            Some(earlier) => format!("{earlier}\n\n{fenced}"),
+            None => fenced,
+        });
+        self.emit_line(
+            OutputKind::Success,
+            format!("pasted {lines} line(s); they'll be sent along with the next prompt"),
+        );
+
+        true
+    }
+
+    fn with_pasted(&mut self, prompt: &str) -> String {
+        match self.pasted.take() {
+            Some(pasted) => format!("{prompt}\n\n{pasted}"),
+            None => prompt.to_string(),
+        }
+    }
+
+    #[instrument(skip(self))]
+    async fn handle_prompt(&mut self, prompt: &str) {
+        let steps_before = self.shadow.as_ref().map(|s| s.steps().len());
+        self.title.name_conversation(prompt);
+        self.run_turn(prompt).await;
+        self.save_history().await;
+        self.title.set_status(TitleStatus::Idle);
+        if let Some(steps_before) = steps_before {
+            self.summarize_turn_changes(steps_before).await;
+        }
+    }
+
+    async fn run_turn(&mut self, prompt: &str) {
+        self.response_truncated = false;
+        self.turn_error = None;
+        self.turn_queued = false;
+        let prompt_text = prompt;
+        let turn_start = self.chat_history.len();
+        let reminder = reminders::system_reminder(
+            &self.approvals,
+            self.skips_confirmation(),
+            &self.workspace,
+            self.guardrails.as_ref(),
+        );
+        self.turns += 1;
+        self.stats.record_turn();
+        self.record_transcript(TranscriptEvent::Prompt {
+            text: prompt.to_string(),
+        });
+        let mut content = vec![UserContent::text(reminder)];
+        if let Some(undone) = self.undo_reminder.take() {
+            content.push(UserContent::text(undone));
+        }
+        content.push(UserContent::text(prompt));
+        let mut prompt = Message::User {
+            content: OneOrMany::many(content).expect("content should be non-empty"),
+        };
+
+        loop {
+            if let Some(guardrails) = &mut self.guardrails
+                && let Err(violation) = guardrails.start_iteration()
+            {
+                self.trip_guardrail(violation);
+                return;
+            }
+
+            self.title.set_status(TitleStatus::Streaming);
+            let (input_tokens_before, output_tokens_before) = self.stats.token_usage();
+            let LlmResponse {
+                text: response_text,
+                reasoning,
+                tool_calls,
+                truncated,
+                cancelled,
+            } = match self.stream_llm_response(prompt.clone()).await {
+                Ok(r) => {
+                    self.connection_failures = 0;
+                    self.chat_history.push(prompt);
+                    r
+                }
+                Err(e)
+                    if self.interactive && (is_connection_error(&e) || is_rate_limit_error(&e)) =>
+                {
+                    // only the turn's first request can be queued; later ones depend on what
+                    // the turn did since
+                    let queueable = self.chat_history.len() == turn_start;
+                    let unsent = if is_rate_limit_error(&e) {
+                        self.handle_rate_limit(e, queueable)
+                    } else {
+                        self.handle_connection_error(&e, queueable).await
+                    };
+                    match unsent {
+                        UnsentRequest::Retry => continue,
+                        UnsentRequest::Queue => {
+                            self.queue_prompt(prompt_text.to_string());
+                            self.turn_queued = true;
+                        }
+                        UnsentRequest::Drop => {
+                            self.turn_error = Some("the request couldn't be sent".to_string());
+                        }
+                    }
+                    break;
+                }
+                Err(e) => {
+                    self.turn_error = Some(format!("{e:#}"));
+                    self.report_error(ErrorCategory::Provider, e);
+                    break;
+                }
+            };
+
+            if self.transcript.is_some() {
+                let (input_tokens, output_tokens) = self.stats.token_usage();
+                self.record_transcript(TranscriptEvent::Response(RecordedResponse {
+                    text: response_text.clone(),
+                    reasoning: reasoning.clone(),
+                    tool_calls: if self.text_tool_calls {
+                        vec![]
+                    } else {
+                        tool_calls.clone()
+                    },
+                    input_tokens: input_tokens - input_tokens_before,
+                    output_tokens: output_tokens - output_tokens_before,
+                }));
+            }
+
+            let mut assistant_contents = vec![];
+
+            // signed reasoning (eg. anthropic's thinking blocks) needs to be sent back to the
+            // provider as is; other providers don't accept reasoning in history
+            for r in reasoning.into_iter().filter(|r| r.signature.is_some()) {
+                assistant_contents.push(AssistantContent::Reasoning(r));
+            }
+
+            if truncated.is_some() {
+                assistant_contents.push(AssistantContent::text(format!(
+                    "{response_text}{}",
+                    continuation::TRUNCATION_MARKER
+                )));
+            } else if !response_text.is_empty() {
+                assistant_contents.push(AssistantContent::text(&response_text));
+            }
+
+            // in text mode, tool calls are already a part of the response text; tool calls in
+            // truncated responses are not executed, so they're left out
+            if !self.text_tool_calls && truncated.is_none() {
+                for tc in &tool_calls {
+                    assistant_contents.push(AssistantContent::ToolCall(tc.clone()));
+                }
+            }
+
+            if !assistant_contents.is_empty() {
                 #[allow(clippy::expect_used)]
                 self.chat_history.push(Message::Assistant {
                     id: None,
@@ -246,121 +1207,266 @@ where
                 });
             }
 
+            let (input_tokens, output_tokens) = self.stats.token_usage();
+            self.record_audit(AuditEvent::Response {
+                text: &response_text,
+                total_input_tokens: input_tokens,
+                total_output_tokens: output_tokens,
+            });
+            if let Some(guardrails) = &self.guardrails
+                && let Err(violation) = guardrails.check_cost(input_tokens, output_tokens)
+            {
+                self.trip_guardrail(violation);
+                return;
+            }
+
+            if cancelled {
+                self.response_truncated = !response_text.is_empty();
+                self.finish_cancelled_turn(turn_start, Interruption::CancelledResponse)
+                    .await;
+                return;
+            }
+
+            if let Some(reason) = truncated {
+                if reason == STOPPED_BY_USER {
+                    self.record_interruption(Interruption::StoppedStreaming);
+                }
+                self.turn_error = Some(format!("response was cut off ({reason})"));
+                self.response_truncated = true;
+                self.emit_line(
+                    OutputKind::Warning,
+                    format!(
+                        "response was cut off ({reason}); use /continue to have the model resume"
+                    ),
+                );
+                break;
+            }
+
             if tool_calls.is_empty() {
                 break;
             }
 
             let mut tool_results = vec![];
+            let mut failure_reminders = vec![];
 
             for (i, tool_call) in tool_calls.iter().enumerate() {
                 let id = tool_call.id.clone();
                 let call_id = tool_call.call_id.clone();
 
-                let tool_call = match AgxToolCall::try_from(tool_call.clone()) {
+                self.record_audit(AuditEvent::ToolCall {
+                    id: &id,
+                    name: &tool_call.function.name,
+                    arguments: &tool_call.function.arguments,
+                });
+                if let Some(guardrails) = &self.guardrails
+                    && let Err(violation) = guardrails.check_tool(&tool_call.function.name)
+                {
+                    self.stop_for_guardrail(
+                        violation,
+                        id,
+                        call_id,
+                        &tool_calls[i + 1..],
+                        tool_results,
+                    );
+                    return;
+                }
+
+                if self.replayed_tool_results.is_some() {
+                    let repr = self
+                        .tools
+                        .resolve(tool_call.clone())
+                        .map(|t| t.repr())
+                        .unwrap_or_else(|_| tool_call.function.name.clone());
+                    self.emit_line(OutputKind::ToolCall, format!("{repr} (replayed)"));
+
+                    let output = self
+                        .replayed_tool_results
+                        .as_mut()
+                        .and_then(|replayed| replayed.get_mut(&id))
+                        .and_then(|outputs| outputs.pop_front())
+                        .unwrap_or_else(|| {
+                            ToolError::new(
+                                "missing_from_transcript",
+                                "the transcript doesn't contain a result for this tool call",
+                            )
+                            .to_json()
+                        });
+                    let result = make_tool_result(id, call_id, output);
+                    self.push_tool_result(&mut tool_results, result);
+                    continue;
+                }
+
+                let mut tool_call = match self.tools.resolve(tool_call.clone()) {
                     Ok(t) => t,
                     Err(e) => {
-                        let result = make_tool_result(
-                            id,
-                            call_id,
-                            format!("failed to parse tool call: {e}"),
-                        );
+                        let result = make_tool_result(id, call_id, ToolError::from(&e).to_json());
                         self.push_tool_result(&mut tool_results, result);
                         continue;
                     }
                 };
 
+                if let Some(guardrails) = &self.guardrails
+                    && let Err(violation) = guardrails.check_confirmation_policy(
+                        &tool_call.confirmation_policy(),
+                        self.workspace.package().as_deref(),
+                    )
+                {
+                    self.stop_for_guardrail(
+                        violation,
+                        id,
+                        call_id,
+                        &tool_calls[i + 1..],
+                        tool_results,
+                    );
+                    return;
+                }
+
                 let confirmation = if tool_call.needs_confirmation() {
                     let details = match tool_call.details().await {
                         Ok(d) => d,
                         Err(e) => {
-                            let result = make_tool_result(id, call_id, e.to_string());
+                            let result = make_tool_result(id, call_id, e.tool_error().to_json());
                             self.push_tool_result(&mut tool_results, result);
                             continue;
                         }
                     };
 
-                    self.confirm_tool_call(&tool_call, details.as_deref()).await
+                    let confirmation = self
+                        .confirm_tool_call(&id, &tool_call, details.as_deref())
+                        .await;
+                    self.record_tool_confirmation(tool_call.name(), &confirmation);
+                    if let ToolCallConfirmation::FeedbackProvided(feedback) = &confirmation {
+                        self.record_correction(tool_call.name(), feedback).await;
+                    }
+                    confirmation
                 } else {
                     ToolCallConfirmation::Approved
                 };
 
+                let approval = confirmation.decision();
+                let confirmation = match confirmation {
+                    ToolCallConfirmation::PartiallyApproved(accepted) => {
+                        tool_call.set_accepted_hunks(accepted);
+                        ToolCallConfirmation::Approved
+                    }
+                    c => c,
+                };
+
                 match confirmation {
-                    ToolCallConfirmation::Approved | ToolCallConfirmation::AutoApproved => {
+                    ToolCallConfirmation::Approved
+                    | ToolCallConfirmation::AutoApproved
+                    | ToolCallConfirmation::PartiallyApproved(_) => {
+                        let tool_name = tool_call.name();
+                        let policy = tool_call.confirmation_policy();
+                        let file_before = self.file_hashes_before(&policy).await;
+                        let snapshot_before = match policy {
+                            ConfirmationPolicy::NotNeeded => None,
+                            _ => self.snapshot_before_step().await,
+                        };
+                        let step_label = tool_call.repr();
+                        self.title.set_status(TitleStatus::RunningTool);
+                        let start = Instant::now();
                         tokio::select! {
                             Ok(_) = tokio::signal::ctrl_c() => {
-                                println!("{}", "\ninterrupted".red());
                                 let result = make_tool_result(
                                     id.clone(),
                                     call_id,
-                                    "tool call interrupted by user",
+                                    ToolError::new("interrupted_by_user", "tool call interrupted by user").to_json(),
                                 );
                                 self.push_tool_result(&mut tool_results, result);
-
-                                if let Some(tx) = &self.debug_tx {
-                                    tx.send(DebugEvent::interrupted());
-                                }
-
                                 self.push_skipped_results(
                                     &tool_calls[i + 1..],
                                     &mut tool_results,
                                     "tool call skipped because user interrupted a previous tool call",
                                 );
+                                self.chat_history.push(self.tool_results_message(tool_results));
+                                self.record_step(snapshot_before, format!("{step_label} (interrupted)")).await;
 
-                                self.chat_history.push(Message::User {
-                                    #[allow(clippy::expect_used)]
-                                    content: OneOrMany::many(
-                                        tool_results
-                                            .into_iter()
-                                            .map(UserContent::ToolResult)
-                                            .collect::<Vec<_>>(),
-                                    )
-                                    .expect("tool results should've been added to chat history"),
-                                });
-
+                                self.finish_cancelled_turn(turn_start, Interruption::CancelledToolCall).await;
                                 return;
                             }
-                            result = tool_call.execute() => {
-                                match result {
-                                    Ok(output) => {
-                                        let result = make_tool_result(id, call_id, output);
-                                        self.push_tool_result(&mut tool_results, result);
+                            result = tool_call.execute(Arc::clone(&self.output)) => {
+                                let elapsed = start.elapsed();
+                                info!(tool = tool_name, elapsed_ms = elapsed.as_millis() as u64, success = result.is_ok(), "tool call executed");
+                                self.stats.record_tool_latency(tool_name, elapsed);
+
+                                let (output, error_code) = match result {
+                                    Ok(outcome) => {
+                                        let error_code = match &outcome {
+                                            ToolCallOutcome::Succeeded(_) => None,
+                                            ToolCallOutcome::Failed(e) => Some(e.code()),
+                                        };
+                                        (outcome.into_content(), error_code)
                                     },
                                     Err(e) => {
-                                        print_error(anyhow::anyhow!("{}", e));
-                                        let result = make_tool_result(id, call_id, e.to_string());
-                                        self.push_tool_result(&mut tool_results, result);
+                                        self.report_error(ErrorCategory::Tool, anyhow::anyhow!("{}", e));
+                                        let error = ToolError::from(&e);
+                                        (error.to_json(), Some(error.code()))
                                     }
+                                };
+                                match error_code {
+                                    None => self.stats.record_tool_success(tool_name),
+                                    Some(code) => self.stats.record_tool_failure(tool_name, code),
+                                }
+                                if policy != ConfirmationPolicy::NotNeeded {
+                                    self.audit_tool_execution(ToolAudit {
+                                        tool_call: &tool_calls[i],
+                                        approval,
+                                        policy: &policy,
+                                        file_before,
+                                        output: &output,
+                                        error_code,
+                                    }).await;
+                                }
+                                let output = self.flag_injections(&step_label, output);
+                                self.record_step(snapshot_before, step_label).await;
+                                // slices of artifacts are small enough already
+                                let output = if tool_name == ReadArtifactTool::NAME {
+                                    output
+                                } else {
+                                    self.artifacts.offload(tool_name, output).await
+                                };
+                                let result = make_tool_result(id, call_id, output);
+                                self.push_tool_result(&mut tool_results, result);
+
+                                let streak = self.stats.failure_streak(tool_name);
+                                if self.config.tool_failure_reminders() && streak.len() == TOOL_FAILURE_REMINDER_THRESHOLD {
+                                    failure_reminders.push(reminders::tool_failure_reminder(tool_name, streak));
                                 }
                             }
                         }
                     }
                     ToolCallConfirmation::Rejected => {
-                        println!("{}", "conversation stopped".red());
-                        let result = make_tool_result(id, call_id, "user rejected tool call");
+                        self.emit_line(OutputKind::Error, "conversation stopped");
+                        let result = make_tool_result(
+                            id,
+                            call_id,
+                            ToolError::new("rejected_by_user", "user rejected tool call").to_json(),
+                        );
                         self.push_tool_result(&mut tool_results, result);
                         self.push_skipped_results(
                             &tool_calls[i + 1..],
                             &mut tool_results,
                             "tool call skipped because user rejected a previous tool call",
                         );
-                        self.chat_history.push(Message::User {
-                            #[allow(clippy::expect_used)]
-                            content: OneOrMany::many(
-                                tool_results
-                                    .into_iter()
-                                    .map(UserContent::ToolResult)
-                                    .collect::<Vec<_>>(),
-                            )
-                            .expect("tool results should've been added to chat history"),
-                        });
+                        self.chat_history
+                            .push(self.tool_results_message(tool_results));
                         return;
                     }
                     ToolCallConfirmation::FeedbackProvided(text) => {
-                        println!("{}", "tool call rejected; providing feedback to LLM".red());
+                        self.emit_line(
+                            OutputKind::Error,
+                            "tool call rejected; providing feedback to LLM",
+                        );
                         let result = make_tool_result(
                             id,
                             call_id,
-                            format!("user rejected tool call with feedback: {text}"),
+                            ToolError::new(
+                                "rejected_by_user",
+                                format!("user rejected tool call with feedback: {text}"),
+                            )
+                            .with_hint("address the user's feedback before trying again")
+                            .to_json(),
                         );
                         self.push_tool_result(&mut tool_results, result);
                         self.push_skipped_results(
@@ -377,52 +1483,400 @@ where
                 break;
             }
 
-            prompt = Message::User {
-                #[allow(clippy::expect_used)]
-                content: OneOrMany::many(
-                    tool_results
-                        .into_iter()
-                        .map(UserContent::ToolResult)
-                        .collect::<Vec<_>>(),
+            prompt = self.tool_results_message(tool_results);
+            if let Message::User { content } = &mut prompt {
+                for reminder in failure_reminders {
+                    content.push(UserContent::text(reminder));
+                }
+            }
+        }
+    }
+
+    async fn handle_json_prompt(&mut self, args: &str) {
+        let Some((schema_path, prompt)) = args.split_once(char::is_whitespace) else {
+            self.emit_line(
+                OutputKind::Error,
+                "error: usage: /json <schema-file> <prompt>",
+            );
+            return;
+        };
+
+        let schema = match read_json_schema(schema_path).await {
+            Ok(s) => s,
+            Err(e) => {
+                self.report_error(ErrorCategory::Session, e);
+                return;
+            }
+        };
+
+        self.response_schema = Some(schema.clone());
+
+        let mut prompt = format!(
+            "{}
+
+Respond only with a JSON document (without any surrounding text or code fences) that conforms to the following JSON schema:
+{}",
+            prompt.trim(),
+            schema
+        );
+
+        for attempt in 1..=MAX_JSON_RESPONSE_ATTEMPTS {
+            self.handle_prompt(&prompt).await;
+
+            let Some(response) = self.last_assistant_text() else {
+                break;
+            };
+
+            let errors = match parse_json_response(&response) {
+                Ok(value) => validate_against_schema(&value, &schema),
+                Err(e) => vec![format!("response is not valid JSON: {e}")],
+            };
+
+            if errors.is_empty() {
+                self.emit_line(OutputKind::Success, "response conforms to the schema");
+                break;
+            }
+
+            let errors = errors.join("\n- ");
+            if attempt == MAX_JSON_RESPONSE_ATTEMPTS {
+                self.report_error(
+                    ErrorCategory::Session,
+                    anyhow::anyhow!(
+                        "response didn't conform to the schema after {} attempts:\n- {}",
+                        MAX_JSON_RESPONSE_ATTEMPTS,
+                        errors
+                    ),
+                );
+                break;
+            }
+
+            self.emit_line(
+                OutputKind::Warning,
+                "response didn't conform to the schema; asking the model to fix it",
+            );
+            prompt = format!(
+                "Your response didn't conform to the JSON schema:
+- {errors}
+
+Respond again with only the corrected JSON document."
+            );
+        }
+
+        self.response_schema = None;
+    }
+
+    /// Sends a prompt to the session's model and to another model of the provider in parallel,
+    /// and shows their responses side by side; tool calls they request aren't executed. The
+    /// response the user picks (if any) is kept in the conversation.
+    async fn compare_models(&mut self, args: &str) {
+        let Some((other_model, prompt)) = args.trim().split_once(char::is_whitespace) else {
+            self.emit_line(OutputKind::Error, "error: usage: /compare <model> <prompt>");
+            return;
+        };
+        let prompt = prompt.trim();
+
+        let Some(access) = self.provider_access.clone() else {
+            self.emit_line(
+                OutputKind::Error,
+                "error: models can't be compared in this session, since it can't reach the provider's API on its own",
+            );
+            return;
+        };
+        let other_agent = match agent_for(&self.provider, access, other_model, &self.config).await {
+            Ok(agent) => agent,
+            Err(e) => {
+                self.report_error(ErrorCategory::Provider, e);
+                return;
+            }
+        };
+
+        let message = Message::user(prompt);
+        let requests = (
+            self.build_llm_request_for(&self.agent, &message).await,
+            self.build_llm_request_for(&other_agent, &message).await,
+        );
+        let (request, other_request) = match requests {
+            (Ok(request), Ok(other_request)) => (request, other_request),
+            (Err(e), _) | (_, Err(e)) => {
+                self.report_error(ErrorCategory::Session, e);
+                return;
+            }
+        };
+
+        self.emit_line(
+            OutputKind::Info,
+            format!(
+                "asking {} and {other_model}; tool calls they request won't be executed",
+                self.model_name
+            ),
+        );
+        let Some(responses) =
+            interrupts::cancellable(async { tokio::join!(request.send(), other_request.send()) })
+                .await
+        else {
+            self.emit_line(
+                OutputKind::Error,
+                "
+comparison cancelled",
+            );
+            return;
+        };
+
+        let mut described = vec![];
+        for (model, response) in [
+            (self.model_name.clone(), responses.0),
+            (other_model.to_string(), responses.1),
+        ] {
+            let response = match response {
+                Ok(r) => r,
+                Err(e) => {
+                    self.report_error(
+                        ErrorCategory::Provider,
+                        anyhow::Error::from(e).context(format!("couldn't get {model}'s response")),
+                    );
+                    return;
+                }
+            };
+            self.stats
+                .record_token_usage(response.usage.input_tokens, response.usage.output_tokens);
+
+            let mut text = String::new();
+            let mut tool_calls = vec![];
+            for content in response.choice {
+                match content {
+                    AssistantContent::Text(t) => text.push_str(&t.text),
+                    AssistantContent::ToolCall(tool_call) => tool_calls.push(tool_call),
+                    AssistantContent::Reasoning(_) | AssistantContent::Image(_) => {}
+                }
+            }
+            described.push((
+                model,
+                compare::describe_response(&text, &tool_calls),
+                !tool_calls.is_empty(),
+            ));
+        }
+
+        let width = console::Term::stdout().size().1 as usize;
+        let [(model_a, response_a, _), (model_b, response_b, _)] = &described[..] else {
+            return;
+        };
+        self.emit_line(
+            OutputKind::Response,
+            format!(
+                "\n{}\n",
+                compare::side_by_side(
+                    (&format!("(a) {model_a}"), response_a),
+                    (&format!("(b) {model_b}"), response_b),
+                    width,
                 )
-                .expect("tool results should've been set as the next prompt"),
+            ),
+        );
+
+        let question = format!(
+            "keep (a) {model_a}'s or (b) {model_b}'s response in the conversation, or (n)either? "
+        );
+        let picked = loop {
+            let Ok(answer) = self.editor.readline(&question) else {
+                break None;
             };
+            match answer.trim() {
+                "a" => break described.first(),
+                "b" => break described.get(1),
+                "n" | "neither" => break None,
+                _ => continue,
+            }
+        };
+
+        let Some((model, response, has_tool_calls)) = picked.cloned() else {
+            self.emit_line(OutputKind::Info, "neither response was kept");
+            return;
+        };
+        self.chat_history.push(Message::user(prompt));
+        self.chat_history.push(Message::assistant(response));
+        self.save_history().await;
+        self.emit_line(
+            OutputKind::Success,
+            if has_tool_calls {
+                format!("kept {model}'s response; the tool calls it requested weren't executed")
+            } else {
+                format!("kept {model}'s response")
+            },
+        );
+    }
+
+    fn last_assistant_text(&self) -> Option<String> {
+        match self.chat_history.last()? {
+            Message::Assistant { content, .. } => {
+                let text = content
+                    .iter()
+                    .filter_map(|c| match c {
+                        AssistantContent::Text(t) => Some(t.text.as_str()),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>()
+                    .join("");
+
+                (!text.is_empty()).then_some(text)
+            }
+            Message::User { .. } => None,
         }
     }
 
     #[instrument(skip(self), fields(prompt = prompt.summary()) err)]
-    async fn stream_llm_response(
+    async fn stream_llm_response(&mut self, prompt: Message) -> anyhow::Result<LlmResponse> {
+        match self
+            .request_llm_response_with_fallback(prompt.clone())
+            .await
+        {
+            Err(e) if is_context_length_exceeded_error(&e) => {
+                let num_elided = compaction::elide_old_tool_outputs(
+                    &mut self.chat_history,
+                    MESSAGES_TO_KEEP_ON_COMPACTION,
+                );
+                if num_elided == 0 {
+                    return Err(e);
+                }
+
+                self.emit_line(
+                    OutputKind::Warning,
+                    format!(
+                        "context length exceeded; elided {num_elided} older tool output(s) and retrying"
+                    ),
+                );
+                self.request_llm_response_with_fallback(prompt).await
+            }
+            result => result,
+        }
+    }
+
+    async fn request_llm_response_with_fallback(
         &mut self,
         prompt: Message,
-    ) -> anyhow::Result<(String, Vec<ToolCall>)> {
-        let request_builder = self
-            .agent
-            .completion(prompt.clone(), self.chat_history.clone())
-            .await
-            .context("couldn't build LLM request builder")?
-            .preamble(self.get_preamble());
+    ) -> anyhow::Result<LlmResponse> {
+        match self.request_llm_response(prompt.clone()).await {
+            Err(e) if !self.text_tool_calls && is_tool_calling_unsupported_error(&e) => {
+                self.text_tool_calls = true;
+                self.emit_line(
+                    OutputKind::Warning,
+                    "model doesn't seem to support native tool calls; falling back to text based tool calls for this session",
+                );
+                self.request_llm_response(prompt).await
+            }
+            result => result,
+        }
+    }
 
-        let mut stream = request_builder
-            .stream()
-            .await
-            .context("couldn't build LLM request stream")?;
+    async fn request_llm_response(&mut self, prompt: Message) -> anyhow::Result<LlmResponse> {
+        if !self.streaming {
+            return self.get_llm_response(prompt).await;
+        }
+
+        loop {
+            if let Some(response) = self.attempt_streamed_response(&prompt).await? {
+                return Ok(response);
+            }
+            info!(provider = %self.provider, "retrying request for a response");
+            self.emit_line(OutputKind::Info, "retrying the request");
+        }
+    }
+
+    /// Streams a response to `prompt`; returns `None` if the user asked for the request to be
+    /// retried while its first token was awaited.
+    async fn attempt_streamed_response(
+        &mut self,
+        prompt: &Message,
+    ) -> anyhow::Result<Option<LlmResponse>> {
+        let request_builder = self.build_llm_request(prompt).await?;
+        let mut interrupts = Interrupts::watch();
+        let mut wait = FirstTokenWait::new(
+            self.config.first_token_notice_after(),
+            self.config.first_token_retry_after(),
+        );
+
+        let stream = request_builder.stream();
+        tokio::pin!(stream);
+        let stream = loop {
+            tokio::select! {
+                stream = &mut stream => break stream,
+                interrupt = interrupts.next() => match interrupt {
+                    Interrupt::Cancel => return Ok(Some(LlmResponse::cancelled())),
+                    Interrupt::StopStreaming => return Ok(Some(LlmResponse::stopped())),
+                    Interrupt::Retry if wait.retry_offered() => return Ok(None),
+                    Interrupt::Retry | Interrupt::TogglePause => {}
+                },
+                notice = wait.next_notice() => self.show_wait_notice(notice),
+            }
+        };
+        let mut stream = match stream {
+            Ok(s) => s,
+            Err(e)
+                if self.provider.may_lack_streaming_tool_calls()
+                    && is_streaming_tool_calls_unsupported(&e) =>
+            {
+                self.disable_streaming(e);
+                return self.get_llm_response(prompt.clone()).await.map(Some);
+            }
+            Err(e) => return Err(e).context("couldn't build LLM request stream"),
+        };
 
         if let Some(tx) = &self.debug_tx {
-            tx.send(DebugEvent::llm_request(&prompt, &self.chat_history));
+            tx.send(DebugEvent::llm_request(prompt, &self.chat_history));
         }
 
         let mut response_text = String::new();
-
+        let mut reasoning_blocks = vec![];
         let mut tool_calls = vec![];
+        let mut truncated = None;
+        // output received while the user has paused printing it
+        let mut held: Option<Vec<(OutputKind, String)>> = None;
+
+        loop {
+            let result = tokio::select! {
+                result = stream.next() => match result {
+                    Some(r) => r,
+                    None => break,
+                },
+                interrupt = interrupts.next() => {
+                    match interrupt {
+                        Interrupt::TogglePause => {
+                            self.toggle_stream_pause(&mut held);
+                            continue;
+                        }
+                        Interrupt::Retry if wait.retry_offered() && !wait.has_received() => {
+                            return Ok(None);
+                        }
+                        Interrupt::Retry => continue,
+                        Interrupt::Cancel | Interrupt::StopStreaming => {}
+                    }
+                    self.release_held_output(&mut held);
+                    if !response_text.is_empty() {
+                        self.emit(OutputKind::Response, "\n");
+                    }
+                    // tool calls are dropped, since the model might not have finished
+                    // requesting the ones it meant to
+                    return Ok(Some(LlmResponse {
+                        text: response_text,
+                        reasoning: reasoning_blocks,
+                        tool_calls: vec![],
+                        truncated: Some(STOPPED_BY_USER.to_string()),
+                        cancelled: interrupt == Interrupt::Cancel,
+                    }));
+                }
+                notice = wait.next_notice() => {
+                    self.show_wait_notice(notice);
+                    continue;
+                }
+            };
+            wait.mark_received();
+            self.title.tick();
 
-        while let Some(result) = stream.next().await {
             match result {
                 Ok(content) => match content {
                     StreamedAssistantContent::Text(text) => {
                         if response_text.is_empty() {
-                            println!();
+                            self.emit_streamed(&mut held, OutputKind::Response, "\n");
                         }
-                        print!("{text}");
+                        self.emit_streamed(&mut held, OutputKind::Response, &text.text);
                         response_text.push_str(&text.text);
                     }
                     StreamedAssistantContent::ToolCall(tool_call) => {
@@ -433,18 +1887,29 @@ where
                     }
                     StreamedAssistantContent::ToolCallDelta { .. } => {}
                     StreamedAssistantContent::Reasoning(reasoning) => {
-                        print!("\n{}", "[reasoning] ".cyan());
+                        self.emit_streamed(&mut held, OutputKind::Reasoning, "\n[reasoning] ");
                         for r in &reasoning.reasoning {
-                            print!("{}", r.to_string().cyan());
+                            self.emit_streamed(&mut held, OutputKind::Reasoning, r);
                         }
                         if let Some(tx) = &self.debug_tx {
-                            tx.send(DebugEvent::reasoning(reasoning));
+                            tx.send(DebugEvent::reasoning(reasoning.clone()));
                         }
+                        reasoning_blocks.push(reasoning);
                     }
                     StreamedAssistantContent::ReasoningDelta { .. } => {}
                     StreamedAssistantContent::Final(r) => {
-                        if let Some(usage) = r.token_usage() {
+                        let usage = r.token_usage();
+                        if let Some(usage) = usage {
                             self.tokens_in_context = usage.total_tokens;
+                            self.stats
+                                .record_token_usage(usage.input_tokens, usage.output_tokens);
+                        }
+                        // not every provider reports a finish reason when streaming, nor has a
+                        // max tokens limit set, so either is enough
+                        if usage.is_some_and(|u| self.reached_max_tokens(u.output_tokens))
+                            || continuation::stopped_due_to_max_tokens(&r.raw_response)
+                        {
+                            truncated = Some(MAX_TOKENS_REACHED.to_string());
                         }
                         if !response_text.is_empty()
                             && let Some(tx) = &self.debug_tx
@@ -454,97 +1919,489 @@ where
                         if let Some(tx) = &self.debug_tx {
                             tx.send(DebugEvent::stream_complete());
                         }
-                        println!();
+                        self.emit_streamed(&mut held, OutputKind::Response, "\n");
                     }
                 },
+                Err(e)
+                    if self.provider.may_lack_streaming_tool_calls()
+                        && is_streaming_tool_calls_unsupported(&e)
+                        && response_text.is_empty()
+                        && tool_calls.is_empty() =>
+                {
+                    self.release_held_output(&mut held);
+                    self.disable_streaming(e);
+                    return self.get_llm_response(prompt.clone()).await.map(Some);
+                }
+                Err(e) if !response_text.is_empty() => {
+                    self.release_held_output(&mut held);
+                    self.emit(OutputKind::Response, "\n");
+                    if let Some(tx) = &self.debug_tx {
+                        tx.send(DebugEvent::error(
+                            ErrorCategory::Stream,
+                            format!("response was cut short: {e}"),
+                        ));
+                    }
+                    // tool calls are dropped, since the model might not have finished
+                    // requesting the ones it meant to
+                    return Ok(Some(LlmResponse {
+                        text: response_text,
+                        reasoning: reasoning_blocks,
+                        tool_calls: vec![],
+                        truncated: Some(e.to_string()),
+                        cancelled: false,
+                    }));
+                }
                 Err(e) => {
+                    self.release_held_output(&mut held);
                     anyhow::bail!(e);
                 }
             }
         }
+        self.release_held_output(&mut held);
+
+        if self.text_tool_calls {
+            tool_calls.extend(text_tools::parse_tool_calls(&response_text));
+        }
 
-        Ok((response_text, tool_calls))
+        Ok(Some(LlmResponse {
+            text: response_text,
+            reasoning: reasoning_blocks,
+            tool_calls,
+            truncated,
+            cancelled: false,
+        }))
     }
 
-    async fn confirm_tool_call(
-        &mut self,
-        tool_call: &AgxToolCall,
-        details: Option<&str>,
-    ) -> ToolCallConfirmation {
-        // TODO: temporary hack to skip HITL
-        if std::env::var("AGX_SKIP_HITL")
-            .map(|s| s == "1")
-            .unwrap_or_default()
-        {
-            return ToolCallConfirmation::Approved;
+    fn show_wait_notice(&self, notice: WaitNotice) {
+        match notice {
+            WaitNotice::StillWaiting(elapsed) => self.emit_line(
+                OutputKind::Info,
+                format!(
+                    "still waiting for a response from {} ({}s)",
+                    self.provider,
+                    elapsed.as_secs()
+                ),
+            ),
+            WaitNotice::OfferRetry(elapsed) => {
+                warn!(provider = %self.provider, elapsed_secs = elapsed.as_secs(), "no response received yet");
+                self.emit_line(
+                    OutputKind::Warning,
+                    format!(
+                        "no response from {} in {}s; the connection might be stuck: press r to retry the request, Esc to stop waiting, or keep waiting",
+                        self.provider,
+                        elapsed.as_secs()
+                    ),
+                );
+            }
+        }
+    }
+
+    /// Prints streamed output, unless the user has paused printing it, in which case it's held
+    /// until they resume.
+    fn emit_streamed(
+        &self,
+        held: &mut Option<Vec<(OutputKind, String)>>,
+        kind: OutputKind,
+        text: &str,
+    ) {
+        match held {
+            Some(held) => held.push((kind, text.to_string())),
+            None => self.emit(kind, text),
         }
+    }
 
-        if self.approvals.is_tool_call_approved(tool_call) {
-            return ToolCallConfirmation::AutoApproved;
+    fn toggle_stream_pause(&self, held: &mut Option<Vec<(OutputKind, String)>>) {
+        if held.is_some() {
+            self.release_held_output(held);
+            return;
         }
 
-        println!(
-            "{}",
-            format!("[request for tool-call] {}", tool_call.repr()).bright_purple()
+        self.emit_line(
+            OutputKind::Info,
+            "\n[paused; the response is still being received; press Space to resume]",
         );
+        *held = Some(vec![]);
+    }
 
-        if let Some(info) = details {
-            println!("{}", info);
+    fn release_held_output(&self, held: &mut Option<Vec<(OutputKind, String)>>) {
+        for (kind, text) in held.take().unwrap_or_default() {
+            self.emit(kind, text);
         }
+    }
 
-        let approval_line = match tool_call {
-            AgxToolCall::CreateFile { .. } | AgxToolCall::EditFile { .. } => {
-                Some("to allow all edits in this session".to_string())
-            }
-            AgxToolCall::RunCmd { args } => {
-                if let Ok(cmd_pattern) = CmdPattern::from_str(&args.command) {
-                    Some(format!(r#"to always allow "{cmd_pattern}" commands"#,))
-                } else {
-                    // TODO: this error shouldn't happen this deep in the call stack
-                    None
-                }
-            }
-            _ => None,
+    async fn get_llm_response(&mut self, prompt: Message) -> anyhow::Result<LlmResponse> {
+        let request = self.build_llm_request(&prompt).await?;
+        let Some(response) = interrupts::cancellable(request.send()).await else {
+            return Ok(LlmResponse::cancelled());
         };
+        let response = response.context("couldn't get LLM response")?;
 
-        let confirmation_prompt = format!(
-            "
-type:
-- y / <enter> to proceed
-- a           {}
-- n / no      to reject
-- reject and provide feedback: ",
-            approval_line.unwrap_or("to always approve this tool call".to_string())
-        );
-
-        match self.editor.readline(&confirmation_prompt) {
-            Ok(input) => {
-                let trimmed = input.trim();
-                match trimmed {
-                    "" | "y" => ToolCallConfirmation::Approved,
-                    "a" => {
-                        // TODO: this can be made nicer
-                        if let Some(confirmation_msg) = self.approvals.save_approval(tool_call) {
-                            if matches!(tool_call, AgxToolCall::RunCmd { .. }) {
-                                self.config.approved_commands =
-                                    self.approvals.approved_commands.clone();
-                                if let Err(e) = save_local_config(&self.config)
-                                    .await
-                                    .context("couldn't update agx's local config")
-                                {
-                                    print_error(e);
-                                }
-                            }
-                            println!("{}", confirmation_msg.green());
-                        }
+        if let Some(tx) = &self.debug_tx {
+            tx.send(DebugEvent::llm_request(&prompt, &self.chat_history));
+        }
 
-                        ToolCallConfirmation::AutoApproved
+        let mut response_text = String::new();
+        let mut reasoning_blocks = vec![];
+        let mut tool_calls = vec![];
+
+        for content in response.choice {
+            match content {
+                AssistantContent::Text(text) => response_text.push_str(&text.text),
+                AssistantContent::ToolCall(tool_call) => {
+                    if let Some(tx) = &self.debug_tx {
+                        tx.send(DebugEvent::tool_call(tool_call.clone()));
+                    }
+                    tool_calls.push(tool_call);
+                }
+                AssistantContent::Reasoning(reasoning) => {
+                    self.emit(OutputKind::Reasoning, "\n[reasoning] ");
+                    for r in &reasoning.reasoning {
+                        self.emit(OutputKind::Reasoning, r);
+                    }
+                    if let Some(tx) = &self.debug_tx {
+                        tx.send(DebugEvent::reasoning(reasoning.clone()));
                     }
-                    "n" | "no" => ToolCallConfirmation::Rejected,
-                    feedback => ToolCallConfirmation::FeedbackProvided(feedback.to_string()),
+                    reasoning_blocks.push(reasoning);
                 }
+                AssistantContent::Image(_) => {}
+            }
+        }
+
+        if response.usage.total_tokens > 0 {
+            self.tokens_in_context = response.usage.total_tokens;
+        }
+        self.stats
+            .record_token_usage(response.usage.input_tokens, response.usage.output_tokens);
+
+        let truncated = (self.reached_max_tokens(response.usage.output_tokens)
+            || continuation::stopped_due_to_max_tokens(&response.raw_response))
+        .then(|| MAX_TOKENS_REACHED.to_string());
+
+        if !response_text.is_empty() {
+            self.emit(OutputKind::Response, format!("\n{response_text}"));
+            if let Some(tx) = &self.debug_tx {
+                tx.send(DebugEvent::assistant_text(&response_text));
+            }
+        }
+        if let Some(tx) = &self.debug_tx {
+            tx.send(DebugEvent::stream_complete());
+        }
+        self.emit(OutputKind::Response, "\n");
+
+        if self.text_tool_calls {
+            tool_calls.extend(text_tools::parse_tool_calls(&response_text));
+        }
+
+        Ok(LlmResponse {
+            text: response_text,
+            reasoning: reasoning_blocks,
+            tool_calls,
+            truncated,
+            cancelled: false,
+        })
+    }
+
+    fn reached_max_tokens(&self, output_tokens: u64) -> bool {
+        self.output_overrides
+            .max_output
+            .or(self.agent.max_tokens)
+            .is_some_and(|max| output_tokens > 0 && output_tokens >= max)
+    }
+
+    async fn build_llm_request(
+        &self,
+        prompt: &Message,
+    ) -> anyhow::Result<CompletionRequestBuilder<DynModel>> {
+        self.build_llm_request_for(&self.agent, prompt).await
+    }
+
+    /// Builds a request for the conversation so far, to be sent to `agent`'s model.
+    async fn build_llm_request_for(
+        &self,
+        agent: &Agent<DynModel>,
+        prompt: &Message,
+    ) -> anyhow::Result<CompletionRequestBuilder<DynModel>> {
+        let mut history = self.chat_history.clone();
+        let num_deduped = compaction::dedupe_tool_outputs(&mut history, prompt);
+        if num_deduped > 0 {
+            debug!(num_deduped, "replaced repeated tool outputs in request");
+        }
+
+        if self.text_tool_calls {
+            let tool_defs = self.tools.definitions().await;
+
+            let preamble = format!(
+                "{}\n{}",
+                self.get_preamble(),
+                text_tools::instructions(&tool_defs)
+            );
+
+            let request_builder =
+                CompletionRequestBuilder::new((*agent.model).clone(), prompt.clone())
+                    .messages(history)
+                    .preamble(preamble)
+                    .temperature_opt(agent.temperature)
+                    .max_tokens_opt(agent.max_tokens)
+                    .additional_params_opt(agent.additional_params.clone());
+
+            return Ok(self.with_response_format(self.with_output_overrides(request_builder)));
+        }
+
+        let request_builder = agent
+            .completion(prompt.clone(), history)
+            .await
+            .context("couldn't build LLM request builder")?
+            .preamble(self.get_preamble())
+            .tools(self.tools.definitions().await);
+
+        Ok(self.with_response_format(self.with_output_overrides(request_builder)))
+    }
+
+    fn with_output_overrides(
+        &self,
+        mut request_builder: CompletionRequestBuilder<DynModel>,
+    ) -> CompletionRequestBuilder<DynModel> {
+        let OutputOverrides {
+            max_output,
+            stop_sequences,
+            ..
+        } = &self.output_overrides;
+
+        if let Some(max_output) = max_output {
+            request_builder = request_builder.max_tokens(*max_output);
+        }
+
+        if let Some(params) = self
+            .provider
+            .output_limit_params(*max_output, stop_sequences)
+        {
+            request_builder = request_builder.additional_params(params);
+        }
+
+        request_builder
+    }
+
+    fn with_response_format(
+        &self,
+        request_builder: CompletionRequestBuilder<DynModel>,
+    ) -> CompletionRequestBuilder<DynModel> {
+        match &self.response_schema {
+            Some(schema) if self.provider.supports_json_schema_response_format() => request_builder
+                .additional_params(serde_json::json!({
+                    "response_format": {
+                        "type": "json_schema",
+                        "json_schema": {
+                            "name": "response",
+                            "schema": schema,
+                        }
+                    }
+                })),
+            _ => request_builder,
+        }
+    }
+
+    fn tool_results_message(&self, tool_results: Vec<ToolResult>) -> Message {
+        if self.text_tool_calls {
+            return text_tools::tool_results_message(&tool_results);
+        }
+
+        Message::User {
+            #[allow(clippy::expect_used)]
+            content: OneOrMany::many(
+                tool_results
+                    .into_iter()
+                    .map(UserContent::ToolResult)
+                    .collect::<Vec<_>>(),
+            )
+            .expect("tool results should've been non-empty"),
+        }
+    }
+
+    fn disable_streaming(&mut self, error: impl std::fmt::Display) {
+        self.streaming = false;
+        if let Some(tx) = &self.debug_tx {
+            tx.send(DebugEvent::error(
+                ErrorCategory::Stream,
+                format!("streaming request failed: {error}"),
+            ));
+        }
+        self.emit_line(
+            OutputKind::Warning,
+            format!(
+                "the provider doesn't support tool calls in streaming responses ({error}); falling back to non-streaming responses for this session"
+            ),
+        );
+    }
+
+    async fn confirm_tool_call(
+        &mut self,
+        id: &str,
+        tool_call: &AgxToolCall,
+        details: Option<&str>,
+    ) -> ToolCallConfirmation {
+        // the first tool call that might change the workspace acknowledges its uncommitted
+        // changes, and hence is confirmed regardless of approvals
+        let dirty_workspace = match tool_call.confirmation_policy() {
+            ConfirmationPolicy::FileChange(_) | ConfirmationPolicy::Command(_) => {
+                self.dirty_workspace.clone()
+            }
+            ConfirmationPolicy::RemoteAction | ConfirmationPolicy::NotNeeded => None,
+        };
+        let details = match &dirty_workspace {
+            Some(changes) => Some(format!(
+                "the workspace has uncommitted changes; approving this tool call acknowledges that its changes will be mixed with them:\n{changes}{}",
+                details.map(|d| format!("\n\n{d}")).unwrap_or_default()
+            )),
+            None => details.map(str::to_string),
+        };
+        let details = match tool_call.confirmation_policy() {
+            ConfirmationPolicy::FileChange(path) => match generated_file_warning(&path) {
+                Some(warning) => Some(format!(
+                    "warning: {warning}{}",
+                    details.map(|d| format!("\n\n{d}")).unwrap_or_default()
+                )),
+                None => details,
+            },
+            _ => details,
+        };
+        // approving a binary doesn't extend to it touching files outside the workspace
+        let outside_paths = match tool_call.confirmation_policy() {
+            ConfirmationPolicy::Command(command) => {
+                paths_outside_workspace(&command, &self.workspace.dir())
+            }
+            _ => vec![],
+        };
+        let details = if outside_paths.is_empty() {
+            details
+        } else {
+            Some(format!(
+                "{}{}",
+                console::style(format!(
+                    "warning: this command refers to paths outside the workspace: {}",
+                    outside_paths.join(", ")
+                ))
+                .yellow()
+                .bold(),
+                details.map(|d| format!("\n\n{d}")).unwrap_or_default()
+            ))
+        };
+        let details = details.as_deref();
+
+        if dirty_workspace.is_none() {
+            if self.skips_confirmation() {
+                return ToolCallConfirmation::Approved;
+            }
+
+            if outside_paths.is_empty() && self.approvals.is_tool_call_approved(tool_call) {
+                return ToolCallConfirmation::AutoApproved;
+            }
+        }
+
+        if let Some(tx) = &self.debug_tx {
+            tx.send(DebugEvent::approval_requested(
+                id,
+                tool_call.repr(),
+                details.map(|d| console::strip_ansi_codes(d).to_string()),
+            ));
+        }
+
+        self.emit_line(
+            OutputKind::ApprovalRequest,
+            format!("[request for tool-call] {}", tool_call.repr()),
+        );
+        if let Some(info) = details {
+            self.emit_line(OutputKind::Response, info);
+        }
+
+        let hunks = match tool_call.hunk_diff().await {
+            Some(diff) if diff.hunks.len() > 1 => (0..diff.hunks.len())
+                .map(|i| diff.get_terminal_output_for_hunk(i).unwrap_or_default())
+                .collect(),
+            _ => vec![],
+        };
+        let request = ApprovalRequest {
+            tool_call_id: id.to_string(),
+            tool_name: tool_call.name().to_string(),
+            repr: tool_call.repr(),
+            details: details.map(str::to_string),
+            policy: tool_call.confirmation_policy(),
+            hunks,
+            // approvals can't be saved in strict mode
+            can_always_approve: self.approval_mode != ApprovalMode::Strict
+                && dirty_workspace.is_none()
+                && outside_paths.is_empty(),
+        };
+
+        self.title.set_status(TitleStatus::AwaitingApproval);
+        let start = Instant::now();
+        let confirmations = Arc::clone(&self.confirmations);
+        let pending = self.webhooks.approval_pending(&request.repr);
+        let response = confirmations.confirm(&request).await;
+        drop(pending);
+        let confirmation = self
+            .handle_approval_response(tool_call, &request, response)
+            .await;
+        if matches!(
+            confirmation,
+            ToolCallConfirmation::Approved | ToolCallConfirmation::PartiallyApproved(_)
+        ) {
+            self.dirty_workspace = None;
+        }
+
+        if let Some(tx) = &self.debug_tx {
+            tx.send(DebugEvent::approval_resolved(
+                id,
+                confirmation.decision(),
+                start.elapsed(),
+            ));
+        }
+
+        confirmation
+    }
+
+    async fn handle_approval_response(
+        &mut self,
+        tool_call: &AgxToolCall,
+        request: &ApprovalRequest,
+        response: ApprovalResponse,
+    ) -> ToolCallConfirmation {
+        match response {
+            ApprovalResponse::Approve => ToolCallConfirmation::Approved,
+            ApprovalResponse::AlwaysApprove if !request.can_always_approve => {
+                ToolCallConfirmation::Approved
+            }
+            ApprovalResponse::AlwaysApprove => {
+                // TODO: this can be made nicer
+                if let Some(confirmation_msg) = self.approvals.save_approval(tool_call) {
+                    if let ConfirmationPolicy::Command(command) = &request.policy
+                        && let Ok(pattern) = CmdPattern::from_str(command)
+                    {
+                        self.config.approved_commands = self.approvals.approved_commands.clone();
+                        if let Err(e) = save_approved_command(&pattern)
+                            .await
+                            .context("couldn't update agx's local config")
+                        {
+                            self.report_error(ErrorCategory::Session, e);
+                        }
+                    }
+                    self.emit_line(OutputKind::Success, confirmation_msg);
+                }
+
+                ToolCallConfirmation::AutoApproved
+            }
+            ApprovalResponse::ApproveHunks(accepted) => {
+                if accepted.iter().all(|a| *a) {
+                    ToolCallConfirmation::Approved
+                } else if accepted.iter().any(|a| *a) {
+                    ToolCallConfirmation::PartiallyApproved(accepted)
+                } else {
+                    ToolCallConfirmation::Rejected
+                }
+            }
+            ApprovalResponse::Reject => ToolCallConfirmation::Rejected,
+            ApprovalResponse::Feedback(feedback) => {
+                ToolCallConfirmation::FeedbackProvided(feedback)
             }
-            Err(_) => ToolCallConfirmation::Rejected,
         }
     }
 
@@ -555,7 +2412,11 @@ type:
         reason: &str,
     ) {
         for tc in remaining_tool_calls {
-            let result = make_tool_result(tc.id.clone(), tc.call_id.clone(), reason);
+            let result = make_tool_result(
+                tc.id.clone(),
+                tc.call_id.clone(),
+                ToolError::new("skipped", reason).to_json(),
+            );
             self.push_tool_result(tool_results, result);
         }
     }
@@ -564,12 +2425,1088 @@ type:
         if let Some(tx) = &self.debug_tx {
             tx.send(DebugEvent::tool_result(&result));
         }
+        if self.audit_log.is_some() || self.transcript.is_some() {
+            let output = result
+                .content
+                .iter()
+                .filter_map(|c| match c {
+                    ToolResultContent::Text(t) => Some(t.text.as_str()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            self.record_audit(AuditEvent::ToolResult {
+                id: &result.id,
+                result: &output,
+            });
+            self.record_transcript(TranscriptEvent::ToolResult {
+                id: result.id.clone(),
+                output,
+            });
+        }
         tool_results.push(result);
     }
 
+    fn emit(&self, kind: OutputKind, text: impl AsRef<str>) {
+        self.output.emit(kind, text.as_ref());
+    }
+
+    fn emit_line(&self, kind: OutputKind, text: impl AsRef<str>) {
+        self.output.emit(kind, &format!("{}\n", text.as_ref()));
+    }
+
+    fn record_transcript(&self, event: TranscriptEvent) {
+        if let Some(transcript) = &self.transcript {
+            transcript.record(&event);
+        }
+    }
+
+    /// Saves the chat history in the session's chats directory, so that the conversation can be
+    /// resumed later on (see [`history`]); replays aren't saved.
+    async fn save_history(&self) {
+        if self.replayed_tool_results.is_some() || self.chat_history.is_empty() {
+            return;
+        }
+
+        let history = SavedHistory {
+            provider: self.provider.to_string(),
+            model: self.model_name.clone(),
+            title: self.title.conversation().map(str::to_string),
+            turns: self.turns,
+            messages: self.chat_history.clone(),
+        };
+        if let Err(e) = history.save(&self.chats_dir).await {
+            warn!(error = format!("{e:#}"), "couldn't save chat history");
+        }
+    }
+
+    /// Continues the conversation of a saved session (or the most recent one, for "latest") in
+    /// place of the current one.
+    async fn resume(&mut self, name: &str) -> anyhow::Result<()> {
+        let session =
+            find_saved_session(&self.project_log_dir.join("chats"), name, &self.chats_dir).await?;
+        let saved = SavedHistory::load(&session.dir).await?;
+
+        self.chat_history = saved.messages;
+        self.turns = saved.turns;
+        self.response_truncated = false;
+        self.tokens_in_context = 0;
+        self.title.clear_conversation();
+        if let Some(title) = &saved.title {
+            self.title.name_conversation(title);
+        }
+        self.emit_line(
+            OutputKind::Info,
+            format!(
+                "resumed session {}: {} prompt(s), {} message(s)",
+                session.name,
+                saved.turns,
+                self.chat_history.len()
+            ),
+        );
+        if saved.provider != self.provider.to_string() || saved.model != self.model_name {
+            self.emit_line(
+                OutputKind::Warning,
+                format!(
+                    "the session used {}/{}; it's continued with {}/{}",
+                    saved.provider, saved.model, self.provider, self.model_name
+                ),
+            );
+        }
+        self.save_history().await;
+
+        Ok(())
+    }
+
+    async fn list_saved_sessions(&mut self) {
+        let sessions =
+            match saved_sessions(&self.project_log_dir.join("chats"), &self.chats_dir).await {
+                Ok(sessions) => sessions,
+                Err(e) => {
+                    self.report_error(ErrorCategory::Session, e);
+                    return;
+                }
+            };
+        if sessions.is_empty() {
+            self.emit_line(
+                OutputKind::Warning,
+                "there are no saved sessions for this project",
+            );
+            return;
+        }
+
+        let mut lines = vec![];
+        for session in sessions.iter().take(MAX_LISTED_SESSIONS) {
+            lines.push(match SavedHistory::load(&session.dir).await {
+                Ok(history) => format!(
+                    "- {}  {} prompt(s)  {}",
+                    session.name,
+                    history.turns,
+                    history.title.unwrap_or_default()
+                ),
+                Err(_) => format!("- {}  (history couldn't be read)", session.name),
+            });
+        }
+        lines.push("resume one via /resume <session> (or /resume latest)".to_string());
+        self.emit_line(OutputKind::Success, lines.join("\n"));
+    }
+
+    /// Returns the name of the session's chats directory (ie. the time it started at).
+    fn session_name(&self) -> String {
+        self.chats_dir
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+
+    /// Records feedback the user rejected a tool call with (for /learn), and suggests /learn
+    /// once they've given the same feedback a few times.
+    async fn record_correction(&mut self, tool_name: &str, feedback: &str) {
+        if self.replayed_tool_results.is_some() {
+            return;
+        }
+
+        let correction = Correction::new(&self.session_name(), tool_name, feedback);
+        if let Err(e) = self.corrections.record(&correction).await {
+            warn!(error = format!("{e:#}"), "couldn't record correction");
+            return;
+        }
+        if self.learn_suggested || self.read_only {
+            return;
+        }
+
+        let given = match self.corrections.load().await {
+            Ok(corrections) => times_given(&corrections, feedback),
+            Err(e) => {
+                warn!(error = format!("{e:#}"), "couldn't read corrections");
+                return;
+            }
+        };
+        if given >= SUGGEST_LEARN_AFTER {
+            self.learn_suggested = true;
+            self.emit_line(
+                OutputKind::Info,
+                format!(
+                    "you've given this feedback {given} times; /learn can propose an {AGENTS_CONTEXT_FILE} update so that you don't have to repeat it"
+                ),
+            );
+        }
+    }
+
+    /// Has the model propose an update to AGENTS.md (in the workspace) that captures the
+    /// corrections the user has given; they're cleared once the update is made.
+    async fn learn(&mut self) {
+        if self.read_only {
+            self.emit_line(
+                OutputKind::Warning,
+                format!("/learn needs to change {AGENTS_CONTEXT_FILE}, which can't be done in a read-only session"),
+            );
+            return;
+        }
+
+        let corrections = match self.corrections.load().await {
+            Ok(corrections) => corrections,
+            Err(e) => {
+                self.report_error(ErrorCategory::Session, e);
+                return;
+            }
+        };
+        if corrections.is_empty() {
+            self.emit_line(
+                OutputKind::Warning,
+                "there are no corrections to learn from; they're recorded as you reject tool calls with feedback",
+            );
+            return;
+        }
+
+        let path = self.workspace.resolve(AGENTS_CONTEXT_FILE);
+        let before = tokio::fs::read(&path).await.ok();
+        self.emit_line(
+            OutputKind::Prompt,
+            format!(
+                "> proposing an {AGENTS_CONTEXT_FILE} update based on {} correction(s)\n",
+                corrections.len()
+            ),
+        );
+        let prompt = learn_prompt(&group_corrections(&corrections), AGENTS_CONTEXT_FILE);
+        self.submit_prompt(prompt).await;
+        if let Some(tx) = &self.debug_tx {
+            tx.send(DebugEvent::turn_complete(&self.chat_history));
+        }
+
+        if tokio::fs::read(&path).await.ok() == before {
+            self.emit_line(
+                OutputKind::Warning,
+                format!("{AGENTS_CONTEXT_FILE} wasn't changed; the corrections are kept for the next /learn"),
+            );
+            return;
+        }
+
+        if let Err(e) = self.corrections.clear().await {
+            self.report_error(ErrorCategory::Session, e);
+        }
+        match self.reload().await {
+            Ok(()) => self.emit_line(
+                OutputKind::Success,
+                format!("{AGENTS_CONTEXT_FILE} was updated and reloaded; the corrections it captures were cleared"),
+            ),
+            Err(e) => self.report_error(ErrorCategory::Session, e),
+        }
+    }
+
+    fn api_endpoint(&self) -> Option<ApiEndpoint> {
+        let base_url = self
+            .provider_access
+            .as_ref()
+            .and_then(|a| a.base_url.as_deref());
+        ApiEndpoint::of(&self.provider, base_url)
+    }
+
+    /// Warns if the provider can't be reached as the session starts, so that the first prompt
+    /// doesn't fail with a cryptic error.
+    async fn check_connectivity(&mut self) {
+        let Some(endpoint) = self.api_endpoint() else {
+            return;
+        };
+        let Err(reason) = endpoint.probe().await else {
+            return;
+        };
+
+        warn!(%endpoint, reason, "provider can't be reached");
+        self.connection_failures = OFFLINE_AFTER_FAILURES;
+        self.emit_line(
+            OutputKind::Warning,
+            format!(
+                "offline: {endpoint} can't be reached ({reason}); prompts sent to {} will fail until it can be",
+                self.provider
+            ),
+        );
+    }
+
+    /// Tells the user that a request couldn't reach the provider; once that's happened
+    /// repeatedly, offers to retry it, queue it (or wait) until the provider can be reached
+    /// again, or switch to a local model.
+    async fn handle_connection_error(
+        &mut self,
+        error: &anyhow::Error,
+        queueable: bool,
+    ) -> UnsentRequest {
+        self.connection_failures += 1;
+        if let Some(tx) = &self.debug_tx {
+            tx.send(DebugEvent::error(
+                ErrorCategory::Provider,
+                format!("{error:#}"),
+            ));
+        }
+        let reason = error.root_cause().to_string();
+        warn!(provider = %self.provider, failures = self.connection_failures, reason, "couldn't connect to provider");
+
+        if self.sending_queued && queueable {
+            self.connection_failures = self.connection_failures.max(OFFLINE_AFTER_FAILURES);
+            self.emit_line(
+                OutputKind::Error,
+                format!(
+                    "offline: {} can't be reached ({reason}); the prompt is back in the queue",
+                    self.provider
+                ),
+            );
+            return UnsentRequest::Queue;
+        }
+
+        if self.connection_failures < OFFLINE_AFTER_FAILURES {
+            self.emit_line(
+                OutputKind::Error,
+                format!(
+                    "error: couldn't connect to {} ({reason}); check the connection, and send the prompt again",
+                    self.provider
+                ),
+            );
+            return UnsentRequest::Drop;
+        }
+
+        let local_model = self.config.local_model.clone();
+        let mut options = vec!["  r  retry now".to_string()];
+        if queueable {
+            options.push(
+                "  q  queue the prompt, and keep going; queued prompts are sent in order once the provider can be reached (see /queue)"
+                    .to_string(),
+            );
+        } else {
+            options.push("  w  wait until the provider can be reached, and retry then".to_string());
+        }
+        if let Some(local) = &local_model {
+            options.push(format!(
+                "  l  switch to {} (at {}) for the rest of the session",
+                local.model, local.base_url
+            ));
+        }
+        options.push("  n  give up on the request".to_string());
+        self.emit_line(
+            OutputKind::Error,
+            format!(
+                "offline: {} can't be reached ({reason})\n{}",
+                self.provider,
+                options.join("\n")
+            ),
+        );
+        if local_model.is_none() {
+            self.emit_line(
+                OutputKind::Info,
+                "(set local_model in config to be able to switch to a locally served model)",
+            );
+        }
+
+        let question = match (queueable, local_model.is_some()) {
+            (true, true) => "r/q/l/n? ",
+            (true, false) => "r/q/n? ",
+            (false, true) => "r/w/l/n? ",
+            (false, false) => "r/w/n? ",
+        };
+        loop {
+            let Ok(answer) = self.editor.readline(question) else {
+                return UnsentRequest::Drop;
+            };
+            match answer.trim() {
+                "r" => return UnsentRequest::Retry,
+                "q" if queueable => {
+                    self.emit_line(
+                        OutputKind::Info,
+                        "the prompt was queued; press Enter (on an empty prompt) to check whether the provider can be reached again",
+                    );
+                    return UnsentRequest::Queue;
+                }
+                "w" if !queueable => {
+                    if self.wait_until_reachable().await {
+                        return UnsentRequest::Retry;
+                    }
+                    return UnsentRequest::Drop;
+                }
+                "l" if local_model.is_some() => match self.switch_to_local_model().await {
+                    Ok(()) => return UnsentRequest::Retry,
+                    Err(e) => self.report_error(ErrorCategory::Provider, e),
+                },
+                "n" => {
+                    self.emit_line(
+                        OutputKind::Info,
+                        "the request was dropped; send the prompt again once the provider can be reached",
+                    );
+                    return UnsentRequest::Drop;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Holds back requests for a while after the provider rate limits one, queueing the prompt
+    /// (if it can be) to be sent after that.
+    fn handle_rate_limit(&mut self, error: anyhow::Error, queueable: bool) -> UnsentRequest {
+        warn!(provider = %self.provider, "rate limited by provider");
+        self.queued.hold_back(RATE_LIMIT_COOLDOWN);
+        if !queueable {
+            self.report_error(ErrorCategory::Provider, error);
+            return UnsentRequest::Drop;
+        }
+
+        if let Some(tx) = &self.debug_tx {
+            tx.send(DebugEvent::error(
+                ErrorCategory::Provider,
+                format!("{error:#}"),
+            ));
+        }
+        self.emit_line(
+            OutputKind::Warning,
+            format!(
+                "rate limited by {}; the prompt was queued, and can be sent in {}s (see /queue)",
+                self.provider,
+                RATE_LIMIT_COOLDOWN.as_secs()
+            ),
+        );
+        UnsentRequest::Queue
+    }
+
+    /// Sends a prompt the user submitted, after any that were queued before it; while the
+    /// provider can't be used, it's queued instead.
+    async fn submit_prompt(&mut self, prompt: String) {
+        self.send_queued().await;
+        if !self.interactive || (self.queued.is_empty() && self.provider_usable().await) {
+            self.handle_prompt(&prompt).await;
+            return;
+        }
+
+        self.queue_prompt(prompt);
+        self.emit_line(
+            OutputKind::Info,
+            format!(
+                "{} can't be used right now; the prompt was queued ({} in total), and will be sent once it can be (see /queue)",
+                self.provider,
+                self.queued.len()
+            ),
+        );
+    }
+
+    fn queue_prompt(&mut self, prompt: String) {
+        if self.sending_queued {
+            self.queued.push_front(prompt);
+        } else {
+            self.queued.push_back(prompt);
+        }
+
+        let endpoint = (self.connection_failures >= OFFLINE_AFTER_FAILURES)
+            .then(|| self.api_endpoint())
+            .flatten();
+        if let Ok(printer) = self.editor.create_external_printer() {
+            self.queued.watch(endpoint, printer);
+        }
+    }
+
+    /// Whether requests can be sent to the provider: it isn't rate limiting them, and (if it
+    /// couldn't be reached lately) it can be reached again.
+    async fn provider_usable(&mut self) -> bool {
+        if self.queued.rate_limited() {
+            return false;
+        }
+        if self.connection_failures < OFFLINE_AFTER_FAILURES {
+            return true;
+        }
+
+        let reachable = match self.api_endpoint() {
+            Some(endpoint) => endpoint.probe().await.is_ok(),
+            None => true,
+        };
+        if reachable {
+            self.connection_failures = 0;
+        }
+
+        reachable
+    }
+
+    /// Sends queued prompts in order, if the provider can be used; stops if one gets queued
+    /// again.
+    async fn send_queued(&mut self) {
+        if self.queued.is_empty() || !self.provider_usable().await {
+            return;
+        }
+
+        self.queued.stop_watching();
+        self.emit_line(
+            OutputKind::Info,
+            format!(
+                "{} can be used again; sending {} queued prompt(s)",
+                self.provider,
+                self.queued.len()
+            ),
+        );
+        self.sending_queued = true;
+        while let Some(prompt) = self.queued.pop_front() {
+            self.emit_line(OutputKind::Prompt, format!("> {prompt}\n"));
+            self.handle_prompt(&prompt).await;
+            if let Some(tx) = &self.debug_tx {
+                tx.send(DebugEvent::turn_complete(&self.chat_history));
+            }
+            if self.turn_queued {
+                break;
+            }
+        }
+        self.sending_queued = false;
+    }
+
+    /// Checks whether the provider can be reached every [`PROBE_INTERVAL`], until it can, or the
+    /// user gives up (via Ctrl+C). Returns whether it can.
+    async fn wait_until_reachable(&self) -> bool {
+        let Some(endpoint) = self.api_endpoint() else {
+            return true;
+        };
+
+        self.emit_line(
+            OutputKind::Info,
+            format!("waiting for {endpoint} to be reachable; press Ctrl+C to give up"),
+        );
+        let reachable = interrupts::cancellable(async {
+            while endpoint.probe().await.is_err() {
+                tokio::time::sleep(PROBE_INTERVAL).await;
+            }
+        })
+        .await
+        .is_some();
+
+        if reachable {
+            self.emit_line(
+                OutputKind::Info,
+                format!("{endpoint} can be reached again; sending the request"),
+            );
+        } else {
+            self.emit_line(
+                OutputKind::Info,
+                "\nstopped waiting; the request was dropped",
+            );
+        }
+
+        reachable
+    }
+
+    /// Switches the session to the local model in config, for the rest of the session.
+    async fn switch_to_local_model(&mut self) -> anyhow::Result<()> {
+        let local = self
+            .config
+            .local_model
+            .clone()
+            .context("no local model is configured")?;
+        let access = ProviderAccess {
+            api_key: String::new(),
+            base_url: Some(local.base_url.clone()),
+        };
+        let provider = Provider::OpenAICompatible;
+        self.agent = agent_for(&provider, access.clone(), &local.model, &self.config)
+            .await
+            .context("couldn't set up the local model")?;
+
+        info!(
+            model = local.model,
+            base_url = local.base_url,
+            "switched to local model"
+        );
+        self.provider = provider;
+        self.provider_access = Some(access);
+        self.streaming = true;
+        self.text_tool_calls = self.config.tool_calling_mode(&local.model) == ToolCallingMode::Text;
+        self.model_name = local.model;
+        self.connection_failures = 0;
+        self.emit_line(
+            OutputKind::Info,
+            format!(
+                "switched to {} (at {}) for the rest of the session",
+                self.model_name, local.base_url
+            ),
+        );
+
+        Ok(())
+    }
+
+    /// Prints an error, and emits it as a debug event.
+    fn report_error(&self, category: ErrorCategory, error: anyhow::Error) {
+        if let Some(tx) = &self.debug_tx {
+            tx.send(DebugEvent::error(category, format!("{error:#}")));
+        }
+        self.emit_line(OutputKind::Error, format!("error: {error:?}"));
+    }
+
+    fn record_audit(&self, event: AuditEvent<'_>) {
+        if let Some(audit_log) = &self.audit_log {
+            audit_log.record(event);
+        }
+    }
+
+    /// Notifies webhooks that the session ended, and waits for notifications still being sent,
+    /// since agx exits right after.
+    async fn notify_session_ended(&self, result: &anyhow::Result<()>) {
+        self.webhooks.notify(&Notification::SessionEnded {
+            error: result.as_ref().err().map(|e| format!("{e:#}")),
+        });
+        self.webhooks.flush().await;
+    }
+
+    fn trip_guardrail(&mut self, violation: GuardrailViolation) {
+        self.emit_line(OutputKind::Error, format!("guardrail tripped: {violation}"));
+        self.record_interruption(Interruption::GuardrailTripped);
+        self.record_audit(AuditEvent::GuardrailTripped {
+            reason: violation.to_string(),
+        });
+        self.webhooks.notify(&Notification::GuardrailTripped {
+            reason: violation.to_string(),
+        });
+        self.guardrail_violation = Some(violation);
+    }
+
+    /// Stops the conversation when a tool call trips a guardrail.
+    fn stop_for_guardrail(
+        &mut self,
+        violation: GuardrailViolation,
+        id: String,
+        call_id: Option<String>,
+        remaining_tool_calls: &[ToolCall],
+        mut tool_results: Vec<ToolResult>,
+    ) {
+        let result = make_tool_result(
+            id,
+            call_id,
+            ToolError::new("guardrail_tripped", violation.to_string()).to_json(),
+        );
+        self.push_tool_result(&mut tool_results, result);
+        self.push_skipped_results(
+            remaining_tool_calls,
+            &mut tool_results,
+            "tool call skipped because a guardrail tripped",
+        );
+        self.chat_history
+            .push(self.tool_results_message(tool_results));
+        self.trip_guardrail(violation);
+    }
+
+    async fn switch_workspace(&mut self, package: &str) -> anyhow::Result<()> {
+        let package = PathBuf::from(package);
+        if !is_path_in_workspace(&package) {
+            anyhow::bail!("workspace must be a path relative to the project root, without '..'");
+        }
+
+        let dir = self.workspace.root().join(&package);
+        if !tokio::fs::metadata(&dir)
+            .await
+            .with_context(|| format!("couldn't get metadata for {}", dir.to_string_lossy()))?
+            .is_dir()
+        {
+            anyhow::bail!("{} is not a directory", dir.to_string_lossy());
+        }
+
+        let is_root = package.components().all(|c| c == Component::CurDir);
+        if is_root {
+            self.workspace.set_package(None);
+            self.package_context = None;
+        } else {
+            self.package_context = get_project_context(&dir).await?;
+            self.workspace.set_package(Some(package));
+        }
+        self.refresh_toolchains().await;
+
+        Ok(())
+    }
+
+    /// Detects the workspace's toolchains; packages without a manifest of their own get the
+    /// project's.
+    async fn refresh_toolchains(&mut self) {
+        let mut toolchains = detect_toolchains(&self.workspace.dir()).await;
+        if toolchains.is_empty() && self.workspace.package().is_some() {
+            toolchains = detect_toolchains(self.workspace.root()).await;
+        }
+
+        self.toolchains = toolchains;
+    }
+
+    /// Returns the file a tool call is about to change (if it changes one), with its hash.
+    async fn file_hashes_before(&self, policy: &ConfirmationPolicy) -> Option<FileHashes> {
+        let ConfirmationPolicy::FileChange(path) = policy else {
+            return None;
+        };
+
+        Some(FileHashes {
+            path: path.clone(),
+            before: hash_file(&self.workspace.resolve(path)).await,
+            after: None,
+        })
+    }
+
+    async fn audit_tool_execution(&self, audit: ToolAudit<'_>) {
+        let file = match audit.file_before {
+            Some(hashes) => Some(FileHashes {
+                after: hash_file(&self.workspace.resolve(&hashes.path)).await,
+                ..hashes
+            }),
+            None => None,
+        };
+        let exit_code = match audit.policy {
+            ConfirmationPolicy::Command(_) => {
+                serde_json::from_str::<serde_json::Value>(audit.output)
+                    .ok()
+                    .and_then(|o| o.get("status_code").and_then(|c| c.as_i64()))
+                    .and_then(|c| i32::try_from(c).ok())
+            }
+            _ => None,
+        };
+        let session = self.session_name();
+
+        let execution = ToolExecution {
+            session: &session,
+            tool: &audit.tool_call.function.name,
+            id: &audit.tool_call.id,
+            arguments: &audit.tool_call.function.arguments,
+            approval: audit.approval,
+            file,
+            exit_code,
+            succeeded: audit.error_code.is_none(),
+            error: audit.error_code,
+        };
+        if let Err(e) = self.tool_audit_log.record(&execution).await {
+            warn!(
+                error = format!("{e:#}"),
+                "couldn't write to the tool audit log"
+            );
+        }
+    }
+
+    /// Starts tracking the changes tools make, in a shadow repository kept in the project's log
+    /// directory; replays don't change anything, and aren't tracked.
+    /// Warns about uncommitted changes in the workspace, so that they aren't mixed up with the
+    /// changes tools make; depending on config, they have to be acknowledged as well (see
+    /// [`Session::confirm_tool_call`]).
+    async fn check_dirty_workspace(&mut self) {
+        let policy = self.config.dirty_workspace();
+        if self.replayed_tool_results.is_some() || policy == DirtyWorkspacePolicy::Ignore {
+            return;
+        }
+
+        // projects that aren't git repositories can't be checked
+        let changes = match uncommitted_changes(&self.workspace.dir()).await {
+            Ok(changes) if !changes.is_empty() => changes,
+            Ok(_) | Err(_) => return,
+        };
+
+        self.emit_line(
+            OutputKind::Warning,
+            format!(
+                "the workspace has uncommitted changes in {} file(s); changes made by tools will be mixed with them, so consider committing or stashing them first",
+                changes.len()
+            ),
+        );
+        if policy == DirtyWorkspacePolicy::Confirm {
+            self.dirty_workspace = Some(changes.join("\n"));
+        }
+    }
+
+    async fn start_change_tracking(&mut self) {
+        // the shadow repository is shared with the session that's changing the project
+        if self.replayed_tool_results.is_some() || self.read_only {
+            return;
+        }
+
+        let git_dir = self.project_log_dir.join(SHADOW_REPO_DIR);
+        match ShadowRepo::open(git_dir, self.workspace.root().to_path_buf()).await {
+            Ok(shadow) => self.shadow = Some(shadow),
+            Err(e) => {
+                self.shadow = None;
+                self.report_error(
+                    ErrorCategory::Session,
+                    e.context("couldn't start tracking changes; /diff and /undo won't work"),
+                );
+            }
+        }
+    }
+
+    /// Snapshots the workspace before a tool call that might change it.
+    async fn snapshot_before_step(&self) -> Option<String> {
+        let shadow = self.shadow.as_ref()?;
+        match shadow.before_step().await {
+            Ok(snapshot) => Some(snapshot),
+            Err(e) => {
+                warn!(error = format!("{e:#}"), "couldn't snapshot workspace");
+                None
+            }
+        }
+    }
+
+    async fn record_step(&mut self, before: Option<String>, label: String) {
+        let (Some(shadow), Some(before)) = (self.shadow.as_mut(), before) else {
+            return;
+        };
+
+        if let Err(e) = shadow.record_step(before, label).await {
+            warn!(
+                error = format!("{e:#}"),
+                "couldn't record changes made by tool"
+            );
+        }
+    }
+
+    async fn show_changes(&self) {
+        let Some(shadow) = &self.shadow else {
+            self.emit_line(OutputKind::Warning, NO_CHANGE_TRACKING);
+            return;
+        };
+        if shadow.steps().is_empty() {
+            self.emit_line(OutputKind::Warning, "tools haven't changed anything yet");
+            return;
+        }
+
+        let mut lines = vec![];
+        for (i, step) in shadow.steps().iter().enumerate() {
+            let stat = shadow.stat(i + 1).await.unwrap_or_default();
+            lines.push(format!("{:>3}. {} ({})", i + 1, step.label, stat.trim()));
+        }
+        self.emit_line(OutputKind::Success, lines.join("\n"));
+    }
+
+    /// Flags instruction-like text in a tool result (to the model, and to the user), and defangs
+    /// parts of it that could pass it off as a system message.
+    fn flag_injections(&self, tool_call: &str, output: String) -> String {
+        let suspicions = injection::scan(&output);
+        if suspicions.is_empty() {
+            return output;
+        }
+
+        warn!(
+            tool_call,
+            count = suspicions.len(),
+            "tool result looks like it contains a prompt injection"
+        );
+        let flagged = suspicions
+            .iter()
+            .map(|s| format!("  - {s}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        self.emit_line(
+            OutputKind::Warning,
+            format!(
+                "the result of {tool_call} looks like it contains a prompt injection; the model has been told not to follow it:\n{flagged}"
+            ),
+        );
+
+        injection::neutralize(&output, &suspicions)
+    }
+
+    /// Lists the files tools changed during a turn, so that changes aren't buried in the
+    /// turn's output.
+    async fn summarize_turn_changes(&self, steps_before: usize) {
+        let Some(shadow) = &self.shadow else {
+            return;
+        };
+
+        let changes = match shadow.changed_files(steps_before).await {
+            Ok(changes) if !changes.is_empty() => changes,
+            Ok(_) => return,
+            Err(e) => {
+                warn!(
+                    error = format!("{e:#}"),
+                    "couldn't summarize changes made during turn"
+                );
+                return;
+            }
+        };
+
+        if let Some(tx) = &self.debug_tx {
+            let files = changes
+                .iter()
+                .map(|c| FileDiffStat {
+                    path: c.path.clone(),
+                    change: c.kind,
+                    lines_added: c.lines.map(|(added, _)| added),
+                    lines_removed: c.lines.map(|(_, removed)| removed),
+                })
+                .collect();
+            tx.send(DebugEvent::turn_diff_stat(self.turns, files));
+        }
+
+        let mut lines = vec![format!("\nfiles changed this turn ({}):", changes.len())];
+        lines.extend(changes.iter().map(|c| format!("  {c}")));
+        self.emit_line(OutputKind::Info, lines.join("\n"));
+    }
+
+    async fn show_diff(&self, step: Option<usize>) {
+        let Some(shadow) = &self.shadow else {
+            self.emit_line(OutputKind::Warning, NO_CHANGE_TRACKING);
+            return;
+        };
+
+        match shadow.diff(step, self.workspace.diff_options()).await {
+            Ok(diff) if diff.is_empty() => {
+                self.emit_line(OutputKind::Warning, "tools haven't changed anything yet")
+            }
+            Ok(diff) => self.emit_line(OutputKind::Response, diff),
+            Err(e) => self.report_error(ErrorCategory::Session, e),
+        }
+    }
+
+    /// Prints a file in the workspace (or some of its lines), with line numbers; the model isn't
+    /// involved.
+    async fn view_file(&self, path: &str, range: Option<&str>) {
+        let full_path = self.workspace.resolve(path);
+        let rendered = match tokio::fs::read_to_string(&full_path).await {
+            Ok(contents) => render_file(&full_path, &contents, range),
+            Err(e) => Err(anyhow::Error::from(e).context(format!("couldn't read {path}"))),
+        };
+
+        match rendered {
+            Ok(rendered) => self.emit_line(OutputKind::Response, rendered),
+            Err(e) => self.report_error(ErrorCategory::Session, e),
+        }
+    }
+
+    /// Reverts the changes made after a step (the last step alone, if none is given); the model
+    /// is told about it along with the next prompt.
+    async fn undo(&mut self, to: Option<usize>) {
+        let Some(shadow) = self.shadow.as_mut() else {
+            self.emit_line(OutputKind::Warning, NO_CHANGE_TRACKING);
+            return;
+        };
+        let steps = shadow.steps().len();
+        if steps == 0 {
+            self.emit_line(OutputKind::Warning, "there are no changes to undo");
+            return;
+        }
+
+        let undo = match shadow.undo(to.unwrap_or(steps - 1)).await {
+            Ok(undo) => undo,
+            Err(e) => {
+                self.report_error(ErrorCategory::Session, e);
+                return;
+            }
+        };
+
+        if !undo.undone.is_empty() {
+            let labels = undo
+                .undone
+                .iter()
+                .map(|s| s.label.as_str())
+                .collect::<Vec<_>>();
+            self.emit_line(OutputKind::Success, format!("undid: {}", labels.join(", ")));
+            let reminder = reminders::undo_reminder(&labels);
+            self.undo_reminder = Some(match self.undo_reminder.take() {
+                Some(earlier) => format!("{earlier}\n{reminder}"),
+                None => reminder,
+            });
+        }
+        if let Some(e) = undo.error {
+            self.report_error(ErrorCategory::Session, e);
+        }
+    }
+
+    /// Wraps up a turn the user cancelled via Ctrl+C; what the turn produced is kept in history,
+    /// unless Ctrl+C is pressed again shortly after, in which case the turn is discarded.
+    async fn finish_cancelled_turn(&mut self, turn_start: usize, interruption: Interruption) {
+        self.turn_error = Some("interrupted by user".to_string());
+        if let Some(tx) = &self.debug_tx {
+            tx.send(DebugEvent::interrupted());
+        }
+        self.emit_line(
+            OutputKind::Error,
+            format!(
+                "\ninterrupted; press Ctrl+C again within {}s to discard this turn",
+                interrupts::DOUBLE_INTERRUPT_WINDOW.as_secs()
+            ),
+        );
+
+        if !interrupts::pressed_again().await {
+            self.record_interruption(interruption);
+            return;
+        }
+
+        self.record_interruption(Interruption::DiscardedTurn);
+        self.chat_history.truncate(turn_start);
+        self.response_truncated = false;
+        self.emit_line(
+            OutputKind::Error,
+            "turn discarded (changes made by tools can be reverted via /undo)",
+        );
+    }
+
+    fn record_interruption(&mut self, interruption: Interruption) {
+        info!(
+            reason = interruption.as_str(),
+            turn = self.turns,
+            "turn interrupted"
+        );
+        self.stats.record_interruption(interruption);
+    }
+
+    /// Keeps track of how the user responds to tool calls, which hints at how well the model
+    /// follows their (and the project's) policies.
+    fn record_tool_confirmation(
+        &mut self,
+        tool_name: &'static str,
+        confirmation: &ToolCallConfirmation,
+    ) {
+        let rejection = match confirmation {
+            ToolCallConfirmation::Approved | ToolCallConfirmation::AutoApproved => None,
+            ToolCallConfirmation::PartiallyApproved(_) => Some(Rejection::PartiallyApproved),
+            ToolCallConfirmation::Rejected => Some(Rejection::Rejected),
+            ToolCallConfirmation::FeedbackProvided(text) => {
+                Some(Rejection::RejectedWithFeedback(text.clone()))
+            }
+        };
+        if let Some(rejection) = &rejection {
+            let feedback = match rejection {
+                Rejection::RejectedWithFeedback(text) => Some(text.as_str()),
+                _ => None,
+            };
+            info!(
+                tool = tool_name,
+                reason = rejection.code(),
+                feedback,
+                turn = self.turns,
+                "tool call rejected"
+            );
+        }
+        self.stats.record_tool_confirmation(tool_name, rejection);
+    }
+
+    /// Re-reads config (user and project level) and context files, so that changes to them apply
+    /// to the rest of the session (including the environment commands are run with). Approved
+    /// commands are replaced with the ones in config (ones approved during the session are saved
+    /// there), unless the approval mode is strict.
+    async fn reload(&mut self) -> anyhow::Result<()> {
+        let project_config = get_project_config().await?;
+        let config = match &self.user_config_dir {
+            Some(dir) => get_user_config(dir)
+                .await?
+                .defaults
+                .layered_with(project_config),
+            None => project_config,
+        };
+
+        let project_context = get_project_context(&self.project_dir).await?;
+        let package_context = match self.workspace.package() {
+            Some(package) => get_project_context(self.workspace.root().join(package)).await?,
+            None => None,
+        };
+
+        if self.approval_mode != ApprovalMode::Strict {
+            self.approvals.approved_commands = config.approved_commands.clone();
+        }
+        // falling back to text tool calls (when the model doesn't support native ones) sticks
+        if config.tool_calling_mode(&self.model_name) == ToolCallingMode::Text {
+            self.text_tool_calls = true;
+        }
+        self.workspace
+            .set_command_settings(CommandSettings::from(&config));
+        self.workspace.set_max_write_bytes(config.max_write_bytes());
+        self.workspace.set_formatters(config.formatters.clone());
+        self.workspace.set_linters(config.linters.clone());
+        self.workspace.set_diff_mode(config.diff_mode());
+        self.webhooks.set_hooks(config.webhooks.clone());
+        self.title.set_enabled(config.terminal_title());
+        self.config = config;
+        self.project_context = project_context;
+        self.package_context = package_context;
+        self.refresh_toolchains().await;
+
+        Ok(())
+    }
+
+    /// Switches to (or back from) the permissive approval mode, where tool calls are approved
+    /// without confirmation.
+    fn set_auto_approval(&mut self, on: bool) {
+        if on && self.approval_mode != ApprovalMode::Permissive {
+            self.approval_mode_before_auto = Some(self.approval_mode);
+            self.approval_mode = ApprovalMode::Permissive;
+        } else if !on && self.approval_mode == ApprovalMode::Permissive {
+            self.approval_mode = self.approval_mode_before_auto.take().unwrap_or_default();
+        }
+
+        if on {
+            self.emit_line(
+                OutputKind::Warning,
+                "auto-approve is on; file changes and commands will run without confirmation",
+            );
+        } else if is_hitl_skipped() {
+            self.emit_line(
+                OutputKind::Warning,
+                "auto-approve stays on, since AGX_SKIP_HITL is set",
+            );
+        } else {
+            self.emit_line(
+                OutputKind::Success,
+                format!(
+                    "auto-approve is off; tool calls are confirmed as per the {} approval mode",
+                    self.approval_mode
+                ),
+            );
+        }
+    }
+
+    fn skips_confirmation(&self) -> bool {
+        is_hitl_skipped() || self.approval_mode == ApprovalMode::Permissive
+    }
+
     fn get_preamble(&self) -> String {
         let now = Utc::now().format("%A, %B %d, %Y %H:%M UTC").to_string();
-        let system_prompt = match &self.project_context {
+        let mut system_prompt = match &self.project_context {
             Some(p) => Cow::Owned(format!(
                 "{}
 
@@ -580,23 +3517,120 @@ The following is context specific to this project:
             )),
             None => Cow::Borrowed(SYSTEM_PROMPT),
         };
+
+        let package = self.workspace.package();
+        if let (Some(package), Some(c)) = (&package, &self.package_context) {
+            system_prompt = Cow::Owned(format!(
+                "{}
+
+The following is context specific to the package you're working in ({}):
+
+{}",
+                system_prompt,
+                package.to_string_lossy(),
+                c
+            ));
+        }
+
+        if !self.toolchains.is_empty() {
+            system_prompt = Cow::Owned(format!(
+                "{}
+
+The following toolchains were detected in the project (via their manifests); unless the context above says otherwise, use these commands to build the project, and to verify your changes (by running the tests and lints) before you're done:
+
+{}",
+                system_prompt,
+                self.toolchains
+                    .iter()
+                    .map(|t| format!("- {t}"))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            ));
+        }
+
+        if self.read_only {
+            system_prompt = Cow::Owned(format!(
+                "{}
+
+This session is read-only, since another session is running in the project: you can read files, but can't change them or run commands. If the user asks for changes, describe what you'd change instead.",
+                system_prompt
+            ));
+        }
+
+        if let Some(instructions) = &self.task_instructions {
+            system_prompt = Cow::Owned(format!(
+                "{}
+
+The following are instructions for the task at hand:
+
+{}",
+                system_prompt, instructions
+            ));
+        }
+
+        let project_root = match &package {
+            Some(_) => format!(
+                "\nProject root (shared files live here): {}",
+                self.project_dir.to_string_lossy()
+            ),
+            None => String::new(),
+        };
+
         format!(
             "{}
 
 ---
 Extra information for you
-Current directory: {}
+Current directory: {}{}
 Current date/time: {}
 ",
             system_prompt,
-            self.project_dir.to_string_lossy(),
+            self.workspace.dir().to_string_lossy(),
+            project_root,
             now,
         )
     }
 }
 
-fn print_error(error: anyhow::Error) {
-    println!("{}", format!("error: {:?}", error).red());
+async fn read_json_schema(path: &str) -> anyhow::Result<serde_json::Value> {
+    let bytes = tokio::fs::read(path)
+        .await
+        .with_context(|| format!(r#"couldn't read JSON schema from "{path}""#))?;
+
+    serde_json::from_slice(&bytes)
+        .with_context(|| format!(r#"couldn't parse JSON schema in "{path}""#))
+}
+
+fn parse_json_response(response: &str) -> Result<serde_json::Value, serde_json::Error> {
+    // models sometimes wrap JSON in code fences despite being asked not to
+    let trimmed = response.trim();
+    let json = trimmed
+        .strip_prefix("```json")
+        .or_else(|| trimmed.strip_prefix("```"))
+        .and_then(|s| s.strip_suffix("```"))
+        .unwrap_or(trimmed);
+
+    serde_json::from_str(json)
+}
+
+fn is_tool_calling_unsupported_error(error: &anyhow::Error) -> bool {
+    let error = format!("{error:#}").to_lowercase();
+    [
+        "does not support tools",
+        "tools is not supported",
+        "tool use is not supported",
+        "support tool use",
+        "tool calling is not supported",
+    ]
+    .iter()
+    .any(|p| error.contains(p))
+}
+
+// TODO: temporary hack to skip HITL
+fn is_hitl_skipped() -> bool {
+    std::env::var("AGX_SKIP_HITL")
+        .map(|s| s == "1")
+        .unwrap_or_default()
 }
 
 fn make_tool_result(id: String, call_id: Option<String>, text: impl Into<String>) -> ToolResult {
@@ -607,6 +3641,14 @@ fn make_tool_result(id: String, call_id: Option<String>, text: impl Into<String>
     }
 }
 
+/// Parses the optional step number passed to /diff and /undo.
+fn parse_step(arg: &str) -> Result<Option<usize>, ()> {
+    match arg.trim() {
+        "" => Ok(None),
+        n => n.parse().map(Some).map_err(|_| ()),
+    }
+}
+
 fn get_token_count_repr(count: u64) -> String {
     if count >= 1_000 {
         format!("{:.1}k", count as f64 / 1_000.0)
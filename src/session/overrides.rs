@@ -0,0 +1,125 @@
+use std::fmt::Display;
+
+/// Output controls that can be changed mid-session via `/set`; they apply to every request
+/// made (or diff shown) after they're set.
+#[derive(Debug, Default)]
+pub struct OutputOverrides {
+    pub max_output: Option<u64>,
+    pub stop_sequences: Vec<String>,
+    /// Unchanged lines shown around changes in diffs.
+    pub diff_context: Option<usize>,
+}
+
+impl OutputOverrides {
+    /// Applies a setting of the form "<key> <value>"; "off" resets a setting.
+    pub fn set(&mut self, setting: &str) -> anyhow::Result<()> {
+        let (key, value) = setting
+            .split_once(char::is_whitespace)
+            .map(|(k, v)| (k, v.trim()))
+            .unwrap_or((setting, ""));
+
+        match (key, value) {
+            (_, "") => anyhow::bail!(r#"no value provided for "{key}""#),
+            ("max_output", "off") => self.max_output = None,
+            ("max_output", v) => {
+                let max_output =
+                    v.parse::<u64>().ok().filter(|n| *n > 0).ok_or_else(|| {
+                        anyhow::anyhow!("max_output needs to be a positive number")
+                    })?;
+                self.max_output = Some(max_output);
+            }
+            ("stop", "off") => self.stop_sequences.clear(),
+            ("stop", v) => {
+                // allow specifying sequences with newlines/tabs
+                let sequence = v.replace("\\n", "\n").replace("\\t", "\t");
+                if !self.stop_sequences.contains(&sequence) {
+                    self.stop_sequences.push(sequence);
+                }
+            }
+            ("diff_context", "off") => self.diff_context = None,
+            ("diff_context", v) => {
+                let lines = v.parse::<usize>().map_err(|_| {
+                    anyhow::anyhow!("diff_context needs to be a number of lines (0 or more)")
+                })?;
+                self.diff_context = Some(lines);
+            }
+            _ => anyhow::bail!(
+                r#"unknown setting "{key}"; allowed: [max_output, stop, diff_context]"#
+            ),
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for OutputOverrides {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let max_output = self
+            .max_output
+            .map(|n| n.to_string())
+            .unwrap_or("default".to_string());
+        let stop_sequences = if self.stop_sequences.is_empty() {
+            "none".to_string()
+        } else {
+            self.stop_sequences
+                .iter()
+                .map(|s| format!("{s:?}"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        let diff_context = self
+            .diff_context
+            .map(|n| n.to_string())
+            .unwrap_or("default".to_string());
+
+        write!(
+            f,
+            r#"settings:
+- max_output: {max_output}
+- stop: {stop_sequences}
+- diff_context: {diff_context}
+"#
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::assert_snapshot;
+
+    #[test]
+    fn setting_and_resetting_overrides_works() -> anyhow::Result<()> {
+        // GIVEN
+        let mut overrides = OutputOverrides::default();
+
+        // WHEN
+        overrides.set("max_output 2000")?;
+        overrides.set("stop END")?;
+        overrides.set(r"stop \n\n")?;
+        overrides.set("stop END")?;
+        overrides.set("diff_context 10")?;
+        let with_overrides = overrides.to_string();
+        overrides.set("max_output off")?;
+        overrides.set("stop off")?;
+        overrides.set("diff_context off")?;
+
+        // THEN
+        assert_snapshot!(with_overrides, @r#"
+        settings:
+        - max_output: 2000
+        - stop: "END", "\n\n"
+        - diff_context: 10
+        "#);
+        assert_snapshot!(overrides.to_string(), @r"
+        settings:
+        - max_output: default
+        - stop: none
+        - diff_context: default
+        ");
+        assert!(overrides.set("max_output lots").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,158 @@
+use super::connectivity::{ApiEndpoint, PROBE_INTERVAL};
+use colored::Colorize;
+use rustyline::ExternalPrinter;
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+// Prompts submitted while the provider can't be used (it can't be reached, or it's rate limiting
+// requests) are queued, rather than lost, and sent in order once it can be used again. Since the
+// session can't act while the user is typing a prompt, the queue is sent with the next input
+// (an empty one will do); the user is told when that's worth doing.
+
+/// How long requests are held back after the provider rate limits one.
+pub const RATE_LIMIT_COOLDOWN: Duration = Duration::from_secs(30);
+/// Queued prompts are listed by their first line, cut down to this many characters.
+const MAX_LISTED_CHARS: usize = 80;
+
+#[derive(Default)]
+pub struct PromptQueue {
+    prompts: VecDeque<String>,
+    /// Set while the provider is rate limiting requests.
+    not_before: Option<Instant>,
+    /// Tells the user once the queue can be sent.
+    watcher: Option<JoinHandle<()>>,
+}
+
+impl PromptQueue {
+    pub fn is_empty(&self) -> bool {
+        self.prompts.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.prompts.len()
+    }
+
+    pub fn push_back(&mut self, prompt: String) {
+        self.prompts.push_back(prompt);
+    }
+
+    /// For prompts that were taken off the queue, but couldn't be sent after all.
+    pub fn push_front(&mut self, prompt: String) {
+        self.prompts.push_front(prompt);
+    }
+
+    pub fn pop_front(&mut self) -> Option<String> {
+        self.prompts.pop_front()
+    }
+
+    /// Drops every queued prompt; returns how many there were.
+    pub fn clear(&mut self) -> usize {
+        self.stop_watching();
+        std::mem::take(&mut self.prompts).len()
+    }
+
+    pub fn hold_back(&mut self, duration: Duration) {
+        self.not_before = Some(Instant::now() + duration);
+    }
+
+    pub fn rate_limited(&self) -> bool {
+        self.not_before.is_some_and(|t| Instant::now() < t)
+    }
+
+    /// Waits (in the background) for the rate limit to lapse, and for `endpoint` (if any) to be
+    /// reachable, then prints that the queue can be sent.
+    pub fn watch<P>(&mut self, endpoint: Option<ApiEndpoint>, mut printer: P)
+    where
+        P: ExternalPrinter + Send + 'static,
+    {
+        self.stop_watching();
+        let not_before = self.not_before;
+        self.watcher = Some(tokio::spawn(async move {
+            if let Some(not_before) = not_before {
+                tokio::time::sleep_until(not_before).await;
+            }
+            if let Some(endpoint) = endpoint {
+                while endpoint.probe().await.is_err() {
+                    tokio::time::sleep(PROBE_INTERVAL).await;
+                }
+            }
+
+            let notice = "the provider can be used again; press Enter to send the queued prompts"
+                .yellow()
+                .to_string();
+            // the printer blocks until the prompt being edited has been redrawn
+            _ = tokio::task::spawn_blocking(move || printer.print(format!("\n{notice}\n"))).await;
+        }));
+    }
+
+    pub fn stop_watching(&mut self) {
+        if let Some(watcher) = self.watcher.take() {
+            watcher.abort();
+        }
+    }
+}
+
+impl Drop for PromptQueue {
+    fn drop(&mut self) {
+        self.stop_watching();
+    }
+}
+
+impl Display for PromptQueue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.prompts.is_empty() {
+            return writeln!(f, "no prompts are queued");
+        }
+
+        writeln!(
+            f,
+            "queued prompts (sent in this order once the provider can be used):"
+        )?;
+        for (i, prompt) in self.prompts.iter().enumerate() {
+            let first_line = prompt.lines().find(|l| !l.trim().is_empty()).unwrap_or("");
+            let mut listed = first_line
+                .chars()
+                .take(MAX_LISTED_CHARS)
+                .collect::<String>();
+            if first_line.chars().count() > MAX_LISTED_CHARS || prompt.trim().lines().count() > 1 {
+                listed.push('…');
+            }
+            writeln!(f, " {}. {listed}", i + 1)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::assert_snapshot;
+
+    #[test]
+    fn queued_prompts_are_listed_in_the_order_they_are_sent() {
+        // GIVEN
+        let mut queue = PromptQueue::default();
+        let empty = queue.to_string();
+
+        // WHEN
+        queue.push_back("add a --verbose flag to the CLI".to_string());
+        queue.push_back(
+            "then write tests for it\n\nthey should cover the flag being passed more than once"
+                .to_string(),
+        );
+        queue.push_front("why does the config loader ignore XDG_CONFIG_HOME?".to_string());
+
+        // THEN
+        assert_snapshot!(format!("{empty}{queue}"), @r"
+        no prompts are queued
+        queued prompts (sent in this order once the provider can be used):
+         1. why does the config loader ignore XDG_CONFIG_HOME?
+         2. add a --verbose flag to the CLI
+         3. then write tests for it…
+        ");
+    }
+}
@@ -0,0 +1,170 @@
+use super::guardrails::Guardrails;
+use super::hitl::Approvals;
+use crate::tools::Workspace;
+
+// Sent along with each user prompt so that the model knows about the policies in effect,
+// instead of discovering them via failed or rejected tool calls.
+
+// Sessions running under guardrails (via `agx auto`) are told about the paths they can't change,
+// and how many requests to the model they have left.
+pub fn system_reminder(
+    approvals: &Approvals,
+    hitl_skipped: bool,
+    workspace: &Workspace,
+    guardrails: Option<&Guardrails>,
+) -> String {
+    let file_changes = if hitl_skipped || approvals.fs_changes {
+        "approved for this session"
+    } else {
+        "need the user's confirmation"
+    };
+
+    let commands = if hitl_skipped {
+        "approved for this session".to_string()
+    } else {
+        format!(
+            "need the user's confirmation, unless they match these approved patterns: {}",
+            approvals.approved_commands
+        )
+    };
+
+    let workspace = match workspace.package() {
+        Some(package) => format!(
+            "scoped to the package {}; paths are resolved relative to it, and files can only be created/edited within it",
+            package.to_string_lossy()
+        ),
+        None => "the project root; files can only be created/edited within it".to_string(),
+    };
+
+    let mut lines = vec![
+        format!("- file changes: {file_changes}"),
+        format!("- commands: {commands}"),
+        format!("- workspace: {workspace}"),
+    ];
+    if let Some(guardrails) = guardrails {
+        if !guardrails.protected_patterns().is_empty() {
+            lines.push(format!(
+                "- protected paths (relative to the project root; these can't be created/edited): {}",
+                guardrails.protected_patterns().join(", ")
+            ));
+        }
+        lines.push(format!(
+            "- iteration budget: {} more request(s) to the model, after which the task is stopped",
+            guardrails.remaining_iterations()
+        ));
+    }
+
+    format!(
+        "<system-reminder>\n{}\n</system-reminder>",
+        lines.join("\n")
+    )
+}
+
+// Sent along with tool results when a tool keeps failing, so that the model changes course
+// instead of retrying the same call.
+pub fn tool_failure_reminder(tool_name: &str, codes: &[&str]) -> String {
+    let mut unique_codes = codes.to_vec();
+    unique_codes.sort();
+    unique_codes.dedup();
+
+    let suggestion = if codes.contains(&"old_str_not_found") {
+        "read the file again before editing it, and copy old_str exactly from its current contents"
+    } else {
+        "re-read the files involved, double check the arguments against the tool's parameters, or try a different approach"
+    };
+
+    format!(
+        "<system-reminder>
+{tool_name} has failed {} times in a row ({}); don't retry it as is: {suggestion}.
+</system-reminder>",
+        codes.len(),
+        unique_codes.join(", "),
+    )
+}
+
+// Sent along with the next prompt after the user undoes changes made by tools, so that the model
+// doesn't assume they're still in place.
+pub fn undo_reminder(steps: &[&str]) -> String {
+    format!(
+        "<system-reminder>
+The user reverted the changes made by these tool calls: {}. Re-read files before relying on their contents.
+</system-reminder>",
+        steps.join("; ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::AutopilotConfig;
+    use insta::assert_snapshot;
+    use std::path::PathBuf;
+
+    #[test]
+    fn system_reminder_reflects_approvals_and_workspace() {
+        // GIVEN
+        let approvals = Approvals {
+            fs_changes: true,
+            ..Default::default()
+        };
+        let workspace = Workspace::new("/projects/monorepo");
+        workspace.set_package(Some(PathBuf::from("packages/api")));
+
+        // WHEN
+        let reminder = system_reminder(&approvals, false, &workspace, None);
+
+        // THEN
+        assert_snapshot!(reminder, @r"
+        <system-reminder>
+        - file changes: approved for this session
+        - commands: need the user's confirmation, unless they match these approved patterns: none
+        - workspace: scoped to the package packages/api; paths are resolved relative to it, and files can only be created/edited within it
+        </system-reminder>
+        ");
+    }
+
+    #[test]
+    fn system_reminder_reflects_guardrails() -> anyhow::Result<()> {
+        // GIVEN
+        let config = AutopilotConfig {
+            max_iterations: Some(20),
+            protected_paths: vec!["Cargo.lock".to_string(), ".github/**".to_string()],
+            ..Default::default()
+        };
+        let mut guardrails = Guardrails::new(&config, None)?;
+        guardrails.start_iteration()?;
+        let workspace = Workspace::new("/projects/shop");
+
+        // WHEN
+        let reminder = system_reminder(&Approvals::default(), true, &workspace, Some(&guardrails));
+
+        // THEN
+        assert_snapshot!(reminder, @r"
+        <system-reminder>
+        - file changes: approved for this session
+        - commands: approved for this session
+        - workspace: the project root; files can only be created/edited within it
+        - protected paths (relative to the project root; these can't be created/edited): Cargo.lock, .github/**
+        - iteration budget: 19 more request(s) to the model, after which the task is stopped
+        </system-reminder>
+        ");
+
+        Ok(())
+    }
+
+    #[test]
+    fn tool_failure_reminder_suggests_rereading_files_for_edit_misses() {
+        // GIVEN
+        let codes = ["old_str_not_found", "file_not_found", "old_str_not_found"];
+
+        // WHEN
+        let reminder = tool_failure_reminder("edit_file", &codes);
+
+        // THEN
+        assert_snapshot!(reminder, @r"
+        <system-reminder>
+        edit_file has failed 3 times in a row (file_not_found, old_str_not_found); don't retry it as is: read the file again before editing it, and copy old_str exactly from its current contents.
+        </system-reminder>
+        ");
+    }
+}
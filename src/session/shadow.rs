@@ -0,0 +1,439 @@
+use crate::domain::{DiffMode, FileChangeKind};
+use crate::helpers::DiffOptions;
+use anyhow::Context;
+use std::fmt::Display;
+use std::path::PathBuf;
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+pub const SHADOW_REPO_DIR: &str = "shadow.git";
+
+/// A git repository of the workspace, kept in agx's state directory, that's committed to after
+/// every change made by tools. The workspace's own repository (if there's one) isn't touched;
+/// the shadow repository only shares its working tree (and respects its ignore files).
+pub struct ShadowRepo {
+    git_dir: PathBuf,
+    work_tree: PathBuf,
+    /// Snapshot of the workspace when change tracking started.
+    baseline: String,
+    steps: Vec<Step>,
+}
+
+/// A change made to the workspace by a tool call.
+#[derive(Debug, Clone)]
+pub struct Step {
+    pub label: String,
+    /// Snapshot taken right before the tool call, so that changes made in between steps (eg. by
+    /// the user) aren't attributed to it.
+    before: String,
+    after: String,
+}
+
+/// A file changed over a range of steps, with the number of lines added and removed (unknown
+/// for binary files).
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileChange {
+    pub path: String,
+    pub kind: FileChangeKind,
+    pub lines: Option<(usize, usize)>,
+}
+
+impl Display for FileChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (symbol, kind) = match self.kind {
+            FileChangeKind::Created => ('+', "created"),
+            FileChangeKind::Edited => ('~', "edited"),
+            FileChangeKind::Deleted => ('-', "deleted"),
+        };
+        write!(f, "{symbol} {} ({kind}", self.path)?;
+        match self.lines {
+            Some((added, removed)) => write!(f, ", +{added} -{removed})"),
+            None => write!(f, ", binary)"),
+        }
+    }
+}
+
+/// What [`ShadowRepo::undo`] reverted (latest first), and why it stopped short, if it did.
+pub struct Undo {
+    pub undone: Vec<Step>,
+    pub error: Option<anyhow::Error>,
+}
+
+impl ShadowRepo {
+    /// Opens (or creates) the shadow repository, and snapshots the workspace as the baseline
+    /// that steps are recorded against.
+    pub async fn open(git_dir: PathBuf, work_tree: PathBuf) -> anyhow::Result<Self> {
+        let mut repo = Self {
+            git_dir,
+            work_tree,
+            baseline: String::new(),
+            steps: vec![],
+        };
+
+        if !repo.git_dir.join("HEAD").exists() {
+            repo.git(&["init", "--quiet"])
+                .await
+                .context("couldn't create shadow repository")?;
+        }
+        repo.baseline = repo.snapshot("baseline").await?;
+
+        Ok(repo)
+    }
+
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+
+    /// Snapshots the workspace before a tool call that might change it; the snapshot is passed
+    /// to [`ShadowRepo::record_step`] once the tool call is done.
+    pub async fn before_step(&self) -> anyhow::Result<String> {
+        self.snapshot("changes made outside agx").await
+    }
+
+    /// Records what a tool call changed, if anything. Returns the step's number.
+    pub async fn record_step(
+        &mut self,
+        before: String,
+        label: impl Into<String>,
+    ) -> anyhow::Result<Option<usize>> {
+        let label = label.into();
+        let after = self.snapshot(&label).await?;
+        if after == before {
+            return Ok(None);
+        }
+
+        self.steps.push(Step {
+            label,
+            before,
+            after,
+        });
+
+        Ok(Some(self.steps.len()))
+    }
+
+    /// Returns the changes made in a step (numbered from 1), or all the changes made since the
+    /// baseline if no step is given.
+    pub async fn diff(&self, step: Option<usize>, options: DiffOptions) -> anyhow::Result<String> {
+        let (from, to) = self.range(step)?;
+        let context = format!("--unified={}", options.context_lines);
+        let mut args = vec!["diff", "--no-color", context.as_str()];
+        if options.mode == DiffMode::Word {
+            args.push("--word-diff=plain");
+        }
+        args.extend([from, to]);
+
+        self.git(&args).await
+    }
+
+    /// Returns a summary of the changes made in a step (eg. "1 file changed, 2 insertions(+)").
+    pub async fn stat(&self, step: usize) -> anyhow::Result<String> {
+        let (from, to) = self.range(Some(step))?;
+        self.git(&["diff", "--shortstat", from, to]).await
+    }
+
+    /// Returns the files changed by the steps after the one given (numbered from 1; 0 covers
+    /// all of them). Changes made in between those steps (eg. by the user) are included.
+    pub async fn changed_files(&self, after: usize) -> anyhow::Result<Vec<FileChange>> {
+        let (Some(first), Some(last)) = (self.steps.get(after), self.steps.last()) else {
+            return Ok(vec![]);
+        };
+        let range = [first.before.as_str(), last.after.as_str()];
+
+        let statuses = self
+            .git(&[&["diff", "--no-renames", "--name-status"][..], &range].concat())
+            .await?;
+        let line_counts = self
+            .git(&[&["diff", "--no-renames", "--numstat"][..], &range].concat())
+            .await?;
+
+        Ok(parse_file_changes(&statuses, &line_counts))
+    }
+
+    /// Reverts the steps after the one given (0 reverts all of them), latest first. Changes
+    /// made to the workspace in between steps are left as is; if a step can't be reverted
+    /// because the files it changed have been changed since, the ones after it stay reverted.
+    pub async fn undo(&mut self, to: usize) -> anyhow::Result<Undo> {
+        if to >= self.steps.len() {
+            anyhow::bail!(
+                "there are no steps after step {to}; steps recorded: {}",
+                self.steps.len()
+            );
+        }
+
+        let mut undo = Undo {
+            undone: vec![],
+            error: None,
+        };
+        while let Some(step) = self.steps.last()
+            && self.steps.len() > to
+        {
+            if let Err(e) = self.revert(step).await {
+                undo.error = Some(e.context(format!(
+                    "couldn't undo step {} ({}); the files it changed have been changed since",
+                    self.steps.len(),
+                    step.label
+                )));
+                break;
+            }
+            undo.undone.extend(self.steps.pop());
+        }
+        self.snapshot(&format!("undo to step {to}")).await?;
+
+        Ok(undo)
+    }
+
+    async fn revert(&self, step: &Step) -> anyhow::Result<()> {
+        // the patch is kept as is, since files that aren't UTF-8 encoded would get mangled if it
+        // were decoded
+        let patch = self
+            .git_output(&["diff", "--binary", &step.after, &step.before])
+            .await?;
+        self.apply(&patch).await
+    }
+
+    fn range(&self, step: Option<usize>) -> anyhow::Result<(&str, &str)> {
+        match step {
+            None => Ok((
+                &self.baseline,
+                self.steps.last().map_or(&self.baseline, |s| &s.after),
+            )),
+            Some(n) => match n.checked_sub(1).and_then(|i| self.steps.get(i)) {
+                Some(step) => Ok((&step.before, &step.after)),
+                None => anyhow::bail!("there's no step {n}; steps recorded: {}", self.steps.len()),
+            },
+        }
+    }
+
+    /// Commits the workspace as it is, unless it's unchanged since the last commit. Returns the
+    /// commit.
+    async fn snapshot(&self, message: &str) -> anyhow::Result<String> {
+        self.git(&["add", "--all"])
+            .await
+            .context("couldn't snapshot workspace")?;
+
+        let has_commits = self
+            .git(&["rev-parse", "--verify", "--quiet", "HEAD"])
+            .await
+            .is_ok();
+        let unchanged = has_commits
+            && self
+                .git(&["diff", "--cached", "--quiet", "HEAD"])
+                .await
+                .is_ok();
+        if !unchanged {
+            self.git(&[
+                "commit",
+                "--quiet",
+                "--allow-empty",
+                "--no-verify",
+                "-m",
+                message,
+            ])
+            .await
+            .context("couldn't snapshot workspace")?;
+        }
+
+        self.git(&["rev-parse", "HEAD"]).await
+    }
+
+    async fn apply(&self, patch: &[u8]) -> anyhow::Result<()> {
+        let mut child = self
+            .command(&["apply", "--binary", "-"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context("couldn't run git")?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(patch)
+                .await
+                .context("couldn't pass patch to git")?;
+        }
+
+        let output = child.wait_with_output().await.context("couldn't run git")?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "git apply failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        Ok(())
+    }
+
+    async fn git(&self, args: &[&str]) -> anyhow::Result<String> {
+        let output = self.git_output(args).await?;
+
+        Ok(String::from_utf8_lossy(&output).trim_end().to_string())
+    }
+
+    /// Runs git, and returns its output as is.
+    async fn git_output(&self, args: &[&str]) -> anyhow::Result<Vec<u8>> {
+        let output = self
+            .command(args)
+            .output()
+            .await
+            .context("couldn't run git")?;
+
+        if !output.status.success() {
+            anyhow::bail!(
+                "git {} failed: {}",
+                args.first().unwrap_or(&""),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        Ok(output.stdout)
+    }
+
+    fn command(&self, args: &[&str]) -> Command {
+        let mut command = Command::new("git");
+        command
+            .arg(format!("--git-dir={}", self.git_dir.display()))
+            .arg(format!("--work-tree={}", self.work_tree.display()))
+            // the user's git config shouldn't affect snapshots
+            .args(["-c", "user.name=agx", "-c", "user.email=agx@localhost"])
+            .args(["-c", "commit.gpgsign=false", "-c", "core.autocrlf=false"])
+            .args(["-c", "core.hooksPath=/dev/null"])
+            .args(args)
+            .env_remove("GIT_INDEX_FILE")
+            .current_dir(&self.work_tree);
+
+        command
+    }
+}
+
+/// Combines the output of `git diff --name-status` and `git diff --numstat` for the same range.
+fn parse_file_changes(statuses: &str, line_counts: &str) -> Vec<FileChange> {
+    let lines = line_counts
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, '\t');
+            let added = parts.next()?.parse::<usize>().ok();
+            let removed = parts.next()?.parse::<usize>().ok();
+            Some((parts.next()?, added.zip(removed)))
+        })
+        .collect::<Vec<_>>();
+
+    statuses
+        .lines()
+        .filter_map(|line| {
+            let (status, path) = line.split_once('\t')?;
+            let kind = match status {
+                "A" => FileChangeKind::Created,
+                "D" => FileChangeKind::Deleted,
+                _ => FileChangeKind::Edited,
+            };
+            Some(FileChange {
+                path: path.to_string(),
+                kind,
+                lines: lines
+                    .iter()
+                    .find(|(p, _)| *p == path)
+                    .and_then(|(_, lines)| *lines),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::assert_snapshot;
+
+    #[tokio::test]
+    async fn steps_can_be_diffed_and_undone() -> anyhow::Result<()> {
+        // GIVEN
+        let temp_dir = tempfile::tempdir()?;
+        let dir = temp_dir.path();
+        let work_tree = dir.join("workspace");
+        tokio::fs::create_dir_all(&work_tree).await?;
+        tokio::fs::write(work_tree.join("main.rs"), "fn main() {}\n").await?;
+        let mut repo = ShadowRepo::open(dir.join(SHADOW_REPO_DIR), work_tree.clone()).await?;
+
+        let before = repo.before_step().await?;
+        tokio::fs::write(work_tree.join("main.rs"), "fn main() {\n    run();\n}\n").await?;
+        repo.record_step(before, "edit_file: main.rs").await?;
+
+        let before = repo.before_step().await?;
+        tokio::fs::write(work_tree.join("notes.md"), "# notes\n").await?;
+        repo.record_step(before, "create_file: notes.md").await?;
+
+        let before = repo.before_step().await?;
+        let unchanged = repo.record_step(before, "read_file: main.rs").await?;
+
+        // WHEN
+        let diff = repo.diff(Some(1), DiffOptions::default()).await?;
+        let changed = repo
+            .changed_files(0)
+            .await?
+            .iter()
+            .map(|c| c.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let undo = repo.undo(0).await?;
+
+        // THEN
+        assert_eq!(unchanged, None);
+        assert_snapshot!(diff, @r"
+        diff --git a/main.rs b/main.rs
+        index f328e4d..edb3f76 100644
+        --- a/main.rs
+        +++ b/main.rs
+        @@ -1 +1,3 @@
+        -fn main() {}
+        +fn main() {
+        +    run();
+        +}
+        ");
+        assert_snapshot!(changed, @r"
+        ~ main.rs (edited, +3 -1)
+        + notes.md (created, +1 -0)
+        ");
+        assert!(undo.error.is_none());
+        assert_eq!(
+            undo.undone
+                .iter()
+                .map(|s| s.label.as_str())
+                .collect::<Vec<_>>(),
+            vec!["create_file: notes.md", "edit_file: main.rs"]
+        );
+        assert_eq!(
+            tokio::fs::read_to_string(work_tree.join("main.rs")).await?,
+            "fn main() {}\n"
+        );
+        assert!(!work_tree.join("notes.md").exists());
+        assert!(repo.steps().is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn steps_changing_files_that_arent_utf8_encoded_can_be_undone() -> anyhow::Result<()> {
+        // GIVEN
+        let temp_dir = tempfile::tempdir()?;
+        let dir = temp_dir.path();
+        let work_tree = dir.join("workspace");
+        tokio::fs::create_dir_all(&work_tree).await?;
+        // "café" and "crème", encoded as Latin-1
+        let original = b"caf\xe9\n".to_vec();
+        tokio::fs::write(work_tree.join("menu.txt"), &original).await?;
+        let mut repo = ShadowRepo::open(dir.join(SHADOW_REPO_DIR), work_tree.clone()).await?;
+
+        let before = repo.before_step().await?;
+        tokio::fs::write(work_tree.join("menu.txt"), b"caf\xe9\ncr\xe8me\n").await?;
+        repo.record_step(before, "edit_file: menu.txt").await?;
+
+        // WHEN
+        let undo = repo.undo(0).await?;
+
+        // THEN
+        assert!(undo.error.is_none());
+        assert_eq!(tokio::fs::read(work_tree.join("menu.txt")).await?, original);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,435 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::time::Duration;
+
+/// Feedback given when rejecting a tool's calls that's shown per tool (the latest ones).
+const FEEDBACK_SHOWN: usize = 3;
+
+#[derive(Debug, Default)]
+pub struct SessionStats {
+    tool_latencies: BTreeMap<&'static str, Vec<Duration>>,
+    tool_failures: BTreeMap<&'static str, ToolFailures>,
+    tool_confirmations: BTreeMap<&'static str, ToolConfirmations>,
+    turns: usize,
+    interruptions: BTreeMap<Interruption, usize>,
+    input_tokens: u64,
+    output_tokens: u64,
+}
+
+#[derive(Debug, Default)]
+struct ToolFailures {
+    counts: BTreeMap<&'static str, usize>,
+    /// Codes of the failures since the tool last succeeded.
+    streak: Vec<&'static str>,
+}
+
+#[derive(Debug, Default)]
+struct ToolConfirmations {
+    count: usize,
+    rejections: BTreeMap<&'static str, usize>,
+    feedback: Vec<String>,
+}
+
+/// How a turn was cut short.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Interruption {
+    /// Via Esc, while a response was streamed.
+    StoppedStreaming,
+    /// Via Ctrl+C, while waiting on a response.
+    CancelledResponse,
+    /// Via Ctrl+C, while a tool call was running.
+    CancelledToolCall,
+    /// Via Ctrl+C twice.
+    DiscardedTurn,
+    GuardrailTripped,
+}
+
+impl Interruption {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Interruption::StoppedStreaming => "stopped_streaming",
+            Interruption::CancelledResponse => "cancelled_response",
+            Interruption::CancelledToolCall => "cancelled_tool_call",
+            Interruption::DiscardedTurn => "discarded_turn",
+            Interruption::GuardrailTripped => "guardrail_tripped",
+        }
+    }
+}
+
+/// Why a tool call the user was asked to confirm didn't go through as requested.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rejection {
+    Rejected,
+    RejectedWithFeedback(String),
+    /// Only some of the hunks of a change were accepted.
+    PartiallyApproved,
+}
+
+impl Rejection {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Rejection::Rejected => "rejected",
+            Rejection::RejectedWithFeedback(_) => "rejected_with_feedback",
+            Rejection::PartiallyApproved => "partially_approved",
+        }
+    }
+}
+
+impl SessionStats {
+    pub fn record_tool_latency(&mut self, tool_name: &'static str, elapsed: Duration) {
+        self.tool_latencies
+            .entry(tool_name)
+            .or_default()
+            .push(elapsed);
+    }
+
+    pub fn record_tool_success(&mut self, tool_name: &'static str) {
+        if let Some(failures) = self.tool_failures.get_mut(tool_name) {
+            failures.streak.clear();
+        }
+    }
+
+    pub fn record_tool_failure(&mut self, tool_name: &'static str, code: &'static str) {
+        let failures = self.tool_failures.entry(tool_name).or_default();
+        *failures.counts.entry(code).or_default() += 1;
+        failures.streak.push(code);
+    }
+
+    /// Returns the codes of the tool's failures since it last succeeded.
+    pub fn failure_streak(&self, tool_name: &str) -> &[&'static str] {
+        self.tool_failures
+            .get(tool_name)
+            .map(|f| f.streak.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Records the outcome of a tool call that needed confirmation (`None` if it was approved).
+    pub fn record_tool_confirmation(
+        &mut self,
+        tool_name: &'static str,
+        rejection: Option<Rejection>,
+    ) {
+        let confirmations = self.tool_confirmations.entry(tool_name).or_default();
+        confirmations.count += 1;
+        let Some(rejection) = rejection else {
+            return;
+        };
+
+        *confirmations
+            .rejections
+            .entry(rejection.code())
+            .or_default() += 1;
+        if let Rejection::RejectedWithFeedback(feedback) = rejection {
+            confirmations.feedback.push(feedback);
+        }
+    }
+
+    pub fn record_turn(&mut self) {
+        self.turns += 1;
+    }
+
+    pub fn record_interruption(&mut self, interruption: Interruption) {
+        *self.interruptions.entry(interruption).or_default() += 1;
+    }
+
+    pub fn record_token_usage(&mut self, input_tokens: u64, output_tokens: u64) {
+        self.input_tokens += input_tokens;
+        self.output_tokens += output_tokens;
+    }
+
+    /// Returns the input and output tokens used across all requests in the session.
+    pub fn token_usage(&self) -> (u64, u64) {
+        (self.input_tokens, self.output_tokens)
+    }
+}
+
+struct LatencySummary {
+    count: usize,
+    min: Duration,
+    p50: Duration,
+    p90: Duration,
+    max: Duration,
+    total: Duration,
+}
+
+impl LatencySummary {
+    fn new(latencies: &[Duration]) -> Option<Self> {
+        if latencies.is_empty() {
+            return None;
+        }
+
+        let mut sorted = latencies.to_vec();
+        sorted.sort();
+
+        Some(Self {
+            count: sorted.len(),
+            min: sorted[0],
+            p50: percentile(&sorted, 50),
+            p90: percentile(&sorted, 90),
+            max: sorted[sorted.len() - 1],
+            total: sorted.iter().sum(),
+        })
+    }
+}
+
+fn percentile(sorted: &[Duration], p: usize) -> Duration {
+    // nearest-rank method
+    let rank = (p * sorted.len()).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+impl Display for SessionStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "tool latencies:")?;
+
+        let summaries = self
+            .tool_latencies
+            .iter()
+            .filter_map(|(name, latencies)| LatencySummary::new(latencies).map(|s| (name, s)))
+            .collect::<Vec<_>>();
+
+        if summaries.is_empty() {
+            writeln!(f, "- none")?;
+        }
+
+        for (name, s) in summaries {
+            writeln!(
+                f,
+                "- {:<12} calls: {:<4} min: {:<8} p50: {:<8} p90: {:<8} max: {:<8} total: {}",
+                name,
+                s.count,
+                format_duration(s.min),
+                format_duration(s.p50),
+                format_duration(s.p90),
+                format_duration(s.max),
+                format_duration(s.total),
+            )?;
+        }
+
+        writeln!(f, "\ntool failures:")?;
+
+        if self.tool_failures.is_empty() {
+            writeln!(f, "- none")?;
+        }
+
+        for (name, failures) in &self.tool_failures {
+            let failed = failures.counts.values().sum::<usize>();
+            let calls = self
+                .tool_latencies
+                .get(name)
+                .map_or(failed, Vec::len)
+                .max(failed);
+            let codes = failures
+                .counts
+                .iter()
+                .map(|(code, count)| format!("{code}: {count}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            writeln!(
+                f,
+                "- {:<12} failed: {:<13} {}",
+                name,
+                format!("{failed}/{calls} ({}%)", failed * 100 / calls),
+                codes,
+            )?;
+        }
+
+        writeln!(f, "\ntool call rejections:")?;
+
+        let rejected = self
+            .tool_confirmations
+            .iter()
+            .filter(|(_, c)| !c.rejections.is_empty())
+            .collect::<Vec<_>>();
+        if rejected.is_empty() {
+            writeln!(f, "- none")?;
+        }
+
+        for (name, confirmations) in rejected {
+            let count = confirmations.rejections.values().sum::<usize>();
+            writeln!(
+                f,
+                "- {:<12} rejected: {:<11} {}",
+                name,
+                format!(
+                    "{count}/{} ({}%)",
+                    confirmations.count,
+                    count * 100 / confirmations.count
+                ),
+                join_counts(&confirmations.rejections),
+            )?;
+
+            let skip = confirmations.feedback.len().saturating_sub(FEEDBACK_SHOWN);
+            for feedback in confirmations.feedback.iter().skip(skip) {
+                writeln!(f, "  - feedback: {feedback:?}")?;
+            }
+        }
+
+        writeln!(f, "\nturn interruptions:")?;
+
+        if self.interruptions.is_empty() {
+            return writeln!(f, "- none");
+        }
+
+        let count = self.interruptions.values().sum::<usize>();
+        let turns = self.turns.max(count);
+        let reasons = self
+            .interruptions
+            .iter()
+            .map(|(interruption, count)| (interruption.as_str(), *count))
+            .collect();
+        writeln!(
+            f,
+            "- {:<12} interrupted: {:<11} {}",
+            "turns",
+            format!("{count}/{turns} ({}%)", count * 100 / turns),
+            join_counts(&reasons),
+        )
+    }
+}
+
+fn join_counts(counts: &BTreeMap<&'static str, usize>) -> String {
+    counts
+        .iter()
+        .map(|(code, count)| format!("{code}: {count}"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn format_duration(duration: Duration) -> String {
+    let ms = duration.as_millis();
+    if ms >= 1_000 {
+        format!("{:.1}s", duration.as_secs_f64())
+    } else {
+        format!("{}ms", ms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::assert_snapshot;
+
+    #[test]
+    fn stats_output_is_correct_when_nothing_was_recorded() {
+        // GIVEN
+        let stats = SessionStats::default();
+
+        // WHEN
+        // THEN
+        assert_snapshot!(stats.to_string(), @r"
+        tool latencies:
+        - none
+
+        tool failures:
+        - none
+
+        tool call rejections:
+        - none
+
+        turn interruptions:
+        - none
+        ");
+    }
+
+    #[test]
+    fn stats_output_summarizes_latencies_per_tool() {
+        // GIVEN
+        let mut stats = SessionStats::default();
+        for ms in [40, 10, 20, 30, 2500] {
+            stats.record_tool_latency("run_cmd", Duration::from_millis(ms));
+        }
+        stats.record_tool_latency("read_dir", Duration::from_millis(3));
+
+        // WHEN
+        // THEN
+        assert_snapshot!(stats.to_string(), @r"
+        tool latencies:
+        - read_dir     calls: 1    min: 3ms      p50: 3ms      p90: 3ms      max: 3ms      total: 3ms
+        - run_cmd      calls: 5    min: 10ms     p50: 30ms     p90: 2.5s     max: 2.5s     total: 2.6s
+
+        tool failures:
+        - none
+
+        tool call rejections:
+        - none
+
+        turn interruptions:
+        - none
+        ");
+    }
+
+    #[test]
+    fn stats_output_summarizes_failures_per_tool() {
+        // GIVEN
+        let mut stats = SessionStats::default();
+        for code in ["old_str_not_found", "file_not_found", "old_str_not_found"] {
+            stats.record_tool_latency("edit_file", Duration::from_millis(5));
+            stats.record_tool_failure("edit_file", code);
+        }
+        stats.record_tool_latency("edit_file", Duration::from_millis(5));
+        stats.record_tool_success("edit_file");
+        stats.record_tool_latency("edit_file", Duration::from_millis(5));
+        stats.record_tool_failure("edit_file", "old_str_not_found");
+
+        // WHEN
+        let streak = stats.failure_streak("edit_file").to_vec();
+
+        // THEN
+        assert_eq!(streak, vec!["old_str_not_found"]);
+        assert_snapshot!(stats.to_string(), @r"
+        tool latencies:
+        - edit_file    calls: 5    min: 5ms      p50: 5ms      p90: 5ms      max: 5ms      total: 25ms
+
+        tool failures:
+        - edit_file    failed: 4/5 (80%)     file_not_found: 1, old_str_not_found: 3
+
+        tool call rejections:
+        - none
+
+        turn interruptions:
+        - none
+        ");
+    }
+
+    #[test]
+    fn stats_output_summarizes_rejections_and_interruptions() {
+        // GIVEN
+        let mut stats = SessionStats::default();
+        stats.record_tool_confirmation("run_cmd", None);
+        stats.record_tool_confirmation("run_cmd", Some(Rejection::Rejected));
+        stats.record_tool_confirmation(
+            "run_cmd",
+            Some(Rejection::RejectedWithFeedback(
+                "use cargo nextest instead".to_string(),
+            )),
+        );
+        stats.record_tool_confirmation("run_cmd", None);
+        stats.record_tool_confirmation("edit_file", Some(Rejection::PartiallyApproved));
+        stats.record_tool_confirmation("create_file", None);
+        for _ in 0..5 {
+            stats.record_turn();
+        }
+        stats.record_interruption(Interruption::StoppedStreaming);
+        stats.record_interruption(Interruption::CancelledToolCall);
+
+        // WHEN
+        // THEN
+        assert_snapshot!(stats.to_string(), @r#"
+        tool latencies:
+        - none
+
+        tool failures:
+        - none
+
+        tool call rejections:
+        - edit_file    rejected: 1/1 (100%)  partially_approved: 1
+        - run_cmd      rejected: 2/4 (50%)   rejected: 1, rejected_with_feedback: 1
+          - feedback: "use cargo nextest instead"
+
+        turn interruptions:
+        - turns        interrupted: 2/5 (40%)   cancelled_tool_call: 1, stopped_streaming: 1
+        "#);
+    }
+}
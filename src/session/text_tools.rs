@@ -0,0 +1,158 @@
+use rig::completion::ToolDefinition;
+use rig::message::{Message, ToolCall, ToolFunction, ToolResult, ToolResultContent};
+
+// Used for models that don't support native tool calling. Tool definitions are sent as part of
+// the system prompt, and the model is asked to request tool calls via fenced blocks in its
+// response text. Tool results are sent back as regular user messages.
+
+const TOOL_CALL_FENCE: &str = "```tool_call";
+const FENCE: &str = "```";
+
+pub fn instructions(tool_defs: &[ToolDefinition]) -> String {
+    let tools = tool_defs
+        .iter()
+        .map(|t| {
+            format!(
+                "- {}: {}\n  parameters (JSON schema): {}",
+                t.name, t.description, t.parameters
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    format!(
+        r#"# Tool calls
+
+You can call the following tools:
+
+{tools}
+
+To call a tool, respond with a fenced block like the following (one block per tool call):
+
+{TOOL_CALL_FENCE}
+{{"name": "<tool name>", "arguments": {{<arguments as per the tool's parameters>}}}}
+{FENCE}
+
+Stop your response after requesting tool calls; their results will be sent to you in the next
+message."#
+    )
+}
+
+pub fn parse_tool_calls(text: &str) -> Vec<ToolCall> {
+    let mut tool_calls = vec![];
+    let mut lines = text.lines();
+
+    while let Some(line) = lines.next() {
+        if line.trim() != TOOL_CALL_FENCE {
+            continue;
+        }
+
+        let block = lines
+            .by_ref()
+            .take_while(|l| l.trim() != FENCE)
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let Ok(value) = serde_json::from_str::<serde_json::Value>(&block) else {
+            continue;
+        };
+
+        let Some(name) = value.get("name").and_then(|n| n.as_str()) else {
+            continue;
+        };
+
+        let arguments = value
+            .get("arguments")
+            .cloned()
+            .unwrap_or_else(|| serde_json::json!({}));
+
+        tool_calls.push(ToolCall::new(
+            format!("text_call_{}", tool_calls.len() + 1),
+            ToolFunction::new(name.to_string(), arguments),
+        ));
+    }
+
+    tool_calls
+}
+
+pub fn tool_results_message(tool_results: &[ToolResult]) -> Message {
+    let results = tool_results
+        .iter()
+        .map(|r| {
+            let output = r
+                .content
+                .iter()
+                .filter_map(|c| match c {
+                    ToolResultContent::Text(t) => Some(t.text.as_str()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            format!("[tool result for {}]\n{}", r.id, output)
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    Message::user(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::assert_yaml_snapshot;
+
+    #[test]
+    fn parsing_tool_calls_from_text_works() {
+        // GIVEN
+        let text = r#"Let me look at the file first.
+
+```tool_call
+{"name": "read_file", "arguments": {"path": "src/main.rs"}}
+```
+
+```tool_call
+{"name": "run_cmd", "arguments": {"command": "cargo test"}}
+```
+
+```rust
+fn main() {}
+```
+"#;
+
+        // WHEN
+        let tool_calls = parse_tool_calls(text)
+            .into_iter()
+            .map(|tc| (tc.id, tc.function.name, tc.function.arguments))
+            .collect::<Vec<_>>();
+
+        // THEN
+        assert_yaml_snapshot!(tool_calls, @r"
+        - - text_call_1
+          - read_file
+          - path: src/main.rs
+        - - text_call_2
+          - run_cmd
+          - command: cargo test
+        ");
+    }
+
+    #[test]
+    fn invalid_tool_call_blocks_are_ignored() {
+        // GIVEN
+        let text = r#"```tool_call
+{"name": "read_file", "arguments":
+```
+
+```tool_call
+{"arguments": {"path": "src/main.rs"}}
+```
+"#;
+
+        // WHEN
+        let tool_calls = parse_tool_calls(text);
+
+        // THEN
+        assert!(tool_calls.is_empty());
+    }
+}
@@ -0,0 +1,198 @@
+use std::io::{IsTerminal, Write};
+use std::time::{Duration, Instant};
+
+const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+/// The spinner advances as chunks of a response arrive, but no faster than this.
+const SPINNER_INTERVAL: Duration = Duration::from_millis(100);
+const MAX_CONVERSATION_CHARS: usize = 40;
+
+// xterm's title stack; terminals that don't support it ignore these
+const SAVE_TITLE: &str = "\x1b[22;0t";
+const RESTORE_TITLE: &str = "\x1b[23;0t";
+
+/// What the session is doing; shown as a marker in front of the title.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TitleStatus {
+    Idle,
+    Streaming,
+    RunningTool,
+    AwaitingApproval,
+}
+
+/// Keeps the terminal's title set to "agx: <project> — <conversation>", so that the session's
+/// terminal is easy to find among many. The conversation is named after its first prompt. The
+/// title that was in place before is restored once the title is disabled, or dropped.
+pub struct TerminalTitle {
+    project: String,
+    conversation: Option<String>,
+    status: TitleStatus,
+    frame: usize,
+    frame_shown_at: Instant,
+    enabled: bool,
+    shown: Option<String>,
+}
+
+impl TerminalTitle {
+    pub fn new(project: impl Into<String>) -> Self {
+        Self {
+            project: sanitize(&project.into()),
+            conversation: None,
+            status: TitleStatus::Idle,
+            frame: 0,
+            frame_shown_at: Instant::now(),
+            enabled: false,
+            shown: None,
+        }
+    }
+
+    /// The title is only ever set when stdout is a terminal.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        let enabled = enabled && std::io::stdout().is_terminal();
+        if enabled == self.enabled {
+            return;
+        }
+
+        self.enabled = enabled;
+        if enabled {
+            write_escape(SAVE_TITLE);
+            self.refresh();
+        } else {
+            write_escape(RESTORE_TITLE);
+            self.shown = None;
+        }
+    }
+
+    /// Names the conversation after `prompt`, unless it's already named.
+    pub fn name_conversation(&mut self, prompt: &str) {
+        if self.conversation.is_some() {
+            return;
+        }
+
+        self.conversation = conversation_name(prompt);
+        self.refresh();
+    }
+
+    pub fn conversation(&self) -> Option<&str> {
+        self.conversation.as_deref()
+    }
+
+    pub fn clear_conversation(&mut self) {
+        self.conversation = None;
+        self.refresh();
+    }
+
+    pub fn set_status(&mut self, status: TitleStatus) {
+        self.status = status;
+        self.refresh();
+    }
+
+    /// Advances the spinner shown while a response is streamed.
+    pub fn tick(&mut self) {
+        if self.status != TitleStatus::Streaming || self.frame_shown_at.elapsed() < SPINNER_INTERVAL
+        {
+            return;
+        }
+
+        self.frame = (self.frame + 1) % SPINNER.len();
+        self.frame_shown_at = Instant::now();
+        self.refresh();
+    }
+
+    fn render(&self) -> String {
+        let title = match &self.conversation {
+            Some(conversation) => format!("agx: {} — {conversation}", self.project),
+            None => format!("agx: {}", self.project),
+        };
+
+        match self.status {
+            TitleStatus::Idle => title,
+            TitleStatus::Streaming => format!("{} {title}", SPINNER[self.frame]),
+            TitleStatus::RunningTool => format!("⚙ {title}"),
+            TitleStatus::AwaitingApproval => format!("? {title}"),
+        }
+    }
+
+    fn refresh(&mut self) {
+        if !self.enabled {
+            return;
+        }
+
+        let title = self.render();
+        if self.shown.as_ref() == Some(&title) {
+            return;
+        }
+
+        write_escape(&format!("\x1b]0;{title}\x07"));
+        self.shown = Some(title);
+    }
+}
+
+impl Drop for TerminalTitle {
+    fn drop(&mut self) {
+        self.set_enabled(false);
+    }
+}
+
+/// The prompt's first line, cut down to a reasonable length.
+fn conversation_name(prompt: &str) -> Option<String> {
+    let first_line = sanitize(prompt.lines().find(|l| !l.trim().is_empty())?);
+    let first_line = first_line.split_whitespace().collect::<Vec<_>>().join(" ");
+    if first_line.chars().count() <= MAX_CONVERSATION_CHARS {
+        return Some(first_line);
+    }
+
+    let mut name = first_line
+        .chars()
+        .take(MAX_CONVERSATION_CHARS - 1)
+        .collect::<String>();
+    name.push('…');
+    Some(name)
+}
+
+/// Control characters could end the escape sequence the title is sent in early.
+fn sanitize(text: &str) -> String {
+    text.chars().filter(|c| !c.is_control()).collect()
+}
+
+fn write_escape(sequence: &str) {
+    let mut stdout = std::io::stdout();
+    let _ = stdout
+        .write_all(sequence.as_bytes())
+        .and_then(|_| stdout.flush());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::assert_snapshot;
+
+    #[test]
+    fn title_shows_the_project_conversation_and_status() {
+        // GIVEN
+        let mut title = TerminalTitle::new("agx");
+        let mut rendered = vec![title.render()];
+
+        // WHEN
+        title.name_conversation(
+            "\n  Add retries\x1b]0;pwned\x07 to   the HTTP client, with exponential backoff\nthanks",
+        );
+        title.set_status(TitleStatus::Streaming);
+        rendered.push(title.render());
+        title.name_conversation("another prompt");
+        title.set_status(TitleStatus::AwaitingApproval);
+        rendered.push(title.render());
+        title.set_status(TitleStatus::RunningTool);
+        rendered.push(title.render());
+        title.set_status(TitleStatus::Idle);
+        rendered.push(title.render());
+
+        // THEN
+        assert_snapshot!(rendered.join("\n"), @r"
+        agx: agx
+        ⠋ agx: agx — Add retries]0;pwned to the HTTP client,…
+        ? agx: agx — Add retries]0;pwned to the HTTP client,…
+        ⚙ agx: agx — Add retries]0;pwned to the HTTP client,…
+        agx: agx — Add retries]0;pwned to the HTTP client,…
+        ");
+    }
+}
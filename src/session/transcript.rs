@@ -0,0 +1,237 @@
+use anyhow::Context;
+use rig::message::{Reasoning, ToolCall};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use tracing::warn;
+
+/// Records the model's side of a session (prompts, responses, and tool results) as JSON lines,
+/// so that its turns can be replayed via `agx replay`.
+pub(super) struct TranscriptWriter {
+    file: File,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum TranscriptEvent {
+    Started {
+        provider: String,
+        model: String,
+        text_tool_calls: bool,
+        /// Version of agx that recorded the transcript; missing in transcripts recorded before
+        /// it was.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        agx_version: Option<String>,
+        /// See [`Config::digest`]; missing in imported transcripts as well.
+        ///
+        /// [`Config::digest`]: crate::domain::Config::digest
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        config_digest: Option<String>,
+    },
+    Prompt {
+        text: String,
+    },
+    Response(RecordedResponse),
+    ToolResult {
+        id: String,
+        output: String,
+    },
+    Bookmark(Bookmark),
+}
+
+/// A point in a conversation the user marked (via /bookmark), eg. where a key decision was made.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Bookmark {
+    /// The number of prompts sent before the bookmark was added.
+    pub turn: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+impl std::fmt::Display for Bookmark {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.note {
+            Some(note) => write!(f, "turn {}: {note}", self.turn),
+            None => write!(f, "turn {}", self.turn),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    pub text: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reasoning: Vec<Reasoning>,
+    /// Only contains native tool calls; tool calls in text mode are a part of the text.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+/// A transcript loaded for a replay.
+#[derive(Debug)]
+pub struct Transcript {
+    pub provider: String,
+    pub model: String,
+    pub text_tool_calls: bool,
+    pub agx_version: Option<String>,
+    pub config_digest: Option<String>,
+    pub prompts: Vec<String>,
+    pub responses: VecDeque<RecordedResponse>,
+    /// Outputs of tool calls in the order they were recorded, keyed by the tool call's ID (IDs
+    /// of tool calls made via text aren't unique across responses).
+    pub tool_results: HashMap<String, VecDeque<String>>,
+}
+
+impl TranscriptWriter {
+    pub(super) fn create(path: &Path) -> anyhow::Result<Self> {
+        let file = File::create(path)?;
+
+        Ok(Self { file })
+    }
+
+    pub(super) fn record(&self, event: &TranscriptEvent) {
+        let result = serde_json::to_string(event)
+            .map_err(std::io::Error::from)
+            .and_then(|line| writeln!(&self.file, "{line}"));
+
+        if let Err(e) = result {
+            warn!(error = %e, "couldn't write to transcript");
+        }
+    }
+}
+
+impl Transcript {
+    pub async fn load(path: &Path) -> anyhow::Result<Self> {
+        let contents = tokio::fs::read_to_string(path)
+            .await
+            .with_context(|| format!("couldn't read transcript {}", path.to_string_lossy()))?;
+
+        contents.parse()
+    }
+}
+
+impl std::str::FromStr for Transcript {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut started = None;
+        let mut prompts = vec![];
+        let mut responses = VecDeque::new();
+        let mut tool_results = HashMap::new();
+
+        for (i, line) in s.lines().enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+            let event: TranscriptEvent = serde_json::from_str(line)
+                .with_context(|| format!("line {} of the transcript is invalid", i + 1))?;
+
+            match event {
+                TranscriptEvent::Started {
+                    provider,
+                    model,
+                    text_tool_calls,
+                    agx_version,
+                    config_digest,
+                } => started = Some((provider, model, text_tool_calls, agx_version, config_digest)),
+                TranscriptEvent::Prompt { text } => prompts.push(text),
+                TranscriptEvent::Response(response) => responses.push_back(response),
+                TranscriptEvent::ToolResult { id, output } => tool_results
+                    .entry(id)
+                    .or_insert_with(VecDeque::new)
+                    .push_back(output),
+                TranscriptEvent::Bookmark(_) => {}
+            }
+        }
+
+        let (provider, model, text_tool_calls, agx_version, config_digest) =
+            started.context("transcript doesn't record how the session was started")?;
+
+        Ok(Self {
+            provider,
+            model,
+            text_tool_calls,
+            agx_version,
+            config_digest,
+            prompts,
+            responses,
+            tool_results,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::assert_yaml_snapshot;
+    use rig::message::ToolFunction;
+
+    #[test]
+    fn a_recorded_transcript_can_be_loaded() {
+        // GIVEN
+        let events = [
+            TranscriptEvent::Started {
+                provider: "anthropic".to_string(),
+                model: "claude-sonnet-4-5".to_string(),
+                text_tool_calls: false,
+                agx_version: Some("0.9.0".to_string()),
+                config_digest: Some("3f2a9c1d7b4e".to_string()),
+            },
+            TranscriptEvent::Prompt {
+                text: "what does main.rs do?".to_string(),
+            },
+            TranscriptEvent::Response(RecordedResponse {
+                text: String::new(),
+                reasoning: vec![],
+                tool_calls: vec![ToolCall::new(
+                    "call_1".to_string(),
+                    ToolFunction::new(
+                        "read_file".to_string(),
+                        serde_json::json!({"path": "src/main.rs"}),
+                    ),
+                )],
+                input_tokens: 1200,
+                output_tokens: 20,
+            }),
+            TranscriptEvent::ToolResult {
+                id: "call_1".to_string(),
+                output: "fn main() {}".to_string(),
+            },
+            TranscriptEvent::Response(RecordedResponse {
+                text: "It does nothing.".to_string(),
+                reasoning: vec![],
+                tool_calls: vec![],
+                input_tokens: 1250,
+                output_tokens: 5,
+            }),
+        ];
+        let contents = events
+            .iter()
+            .map(|e| serde_json::to_string(e).expect("event should've been serialized"))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        // WHEN
+        let transcript: Transcript = contents.parse().expect("transcript should've been parsed");
+
+        // THEN
+        let responses = transcript
+            .responses
+            .iter()
+            .map(|r| (r.text.as_str(), r.tool_calls.len()))
+            .collect::<Vec<_>>();
+        assert_yaml_snapshot!((transcript.provider, transcript.agx_version, transcript.config_digest, transcript.prompts, responses, transcript.tool_results), @r#"
+        - anthropic
+        - 0.9.0
+        - 3f2a9c1d7b4e
+        - - what does main.rs do?
+        - - - ""
+            - 1
+          - - It does nothing.
+            - 0
+        - call_1:
+            - "fn main() {}"
+        "#);
+    }
+}
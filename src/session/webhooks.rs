@@ -0,0 +1,219 @@
+use crate::domain::{WebhookConfig, WebhookEvent, WebhookKind};
+use reqwest::header::{HeaderMap, HeaderValue, USER_AGENT};
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::{JoinHandle, JoinSet};
+use tracing::warn;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Something that happened in a session that webhooks can be notified of.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Notification {
+    SessionEnded {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    ApprovalPending {
+        tool_call: String,
+        waiting_mins: u64,
+    },
+    GuardrailTripped {
+        reason: String,
+    },
+}
+
+impl Notification {
+    fn event(&self) -> WebhookEvent {
+        match self {
+            Notification::SessionEnded { .. } => WebhookEvent::SessionEnded,
+            Notification::ApprovalPending { .. } => WebhookEvent::ApprovalPending,
+            Notification::GuardrailTripped { .. } => WebhookEvent::GuardrailTripped,
+        }
+    }
+
+    fn text(&self, project: &str) -> String {
+        match self {
+            Notification::SessionEnded { error: None } => {
+                format!("agx session in {project} ended")
+            }
+            Notification::SessionEnded { error: Some(error) } => {
+                format!("agx session in {project} ended with an error: {error}")
+            }
+            Notification::ApprovalPending {
+                tool_call,
+                waiting_mins,
+            } => format!(
+                "agx session in {project} has been waiting for approval for {waiting_mins} min: {tool_call}"
+            ),
+            Notification::GuardrailTripped { reason } => {
+                format!("agx session in {project} tripped a guardrail: {reason}")
+            }
+        }
+    }
+
+    /// Returns the request body for a kind of webhook.
+    fn payload(&self, kind: WebhookKind, project: &str) -> Value {
+        let text = self.text(project);
+        match kind {
+            WebhookKind::Slack => json!({ "text": text }),
+            WebhookKind::Discord => json!({ "content": text }),
+            WebhookKind::Generic => {
+                let mut payload = serde_json::to_value(self).unwrap_or_else(|_| json!({}));
+                if let Some(fields) = payload.as_object_mut() {
+                    fields.insert("project".to_string(), json!(project));
+                    fields.insert("text".to_string(), json!(text));
+                }
+                payload
+            }
+        }
+    }
+}
+
+/// Notifies the webhooks in config of session events. Notifications are sent in the background;
+/// failing to send one is logged, and doesn't affect the session.
+pub struct Webhooks {
+    hooks: Vec<WebhookConfig>,
+    project: String,
+    http: reqwest::Client,
+    in_flight: Arc<Mutex<JoinSet<()>>>,
+}
+
+impl Webhooks {
+    pub fn new(hooks: Vec<WebhookConfig>, project: impl Into<String>) -> Self {
+        let mut headers = HeaderMap::new();
+        headers.insert(USER_AGENT, HeaderValue::from_static("agx"));
+        let http = reqwest::Client::builder()
+            .default_headers(headers)
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+
+        Self {
+            hooks,
+            project: project.into(),
+            http,
+            in_flight: Arc::default(),
+        }
+    }
+
+    pub fn set_hooks(&mut self, hooks: Vec<WebhookConfig>) {
+        self.hooks = hooks;
+    }
+
+    pub fn notify(&self, notification: &Notification) {
+        let Ok(mut in_flight) = self.in_flight.lock() else {
+            return;
+        };
+
+        for hook in self
+            .hooks
+            .iter()
+            .filter(|h| h.notifies_of(notification.event()))
+        {
+            in_flight.spawn(self.send(hook, notification));
+        }
+    }
+
+    /// Notifies the webhooks that care about pending approvals once a tool call has been waiting
+    /// for as long as they're configured to tolerate. Nothing is sent if the returned guard is
+    /// dropped (ie. the tool call is decided on) before then.
+    pub fn approval_pending(&self, tool_call: &str) -> PendingApproval {
+        let timers = self
+            .hooks
+            .iter()
+            .filter(|h| h.notifies_of(WebhookEvent::ApprovalPending))
+            .map(|hook| {
+                let waiting_mins = hook.approval_pending_mins();
+                let send = self.send(
+                    hook,
+                    &Notification::ApprovalPending {
+                        tool_call: tool_call.to_string(),
+                        waiting_mins,
+                    },
+                );
+                tokio::spawn(async move {
+                    tokio::time::sleep(Duration::from_secs(waiting_mins * 60)).await;
+                    send.await;
+                })
+            })
+            .collect();
+
+        PendingApproval { timers }
+    }
+
+    /// Waits for notifications that are still being sent (eg. before agx exits).
+    pub async fn flush(&self) {
+        let in_flight = match self.in_flight.lock() {
+            Ok(mut in_flight) => std::mem::take(&mut *in_flight),
+            Err(_) => return,
+        };
+
+        in_flight.join_all().await;
+    }
+
+    fn send(
+        &self,
+        hook: &WebhookConfig,
+        notification: &Notification,
+    ) -> impl Future<Output = ()> + Send + 'static {
+        let request = self
+            .http
+            .post(&hook.url)
+            .json(&notification.payload(hook.kind, &self.project));
+        let event = notification.event();
+
+        async move {
+            match request.send().await.and_then(|r| r.error_for_status()) {
+                Ok(_) => {}
+                Err(e) => warn!("couldn't notify webhook of {event:?}: {e}"),
+            }
+        }
+    }
+}
+
+pub struct PendingApproval {
+    timers: Vec<JoinHandle<()>>,
+}
+
+impl Drop for PendingApproval {
+    fn drop(&mut self) {
+        for timer in &self.timers {
+            timer.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::assert_snapshot;
+
+    #[test]
+    fn payloads_are_formatted_as_per_the_kind_of_webhook() {
+        // GIVEN
+        let notification = Notification::ApprovalPending {
+            tool_call: "run_cmd: cargo publish".to_string(),
+            waiting_mins: 5,
+        };
+
+        // WHEN
+        let payloads = [
+            WebhookKind::Generic,
+            WebhookKind::Slack,
+            WebhookKind::Discord,
+        ]
+        .map(|kind| notification.payload(kind, "agx").to_string())
+        .join("\n");
+
+        // THEN
+        assert_snapshot!(payloads, @r#"
+        {"event":"approval_pending","project":"agx","text":"agx session in agx has been waiting for approval for 5 min: run_cmd: cargo publish","tool_call":"run_cmd: cargo publish","waiting_mins":5}
+        {"text":"agx session in agx has been waiting for approval for 5 min: run_cmd: cargo publish"}
+        {"content":"agx session in agx has been waiting for approval for 5 min: run_cmd: cargo publish"}
+        "#);
+    }
+}
@@ -0,0 +1,334 @@
+//! Renders a session's transcript as a single, self-contained HTML file (styled like the debug
+//! client), so that sessions can be shared in code reviews or issue threads.
+
+use crate::debug::DEPS_CSS;
+use crate::helpers::unified_diff;
+use crate::session::{Bookmark, RecordedResponse, TranscriptEvent};
+use crate::tools::{CreateFileArgs, CreateFileTool, EditFileArgs, EditFileTool};
+use anyhow::Context;
+use rig::message::ToolCall;
+use rig::tool::Tool;
+
+// styles the debug client doesn't need (and hence aren't in its stylesheet)
+const EXTRA_CSS: &str = "
+.diff-added { color: #b8bb26; }
+.diff-removed { color: #fb4934; }
+.diff-hunk { color: #83a598; }
+";
+
+/// Renders a transcript (as recorded in a session's chats directory) as an HTML page that
+/// doesn't depend on anything outside of it.
+pub fn transcript_to_html(transcript: &str, title: &str) -> anyhow::Result<String> {
+    let mut subtitle = String::new();
+    let mut events = vec![];
+    let mut bookmarks = vec![];
+    for (i, line) in transcript
+        .lines()
+        .enumerate()
+        .filter(|(_, l)| !l.trim().is_empty())
+    {
+        let event: TranscriptEvent = serde_json::from_str(line)
+            .with_context(|| format!("line {} of the transcript is invalid", i + 1))?;
+
+        match event {
+            TranscriptEvent::Started {
+                provider,
+                model,
+                agx_version,
+                config_digest,
+                ..
+            } => {
+                subtitle = [
+                    Some(format!("{provider}/{model}")),
+                    agx_version.map(|v| format!("agx {v}")),
+                    config_digest.map(|d| format!("config {d}")),
+                ]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>()
+                .join(" · ");
+            }
+            event => {
+                if let TranscriptEvent::Bookmark(bookmark) = &event {
+                    bookmarks.push((events.len() + 1, bookmark.clone()));
+                }
+                events.push(render_event(&event, events.len() + 1));
+            }
+        }
+    }
+
+    Ok(format!(
+        r#"<!doctype html>
+<html lang="en">
+  <head>
+    <meta charset="UTF-8" />
+    <meta name="viewport" content="width=device-width, initial-scale=1.0" />
+    <title>agx: {title}</title>
+    <style>
+{DEPS_CSS}
+{EXTRA_CSS}
+    </style>
+  </head>
+  <body>
+    <div class="flex flex-col min-h-screen bg-[#282828] text-[#ebdbb2]">
+      <div class="mt-8 mb-12 w-full max-w-7xl mx-auto px-4">
+        <h1 class="font-bold">
+          <span class="text-[#d3869b] text-4xl">agx</span>
+          <span class="text-[#a89984] text-base ml-1">{title}</span>
+        </h1>
+        <div class="text-sm text-[#a89984] mt-1">{subtitle}</div>
+{bookmarks}
+        <div class="flex flex-col gap-4 mt-4">
+{events}
+        </div>
+      </div>
+    </div>
+  </body>
+</html>
+"#,
+        title = escape(title),
+        subtitle = escape(&subtitle),
+        bookmarks = render_bookmarks(&bookmarks),
+        events = events.join("\n"),
+    ))
+}
+
+/// Lists bookmarks (along with the numbers of their events), linking to where they are.
+fn render_bookmarks(bookmarks: &[(usize, Bookmark)]) -> String {
+    if bookmarks.is_empty() {
+        return String::new();
+    }
+
+    let items = bookmarks
+        .iter()
+        .map(|(number, bookmark)| {
+            format!(
+                r##"  <li><a class="underline" href="#event-{number}">{}</a></li>"##,
+                escape(&bookmark.to_string())
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    format!(
+        r#"<div class="mt-4 text-sm">
+<div class="font-semibold text-[#fabd2f]">bookmarks</div>
+<ul class="list-disc ml-5">
+{items}
+</ul>
+</div>"#
+    )
+}
+
+fn render_event(event: &TranscriptEvent, number: usize) -> String {
+    // bookmarks are linked to from the top of the page
+    let anchor = match event {
+        TranscriptEvent::Bookmark(_) => format!(r#" id="event-{number}""#),
+        _ => String::new(),
+    };
+    let (kind, color, body) = match event {
+        TranscriptEvent::Started { .. } => return String::new(),
+        TranscriptEvent::Prompt { text } => (
+            "prompt",
+            "#fe8019",
+            format!(
+                r#"<div class="p-2 bg-[#3c3836] rounded text-sm whitespace-pre-wrap">{}</div>"#,
+                escape(text)
+            ),
+        ),
+        TranscriptEvent::Response(response) => ("response", "#fbf1c7", render_response(response)),
+        TranscriptEvent::ToolResult { id, output } => (
+            "tool_result",
+            "#b8bb26",
+            format!(
+                r#"<div class="p-2 bg-[#3c3836] rounded">
+  <div class="text-xs text-[#a89984] mb-1">id: {}</div>
+  <pre class="text-xs bg-[#282828] p-1 rounded whitespace-pre-wrap break-all max-h-[50vh] overflow-auto">{}</pre>
+</div>"#,
+                escape(id),
+                escape(output)
+            ),
+        ),
+        TranscriptEvent::Bookmark(bookmark) => (
+            "bookmark",
+            "#fabd2f",
+            format!(
+                r#"<div class="p-2 bg-[#3c3836] rounded text-sm whitespace-pre-wrap">{}</div>"#,
+                escape(&bookmark.to_string())
+            ),
+        ),
+    };
+
+    format!(
+        r#"<div class="flex gap-3 items-start"{anchor}>
+<div class="flex-shrink-0 w-36 p-3 rounded text-sm font-mono" style="background-color: {color}">
+  <div class="font-semibold text-[#282828]">{kind}</div>
+  <div class="text-xs text-[#282828] opacity-70 mt-2">{number}</div>
+</div>
+<div class="flex-1 flex flex-col gap-2 min-w-0">
+{body}
+</div>
+</div>"#
+    )
+}
+
+fn render_response(response: &RecordedResponse) -> String {
+    let mut parts = vec![];
+
+    let reasoning = response
+        .reasoning
+        .iter()
+        .flat_map(|r| r.reasoning.iter().map(String::as_str))
+        .collect::<Vec<_>>();
+    if !reasoning.is_empty() {
+        parts.push(format!(
+            r#"<div class="p-2 bg-[#3c3836] rounded italic text-sm whitespace-pre-wrap">{}</div>"#,
+            escape(&reasoning.join(" "))
+        ));
+    }
+
+    if !response.text.trim().is_empty() {
+        parts.push(format!(
+            r#"<div class="p-2 bg-[#3c3836] rounded text-sm whitespace-pre-wrap">{}</div>"#,
+            escape(response.text.trim())
+        ));
+    }
+
+    parts.extend(response.tool_calls.iter().map(render_tool_call));
+
+    parts.join("\n")
+}
+
+/// File changes are shown as diffs; other tool calls, with their arguments.
+fn render_tool_call(tool_call: &ToolCall) -> String {
+    let arguments = &tool_call.function.arguments;
+    let diff = match tool_call.function.name.as_str() {
+        EditFileTool::NAME => serde_json::from_value::<EditFileArgs>(arguments.clone())
+            .ok()
+            .map(|args| unified_diff(&args.old_str, &args.new_str, &args.path)),
+        CreateFileTool::NAME => serde_json::from_value::<CreateFileArgs>(arguments.clone())
+            .ok()
+            .map(|args| unified_diff("", &args.contents, &args.path)),
+        _ => None,
+    };
+
+    let details = match diff {
+        Some(diff) => diff
+            .lines()
+            .map(render_diff_line)
+            .collect::<Vec<_>>()
+            .join("\n"),
+        None => escape(&serde_json::to_string_pretty(arguments).unwrap_or_default()),
+    };
+
+    format!(
+        r#"<div class="p-2 bg-[#3c3836] rounded">
+  <div class="flex gap-2 items-center mb-1">
+    <span class="font-mono text-sm bg-[#282828] px-1 rounded">{}</span>
+    <span class="text-xs text-[#a89984]">id: {}</span>
+  </div>
+  <pre class="text-xs bg-[#282828] p-1 rounded whitespace-pre-wrap break-all">{details}</pre>
+</div>"#,
+        escape(&tool_call.function.name),
+        escape(&tool_call.id),
+    )
+}
+
+fn render_diff_line(line: &str) -> String {
+    let class = if line.starts_with("+++") || line.starts_with("---") {
+        None
+    } else if line.starts_with('+') {
+        Some("diff-added")
+    } else if line.starts_with('-') {
+        Some("diff-removed")
+    } else if line.starts_with("@@") {
+        Some("diff-hunk")
+    } else {
+        None
+    };
+
+    match class {
+        Some(class) => format!(r#"<span class="{class}">{}</span>"#, escape(line)),
+        None => escape(line),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::assert_snapshot;
+
+    #[test]
+    fn bookmarks_are_listed_and_linked_to() -> anyhow::Result<()> {
+        // GIVEN
+        let transcript = r#"{"event":"started","provider":"anthropic","model":"claude-sonnet-4-5","text_tool_calls":false}
+{"event":"prompt","text":"should retries use backoff?"}
+{"event":"response","text":"Yes, exponential.","input_tokens":100,"output_tokens":5}
+{"event":"bookmark","turn":1,"note":"decided on <exponential> backoff"}
+{"event":"bookmark","turn":1}"#;
+
+        // WHEN
+        let html = transcript_to_html(transcript, "2025-06-10-09-00-00")?;
+
+        // THEN
+        let bookmark_lines = html
+            .lines()
+            .filter(|l| l.contains("event-") || l.contains("turn 1"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_snapshot!(bookmark_lines, @r##"
+          <li><a class="underline" href="#event-3">turn 1: decided on &lt;exponential&gt; backoff</a></li>
+          <li><a class="underline" href="#event-4">turn 1</a></li>
+        <div class="flex gap-3 items-start" id="event-3">
+        <div class="p-2 bg-[#3c3836] rounded text-sm whitespace-pre-wrap">turn 1: decided on &lt;exponential&gt; backoff</div>
+        <div class="flex gap-3 items-start" id="event-4">
+        <div class="p-2 bg-[#3c3836] rounded text-sm whitespace-pre-wrap">turn 1</div>
+        "##);
+
+        Ok(())
+    }
+
+    #[test]
+    fn file_edits_are_rendered_as_diffs() -> anyhow::Result<()> {
+        // GIVEN
+        let event: TranscriptEvent = serde_json::from_str(
+            r#"{"event":"response","text":"Fixing the <Button> label.","tool_calls":[{"id":"call_1","function":{"name":"edit_file","arguments":{"path":"src/button.tsx","old_str":"<b>Save</b>\n","new_str":"<b>Submit</b>\n"}}}],"input_tokens":100,"output_tokens":20}"#,
+        )?;
+
+        // WHEN
+        let html = render_event(&event, 2);
+
+        // THEN
+        assert_snapshot!(html, @r#"
+        <div class="flex gap-3 items-start">
+        <div class="flex-shrink-0 w-36 p-3 rounded text-sm font-mono" style="background-color: #fbf1c7">
+          <div class="font-semibold text-[#282828]">response</div>
+          <div class="text-xs text-[#282828] opacity-70 mt-2">2</div>
+        </div>
+        <div class="flex-1 flex flex-col gap-2 min-w-0">
+        <div class="p-2 bg-[#3c3836] rounded text-sm whitespace-pre-wrap">Fixing the &lt;Button&gt; label.</div>
+        <div class="p-2 bg-[#3c3836] rounded">
+          <div class="flex gap-2 items-center mb-1">
+            <span class="font-mono text-sm bg-[#282828] px-1 rounded">edit_file</span>
+            <span class="text-xs text-[#a89984]">id: call_1</span>
+          </div>
+          <pre class="text-xs bg-[#282828] p-1 rounded whitespace-pre-wrap break-all">--- src/button.tsx
+        +++ src/button.tsx
+        <span class="diff-hunk">@@ -1 +1 @@</span>
+        <span class="diff-removed">-&lt;b&gt;Save&lt;/b&gt;</span>
+        <span class="diff-added">+&lt;b&gt;Submit&lt;/b&gt;</span></pre>
+        </div>
+        </div>
+        </div>
+        "#);
+
+        Ok(())
+    }
+}
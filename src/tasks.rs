@@ -0,0 +1,179 @@
+use crate::cli::ProviderArgs;
+use agx_core::domain::TaskList;
+use agx_core::worktree::branch_exists;
+use anyhow::Context;
+use chrono::Local;
+use colored::Colorize;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use tokio::process::Command;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+struct TaskOutcome {
+    name: String,
+    branch: String,
+    log_path: PathBuf,
+    result: anyhow::Result<()>,
+    has_changes: bool,
+}
+
+/// Runs the tasks in a task list concurrently, each via `agx auto` in a worktree of its own.
+/// The output of each task is written to a log file; branches of tasks that made changes are
+/// kept for review.
+pub async fn run_tasks(
+    task_list_path: &Path,
+    project_dir: &Path,
+    project_log_dir: &Path,
+    provider: &ProviderArgs,
+) -> anyhow::Result<()> {
+    let contents = tokio::fs::read_to_string(task_list_path)
+        .await
+        .with_context(|| {
+            format!(
+                "couldn't read task list from {}",
+                task_list_path.to_string_lossy()
+            )
+        })?;
+    let task_list: TaskList = contents.parse().context("couldn't parse task list")?;
+
+    let run_id = Local::now().format("%Y-%m-%d-%H-%M-%S").to_string();
+    let log_dir = project_log_dir.join("tasks").join(&run_id);
+    tokio::fs::create_dir_all(&log_dir).await.with_context(|| {
+        format!(
+            "couldn't create directory for task logs: {}",
+            log_dir.to_string_lossy()
+        )
+    })?;
+
+    let agx = std::env::current_exe().context("couldn't determine agx's executable")?;
+    println!(
+        "{}",
+        format!(
+            "running {} tasks ({} at a time); logs: {}",
+            task_list.tasks.len(),
+            task_list.concurrency,
+            log_dir.to_string_lossy()
+        )
+        .blue()
+    );
+
+    let semaphore = Arc::new(Semaphore::new(task_list.concurrency));
+    let mut join_set = JoinSet::new();
+    for task in task_list.tasks {
+        let semaphore = Arc::clone(&semaphore);
+        let agx = agx.clone();
+        let provider = provider.clone();
+        let project_dir = project_dir.to_path_buf();
+        let branch = format!("agx/tasks-{run_id}/{}", task.name);
+        let log_path = log_dir.join(format!("{}.log", task.name));
+
+        join_set.spawn(async move {
+            let _permit = semaphore.acquire_owned().await;
+            println!("{}", format!("started {}", task.name).blue());
+
+            let result = run_auto(
+                &agx,
+                &provider,
+                &project_dir,
+                &task.prompt,
+                &branch,
+                false,
+                &log_path,
+            )
+            .await;
+            // the worktree (and its branch) is removed if the task made no changes
+            let has_changes = branch_exists(&project_dir, &branch).await;
+            let outcome = TaskOutcome {
+                name: task.name,
+                branch,
+                log_path,
+                result,
+                has_changes,
+            };
+            print_outcome(&outcome);
+
+            outcome
+        });
+    }
+
+    let mut outcomes = join_set.join_all().await;
+    outcomes.sort_by(|a, b| a.name.cmp(&b.name));
+
+    println!("\n{}", "summary".bold());
+    for outcome in &outcomes {
+        print_outcome(outcome);
+    }
+
+    let failed = outcomes.iter().filter(|o| o.result.is_err()).count();
+    if failed > 0 {
+        anyhow::bail!("{failed} of {} tasks failed", outcomes.len());
+    }
+
+    Ok(())
+}
+
+/// Runs `agx auto` in a worktree on `branch`, with its output written to a log file; if
+/// `open_pr` is set, a pull request is opened for the changes made.
+pub async fn run_auto(
+    agx: &Path,
+    provider: &ProviderArgs,
+    project_dir: &Path,
+    prompt: &str,
+    branch: &str,
+    open_pr: bool,
+    log_path: &Path,
+) -> anyhow::Result<()> {
+    let log_file = std::fs::File::create(log_path).context("couldn't create log file")?;
+    let stderr = log_file
+        .try_clone()
+        .context("couldn't set up logging of stderr")?;
+
+    // the provider flags this process was started with (if any) apply to the tasks as well
+    let mut args = provider.to_args();
+    args.extend(["--worktree", "--worktree-branch", branch, "auto"].map(String::from));
+    if open_pr {
+        args.push("--open-pr".to_string());
+    }
+    args.extend(["--", prompt].map(String::from));
+
+    let status = Command::new(agx)
+        .args(args)
+        // the output goes to a log file
+        .env("NO_COLOR", "1")
+        .current_dir(project_dir)
+        .stdin(Stdio::null())
+        .stdout(log_file)
+        .stderr(stderr)
+        .status()
+        .await
+        .context("couldn't run agx")?;
+
+    if !status.success() {
+        anyhow::bail!("agx exited with {status}");
+    }
+
+    Ok(())
+}
+
+fn print_outcome(outcome: &TaskOutcome) {
+    let changes = if outcome.has_changes {
+        format!("changes on branch {}", outcome.branch)
+    } else {
+        "no changes".to_string()
+    };
+
+    match &outcome.result {
+        Ok(_) => println!("{}", format!("✓ {}: {changes}", outcome.name).green()),
+        Err(e) => println!(
+            "{}",
+            format!(
+                "✗ {}: {e:#}; {changes} (see {})",
+                outcome.name,
+                outcome.log_path.to_string_lossy()
+            )
+            .red()
+        ),
+    }
+}
@@ -0,0 +1,175 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Tool outputs longer than this are stored as artifacts instead of being sent to the model.
+const MAX_INLINE_OUTPUT_CHARS: usize = 30_000;
+const EXCERPT_LINES: usize = 20;
+const MAX_EXCERPT_LINE_CHARS: usize = 200;
+
+/// A directory where oversized tool outputs are written, so that the model only gets an excerpt
+/// of them, and can read the rest in slices (via `read_artifact`) if it needs to.
+#[derive(Debug)]
+pub struct ArtifactStore {
+    dir: PathBuf,
+    saved: AtomicUsize,
+}
+
+/// A saved artifact; `name` is how the model refers to it.
+#[derive(Debug, Clone, Serialize)]
+pub struct Artifact {
+    pub name: String,
+    pub path: PathBuf,
+    pub lines: usize,
+    pub bytes: usize,
+}
+
+#[derive(Serialize)]
+struct OffloadedOutput<'a> {
+    artifact: &'a Artifact,
+    excerpt: String,
+    note: String,
+}
+
+impl ArtifactStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            saved: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Saves `contents` as a new artifact; `label` ends up in its name.
+    pub async fn save(&self, label: &str, contents: &str) -> std::io::Result<Artifact> {
+        tokio::fs::create_dir_all(&self.dir).await?;
+
+        let number = self.saved.fetch_add(1, Ordering::Relaxed) + 1;
+        let name = format!("{number:03}-{label}.txt");
+        let path = self.dir.join(&name);
+        tokio::fs::write(&path, contents).await?;
+
+        Ok(Artifact {
+            name,
+            path,
+            lines: contents.lines().count(),
+            bytes: contents.len(),
+        })
+    }
+
+    /// Returns the path of an artifact, if `name` could refer to one.
+    pub fn path(&self, name: &str) -> Option<PathBuf> {
+        let is_valid = !name.is_empty()
+            && !name.starts_with('.')
+            && !name.contains(['/', '\\'])
+            && Path::new(name).file_name().is_some();
+
+        is_valid.then(|| self.dir.join(name))
+    }
+
+    /// Returns tool output as is if it's small enough; otherwise, saves it as an artifact, and
+    /// returns a pointer to it along with an excerpt. Output is saved in a readable form (eg.
+    /// JSON strings are unescaped) so that it can be read in slices of lines.
+    pub async fn offload(&self, tool_name: &str, output: String) -> String {
+        if output.chars().count() <= MAX_INLINE_OUTPUT_CHARS {
+            return output;
+        }
+
+        let readable = readable_output(&output);
+        let artifact = match self.save(tool_name, &readable).await {
+            Ok(a) => a,
+            Err(e) => {
+                tracing::warn!("couldn't save tool output as an artifact: {e}");
+                return output;
+            }
+        };
+
+        let offloaded = OffloadedOutput {
+            artifact: &artifact,
+            excerpt: excerpt(&readable),
+            note: format!(
+                "the output was too large to include, so it was saved as an artifact; only its first {EXCERPT_LINES} lines are shown. Use read_artifact to read more of it, preferably in slices"
+            ),
+        };
+
+        serde_json::to_string(&offloaded).unwrap_or(output)
+    }
+}
+
+/// Unwraps JSON output into lines: strings are unescaped, and objects are rendered with one
+/// field per line (multi line strings get a section of their own).
+fn readable_output(output: &str) -> String {
+    match serde_json::from_str::<Value>(output) {
+        Ok(Value::String(s)) => s,
+        Ok(Value::Object(fields)) => {
+            let mut readable = String::new();
+            for (key, value) in fields {
+                match value {
+                    Value::String(s) if s.contains('\n') => {
+                        readable.push_str(&format!("--- {key} ---\n{s}"));
+                        if !s.ends_with('\n') {
+                            readable.push('\n');
+                        }
+                    }
+                    Value::String(s) => readable.push_str(&format!("{key}: {s}\n")),
+                    other => readable.push_str(&format!("{key}: {other}\n")),
+                }
+            }
+            readable
+        }
+        _ => output.to_string(),
+    }
+}
+
+fn excerpt(contents: &str) -> String {
+    contents
+        .lines()
+        .take(EXCERPT_LINES)
+        .map(
+            |line| match line.char_indices().nth(MAX_EXCERPT_LINE_CHARS) {
+                Some((i, _)) => format!("{}...", &line[..i]),
+                None => line.to_string(),
+            },
+        )
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::assert_snapshot;
+
+    #[tokio::test]
+    async fn oversized_output_is_replaced_with_an_excerpt_and_a_pointer() -> anyhow::Result<()> {
+        // GIVEN
+        let temp_dir = tempfile::tempdir()?;
+        let dir = temp_dir.path();
+        let store = ArtifactStore::new(dir);
+        let stdout = (1..=5000)
+            .map(|i| format!("line {i}\n"))
+            .collect::<String>();
+        let output = serde_json::json!({
+            "success": true,
+            "stdout": stdout,
+        })
+        .to_string();
+
+        // WHEN
+        let small = store.offload("run_cmd", "ok".to_string()).await;
+        let large = store.offload("run_cmd", output).await;
+
+        // THEN
+        assert_eq!(small, "ok");
+        let large = large.replace(&dir.to_string_lossy().to_string(), "<dir>");
+        assert_snapshot!(large, @r#"{"artifact":{"name":"001-run_cmd.txt","path":"<dir>/001-run_cmd.txt","lines":5002,"bytes":48922},"excerpt":"--- stdout ---\nline 1\nline 2\nline 3\nline 4\nline 5\nline 6\nline 7\nline 8\nline 9\nline 10\nline 11\nline 12\nline 13\nline 14\nline 15\nline 16\nline 17\nline 18\nline 19","note":"the output was too large to include, so it was saved as an artifact; only its first 20 lines are shown. Use read_artifact to read more of it, preferably in slices"}"#);
+        let saved = std::fs::read_to_string(dir.join("001-run_cmd.txt"))?;
+        assert_eq!(saved.lines().count(), 5002);
+
+        Ok(())
+    }
+}
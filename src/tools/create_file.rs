@@ -1,24 +1,36 @@
-use crate::helpers::is_path_in_workspace;
+use super::{
+    AgxTool, ConfirmationPolicy, Formatting, Lint, ToolError, ToolErrorKind, Workspace,
+    format_written_file, lint_written_file,
+};
+use crate::helpers::{Diff, TextEncoding, generated_file_warning, read_text_file};
 use rig::completion::ToolDefinition;
 use rig::tool::Tool;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use std::path::PathBuf;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
 use tracing::instrument;
 
 #[derive(Debug, Deserialize)]
 pub struct CreateFileArgs {
     pub path: String,
     pub contents: String,
+    #[serde(default)]
+    pub overwrite: bool,
+    #[serde(default)]
+    pub executable: bool,
 }
 
 impl std::fmt::Display for CreateFileArgs {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "path={}, num_lines={}",
+            "path={}, num_lines={}, overwrite={}, executable={}",
             self.path,
-            self.contents.lines().count()
+            self.contents.lines().count(),
+            self.overwrite,
+            self.executable,
         )
     }
 }
@@ -35,19 +47,74 @@ pub enum CreateFileError {
     AlreadyExists,
     #[error("a directory already exists at this path")]
     IsADir,
+    #[error("writing {size} bytes exceeds the limit of {max} bytes for a single write")]
+    WriteTooLarge { size: u64, max: usize },
     #[error("couldn't create directory: {0}")]
     CouldntCreateDirectory(std::io::Error),
+    #[error("couldn't keep the file's encoding: {0}")]
+    CouldntKeepEncoding(std::io::Error),
     #[error("couldn't write to file: {0}")]
     CouldntWriteToFile(std::io::Error),
+    #[error("couldn't make file executable: {0}")]
+    CouldntSetPermissions(std::io::Error),
 }
 
-#[derive(Deserialize, Serialize)]
-pub struct CreateFileTool;
+impl ToolErrorKind for CreateFileError {
+    fn code(&self) -> &'static str {
+        match self {
+            CreateFileError::InvalidInput(_) => "invalid_input",
+            CreateFileError::PathNotAllowed => "path_not_allowed",
+            CreateFileError::CouldntGetMetadata(_) => "couldnt_get_metadata",
+            CreateFileError::AlreadyExists => "file_already_exists",
+            CreateFileError::IsADir => "is_a_dir",
+            CreateFileError::WriteTooLarge { .. } => "write_too_large",
+            CreateFileError::CouldntCreateDirectory(_) => "couldnt_create_dir",
+            CreateFileError::CouldntKeepEncoding(_) => "couldnt_keep_encoding",
+            CreateFileError::CouldntWriteToFile(_) => "couldnt_write_file",
+            CreateFileError::CouldntSetPermissions(_) => "couldnt_set_permissions",
+        }
+    }
+
+    fn hint(&self) -> Option<&'static str> {
+        match self {
+            CreateFileError::PathNotAllowed => Some("use a path relative to the project directory"),
+            CreateFileError::AlreadyExists => Some(
+                "use edit_file to change the file, or set overwrite to true to replace it entirely",
+            ),
+            CreateFileError::WriteTooLarge { .. } => Some(
+                "change large files in parts via edit_file, or generate them via a command (run_cmd) instead of writing them whole",
+            ),
+            CreateFileError::CouldntKeepEncoding(_) => Some(
+                "use characters the file's encoding can represent (eg. an escape sequence, or an ASCII equivalent)",
+            ),
+            _ => None,
+        }
+    }
+}
+
+pub struct CreateFileTool {
+    workspace: Arc<Workspace>,
+}
+
+impl CreateFileTool {
+    pub fn new(workspace: Arc<Workspace>) -> Self {
+        Self { workspace }
+    }
+}
 
 #[derive(Debug, Serialize)]
 pub struct CreateFileResponse {
     path: String,
     pub num_bytes_written: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    warning: Option<String>,
+    /// Set when an overwritten file that isn't in UTF-8 was written back in its encoding.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    encoding: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    formatting: Option<Formatting>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lint: Option<Lint>,
 }
 
 impl Tool for CreateFileTool {
@@ -71,6 +138,14 @@ impl Tool for CreateFileTool {
                         "type": "string",
                         "description": "contents to write"
                     },
+                    "overwrite": {
+                        "type": "boolean",
+                        "description": "replace the file if it already exists (defaults to false)"
+                    },
+                    "executable": {
+                        "type": "boolean",
+                        "description": "make the file executable (defaults to false)"
+                    },
                 },
                 "required": ["path", "contents"],
             }),
@@ -86,47 +161,229 @@ impl Tool for CreateFileTool {
             ));
         }
         let contents = args.contents;
-
-        let path = PathBuf::from(&args.path);
-        if !is_path_in_workspace(&path) {
-            return Err(CreateFileError::PathNotAllowed);
+        let max_write_bytes = self.workspace.max_write_bytes();
+        if contents.len() > max_write_bytes {
+            return Err(CreateFileError::WriteTooLarge {
+                size: contents.len() as u64,
+                max: max_write_bytes,
+            });
         }
 
-        match tokio::fs::metadata(&path).await {
+        let path = self
+            .workspace
+            .resolve_for_write(&args.path)
+            .ok_or(CreateFileError::PathNotAllowed)?;
+
+        let exists = match tokio::fs::metadata(&path).await {
             Ok(m) => {
                 if m.is_dir() {
                     Err(CreateFileError::IsADir)
+                } else if args.overwrite && m.len() > max_write_bytes as u64 {
+                    // rewriting a large file whole would replace all of it, even if the new
+                    // contents are small
+                    Err(CreateFileError::WriteTooLarge {
+                        size: m.len(),
+                        max: max_write_bytes,
+                    })
+                } else if args.overwrite {
+                    Ok(true)
                 } else {
                     Err(CreateFileError::AlreadyExists)
                 }
             }
-            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
             Err(e) => Err(CreateFileError::CouldntGetMetadata(e)),
         }?;
 
+        // overwritten text files are written back in their own encoding; files that can't be
+        // read as text are replaced with UTF-8 contents
+        let encoding = if exists {
+            read_text_file(&path)
+                .await
+                .map(|(_, encoding)| encoding)
+                .unwrap_or(TextEncoding::Utf8)
+        } else {
+            TextEncoding::Utf8
+        };
+        let bytes = encoding
+            .encode(&contents)
+            .map_err(CreateFileError::CouldntKeepEncoding)?;
+
         if let Some(parent) = path.parent() {
             tokio::fs::create_dir_all(parent)
                 .await
                 .map_err(CreateFileError::CouldntCreateDirectory)?;
         }
 
-        tokio::fs::write(&path, &contents)
+        tokio::fs::write(&path, &bytes)
             .await
             .map_err(CreateFileError::CouldntWriteToFile)?;
 
+        if args.executable {
+            make_executable(&path)
+                .await
+                .map_err(CreateFileError::CouldntSetPermissions)?;
+        }
+
         Ok(CreateFileResponse {
-            path: path.to_string_lossy().to_string(),
-            num_bytes_written: contents.len(),
+            warning: generated_file_warning(&args.path),
+            encoding: (encoding != TextEncoding::Utf8).then(|| encoding.to_string()),
+            formatting: format_written_file(&self.workspace, &args.path, &contents).await,
+            lint: lint_written_file(&self.workspace, &args.path).await,
+            path: args.path,
+            num_bytes_written: bytes.len(),
         })
     }
 }
 
-impl CreateFileTool {
-    pub fn repr(args: &CreateFileArgs) -> String {
+impl AgxTool for CreateFileTool {
+    fn repr(args: &CreateFileArgs) -> String {
         format!("create_file: {}", args.path)
     }
 
-    pub fn details(args: &CreateFileArgs) -> Option<String> {
-        Some(args.contents.clone())
+    fn confirmation_policy(args: &CreateFileArgs) -> ConfirmationPolicy {
+        ConfirmationPolicy::FileChange(args.path.clone())
+    }
+
+    async fn details(&self, args: &CreateFileArgs) -> Result<Option<String>, ToolError> {
+        if args.overwrite
+            && let Ok((existing, _)) = read_text_file(self.workspace.resolve(&args.path)).await
+        {
+            return match Diff::with_options(
+                &existing,
+                &args.contents,
+                self.workspace.diff_options(),
+            ) {
+                Some(diff) => Ok(Some(diff.get_terminal_output())),
+                None => Ok(Some("(contents are unchanged)".to_string())),
+            };
+        }
+
+        Ok(Some(args.contents.clone()))
+    }
+
+    fn summary(response: &CreateFileResponse, _elapsed: Duration) -> String {
+        let notes = [
+            response.formatting.as_ref().and_then(Formatting::summary),
+            response.lint.as_ref().and_then(Lint::summary),
+        ];
+
+        let mut summary = format!("wrote {} bytes", response.num_bytes_written);
+        for note in notes.into_iter().flatten() {
+            summary.push_str(&format!("; {note}"));
+        }
+        summary
+    }
+}
+
+#[cfg(unix)]
+async fn make_executable(path: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mut permissions = tokio::fs::metadata(path).await?.permissions();
+    // add execute permissions wherever read permissions are present
+    let mode = permissions.mode();
+    permissions.set_mode(mode | ((mode & 0o444) >> 2));
+    tokio::fs::set_permissions(path, permissions).await
+}
+
+#[cfg(not(unix))]
+async fn make_executable(_path: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::assert_snapshot;
+
+    fn args(path: &str, contents: &str, overwrite: bool, executable: bool) -> CreateFileArgs {
+        CreateFileArgs {
+            path: path.to_string(),
+            contents: contents.to_string(),
+            overwrite,
+            executable,
+        }
+    }
+
+    #[tokio::test]
+    async fn existing_files_are_only_replaced_when_overwrite_is_set() -> anyhow::Result<()> {
+        // GIVEN
+        let temp_dir = tempfile::tempdir()?;
+        let dir = temp_dir.path();
+        tokio::fs::write(dir.join("notes.txt"), "old\n").await?;
+        let tool = CreateFileTool::new(Arc::new(Workspace::new(dir)));
+
+        // WHEN
+        let error = tool
+            .call(args("notes.txt", "new\n", false, false))
+            .await
+            .expect_err("create should've been refused");
+        let contents_after_refusal = tokio::fs::read_to_string(dir.join("notes.txt")).await?;
+        tool.call(args("notes.txt", "new\n", true, false)).await?;
+
+        // THEN
+        assert_snapshot!(ToolError::from(&error).to_json(), @r#"
+        {"error":{"code":"file_already_exists","message":"file already exists","hint":"use edit_file to change the file, or set overwrite to true to replace it entirely"}}
+        "#);
+        assert_eq!(contents_after_refusal, "old\n");
+        assert_eq!(
+            tokio::fs::read_to_string(dir.join("notes.txt")).await?,
+            "new\n"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn overwritten_files_keep_their_encoding() -> anyhow::Result<()> {
+        // GIVEN
+        let temp_dir = tempfile::tempdir()?;
+        let dir = temp_dir.path();
+        tokio::fs::write(dir.join("notes.txt"), b"caf\xe9 ouvert\n").await?;
+        let tool = CreateFileTool::new(Arc::new(Workspace::new(dir)));
+
+        // WHEN
+        let response = tool
+            .call(args("notes.txt", "café fermé\n", true, false))
+            .await?;
+        let error = tool
+            .call(args("notes.txt", "café → fermé\n", true, false))
+            .await
+            .expect_err("overwrite should've been refused");
+
+        // THEN
+        assert_eq!(response.encoding.as_deref(), Some("Latin-1"));
+        assert_eq!(
+            tokio::fs::read(dir.join("notes.txt")).await?,
+            b"caf\xe9 ferm\xe9\n"
+        );
+        assert_snapshot!(ToolError::from(&error).to_json(), @r#"
+        {"error":{"code":"couldnt_keep_encoding","message":"couldn't keep the file's encoding: '→' can't be represented in Latin-1","hint":"use characters the file's encoding can represent (eg. an escape sequence, or an ASCII equivalent)"}}
+        "#);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn files_can_be_made_executable() -> anyhow::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        // GIVEN
+        let temp_dir = tempfile::tempdir()?;
+        let dir = temp_dir.path();
+        let tool = CreateFileTool::new(Arc::new(Workspace::new(dir)));
+
+        // WHEN
+        tool.call(args("run.sh", "#!/bin/sh\n", false, true))
+            .await?;
+        tool.call(args("notes.txt", "notes\n", false, false))
+            .await?;
+
+        // THEN
+        let mode =
+            |name: &str| std::fs::metadata(dir.join(name)).map(|m| m.permissions().mode() & 0o111);
+        assert_ne!(mode("run.sh")?, 0, "run.sh should've been made executable");
+        assert_eq!(mode("notes.txt")?, 0, "notes.txt shouldn't be executable");
+        Ok(())
     }
 }
@@ -0,0 +1,120 @@
+use super::tool_call::{DynTool, DynToolCall};
+use super::{BoxFuture, ConfirmationPolicy, ToolCallOutcome, ToolError, ToolExecutionError};
+use crate::domain::{OutputKind, OutputSink};
+use crate::helpers::Diff;
+use rig::completion::ToolDefinition;
+use rig::tool::Tool;
+use std::fmt::Debug;
+use std::sync::Arc;
+use tokio::time::Instant;
+
+/// Adapts a plain rig [`Tool`] (eg. one supplied by an embedder) to the tool registry. Calls are
+/// shown with their raw arguments, and errors are passed on to the model as is.
+pub(super) struct CustomTool<T> {
+    tool: Arc<T>,
+    policy: ConfirmationPolicy,
+}
+
+struct CustomToolCall<T: Tool> {
+    tool: Arc<T>,
+    policy: ConfirmationPolicy,
+    raw_args: serde_json::Value,
+    args: T::Args,
+}
+
+impl<T> CustomTool<T> {
+    pub(super) fn new(tool: T, policy: ConfirmationPolicy) -> Self {
+        Self {
+            tool: Arc::new(tool),
+            policy,
+        }
+    }
+}
+
+impl<T> DynTool for CustomTool<T>
+where
+    T: Tool + 'static,
+{
+    fn definition(&self) -> BoxFuture<'_, ToolDefinition> {
+        Box::pin(self.tool.definition(String::new()))
+    }
+
+    fn parse(
+        self: Arc<Self>,
+        args: serde_json::Value,
+    ) -> Result<Box<dyn DynToolCall>, serde_json::Error> {
+        let parsed = serde_json::from_value(args.clone())?;
+
+        Ok(Box::new(CustomToolCall {
+            tool: Arc::clone(&self.tool),
+            policy: self.policy.clone(),
+            raw_args: args,
+            args: parsed,
+        }))
+    }
+}
+
+impl<T: Tool> Debug for CustomToolCall<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CustomToolCall")
+            .field("name", &T::NAME)
+            .field("args", &self.raw_args)
+            .finish()
+    }
+}
+
+impl<T> DynToolCall for CustomToolCall<T>
+where
+    T: Tool + 'static,
+{
+    fn name(&self) -> &'static str {
+        T::NAME
+    }
+
+    fn repr(&self) -> String {
+        format!("{}: {}", T::NAME, self.raw_args)
+    }
+
+    fn confirmation_policy(&self) -> ConfirmationPolicy {
+        self.policy.clone()
+    }
+
+    fn details(&self) -> BoxFuture<'_, Result<Option<String>, ToolError>> {
+        Box::pin(async { Ok(None) })
+    }
+
+    fn hunk_diff(&self) -> BoxFuture<'_, Option<Diff>> {
+        Box::pin(async { None })
+    }
+
+    fn set_accepted_hunks(&mut self, _accepted: Vec<bool>) {}
+
+    fn execute(
+        self: Box<Self>,
+        output: Arc<dyn OutputSink>,
+    ) -> BoxFuture<'static, Result<ToolCallOutcome, ToolExecutionError>> {
+        Box::pin(async move {
+            output.emit(OutputKind::ToolCall, &format!("{} ", self.repr()));
+
+            let start = Instant::now();
+            let result = self.tool.call(self.args).await;
+            let elapsed = start.elapsed().as_millis();
+
+            match result {
+                Ok(response) => {
+                    output.emit(OutputKind::Success, &format!("✓ (took {elapsed} ms)\n"));
+                    serde_json::to_string(&response)
+                        .map(ToolCallOutcome::Succeeded)
+                        .map_err(ToolExecutionError::CouldntSerialiseResult)
+                }
+                Err(e) => {
+                    output.emit(OutputKind::Error, &format!("✗ (took {elapsed} ms)\n"));
+                    Ok(ToolCallOutcome::Failed(ToolError::new(
+                        "tool_failed",
+                        e.to_string(),
+                    )))
+                }
+            }
+        })
+    }
+}
@@ -1,9 +1,18 @@
-use crate::helpers::{Diff, is_path_in_workspace};
+use super::{
+    AgxTool, ConfirmationPolicy, Formatting, Lint, ToolError, ToolErrorKind, Workspace,
+    format_written_file, lint_written_file,
+};
+use crate::helpers::{
+    Diff, TextEncoding, apply_hunks, generated_file_warning, read_text_file, unified_diff,
+};
 use rig::completion::ToolDefinition;
 use rig::tool::Tool;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use similar::TextDiff;
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 use tracing::instrument;
 
 #[derive(Debug, Deserialize)]
@@ -11,6 +20,11 @@ pub struct EditFileArgs {
     pub path: String,
     pub old_str: String,
     pub new_str: String,
+    #[serde(default)]
+    pub ignore_whitespace: bool,
+    /// Set when the user chose to apply only some of the hunks of the change.
+    #[serde(skip)]
+    pub accepted_hunks: Option<Vec<bool>>,
 }
 
 impl std::fmt::Display for EditFileArgs {
@@ -39,15 +53,221 @@ pub enum EditFileError {
     CouldntWriteToFile(std::io::Error),
     #[error("nothing will change in the file")]
     NothingWillChange,
+    #[error("replacing {size} bytes exceeds the limit of {max} bytes for a single write")]
+    WriteTooLarge { size: usize, max: usize },
+    #[error("old_str wasn't found in the file{0}")]
+    OldStrNotFound(ClosestMatches),
+    #[error("couldn't keep the file's encoding: {0}")]
+    CouldntKeepEncoding(std::io::Error),
 }
 
-#[derive(Deserialize, Serialize)]
-pub struct EditFileTool;
+impl ToolErrorKind for EditFileError {
+    fn code(&self) -> &'static str {
+        match self {
+            EditFileError::InvalidInput(_) => "invalid_input",
+            EditFileError::PathNotAllowed => "path_not_allowed",
+            EditFileError::NoChangesRequested => "no_changes_requested",
+            EditFileError::CouldntGetMetadata(_) => "couldnt_get_metadata",
+            EditFileError::NotAFile => "not_a_file",
+            EditFileError::FileDoesntExist => "file_not_found",
+            EditFileError::CouldntReadFile(_) => "couldnt_read_file",
+            EditFileError::CouldntWriteToFile(_) => "couldnt_write_file",
+            EditFileError::NothingWillChange => "nothing_will_change",
+            EditFileError::WriteTooLarge { .. } => "write_too_large",
+            EditFileError::OldStrNotFound(_) => "old_str_not_found",
+            EditFileError::CouldntKeepEncoding(_) => "couldnt_keep_encoding",
+        }
+    }
+
+    fn hint(&self) -> Option<&'static str> {
+        match self {
+            EditFileError::PathNotAllowed => Some("use a path relative to the project directory"),
+            EditFileError::FileDoesntExist => Some("use create_file to create new files"),
+            EditFileError::OldStrNotFound(_) => Some(
+                "read the file again and copy old_str exactly, or set ignore_whitespace to true",
+            ),
+            EditFileError::WriteTooLarge { .. } => Some(
+                "split the change into several edit_file calls, each replacing a smaller part of the file",
+            ),
+            EditFileError::CouldntKeepEncoding(_) => Some(
+                "use characters the file's encoding can represent (eg. an escape sequence, or an ASCII equivalent)",
+            ),
+            _ => None,
+        }
+    }
+}
+
+const MAX_CLOSEST_MATCHES: usize = 3;
+const MIN_MATCH_SIMILARITY: f32 = 0.5;
+
+#[derive(Debug, PartialEq)]
+pub struct ClosestMatch {
+    start_line: usize,
+    end_line: usize,
+    similarity: f32,
+    text: String,
+}
+
+#[derive(Debug)]
+pub struct ClosestMatches(Vec<ClosestMatch>);
+
+impl std::fmt::Display for ClosestMatches {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.0.is_empty() {
+            return Ok(());
+        }
+
+        write!(
+            f,
+            "; closest matches (use the exact text from one of these as old_str):"
+        )?;
+        for m in &self.0 {
+            let location = if m.start_line == m.end_line {
+                format!("line {}", m.start_line)
+            } else {
+                format!("lines {}-{}", m.start_line, m.end_line)
+            };
+            write!(
+                f,
+                "\n- {} (similarity: {:.2}):\n{}",
+                location, m.similarity, m.text
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Replaces every run of lines in `contents` that matches the lines of `old` when leading and
+/// trailing whitespace is ignored. The indentation of `new` is adjusted to that of the matched
+/// lines. Returns `None` if nothing matches.
+fn replace_ignoring_whitespace(contents: &str, old: &str, new: &str) -> Option<String> {
+    let old = old.trim_matches('\n');
+    let new = new.trim_matches('\n');
+    let old_lines = old.lines().map(str::trim).collect::<Vec<_>>();
+    if old_lines.iter().all(|l| l.is_empty()) {
+        return None;
+    }
+
+    let old_indent = indentation(old);
+    let lines = contents.lines().collect::<Vec<_>>();
+    let mut result = Vec::with_capacity(lines.len());
+    let mut num_replacements = 0;
+    let mut i = 0;
+
+    while i < lines.len() {
+        let end = i + old_lines.len();
+        let matches = end <= lines.len()
+            && lines[i..end]
+                .iter()
+                .zip(&old_lines)
+                .all(|(l, o)| l.trim() == *o);
+
+        if !matches {
+            result.push(lines[i].to_string());
+            i += 1;
+            continue;
+        }
+
+        let file_indent = indentation(lines[i]);
+        for line in new.lines() {
+            match line.strip_prefix(old_indent) {
+                Some(rest) if !line.is_empty() => result.push(format!("{file_indent}{rest}")),
+                _ => result.push(line.to_string()),
+            }
+        }
+        num_replacements += 1;
+        i = end;
+    }
+
+    if num_replacements == 0 {
+        return None;
+    }
+
+    let mut replaced = result.join("\n");
+    if contents.ends_with('\n') {
+        replaced.push('\n');
+    }
+
+    Some(replaced)
+}
+
+fn indentation(line: &str) -> &str {
+    &line[..line.len() - line.trim_start().len()]
+}
+
+/// Returns the regions of `contents` that most closely resemble `needle`, comparing windows
+/// spanning as many lines as `needle` does.
+fn closest_matches(contents: &str, needle: &str) -> Vec<ClosestMatch> {
+    let lines = contents.lines().collect::<Vec<_>>();
+    let window = needle.lines().count().max(1);
+    if lines.is_empty() {
+        return vec![];
+    }
+
+    let mut matches = lines
+        .windows(window.min(lines.len()))
+        .enumerate()
+        .map(|(i, w)| {
+            let text = w.join("\n");
+            let similarity = TextDiff::from_chars(needle.trim(), text.trim()).ratio();
+            ClosestMatch {
+                start_line: i + 1,
+                end_line: i + w.len(),
+                similarity,
+                text,
+            }
+        })
+        .filter(|m| m.similarity >= MIN_MATCH_SIMILARITY)
+        .collect::<Vec<_>>();
+
+    matches.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+
+    let mut selected: Vec<ClosestMatch> = vec![];
+    for m in matches {
+        // overlapping windows usually point at the same region
+        if selected
+            .iter()
+            .any(|s| m.start_line <= s.end_line && s.start_line <= m.end_line)
+        {
+            continue;
+        }
+        selected.push(m);
+        if selected.len() == MAX_CLOSEST_MATCHES {
+            break;
+        }
+    }
+
+    selected
+}
+
+pub struct EditFileTool {
+    workspace: Arc<Workspace>,
+}
+
+impl EditFileTool {
+    pub fn new(workspace: Arc<Workspace>) -> Self {
+        Self { workspace }
+    }
+}
 
 #[derive(Debug, Serialize)]
 pub struct EditFileResponse {
     path: String,
     pub num_bytes_written: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    note: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    applied_diff: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    warning: Option<String>,
+    /// Set for files that aren't in UTF-8.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    encoding: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    formatting: Option<Formatting>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lint: Option<Lint>,
 }
 
 impl Tool for EditFileTool {
@@ -69,12 +289,16 @@ impl Tool for EditFileTool {
                     },
                     "old_str": {
                         "type": "string",
-                        "description": "Replace all occurrences of this string with new_str"
+                        "description": "Replace all occurrences of this string with new_str (leave out the line numbers read_file prefixes lines with)"
                     },
                     "new_str": {
                         "type": "string",
                         "description": "string to replace with"
                     },
+                    "ignore_whitespace": {
+                        "type": "boolean",
+                        "description": "if old_str isn't found as is, match whole lines while ignoring differences in indentation and trailing spaces; new_str is re-indented to match the file"
+                    },
                 },
                 "required": ["path", "old_str", "new_str"],
             }),
@@ -83,33 +307,101 @@ impl Tool for EditFileTool {
 
     #[instrument(name = "tool-call: edit_file", skip(self), err)]
     async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
-        let path = PathBuf::from(&args.path);
-        let (_, new_contents) = Self::validate_and_read(&args).await?;
+        let (path, old_contents, mut new_contents, encoding) =
+            self.validate_and_read(&args).await?;
+
+        let mut note = None;
+        let mut applied_diff = None;
+        if let Some(accepted) = &args.accepted_hunks {
+            new_contents = apply_hunks(
+                &old_contents,
+                &new_contents,
+                accepted,
+                self.workspace.diff_options().context_lines,
+            );
+            note = Some(format!(
+                "the user only accepted {} of the {} hunks of the requested change; applied_diff contains the change that was actually made",
+                accepted.iter().filter(|a| **a).count(),
+                accepted.len(),
+            ));
+            applied_diff = Some(unified_diff(&old_contents, &new_contents, &args.path));
+        }
 
-        tokio::fs::write(&path, &new_contents)
+        let bytes = encoding
+            .encode(&new_contents)
+            .map_err(EditFileError::CouldntKeepEncoding)?;
+        tokio::fs::write(&path, &bytes)
             .await
             .map_err(EditFileError::CouldntWriteToFile)?;
 
         Ok(EditFileResponse {
-            path: path.to_string_lossy().to_string(),
-            num_bytes_written: new_contents.len(),
+            warning: generated_file_warning(&args.path),
+            encoding: (encoding != TextEncoding::Utf8).then(|| encoding.to_string()),
+            formatting: format_written_file(&self.workspace, &args.path, &new_contents).await,
+            lint: lint_written_file(&self.workspace, &args.path).await,
+            path: args.path,
+            num_bytes_written: bytes.len(),
+            note,
+            applied_diff,
         })
     }
 }
 
-impl EditFileTool {
-    pub fn repr(args: &EditFileArgs) -> String {
+impl AgxTool for EditFileTool {
+    fn repr(args: &EditFileArgs) -> String {
         format!("edit_file: {}", args.path)
     }
 
-    pub async fn details(args: &EditFileArgs) -> Result<Option<String>, EditFileError> {
-        let (old_contents, new_contents) = Self::validate_and_read(args).await?;
+    fn confirmation_policy(args: &EditFileArgs) -> ConfirmationPolicy {
+        ConfirmationPolicy::FileChange(args.path.clone())
+    }
 
-        let diff = Diff::new(&old_contents, &new_contents).map(|d| d.get_terminal_output());
+    async fn details(&self, args: &EditFileArgs) -> Result<Option<String>, ToolError> {
+        let diff = self
+            .diff(args)
+            .await
+            .map_err(|e| ToolError::from(&e))?
+            .map(|d| d.get_terminal_output());
         Ok(diff)
     }
 
-    async fn validate_and_read(args: &EditFileArgs) -> Result<(String, String), EditFileError> {
+    async fn hunk_diff(&self, args: &EditFileArgs) -> Option<Diff> {
+        self.diff(args).await.ok().flatten()
+    }
+
+    fn set_accepted_hunks(args: &mut EditFileArgs, accepted: Vec<bool>) {
+        args.accepted_hunks = Some(accepted);
+    }
+
+    fn summary(response: &EditFileResponse, _elapsed: Duration) -> String {
+        let notes = [
+            response.formatting.as_ref().and_then(Formatting::summary),
+            response.lint.as_ref().and_then(Lint::summary),
+        ];
+
+        let mut summary = format!("wrote {} bytes", response.num_bytes_written);
+        for note in notes.into_iter().flatten() {
+            summary.push_str(&format!("; {note}"));
+        }
+        summary
+    }
+}
+
+impl EditFileTool {
+    async fn diff(&self, args: &EditFileArgs) -> Result<Option<Diff>, EditFileError> {
+        let (_, old_contents, new_contents, _) = self.validate_and_read(args).await?;
+
+        Ok(Diff::with_options(
+            &old_contents,
+            &new_contents,
+            self.workspace.diff_options(),
+        ))
+    }
+
+    async fn validate_and_read(
+        &self,
+        args: &EditFileArgs,
+    ) -> Result<(PathBuf, String, String, TextEncoding), EditFileError> {
         if args.path.is_empty() {
             return Err(EditFileError::InvalidInput(
                 "path cannot be empty".to_string(),
@@ -126,11 +418,20 @@ impl EditFileTool {
             return Err(EditFileError::NoChangesRequested);
         }
 
-        let path = PathBuf::from(&args.path);
-        if !is_path_in_workspace(&path) {
-            return Err(EditFileError::PathNotAllowed);
+        let max_write_bytes = self.workspace.max_write_bytes();
+        let size = args.old_str.len().max(args.new_str.len());
+        if size > max_write_bytes {
+            return Err(EditFileError::WriteTooLarge {
+                size,
+                max: max_write_bytes,
+            });
         }
 
+        let path = self
+            .workspace
+            .resolve_for_write(&args.path)
+            .ok_or(EditFileError::PathNotAllowed)?;
+
         let metadata = tokio::fs::metadata(&path).await.map_err(|e| {
             if e.kind() == std::io::ErrorKind::NotFound {
                 EditFileError::FileDoesntExist
@@ -143,15 +444,149 @@ impl EditFileTool {
             return Err(EditFileError::NotAFile);
         }
 
-        let old_contents = tokio::fs::read_to_string(&path)
+        let (old_contents, encoding) = read_text_file(&path)
             .await
             .map_err(EditFileError::CouldntReadFile)?;
-        let new_contents = old_contents.replace(&args.old_str, &args.new_str);
+
+        let new_contents = if old_contents.contains(&args.old_str) {
+            old_contents.replace(&args.old_str, &args.new_str)
+        } else if let Some(new_contents) = args
+            .ignore_whitespace
+            .then(|| replace_ignoring_whitespace(&old_contents, &args.old_str, &args.new_str))
+            .flatten()
+        {
+            new_contents
+        } else {
+            return Err(EditFileError::OldStrNotFound(ClosestMatches(
+                closest_matches(&old_contents, &args.old_str),
+            )));
+        };
 
         if old_contents == new_contents {
             return Err(EditFileError::NothingWillChange);
         }
 
-        Ok((old_contents, new_contents))
+        // checked up front, so that changes that can't be made aren't offered for approval
+        encoding
+            .encode(&new_contents)
+            .map_err(EditFileError::CouldntKeepEncoding)?;
+
+        Ok((path, old_contents, new_contents, encoding))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::assert_snapshot;
+
+    #[test]
+    fn replacing_while_ignoring_whitespace_reindents_new_str() {
+        // GIVEN
+        let contents = "fn main() {\n    if true {\n        run();   \n        stop();\n    }\n}\n";
+        let old_str = "run();\nstop();";
+        let new_str = "run();\nif done {\n    stop();\n}";
+
+        // WHEN
+        let result = replace_ignoring_whitespace(contents, old_str, new_str).expect("should match");
+
+        // THEN
+        assert_snapshot!(result, @r"
+        fn main() {
+            if true {
+                run();
+                if done {
+                    stop();
+                }
+            }
+        }
+        ");
+    }
+
+    #[test]
+    fn closest_matches_are_reported_when_old_str_is_not_found() {
+        // GIVEN
+        let contents = r#"fn main() {
+    let name = "agx";
+    println!("hello, {}", name);
+}
+
+fn greet(name: &str) {
+    println!("hi, {}", name);
+}
+"#;
+        let old_str = r#"    println!("hello {}", name);"#;
+
+        // WHEN
+        let matches = ClosestMatches(closest_matches(contents, old_str));
+
+        // THEN
+        assert_snapshot!(matches.to_string(), @r#"
+        ; closest matches (use the exact text from one of these as old_str):
+        - line 3 (similarity: 0.98):
+            println!("hello, {}", name);
+        - line 7 (similarity: 0.88):
+            println!("hi, {}", name);
+        "#);
+    }
+
+    #[tokio::test]
+    async fn edits_larger_than_the_write_limit_are_refused() {
+        // GIVEN
+        let workspace = Arc::new(Workspace::new("."));
+        workspace.set_max_write_bytes(16);
+        let tool = EditFileTool::new(workspace);
+        let args = EditFileArgs {
+            path: "Cargo.lock".to_string(),
+            old_str: "[[package]]\nname = \"agx\"".to_string(),
+            new_str: "[[package]]".to_string(),
+            ignore_whitespace: false,
+            accepted_hunks: None,
+        };
+
+        // WHEN
+        let error = tool
+            .call(args)
+            .await
+            .expect_err("edit should've been refused");
+
+        // THEN
+        assert_snapshot!(ToolError::from(&error).to_json(), @r#"
+        {"error":{"code":"write_too_large","message":"replacing 24 bytes exceeds the limit of 16 bytes for a single write","hint":"split the change into several edit_file calls, each replacing a smaller part of the file"}}
+        "#);
+    }
+
+    #[tokio::test]
+    async fn edits_keep_the_files_encoding() -> anyhow::Result<()> {
+        // GIVEN
+        let temp_dir = tempfile::tempdir()?;
+        let dir = temp_dir.path();
+        tokio::fs::write(dir.join("notes.txt"), b"caf\xe9 ouvert\n").await?;
+        let tool = EditFileTool::new(Arc::new(Workspace::new(dir)));
+        let args = |old_str: &str, new_str: &str| EditFileArgs {
+            path: "notes.txt".to_string(),
+            old_str: old_str.to_string(),
+            new_str: new_str.to_string(),
+            ignore_whitespace: false,
+            accepted_hunks: None,
+        };
+
+        // WHEN
+        let response = tool.call(args("ouvert", "fermé")).await?;
+        let error = tool
+            .call(args("fermé", "fermé → ouvert"))
+            .await
+            .expect_err("edit should've been refused");
+
+        // THEN
+        assert_eq!(response.encoding.as_deref(), Some("Latin-1"));
+        assert_eq!(
+            tokio::fs::read(dir.join("notes.txt")).await?,
+            b"caf\xe9 ferm\xe9\n"
+        );
+        assert_snapshot!(ToolError::from(&error).to_json(), @r#"
+        {"error":{"code":"couldnt_keep_encoding","message":"couldn't keep the file's encoding: '→' can't be represented in Latin-1","hint":"use characters the file's encoding can represent (eg. an escape sequence, or an ASCII equivalent)"}}
+        "#);
+        Ok(())
     }
 }
@@ -0,0 +1,81 @@
+use serde::Serialize;
+
+/// Error returned to the model in place of a tool's output.
+#[derive(Debug, Serialize)]
+pub struct ToolError {
+    code: &'static str,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hint: Option<&'static str>,
+}
+
+/// Implemented by tool errors so that they can be categorized by the model (and the debug UI).
+pub trait ToolErrorKind: std::error::Error {
+    fn code(&self) -> &'static str;
+
+    fn hint(&self) -> Option<&'static str> {
+        None
+    }
+}
+
+#[derive(Serialize)]
+struct ToolErrorEnvelope<'a> {
+    error: &'a ToolError,
+}
+
+impl ToolError {
+    pub fn new(code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            hint: None,
+        }
+    }
+
+    pub fn with_hint(mut self, hint: &'static str) -> Self {
+        self.hint = Some(hint);
+        self
+    }
+
+    pub fn code(&self) -> &'static str {
+        self.code
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(&ToolErrorEnvelope { error: self })
+            .unwrap_or_else(|_| format!("error: {}", self.message))
+    }
+}
+
+impl<E: ToolErrorKind> From<&E> for ToolError {
+    fn from(error: &E) -> Self {
+        Self {
+            code: error.code(),
+            message: error.to_string(),
+            hint: error.hint(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::assert_snapshot;
+
+    #[test]
+    fn tool_errors_are_serialized_with_code_message_and_hint() {
+        // GIVEN
+        let error = ToolError::new("file_already_exists", "file already exists")
+            .with_hint("set overwrite to true to replace the file");
+
+        // WHEN
+        let json = error.to_json();
+
+        // THEN
+        assert_snapshot!(json, @r#"{"error":{"code":"file_already_exists","message":"file already exists","hint":"set overwrite to true to replace the file"}}"#);
+    }
+}
@@ -0,0 +1,153 @@
+use super::Workspace;
+use crate::helpers::{read_text_file, unified_diff};
+use serde::Serialize;
+use std::path::Path;
+use std::time::Duration;
+
+/// Formatters (and linters) still running after this long are stopped; the file is left as it
+/// was written.
+const FILE_COMMAND_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// What the formatter configured for a file's type did to it, after a tool wrote it.
+#[derive(Debug, Serialize)]
+pub struct Formatting {
+    command: String,
+    /// The changes the formatter made, as a unified diff; unset if it didn't change anything.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    diff: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl Formatting {
+    /// Describes what the formatter did, if it did anything worth mentioning to the user.
+    pub fn summary(&self) -> Option<String> {
+        if self.error.is_some() {
+            Some(format!("formatting via `{}` failed", self.command))
+        } else if self.diff.is_some() {
+            Some(format!("formatted via `{}`", self.command))
+        } else {
+            None
+        }
+    }
+}
+
+/// Runs the formatter configured for the type of the file at `path` (relative to the workspace)
+/// on it, if there is one; `written` is what the tool wrote to the file. The model is told about
+/// the changes the formatter makes, so that it doesn't expect the file to contain what it wrote.
+pub async fn format_written_file(
+    workspace: &Workspace,
+    path: &str,
+    written: &str,
+) -> Option<Formatting> {
+    let command = workspace.formatter_for(path)?;
+    let abs_path = workspace.resolve(path);
+
+    let result = match run_formatter(workspace, &command, &abs_path).await {
+        Ok(()) => read_text_file(&abs_path)
+            .await
+            .map_err(|e| format!("couldn't read the formatted file: {e}")),
+        Err(e) => Err(e),
+    };
+
+    Some(match result {
+        Ok((formatted, _)) => Formatting {
+            diff: (formatted != written).then(|| unified_diff(written, &formatted, path)),
+            command,
+            error: None,
+        },
+        Err(error) => Formatting {
+            command,
+            diff: None,
+            error: Some(error),
+        },
+    })
+}
+
+async fn run_formatter(workspace: &Workspace, command: &str, path: &Path) -> Result<(), String> {
+    let output = run_on_file(workspace, command, path).await?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "formatter failed ({}): {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Runs `command` (a formatter, or a linter) in the workspace, with `path` appended to it.
+pub(super) async fn run_on_file(
+    workspace: &Workspace,
+    command: &str,
+    path: &Path,
+) -> Result<std::process::Output, String> {
+    let path = path.to_string_lossy();
+    let quoted_path = shlex::try_quote(&path).map_err(|e| format!("invalid path: {e}"))?;
+    let settings = workspace.command_settings();
+
+    tokio::time::timeout(
+        FILE_COMMAND_TIMEOUT,
+        tokio::process::Command::new("bash")
+            .args(["-c", &format!("{command} {quoted_path}")])
+            .current_dir(workspace.dir())
+            .envs(&settings.env)
+            .kill_on_drop(true)
+            .output(),
+    )
+    .await
+    .map_err(|_| {
+        format!(
+            "`{command}` didn't finish within {}s",
+            FILE_COMMAND_TIMEOUT.as_secs()
+        )
+    })?
+    .map_err(|e| format!("couldn't run `{command}`: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::assert_snapshot;
+    use std::collections::BTreeMap;
+
+    #[tokio::test]
+    async fn changes_formatters_make_are_reported() -> anyhow::Result<()> {
+        // GIVEN
+        let temp_dir = tempfile::tempdir()?;
+        let dir = temp_dir.path();
+        let workspace = Workspace::new(dir);
+        workspace.set_formatters(BTreeMap::from([
+            ("txt".to_string(), "perl -pi -e 's/ +$//'".to_string()),
+            (
+                "md".to_string(),
+                "echo 'unexpected token' >&2; false".to_string(),
+            ),
+        ]));
+        let written = "first line   \nsecond line\n";
+        for file in ["notes.txt", "README.md", "main.rs"] {
+            tokio::fs::write(dir.join(file), written).await?;
+        }
+
+        // WHEN
+        let mut results = vec![];
+        for file in ["notes.txt", "README.md", "main.rs"] {
+            let formatting = format_written_file(&workspace, file, written).await;
+            results.push(format!("{file}: {}", serde_json::to_string(&formatting)?));
+        }
+
+        // THEN
+        assert_snapshot!(results.join("\n"), @r#"
+        notes.txt: {"command":"perl -pi -e 's/ +$//'","diff":"--- notes.txt\n+++ notes.txt\n@@ -1,2 +1,2 @@\n-first line   \n+first line\n second line\n"}
+        README.md: {"command":"echo 'unexpected token' >&2; false","error":"formatter failed (exit status: 1): unexpected token"}
+        main.rs: null
+        "#);
+        assert_eq!(
+            tokio::fs::read_to_string(dir.join("notes.txt")).await?,
+            "first line\nsecond line\n"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,67 @@
+use super::AgxTool;
+use crate::forge::{Forge, ForgeError, Issue};
+use rig::completion::ToolDefinition;
+use rig::tool::Tool;
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::instrument;
+
+#[derive(Debug, Deserialize)]
+pub struct IssueViewArgs {
+    pub number: u64,
+}
+
+pub struct IssueViewTool {
+    forge: Arc<dyn Forge>,
+}
+
+impl IssueViewTool {
+    pub fn new(forge: Arc<dyn Forge>) -> Self {
+        Self { forge }
+    }
+}
+
+impl Tool for IssueViewTool {
+    const NAME: &'static str = "issue_view";
+    type Error = ForgeError;
+    type Args = IssueViewArgs;
+    type Output = Issue;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "issue_view".to_string(),
+            description: format!(
+                "View an issue in the {} repository {}, along with its comments",
+                self.forge.kind(),
+                self.forge.repo()
+            ),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "number": {
+                        "type": "integer",
+                        "description": "number of the issue"
+                    },
+                },
+                "required": ["number"],
+            }),
+        }
+    }
+
+    #[instrument(name = "tool-call: issue_view", skip(self), err)]
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        self.forge.get_issue(args.number).await
+    }
+}
+
+impl AgxTool for IssueViewTool {
+    fn repr(args: &IssueViewArgs) -> String {
+        format!("issue_view: #{}", args.number)
+    }
+
+    fn summary(issue: &Issue, _elapsed: Duration) -> String {
+        format!("{} comments", issue.comments.len())
+    }
+}
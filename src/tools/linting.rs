@@ -0,0 +1,113 @@
+use super::run_cmd::{clean_output, summarize_output};
+use super::{Workspace, run_on_file};
+use serde::Serialize;
+
+/// What the linter configured for a file's type reported about it, after a tool wrote it.
+#[derive(Debug, Serialize)]
+pub struct Lint {
+    command: String,
+    /// Whether the linter exited successfully; unset if it couldn't be run.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    passed: Option<bool>,
+    /// The warnings and errors the linter reported (its stdout and stderr); long reports are cut
+    /// down.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    report: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl Lint {
+    /// Describes what the linter found, if it found anything worth mentioning to the user.
+    pub fn summary(&self) -> Option<String> {
+        if self.error.is_some() {
+            Some(format!("linting via `{}` failed", self.command))
+        } else if self.passed == Some(false) || !self.report.is_empty() {
+            Some(format!("`{}` reported issues", self.command))
+        } else {
+            None
+        }
+    }
+}
+
+/// Runs the linter configured for the type of the file at `path` (relative to the workspace) on
+/// it, if there is one. What it reports is sent back to the model along with the tool's result,
+/// so that issues get fixed right away, rather than after the changes are reviewed.
+pub async fn lint_written_file(workspace: &Workspace, path: &str) -> Option<Lint> {
+    let command = workspace.linter_for(path)?;
+    let abs_path = workspace.resolve(path);
+
+    let output = match run_on_file(workspace, &command, &abs_path).await {
+        Ok(output) => output,
+        Err(error) => {
+            return Some(Lint {
+                command,
+                passed: None,
+                report: String::new(),
+                error: Some(error),
+            });
+        }
+    };
+
+    let report = [output.stdout, output.stderr]
+        .iter()
+        .map(|o| clean_output(String::from_utf8_lossy(o).trim()))
+        .filter(|o| !o.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+        // linters mention the file by the path they were given; the model knows it by this one
+        .replace(abs_path.to_string_lossy().as_ref(), path);
+
+    Some(Lint {
+        command,
+        passed: Some(output.status.success()),
+        report: summarize_output(&report).unwrap_or(report),
+        error: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::assert_snapshot;
+    use std::collections::BTreeMap;
+
+    #[tokio::test]
+    async fn issues_linters_find_are_reported() -> anyhow::Result<()> {
+        // GIVEN
+        let temp_dir = tempfile::tempdir()?;
+        let dir = temp_dir.path();
+        let workspace = Workspace::new(dir);
+        workspace.set_linters(BTreeMap::from([(
+            "txt".to_string(),
+            r#"awk 'length > 20 { print FILENAME ":" FNR ": line too long"; bad = 1 } END { exit bad }'"#
+                .to_string(),
+        )]));
+        tokio::fs::write(
+            dir.join("notes.txt"),
+            "short line\na line that goes on for too long\n",
+        )
+        .await?;
+        tokio::fs::write(dir.join("clean.txt"), "short line\n").await?;
+        tokio::fs::write(dir.join("main.rs"), "fn main() {}\n").await?;
+
+        // WHEN
+        let mut results = vec![];
+        for file in ["notes.txt", "clean.txt", "main.rs"] {
+            let lint = lint_written_file(&workspace, file).await;
+            let summary = lint.as_ref().and_then(Lint::summary);
+            results.push(format!(
+                "{file}: {} ({summary:?})",
+                serde_json::to_string(&lint)?
+            ));
+        }
+
+        // THEN
+        assert_snapshot!(results.join("\n"), @r#"
+        notes.txt: {"command":"awk 'length > 20 { print FILENAME \":\" FNR \": line too long\"; bad = 1 } END { exit bad }'","passed":false,"report":"notes.txt:2: line too long"} (Some("`awk 'length > 20 { print FILENAME \":\" FNR \": line too long\"; bad = 1 } END { exit bad }'` reported issues"))
+        clean.txt: {"command":"awk 'length > 20 { print FILENAME \":\" FNR \": line too long\"; bad = 1 } END { exit bad }'","passed":true} (None)
+        main.rs: null (None)
+        "#);
+        Ok(())
+    }
+}
@@ -0,0 +1,282 @@
+use super::tool_call::{DynTool, DynToolCall};
+use super::{BoxFuture, ConfirmationPolicy, ToolCallOutcome, ToolError, ToolExecutionError};
+use crate::domain::{OutputKind, OutputSink};
+use crate::helpers::Diff;
+use crate::mcp::{McpServer, result_text};
+use rig::completion::ToolDefinition;
+use std::sync::Arc;
+use tokio::time::Instant;
+
+/// Adapts a tool offered by an MCP server to the tool registry. Tools are named after the server
+/// as well ("<server>__<tool>"), so that servers offering tools with the same name can be used
+/// together. Calls are shown with their raw arguments, and need to be approved (like remote
+/// actions), unless the server marks the tool as read-only and the user trusts it to (annotations
+/// like these come from the server, and can't be relied on otherwise).
+pub(super) struct McpTool {
+    server: Arc<McpServer>,
+    name: &'static str,
+    /// The tool's name on the server.
+    tool: String,
+    definition: ToolDefinition,
+    policy: ConfirmationPolicy,
+}
+
+#[derive(Debug)]
+struct McpToolCall {
+    server: Arc<McpServer>,
+    tool: String,
+    name: &'static str,
+    policy: ConfirmationPolicy,
+    args: serde_json::Map<String, serde_json::Value>,
+}
+
+impl McpTool {
+    pub(super) fn new(server: Arc<McpServer>, tool: rmcp::model::Tool) -> Self {
+        // tools are registered by a 'static name; MCP tools are only discovered as a session
+        // starts, so leaking their names is fine
+        let name: &'static str =
+            Box::leak(qualified_name(server.name(), &tool.name).into_boxed_str());
+        let description = tool
+            .description
+            .as_deref()
+            .or(tool.title.as_deref())
+            .unwrap_or_default();
+        let read_only = server.trusts_read_only_hints()
+            && tool
+                .annotations
+                .as_ref()
+                .and_then(|a| a.read_only_hint)
+                .unwrap_or(false);
+
+        Self {
+            definition: ToolDefinition {
+                description: format!(
+                    "{description} (provided by the {} MCP server)",
+                    server.name()
+                ),
+                name: name.to_string(),
+                parameters: serde_json::Value::Object(tool.input_schema.as_ref().clone()),
+            },
+            name,
+            tool: tool.name.to_string(),
+            policy: if read_only {
+                ConfirmationPolicy::NotNeeded
+            } else {
+                ConfirmationPolicy::RemoteAction
+            },
+            server,
+        }
+    }
+
+    /// The name the tool is offered to the model as.
+    pub(super) fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl DynTool for McpTool {
+    fn definition(&self) -> BoxFuture<'_, ToolDefinition> {
+        Box::pin(async { self.definition.clone() })
+    }
+
+    fn parse(
+        self: Arc<Self>,
+        args: serde_json::Value,
+    ) -> Result<Box<dyn DynToolCall>, serde_json::Error> {
+        let args = serde_json::from_value(args)?;
+
+        Ok(Box::new(McpToolCall {
+            server: Arc::clone(&self.server),
+            tool: self.tool.clone(),
+            name: self.name,
+            policy: self.policy.clone(),
+            args,
+        }))
+    }
+}
+
+impl DynToolCall for McpToolCall {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn repr(&self) -> String {
+        format!(
+            "{}: {}",
+            self.name,
+            serde_json::Value::Object(self.args.clone())
+        )
+    }
+
+    fn confirmation_policy(&self) -> ConfirmationPolicy {
+        self.policy.clone()
+    }
+
+    fn details(&self) -> BoxFuture<'_, Result<Option<String>, ToolError>> {
+        Box::pin(async { Ok(None) })
+    }
+
+    fn hunk_diff(&self) -> BoxFuture<'_, Option<Diff>> {
+        Box::pin(async { None })
+    }
+
+    fn set_accepted_hunks(&mut self, _accepted: Vec<bool>) {}
+
+    fn execute(
+        self: Box<Self>,
+        output: Arc<dyn OutputSink>,
+    ) -> BoxFuture<'static, Result<ToolCallOutcome, ToolExecutionError>> {
+        Box::pin(async move {
+            output.emit(OutputKind::ToolCall, &format!("{} ", self.repr()));
+
+            let start = Instant::now();
+            let result = self.server.call_tool(&self.tool, self.args).await;
+            let elapsed = start.elapsed().as_millis();
+
+            match result {
+                Ok(result) if !result.is_error.unwrap_or(false) => {
+                    output.emit(OutputKind::Success, &format!("✓ (took {elapsed} ms)\n"));
+                    Ok(ToolCallOutcome::Succeeded(result_text(&result)))
+                }
+                Ok(result) => {
+                    output.emit(OutputKind::Error, &format!("✗ (took {elapsed} ms)\n"));
+                    Ok(ToolCallOutcome::Failed(ToolError::new(
+                        "tool_failed",
+                        result_text(&result),
+                    )))
+                }
+                Err(e) => {
+                    output.emit(OutputKind::Error, &format!("✗ (took {elapsed} ms)\n"));
+                    Ok(ToolCallOutcome::Failed(ToolError::new(
+                        "mcp_server_unavailable",
+                        format!("{e:#}"),
+                    )))
+                }
+            }
+        })
+    }
+}
+
+/// Returns "<server>__<tool>", with characters that providers don't allow in tool names replaced.
+fn qualified_name(server: &str, tool: &str) -> String {
+    format!("{server}__{tool}")
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{CollectingSink, McpServerConfig};
+    use crate::tools::ToolRegistry;
+    use insta::assert_snapshot;
+    use rig::message::{ToolCall, ToolFunction};
+    use std::collections::BTreeMap;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn tools_of_mcp_servers_can_be_called() -> anyhow::Result<()> {
+        // GIVEN
+        let config = McpServerConfig {
+            command: "bash".to_string(),
+            args: vec!["src/tools/testdata/fake-mcp-server.sh".to_string()],
+            env: BTreeMap::new(),
+            trust_read_only_hints: None,
+        };
+        let temp_dir = tempfile::tempdir()?;
+        let logs_dir = temp_dir.path();
+        let log = logs_dir.join("notes.log");
+        let server = McpServer::start("notes", &config, Path::new("."), &log).await?;
+        let tools = server.tools().await?;
+        let server = Arc::new(server);
+        let mut registry = ToolRegistry::default();
+        for tool in tools {
+            registry.register_mcp(Arc::clone(&server), tool);
+        }
+
+        // WHEN
+        let mut results = vec![];
+        for (name, args) in [
+            ("notes__search", serde_json::json!({"query": "notes"})),
+            ("notes__delete", serde_json::json!({"title": "todo"})),
+        ] {
+            let call = registry.resolve(ToolCall::new(
+                "call_1".to_string(),
+                ToolFunction::new(name.to_string(), args),
+            ))?;
+            let needs_confirmation = call.needs_confirmation();
+            let outcome = call.execute(Arc::new(CollectingSink::default())).await?;
+            results.push(format!(
+                "{name} (needs confirmation: {needs_confirmation}): {}",
+                outcome.into_content()
+            ));
+        }
+
+        // THEN
+        assert_eq!(registry.names(), vec!["notes__delete", "notes__search"]);
+        assert_snapshot!(results.join("\n"), @r#"
+        notes__search (needs confirmation: true): meeting notes (2026-10-12)
+        release checklist
+        notes__delete (needs confirmation: true): {"error":{"code":"tool_failed","message":"note not found"}}
+        "#);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn read_only_hints_are_only_relied_on_for_trusted_servers() -> anyhow::Result<()> {
+        // GIVEN
+        let temp_dir = tempfile::tempdir()?;
+        let logs_dir = temp_dir.path();
+        let mut registry = ToolRegistry::default();
+        for (name, trust_read_only_hints) in [("trusted", Some(true)), ("untrusted", None)] {
+            let config = McpServerConfig {
+                command: "bash".to_string(),
+                args: vec!["src/tools/testdata/fake-mcp-server.sh".to_string()],
+                env: BTreeMap::new(),
+                trust_read_only_hints,
+            };
+            let log = logs_dir.join(format!("{name}.log"));
+            let server = McpServer::start(name, &config, Path::new("."), &log).await?;
+            let tools = server.tools().await?;
+            let server = Arc::new(server);
+            for tool in tools {
+                registry.register_mcp(Arc::clone(&server), tool);
+            }
+        }
+
+        // WHEN
+        let mut results = vec![];
+        for name in [
+            "trusted__search",
+            "trusted__delete",
+            "untrusted__search",
+            "untrusted__delete",
+        ] {
+            let call = registry.resolve(ToolCall::new(
+                "call_1".to_string(),
+                ToolFunction::new(name.to_string(), serde_json::json!({})),
+            ))?;
+            results.push(format!(
+                "{name}: needs confirmation: {}",
+                call.needs_confirmation()
+            ));
+        }
+
+        // THEN
+        assert_snapshot!(results.join("\n"), @r"
+        trusted__search: needs confirmation: false
+        trusted__delete: needs confirmation: true
+        untrusted__search: needs confirmation: true
+        untrusted__delete: needs confirmation: true
+        ");
+        Ok(())
+    }
+}
@@ -1,13 +1,43 @@
+//! Tools the model can call, and the [`ToolRegistry`] sessions resolve tool calls with.
+
+mod artifacts;
 mod create_file;
+mod custom;
 mod edit_file;
+mod error;
+mod formatting;
+mod issue_view;
+mod linting;
+mod mcp;
+mod pr_comment;
+mod pr_create;
+mod read_artifact;
 mod read_dir;
 mod read_file;
+mod registry;
+mod replace_in_files;
 mod run_cmd;
+mod search_knowledge;
+mod semantic_search;
 mod tool_call;
+mod workspace;
 
+pub use artifacts::*;
 pub use create_file::*;
 pub use edit_file::*;
+pub use error::*;
+pub use formatting::*;
+pub use issue_view::*;
+pub use linting::*;
+pub use pr_comment::*;
+pub use pr_create::*;
+pub use read_artifact::*;
 pub use read_dir::*;
 pub use read_file::*;
+pub use registry::*;
+pub use replace_in_files::*;
 pub use run_cmd::*;
+pub use search_knowledge::*;
+pub use semantic_search::*;
 pub use tool_call::*;
+pub use workspace::*;
@@ -0,0 +1,83 @@
+use super::{AgxTool, ConfirmationPolicy, ToolError};
+use crate::forge::{Comment, Forge, ForgeError};
+use rig::completion::ToolDefinition;
+use rig::tool::Tool;
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::instrument;
+
+#[derive(Debug, Deserialize)]
+pub struct PrCommentArgs {
+    pub number: u64,
+    pub body: String,
+}
+
+pub struct PrCommentTool {
+    forge: Arc<dyn Forge>,
+}
+
+impl PrCommentTool {
+    pub fn new(forge: Arc<dyn Forge>) -> Self {
+        Self { forge }
+    }
+}
+
+impl Tool for PrCommentTool {
+    const NAME: &'static str = "pr_comment";
+    type Error = ForgeError;
+    type Args = PrCommentArgs;
+    type Output = Comment;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "pr_comment".to_string(),
+            description: format!(
+                "Comment on a {} in the {} repository {}",
+                self.forge.kind().pull_request_term(),
+                self.forge.kind(),
+                self.forge.repo()
+            ),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "number": {
+                        "type": "integer",
+                        "description": "number of the pull request"
+                    },
+                    "body": {
+                        "type": "string",
+                        "description": "the comment (markdown)"
+                    },
+                },
+                "required": ["number", "body"],
+            }),
+        }
+    }
+
+    #[instrument(name = "tool-call: pr_comment", skip(self), err)]
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        self.forge
+            .comment_on_pull_request(args.number, &args.body)
+            .await
+    }
+}
+
+impl AgxTool for PrCommentTool {
+    fn repr(args: &PrCommentArgs) -> String {
+        format!("pr_comment: #{}", args.number)
+    }
+
+    fn confirmation_policy(_args: &PrCommentArgs) -> ConfirmationPolicy {
+        ConfirmationPolicy::RemoteAction
+    }
+
+    async fn details(&self, args: &PrCommentArgs) -> Result<Option<String>, ToolError> {
+        Ok(Some(args.body.clone()))
+    }
+
+    fn summary(comment: &Comment, _elapsed: Duration) -> String {
+        format!("commented {}", comment.url)
+    }
+}
@@ -0,0 +1,114 @@
+use super::{AgxTool, ConfirmationPolicy, ToolError};
+use crate::forge::{Forge, ForgeError, PullRequest};
+use rig::completion::ToolDefinition;
+use rig::tool::Tool;
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::instrument;
+
+#[derive(Debug, Deserialize)]
+pub struct PrCreateArgs {
+    pub title: String,
+    pub body: String,
+    pub head: String,
+    #[serde(default)]
+    pub base: Option<String>,
+    #[serde(default)]
+    pub draft: bool,
+}
+
+pub struct PrCreateTool {
+    forge: Arc<dyn Forge>,
+}
+
+impl PrCreateTool {
+    pub fn new(forge: Arc<dyn Forge>) -> Self {
+        Self { forge }
+    }
+}
+
+impl Tool for PrCreateTool {
+    const NAME: &'static str = "pr_create";
+    type Error = ForgeError;
+    type Args = PrCreateArgs;
+    type Output = PullRequest;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "pr_create".to_string(),
+            description: format!(
+                "Open a {} in the {} repository {}. The head branch needs to be pushed to the remote first (eg. via run_cmd)",
+                self.forge.kind().pull_request_term(),
+                self.forge.kind(),
+                self.forge.repo()
+            ),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "title": {
+                        "type": "string",
+                        "description": "title of the pull request"
+                    },
+                    "body": {
+                        "type": "string",
+                        "description": "description of the pull request (markdown)"
+                    },
+                    "head": {
+                        "type": "string",
+                        "description": "branch containing the changes"
+                    },
+                    "base": {
+                        "type": "string",
+                        "description": "branch to merge the changes into; defaults to the repository's default branch"
+                    },
+                    "draft": {
+                        "type": "boolean",
+                        "description": "whether to open the pull request as a draft"
+                    },
+                },
+                "required": ["title", "body", "head"],
+            }),
+        }
+    }
+
+    #[instrument(name = "tool-call: pr_create", skip(self), err)]
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        self.forge
+            .create_pull_request(
+                &args.title,
+                &args.body,
+                &args.head,
+                args.base.as_deref(),
+                args.draft,
+            )
+            .await
+    }
+}
+
+impl AgxTool for PrCreateTool {
+    fn repr(args: &PrCreateArgs) -> String {
+        format!("pr_create: {} ({})", args.title, args.head)
+    }
+
+    fn confirmation_policy(_args: &PrCreateArgs) -> ConfirmationPolicy {
+        ConfirmationPolicy::RemoteAction
+    }
+
+    async fn details(&self, args: &PrCreateArgs) -> Result<Option<String>, ToolError> {
+        Ok(Some(format!(
+            "repository: {}\nhead: {}\nbase: {}\ndraft: {}\n\n{}\n\n{}",
+            self.forge.repo(),
+            args.head,
+            args.base.as_deref().unwrap_or("(default branch)"),
+            args.draft,
+            args.title,
+            args.body
+        )))
+    }
+
+    fn summary(pull_request: &PullRequest, _elapsed: Duration) -> String {
+        format!("opened {}", pull_request.url)
+    }
+}
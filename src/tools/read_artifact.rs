@@ -0,0 +1,203 @@
+use super::{AgxTool, ArtifactStore, ToolErrorKind};
+use rig::completion::ToolDefinition;
+use rig::tool::Tool;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::instrument;
+
+const DEFAULT_LINE_COUNT: usize = 200;
+const MAX_LINE_COUNT: usize = 500;
+
+#[derive(Debug, Deserialize)]
+pub struct ReadArtifactArgs {
+    pub artifact: String,
+    #[serde(default)]
+    pub start_line: Option<usize>,
+    #[serde(default)]
+    pub line_count: Option<usize>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReadArtifactError {
+    #[error("invalid artifact name")]
+    InvalidName,
+    #[error("couldn't read artifact: {0}")]
+    CouldntReadArtifact(#[from] std::io::Error),
+    #[error("start line is past the end of the artifact, which has {0} lines")]
+    StartLineOutOfRange(usize),
+}
+
+impl ToolErrorKind for ReadArtifactError {
+    fn code(&self) -> &'static str {
+        match self {
+            ReadArtifactError::InvalidName => "invalid_artifact_name",
+            ReadArtifactError::CouldntReadArtifact(e)
+                if e.kind() == std::io::ErrorKind::NotFound =>
+            {
+                "artifact_not_found"
+            }
+            ReadArtifactError::CouldntReadArtifact(_) => "couldnt_read_artifact",
+            ReadArtifactError::StartLineOutOfRange(_) => "start_line_out_of_range",
+        }
+    }
+
+    fn hint(&self) -> Option<&'static str> {
+        match self {
+            ReadArtifactError::InvalidName | ReadArtifactError::CouldntReadArtifact(_) => {
+                Some("use the artifact name exactly as it was returned by the tool")
+            }
+            ReadArtifactError::StartLineOutOfRange(_) => None,
+        }
+    }
+}
+
+pub struct ReadArtifactTool {
+    artifacts: Arc<ArtifactStore>,
+}
+
+impl ReadArtifactTool {
+    pub fn new(artifacts: Arc<ArtifactStore>) -> Self {
+        Self { artifacts }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReadArtifactResponse {
+    start_line: usize,
+    end_line: usize,
+    total_lines: usize,
+    content: String,
+}
+
+impl Tool for ReadArtifactTool {
+    const NAME: &'static str = "read_artifact";
+    type Error = ReadArtifactError;
+    type Args = ReadArtifactArgs;
+    type Output = ReadArtifactResponse;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: "Read a slice of an artifact: a tool output that was too large to be returned in full, and was saved instead. Returns the lines requested, along with the artifact's total line count".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "artifact": {
+                        "type": "string",
+                        "description": "name of the artifact, as returned by the tool"
+                    },
+                    "start_line": {
+                        "type": "integer",
+                        "description": "line to start reading from, starting at 1 (default: 1)"
+                    },
+                    "line_count": {
+                        "type": "integer",
+                        "description": format!("number of lines to read (default: {DEFAULT_LINE_COUNT}, max: {MAX_LINE_COUNT})")
+                    },
+                },
+                "required": ["artifact"],
+            }),
+        }
+    }
+
+    #[instrument(name = "tool-call: read_artifact", skip(self), err)]
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let path = self
+            .artifacts
+            .path(&args.artifact)
+            .ok_or(ReadArtifactError::InvalidName)?;
+        let contents = tokio::fs::read_to_string(path).await?;
+
+        let lines = contents.lines().collect::<Vec<_>>();
+        let start_line = args.start_line.unwrap_or(1).max(1);
+        if start_line > lines.len() {
+            return Err(ReadArtifactError::StartLineOutOfRange(lines.len()));
+        }
+        let line_count = args
+            .line_count
+            .unwrap_or(DEFAULT_LINE_COUNT)
+            .clamp(1, MAX_LINE_COUNT);
+        let end_line = (start_line + line_count - 1).min(lines.len());
+
+        Ok(ReadArtifactResponse {
+            start_line,
+            end_line,
+            total_lines: lines.len(),
+            content: lines[start_line - 1..end_line].join("\n"),
+        })
+    }
+}
+
+impl AgxTool for ReadArtifactTool {
+    fn repr(args: &ReadArtifactArgs) -> String {
+        format!(
+            "read_artifact: {} (from line {})",
+            args.artifact,
+            args.start_line.unwrap_or(1)
+        )
+    }
+
+    fn summary(output: &ReadArtifactResponse, _elapsed: Duration) -> String {
+        format!(
+            "read lines {}-{} of {}",
+            output.start_line, output.end_line, output.total_lines
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::assert_yaml_snapshot;
+
+    #[tokio::test]
+    async fn slices_of_an_artifact_can_be_read() -> anyhow::Result<()> {
+        // GIVEN
+        let temp_dir = tempfile::tempdir()?;
+        let dir = temp_dir.path();
+        let artifacts = Arc::new(ArtifactStore::new(dir));
+        let artifact = artifacts
+            .save("run_cmd", "one\ntwo\nthree\nfour\nfive\n")
+            .await?;
+        let tool = ReadArtifactTool::new(Arc::clone(&artifacts));
+
+        // WHEN
+        let result = tool
+            .call(ReadArtifactArgs {
+                artifact: artifact.name,
+                start_line: Some(4),
+                line_count: Some(10),
+            })
+            .await?;
+
+        // THEN
+        assert_yaml_snapshot!(result, @r#"
+        start_line: 4
+        end_line: 5
+        total_lines: 5
+        content: "four\nfive"
+        "#);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn artifacts_outside_the_store_cant_be_read() {
+        // GIVEN
+        let tool = ReadArtifactTool::new(Arc::new(ArtifactStore::new(std::env::temp_dir())));
+
+        // WHEN
+        let result = tool
+            .call(ReadArtifactArgs {
+                artifact: "../secrets.txt".to_string(),
+                start_line: None,
+                line_count: None,
+            })
+            .await;
+
+        // THEN
+        assert!(matches!(result, Err(ReadArtifactError::InvalidName)));
+    }
+}
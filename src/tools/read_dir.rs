@@ -1,7 +1,11 @@
+use super::{AgxTool, ToolErrorKind, Workspace};
 use rig::completion::ToolDefinition;
 use rig::tool::Tool;
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 use serde_json::json;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
 use tracing::instrument;
 
 #[derive(Debug, serde::Serialize)]
@@ -44,8 +48,37 @@ pub enum ReadDirError {
     CouldntGetEntryMetadata(std::io::Error),
 }
 
-#[derive(Deserialize, Serialize)]
-pub struct ReadDirTool;
+impl ToolErrorKind for ReadDirError {
+    fn code(&self) -> &'static str {
+        match self {
+            ReadDirError::CouldntGetMetadata(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                "dir_not_found"
+            }
+            ReadDirError::CouldntGetMetadata(_) => "couldnt_get_metadata",
+            ReadDirError::PathNotADir => "not_a_dir",
+            ReadDirError::CouldntReadDirectory(_) => "couldnt_read_dir",
+            ReadDirError::CouldntReadEntry(_) => "couldnt_read_entry",
+            ReadDirError::CouldntGetEntryMetadata(_) => "couldnt_get_entry_metadata",
+        }
+    }
+
+    fn hint(&self) -> Option<&'static str> {
+        match self {
+            ReadDirError::PathNotADir => Some("use read_file to read files"),
+            _ => None,
+        }
+    }
+}
+
+pub struct ReadDirTool {
+    workspace: Arc<Workspace>,
+}
+
+impl ReadDirTool {
+    pub fn new(workspace: Arc<Workspace>) -> Self {
+        Self { workspace }
+    }
+}
 
 impl Tool for ReadDirTool {
     const NAME: &'static str = "read_dir";
@@ -72,12 +105,13 @@ impl Tool for ReadDirTool {
 
     #[instrument(name = "tool-call: read_dir", skip(self), err)]
     async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
-        let metadata = tokio::fs::metadata(&args.path).await?;
+        let path = self.workspace.resolve(&args.path);
+        let metadata = tokio::fs::metadata(&path).await?;
         if !metadata.is_dir() {
             return Err(ReadDirError::PathNotADir);
         }
 
-        let mut read_dir = tokio::fs::read_dir(&args.path)
+        let mut read_dir = tokio::fs::read_dir(&path)
             .await
             .map_err(ReadDirError::CouldntReadDirectory)?;
 
@@ -102,7 +136,11 @@ impl Tool for ReadDirTool {
             let size = entry_metadata.is_file().then_some(entry_metadata.len());
 
             entries.push(DirEntry {
-                name: entry.path().to_string_lossy().to_string(),
+                // names are relative to the path the model provided, same as the path itself
+                name: Path::new(&args.path)
+                    .join(entry.file_name())
+                    .to_string_lossy()
+                    .to_string(),
                 kind,
                 size,
             });
@@ -112,12 +150,12 @@ impl Tool for ReadDirTool {
     }
 }
 
-impl ReadDirTool {
-    pub fn repr(args: &ReadDirArgs) -> String {
+impl AgxTool for ReadDirTool {
+    fn repr(args: &ReadDirArgs) -> String {
         format!("read_dir: {}", args.path)
     }
 
-    pub fn details(_args: &ReadDirArgs) -> Option<String> {
-        None
+    fn summary(entries: &Vec<DirEntry>, _elapsed: Duration) -> String {
+        format!("read {} entries", entries.len())
     }
 }
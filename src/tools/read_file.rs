@@ -1,12 +1,19 @@
+use super::{AgxTool, ToolErrorKind, Workspace};
+use crate::helpers::{TextEncoding, generated_file_warning, read_text_file};
 use rig::completion::ToolDefinition;
 use rig::tool::Tool;
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
 use tracing::{instrument, trace};
 
 #[derive(Debug, Deserialize)]
 pub struct ReadFileArgs {
     pub path: String,
+    /// Defaults to true.
+    #[serde(default)]
+    pub with_line_numbers: Option<bool>,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -15,8 +22,35 @@ pub enum ReadFileError {
     CouldntReadFile(#[from] std::io::Error),
 }
 
-#[derive(Deserialize, Serialize)]
-pub struct ReadFileTool;
+impl ToolErrorKind for ReadFileError {
+    fn code(&self) -> &'static str {
+        match self {
+            ReadFileError::CouldntReadFile(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                "file_not_found"
+            }
+            ReadFileError::CouldntReadFile(_) => "couldnt_read_file",
+        }
+    }
+
+    fn hint(&self) -> Option<&'static str> {
+        match self {
+            ReadFileError::CouldntReadFile(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                Some("use read_dir to find the correct path")
+            }
+            ReadFileError::CouldntReadFile(_) => None,
+        }
+    }
+}
+
+pub struct ReadFileTool {
+    workspace: Arc<Workspace>,
+}
+
+impl ReadFileTool {
+    pub fn new(workspace: Arc<Workspace>) -> Self {
+        Self { workspace }
+    }
+}
 
 impl Tool for ReadFileTool {
     const NAME: &'static str = "read_file";
@@ -27,7 +61,7 @@ impl Tool for ReadFileTool {
     async fn definition(&self, _prompt: String) -> ToolDefinition {
         ToolDefinition {
             name: "read_file".to_string(),
-            description: "Read a file on the local filesystem".to_string(),
+            description: "Read a file on the local filesystem. By default, each line is prefixed with its line number and a tab; these prefixes aren't a part of the file, and must be left out of strings passed to edit_file".to_string(),
             parameters: json!({
                 "type": "object",
                 "properties": {
@@ -35,6 +69,10 @@ impl Tool for ReadFileTool {
                         "type": "string",
                         "description": "path of the file to read"
                     },
+                    "with_line_numbers": {
+                        "type": "boolean",
+                        "description": "prefix each line with its line number (defaults to true)"
+                    },
                 },
                 "required": ["path"],
             }),
@@ -43,20 +81,91 @@ impl Tool for ReadFileTool {
 
     #[instrument(name = "tool-call: read_file", skip(self), err)]
     async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
-        let contents = tokio::fs::read_to_string(&args.path).await?;
+        let (contents, encoding) = read_text_file(self.workspace.resolve(&args.path)).await?;
+
+        trace!(bytes_read = contents.len(), %encoding, "file read successfully");
+
+        let contents = if args.with_line_numbers.unwrap_or(true) {
+            with_line_numbers(&contents)
+        } else {
+            contents
+        };
 
-        trace!(bytes_read = contents.len(), "file read successfully");
+        let mut notes = vec![];
+        if encoding != TextEncoding::Utf8 {
+            notes.push(format!(
+                "[note: this file is encoded in {encoding}; it's shown in UTF-8, and edit_file writes it back in {encoding}]"
+            ));
+        }
+        if let Some(warning) = generated_file_warning(&args.path) {
+            notes.push(format!("[note: {warning}]"));
+        }
+
+        if notes.is_empty() {
+            return Ok(contents);
+        }
 
-        Ok(contents)
+        Ok(format!("{}\n\n{contents}", notes.join("\n")))
     }
 }
 
-impl ReadFileTool {
-    pub fn repr(args: &ReadFileArgs) -> String {
+impl AgxTool for ReadFileTool {
+    fn repr(args: &ReadFileArgs) -> String {
         format!("read_file: {}", args.path)
     }
 
-    pub fn details(_args: &ReadFileArgs) -> Option<String> {
-        None
+    fn summary(contents: &String, _elapsed: Duration) -> String {
+        format!("read {} bytes", contents.len())
+    }
+}
+
+/// Prefixes lines with their (right-aligned) numbers and a tab, like `cat -n` does.
+fn with_line_numbers(contents: &str) -> String {
+    let width = contents.lines().count().to_string().len();
+    let mut numbered = contents
+        .lines()
+        .enumerate()
+        .map(|(i, line)| format!("{:>width$}\t{line}", i + 1))
+        .collect::<Vec<_>>()
+        .join("\n");
+    if contents.ends_with('\n') {
+        numbered.push('\n');
+    }
+
+    numbered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::assert_snapshot;
+
+    #[test]
+    fn lines_are_prefixed_with_aligned_line_numbers() {
+        // GIVEN
+        let contents = (1..=10)
+            .map(|i| match i {
+                5 => String::new(),
+                i => format!("line {i}"),
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        // WHEN
+        let numbered = with_line_numbers(&format!("{contents}\n"));
+
+        // THEN
+        assert_snapshot!(numbered.replace('\t', "→"), @r"
+         1→line 1
+         2→line 2
+         3→line 3
+         4→line 4
+         5→
+         6→line 6
+         7→line 7
+         8→line 8
+         9→line 9
+        10→line 10
+        ");
     }
 }
@@ -0,0 +1,148 @@
+use super::custom::CustomTool;
+use super::mcp::McpTool;
+use super::{
+    AgxTool, AgxToolCall, AgxToolCallError, ArtifactStore, ConfirmationPolicy, CreateFileTool,
+    DynTool, EditFileTool, ReadArtifactTool, ReadDirTool, ReadFileTool, ReplaceInFilesTool,
+    RunCmdTool, Workspace,
+};
+use crate::mcp::McpServer;
+use rig::completion::ToolDefinition;
+use rig::message::ToolCall;
+use rig::tool::Tool;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Debug;
+use std::sync::Arc;
+
+/// Owns the tools available to the model; adding a tool only requires registering it here.
+#[derive(Default, Clone)]
+pub struct ToolRegistry {
+    tools: BTreeMap<&'static str, Arc<dyn DynTool>>,
+    allowed: Option<BTreeSet<String>>,
+}
+
+impl ToolRegistry {
+    pub fn builtin(workspace: Arc<Workspace>, artifacts: Arc<ArtifactStore>) -> Self {
+        let mut registry = Self::default();
+        registry.register(CreateFileTool::new(Arc::clone(&workspace)));
+        registry.register(EditFileTool::new(Arc::clone(&workspace)));
+        registry.register(ReadDirTool::new(Arc::clone(&workspace)));
+        registry.register(ReadFileTool::new(Arc::clone(&workspace)));
+        registry.register(ReadArtifactTool::new(Arc::clone(&artifacts)));
+        registry.register(ReplaceInFilesTool::new(Arc::clone(&workspace)));
+        registry.register(RunCmdTool::new(workspace, artifacts));
+
+        registry
+    }
+
+    pub fn register<T>(&mut self, tool: T)
+    where
+        T: AgxTool,
+        T::Args: Debug + Send + Sync + 'static,
+    {
+        if self
+            .allowed
+            .as_ref()
+            .is_some_and(|allowed| !allowed.contains(T::NAME))
+        {
+            return;
+        }
+
+        self.tools.insert(T::NAME, Arc::new(tool));
+    }
+
+    /// Registers a plain rig tool; calls to it are confirmed as per `policy`.
+    pub fn register_custom<T>(&mut self, tool: T, policy: ConfirmationPolicy)
+    where
+        T: Tool + 'static,
+    {
+        if self
+            .allowed
+            .as_ref()
+            .is_some_and(|allowed| !allowed.contains(T::NAME))
+        {
+            return;
+        }
+
+        self.tools
+            .insert(T::NAME, Arc::new(CustomTool::new(tool, policy)));
+    }
+
+    /// Registers a tool offered by an MCP server, as "<server>__<tool>"; calls to it need to be
+    /// approved, unless the server marks it as read-only. Returns the name it's registered as.
+    pub fn register_mcp(
+        &mut self,
+        server: Arc<McpServer>,
+        tool: rmcp::model::Tool,
+    ) -> Option<&'static str> {
+        let tool = McpTool::new(server, tool);
+        let name = tool.name();
+        if self
+            .allowed
+            .as_ref()
+            .is_some_and(|allowed| !allowed.contains(name))
+        {
+            return None;
+        }
+
+        self.tools.insert(name, Arc::new(tool));
+        Some(name)
+    }
+
+    /// Adds the tools of another registry, replacing ones with the same name.
+    pub fn extend(&mut self, other: ToolRegistry) {
+        for (name, tool) in other.tools {
+            if self
+                .allowed
+                .as_ref()
+                .is_some_and(|allowed| !allowed.contains(name))
+            {
+                continue;
+            }
+
+            self.tools.insert(name, tool);
+        }
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.tools.keys().copied().collect()
+    }
+
+    /// Limits the model to the provided tools, including ones registered later on. Returns the
+    /// provided names that don't match a registered tool.
+    pub fn restrict(&mut self, allowed: &[String]) -> Vec<String> {
+        let allowed = allowed.iter().cloned().collect::<BTreeSet<_>>();
+        let unknown = allowed
+            .iter()
+            .filter(|name| !self.tools.contains_key(name.as_str()))
+            .cloned()
+            .collect();
+
+        self.tools.retain(|name, _| allowed.contains(*name));
+        self.allowed = Some(allowed);
+
+        unknown
+    }
+
+    pub fn unregister(&mut self, name: &str) {
+        self.tools.remove(name);
+    }
+
+    pub async fn definitions(&self) -> Vec<ToolDefinition> {
+        let mut definitions = Vec::with_capacity(self.tools.len());
+        for tool in self.tools.values() {
+            definitions.push(tool.definition().await);
+        }
+
+        definitions
+    }
+
+    pub fn resolve(&self, call: ToolCall) -> Result<AgxToolCall, AgxToolCallError> {
+        let name = call.function.name.as_str();
+        let tool = self
+            .tools
+            .get(name)
+            .ok_or_else(|| AgxToolCallError::UnknownTool(name.to_string()))?;
+
+        AgxToolCall::new(Arc::clone(tool), call)
+    }
+}
@@ -0,0 +1,521 @@
+use super::{AgxTool, ConfirmationPolicy, ToolError, ToolErrorKind, Workspace};
+use crate::helpers::{Diff, decode_text, generated_file_warning, is_path_in_workspace};
+use console::style;
+use globset::GlobBuilder;
+use regex::{NoExpand, Regex};
+use rig::completion::ToolDefinition;
+use rig::tool::Tool;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::instrument;
+
+/// Replacements spanning more files than this are refused; they're better made via a command.
+const MAX_CHANGED_FILES: usize = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct ReplaceInFilesArgs {
+    pub pattern: String,
+    pub replacement: String,
+    pub glob: String,
+    #[serde(default)]
+    pub regex: bool,
+}
+
+impl std::fmt::Display for ReplaceInFilesArgs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "glob={}", self.glob)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReplaceInFilesError {
+    #[error("invalid input provided: {0}")]
+    InvalidInput(String),
+    #[error("absolute paths and parent directory traversal ('..') are not allowed")]
+    PathNotAllowed,
+    #[error("invalid glob: {0}")]
+    InvalidGlob(#[from] globset::Error),
+    #[error("invalid regex: {0}")]
+    InvalidRegex(#[from] regex::Error),
+    #[error(
+        "replacement is {size} bytes, which exceeds the limit of {max} bytes for a single write"
+    )]
+    WriteTooLarge { size: usize, max: usize },
+    #[error("couldn't list files: {0}")]
+    CouldntListFiles(String),
+    #[error("couldn't read {path}: {error}")]
+    CouldntReadFile { path: String, error: std::io::Error },
+    #[error("pattern wasn't found in any of the files matching the glob")]
+    NoMatches,
+    #[error(
+        "pattern was found in {0} files, more than the {MAX_CHANGED_FILES} that can be changed at once"
+    )]
+    TooManyFiles(usize),
+    #[error("couldn't keep the encoding of {path}: {error}")]
+    CouldntKeepEncoding { path: String, error: std::io::Error },
+    #[error("couldn't write to {path} (no files were changed): {error}")]
+    CouldntWriteToFile { path: String, error: std::io::Error },
+}
+
+impl ToolErrorKind for ReplaceInFilesError {
+    fn code(&self) -> &'static str {
+        match self {
+            ReplaceInFilesError::InvalidInput(_) => "invalid_input",
+            ReplaceInFilesError::PathNotAllowed => "path_not_allowed",
+            ReplaceInFilesError::InvalidGlob(_) => "invalid_glob",
+            ReplaceInFilesError::InvalidRegex(_) => "invalid_regex",
+            ReplaceInFilesError::WriteTooLarge { .. } => "write_too_large",
+            ReplaceInFilesError::CouldntListFiles(_) => "couldnt_list_files",
+            ReplaceInFilesError::CouldntReadFile { .. } => "couldnt_read_file",
+            ReplaceInFilesError::NoMatches => "no_matches",
+            ReplaceInFilesError::TooManyFiles(_) => "too_many_files",
+            ReplaceInFilesError::CouldntKeepEncoding { .. } => "couldnt_keep_encoding",
+            ReplaceInFilesError::CouldntWriteToFile { .. } => "couldnt_write_file",
+        }
+    }
+
+    fn hint(&self) -> Option<&'static str> {
+        match self {
+            ReplaceInFilesError::PathNotAllowed => {
+                Some("use a glob relative to the project directory")
+            }
+            ReplaceInFilesError::InvalidRegex(_) => {
+                Some("fix the regex, or set regex to false to match pattern literally")
+            }
+            ReplaceInFilesError::NoMatches => Some(
+                "check the glob (eg. src/**/*.rs; '*' doesn't match across directories), and whether pattern is a regex",
+            ),
+            ReplaceInFilesError::TooManyFiles(_) => {
+                Some("narrow down the glob, and make the replacement in several calls")
+            }
+            ReplaceInFilesError::CouldntKeepEncoding { .. } => Some(
+                "use characters the file's encoding can represent, or leave the file out via the glob",
+            ),
+            _ => None,
+        }
+    }
+}
+
+/// The change to be made to a single file.
+struct FileReplacement {
+    path: String,
+    abs_path: PathBuf,
+    old_contents: String,
+    new_contents: String,
+    old_bytes: Vec<u8>,
+    new_bytes: Vec<u8>,
+    num_replacements: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChangedFile {
+    path: String,
+    num_replacements: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReplaceInFilesResponse {
+    pub files: Vec<ChangedFile>,
+    pub num_replacements: usize,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    warnings: Vec<String>,
+}
+
+pub struct ReplaceInFilesTool {
+    workspace: Arc<Workspace>,
+}
+
+impl ReplaceInFilesTool {
+    pub fn new(workspace: Arc<Workspace>) -> Self {
+        Self { workspace }
+    }
+}
+
+impl Tool for ReplaceInFilesTool {
+    const NAME: &'static str = "replace_in_files";
+    type Error = ReplaceInFilesError;
+    type Args = ReplaceInFilesArgs;
+    type Output = ReplaceInFilesResponse;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: "Replace a string (or regex) in every file matching a glob, eg. to rename an identifier across the project. Files ignored by git are skipped. Either all of the files are changed, or none are".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "pattern": {
+                        "type": "string",
+                        "description": "string to replace; a regex if regex is true"
+                    },
+                    "replacement": {
+                        "type": "string",
+                        "description": "string to replace matches with; if regex is true, $1 or ${name} refer to capture groups (use $$ for a literal $)"
+                    },
+                    "glob": {
+                        "type": "string",
+                        "description": "files to make the replacement in, relative to the project directory (eg. src/**/*.rs; '*' doesn't match '/')"
+                    },
+                    "regex": {
+                        "type": "boolean",
+                        "description": "whether pattern is a regex (defaults to false)"
+                    },
+                },
+                "required": ["pattern", "replacement", "glob"],
+            }),
+        }
+    }
+
+    #[instrument(name = "tool-call: replace_in_files", skip(self), err)]
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let changes = self.plan(&args).await?;
+        write_atomically(&changes).await?;
+
+        Ok(ReplaceInFilesResponse {
+            num_replacements: changes.iter().map(|c| c.num_replacements).sum(),
+            warnings: changes
+                .iter()
+                .filter_map(|c| generated_file_warning(&c.path))
+                .collect(),
+            files: changes
+                .into_iter()
+                .map(|c| ChangedFile {
+                    path: c.path,
+                    num_replacements: c.num_replacements,
+                })
+                .collect(),
+        })
+    }
+}
+
+impl AgxTool for ReplaceInFilesTool {
+    fn repr(args: &ReplaceInFilesArgs) -> String {
+        format!("replace_in_files: {}", args.glob)
+    }
+
+    fn confirmation_policy(args: &ReplaceInFilesArgs) -> ConfirmationPolicy {
+        ConfirmationPolicy::FileChange(args.glob.clone())
+    }
+
+    async fn details(&self, args: &ReplaceInFilesArgs) -> Result<Option<String>, ToolError> {
+        let changes = self.plan(args).await.map_err(|e| ToolError::from(&e))?;
+        let options = self.workspace.diff_options();
+
+        let diffs = changes
+            .iter()
+            .map(|c| {
+                let header = format!(
+                    "{} ({} {})",
+                    c.path,
+                    c.num_replacements,
+                    if c.num_replacements == 1 {
+                        "replacement"
+                    } else {
+                        "replacements"
+                    }
+                );
+                let diff = Diff::with_options(&c.old_contents, &c.new_contents, options)
+                    .map(|d| d.get_terminal_output())
+                    .unwrap_or_default();
+                format!("{}\n{diff}", style(header).bold().underlined())
+            })
+            .collect::<Vec<_>>();
+
+        Ok(Some(diffs.join("\n\n")))
+    }
+
+    fn summary(response: &ReplaceInFilesResponse, _elapsed: Duration) -> String {
+        format!(
+            "made {} replacements in {} files",
+            response.num_replacements,
+            response.files.len()
+        )
+    }
+}
+
+impl ReplaceInFilesTool {
+    /// Works out the changes to be made, without making them.
+    async fn plan(
+        &self,
+        args: &ReplaceInFilesArgs,
+    ) -> Result<Vec<FileReplacement>, ReplaceInFilesError> {
+        if args.pattern.is_empty() {
+            return Err(ReplaceInFilesError::InvalidInput(
+                "pattern cannot be empty".to_string(),
+            ));
+        }
+
+        if args.glob.is_empty() {
+            return Err(ReplaceInFilesError::InvalidInput(
+                "glob cannot be empty".to_string(),
+            ));
+        }
+
+        if !args.regex && args.pattern == args.replacement {
+            return Err(ReplaceInFilesError::InvalidInput(
+                "pattern and replacement are the same".to_string(),
+            ));
+        }
+
+        if !is_path_in_workspace(&args.glob) {
+            return Err(ReplaceInFilesError::PathNotAllowed);
+        }
+
+        let max_write_bytes = self.workspace.max_write_bytes();
+        if args.replacement.len() > max_write_bytes {
+            return Err(ReplaceInFilesError::WriteTooLarge {
+                size: args.replacement.len(),
+                max: max_write_bytes,
+            });
+        }
+
+        let regex = if args.regex {
+            Regex::new(&args.pattern)?
+        } else {
+            Regex::new(&regex::escape(&args.pattern))?
+        };
+
+        let mut changes = vec![];
+        for (path, abs_path) in self.matching_files(&args.glob).await? {
+            let old_bytes = tokio::fs::read(&abs_path).await.map_err(|error| {
+                ReplaceInFilesError::CouldntReadFile {
+                    path: path.clone(),
+                    error,
+                }
+            })?;
+            // binary files are left alone
+            let Some((old_contents, encoding)) = decode_text(&old_bytes) else {
+                continue;
+            };
+            let Some((new_contents, num_replacements)) =
+                replace_all(&regex, &old_contents, &args.replacement, args.regex)
+            else {
+                continue;
+            };
+
+            let new_bytes = encoding.encode(&new_contents).map_err(|error| {
+                ReplaceInFilesError::CouldntKeepEncoding {
+                    path: path.clone(),
+                    error,
+                }
+            })?;
+
+            changes.push(FileReplacement {
+                path,
+                abs_path,
+                old_contents,
+                new_contents,
+                old_bytes,
+                new_bytes,
+                num_replacements,
+            });
+        }
+
+        if changes.is_empty() {
+            return Err(ReplaceInFilesError::NoMatches);
+        }
+
+        if changes.len() > MAX_CHANGED_FILES {
+            return Err(ReplaceInFilesError::TooManyFiles(changes.len()));
+        }
+
+        Ok(changes)
+    }
+
+    /// Returns the files in the workspace matching `glob` (skipping the ones ignored by git),
+    /// along with their paths relative to the workspace, sorted by path.
+    async fn matching_files(
+        &self,
+        glob: &str,
+    ) -> Result<Vec<(String, PathBuf)>, ReplaceInFilesError> {
+        let glob = glob.strip_prefix("./").unwrap_or(glob);
+        let matcher = GlobBuilder::new(glob)
+            .literal_separator(true)
+            .build()?
+            .compile_matcher();
+        let dir = self.workspace.dir();
+
+        let walk = tokio::task::spawn_blocking(move || {
+            let mut files = vec![];
+            for entry in ignore::WalkBuilder::new(&dir).build() {
+                let entry = entry?;
+                if !entry.file_type().is_some_and(|t| t.is_file()) {
+                    continue;
+                }
+
+                let relative = entry.path().strip_prefix(&dir).unwrap_or(entry.path());
+                if matcher.is_match(relative) {
+                    files.push((
+                        relative.to_string_lossy().to_string(),
+                        entry.path().to_path_buf(),
+                    ));
+                }
+            }
+            files.sort();
+
+            Ok::<_, ignore::Error>(files)
+        });
+
+        walk.await
+            .map_err(|e| ReplaceInFilesError::CouldntListFiles(e.to_string()))?
+            .map_err(|e| ReplaceInFilesError::CouldntListFiles(e.to_string()))
+    }
+}
+
+/// Replaces every match of `regex` in `contents`; `replacement` is expanded (eg. `$1` refers to
+/// the first capture group) only if `expand` is set. Returns `None` if nothing changes.
+fn replace_all(
+    regex: &Regex,
+    contents: &str,
+    replacement: &str,
+    expand: bool,
+) -> Option<(String, usize)> {
+    let num_replacements = regex.find_iter(contents).count();
+    if num_replacements == 0 {
+        return None;
+    }
+
+    let replaced = if expand {
+        regex.replace_all(contents, replacement)
+    } else {
+        regex.replace_all(contents, NoExpand(replacement))
+    };
+
+    (replaced != contents).then(|| (replaced.into_owned(), num_replacements))
+}
+
+/// Writes every change, or none: new contents are first written to temporary files next to the
+/// ones they replace, which are then renamed over them; files already replaced are restored if a
+/// rename fails.
+async fn write_atomically(changes: &[FileReplacement]) -> Result<(), ReplaceInFilesError> {
+    let write_error = |change: &FileReplacement, error| ReplaceInFilesError::CouldntWriteToFile {
+        path: change.path.clone(),
+        error,
+    };
+
+    let mut temp_paths = Vec::with_capacity(changes.len());
+    for change in changes {
+        let temp_path = temp_path_for(&change.abs_path);
+        if let Err(error) = write_temp_file(&temp_path, change).await {
+            for path in temp_paths.iter().chain([&temp_path]) {
+                _ = tokio::fs::remove_file(path).await;
+            }
+            return Err(write_error(change, error));
+        }
+        temp_paths.push(temp_path);
+    }
+
+    for (i, (change, temp_path)) in changes.iter().zip(&temp_paths).enumerate() {
+        if let Err(error) = tokio::fs::rename(temp_path, &change.abs_path).await {
+            for replaced in &changes[..i] {
+                _ = tokio::fs::write(&replaced.abs_path, &replaced.old_bytes).await;
+            }
+            for path in &temp_paths[i..] {
+                _ = tokio::fs::remove_file(path).await;
+            }
+            return Err(write_error(change, error));
+        }
+    }
+
+    Ok(())
+}
+
+async fn write_temp_file(temp_path: &Path, change: &FileReplacement) -> std::io::Result<()> {
+    tokio::fs::write(temp_path, &change.new_bytes).await?;
+    // the temporary file replaces the original, so it needs its permissions
+    let permissions = tokio::fs::metadata(&change.abs_path).await?.permissions();
+    tokio::fs::set_permissions(temp_path, permissions).await
+}
+
+fn temp_path_for(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{name}.agx-replace"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::assert_snapshot;
+
+    #[test]
+    fn replacements_are_only_expanded_for_regexes() {
+        // GIVEN
+        let contents = "let total = fetch_total($1);\nprint(fetch_total(2));\n";
+        let regex = Regex::new(r"fetch_total\(([^)]*)\)").expect("regex should be valid");
+        let literal = Regex::new(&regex::escape("fetch_total")).expect("regex should be valid");
+
+        // WHEN
+        let expanded = replace_all(&regex, contents, "load_total($1, None)", true);
+        let literal_replaced = replace_all(&literal, contents, "load_$1", false);
+        let unchanged = replace_all(&literal, contents, "fetch_total", false);
+
+        // THEN
+        let (expanded, num_expanded) = expanded.expect("contents should change");
+        let (literal_replaced, num_literal) = literal_replaced.expect("contents should change");
+        assert_eq!((num_expanded, num_literal), (2, 2));
+        assert_snapshot!(format!("{expanded}{literal_replaced}"), @r"
+        let total = load_total($1, None);
+        print(load_total(2, None));
+        let total = load_$1($1);
+        print(load_$1(2));
+        ");
+        assert!(unchanged.is_none());
+    }
+
+    #[tokio::test]
+    async fn files_matching_the_glob_are_changed_together() -> anyhow::Result<()> {
+        // GIVEN
+        let temp_dir = tempfile::tempdir()?;
+        let dir = temp_dir.path();
+        tokio::fs::create_dir_all(dir.join("src/models")).await?;
+        tokio::fs::write(dir.join("src/main.rs"), "use models::Account;\n").await?;
+        tokio::fs::write(
+            dir.join("src/models/account.rs"),
+            "pub struct Account;\nimpl Account {}\n",
+        )
+        .await?;
+        tokio::fs::write(dir.join("src/models/notes.txt"), b"Account caf\xe9\n").await?;
+        tokio::fs::write(dir.join("README.md"), "Account\n").await?;
+        let tool = ReplaceInFilesTool::new(Arc::new(Workspace::new(dir)));
+        let args = |pattern: &str, replacement: &str, glob: &str| ReplaceInFilesArgs {
+            pattern: pattern.to_string(),
+            replacement: replacement.to_string(),
+            glob: glob.to_string(),
+            regex: false,
+        };
+
+        // WHEN
+        let response = tool.call(args("Account", "User", "src/**/*.rs")).await?;
+        let error = tool
+            .call(args("caf", "→", "src/**"))
+            .await
+            .expect_err("replacement should've been refused");
+
+        // THEN
+        assert_snapshot!(serde_json::to_string(&response)?, @r#"
+        {"files":[{"path":"src/main.rs","num_replacements":1},{"path":"src/models/account.rs","num_replacements":2}],"num_replacements":3}
+        "#);
+        assert_eq!(
+            tokio::fs::read_to_string(dir.join("src/models/account.rs")).await?,
+            "pub struct User;\nimpl User {}\n"
+        );
+        assert_eq!(
+            tokio::fs::read_to_string(dir.join("README.md")).await?,
+            "Account\n"
+        );
+        assert_snapshot!(ToolError::from(&error).to_json(), @r#"
+        {"error":{"code":"couldnt_keep_encoding","message":"couldn't keep the encoding of src/models/notes.txt: '→' can't be represented in Latin-1","hint":"use characters the file's encoding can represent, or leave the file out via the glob"}}
+        "#);
+        assert_eq!(
+            tokio::fs::read(dir.join("src/models/notes.txt")).await?,
+            b"Account caf\xe9\n"
+        );
+        Ok(())
+    }
+}
@@ -1,12 +1,43 @@
+use super::{AgxTool, ArtifactStore, ConfirmationPolicy, ToolErrorKind, Workspace};
+use portable_pty::{CommandBuilder, PtySize, native_pty_system};
 use rig::completion::ToolDefinition;
 use rig::tool::Tool;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use tracing::instrument;
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{instrument, warn};
+
+// outputs longer than this are condensed when summarization is requested
+const MAX_LINES_BEFORE_SUMMARIZING: usize = 200;
+const SUMMARY_TAIL_LINES: usize = 50;
+const MAX_SUMMARY_ERROR_LINES: usize = 100;
+const ERROR_PATTERNS: [&str; 6] = ["error", "fail", "panic", "exception", "traceback", "fatal"];
+
+const PTY_SIZE: PtySize = PtySize {
+    rows: 50,
+    cols: 200,
+    pixel_width: 0,
+    pixel_height: 0,
+};
 
 #[derive(Debug, Deserialize)]
 pub struct RunCmdArgs {
     pub command: String,
+    #[serde(default)]
+    pub summarize: bool,
+    /// Directory to run the command in, relative to the workspace.
+    #[serde(default)]
+    pub cwd: Option<String>,
+    /// Runs the command in a pseudo terminal, for commands that need one.
+    #[serde(default)]
+    pub tty: bool,
+    /// Typed into the terminal once the command starts; only used along with `tty`.
+    #[serde(default)]
+    pub input: Option<String>,
 }
 
 impl std::fmt::Display for RunCmdArgs {
@@ -19,11 +50,49 @@ impl std::fmt::Display for RunCmdArgs {
 pub enum RunCmdError {
     #[error("command is empty")]
     CmdIsEmpty,
+    #[error("working directory is outside the workspace")]
+    CwdOutsideWorkspace,
+    #[error("working directory doesn't exist: {0}")]
+    CwdNotFound(String),
     #[error("couldn't run command: {0}")]
     CouldntRunCmd(#[from] std::io::Error),
 }
 
-pub struct RunCmdTool;
+impl ToolErrorKind for RunCmdError {
+    fn code(&self) -> &'static str {
+        match self {
+            RunCmdError::CmdIsEmpty => "empty_command",
+            RunCmdError::CwdOutsideWorkspace => "cwd_outside_workspace",
+            RunCmdError::CwdNotFound(_) => "cwd_not_found",
+            RunCmdError::CouldntRunCmd(_) => "couldnt_run_command",
+        }
+    }
+
+    fn hint(&self) -> Option<&'static str> {
+        match self {
+            RunCmdError::CwdOutsideWorkspace => {
+                Some("use a path relative to the workspace, without any \"..\" components")
+            }
+            RunCmdError::CwdNotFound(_) => Some("check the path with read_dir"),
+            RunCmdError::CmdIsEmpty | RunCmdError::CouldntRunCmd(_) => None,
+        }
+    }
+}
+
+pub struct RunCmdTool {
+    workspace: Arc<Workspace>,
+    artifacts: Arc<ArtifactStore>,
+}
+
+impl RunCmdTool {
+    /// Full outputs of summarized commands are saved as artifacts.
+    pub fn new(workspace: Arc<Workspace>, artifacts: Arc<ArtifactStore>) -> Self {
+        Self {
+            workspace,
+            artifacts,
+        }
+    }
+}
 
 #[derive(Debug, Serialize)]
 pub struct RunCmdResponse {
@@ -31,6 +100,9 @@ pub struct RunCmdResponse {
     pub status_code: Option<i32>,
     stdout: String,
     stderr: String,
+    /// The artifact the full output was saved as, if stdout/stderr were summarized.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    full_output_artifact: Option<String>,
 }
 
 impl Tool for RunCmdTool {
@@ -42,7 +114,7 @@ impl Tool for RunCmdTool {
     async fn definition(&self, _prompt: String) -> ToolDefinition {
         ToolDefinition {
             name: Self::NAME.to_string(),
-            description: "Run a shell command via bash. Returns the command’s success flag, exit status code (if available), stdout, and stderr".to_string(),
+            description: "Run a shell command via bash. Returns the command’s success flag, exit status code (if available), stdout, and stderr. Commands that need a terminal (eg. interactive ones, or ones that behave differently without one) can be run in a pseudo terminal via tty, in which case stdout and stderr are returned together as stdout".to_string(),
             parameters: json!({
                 "type": "object",
                 "properties": {
@@ -50,6 +122,24 @@ impl Tool for RunCmdTool {
                         "type": "string",
                         "description": "the shell command to run"
                     },
+                    "summarize": {
+                        "type": "boolean",
+                        "description": "set to true for commands with long output (eg. test suites, builds); long stdout/stderr are then cut down to the lines that look like errors and the last few lines, and the full output is saved as an artifact that can be read with read_artifact",
+                        "default": false
+                    },
+                    "cwd": {
+                        "type": "string",
+                        "description": "directory to run the command in, relative to the workspace (default: the workspace); use this instead of prefixing the command with \"cd <dir> &&\""
+                    },
+                    "tty": {
+                        "type": "boolean",
+                        "description": "set to true to run the command in a pseudo terminal; colors and other terminal escape codes are stripped from the output",
+                        "default": false
+                    },
+                    "input": {
+                        "type": "string",
+                        "description": "only used with tty: text typed into the terminal once the command starts (eg. \"y\\nn\\n\" to answer prompts); the terminal's input is closed afterwards"
+                    },
                 },
                 "required": ["command"],
             }),
@@ -62,39 +152,246 @@ impl Tool for RunCmdTool {
             return Err(RunCmdError::CmdIsEmpty);
         }
 
-        // TODO: make it cross-platform, have fallback if bash unavailable
-        // TODO: add timeout
-        let output = tokio::process::Command::new("bash")
-            .args(["-c", &args.command])
-            .output()
-            .await?;
+        let dir = match &args.cwd {
+            Some(cwd) => {
+                let dir = self
+                    .workspace
+                    .resolve_for_write(cwd)
+                    .ok_or(RunCmdError::CwdOutsideWorkspace)?;
+                if !dir.is_dir() {
+                    return Err(RunCmdError::CwdNotFound(cwd.clone()));
+                }
+                dir
+            }
+            None => self.workspace.dir(),
+        };
+
+        let settings = self.workspace.command_settings();
+        let env = settings.env;
+        let (success, status_code, mut stdout, mut stderr) = if args.tty {
+            let command = args.command.clone();
+            let input = args.input.clone();
+            let (output, status) = tokio::task::spawn_blocking(move || {
+                run_in_pty(&command, &dir, &env, input.as_deref())
+            })
+            .await
+            .map_err(std::io::Error::other)?
+            .map_err(std::io::Error::other)?;
+            let status_code = match status.signal() {
+                Some(_) => None,
+                None => i32::try_from(status.exit_code()).ok(),
+            };
+
+            (status.success(), status_code, output, String::new())
+        } else {
+            // TODO: make it cross-platform, have fallback if bash unavailable
+            // TODO: add timeout
+            let output = tokio::process::Command::new("bash")
+                .args(["-c", &args.command])
+                .current_dir(&dir)
+                .envs(&env)
+                .output()
+                .await?;
+
+            (
+                output.status.success(),
+                output.status.code(),
+                String::from_utf8(output.stdout)
+                    .unwrap_or_else(|_| "couldn't get command stdout".to_string()),
+                String::from_utf8(output.stderr)
+                    .unwrap_or_else(|_| "couldn't get command stderr".to_string()),
+            )
+        };
+        if settings.clean_output {
+            stdout = clean_output(&stdout);
+            stderr = clean_output(&stderr);
+        }
+        let mut full_output_artifact = None;
+
+        if args.summarize {
+            let summaries = (summarize_output(&stdout), summarize_output(&stderr));
+            if summaries.0.is_some() || summaries.1.is_some() {
+                // the output is only cut down if the model can get to all of it
+                let full_output = format!(
+                    "$ {}\n\n--- stdout ---\n{stdout}\n--- stderr ---\n{stderr}",
+                    args.command
+                );
+                match self.artifacts.save(Self::NAME, &full_output).await {
+                    Ok(artifact) => {
+                        full_output_artifact = Some(artifact.name);
+                        stdout = summaries.0.unwrap_or(stdout);
+                        stderr = summaries.1.unwrap_or(stderr);
+                    }
+                    Err(e) => warn!("couldn't save command output: {e}"),
+                }
+            }
+        }
 
         Ok(RunCmdResponse {
-            success: output.status.success(),
-            status_code: output.status.code(),
-            stdout: String::from_utf8(output.stdout)
-                .unwrap_or_else(|_| "couldn't get command stdout".to_string()),
-            stderr: String::from_utf8(output.stderr)
-                .unwrap_or_else(|_| "couldn't get command stderr".to_string()),
+            success,
+            status_code,
+            stdout,
+            stderr,
+            full_output_artifact,
         })
     }
 }
 
-impl RunCmdTool {
-    pub fn repr(args: &RunCmdArgs) -> String {
-        format!("run_cmd: {}", args.command)
+/// Runs a command in a pseudo terminal, so that commands that need one (or behave differently
+/// without one) work as they would in a terminal. `input` is typed into the terminal, after which
+/// its input is closed. Returns what the terminal showed (stdout and stderr interleaved), along
+/// with the command's exit status.
+fn run_in_pty(
+    command: &str,
+    dir: &Path,
+    env: &BTreeMap<String, String>,
+    input: Option<&str>,
+) -> anyhow::Result<(String, portable_pty::ExitStatus)> {
+    let pair = native_pty_system().openpty(PTY_SIZE)?;
+
+    let mut builder = CommandBuilder::new("bash");
+    builder.args(["-c", command]);
+    builder.cwd(dir);
+    builder.env("TERM", "xterm-256color");
+    // pagers would wait for key presses that never come
+    builder.env("PAGER", "cat");
+    builder.env("GIT_PAGER", "cat");
+    for (key, value) in env {
+        builder.env(key, value);
     }
+    let mut child = pair.slave.spawn_command(builder)?;
+    // the terminal's output only ends once every handle to the command's side of it is closed
+    drop(pair.slave);
 
-    pub fn details(_args: &RunCmdArgs) -> Option<String> {
-        None
+    let mut reader = pair.master.try_clone_reader()?;
+    let mut writer = pair.master.take_writer()?;
+    let input = input.unwrap_or_default().to_string();
+    // writing blocks while the command isn't reading; dropping the writer sends EOF
+    std::thread::spawn(move || {
+        _ = writer.write_all(input.as_bytes());
+    });
+
+    let mut output = vec![];
+    let mut buf = [0; 8192];
+    loop {
+        match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => output.extend_from_slice(&buf[..n]),
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            // on Linux, reading fails (with EIO) once the command's side is closed
+            Err(_) => break,
+        }
+    }
+    let status = child.wait()?;
+
+    Ok((String::from_utf8_lossy(&output).into_owned(), status))
+}
+
+/// Strips escape codes (eg. colors) from a command's output, and keeps only what's last written
+/// to a line that was redrawn via carriage returns (eg. progress bars); tokens spent on those
+/// don't tell the model anything.
+pub(super) fn clean_output(output: &str) -> String {
+    console::strip_ansi_codes(output)
+        .split('\n')
+        .map(|line| {
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            line.rsplit('\r').next().unwrap_or(line)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Cuts down long output to the lines that look like errors, followed by the last few lines;
+/// returns `None` if the output is short enough to be sent as is.
+pub(super) fn summarize_output(output: &str) -> Option<String> {
+    let lines = output.lines().collect::<Vec<_>>();
+    if lines.len() <= MAX_LINES_BEFORE_SUMMARIZING {
+        return None;
+    }
+
+    let tail_start = lines.len() - SUMMARY_TAIL_LINES;
+    let error_lines = lines[..tail_start]
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| {
+            let line = line.to_lowercase();
+            ERROR_PATTERNS.iter().any(|p| line.contains(p))
+        })
+        .take(MAX_SUMMARY_ERROR_LINES)
+        .map(|(i, line)| format!("{}: {line}", i + 1))
+        .collect::<Vec<_>>();
+
+    let mut summary = String::new();
+    if !error_lines.is_empty() {
+        summary.push_str(&format!(
+            "[lines that look like errors, with line numbers]\n{}\n\n",
+            error_lines.join("\n")
+        ));
+    }
+    summary.push_str(&format!(
+        "[last {SUMMARY_TAIL_LINES} of {} lines]\n{}\n",
+        lines.len(),
+        lines[tail_start..].join("\n")
+    ));
+
+    Some(summary)
+}
+
+impl AgxTool for RunCmdTool {
+    fn repr(args: &RunCmdArgs) -> String {
+        let mut details = vec![];
+        if let Some(cwd) = &args.cwd {
+            details.push(format!("in {cwd}"));
+        }
+        match (args.tty, &args.input) {
+            (true, Some(input)) => details.push(format!("in a terminal, with input {input:?}")),
+            (true, None) => details.push("in a terminal".to_string()),
+            (false, _) => {}
+        }
+
+        if details.is_empty() {
+            format!("run_cmd: {}", args.command)
+        } else {
+            format!("run_cmd: {} ({})", args.command, details.join(", "))
+        }
+    }
+
+    fn confirmation_policy(args: &RunCmdArgs) -> ConfirmationPolicy {
+        ConfirmationPolicy::Command(args.command.clone())
+    }
+
+    fn summary(output: &RunCmdResponse, elapsed: Duration) -> String {
+        let summary = match output.status_code {
+            Some(c) if c != 0 => format!("took {} ms; exit code: {c}", elapsed.as_millis()),
+            _ => format!("took {} ms", elapsed.as_millis()),
+        };
+
+        match &output.full_output_artifact {
+            Some(name) => format!("{summary}; output summarized, full output in artifact {name}"),
+            None => summary,
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::tools::CommandSettings;
     use insta::{assert_debug_snapshot, assert_yaml_snapshot};
 
+    /// Returns the tool along with the directory its oversized output is saved to, which is
+    /// removed once dropped.
+    fn tool() -> (RunCmdTool, tempfile::TempDir) {
+        let artifacts_dir =
+            tempfile::tempdir().expect("temporary directory should've been created");
+        let tool = RunCmdTool::new(
+            Arc::new(Workspace::new(".")),
+            Arc::new(ArtifactStore::new(artifacts_dir.path())),
+        );
+
+        (tool, artifacts_dir)
+    }
+
     //-------------//
     //  SUCCESSES  //
     //-------------//
@@ -102,9 +399,13 @@ mod tests {
     #[tokio::test]
     async fn output_of_a_successful_command_is_returned() -> anyhow::Result<()> {
         // GIVEN
-        let tool = RunCmdTool;
+        let (tool, _artifacts_dir) = tool();
         let args = RunCmdArgs {
             command: "cat src/tools/testdata/sample.txt".to_string(),
+            summarize: false,
+            cwd: None,
+            tty: false,
+            input: None,
         };
 
         // WHEN
@@ -124,9 +425,13 @@ mod tests {
     #[tokio::test]
     async fn output_of_a_failing_command_is_returned() -> anyhow::Result<()> {
         // GIVEN
-        let tool = RunCmdTool;
+        let (tool, _artifacts_dir) = tool();
         let args = RunCmdArgs {
             command: r#"echo "something went wrong" >&2; false"#.to_string(),
+            summarize: false,
+            cwd: None,
+            tty: false,
+            input: None,
         };
 
         // WHEN
@@ -146,9 +451,13 @@ mod tests {
     #[tokio::test]
     async fn command_with_pipes_can_be_run() -> anyhow::Result<()> {
         // GIVEN
-        let tool = RunCmdTool;
+        let (tool, _artifacts_dir) = tool();
         let args = RunCmdArgs {
             command: "cat src/tools/testdata/sample.txt | grep '#' | wc -l | xargs".to_string(),
+            summarize: false,
+            cwd: None,
+            tty: false,
+            input: None,
         };
 
         // WHEN
@@ -165,6 +474,185 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn commands_are_run_with_the_environment_from_config() -> anyhow::Result<()> {
+        // GIVEN
+        let (tool, _artifacts_dir) = tool();
+        tool.workspace.set_command_settings(CommandSettings {
+            env: BTreeMap::from([
+                ("RUST_BACKTRACE".to_string(), "1".to_string()),
+                (
+                    "DATABASE_URL".to_string(),
+                    "postgres://localhost/test".to_string(),
+                ),
+            ]),
+            ..Default::default()
+        });
+        let args = RunCmdArgs {
+            command: r#"echo "$RUST_BACKTRACE $DATABASE_URL""#.to_string(),
+            summarize: false,
+            cwd: None,
+            tty: false,
+            input: None,
+        };
+
+        // WHEN
+        let result = tool.call(args).await?;
+
+        // THEN
+        assert_yaml_snapshot!(result, @r#"
+        success: true
+        status_code: 0
+        stdout: "1 postgres://localhost/test\n"
+        stderr: ""
+        "#);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn colored_output_is_only_cleaned_up_if_configured() -> anyhow::Result<()> {
+        // GIVEN
+        let (tool, _artifacts_dir) = tool();
+        let args = || RunCmdArgs {
+            command: r"printf '\033[31merror\033[0m: 1 test failed\n'; printf '10%%\r100%%\n' >&2"
+                .to_string(),
+            summarize: false,
+            cwd: None,
+            tty: false,
+            input: None,
+        };
+
+        // WHEN
+        let cleaned = tool.call(args()).await?;
+        tool.workspace.set_command_settings(CommandSettings {
+            clean_output: false,
+            ..Default::default()
+        });
+        let raw = tool.call(args()).await?;
+
+        // THEN
+        assert_yaml_snapshot!([cleaned, raw], @r#"
+        - success: true
+          status_code: 0
+          stdout: "error: 1 test failed\n"
+          stderr: "100%\n"
+        - success: true
+          status_code: 0
+          stdout: "\u001b[31merror\u001b[0m: 1 test failed\n"
+          stderr: "10%\r100%\n"
+        "#);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn long_output_is_summarized_when_asked_to() -> anyhow::Result<()> {
+        // GIVEN
+        let (tool, _artifacts_dir) = tool();
+        let args = RunCmdArgs {
+            command: "seq 1 150; echo 'error: 7 tests failed'; seq 151 300".to_string(),
+            summarize: true,
+            cwd: None,
+            tty: false,
+            input: None,
+        };
+
+        // WHEN
+        let result = tool.call(args).await?;
+
+        // THEN
+        let full_output_artifact = result
+            .full_output_artifact
+            .as_ref()
+            .and_then(|name| tool.artifacts.path(name))
+            .expect("full output artifact should've been returned");
+        let full_output = std::fs::read_to_string(full_output_artifact)?;
+        assert_eq!(full_output.lines().count(), 306);
+        assert!(result.stdout.starts_with(
+            "[lines that look like errors, with line numbers]\n151: error: 7 tests failed\n\n[last 50 of 301 lines]\n251\n"
+        ));
+        assert!(result.stdout.ends_with("300\n"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn commands_can_be_run_in_a_terminal() -> anyhow::Result<()> {
+        // GIVEN
+        let (tool, _artifacts_dir) = tool();
+        let args = RunCmdArgs {
+            command: r#"[ -t 1 ] && echo "stdout is a terminal"; read -r answer; printf '\e[31m%s\e[0m\n' "got $answer" >&2"#
+                .to_string(),
+            summarize: false,
+            cwd: None,
+            tty: true,
+            input: Some("y\n".to_string()),
+        };
+
+        // WHEN
+        let result = tool.call(args).await?;
+
+        // THEN
+        // input is echoed by the terminal as soon as it's typed, so the order of lines can vary
+        assert!(result.success);
+        assert!(result.stdout.contains("stdout is a terminal\n"));
+        assert!(result.stdout.contains("got y\n"));
+        assert!(result.stderr.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn commands_can_be_run_in_a_subdirectory() -> anyhow::Result<()> {
+        // GIVEN
+        let (tool, _artifacts_dir) = tool();
+        let args = RunCmdArgs {
+            command: "cat sample.txt | wc -l | xargs".to_string(),
+            summarize: false,
+            cwd: Some("src/tools/testdata".to_string()),
+            tty: false,
+            input: None,
+        };
+
+        // WHEN
+        let result = tool.call(args).await?;
+
+        // THEN
+        assert_yaml_snapshot!(result, @r#"
+        success: true
+        status_code: 0
+        stdout: "3\n"
+        stderr: ""
+        "#);
+
+        Ok(())
+    }
+
+    #[test]
+    fn escape_codes_and_redrawn_lines_are_cleaned_up() {
+        // GIVEN
+        let output = "\x1b[32mCompiling\x1b[0m agx\r\n[1/3]\r[2/3]\r[3/3]\r\ndone\r\n";
+
+        // WHEN
+        let cleaned = clean_output(output);
+
+        // THEN
+        assert_eq!(cleaned, "Compiling agx\n[3/3]\ndone\n");
+    }
+
+    #[test]
+    fn short_output_is_not_summarized() {
+        // GIVEN
+        let output = "running 3 tests\ntest result: FAILED. 2 passed; 1 failed\n";
+
+        // WHEN
+        let summary = summarize_output(output);
+
+        // THEN
+        assert!(summary.is_none());
+    }
+
     //------------//
     //  FAILURES  //
     //------------//
@@ -172,9 +660,13 @@ mod tests {
     #[tokio::test]
     async fn running_empty_command_fails() {
         // GIVEN
-        let tool = RunCmdTool;
+        let (tool, _artifacts_dir) = tool();
         let args = RunCmdArgs {
             command: "".to_string(),
+            summarize: false,
+            cwd: None,
+            tty: false,
+            input: None,
         };
 
         // WHEN
@@ -186,4 +678,26 @@ mod tests {
         // THEN
         assert_debug_snapshot!(result, @"CmdIsEmpty");
     }
+
+    #[tokio::test]
+    async fn running_a_command_outside_the_workspace_fails() {
+        // GIVEN
+        let (tool, _artifacts_dir) = tool();
+        let args = RunCmdArgs {
+            command: "ls".to_string(),
+            summarize: false,
+            cwd: Some("src/../..".to_string()),
+            tty: false,
+            input: None,
+        };
+
+        // WHEN
+        let result = tool
+            .call(args)
+            .await
+            .expect_err("result should've been an error");
+
+        // THEN
+        assert_debug_snapshot!(result, @"CwdOutsideWorkspace");
+    }
 }
@@ -0,0 +1,95 @@
+use super::{AgxTool, ToolErrorKind};
+use crate::index::{KnowledgeHit, KnowledgeStore};
+use rig::completion::ToolDefinition;
+use rig::tool::Tool;
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::instrument;
+
+const DEFAULT_LIMIT: usize = 5;
+const MAX_LIMIT: usize = 20;
+
+#[derive(Debug, Deserialize)]
+pub struct SearchKnowledgeArgs {
+    pub query: String,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SearchKnowledgeError {
+    #[error("no documents are attached")]
+    NothingAttached,
+}
+
+impl ToolErrorKind for SearchKnowledgeError {
+    fn code(&self) -> &'static str {
+        match self {
+            SearchKnowledgeError::NothingAttached => "nothing_attached",
+        }
+    }
+}
+
+pub struct SearchKnowledgeTool {
+    knowledge: Arc<KnowledgeStore>,
+}
+
+impl SearchKnowledgeTool {
+    pub fn new(knowledge: Arc<KnowledgeStore>) -> Self {
+        Self { knowledge }
+    }
+}
+
+impl Tool for SearchKnowledgeTool {
+    const NAME: &'static str = "search_knowledge";
+    type Error = SearchKnowledgeError;
+    type Args = SearchKnowledgeArgs;
+    type Output = Vec<KnowledgeHit>;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "search_knowledge".to_string(),
+            description: format!(
+                "Search documents the user attached to this session (eg. design docs, API references) by keywords. Returns the most relevant chunks of the documents. Attached documents: {}",
+                self.knowledge.sources().join(", ")
+            ),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "query": {
+                        "type": "string",
+                        "description": "keywords to look for"
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "description": format!("maximum number of results to return (default: {DEFAULT_LIMIT}, max: {MAX_LIMIT})")
+                    },
+                },
+                "required": ["query"],
+            }),
+        }
+    }
+
+    #[instrument(name = "tool-call: search_knowledge", skip(self), err)]
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        if self.knowledge.sources().is_empty() {
+            return Err(SearchKnowledgeError::NothingAttached);
+        }
+
+        let limit = args.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+        Ok(self.knowledge.search(&args.query, limit))
+    }
+}
+
+impl AgxTool for SearchKnowledgeTool {
+    fn repr(args: &SearchKnowledgeArgs) -> String {
+        format!("search_knowledge: {}", args.query)
+    }
+
+    fn summary(hits: &Vec<KnowledgeHit>, _elapsed: Duration) -> String {
+        format!("found {} matches", hits.len())
+    }
+}
@@ -0,0 +1,96 @@
+use super::{AgxTool, ToolErrorKind, Workspace};
+use crate::index::{SearchHit, SemanticIndex};
+use rig::completion::ToolDefinition;
+use rig::embeddings::EmbeddingError;
+use rig::tool::Tool;
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::instrument;
+
+const DEFAULT_LIMIT: usize = 5;
+const MAX_LIMIT: usize = 20;
+
+#[derive(Debug, Deserialize)]
+pub struct SemanticSearchArgs {
+    pub query: String,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SemanticSearchError {
+    #[error("couldn't embed query: {0}")]
+    CouldntEmbedQuery(#[from] EmbeddingError),
+}
+
+impl ToolErrorKind for SemanticSearchError {
+    fn code(&self) -> &'static str {
+        match self {
+            SemanticSearchError::CouldntEmbedQuery(_) => "couldnt_embed_query",
+        }
+    }
+}
+
+pub struct SemanticSearchTool {
+    index: Arc<SemanticIndex>,
+    workspace: Arc<Workspace>,
+}
+
+impl SemanticSearchTool {
+    pub fn new(index: Arc<SemanticIndex>, workspace: Arc<Workspace>) -> Self {
+        Self { index, workspace }
+    }
+}
+
+impl Tool for SemanticSearchTool {
+    const NAME: &'static str = "semantic_search";
+    type Error = SemanticSearchError;
+    type Args = SemanticSearchArgs;
+    type Output = Vec<SearchHit>;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "semantic_search".to_string(),
+            description: "Search the project's code by meaning, using an embedding index. Returns the most relevant chunks of files, along with their line ranges. Useful for finding code related to a concept when the exact identifiers aren't known".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "query": {
+                        "type": "string",
+                        "description": "natural language description of the code to look for"
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "description": format!("maximum number of results to return (default: {DEFAULT_LIMIT}, max: {MAX_LIMIT})")
+                    },
+                },
+                "required": ["query"],
+            }),
+        }
+    }
+
+    #[instrument(name = "tool-call: semantic_search", skip(self), err)]
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let limit = args.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+        let package = self.workspace.package();
+
+        let hits = self
+            .index
+            .search(&args.query, limit, package.as_deref())
+            .await?;
+
+        Ok(hits)
+    }
+}
+
+impl AgxTool for SemanticSearchTool {
+    fn repr(args: &SemanticSearchArgs) -> String {
+        format!("semantic_search: {}", args.query)
+    }
+
+    fn summary(hits: &Vec<SearchHit>, _elapsed: Duration) -> String {
+        format!("found {} matches", hits.len())
+    }
+}
@@ -0,0 +1,26 @@
+#!/usr/bin/env bash
+# A minimal MCP server (spoken to over stdio) for tests. It offers a read-only tool that always
+# finds the same notes, and one that always fails.
+
+while IFS= read -r line; do
+    id=$(printf '%s' "$line" | grep -o '"id":[0-9]*' | head -n 1 | cut -d: -f2)
+    case "$line" in
+    *'"method":"initialize"'*)
+        result='{"protocolVersion":"2025-03-26","capabilities":{"tools":{}},"serverInfo":{"name":"notes","version":"0.1.0"}}'
+        ;;
+    *'"method":"tools/list"'*)
+        result='{"tools":[{"name":"search","description":"Searches notes","inputSchema":{"type":"object","properties":{"query":{"type":"string"}},"required":["query"]},"annotations":{"readOnlyHint":true}},{"name":"delete","description":"Deletes a note","inputSchema":{"type":"object","properties":{"title":{"type":"string"}}}}]}'
+        ;;
+    *'"method":"tools/call"'*'"name":"search"'*)
+        result='{"content":[{"type":"text","text":"meeting notes (2026-10-12)"},{"type":"text","text":"release checklist"}]}'
+        ;;
+    *'"method":"tools/call"'*)
+        result='{"content":[{"type":"text","text":"note not found"}],"isError":true}'
+        ;;
+    *)
+        # notifications don't get a response
+        continue
+        ;;
+    esac
+    printf '{"jsonrpc":"2.0","id":%s,"result":%s}\n' "$id" "$result"
+done
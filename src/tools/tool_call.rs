@@ -1,20 +1,58 @@
-use super::{
-    CreateFileArgs, CreateFileTool, EditFileArgs, EditFileTool, ReadDirArgs, ReadDirTool,
-    ReadFileArgs, ReadFileTool, RunCmdArgs, RunCmdTool,
-};
-use colored::Colorize;
+use super::{ToolError, ToolErrorKind};
+use crate::domain::{OutputKind, OutputSink};
+use crate::helpers::Diff;
+use rig::completion::ToolDefinition;
 use rig::message::ToolCall;
 use rig::tool::Tool;
-use std::io::Write;
+use serde::Serialize;
+use std::fmt::Debug;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::time::Instant;
 
-#[derive(Debug)]
-pub enum AgxToolCall {
-    CreateFile { args: CreateFileArgs },
-    EditFile { args: EditFileArgs },
-    ReadFile { args: ReadFileArgs },
-    ReadDir { args: ReadDirArgs },
-    RunCmd { args: RunCmdArgs },
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Determines whether (and how) the user needs to approve a tool call before it's executed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfirmationPolicy {
+    NotNeeded,
+    /// Creating or editing the file at the provided path (relative to the workspace), or the
+    /// files matching the provided glob.
+    FileChange(String),
+    Command(String),
+    /// Changing state outside the machine (eg. opening a pull request).
+    RemoteAction,
+}
+
+/// Extends rig's [`Tool`] with everything agx needs to present, confirm, and execute tool calls.
+pub trait AgxTool: Tool<Error: ToolErrorKind, Output: Serialize> + Send + Sync + 'static
+where
+    Self::Args: Debug + Send + Sync + 'static,
+{
+    fn repr(args: &Self::Args) -> String;
+
+    fn confirmation_policy(_args: &Self::Args) -> ConfirmationPolicy {
+        ConfirmationPolicy::NotNeeded
+    }
+
+    fn details(
+        &self,
+        _args: &Self::Args,
+    ) -> impl Future<Output = Result<Option<String>, ToolError>> + Send {
+        async { Ok(None) }
+    }
+
+    /// Returns the diff for tool calls whose changes can be applied partially.
+    fn hunk_diff(&self, _args: &Self::Args) -> impl Future<Output = Option<Diff>> + Send {
+        async { None }
+    }
+
+    fn set_accepted_hunks(_args: &mut Self::Args, _accepted: Vec<bool>) {}
+
+    /// Short summary of a successful call, shown to the user.
+    fn summary(output: &Self::Output, elapsed: Duration) -> String;
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -25,208 +63,233 @@ pub enum AgxToolCallError {
     InvalidArgs(#[from] serde_json::Error),
 }
 
+impl ToolErrorKind for AgxToolCallError {
+    fn code(&self) -> &'static str {
+        match self {
+            AgxToolCallError::UnknownTool(_) => "unknown_tool",
+            AgxToolCallError::InvalidArgs(_) => "invalid_arguments",
+        }
+    }
+
+    fn hint(&self) -> Option<&'static str> {
+        match self {
+            AgxToolCallError::UnknownTool(_) => Some("only call the tools that are available"),
+            AgxToolCallError::InvalidArgs(_) => {
+                Some("make sure the arguments match the tool's parameters")
+            }
+        }
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum ToolExecutionError {
     #[error("couldn't serialise result: {0}")]
     CouldntSerialiseResult(serde_json::Error),
 }
 
-#[derive(Debug, thiserror::Error)]
-#[error("couldn't get tool call details: {0}")]
-pub struct ToolCallDetailsError(String);
-
-impl ToolCallDetailsError {
-    pub fn new(msg: impl Into<String>) -> Self {
-        Self(msg.into())
-    }
-}
-
-impl TryFrom<ToolCall> for AgxToolCall {
-    type Error = AgxToolCallError;
-
-    fn try_from(call: ToolCall) -> Result<Self, Self::Error> {
-        let name = call.function.name.as_str();
-        let args = call.function.arguments;
-
-        match name {
-            "create_file" => Ok(AgxToolCall::CreateFile {
-                args: serde_json::from_value(args)?,
-            }),
-            "edit_file" => Ok(AgxToolCall::EditFile {
-                args: serde_json::from_value(args)?,
-            }),
-            "read_file" => Ok(AgxToolCall::ReadFile {
-                args: serde_json::from_value(args)?,
-            }),
-            "read_dir" => Ok(AgxToolCall::ReadDir {
-                args: serde_json::from_value(args)?,
-            }),
-            "run_cmd" => Ok(AgxToolCall::RunCmd {
-                args: serde_json::from_value(args)?,
-            }),
-            _ => Err(AgxToolCallError::UnknownTool(name.to_string())),
+impl ToolErrorKind for ToolExecutionError {
+    fn code(&self) -> &'static str {
+        match self {
+            ToolExecutionError::CouldntSerialiseResult(_) => "couldnt_serialise_result",
         }
     }
 }
 
-impl AgxToolCall {
-    pub fn repr(&self) -> String {
+/// What an executed tool call hands back to the model.
+#[derive(Debug)]
+pub enum ToolCallOutcome {
+    /// The tool's output, serialized.
+    Succeeded(String),
+    Failed(ToolError),
+}
+
+impl ToolCallOutcome {
+    /// Content of the tool result sent to the model.
+    pub fn into_content(self) -> String {
         match self {
-            AgxToolCall::CreateFile { args, .. } => CreateFileTool::repr(args),
-            AgxToolCall::EditFile { args, .. } => EditFileTool::repr(args),
-            AgxToolCall::ReadFile { args, .. } => ReadFileTool::repr(args),
-            AgxToolCall::ReadDir { args, .. } => ReadDirTool::repr(args),
-            AgxToolCall::RunCmd { args, .. } => RunCmdTool::repr(args),
+            ToolCallOutcome::Succeeded(output) => output,
+            ToolCallOutcome::Failed(error) => error.to_json(),
         }
     }
+}
 
-    pub async fn details(&self) -> Result<Option<String>, ToolCallDetailsError> {
-        match self {
-            AgxToolCall::EditFile { args, .. } => EditFileTool::details(args)
-                .await
-                .map_err(|e| ToolCallDetailsError::new(e.to_string())),
-            AgxToolCall::CreateFile { args, .. } => Ok(CreateFileTool::details(args)),
-            AgxToolCall::ReadFile { args, .. } => Ok(ReadFileTool::details(args)),
-            AgxToolCall::ReadDir { args, .. } => Ok(ReadDirTool::details(args)),
-            AgxToolCall::RunCmd { args, .. } => Ok(RunCmdTool::details(args)),
-        }
+#[derive(Debug, thiserror::Error)]
+#[error("couldn't get tool call details: {}", .0.message())]
+pub struct ToolCallDetailsError(ToolError);
+
+impl ToolCallDetailsError {
+    pub fn tool_error(&self) -> &ToolError {
+        &self.0
     }
+}
 
-    pub fn needs_confirmation(&self) -> bool {
-        matches!(
-            self,
-            AgxToolCall::EditFile { .. }
-                | AgxToolCall::CreateFile { .. }
-                | AgxToolCall::RunCmd { .. }
-        )
+/// A type erased [`AgxTool`], as stored in the tool registry.
+pub(super) trait DynTool: Send + Sync {
+    fn definition(&self) -> BoxFuture<'_, ToolDefinition>;
+
+    fn parse(
+        self: Arc<Self>,
+        args: serde_json::Value,
+    ) -> Result<Box<dyn DynToolCall>, serde_json::Error>;
+}
+
+impl<T> DynTool for T
+where
+    T: AgxTool,
+    T::Args: Debug + Send + Sync + 'static,
+{
+    fn definition(&self) -> BoxFuture<'_, ToolDefinition> {
+        Box::pin(Tool::definition(self, String::new()))
     }
 
-    pub async fn execute(self) -> Result<String, ToolExecutionError> {
-        let repr = self.repr();
+    fn parse(
+        self: Arc<Self>,
+        args: serde_json::Value,
+    ) -> Result<Box<dyn DynToolCall>, serde_json::Error> {
+        let args = serde_json::from_value(args)?;
 
-        match self {
-            AgxToolCall::RunCmd { args, .. } => {
-                print!("{} ", repr.cyan());
-                let _ = std::io::stdout().flush();
-
-                let start = Instant::now();
-                let result = RunCmdTool.call(args).await;
-                let elapsed_ms = start.elapsed().as_millis();
-
-                match &result {
-                    Ok(r) => println!(
-                        "{}",
-                        format!(
-                            "✓ (took {} ms{})",
-                            elapsed_ms,
-                            match r.status_code {
-                                Some(c) if c != 0 => format!("; exit code: {c}"),
-                                _ => "".to_string(),
-                            }
-                        )
-                        .green()
-                    ),
-                    Err(_) => println!("{}", format!("✗ (took {} ms)", elapsed_ms).red()),
-                }
+        Ok(Box::new(TypedToolCall { tool: self, args }))
+    }
+}
 
-                match result {
-                    Ok(r) => serde_json::to_string(&r)
-                        .map_err(ToolExecutionError::CouldntSerialiseResult),
-                    Err(e) => Ok(format!("error: {e}")),
-                }
-            }
+pub(super) trait DynToolCall: Debug + Send + Sync {
+    fn name(&self) -> &'static str;
+    fn repr(&self) -> String;
+    fn confirmation_policy(&self) -> ConfirmationPolicy;
+    fn details(&self) -> BoxFuture<'_, Result<Option<String>, ToolError>>;
+    fn hunk_diff(&self) -> BoxFuture<'_, Option<Diff>>;
+    fn set_accepted_hunks(&mut self, accepted: Vec<bool>);
+    fn execute(
+        self: Box<Self>,
+        output: Arc<dyn OutputSink>,
+    ) -> BoxFuture<'static, Result<ToolCallOutcome, ToolExecutionError>>;
+}
 
-            AgxToolCall::ReadFile { args, .. } => {
-                let result = ReadFileTool.call(args).await;
-
-                match &result {
-                    Ok(contents) => {
-                        println!(
-                            "{} {}",
-                            repr.cyan(),
-                            format!("✓ (read {} bytes)", contents.len()).green()
-                        );
-                    }
-                    Err(_) => {
-                        println!("{} {}", repr.cyan(), "✗".red());
-                    }
-                }
+struct TypedToolCall<T: AgxTool>
+where
+    T::Args: Debug + Send + Sync + 'static,
+{
+    tool: Arc<T>,
+    args: T::Args,
+}
 
-                match result {
-                    Ok(r) => serde_json::to_string(&r)
-                        .map_err(ToolExecutionError::CouldntSerialiseResult),
-                    Err(e) => Ok(format!("error: {e}")),
-                }
-            }
+impl<T> Debug for TypedToolCall<T>
+where
+    T: AgxTool,
+    T::Args: Debug + Send + Sync + 'static,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TypedToolCall")
+            .field("name", &T::NAME)
+            .field("args", &self.args)
+            .finish()
+    }
+}
 
-            AgxToolCall::CreateFile { args, .. } => {
-                let result = CreateFileTool.call(args).await;
-
-                match &result {
-                    Ok(response) => {
-                        println!(
-                            "{} {}",
-                            repr.cyan(),
-                            format!("✓ (wrote {} bytes)", response.num_bytes_written).green()
-                        );
-                    }
-                    Err(_) => {
-                        println!("{} {}", repr.cyan(), "✗".red());
-                    }
-                }
+impl<T> DynToolCall for TypedToolCall<T>
+where
+    T: AgxTool,
+    T::Args: Debug + Send + Sync + 'static,
+{
+    fn name(&self) -> &'static str {
+        T::NAME
+    }
 
-                match result {
-                    Ok(r) => serde_json::to_string(&r)
-                        .map_err(ToolExecutionError::CouldntSerialiseResult),
-                    Err(e) => Ok(format!("error: {e}")),
-                }
-            }
+    fn repr(&self) -> String {
+        T::repr(&self.args)
+    }
 
-            AgxToolCall::EditFile { args, .. } => {
-                let result = EditFileTool.call(args).await;
-
-                match &result {
-                    Ok(response) => {
-                        println!(
-                            "{} {}",
-                            repr.cyan(),
-                            format!("✓ (wrote {} bytes)", response.num_bytes_written).green()
-                        );
-                    }
-                    Err(_) => {
-                        println!("{} {}", repr.cyan(), "✗".red());
-                    }
-                }
+    fn confirmation_policy(&self) -> ConfirmationPolicy {
+        T::confirmation_policy(&self.args)
+    }
 
-                match result {
-                    Ok(r) => serde_json::to_string(&r)
-                        .map_err(ToolExecutionError::CouldntSerialiseResult),
-                    Err(e) => Ok(format!("error: {e}")),
-                }
-            }
+    fn details(&self) -> BoxFuture<'_, Result<Option<String>, ToolError>> {
+        Box::pin(self.tool.details(&self.args))
+    }
 
-            AgxToolCall::ReadDir { args, .. } => {
-                let result = ReadDirTool.call(args).await;
-
-                match &result {
-                    Ok(entries) => {
-                        println!(
-                            "{} {}",
-                            repr.cyan(),
-                            format!("✓ (read {} entries)", entries.len()).green()
-                        );
-                    }
-                    Err(_) => {
-                        println!("{} {}", repr.cyan(), "✗".red());
-                    }
-                }
+    fn hunk_diff(&self) -> BoxFuture<'_, Option<Diff>> {
+        Box::pin(self.tool.hunk_diff(&self.args))
+    }
+
+    fn set_accepted_hunks(&mut self, accepted: Vec<bool>) {
+        T::set_accepted_hunks(&mut self.args, accepted);
+    }
+
+    fn execute(
+        self: Box<Self>,
+        output: Arc<dyn OutputSink>,
+    ) -> BoxFuture<'static, Result<ToolCallOutcome, ToolExecutionError>> {
+        Box::pin(async move {
+            output.emit(OutputKind::ToolCall, &format!("{} ", T::repr(&self.args)));
+
+            let start = Instant::now();
+            let result = self.tool.call(self.args).await;
+            let elapsed = start.elapsed();
 
-                match result {
-                    Ok(r) => serde_json::to_string(&r)
-                        .map_err(ToolExecutionError::CouldntSerialiseResult),
-                    Err(e) => Ok(format!("error: {e}")),
+            match result {
+                Ok(response) => {
+                    output.emit(
+                        OutputKind::Success,
+                        &format!("✓ ({})\n", T::summary(&response, elapsed)),
+                    );
+                    serde_json::to_string(&response)
+                        .map(ToolCallOutcome::Succeeded)
+                        .map_err(ToolExecutionError::CouldntSerialiseResult)
+                }
+                Err(e) => {
+                    output.emit(
+                        OutputKind::Error,
+                        &format!("✗ (took {} ms)\n", elapsed.as_millis()),
+                    );
+                    Ok(ToolCallOutcome::Failed(ToolError::from(&e)))
                 }
             }
-        }
+        })
+    }
+}
+
+/// A tool call requested by the model, resolved against the tool registry.
+#[derive(Debug)]
+pub struct AgxToolCall(Box<dyn DynToolCall>);
+
+impl AgxToolCall {
+    pub(super) fn new(tool: Arc<dyn DynTool>, call: ToolCall) -> Result<Self, AgxToolCallError> {
+        Ok(Self(tool.parse(call.function.arguments)?))
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.0.name()
+    }
+
+    pub fn repr(&self) -> String {
+        self.0.repr()
+    }
+
+    pub async fn details(&self) -> Result<Option<String>, ToolCallDetailsError> {
+        self.0.details().await.map_err(ToolCallDetailsError)
+    }
+
+    pub async fn hunk_diff(&self) -> Option<Diff> {
+        self.0.hunk_diff().await
+    }
+
+    pub fn set_accepted_hunks(&mut self, accepted: Vec<bool>) {
+        self.0.set_accepted_hunks(accepted);
+    }
+
+    pub fn confirmation_policy(&self) -> ConfirmationPolicy {
+        self.0.confirmation_policy()
+    }
+
+    pub fn needs_confirmation(&self) -> bool {
+        self.confirmation_policy() != ConfirmationPolicy::NotNeeded
+    }
+
+    /// Executes the tool call, reporting its progress to `output`.
+    pub async fn execute(
+        self,
+        output: Arc<dyn OutputSink>,
+    ) -> Result<ToolCallOutcome, ToolExecutionError> {
+        self.0.execute(output).await
     }
 }
@@ -0,0 +1,208 @@
+use crate::domain::{Config, DEFAULT_MAX_WRITE_BYTES, DiffMode};
+use crate::helpers::{DiffOptions, is_path_in_workspace};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+/// The directory tools operate in; paths provided by the model are resolved against it rather
+/// than the process's working directory.
+///
+/// In monorepos, the workspace can be scoped to a package within the project root. Paths are
+/// then resolved relative to the package, and changes are only allowed within it.
+#[derive(Debug)]
+pub struct Workspace {
+    root: PathBuf,
+    package: RwLock<Option<PathBuf>>,
+    commands: RwLock<CommandSettings>,
+    max_write_bytes: RwLock<usize>,
+    diff_options: RwLock<DiffOptions>,
+    /// Formatters for files written by tools, keyed by file extension (as per config).
+    formatters: RwLock<BTreeMap<String, String>>,
+    /// Linters for files written by tools, keyed by file extension (as per config).
+    linters: RwLock<BTreeMap<String, String>>,
+}
+
+/// How commands are run in the workspace (as per config).
+#[derive(Debug, Clone)]
+pub struct CommandSettings {
+    /// Environment variables commands are run with.
+    pub env: BTreeMap<String, String>,
+    /// Whether escape codes and lines redrawn via carriage returns are cleaned up from output.
+    pub clean_output: bool,
+}
+
+impl Default for CommandSettings {
+    fn default() -> Self {
+        Self {
+            env: BTreeMap::new(),
+            clean_output: true,
+        }
+    }
+}
+
+impl From<&Config> for CommandSettings {
+    fn from(config: &Config) -> Self {
+        Self {
+            env: config.command_env.clone(),
+            clean_output: config.clean_command_output(),
+        }
+    }
+}
+
+impl Workspace {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            package: RwLock::new(None),
+            commands: RwLock::new(CommandSettings::default()),
+            max_write_bytes: RwLock::new(DEFAULT_MAX_WRITE_BYTES),
+            diff_options: RwLock::new(DiffOptions::default()),
+            formatters: RwLock::new(BTreeMap::new()),
+            linters: RwLock::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Returns the package (relative to the root) that the workspace is scoped to, if any.
+    pub fn package(&self) -> Option<PathBuf> {
+        self.package.read().ok().and_then(|p| p.clone())
+    }
+
+    pub fn set_package(&self, package: Option<PathBuf>) {
+        if let Ok(mut p) = self.package.write() {
+            *p = package;
+        }
+    }
+
+    pub fn command_settings(&self) -> CommandSettings {
+        self.commands.read().map(|c| c.clone()).unwrap_or_default()
+    }
+
+    pub fn set_command_settings(&self, settings: CommandSettings) {
+        if let Ok(mut c) = self.commands.write() {
+            *c = settings;
+        }
+    }
+
+    /// Returns the largest write file tools can make in a single call (as per config).
+    pub fn max_write_bytes(&self) -> usize {
+        self.max_write_bytes
+            .read()
+            .map(|m| *m)
+            .unwrap_or(DEFAULT_MAX_WRITE_BYTES)
+    }
+
+    pub fn set_max_write_bytes(&self, max: usize) {
+        if let Ok(mut m) = self.max_write_bytes.write() {
+            *m = max;
+        }
+    }
+
+    /// Returns how changes to files are shown: the mode is as per config (unless changed via
+    /// /diffmode), and context lines can be changed via `/set diff_context`.
+    pub fn diff_options(&self) -> DiffOptions {
+        self.diff_options.read().map(|o| *o).unwrap_or_default()
+    }
+
+    pub fn set_diff_mode(&self, mode: DiffMode) {
+        if let Ok(mut o) = self.diff_options.write() {
+            o.mode = mode;
+        }
+    }
+
+    pub fn set_diff_context_lines(&self, context_lines: usize) {
+        if let Ok(mut o) = self.diff_options.write() {
+            o.context_lines = context_lines;
+        }
+    }
+
+    pub fn set_formatters(&self, formatters: BTreeMap<String, String>) {
+        if let Ok(mut f) = self.formatters.write() {
+            *f = formatters;
+        }
+    }
+
+    /// Returns the command that formats files like the one at `path`, if one is configured.
+    pub fn formatter_for(&self, path: impl AsRef<Path>) -> Option<String> {
+        let extension = path.as_ref().extension()?.to_str()?;
+        self.formatters.read().ok()?.get(extension).cloned()
+    }
+
+    pub fn set_linters(&self, linters: BTreeMap<String, String>) {
+        if let Ok(mut l) = self.linters.write() {
+            *l = linters;
+        }
+    }
+
+    /// Returns the command that lints files like the one at `path`, if one is configured.
+    pub fn linter_for(&self, path: impl AsRef<Path>) -> Option<String> {
+        let extension = path.as_ref().extension()?.to_str()?;
+        self.linters.read().ok()?.get(extension).cloned()
+    }
+
+    /// Returns the directory the workspace is currently scoped to.
+    pub fn dir(&self) -> PathBuf {
+        match self.package() {
+            Some(package) => self.root.join(package),
+            None => self.root.clone(),
+        }
+    }
+
+    pub fn resolve(&self, path: impl AsRef<Path>) -> PathBuf {
+        self.dir().join(path)
+    }
+
+    /// Resolves a path that is to be modified; only paths within the workspace are allowed.
+    pub fn resolve_for_write(&self, path: impl AsRef<Path>) -> Option<PathBuf> {
+        is_path_in_workspace(&path).then(|| self.dir().join(path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_are_resolved_against_the_workspace() {
+        // GIVEN
+        let workspace = Workspace::new("/projects/shop");
+
+        // WHEN
+        // THEN
+        assert_eq!(
+            workspace.resolve("src/main.rs"),
+            PathBuf::from("/projects/shop/src/main.rs")
+        );
+        assert_eq!(
+            workspace.resolve_for_write("src/main.rs"),
+            Some(PathBuf::from("/projects/shop/src/main.rs"))
+        );
+
+        workspace.set_package(Some(PathBuf::from("packages/web")));
+        assert_eq!(
+            workspace.resolve_for_write("src/main.rs"),
+            Some(PathBuf::from("/projects/shop/packages/web/src/main.rs"))
+        );
+    }
+
+    #[test]
+    fn paths_outside_the_workspace_cant_be_written_to() {
+        // GIVEN
+        let workspace = Workspace::new("/projects/shop");
+        workspace.set_package(Some(PathBuf::from("packages/web")));
+
+        // WHEN
+        // THEN
+        assert_eq!(workspace.resolve_for_write("../api/src/main.rs"), None);
+        assert_eq!(workspace.resolve_for_write("src/../../../.env"), None);
+        assert_eq!(workspace.resolve_for_write("/etc/passwd"), None);
+        // reads of absolute paths aren't resolved against the workspace
+        assert_eq!(
+            workspace.resolve("/etc/passwd"),
+            PathBuf::from("/etc/passwd")
+        );
+    }
+}
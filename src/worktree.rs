@@ -0,0 +1,291 @@
+use crate::helpers::path_to_dirname;
+use anyhow::Context;
+use chrono::Local;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+/// A temporary git worktree, checked out on a branch of its own, for sessions that shouldn't
+/// touch the main working tree. Only tracked files are available in the worktree.
+#[derive(Debug)]
+pub struct Worktree {
+    repo_root: PathBuf,
+    path: PathBuf,
+    /// The project directory's counterpart in the worktree.
+    project_dir: PathBuf,
+    branch: String,
+    base_commit: String,
+}
+
+impl Worktree {
+    /// Creates a worktree for the repository containing `project_dir`, branching off its current
+    /// commit. The branch is named after the current time, unless a name is provided.
+    pub async fn create(project_dir: &Path, branch: Option<String>) -> anyhow::Result<Self> {
+        let repo_root = PathBuf::from(
+            git(project_dir, &["rev-parse", "--show-toplevel"])
+                .await
+                .context("--worktree needs agx to be run in a git repository")?,
+        );
+        let base_commit = git(&repo_root, &["rev-parse", "HEAD"])
+            .await
+            .context("couldn't determine the current commit; does the repository have one?")?;
+
+        let branch =
+            branch.unwrap_or_else(|| format!("agx/{}", Local::now().format("%Y-%m-%d-%H-%M-%S")));
+        let path = std::env::temp_dir().join("agx-worktrees").join(format!(
+            "{}-{}",
+            path_to_dirname(&repo_root),
+            branch.replace('/', "-")
+        ));
+
+        git(
+            &repo_root,
+            &[
+                "worktree",
+                "add",
+                "-b",
+                &branch,
+                &path.to_string_lossy(),
+                &base_commit,
+            ],
+        )
+        .await
+        .context("couldn't create worktree")?;
+
+        // git reports the repository's root with symlinks resolved
+        let project_dir = project_dir
+            .canonicalize()
+            .unwrap_or_else(|_| project_dir.to_path_buf());
+        let project_dir = match project_dir.strip_prefix(&repo_root) {
+            Ok(relative) => path.join(relative),
+            Err(_) => path.clone(),
+        };
+
+        Ok(Self {
+            repo_root,
+            path,
+            project_dir,
+            branch,
+            base_commit,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn project_dir(&self) -> &Path {
+        &self.project_dir
+    }
+
+    pub fn branch(&self) -> &str {
+        &self.branch
+    }
+
+    /// Whether the worktree has uncommitted changes, or commits that weren't there when it was
+    /// created.
+    pub async fn has_changes(&self) -> anyhow::Result<bool> {
+        let status = git(&self.path, &["status", "--porcelain"]).await?;
+        if !status.is_empty() {
+            return Ok(true);
+        }
+
+        let new_commits = git(
+            &self.path,
+            &[
+                "rev-list",
+                "--count",
+                &format!("{}..HEAD", self.base_commit),
+            ],
+        )
+        .await?;
+
+        Ok(new_commits != "0")
+    }
+
+    /// Commits pending changes in the worktree, if there are any.
+    pub async fn commit(&self, message: &str) -> anyhow::Result<()> {
+        let status = git(&self.path, &["status", "--porcelain"]).await?;
+        if status.is_empty() {
+            return Ok(());
+        }
+
+        git(&self.path, &["add", "--all"]).await?;
+        git(&self.path, &["commit", "--message", message])
+            .await
+            .context("couldn't commit changes in the worktree")?;
+
+        Ok(())
+    }
+
+    /// Pushes the worktree's branch to the "origin" remote.
+    pub async fn push(&self) -> anyhow::Result<()> {
+        git(
+            &self.path,
+            &["push", "--set-upstream", "origin", &self.branch],
+        )
+        .await
+        .with_context(|| format!("couldn't push branch {}", self.branch))?;
+
+        Ok(())
+    }
+
+    /// Commits any pending changes in the worktree, merges its branch into the branch checked
+    /// out in the main working tree, and removes the worktree. The worktree is left as is if the
+    /// merge fails.
+    pub async fn merge(self) -> anyhow::Result<()> {
+        self.commit("apply changes made by agx").await?;
+
+        git(&self.repo_root, &["merge", "--no-edit", &self.branch])
+            .await
+            .with_context(|| {
+                format!(
+                    "couldn't merge branch {}; the worktree has been kept at {}",
+                    self.branch,
+                    self.path.to_string_lossy()
+                )
+            })?;
+
+        self.remove().await
+    }
+
+    /// Removes the worktree along with its branch, dropping all changes made in it.
+    pub async fn discard(self) -> anyhow::Result<()> {
+        self.remove().await
+    }
+
+    async fn remove(self) -> anyhow::Result<()> {
+        git(
+            &self.repo_root,
+            &[
+                "worktree",
+                "remove",
+                "--force",
+                &self.path.to_string_lossy(),
+            ],
+        )
+        .await
+        .context("couldn't remove worktree")?;
+        git(&self.repo_root, &["branch", "-D", &self.branch])
+            .await
+            .context("couldn't delete the worktree's branch")?;
+
+        Ok(())
+    }
+}
+
+pub async fn branch_exists(dir: &Path, branch: &str) -> bool {
+    git(
+        dir,
+        &[
+            "rev-parse",
+            "--verify",
+            "--quiet",
+            &format!("refs/heads/{branch}"),
+        ],
+    )
+    .await
+    .is_ok()
+}
+
+/// Returns the uncommitted changes (including untracked files) in the repository containing
+/// `dir`, as lines of `git status --short`.
+pub async fn uncommitted_changes(dir: &Path) -> anyhow::Result<Vec<String>> {
+    let status = git(dir, &["status", "--short"]).await?;
+
+    Ok(status.lines().map(str::to_string).collect())
+}
+
+async fn git(dir: &Path, args: &[&str]) -> anyhow::Result<String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .await
+        .context("couldn't run git")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "git {} failed: {}",
+            args.first().unwrap_or(&""),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn repo_with_a_commit(dir: &Path) -> anyhow::Result<()> {
+        git(dir, &["init", "--quiet", "--initial-branch", "main"]).await?;
+        git(dir, &["config", "user.name", "agx"]).await?;
+        git(dir, &["config", "user.email", "agx@example.com"]).await?;
+        tokio::fs::write(dir.join("main.rs"), "fn main() {}\n").await?;
+        git(dir, &["add", "--all"]).await?;
+        git(dir, &["commit", "--quiet", "--message", "initial commit"]).await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn discarding_a_worktree_removes_it_along_with_its_branch() -> anyhow::Result<()> {
+        // GIVEN
+        let temp_dir = tempfile::tempdir()?;
+        let dir = temp_dir.path();
+        repo_with_a_commit(dir).await?;
+        let worktree = Worktree::create(dir, Some("agx/discard".to_string())).await?;
+        let path = worktree.path().to_path_buf();
+        let created = path.join("main.rs").exists() && branch_exists(dir, "agx/discard").await;
+        let had_changes_initially = worktree.has_changes().await?;
+        tokio::fs::write(
+            worktree.project_dir().join("main.rs"),
+            "fn main() {\n    run();\n}\n",
+        )
+        .await?;
+
+        // WHEN
+        let has_changes = worktree.has_changes().await?;
+        worktree.discard().await?;
+
+        // THEN
+        assert!(created, "worktree should've been created on its own branch");
+        assert!(!had_changes_initially);
+        assert!(has_changes);
+        assert!(!path.exists());
+        assert!(!branch_exists(dir, "agx/discard").await);
+        assert_eq!(
+            tokio::fs::read_to_string(dir.join("main.rs")).await?,
+            "fn main() {}\n"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn merging_a_worktree_brings_its_changes_into_the_main_working_tree() -> anyhow::Result<()>
+    {
+        // GIVEN
+        let temp_dir = tempfile::tempdir()?;
+        let dir = temp_dir.path();
+        repo_with_a_commit(dir).await?;
+        let worktree = Worktree::create(dir, Some("agx/merge".to_string())).await?;
+        let path = worktree.path().to_path_buf();
+        tokio::fs::write(worktree.project_dir().join("notes.md"), "# notes\n").await?;
+
+        // WHEN
+        worktree.merge().await?;
+
+        // THEN
+        assert_eq!(
+            tokio::fs::read_to_string(dir.join("notes.md")).await?,
+            "# notes\n"
+        );
+        assert!(uncommitted_changes(dir).await?.is_empty());
+        assert!(!path.exists());
+        assert!(!branch_exists(dir, "agx/merge").await);
+
+        Ok(())
+    }
+}